@@ -11,6 +11,7 @@ cargo build --release
 # 2) Place binary at a well-known path (hard link to ~/.local/bin)
 mkdir -p ~/.local/bin
 ln -f target/release/trngdbus ~/.local/bin/trngdbus
+ln -f target/release/trngctl ~/.local/bin/trngctl
 
 # 3) Copy config file to user config directory
 mkdir -p ~/.config/trng-dbus
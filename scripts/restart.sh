@@ -11,6 +11,7 @@ cargo build --release
 # 2) Update the binary at the well-known path (hard link to ~/.local/bin)
 mkdir -p ~/.local/bin
 ln -f target/release/trngdbus ~/.local/bin/trngdbus
+ln -f target/release/trngctl ~/.local/bin/trngctl
 
 # 3) Find out the PID and kill the running service
 OUT=$(busctl --user status lv.lumii.trng 2>/dev/null || true)
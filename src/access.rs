@@ -0,0 +1,289 @@
+use crate::config::AccessConfig;
+use crate::error::Error;
+use crate::polkit::{self, Action};
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fmt::Write;
+use zbus::message::{Flags, Header, Message};
+use zbus::names::{BusName, InterfaceName, MemberName};
+use zbus::object_server::{DispatchResult2, Interface, SignalEmitter};
+use zbus::zvariant::{OwnedValue, Value};
+use zbus::{fdo, Connection, ObjectServer};
+
+/// Which callers may use an endpoint and its privileged methods.
+#[derive(Clone)]
+pub struct AccessPolicy {
+    call_uids: Option<Vec<u32>>,
+    capture_uids: Vec<u32>,
+    tune_uids: Vec<u32>,
+    polkit: bool,
+}
+
+impl AccessPolicy {
+    /// The policy of a consuming endpoint, admitting `consume_uids` and the
+    /// uids of `allow_users`.
+    pub fn new(cfg: Option<&AccessConfig>) -> Self {
+        let cfg = cfg.cloned().unwrap_or_default();
+        let mut call_uids = cfg.consume_uids;
+        if !cfg.allow_users.is_empty() {
+            let uids = call_uids.get_or_insert_with(Vec::new);
+            for name in &cfg.allow_users {
+                match user_uid(name) {
+                    Some(uid) => uids.push(uid),
+                    None => log::error!("Unknown user '{}' in allow_users - ignoring it", name),
+                }
+            }
+        }
+        Self { call_uids, capture_uids: cfg.capture_uids, tune_uids: cfg.tune_uids, polkit: cfg.polkit.unwrap_or(false) }
+    }
+
+    /// The policy of `lv.lumii.trng.Monitor`, admitting `monitor_uids`. It has
+    /// no privileged methods.
+    pub fn monitor(cfg: Option<&AccessConfig>) -> Self {
+        let call_uids = cfg.and_then(|cfg| cfg.monitor_uids.clone());
+        Self { call_uids, capture_uids: Vec::new(), tune_uids: Vec::new(), polkit: false }
+    }
+
+    /// Admits only `uids` to the endpoint (a group's `uids`).
+    pub fn restrict_callers(self, uids: Vec<u32>) -> Self {
+        Self { call_uids: Some(uids), ..self }
+    }
+
+    pub fn restricts_callers(&self) -> bool {
+        self.call_uids.is_some()
+    }
+
+    /// Every caller is admitted unless the endpoint restricts them.
+    pub fn may_call(&self, uid: u32) -> bool {
+        self.call_uids.as_ref().is_none_or(|uids| uids.contains(&uid))
+    }
+
+    /// `CaptureRawSample` is refused to everyone unless uids are listed.
+    pub fn may_capture(&self, uid: u32) -> bool {
+        self.capture_uids.contains(&uid)
+    }
+
+    /// `SetSourceBufferSize`, `SetSourceTuning` and `Promote`, likewise.
+    pub fn may_tune(&self, uid: u32) -> bool {
+        self.tune_uids.contains(&uid)
+    }
+
+    /// Resolves the caller's uid and admits it to `action` if its uid is
+    /// listed for it or, with `polkit = true`, polkit authorizes it; else
+    /// logs the refused `what` and fails with `AccessDenied`.
+    pub async fn authorize(&self, action: Action, what: &str, connection: &Connection, header: &Header<'_>) -> Result<u32, Error> {
+        let uid = caller_uid(connection, header).await?;
+        let listed = match action {
+            Action::Capture => self.may_capture(uid),
+            Action::Manage => self.may_tune(uid),
+        };
+        if listed {
+            return Ok(uid);
+        }
+        if self.polkit {
+            if let Some(pid) = caller_pid(connection, header).await {
+                let interactive = header.primary().flags().contains(Flags::AllowInteractiveAuth);
+                if polkit::is_authorized(pid, uid, action, interactive).await? {
+                    log::info!("polkit authorized {} for uid {} (pid {})", action.id(), uid, pid);
+                    return Ok(uid);
+                }
+            }
+        }
+        log::warn!("Refused {} for uid {}", what, uid);
+        Err(Error::AccessDenied)
+    }
+}
+
+/// Lets the call `dispatched` to `interface` run only for callers `access`
+/// admits; the others get `AccessDenied`.
+pub fn admit<'call>(
+    access: &'call AccessPolicy,
+    interface: InterfaceName<'static>,
+    connection: &'call Connection,
+    msg: &'call Message,
+    dispatched: DispatchResult2<'call>,
+) -> DispatchResult2<'call> {
+    let call = match dispatched {
+        DispatchResult2::Async(call) if access.restricts_callers() => call,
+        other => return other,
+    };
+    DispatchResult2::Async(Box::pin(async move {
+        let header = msg.header();
+        // caller_uid logs why it could not tell
+        if let Ok(uid) = caller_uid(connection, &header).await {
+            if access.may_call(uid) {
+                return call.await;
+            }
+            let pid = caller_pid(connection, &header).await.map_or_else(|| "unknown".to_string(), |pid| pid.to_string());
+            log::warn!("Refused call to {} for uid {} (pid {})", interface, uid, pid);
+        }
+        if header.primary().flags().contains(Flags::NoReplyExpected) {
+            return Ok(());
+        }
+        let denied = fdo::Error::AccessDenied(format!("Not allowed to call {}", interface));
+        connection.reply_dbus_error(&header, denied).await.map(|_| ()).map_err(|e| fdo::Error::Failed(e.to_string()))
+    }))
+}
+
+/// An interface with an access policy of its own.
+pub trait Endpoint: Interface {
+    fn access(&self) -> &AccessPolicy;
+}
+
+/// `T` under its own name, refusing callers its policy does not admit.
+pub struct Restricted<T>(pub T);
+
+#[async_trait::async_trait]
+impl<T: Endpoint> Interface for Restricted<T> {
+    fn name() -> InterfaceName<'static> {
+        T::name()
+    }
+
+    fn spawn_tasks_for_methods(&self) -> bool {
+        self.0.spawn_tasks_for_methods()
+    }
+
+    async fn get(
+        &self,
+        property_name: &str,
+        server: &ObjectServer,
+        connection: &Connection,
+        header: Option<&Header<'_>>,
+        emitter: &SignalEmitter<'_>,
+    ) -> Option<fdo::Result<OwnedValue>> {
+        self.0.get(property_name, server, connection, header, emitter).await
+    }
+
+    async fn get_all(
+        &self,
+        server: &ObjectServer,
+        connection: &Connection,
+        header: Option<&Header<'_>>,
+        emitter: &SignalEmitter<'_>,
+    ) -> fdo::Result<HashMap<String, OwnedValue>> {
+        self.0.get_all(server, connection, header, emitter).await
+    }
+
+    fn set<'call>(
+        &'call self,
+        property_name: &'call str,
+        value: &'call Value<'_>,
+        server: &'call ObjectServer,
+        connection: &'call Connection,
+        header: Option<&'call Header<'_>>,
+        emitter: &'call SignalEmitter<'_>,
+    ) -> DispatchResult2<'call> {
+        self.0.set(property_name, value, server, connection, header, emitter)
+    }
+
+    async fn set_mut(
+        &mut self,
+        property_name: &str,
+        value: &Value<'_>,
+        server: &ObjectServer,
+        connection: &Connection,
+        header: Option<&Header<'_>>,
+        emitter: &SignalEmitter<'_>,
+    ) -> Option<fdo::Result<()>> {
+        self.0.set_mut(property_name, value, server, connection, header, emitter).await
+    }
+
+    fn call<'call>(
+        &'call self,
+        server: &'call ObjectServer,
+        connection: &'call Connection,
+        msg: &'call Message,
+        name: MemberName<'call>,
+    ) -> DispatchResult2<'call> {
+        admit(self.0.access(), T::name(), connection, msg, self.0.call(server, connection, msg, name))
+    }
+
+    fn call_mut<'call>(
+        &'call mut self,
+        server: &'call ObjectServer,
+        connection: &'call Connection,
+        msg: &'call Message,
+        name: MemberName<'call>,
+    ) -> DispatchResult2<'call> {
+        self.0.call_mut(server, connection, msg, name)
+    }
+
+    fn introspect_to_writer(&self, writer: &mut dyn Write, level: usize) {
+        self.0.introspect_to_writer(writer, level)
+    }
+}
+
+/// Resolves the Unix uid of the peer that sent `header`, as vouched for by
+/// the bus daemon.
+pub async fn caller_uid(connection: &Connection, header: &Header<'_>) -> Result<u32, Error> {
+    let sender = header.sender().ok_or(Error::AccessDenied)?;
+    let proxy = zbus::fdo::DBusProxy::new(connection).await.map_err(|e| {
+        log::error!("Cannot query the bus for caller credentials: {}", e);
+        Error::Unexpected
+    })?;
+    proxy.get_connection_unix_user(BusName::Unique(sender.to_owned())).await.map_err(|e| {
+        log::warn!("No uid for caller {}: {}", sender, e);
+        Error::AccessDenied
+    })
+}
+
+/// The process id of the peer that sent `header`, for logs; `None` if the
+/// bus daemon cannot tell.
+async fn caller_pid(connection: &Connection, header: &Header<'_>) -> Option<u32> {
+    let sender = header.sender()?;
+    let proxy = zbus::fdo::DBusProxy::new(connection).await.ok()?;
+    proxy.get_connection_unix_process_id(BusName::Unique(sender.to_owned())).await.ok()
+}
+
+/// The uid of user `name` in the passwd database.
+fn user_uid(name: &str) -> Option<u32> {
+    let name = CString::new(name).ok()?;
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut buf = vec![0 as libc::c_char; 16 * 1024];
+    let mut found = std::ptr::null_mut();
+    let rc = unsafe { libc::getpwnam_r(name.as_ptr(), &mut pwd, buf.as_mut_ptr(), buf.len(), &mut found) };
+    (rc == 0 && !found.is_null()).then_some(pwd.pw_uid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_allowlist() {
+        assert!(!AccessPolicy::new(None).may_capture(0));
+        let cfg = AccessConfig { capture_uids: vec![0, 1000], tune_uids: vec![0], ..Default::default() };
+        let policy = AccessPolicy::new(Some(&cfg));
+        assert!(policy.may_capture(1000));
+        assert!(!policy.may_capture(1001));
+        assert!(policy.may_tune(0));
+        assert!(!policy.may_tune(1000));
+        assert!(policy.may_call(1001));
+        let policy = policy.restrict_callers(vec![1000]);
+        assert!(policy.may_call(1000));
+        assert!(!policy.may_call(0));
+    }
+
+    #[test]
+    fn test_tiers() {
+        let cfg = AccessConfig { consume_uids: Some(vec![1000]), monitor_uids: Some(vec![1000, 1001]), ..Default::default() };
+        let (consume, monitor) = (AccessPolicy::new(Some(&cfg)), AccessPolicy::monitor(Some(&cfg)));
+        assert!(consume.may_call(1000));
+        assert!(!consume.may_call(1001));
+        assert!(monitor.may_call(1001));
+        assert!(!monitor.may_call(0));
+        assert!(!monitor.may_tune(0));
+        assert!(!AccessPolicy::monitor(None).restricts_callers());
+    }
+
+    #[test]
+    fn test_allow_users() {
+        let cfg: AccessConfig = toml::from_str("allow_uids = [1000]\nallow_users = [\"root\", \"no-such-user\"]").unwrap();
+        let policy = AccessPolicy::new(Some(&cfg));
+        assert!(policy.may_call(0));
+        assert!(policy.may_call(1000));
+        assert!(!policy.may_call(1001));
+        let cfg = AccessConfig { allow_users: vec!["no-such-user".to_string()], ..Default::default() };
+        assert!(!AccessPolicy::new(Some(&cfg)).may_call(0));
+    }
+}
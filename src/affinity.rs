@@ -0,0 +1,105 @@
+use std::future::Future;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use tokio::sync::oneshot;
+
+/// Restricts the calling thread to `cpus`.
+#[cfg(target_os = "linux")]
+pub fn pin_current_thread(cpus: &[usize]) -> io::Result<()> {
+    // SAFETY: cpu_set_t is plain old data; all-zero is the empty set
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    for &cpu in cpus {
+        if cpu >= libc::CPU_SETSIZE as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("cpu {} out of range", cpu)));
+        }
+        unsafe { libc::CPU_SET(cpu, &mut set) };
+    }
+    let rc = unsafe { libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) };
+    if rc != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Only Linux has `sched_setaffinity`; elsewhere `cpus` is not honoured.
+#[cfg(not(target_os = "linux"))]
+pub fn pin_current_thread(_cpus: &[usize]) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "cpu pinning needs Linux"))
+}
+
+/// Pins the calling thread, logging instead of failing; an unpinned
+/// service is better than none.
+pub fn pin_or_warn(cpus: &[usize], what: &str) {
+    if cpus.is_empty() {
+        return;
+    }
+    if let Err(e) = pin_current_thread(cpus) {
+        log::warn!("Could not pin {} to cpus {:?}: {}", what, cpus, e);
+    }
+}
+
+/// Runs `fut` to completion, on a dedicated thread pinned to `cpus` if given.
+/// That thread has its own single-threaded runtime whose blocking threads are
+/// pinned too. Dropping the returned future cancels `fut`; a panic in it is
+/// resumed here so the supervisor sees it.
+pub async fn on_cpus<Fut>(name: String, cpus: Option<Vec<usize>>, fut: Fut)
+where
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let Some(cpus) = cpus.filter(|c| !c.is_empty()) else {
+        return fut.await;
+    };
+    let (done_tx, done_rx) = oneshot::channel();
+    let (_cancel, cancel_rx) = oneshot::channel::<()>();
+    let thread_name = name.clone();
+    let spawned = std::thread::Builder::new().name(name.clone()).spawn(move || {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            pin_or_warn(&cpus, &thread_name);
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .on_thread_start(move || pin_or_warn(&cpus, "blocking thread"))
+                .build()
+                .expect("Failed to build pinned runtime");
+            runtime.block_on(async {
+                tokio::select! {
+                    _ = fut => {}
+                    // Resolves when the caller drops `_cancel`
+                    _ = cancel_rx => {}
+                }
+            });
+        }));
+        let _ = done_tx.send(result);
+    });
+    if let Err(e) = spawned {
+        log::error!("Could not start thread {}: {}", name, e);
+        return;
+    }
+    if let Ok(Err(panic)) = done_rx.await {
+        panic::resume_unwind(panic);
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    fn allowed_cpus() -> Vec<usize> {
+        let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+        unsafe { libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) };
+        (0..libc::CPU_SETSIZE as usize).filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &set) }).collect()
+    }
+
+    #[tokio::test]
+    async fn test_runs_on_pinned_thread() {
+        let first = allowed_cpus()[0];
+        let (tx, rx) = oneshot::channel();
+        on_cpus("pin-test".to_string(), Some(vec![first]), async move {
+            let _ = tx.send((std::thread::current().name().map(str::to_string), allowed_cpus()));
+        })
+        .await;
+        let (name, cpus) = rx.await.unwrap();
+        assert_eq!(name.as_deref(), Some("pin-test"));
+        assert_eq!(cpus, vec![first]);
+        assert!(pin_current_thread(&[libc::CPU_SETSIZE as usize]).is_err());
+    }
+}
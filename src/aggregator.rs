@@ -1,145 +1,2538 @@
-use crate::config::{CombineMode, FlattenedConfig};
+use crate::alerts;
+use crate::cpu;
+use crate::breaker::{BreakerState, CircuitBreaker};
+use crate::budget;
+use crate::bus;
+#[cfg(feature = "chaos")]
+use crate::chaos::Chaos;
+#[cfg(feature = "grpc")]
+use crate::sources::GrpcSource;
+#[cfg(feature = "nats")]
+use crate::sources::NatsSource;
+use crate::circular_buffer::poison;
+use crate::clock::{self, ClockWatch, SystemClock};
+use crate::conditioning::{self, Conditioner};
+use crate::ctr_drbg::{self, CtrDrbg, OutputDrbg};
+use crate::config::{CombineMode, ConfigHashes, FallbackPolicy, FlattenedConfig, MaintenanceConfig, ReadinessConfig, ReadinessMode, SourceCommon, StartupPolicy, BUS_NAME, MIN_ENTROPY_PER_BYTE};
+use crate::drbg::{self, HmacDrbg, SEED_LEN};
+use crate::jitter;
+use crate::kdf;
+use crate::ledger::{self, Purpose, Spans};
+use crate::leftovers::LaneStats;
+use crate::maintenance::Schedule;
+use crate::memory::{Accountant, Reservation};
+use crate::pool::{self, Pool};
+use crate::sampling;
+use crate::scheduler::{self, Admission, Dispatcher};
+use crate::sha3;
 use crate::error::Error;
-use crate::sources::{EntropySource, FileSource, LrngSource};
+use crate::hwrng;
+use crate::events::{self, EventSender, ServiceEvent};
+use crate::lrng::{self, os_fill_insecure_octets, OsRandomSource};
+use crate::shutdown;
+use crate::simulate::Simulation;
+use crate::snapshot;
+use crate::sources::{AudioSource, CpuSource, DbusSource, DeferredSource, EntropySource, ExecSource, FileSource, HttpSource, HwrngSource, JitterSource, LrngSource, MqttSource, Pkcs11Source, PublisherStats, SerialSource, TcpSource};
+use crate::supervisor::{self, Heartbeat, STALL_TIMEOUT};
+use crate::trending::Trends;
+use crate::upstream;
 use futures::future::join_all;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::future::Future;
+use std::os::unix::fs::FileExt;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::SemaphorePermit;
 use tokio::time::{interval, Duration};
+use zeroize::Zeroizing;
+
+fn rate_label(rate: Option<f64>) -> String {
+    match rate {
+        Some(rate) => format!("estimated rate {:.2} MB/s", rate / (1024.0 * 1024.0)),
+        None => "rate not measured".to_string(),
+    }
+}
+
+fn leftovers_label(stats: &LaneStats) -> String {
+    format!("leftovers {} returned, {} reused, {} dropped", stats.returned, stats.reused, stats.dropped)
+}
+
+fn publishers_label(publishers: &BTreeMap<String, PublisherStats>) -> String {
+    let rejected: u64 = publishers.values().map(|p| p.wrong_size + p.constant + p.repeated + p.retained).sum();
+    let overflowed: u64 = publishers.values().map(|p| p.overflowed).sum();
+    format!(
+        "{} publishers, {} messages taken, {} rejected, {} overflowed",
+        publishers.len(),
+        publishers.values().map(|p| p.messages).sum::<u64>(),
+        rejected,
+        overflowed
+    )
+}
+
+fn os_error(e: std::io::Error) -> Error {
+    Error::OsError(e.raw_os_error().unwrap_or(0) as u32)
+}
+
+/// XORs into each byte of `out` the next `ratio` bytes of `raw`.
+fn xor_folded(out: &mut [u8], raw: &[u8], ratio: usize) {
+    for (o, chunk) in out.iter_mut().zip(raw.chunks_exact(ratio)) {
+        *o = chunk.iter().fold(*o, |acc, b| acc ^ b);
+    }
+}
+
+/// Minimum gap between two "request cannot be met" warnings.
+const INFEASIBLE_WARNING_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Largest raw sample one `CaptureRawSample` call may divert.
+pub const MAX_CAPTURE_BYTES: usize = 16 * 1024 * 1024;
+
+/// Largest number of buffers in one `ReadBytesMulti` call.
+pub const MAX_BATCH_BUFFERS: usize = 4096;
+
+/// Most bytes one request takes from the sources at a time. Larger requests
+/// are served slice by slice so that other requests get a turn in between.
+const REQUEST_SLICE: usize = 256 * 1024;
+
+/// How often maintenance windows are checked for opening or closing.
+const MAINTENANCE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Pause between reads of a `ReadBytesExact` request that came back short.
+const EXACT_RETRY_DELAY: Duration = Duration::from_millis(10);
+
+/// How often buffer fills are checked against `entropy_low_percent`.
+const LOW_BUFFER_INTERVAL: Duration = Duration::from_millis(250);
+
+tokio::task_local! {
+    static MIN_SOURCES: Option<usize>;
+    static COMBINE: Option<CombineMode>;
+}
+
+/// Runs `fut` with every combined read it makes failing unless at least
+/// `min` sources deliver in full.
+pub async fn requiring<F: Future>(min: Option<usize>, fut: F) -> F::Output {
+    MIN_SOURCES.scope(min, fut).await
+}
+
+/// The `min_sources` of the endpoint being served, if it has one.
+fn min_sources() -> Option<usize> {
+    MIN_SOURCES.try_with(|m| *m).ok().flatten()
+}
+
+/// Runs `fut` with every combined read it makes combined by `mode` instead
+/// of `[sources] combine`, if set.
+pub async fn combining<F: Future>(mode: Option<CombineMode>, fut: F) -> F::Output {
+    COMBINE.scope(mode, fut).await
+}
+
+/// Per-request options of `ReadBytesEx`.
+#[derive(Debug, Default, Clone)]
+pub struct ReadOptions {
+    /// Pad a short answer from the DRBG seeded by earlier combined output.
+    pub drbg_fill: bool,
+    /// Pad a short answer with `GRND_INSECURE` bytes instead of returning it short.
+    pub insecure_fill: bool,
+    /// Domain separation input mixed into this request's conditioning and DRBG padding.
+    pub personalization: Option<Vec<u8>>,
+    /// Scheduling priority, applied by the caller (see `scheduler.rs`).
+    pub priority: u8,
+}
+
+/// Answer to one request: `secure_len` bytes from the configured sources,
+/// then `drbg_len` bytes of DRBG padding, then any insecure padding.
+pub struct Response {
+    pub bytes: Vec<u8>,
+    pub secure_len: usize,
+    pub drbg_len: usize,
+    /// The leading bytes came from the low-assurance jitter fallback instead
+    /// of the sources (`secure_len` is then 0).
+    pub fallback_len: usize,
+    pub provenance: Provenance,
+}
+
+/// How an answer was produced, for auditors.
+pub struct Provenance {
+    /// The sources combined into the source bytes, as they were at serve time.
+    pub sources: Vec<SourceReport>,
+    /// How the source bytes were conditioned, e.g. `xor+hmac-sha256`.
+    pub conditioning: String,
+    /// Entropy credited to the answer. Only source bytes count, at the best
+    /// credit among the XORed sources; padding and fallback count for nothing.
+    pub entropy_bits: u64,
+}
+
+pub struct SourceReport {
+    pub id: String,
+    pub kind: &'static str,
+    pub version: String,
+    pub healthy: bool,
+    pub breaker: &'static str,
+    pub entropy_credit: f64,
+    pub entropy_per_byte: f64,
+}
+
+/// What provenance reports about a configured source.
+struct Profile {
+    kind: &'static str,
+    version: String,
+    entropy_credit: f64,
+    /// Assessed entropy per byte, which `weighted_xor` draws bytes by.
+    entropy_per_byte: f64,
+    /// Whether the source's errors fail requests, or only leave it out.
+    required: bool,
+}
+
+impl Profile {
+    fn new(kind: &'static str, version: Option<String>, entropy_credit: Option<f64>, entropy_per_byte: Option<f64>, required: Option<bool>) -> Self {
+        let version = version.unwrap_or_else(|| match kind {
+            "lrng" => lrng::kernel_release().map_or_else(String::new, |r| format!("{} {}", std::env::consts::OS, r)),
+            "cpu" => cpu::model().unwrap_or_default(),
+            _ => String::new(),
+        });
+        let entropy_credit = entropy_credit.unwrap_or(1.0);
+        let entropy_per_byte = entropy_per_byte.unwrap_or(entropy_credit * 8.0);
+        Self { kind, version, entropy_credit, entropy_per_byte, required: required.unwrap_or(true) }
+    }
+
+    /// Raw bytes `weighted_xor` folds into each output byte: enough for 8
+    /// bits of assessed entropy.
+    fn draw_ratio(&self) -> usize {
+        (8.0 / self.entropy_per_byte).ceil() as usize
+    }
+}
+
+/// A configured source together with the circuit breaker guarding it.
+struct SourceSlot {
+    id: String,
+    source: Arc<dyn EntropySource>,
+    breaker: CircuitBreaker,
+    maintenance: Schedule,
+    /// Set while one of the source's maintenance windows is open.
+    in_maintenance: AtomicBool,
+    /// Opening minute of the last maintenance window opened, so that a wall
+    /// clock stepped back into it does not open it again.
+    last_window: AtomicI64,
+    profile: Profile,
+    /// What the source's buffer is charged for in the memory account.
+    buffer_memory: std::sync::Mutex<Option<Reservation>>,
+    /// Set while the source's reads fail, so `SourceFailed` is sent once.
+    failing: AtomicBool,
+}
+
+impl SourceSlot {
+    fn new(
+        id: String,
+        source: Arc<dyn EntropySource>,
+        breaker: CircuitBreaker,
+        maintenance: &[MaintenanceConfig],
+        profile: Profile,
+        simulation: Simulation,
+    ) -> Result<Arc<Self>, Error> {
+        let maintenance = Schedule::from_config(maintenance).map_err(|e| {
+            log::error!("Source {}: invalid maintenance window: {}", id, e);
+            Error::InvalidOption("maintenance".to_string())
+        })?;
+        if !(profile.entropy_credit > 0.0 && profile.entropy_credit <= 1.0) {
+            log::error!("Source {}: entropy_credit must be above 0 and at most 1", id);
+            return Err(Error::InvalidOption("entropy_credit".to_string()));
+        }
+        if !(MIN_ENTROPY_PER_BYTE..=8.0).contains(&profile.entropy_per_byte) {
+            log::error!("Source {}: entropy_per_byte must be {} to 8", id, MIN_ENTROPY_PER_BYTE);
+            return Err(Error::InvalidOption("entropy_per_byte".to_string()));
+        }
+        let source = simulation.wrap(&id, source).map_err(|e| {
+            log::error!("Source {}: {}", id, e);
+            Error::InvalidOption("simulate_rate_bytes_per_sec".to_string())
+        })?;
+        Ok(Arc::new(Self { id, source, breaker, maintenance, in_maintenance: AtomicBool::new(false), last_window: AtomicI64::new(i64::MIN), profile, buffer_memory: std::sync::Mutex::new(None), failing: AtomicBool::new(false) }))
+    }
+
+    /// Swaps what the buffer is charged for, returning the old charge.
+    fn charge_buffer(&self, charge: Option<Reservation>) -> Option<Reservation> {
+        std::mem::replace(&mut *self.buffer_memory.lock().unwrap_or_else(|e| e.into_inner()), charge)
+    }
+
+    fn report(&self) -> SourceReport {
+        SourceReport {
+            id: self.id.clone(),
+            kind: self.profile.kind,
+            version: self.profile.version.clone(),
+            healthy: self.source.is_healthy(),
+            breaker: self.breaker.state().as_str(),
+            entropy_credit: self.profile.entropy_credit,
+            entropy_per_byte: self.profile.entropy_per_byte,
+        }
+    }
+
+    fn in_maintenance(&self) -> bool {
+        self.in_maintenance.load(Ordering::SeqCst)
+    }
+
+    /// Quarantined, unavailable or disabled: not fit to serve for a pair.
+    fn is_down(&self, now: Instant) -> bool {
+        !self.source.is_available() || !self.source.is_healthy() || self.breaker.is_quarantined(now)
+    }
+}
+
+/// A primary source and its warm standby (`standby_for`), of which only one
+/// serves at a time. The idle one keeps replenishing, so a failover does not
+/// wait for its buffer.
+struct StandbyPair {
+    primary: usize,
+    standby: usize,
+    /// Set while the standby serves in place of the primary.
+    promoted: AtomicBool,
+}
+
+impl StandbyPair {
+    fn active(&self) -> usize {
+        if self.promoted.load(Ordering::SeqCst) { self.standby } else { self.primary }
+    }
+
+    fn idle(&self) -> usize {
+        if self.promoted.load(Ordering::SeqCst) { self.primary } else { self.standby }
+    }
+}
+
+/// The sources `Aggregator::from_config` has set up so far.
+struct Slots {
+    sources: Vec<Arc<SourceSlot>>,
+    /// (index in `sources`, primary id) of every standby
+    standby_links: Vec<(usize, String)>,
+    #[cfg(feature = "chaos")]
+    chaos: Option<Arc<Chaos>>,
+}
+
+impl Slots {
+    /// Adds a source of `kind` with what every kind shares: its breaker,
+    /// maintenance windows, entropy profile, standby link, simulated
+    /// slowness and, under `[chaos]`, injected faults.
+    fn add(&mut self, kind: &'static str, id: String, common: &SourceCommon, source: Arc<dyn EntropySource>) -> Result<(), Error> {
+        if let Some(primary) = common.standby_for.clone() {
+            self.standby_links.push((self.sources.len(), primary));
+        }
+        #[cfg(feature = "chaos")]
+        let source = match &self.chaos {
+            Some(chaos) => chaos.wrap(&id, source),
+            None => source,
+        };
+        let breaker = CircuitBreaker::new(common.breaker.as_ref());
+        let profile = Profile::new(kind, common.version.clone(), common.entropy_credit, common.entropy_per_byte, common.required);
+        let simulation = Simulation::new(common.simulate_latency_ms, common.simulate_rate_bytes_per_sec);
+        self.sources.push(SourceSlot::new(id, source, breaker, &common.maintenance, profile, simulation)?);
+        Ok(())
+    }
+}
+
+/// Pairs each `(standby index, primary id)` link, checking that the primary
+/// is an enabled source of the same kind that is no standby itself and has
+/// no other standby.
+fn standby_pairs(sources: &[Arc<SourceSlot>], links: &[(usize, String)]) -> Result<Vec<StandbyPair>, Error> {
+    let mut pairs: Vec<StandbyPair> = Vec::new();
+    for (standby, primary_id) in links {
+        let slot = &sources[*standby];
+        let invalid = |why: &str| {
+            log::error!("Source {}: invalid standby_for '{}': {}", slot.id, primary_id, why);
+            Error::InvalidOption("standby_for".to_string())
+        };
+        let primary = sources.iter().position(|s| s.id == *primary_id).ok_or_else(|| invalid("no such enabled source"))?;
+        if sources[primary].profile.kind != slot.profile.kind {
+            return Err(invalid("not a source of the same kind"));
+        }
+        if links.iter().any(|(i, _)| *i == primary) {
+            return Err(invalid("that source is a standby itself"));
+        }
+        if pairs.iter().any(|p| p.primary == primary) {
+            return Err(invalid("that source already has a standby"));
+        }
+        log::info!("Source {} is the warm standby for {}", slot.id, primary_id);
+        pairs.push(StandbyPair { primary, standby: *standby, promoted: AtomicBool::new(false) });
+    }
+    Ok(pairs)
+}
 
 pub struct Aggregator {
-    #[allow(dead_code)]
     combine: CombineMode,
-    sources: Vec<Arc<dyn EntropySource>>,
+    sources: Vec<Arc<SourceSlot>>,
+    pairs: Vec<StandbyPair>,
     bytes_served: Arc<AtomicU64>,
     requests_served: Arc<AtomicU64>,
+    events: EventSender,
+    /// Pads short answers for clients that ask for it; seeded lazily.
+    drbg: Arc<tokio::sync::Mutex<Option<HmacDrbg>>>,
+    drbg_reseed: AtomicBool,
+    /// Serves every request when `[drbg]` is set, seeded by combined output.
+    output_drbg: Option<OutputDrbg>,
+    /// Takes the place of combining per request when `pool` is set.
+    pool: Option<Arc<Pool>>,
+    /// Per-source benchmark budget for `run_benchmark`.
+    benchmark: Duration,
+    last_infeasible_warning: std::sync::Mutex<Option<Instant>>,
+    readiness: Option<ReadinessConfig>,
+    /// Cleared until `wait_until_ready` finishes when `[readiness]` is set.
+    ready: AtomicBool,
+    /// Set while the watchdog's canary reads fail; requests get `NotReady`.
+    watchdog_tripped: AtomicBool,
+    scheduler: Dispatcher,
+    admission: Admission,
+    fallback: FallbackPolicy,
+    /// Set while requests are being answered by the jitter fallback.
+    fallback_active: AtomicBool,
+    config_hashes: ConfigHashes,
+    started: Instant,
+    /// Quality results of the `[trending]` captures, when configured.
+    trends: Option<Trends>,
+    memory: Arc<Accountant>,
+    /// `[service] max_request_bytes`: larger requests are refused up front.
+    max_request_bytes: usize,
 }
 
 impl Aggregator {
     pub async fn from_config(cfg: FlattenedConfig) -> Result<Self, Error> {
-        let mut sources: Vec<Arc<dyn EntropySource>> = Vec::new();
+        #[cfg(feature = "chaos")]
+        let chaos = Chaos::from_config(cfg.chaos.as_ref()).map_err(|e| {
+            log::error!("Invalid [chaos] section: {}", e);
+            Error::InvalidOption("chaos".to_string())
+        })?;
+        #[cfg(not(feature = "chaos"))]
+        if cfg.chaos.is_some() {
+            log::warn!("Ignoring [chaos]: this build does not have the chaos feature");
+        }
+        let mut slots = Slots {
+            sources: Vec::new(),
+            standby_links: Vec::new(),
+            #[cfg(feature = "chaos")]
+            chaos,
+        };
+
+        for lrng in cfg.lrng_sources.into_iter() {
+            log::info!("Initializing LRNG source: {} ({})", lrng.id, OsRandomSource::BACKEND);
+            let (id, common) = (lrng.id.clone(), lrng.common.clone());
+            slots.add("lrng", id, &common, Arc::new(LrngSource::new(lrng)))?;
+        }
+
+        for filecfg in cfg.file_sources.into_iter() {
+            log::info!("Initializing file source: {} at {}", filecfg.id, filecfg.path);
+            let (id, common) = (filecfg.id.clone(), filecfg.common.clone());
+            let policy = filecfg.startup.unwrap_or(cfg.startup);
+            let wait = Duration::from_millis(filecfg.startup_timeout_ms.unwrap_or(cfg.startup_timeout_ms));
+            let source: Arc<dyn EntropySource> = match policy {
+                StartupPolicy::FailFast => Arc::new(FileSource::new(filecfg).await.map_err(|e| {
+                    log::error!("Failed to open file source: {}", e);
+                    Error::OsError(e.raw_os_error().unwrap_or(0) as u32)
+                })?),
+                StartupPolicy::WaitForSource => {
+                    match tokio::time::timeout(wait, FileSource::open_until_ready(filecfg)).await {
+                        Ok(src) => Arc::new(src),
+                        Err(_) => {
+                            log::error!("File source {} did not open within {:?}", id, wait);
+                            return Err(Error::SourcesUnavailable);
+                        }
+                    }
+                }
+                StartupPolicy::StartDegraded => match FileSource::new(filecfg.clone()).await {
+                    Ok(src) => Arc::new(src),
+                    Err(e) => {
+                        log::error!("Failed to open file source {}: {} - starting degraded without it", id, e);
+                        Arc::new(DeferredSource::spawn(filecfg))
+                    }
+                },
+            };
+            slots.add("file", id, &common, source)?;
+        }
+
+        for serialcfg in cfg.serial_sources.into_iter() {
+            log::info!("Initializing serial source: {} at {}", serialcfg.id, serialcfg.path);
+            let (id, common) = (serialcfg.id.clone(), serialcfg.common.clone());
+            let source = SerialSource::new(serialcfg).await.map_err(|e| {
+                log::error!("Failed to open serial source {}: {}", id, e);
+                Error::OsError(e.raw_os_error().unwrap_or(0) as u32)
+            })?;
+            slots.add("serial", id, &common, Arc::new(source))?;
+        }
+
+        for tcpcfg in cfg.tcp_sources.into_iter() {
+            log::info!("Initializing TCP source: {} from {}:{}", tcpcfg.id, tcpcfg.host, tcpcfg.port);
+            let (id, common) = (tcpcfg.id.clone(), tcpcfg.common.clone());
+            let source = TcpSource::new(tcpcfg).map_err(|e| {
+                log::error!("Invalid TCP source {}: {}", id, e);
+                Error::InvalidOption("host".to_string())
+            })?;
+            slots.add("tcp", id, &common, Arc::new(source))?;
+        }
+
+        for httpcfg in cfg.http_sources.into_iter() {
+            log::info!("Initializing HTTP source: {} polling {}", httpcfg.id, httpcfg.url.split('?').next().unwrap_or_default());
+            let (id, common) = (httpcfg.id.clone(), httpcfg.common.clone());
+            let source = HttpSource::new(httpcfg).map_err(|e| {
+                log::error!("Invalid HTTP source {}: {}", id, e);
+                Error::InvalidOption("url".to_string())
+            })?;
+            slots.add("http", id, &common, Arc::new(source))?;
+        }
+
+        for cpucfg in cfg.cpu_sources.into_iter() {
+            log::info!("Initializing CPU source: {} ({})", cpucfg.id, cpu::name(cpucfg.instruction.unwrap_or_default()));
+            let (id, common) = (cpucfg.id.clone(), cpucfg.common.clone());
+            let source = CpuSource::new(cpucfg).map_err(|e| {
+                log::error!("Cannot use CPU source {}: {}", id, e);
+                Error::SourcesUnavailable
+            })?;
+            slots.add("cpu", id, &common, Arc::new(source))?;
+        }
+
+        for jittercfg in cfg.jitter_sources.into_iter() {
+            log::info!("Initializing jitter source: {}", jittercfg.id);
+            let (id, common) = (jittercfg.id.clone(), jittercfg.common.clone());
+            slots.add("jitter", id, &common, Arc::new(JitterSource::new(jittercfg)))?;
+        }
+
+        for mqttcfg in cfg.mqtt_sources.into_iter() {
+            log::info!("Initializing MQTT source: {} from '{}' on {}", mqttcfg.id, mqttcfg.topic, mqttcfg.broker);
+            let (id, common) = (mqttcfg.id.clone(), mqttcfg.common.clone());
+            let source = MqttSource::new(mqttcfg).map_err(|e| {
+                log::error!("Invalid MQTT source {}: {}", id, e);
+                Error::InvalidOption("broker".to_string())
+            })?;
+            slots.add("mqtt", id, &common, Arc::new(source))?;
+        }
+
+        for hwrngcfg in cfg.hwrng_sources.into_iter() {
+            let path = hwrngcfg.path.clone().unwrap_or_else(|| hwrng::DEFAULT_PATH.to_string());
+            log::info!("Initializing hwrng source: {} at {}", hwrngcfg.id, path);
+            let (id, mut common) = (hwrngcfg.id.clone(), hwrngcfg.common.clone());
+            // Unless configured, credit what the kernel credits the backend
+            let sysfs = hwrngcfg.sysfs.clone().unwrap_or_else(|| hwrng::DEFAULT_SYSFS.to_string());
+            common.entropy_credit = common.entropy_credit.or_else(|| hwrng::Backend::probe(std::path::Path::new(&sysfs)).entropy_credit());
+            let source = HwrngSource::new(hwrngcfg).await.map_err(|e| {
+                log::error!("Failed to open hwrng source {} at {}: {}", id, path, e);
+                Error::OsError(e.raw_os_error().unwrap_or(0) as u32)
+            })?;
+            slots.add("hwrng", id, &common, Arc::new(source))?;
+        }
+
+        #[cfg(not(feature = "nats"))]
+        if let Some(natscfg) = cfg.nats_sources.first() {
+            log::error!("Invalid NATS source {}: this build does not have the nats feature", natscfg.id);
+            return Err(Error::InvalidOption("nats".to_string()));
+        }
+        #[cfg(feature = "nats")]
+        for natscfg in cfg.nats_sources.into_iter() {
+            log::info!("Initializing NATS source: {} from stream {} on {}", natscfg.id, natscfg.stream, natscfg.server);
+            let (id, common) = (natscfg.id.clone(), natscfg.common.clone());
+            let source = NatsSource::new(natscfg).map_err(|e| {
+                log::error!("Invalid NATS source {}: {}", id, e);
+                Error::InvalidOption("server".to_string())
+            })?;
+            slots.add("nats", id, &common, Arc::new(source))?;
+        }
+
+        for execcfg in cfg.exec_sources.into_iter() {
+            log::info!("Initializing exec source: {} running {}", execcfg.id, execcfg.command.first().map_or("nothing", String::as_str));
+            let (id, common) = (execcfg.id.clone(), execcfg.common.clone());
+            let source = ExecSource::new(execcfg).map_err(|e| {
+                log::error!("Invalid exec source {}: {}", id, e);
+                Error::InvalidOption("command".to_string())
+            })?;
+            slots.add("exec", id, &common, Arc::new(source))?;
+        }
+
+        for pkcs11cfg in cfg.pkcs11_sources.into_iter() {
+            log::info!("Initializing PKCS#11 source: {} on slot {} of {}", pkcs11cfg.id, pkcs11cfg.slot, pkcs11cfg.module);
+            let (id, mut common) = (pkcs11cfg.id.clone(), pkcs11cfg.common.clone());
+            let source = Pkcs11Source::new(pkcs11cfg).map_err(|e| {
+                log::error!("Cannot open PKCS#11 source {}: {}", id, e);
+                Error::SourcesUnavailable
+            })?;
+            common.version = common.version.or_else(|| Some(source.model()));
+            slots.add("pkcs11", id, &common, Arc::new(source))?;
+        }
+
+        for audiocfg in cfg.audio_sources.into_iter() {
+            log::info!("Initializing audio source: {} from {}", audiocfg.id, audiocfg.device.as_deref().unwrap_or("default"));
+            let (id, common) = (audiocfg.id.clone(), audiocfg.common.clone());
+            let source = AudioSource::new(audiocfg).map_err(|e| {
+                log::error!("Cannot open audio source {}: {}", id, e);
+                Error::SourcesUnavailable
+            })?;
+            slots.add("audio", id, &common, Arc::new(source))?;
+        }
+
+        for dbuscfg in cfg.dbus_sources.into_iter() {
+            log::info!("Initializing D-Bus source: {} from {}", dbuscfg.id, dbuscfg.service.as_deref().unwrap_or(BUS_NAME));
+            let (id, common) = (dbuscfg.id.clone(), dbuscfg.common.clone());
+            if upstream::is_self(&dbuscfg, cfg.bus, &cfg.bus_name) {
+                log::error!("Invalid D-Bus source {}: {} on the {} is this service itself", id, cfg.bus_name, cfg.bus);
+                return Err(Error::InvalidOption("service".to_string()));
+            }
+            let source = DbusSource::new(dbuscfg).map_err(|e| {
+                log::error!("Invalid D-Bus source {}: {}", id, e);
+                Error::InvalidOption("service".to_string())
+            })?;
+            slots.add("dbus", id, &common, Arc::new(source))?;
+        }
+
+        #[cfg(not(feature = "grpc"))]
+        if let Some(grpccfg) = cfg.grpc_sources.first() {
+            log::error!("Invalid gRPC source {}: this build does not have the grpc feature", grpccfg.id);
+            return Err(Error::InvalidOption("grpc".to_string()));
+        }
+        #[cfg(feature = "grpc")]
+        for grpccfg in cfg.grpc_sources.into_iter() {
+            log::info!("Initializing gRPC source: {} from {}:{}", grpccfg.id, grpccfg.host, grpccfg.port);
+            let (id, common) = (grpccfg.id.clone(), grpccfg.common.clone());
+            let source = GrpcSource::new(grpccfg).map_err(|e| {
+                log::error!("Invalid gRPC source {}: {}", id, e);
+                Error::InvalidOption("host".to_string())
+            })?;
+            slots.add("grpc", id, &common, Arc::new(source))?;
+        }
+
+        let Slots { sources, standby_links, .. } = slots;
+        log::info!("Aggregator initialized with {} sources", sources.len());
+        let pairs = standby_pairs(&sources, &standby_links)?;
+
+        let memory = Accountant::new(cfg.memory.as_ref());
+        for slot in &sources {
+            if let Some((_, capacity)) = slot.source.get_buffer_status().await.1 {
+                let charge = memory.reserve(capacity, "a source buffer").inspect_err(|_| {
+                    log::error!("Source {}: buffers take more than the [memory] ceiling", slot.id);
+                })?;
+                slot.charge_buffer(Some(charge));
+            }
+        }
+        
+        let bytes_served = Arc::new(AtomicU64::new(0));
+        let requests_served = Arc::new(AtomicU64::new(0));
+        
+        // Start periodic logging
+        let sources_clone = sources.clone();
+        let bytes_served_clone = bytes_served.clone();
+        let requests_served_clone = requests_served.clone();
+        supervisor::spawn("periodic-logging", Some(STALL_TIMEOUT), move |heartbeat| {
+            Self::periodic_logging(sources_clone.clone(), bytes_served_clone.clone(), requests_served_clone.clone(), heartbeat)
+        });
+
+        // Measure what each source can sustain without delaying startup
+        let benchmark = Duration::from_millis(cfg.benchmark_ms);
+        if !benchmark.is_zero() {
+            let slots: Vec<(String, Arc<dyn EntropySource>)> = sources.iter().map(|s| (s.id.clone(), s.source.clone())).collect();
+            supervisor::spawn("benchmark", None, move |_| {
+                let slots = slots.clone();
+                async move {
+                    Self::benchmark_sources(&slots, benchmark).await;
+                }
+            });
+        }
+
+        let events = events::channel();
+        if let Some(percent) = cfg.entropy_low_percent {
+            let sources_clone = sources.clone();
+            let events_clone = events.clone();
+            supervisor::spawn("entropy-watch", Some(STALL_TIMEOUT), move |heartbeat| {
+                Self::watch_low_buffers(sources_clone.clone(), events_clone.clone(), percent, heartbeat)
+            });
+        }
+        if let Some(alert_cfg) = cfg.alerts {
+            if let Some(seconds) = alert_cfg.buffer_empty_seconds {
+                let sources_clone = sources.clone();
+                let events_clone = events.clone();
+                supervisor::spawn("buffer-watch", Some(STALL_TIMEOUT), move |heartbeat| {
+                    Self::watch_empty_buffers(sources_clone.clone(), events_clone.clone(), Duration::from_secs(seconds), heartbeat)
+                });
+            }
+            let events_clone = events.clone();
+            supervisor::spawn("alerts", None, move |_| {
+                alerts::run(alert_cfg.clone(), events_clone.subscribe(), events_clone.clone())
+            });
+        }
+        
+        if sources.iter().any(|s| !s.maintenance.is_empty()) {
+            let sources_clone = sources.clone();
+            supervisor::spawn("maintenance", Some(STALL_TIMEOUT), move |heartbeat| {
+                Self::run_maintenance(sources_clone.clone(), heartbeat)
+            });
+        }
+
+        let drbg = Arc::new(tokio::sync::Mutex::new(None));
+        let wipe = drbg.clone();
+        shutdown::register(shutdown::Stage::Zeroize, "padding DRBG", move || match wipe.try_lock() {
+            // Dropping the DRBG zeroizes its state
+            Ok(mut drbg) => *drbg = None,
+            Err(_) => log::error!("Could not lock padding DRBG for zeroization"),
+        });
+        let pool = cfg.pool.and_then(|kind| Pool::new(kind, sources.len())).map(Arc::new);
+        if let Some(pool) = &pool {
+            let wipe = pool.clone();
+            shutdown::register(shutdown::Stage::Zeroize, "entropy pool", move || wipe.wipe());
+        }
+
+        Ok(Self {
+            combine: cfg.combine,
+            sources,
+            pairs,
+            bytes_served,
+            requests_served,
+            events,
+            drbg,
+            drbg_reseed: AtomicBool::new(true),
+            output_drbg: cfg.drbg.as_ref().map(OutputDrbg::new),
+            pool,
+            benchmark: if benchmark.is_zero() { Duration::from_millis(1000) } else { benchmark },
+            last_infeasible_warning: std::sync::Mutex::new(None),
+            ready: AtomicBool::new(cfg.readiness.is_none()),
+            watchdog_tripped: AtomicBool::new(false),
+            readiness: cfg.readiness,
+            scheduler: scheduler::from_config(cfg.scheduler.as_ref()),
+            admission: Admission::new(cfg.scheduler.as_ref()),
+            fallback: cfg.fallback,
+            fallback_active: AtomicBool::new(false),
+            config_hashes: cfg.hashes,
+            started: Instant::now(),
+            trends: cfg.trending.as_ref().map(Trends::new),
+            memory,
+            max_request_bytes: cfg.max_request_bytes,
+        })
+    }
+
+    /// Waits for a client request's turn under `max_concurrent_requests`;
+    /// it is served while the returned permit is held. Fails with `Busy`
+    /// when `max_queue_depth` requests are waiting already.
+    pub async fn admit(&self) -> Result<Option<SemaphorePermit<'_>>, Error> {
+        self.admission.enter().await
+    }
+
+    pub fn event_sender(&self) -> EventSender {
+        self.events.clone()
+    }
+
+    pub fn readiness_mode(&self) -> Option<ReadinessMode> {
+        self.readiness.as_ref().map(|r| r.mode.unwrap_or_default())
+    }
+
+    /// Waits until every source is healthy and its buffer holds the configured
+    /// fill level (or the readiness timeout passes), then lets requests through.
+    pub async fn wait_until_ready(&self) {
+        let Some(cfg) = &self.readiness else { return };
+        let min_fill = cfg.min_fill_percent.unwrap_or(50.0);
+        let limit = cfg.timeout_ms.filter(|ms| *ms > 0).map(Duration::from_millis);
+        let started = Instant::now();
+        let mut check = interval(Duration::from_millis(100));
+        loop {
+            check.tick().await;
+            let pending = self.unready_sources(min_fill).await;
+            if pending.is_empty() {
+                log::info!("All sources ready after {:?}", started.elapsed());
+                break;
+            }
+            if limit.is_some_and(|limit| started.elapsed() >= limit) {
+                log::warn!("Readiness timeout passed, serving without: {}", pending.join(", "));
+                break;
+            }
+        }
+        self.ready.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether startup readiness is reached, regardless of the watchdog.
+    pub fn is_primed(&self) -> bool {
+        self.ready.load(Ordering::SeqCst)
+    }
+
+    /// Fails requests with `NotReady` while `tripped`, see `watchdog.rs`.
+    pub fn set_watchdog_tripped(&self, tripped: bool) {
+        self.watchdog_tripped.store(tripped, Ordering::SeqCst);
+    }
+
+    /// Reads and zeroizes `num_bytes` through the same path as a request,
+    /// without counting them as served. Returns how many came back.
+    pub async fn canary(&self, num_bytes: usize, timeout_ms: u64) -> Result<usize, Error> {
+        let (bytes, _) = self.read_combined(num_bytes, timeout_ms, Purpose::Canary).await?;
+        Ok(Zeroizing::new(bytes).len())
+    }
+
+    async fn unready_sources(&self, min_fill: f64) -> Vec<String> {
+        let mut pending = Vec::new();
+        for slot in self.sources.iter().filter(|s| !s.in_maintenance()) {
+            let filled = match slot.source.get_buffer_status().await.1 {
+                Some((current, max)) if max > 0 => current as f64 * 100.0 / max as f64 >= min_fill,
+                _ => true,
+            };
+            if !(slot.source.is_available() && slot.source.is_healthy() && filled) {
+                pending.push(slot.id.clone());
+            }
+        }
+        pending
+    }
+
+    /// Whether source `i` is the idle half of a standby pair.
+    fn is_idle(&self, i: usize) -> bool {
+        self.pairs.iter().any(|p| p.idle() == i)
+    }
+
+    /// The combine mode of the endpoint being served, or `[sources] combine`.
+    fn combine(&self) -> CombineMode {
+        COMBINE.try_with(|c| *c).ok().flatten().unwrap_or(self.combine)
+    }
+
+    /// Bytes read from source `i` per combined byte.
+    fn draw_ratio(&self, i: usize) -> usize {
+        match self.combine() {
+            CombineMode::WeightedXor if self.pool.is_none() => self.sources[i].profile.draw_ratio(),
+            _ => 1,
+        }
+    }
+
+    /// Whether source `i` may be read at `now`, as far as can be told without
+    /// taking a breaker probe.
+    fn is_usable(&self, i: usize, now: Instant) -> bool {
+        let slot = &self.sources[i];
+        slot.source.is_available() && !slot.in_maintenance() && !self.is_idle(i) && !slot.breaker.is_quarantined(now)
+    }
+
+    /// Promotes the idle half of each pair whose serving half is quarantined,
+    /// unavailable or disabled, if the idle half is fit to serve. There is no
+    /// automatic fail-back; the promoted source serves until it fails too or
+    /// `Promote` is called.
+    fn fail_over(&self, now: Instant) {
+        for pair in &self.pairs {
+            let was_promoted = pair.promoted.load(Ordering::SeqCst);
+            let (active, idle) = if was_promoted { (pair.standby, pair.primary) } else { (pair.primary, pair.standby) };
+            let (active, idle) = (&self.sources[active], &self.sources[idle]);
+            if !active.is_down(now) || idle.is_down(now) || idle.in_maintenance() {
+                continue;
+            }
+            // Concurrent requests must not flip the pair back
+            if pair.promoted.compare_exchange(was_promoted, !was_promoted, Ordering::SeqCst, Ordering::SeqCst).is_ok() {
+                let reason = if active.breaker.is_quarantined(now) { "quarantined" } else { "unavailable" };
+                self.announce_promotion(pair, reason);
+            }
+        }
+    }
+
+    /// Makes `source_id` the serving half of its standby pair. Fails with
+    /// `SourcesUnavailable` while it is down, as it would be failed over from
+    /// right away.
+    pub fn promote(&self, source_id: &str) -> Result<(), Error> {
+        let invalid = || Error::InvalidOption("source_id".to_string());
+        let i = self.sources.iter().position(|s| s.id == source_id).ok_or_else(invalid)?;
+        let pair = self.pairs.iter().find(|p| p.primary == i || p.standby == i).ok_or_else(invalid)?;
+        if self.sources[i].is_down(Instant::now()) {
+            log::warn!("Source {} is quarantined or unavailable, not promoting it", source_id);
+            return Err(Error::SourcesUnavailable);
+        }
+        let promoted = i == pair.standby;
+        if pair.promoted.swap(promoted, Ordering::SeqCst) == promoted {
+            log::info!("Source {} already serves for its standby pair", source_id);
+            return Ok(());
+        }
+        self.announce_promotion(pair, "manual");
+        Ok(())
+    }
+
+    /// What `GetHealth` reports: whether requests are let through (startup
+    /// readiness reached and the watchdog not tripped), and the state of each
+    /// source, the first of "failed", "quarantined", "maintenance",
+    /// "unavailable", "standby" and "ok" that applies.
+    pub fn health(&self) -> (bool, HashMap<String, String>) {
+        let now = Instant::now();
+        let ready = self.ready.load(Ordering::SeqCst) && !self.watchdog_tripped.load(Ordering::SeqCst);
+        let states = self.sources.iter().enumerate().map(|(i, slot)| {
+            let state = if !slot.source.is_healthy() {
+                "failed"
+            } else if slot.breaker.is_quarantined(now) {
+                "quarantined"
+            } else if slot.in_maintenance() {
+                "maintenance"
+            } else if !slot.source.is_available() {
+                "unavailable"
+            } else if self.is_idle(i) {
+                "standby"
+            } else {
+                "ok"
+            };
+            (slot.id.clone(), state.to_string())
+        });
+        (ready, states.collect())
+    }
+
+    /// The config hashes, as in stats snapshots.
+    pub fn config_hashes(&self) -> &ConfigHashes {
+        &self.config_hashes
+    }
+
+    /// The `[trending]` time series, if configured.
+    pub fn trends(&self) -> Option<&Trends> {
+        self.trends.as_ref()
+    }
+
+    /// What `GetSourceInfo` reports about `source_id`: its kind and version,
+    /// and what the source itself knows about its device.
+    pub fn source_info(&self, source_id: &str) -> Result<HashMap<String, String>, Error> {
+        let slot = self.sources.iter().find(|s| s.id == source_id).ok_or_else(|| Error::InvalidOption("source_id".to_string()))?;
+        let mut info = slot.source.info();
+        info.insert("kind".to_string(), slot.profile.kind.to_string());
+        info.insert("version".to_string(), slot.profile.version.clone());
+        Ok(info)
+    }
+
+    /// What `GetSourceStatus` reports for each source: its id and kind, its
+    /// buffer fill and size in bytes (both 0 if unbuffered) and whether it
+    /// passes its health tests.
+    pub async fn source_status(&self) -> Vec<(String, String, u64, u64, bool)> {
+        let mut status = Vec::with_capacity(self.sources.len());
+        for slot in &self.sources {
+            let (current, max) = slot.source.get_buffer_status().await.1.unwrap_or((0, 0));
+            status.push((slot.id.clone(), slot.profile.kind.to_string(), current as u64, max as u64, slot.source.is_healthy()));
+        }
+        status
+    }
+
+    fn announce_promotion(&self, pair: &StandbyPair, reason: &str) {
+        let (active, idle) = (&self.sources[pair.active()].id, &self.sources[pair.idle()].id);
+        log::warn!("Source {} promoted in place of {} ({})", active, idle, reason);
+        // No subscribers is fine
+        let _ = self.events.send(ServiceEvent::SourcePromoted {
+            source_id: active.clone(),
+            replaced: idle.clone(),
+            reason: reason.to_string(),
+        });
+    }
+
+    fn breaker_changed(&self, slot: &SourceSlot, state: Option<BreakerState>) {
+        if let Some(state) = state {
+            log::warn!("Source {} circuit breaker is now {}", slot.id, state.as_str());
+            // No subscribers is fine
+            let _ = self.events.send(ServiceEvent::BreakerStateChanged {
+                source_id: slot.id.clone(),
+                state: state.as_str(),
+            });
+        }
+    }
+
+    pub async fn read_bytes(&self, num_bytes: usize, timeout_ms: u64) -> Result<Vec<u8>, Error> {
+        let _memory = self.reserve_request(num_bytes)?;
+        self.read_traced(num_bytes, timeout_ms).await.map(|(bytes, _)| bytes)
+    }
+
+    /// Reads exactly `num_bytes`, reading again while sources come back short,
+    /// or fails with `Timeout` once `timeout_ms` passes; what was read by then
+    /// is zeroized, never served.
+    pub async fn read_bytes_exact(&self, num_bytes: usize, timeout_ms: u64) -> Result<Vec<u8>, Error> {
+        let _memory = self.reserve_request(num_bytes)?;
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        let mut out = Zeroizing::new(Vec::with_capacity(num_bytes));
+        loop {
+            let left_ms = deadline.saturating_duration_since(Instant::now()).as_millis() as u64;
+            let (bytes, _) = self.read_traced(num_bytes - out.len(), left_ms).await?;
+            out.extend_from_slice(&Zeroizing::new(bytes));
+            if out.len() == num_bytes {
+                return Ok(std::mem::take(&mut *out));
+            }
+            if Instant::now() >= deadline {
+                return Err(Error::Timeout);
+            }
+            // Sources that answer empty at once are not read in a busy loop
+            tokio::time::sleep(EXACT_RETRY_DELAY.min(deadline.saturating_duration_since(Instant::now()))).await;
+        }
+    }
+
+    /// Serves at once up to `max_bytes` of what the sources hold buffered,
+    /// never reading a device or file nor waiting for a refill: when combining
+    /// directly, as many bytes as every source read now has buffered for its
+    /// share. Nothing if one of those sources is unbuffered.
+    pub async fn read_available(&self, max_bytes: usize) -> Result<Vec<u8>, Error> {
+        let now = Instant::now();
+        let direct = self.pool.is_none() && self.output_drbg.is_none();
+        let mut available = max_bytes;
+        for (i, slot) in self.sources.iter().enumerate().filter(|&(i, _)| self.is_usable(i, now)) {
+            let buffered = slot.source.get_buffer_status().await.1.map(|(current, _)| current);
+            match buffered {
+                None => available = 0,
+                Some(current) if direct => available = available.min(current / self.draw_ratio(i)),
+                Some(_) => {}
+            }
+        }
+        if available == 0 {
+            return Ok(Vec::new());
+        }
+        let _memory = self.reserve_request(available)?;
+        // Buffered sources serve a timeout of 0 from their buffer alone
+        self.read_traced(available, 0).await.map(|(bytes, _)| bytes)
+    }
+
+    /// Assessed entropy in bits the usable sources hold buffered: each one's
+    /// buffered bytes times its `entropy_per_byte`, capped when combining
+    /// directly by what the combine mode passes on, at most 8 bits per byte of
+    /// the length every source covers. A `pool` absorbs all of it and adds
+    /// its own unsqueezed credit.
+    pub async fn entropy_estimate(&self) -> u64 {
+        let now = Instant::now();
+        let mut buffered = Vec::with_capacity(self.sources.len());
+        for (i, slot) in self.sources.iter().enumerate().filter(|&(i, _)| self.is_usable(i, now)) {
+            let current = slot.source.get_buffer_status().await.1.map_or(0, |(current, _)| current);
+            buffered.push((i, current));
+        }
+        // Bytes combining directly would take from every source, per source
+        let combined = self.pool.is_none().then(|| buffered.iter().map(|&(i, current)| current / self.draw_ratio(i)).min().unwrap_or(0));
+        let bits: f64 = buffered
+            .iter()
+            .map(|&(i, current)| {
+                let used = combined.map_or(current, |len| current.min(len * self.draw_ratio(i)));
+                used as f64 * self.sources[i].profile.entropy_per_byte
+            })
+            .sum();
+        let bits = match (self.pool.as_deref(), combined) {
+            (Some(Pool::Sponge(sponge)), _) => bits + sponge.credit() as f64 * 8.0,
+            (_, Some(len)) => bits.min(len as f64 * 8.0),
+            _ => bits,
+        };
+        bits as u64
+    }
+
+    /// Charges `bytes` for `what` to the memory account until the returned
+    /// reservation is dropped; fails with `MemoryExhausted` at the ceiling.
+    pub fn reserve(&self, bytes: usize, what: &str) -> Result<Reservation, Error> {
+        self.memory.reserve(bytes, what)
+    }
+
+    pub fn max_request_bytes(&self) -> usize {
+        self.max_request_bytes
+    }
+
+    /// Fails with `RequestTooLarge` if `num_bytes` is above `[service]
+    /// max_request_bytes`, logging who asked.
+    pub fn check_request_size(&self, num_bytes: usize) -> Result<(), Error> {
+        if num_bytes <= self.max_request_bytes {
+            return Ok(());
+        }
+        let client = scheduler::current().client;
+        log::warn!("Refused a request for {} bytes from {}, above [service] max_request_bytes of {}", num_bytes, client, self.max_request_bytes);
+        Err(Error::RequestTooLarge)
+    }
+
+    /// Charges what serving `num_bytes` holds at its peak: the answer and its
+    /// copy in the D-Bus reply, and per slice a read from each source and the
+    /// slice combined from them. Requests above `max_request_bytes` fail with
+    /// `RequestTooLarge` before anything is charged.
+    fn reserve_request(&self, num_bytes: usize) -> Result<Reservation, Error> {
+        self.check_request_size(num_bytes)?;
+        let slice = num_bytes.min(REQUEST_SLICE);
+        let slice = conditioning::current().map_or(slice, |c| c.input_len(slice));
+        let reads: usize = (0..self.sources.len()).map(|i| self.draw_ratio(i)).sum();
+        let cost = num_bytes.saturating_mul(2).saturating_add(slice.saturating_mul(reads + 1));
+        self.reserve(cost, "a request")
+    }
+
+    /// `read_bytes`, also returning the indices of the sources that contributed.
+    async fn read_traced(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Vec<usize>), Error> {
+        self.warn_if_infeasible(num_bytes, timeout_ms).await;
+        let (acc, contributors) = match &self.output_drbg {
+            Some(output) => self.read_output_drbg(output, num_bytes, timeout_ms).await?,
+            None if num_bytes > REQUEST_SLICE => self.read_sliced(num_bytes, timeout_ms).await?,
+            None => self.read_combined(num_bytes, timeout_ms, Purpose::Request).await?,
+        };
+
+        // Update statistics
+        self.requests_served.fetch_add(1, Ordering::Relaxed);
+        self.bytes_served.fetch_add(acc.len() as u64, Ordering::Relaxed);
+
+        // Keep the padding DRBG seeded while the sources keep up
+        if acc.len() == num_bytes && self.drbg_reseed.load(Ordering::Relaxed) {
+            self.reseed_drbg().await;
+        }
+
+        Ok((acc, contributors))
+    }
+
+    /// Serves `num_bytes` from the `[drbg]` output stage, first seeding it
+    /// from combined output when it is unseeded or has generated its reseed
+    /// interval. If a seed read fails or comes back short, the answer ends
+    /// there rather than stretch the old seed; an error only if nothing was
+    /// generated, so the jitter fallback still applies. It also ends once the
+    /// request's conditioning slice is used up.
+    async fn read_output_drbg(&self, output: &OutputDrbg, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Vec<usize>), Error> {
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        let mut state = output.state.lock().await;
+        let mut out = Vec::with_capacity(num_bytes);
+        let mut contributors = Vec::new();
+        while out.len() < num_bytes {
+            if state.is_none() || output.budget() == 0 {
+                let left_ms = if timeout_ms == 0 {
+                    0
+                } else {
+                    deadline.saturating_duration_since(Instant::now()).as_millis() as u64
+                };
+                let seed = match self.read_combined(SEED_LEN, left_ms, Purpose::OutputDrbgSeed).await {
+                    Ok((seed, used)) => (Zeroizing::new(seed), used),
+                    Err(e) => {
+                        output.record_reseed(false);
+                        if out.is_empty() {
+                            return Err(e);
+                        }
+                        break;
+                    }
+                };
+                let Ok(entropy) = <&[u8; SEED_LEN]>::try_from(&seed.0[..]) else {
+                    output.record_reseed(false);
+                    break;
+                };
+                match state.as_mut() {
+                    Some((drbg, seeded_by)) => {
+                        drbg.reseed(entropy);
+                        *seeded_by = seed.1;
+                    }
+                    None => *state = Some((CtrDrbg::new(entropy), seed.1)),
+                }
+                output.record_reseed(true);
+            }
+            let Some((drbg, seeded_by)) = state.as_mut() else { break };
+            if !out.is_empty() && budget::overrun() {
+                log::warn!("[drbg] output used up its [latency_budget] slice after {} of {} bytes", out.len(), num_bytes);
+                break;
+            }
+            let n = (num_bytes - out.len()).min(output.budget().try_into().unwrap_or(usize::MAX)).min(ctr_drbg::MAX_REQUEST);
+            out.extend_from_slice(&Zeroizing::new(drbg.generate(n)));
+            output.record_generated(n);
+            contributors.extend_from_slice(seeded_by);
+        }
+        contributors.sort_unstable();
+        contributors.dedup();
+        Ok((out, contributors))
+    }
+
+    /// Serves a large request in `REQUEST_SLICE` pieces, releasing the source
+    /// locks after each one, so small requests wait at most about one slice.
+    async fn read_sliced(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Vec<usize>), Error> {
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        let mut out = Zeroizing::new(Vec::with_capacity(num_bytes));
+        let mut contributors = Vec::new();
+        while out.len() < num_bytes {
+            let want = (num_bytes - out.len()).min(REQUEST_SLICE);
+            let left_ms = if timeout_ms == 0 {
+                0
+            } else {
+                deadline.saturating_duration_since(Instant::now()).as_millis() as u64
+            };
+            let (slice, used) = self.read_combined(want, left_ms, Purpose::Request).await?;
+            let slice = Zeroizing::new(slice);
+            out.extend_from_slice(&slice);
+            contributors.extend(used);
+            if slice.len() < want || (timeout_ms > 0 && left_ms == 0) {
+                break;
+            }
+            // Let queued requests take the source locks before the next slice
+            tokio::task::yield_now().await;
+        }
+        contributors.sort_unstable();
+        contributors.dedup();
+        Ok((std::mem::take(&mut *out), contributors))
+    }
+
+    /// Reads `want` bytes (times its draw ratio) from every source that may
+    /// serve now, skipping those circuit-broken, unavailable, idle or in
+    /// maintenance, and records each answer with the source's breaker.
+    async fn read_sources(&self, want: usize, timeout_ms: u64) -> Result<(Vec<usize>, Vec<Result<(Vec<u8>, Spans), Error>>), Error> {
+        // Skip sources whose breaker is open instead of waiting out their timeout
+        let now = Instant::now();
+        self.fail_over(now);
+        if min_sources().is_some_and(|min| (0..self.sources.len()).filter(|&i| self.is_usable(i, now)).count() < min) {
+            return Err(Error::TooFewSources);
+        }
+        let mut active = Vec::with_capacity(self.sources.len());
+        for (i, slot) in self.sources.iter().enumerate() {
+            if !slot.source.is_available() || slot.in_maintenance() || self.is_idle(i) {
+                continue;
+            }
+            let (allowed, change) = slot.breaker.try_acquire(now);
+            self.breaker_changed(slot, change);
+            if allowed {
+                active.push(i);
+            }
+        }
+        if active.is_empty() {
+            log::error!("All entropy sources are circuit-broken, unavailable or in maintenance");
+            return Err(Error::SourcesUnavailable);
+        }
+
+        let mut futures_vec = Vec::with_capacity(active.len());
+        for &i in &active {
+            futures_vec.push(self.sources[i].source.read_traced(want.saturating_mul(self.draw_ratio(i)), timeout_ms));
+        }
+        let results = join_all(futures_vec).await;
+
+        let done = Instant::now();
+        for (&i, res) in active.iter().zip(&results) {
+            // An empty answer to a request that allowed waiting counts as a failure
+            let ok = match res {
+                Ok((buf, _)) => !(buf.is_empty() && want > 0 && timeout_ms > 0),
+                Err(_) => false,
+            };
+            let slot = &self.sources[i];
+            self.breaker_changed(slot, slot.breaker.on_result(ok, done));
+            if let Err(Error::IntegrityFailure) = res {
+                let _ = self.events.send(ServiceEvent::SelfTestFailed {
+                    source_id: slot.id.clone(),
+                    reason: "integrity verification failed".to_string(),
+                });
+            }
+            match res {
+                Err(e) if !slot.failing.swap(true, Ordering::Relaxed) => {
+                    let _ = self.events.send(ServiceEvent::SourceFailed { source_id: slot.id.clone(), error: e.to_string() });
+                }
+                Err(_) => {}
+                Ok(_) => slot.failing.store(false, Ordering::Relaxed),
+            }
+        }
+        Ok((active, results))
+    }
+
+    /// Reads from every available source and combines the results, or
+    /// squeezes them from the `pool` if there is one, then passes them
+    /// through the endpoint's conditioner if it has one. Also returns the
+    /// indices of the sources read. `purpose` is what the provenance ledger
+    /// records the bytes as used for.
+    async fn read_combined(&self, num_bytes: usize, timeout_ms: u64, purpose: Purpose) -> Result<(Vec<u8>, Vec<usize>), Error> {
+        if self.sources.is_empty() {
+            log::error!("No enabled entropy sources found in config");
+            return Err(Error::Unexpected);
+        }
+        if shutdown::in_progress() {
+            return Err(Error::Unexpected);
+        }
+        // Canaries go through while tripped, to notice recovery
+        let tripped = purpose != Purpose::Canary && self.watchdog_tripped.load(Ordering::SeqCst);
+        if !self.ready.load(Ordering::SeqCst) || tripped {
+            return Err(Error::NotReady);
+        }
+        let _turn = self.scheduler.turn(num_bytes).await;
+
+        let conditioner = conditioning::current();
+        let want = conditioner.as_ref().map_or(num_bytes, |c| c.input_len(num_bytes));
+        // Canaries still read the sources themselves
+        if let Some(pool) = self.pool.as_ref().filter(|_| purpose != Purpose::Canary) {
+            let (acc, contributors) = self.read_pooled(pool, want, timeout_ms).await?;
+            return Ok((self.finish(acc, conditioner.as_deref(), num_bytes, purpose, &[]), contributors));
+        }
+        let min_sources = min_sources();
+        let (active, results) = self.read_sources(want, timeout_ms).await?;
+
+        let mut min_len = usize::MAX;
+        let mut acc: Option<Vec<u8>> = None;
+        let mut source_results = Vec::new();
+        let mut short = Vec::new();
+        let mut optional_error = None;
+        
+        for (i, res) in active.into_iter().zip(results) {
+            let (buf, spans) = match res {
+                Ok(result) => result,
+                Err(e) if self.sources[i].profile.required => {
+                    log::error!("Source {} failed: {:?}", self.sources[i].id, e);
+                    return Err(e);
+                }
+                // An optional source's error only leaves it out
+                Err(e) => {
+                    log::warn!("Optional source {} failed, combining without it: {:?}", self.sources[i].id, e);
+                    optional_error = Some(e);
+                    continue;
+                }
+            };
+            // Under min_sources a short source is left out rather than truncating the rest
+            if min_sources.is_some() && buf.len() < want.saturating_mul(self.draw_ratio(i)) {
+                short.push((i, buf, spans));
+                continue;
+            }
+            // Remove debug logging for performance
+            min_len = min_len.min(buf.len() / self.draw_ratio(i));
+            // Wiped when dropped, whichever way this request ends
+            source_results.push((i, Zeroizing::new(buf), spans));
+        }
+        let too_few = min_sources.filter(|&min| source_results.len() < min);
+        if let Some(min) = too_few {
+            log::warn!("Only {} sources delivered {} bytes in time, min_sources is {}", source_results.len(), want, min);
+            short.extend(source_results.drain(..).map(|(i, buf, spans)| (i, buf.to_vec(), spans)));
+        }
+        // Nothing of theirs was served; keep it for the next request
+        for (i, buf, spans) in short {
+            if !buf.is_empty() {
+                self.sources[i].source.return_leftover(buf, spans).await;
+            }
+        }
+        if too_few.is_some() {
+            return Err(Error::TooFewSources);
+        }
+        if let (true, Some(e)) = (source_results.is_empty(), optional_error) {
+            return Err(e);
+        }
+        let contributors: Vec<usize> = source_results.iter().map(|(i, _, _)| *i).collect();
+        
+        if min_len == usize::MAX { min_len = 0; }
+        match self.combine() {
+            // XOR the common prefix
+            CombineMode::Xor => {
+                for (_, buf, _) in &source_results {
+                    match &mut acc {
+                        None => acc = Some(buf.to_vec()),
+                        Some(existing) => {
+                            let len = existing.len().min(buf.len());
+                            for i in 0..len { existing[i] ^= buf[i]; }
+                        }
+                    }
+                }
+            }
+            // Fold each source down to the common length, then XOR
+            CombineMode::WeightedXor => {
+                let mut out = vec![0; min_len];
+                for (i, buf, _) in &source_results {
+                    xor_folded(&mut out, buf, self.draw_ratio(*i));
+                }
+                acc = (!source_results.is_empty()).then_some(out);
+            }
+            // Condition the common prefixes together
+            CombineMode::Sha3 => {
+                let prefixes: Vec<&[u8]> = source_results.iter().map(|(_, buf, _)| &buf[..min_len]).collect();
+                acc = (!prefixes.is_empty()).then(|| std::mem::take(&mut *sha3::combine(&prefixes)));
+            }
+        }
+        let mut acc = acc.ok_or(Error::Unexpected)?;
+        acc.truncate(min_len);
+        
+        // Return leftover bytes to sources that produced more than min_len
+        let mut served = Vec::with_capacity(source_results.len());
+        for (i, mut buf, mut spans) in source_results {
+            let used = min_len * self.draw_ratio(i);
+            // Debug builds: make a served prefix detectable if it is ever returned
+            poison(&mut buf[..used]);
+            let rest = spans.split_off(used);
+            if buf.len() > used {
+                let leftover = buf[used..].to_vec();
+                self.sources[i].source.return_leftover(leftover, rest).await;
+            }
+            served.push((self.sources[i].id.as_str(), spans));
+        }
+        Ok((self.finish(acc, conditioner.as_deref(), num_bytes, purpose, &served), contributors))
+    }
+
+    /// Conditions combined or squeezed bytes down to `num_bytes`, if the
+    /// endpoint has a conditioner, and records them in the ledger. Past the
+    /// request's conditioning slice the rest of them is dropped.
+    fn finish(&self, mut acc: Vec<u8>, conditioner: Option<&Conditioner>, num_bytes: usize, purpose: Purpose, served: &[(&str, Spans)]) -> Vec<u8> {
+        if let Some(conditioner) = conditioner {
+            let combined = Zeroizing::new(acc);
+            acc = conditioner.condition(&combined, budget::condition_deadline()).to_vec();
+            acc.truncate(num_bytes);
+            if acc.len() < num_bytes && budget::overrun() {
+                log::warn!("Conditioning used up its [latency_budget] slice after {} of {} bytes", acc.len(), num_bytes);
+            }
+        }
+        if ledger::enabled() && !acc.is_empty() {
+            let requester = scheduler::current();
+            let served: Vec<_> = served.iter().map(|(id, spans)| (*id, spans)).collect();
+            ledger::record_serve(requester.request_id, &requester.client, purpose, &acc, &served);
+        }
+        acc
+    }
+
+    /// Squeezes `want` bytes from `pool`, absorbing from the sources as it
+    /// goes what it lacks credit (or a first seed) for. The answer ends short
+    /// when they stop delivering; an error only if nothing was squeezed.
+    async fn read_pooled(&self, pool: &Pool, want: usize, timeout_ms: u64) -> Result<(Vec<u8>, Vec<usize>), Error> {
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        let mut out = Zeroizing::new(Vec::with_capacity(want));
+        loop {
+            let squeezed = pool.squeeze(want - out.len());
+            out.extend_from_slice(&squeezed);
+            if out.len() == want {
+                break;
+            }
+            let left_ms = if timeout_ms == 0 {
+                0
+            } else {
+                deadline.saturating_duration_since(Instant::now()).as_millis() as u64
+            };
+            match self.absorb(pool, pool.read_size(want - out.len()), left_ms).await {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) if out.is_empty() => return Err(e),
+                Err(_) => break,
+            }
+        }
+        Ok((out.to_vec(), pool.contributors(self.sources.len())))
+    }
+
+    /// Reads `want` bytes from every source that may serve now into `pool`
+    /// and returns the bytes credited. A source that fails is left out; an
+    /// error only if none delivered.
+    async fn absorb(&self, pool: &Pool, want: usize, timeout_ms: u64) -> Result<u64, Error> {
+        let (active, results) = self.read_sources(want, timeout_ms).await?;
+        let requester = scheduler::current();
+        let mut credited = 0;
+        let mut failure = None;
+        for (i, res) in active.into_iter().zip(results) {
+            let slot = &self.sources[i];
+            match res {
+                Ok((buf, spans)) => {
+                    let buf = Zeroizing::new(buf);
+                    credited += pool.absorb(i, &buf, slot.profile.entropy_per_byte);
+                    if ledger::enabled() && !buf.is_empty() {
+                        ledger::record_serve(requester.request_id, &requester.client, Purpose::PoolAbsorb, &buf, &[(slot.id.as_str(), &spans)]);
+                    }
+                }
+                Err(e) => {
+                    log::warn!("Source {} failed, absorbing the others: {:?}", slot.id, e);
+                    failure = Some(e);
+                }
+            }
+        }
+        match failure {
+            Some(e) if credited == 0 => Err(e),
+            _ => Ok(credited),
+        }
+    }
+
+    pub fn has_pool(&self) -> bool {
+        self.pool.is_some()
+    }
+
+    /// The pool requests are served from, as named in provenance.
+    pub fn pool_name(&self) -> Option<&'static str> {
+        self.pool.as_ref().map(|pool| pool.name())
+    }
+
+    /// Keeps feeding the `pool` what the sources have buffered, so requests
+    /// need not wait on any of them.
+    pub async fn feed_pool(&self, heartbeat: Heartbeat) {
+        let Some(pool) = &self.pool else { return };
+        let mut tick = interval(pool::ABSORB_INTERVAL);
+        loop {
+            tick.tick().await;
+            heartbeat.beat();
+            let missing = pool.feed_size();
+            let now = Instant::now();
+            if missing == 0 || !self.ready.load(Ordering::SeqCst) || shutdown::in_progress() || !(0..self.sources.len()).any(|i| self.is_usable(i, now)) {
+                continue;
+            }
+            let _turn = self.scheduler.turn(missing).await;
+            let _ = self.absorb(pool, missing, 0).await;
+        }
+    }
+
+    /// Benchmarks every source in turn; returns the measured rates in bytes/s.
+    async fn benchmark_sources(slots: &[(String, Arc<dyn EntropySource>)], budget: Duration) -> HashMap<String, f64> {
+        let mut rates = HashMap::new();
+        for (id, source) in slots {
+            match source.benchmark(budget).await {
+                Some(rate) => {
+                    log::info!("Source {} sustains {:.2} MB/s", id, rate / (1024.0 * 1024.0));
+                    rates.insert(id.clone(), rate);
+                }
+                None => log::info!("Source {} could not be benchmarked", id),
+            }
+        }
+        rates
+    }
+
+    /// Re-runs the per-source benchmark on demand.
+    pub async fn run_benchmark(&self) -> HashMap<String, f64> {
+        let slots: Vec<(String, Arc<dyn EntropySource>)> = self.sources.iter().map(|s| (s.id.clone(), s.source.clone())).collect();
+        Self::benchmark_sources(&slots, self.benchmark).await
+    }
+
+    /// Warns (rate-limited) when a benchmarked source cannot deliver
+    /// `num_bytes` within `timeout_ms` from its buffer plus its sustainable
+    /// rate, i.e. the request is bound to come back short.
+    async fn warn_if_infeasible(&self, num_bytes: usize, timeout_ms: u64) {
+        for (i, slot) in self.sources.iter().enumerate().filter(|(i, _)| !self.is_idle(*i)) {
+            let Some(rate) = slot.source.metrics().estimated_rate else { continue };
+            let producible = rate * timeout_ms as f64 / 1000.0;
+            let needed = num_bytes.saturating_mul(self.draw_ratio(i));
+            if needed as f64 <= producible {
+                continue;
+            }
+            let buffered = slot.source.get_buffer_status().await.1.map_or(0, |(current, _)| current);
+            if needed as f64 <= buffered as f64 + producible {
+                continue;
+            }
+            let mut last = self.last_infeasible_warning.lock().unwrap_or_else(|e| e.into_inner());
+            if last.is_none_or(|at| at.elapsed() >= INFEASIBLE_WARNING_INTERVAL) {
+                *last = Some(Instant::now());
+                log::warn!(
+                    "Request for {} bytes within {} ms cannot be met: source {} has {} bytes buffered and sustains {:.0} B/s",
+                    needed,
+                    timeout_ms,
+                    slot.id,
+                    buffered,
+                    rate
+                );
+            }
+            return;
+        }
+    }
 
-        for lrng in cfg.lrng_sources.into_iter() {
-            log::info!("Initializing LRNG source: {}", lrng.id);
-            sources.push(Arc::new(LrngSource::new(lrng)));
+    /// Seeds (or reseeds) the padding DRBG from combined output that is
+    /// never served to a client. Only uses bytes available right away.
+    async fn reseed_drbg(&self) -> bool {
+        let seed = match self.read_combined(SEED_LEN, 0, Purpose::DrbgSeed).await {
+            Ok((seed, _)) => Zeroizing::new(seed),
+            Err(_) => return false,
+        };
+        if seed.len() < SEED_LEN {
+            return false;
+        }
+        let mut drbg = self.drbg.lock().await;
+        match drbg.as_mut() {
+            Some(drbg) => drbg.reseed(&seed),
+            None => *drbg = Some(HmacDrbg::new(&seed)),
         }
+        self.drbg_reseed.store(false, Ordering::Relaxed);
+        true
+    }
 
-        for filecfg in cfg.file_sources.into_iter() {
-            log::info!("Initializing file source: {} at {}", filecfg.id, filecfg.path);
-            let src = FileSource::new(filecfg)
-                .await
-                .map_err(|e| {
-                    log::error!("Failed to open file source: {}", e);
-                    Error::OsError(e.raw_os_error().unwrap_or(0) as u32)
-                })?;
-            sources.push(Arc::new(src));
+    /// A seed for a client's DRBG stream from combined output that is never
+    /// served to anyone; fails rather than seed from a short read.
+    pub async fn stream_seed(&self, timeout_ms: u64) -> Result<Zeroizing<Vec<u8>>, Error> {
+        let (seed, _) = self.read_combined(SEED_LEN, timeout_ms, Purpose::StreamSeed).await?;
+        let seed = Zeroizing::new(seed);
+        if seed.len() < SEED_LEN {
+            return Err(Error::InsufficientEntropy);
         }
+        Ok(seed)
+    }
 
-        log::info!("Aggregator initialized with {} sources", sources.len());
-        
-        let bytes_served = Arc::new(AtomicU64::new(0));
-        let requests_served = Arc::new(AtomicU64::new(0));
-        
-        // Start periodic logging
-        let sources_clone = sources.clone();
-        let bytes_served_clone = bytes_served.clone();
-        let requests_served_clone = requests_served.clone();
-        tokio::spawn(async move {
-            Self::periodic_logging(sources_clone, bytes_served_clone, requests_served_clone).await;
-        });
-        
-        Ok(Self { combine: cfg.combine, sources, bytes_served, requests_served })
+    /// Counts bytes served from a client stream in the statistics.
+    pub fn count_served(&self, bytes: usize) {
+        self.requests_served.fetch_add(1, Ordering::Relaxed);
+        self.bytes_served.fetch_add(bytes as u64, Ordering::Relaxed);
     }
 
-    pub async fn read_bytes(&self, num_bytes: usize, timeout_ms: u64) -> Result<Vec<u8>, Error> {
-        if self.sources.is_empty() {
-            log::error!("No enabled entropy sources found in config");
-            return Err(Error::Unexpected);
+    /// Appends DRBG output until `bytes` is `num_bytes` long, or the request's
+    /// conditioning slice is used up. Returns the number of bytes added, 0 if
+    /// the DRBG could not be seeded yet.
+    async fn drbg_pad(&self, bytes: &mut Vec<u8>, num_bytes: usize, personalization: &[u8]) -> usize {
+        if self.drbg.lock().await.is_none() && !self.reseed_drbg().await {
+            log::warn!("DRBG padding requested before the DRBG could be seeded");
+            return 0;
         }
-        let mut futures_vec = Vec::with_capacity(self.sources.len());
-        for src in &self.sources {
-            futures_vec.push(src.read_bytes(num_bytes, timeout_ms));
+        let pad = num_bytes - bytes.len();
+        let mut padded = 0;
+        while padded < pad {
+            if padded > 0 && budget::overrun() {
+                log::warn!("DRBG padding used up its [latency_budget] slice after {} of {} bytes", padded, pad);
+                break;
+            }
+            let n = (pad - padded).min(drbg::MAX_REQUEST);
+            let padding = match self.drbg.lock().await.as_mut() {
+                Some(drbg) => Zeroizing::new(drbg.generate(n, personalization)),
+                None => break,
+            };
+            bytes.extend_from_slice(&padding);
+            padded += n;
         }
-        let results = join_all(futures_vec).await;
+        // Fresh source output goes into the DRBG before it pads again
+        self.drbg_reseed.store(true, Ordering::Relaxed);
+        log::debug!("Padded response with {} DRBG bytes", padded);
+        padded
+    }
+    
+    /// `read_bytes`, except that under the `serve_flagged` fallback policy a
+    /// request no source can serve is answered by the jitter generator.
+    /// Returns the bytes and whether they are such fallback output.
+    pub async fn read_bytes_or_fallback(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, bool), Error> {
+        let (bytes, contributors) = self.read_traced_or_fallback(num_bytes, timeout_ms).await?;
+        Ok((bytes, contributors.is_none()))
+    }
 
-        let mut min_len = usize::MAX;
-        let mut acc: Option<Vec<u8>> = None;
-        let mut source_results = Vec::new();
-        
-        for (i, res) in results.into_iter().enumerate() {
-            let buf = match res {
-                Ok(result) => result,
-                Err(e) => {
-                    log::error!("Source {} failed: {:?}", i, e);
-                    return Err(e);
+    /// `read_traced` with the fallback; `None` instead of the contributing
+    /// sources when the bytes are fallback output.
+    async fn read_traced_or_fallback(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Option<Vec<usize>>), Error> {
+        let _memory = self.reserve_request(num_bytes)?;
+        match self.read_traced(num_bytes, timeout_ms).await {
+            Err(Error::SourcesUnavailable) if self.fallback == FallbackPolicy::ServeFlagged => {
+                if !self.fallback_active.swap(true, Ordering::Relaxed) {
+                    log::error!("Every source is quarantined - serving flagged low-assurance jitter fallback");
                 }
-            };
-            // Remove debug logging for performance
-            min_len = min_len.min(buf.len());
-            source_results.push((i, buf));
-        }
-        
-        // XOR the common prefix
-        for (_, buf) in &source_results {
-            match &mut acc {
-                None => acc = Some(buf.clone()),
-                Some(existing) => {
-                    let len = existing.len().min(buf.len());
-                    for i in 0..len { existing[i] ^= buf[i]; }
+                let deadline = std::time::Instant::now() + Duration::from_millis(timeout_ms);
+                let bytes = tokio::task::spawn_blocking(move || jitter::generate(num_bytes, deadline))
+                    .await
+                    .map_err(|_| Error::Unexpected)??;
+                Ok((bytes, None))
+            }
+            res => {
+                if res.is_ok() && self.fallback_active.swap(false, Ordering::Relaxed) {
+                    log::warn!("Sources available again - jitter fallback no longer in use");
                 }
+                res.map(|(bytes, contributors)| (bytes, Some(contributors)))
             }
         }
-        
-        if min_len == usize::MAX { min_len = 0; }
-        let mut acc = acc.ok_or(Error::Unexpected)?;
-        acc.truncate(min_len);
-        
-        // Return leftover bytes to sources that produced more than min_len
-        for (i, buf) in source_results {
-            if buf.len() > min_len {
-                let leftover = buf[min_len..].to_vec();
-                self.sources[i].return_leftover(leftover).await;
-            }
+    }
+
+    /// Like `read_bytes_or_fallback`, but a client that opted in gets a short answer
+    /// padded to `num_bytes`, first from the DRBG and then from the insecure tier.
+    pub async fn read_bytes_ex(&self, num_bytes: usize, timeout_ms: u64, opts: &ReadOptions) -> Result<Response, Error> {
+        let (mut bytes, contributors) = self.read_traced_or_fallback(num_bytes, timeout_ms).await?;
+        let (secure_len, fallback_len) = match contributors {
+            Some(_) => (bytes.len(), 0),
+            None => (0, bytes.len()),
+        };
+        let personalization = opts.personalization.as_deref().unwrap_or_default();
+        if !personalization.is_empty() {
+            drbg::personalize(&mut bytes, personalization);
         }
-        
-        // Update statistics
-        self.requests_served.fetch_add(1, Ordering::Relaxed);
-        self.bytes_served.fetch_add(acc.len() as u64, Ordering::Relaxed);
-        
-        Ok(acc)
+        let mut drbg_len = 0;
+        if opts.drbg_fill && bytes.len() < num_bytes {
+            drbg_len = self.drbg_pad(&mut bytes, num_bytes, personalization).await;
+        }
+        if opts.insecure_fill && bytes.len() < num_bytes {
+            let pad = num_bytes - bytes.len();
+            let padding = tokio::task::spawn_blocking(move || os_fill_insecure_octets(pad))
+                .await
+                .map_err(|_| Error::Unexpected)??;
+            let padding = Zeroizing::new(padding);
+            bytes.extend_from_slice(&padding);
+            log::debug!("Padded response with {} insecure bytes", pad);
+        }
+        let provenance = self.provenance(contributors.as_deref(), secure_len, !personalization.is_empty());
+        Ok(Response { bytes, secure_len, drbg_len, fallback_len, provenance })
     }
-    
+
+    fn provenance(&self, contributors: Option<&[usize]>, secure_len: usize, personalized: bool) -> Provenance {
+        let sources: Vec<SourceReport> = contributors.unwrap_or_default().iter().map(|&i| self.sources[i].report()).collect();
+        let combine = self.combine();
+        let mut conditioning = match (contributors, &self.pool) {
+            (Some(_), Some(pool)) => pool.name().to_string(),
+            (Some(_), None) if combine == CombineMode::Sha3 => "sha3-256".to_string(),
+            (Some(_), None) if combine == CombineMode::WeightedXor => "weighted-xor".to_string(),
+            (Some(_), None) => "xor".to_string(),
+            (None, _) => "jitter-sha256".to_string(),
+        };
+        if let Some(conditioner) = conditioning::current().filter(|_| contributors.is_some()) {
+            conditioning.push('+');
+            conditioning.push_str(conditioner.name());
+        }
+        if self.output_drbg.is_some() && contributors.is_some() {
+            conditioning.push_str("+ctr-drbg-aes256");
+        }
+        if personalized {
+            conditioning.push_str("+hmac-sha256");
+        }
+        // Folded bytes carry 8 assessed bits each, from every source, and the
+        // pool serves only what it credited the same way
+        let credit = match combine {
+            _ if self.pool.as_ref().is_some_and(|p| p.full_credit()) => 1.0,
+            CombineMode::WeightedXor => 1.0,
+            _ => sources.iter().map(|s| s.entropy_credit).fold(0.0, f64::max),
+        };
+        Provenance { sources, conditioning, entropy_bits: (secure_len as f64 * 8.0 * credit) as u64 }
+    }
+
+    /// Derives `length` bytes of key material for `label` from fresh
+    /// combined output. Fails rather than derive from a short read.
+    pub async fn derive_key(&self, label: &str, length: usize, timeout_ms: u64) -> Result<Vec<u8>, Error> {
+        if length == 0 || length > kdf::MAX_KEY_LEN {
+            return Err(Error::InvalidOption("length".to_string()));
+        }
+        let ikm = self.read_exact(kdf::ikm_len(length), timeout_ms).await?;
+        kdf::derive_key(&ikm, label, length).ok_or(Error::Unexpected)
+    }
+
+    /// One buffer per entry of `sizes`, read in a single combined request so
+    /// the batch shares one deadline. Buffers are filled in order, so if the
+    /// sources fall short only the trailing buffers come back short or empty.
+    pub async fn read_bytes_multi(&self, sizes: &[usize], timeout_ms: u64) -> Result<Vec<Vec<u8>>, Error> {
+        if sizes.len() > MAX_BATCH_BUFFERS {
+            return Err(Error::InvalidOption("sizes".to_string()));
+        }
+        let total = sizes.iter().try_fold(0usize, |acc, &n| acc.checked_add(n));
+        let total = total.ok_or_else(|| Error::InvalidOption("sizes".to_string()))?;
+        self.check_request_size(total)?;
+        // The buffers are copies of the combined answer
+        let _memory = self.reserve(total, "a batch")?;
+        let bytes = Zeroizing::new(self.read_bytes(total, timeout_ms).await?);
+        let mut rest = &bytes[..];
+        Ok(sizes
+            .iter()
+            .map(|&n| {
+                let (head, tail) = rest.split_at(n.min(rest.len()));
+                rest = tail;
+                head.to_vec()
+            })
+            .collect())
+    }
+
+    /// Writes up to `len` bytes straight into a client's memory region (a
+    /// memfd or other regular file) at `offset`; returns the bytes written.
+    /// The region must already be large enough, the file is never grown.
+    pub async fn read_bytes_into(&self, target: File, offset: u64, len: usize, timeout_ms: u64) -> Result<usize, Error> {
+        let meta = target.metadata().map_err(os_error)?;
+        if !meta.file_type().is_file() {
+            return Err(Error::InvalidOption("fd".to_string()));
+        }
+        if offset.checked_add(len as u64).is_none_or(|end| end > meta.len()) {
+            return Err(Error::InvalidOption("len".to_string()));
+        }
+        let bytes = Zeroizing::new(self.read_bytes(len, timeout_ms).await?);
+        tokio::task::spawn_blocking(move || target.write_all_at(&bytes, offset).map(|_| bytes.len()))
+            .await
+            .map_err(|_| Error::Unexpected)?
+            .map_err(os_error)
+    }
+
+    /// Diverts up to `num_bytes` raw bytes of one source for offline analysis.
+    /// They are taken out of that source (buffer first), so they are never
+    /// combined into or returned with any other request.
+    pub async fn capture_raw(&self, source_id: &str, num_bytes: usize, timeout_ms: u64) -> Result<Vec<u8>, Error> {
+        if num_bytes > MAX_CAPTURE_BYTES {
+            return Err(Error::InvalidOption("bytes".to_string()));
+        }
+        let slot = self.slot(source_id)?;
+        let _memory = self.reserve(num_bytes.saturating_mul(2), "a capture")?;
+        slot.source.read_bytes(num_bytes, timeout_ms).await
+    }
+
+    /// Resizes one source's buffer in place; returns the bytes discarded.
+    pub async fn set_buffer_size(&self, source_id: &str, bytes: usize) -> Result<usize, Error> {
+        let slot = self.slot(source_id)?;
+        // The old storage lives until its contents are copied over
+        let charge = self.reserve(bytes, "a source buffer")?;
+        let discarded = slot.source.resize_buffer(bytes).await?;
+        slot.charge_buffer(Some(charge));
+        Ok(discarded)
+    }
+
+    /// Retunes one buffered source's replenishing.
+    pub fn set_tuning(&self, source_id: &str, watermarks: (Option<u8>, Option<u8>), chunk: Option<usize>) -> Result<(), Error> {
+        let tuning = self.slot(source_id)?.source.tuning().ok_or_else(|| Error::InvalidOption("source_id".to_string()))?;
+        tuning.set(watermarks.0, watermarks.1, chunk)
+    }
+
+    fn slot(&self, source_id: &str) -> Result<&SourceSlot, Error> {
+        self.sources
+            .iter()
+            .find(|s| s.id == source_id)
+            .map(|s| &**s)
+            .ok_or_else(|| Error::InvalidOption("source_id".to_string()))
+    }
+
+    /// `count` uniform floats in [0, 1) from the combined stream.
+    pub async fn read_floats(&self, count: usize, timeout_ms: u64) -> Result<Vec<f64>, Error> {
+        if count > sampling::MAX_SAMPLES {
+            return Err(Error::InvalidOption("count".to_string()));
+        }
+        let bytes = self.read_exact(count * 8, timeout_ms).await?;
+        Ok(sampling::uniforms(&bytes))
+    }
+
+    /// `count` normally distributed samples from the combined stream.
+    pub async fn read_gaussians(&self, count: usize, mean: f64, stddev: f64, timeout_ms: u64) -> Result<Vec<f64>, Error> {
+        if count > sampling::MAX_SAMPLES {
+            return Err(Error::InvalidOption("count".to_string()));
+        }
+        if !mean.is_finite() || !stddev.is_finite() || stddev < 0.0 {
+            return Err(Error::InvalidOption("stddev".to_string()));
+        }
+        let bytes = self.read_exact(sampling::gaussian_bytes(count), timeout_ms).await?;
+        Ok(sampling::gaussians(&bytes, count, mean, stddev))
+    }
+
+    /// Reads exactly `num_bytes` or fails with `InsufficientEntropy`.
+    async fn read_exact(&self, num_bytes: usize, timeout_ms: u64) -> Result<Zeroizing<Vec<u8>>, Error> {
+        let bytes = Zeroizing::new(self.read_bytes(num_bytes, timeout_ms).await?);
+        if bytes.len() < num_bytes {
+            log::warn!("Got {} of {} bytes before the deadline", bytes.len(), num_bytes);
+            return Err(Error::InsufficientEntropy);
+        }
+        Ok(bytes)
+    }
+
+    /// Every counter and health state, with the config hashes, in the
+    /// versioned layout of `ExportStatsSnapshot` (see `snapshot.rs`).
+    pub async fn snapshot(&self) -> Value {
+        let now = Instant::now();
+        let mut sources = Vec::with_capacity(self.sources.len());
+        for (i, slot) in self.sources.iter().enumerate() {
+            let metrics = slot.source.metrics();
+            let buffer = slot.source.get_buffer_status().await.1;
+            let standby = self.pairs.iter().find(|p| p.primary == i || p.standby == i).map(|p| {
+                json!({ "role": if p.standby == i { "standby" } else { "primary" }, "serving": !self.is_idle(i) })
+            });
+            sources.push(json!({
+                "id": slot.id,
+                "kind": slot.profile.kind,
+                "version": slot.profile.version,
+                "entropy_credit": slot.profile.entropy_credit,
+                "entropy_per_byte": slot.profile.entropy_per_byte,
+                "required": slot.profile.required,
+                "available": slot.source.is_available(),
+                "healthy": slot.source.is_healthy(),
+                "in_maintenance": slot.in_maintenance(),
+                "breaker": slot.breaker.state().as_str(),
+                "quarantined": slot.breaker.is_quarantined(now),
+                "standby": standby,
+                "buffer": buffer.map(|(len, capacity)| json!({ "len": len, "capacity": capacity })),
+                "info": slot.source.info(),
+                "retries": metrics.retries,
+                "estimated_rate": metrics.estimated_rate,
+                "leftovers": {
+                    "returned": metrics.leftovers.returned,
+                    "reused": metrics.leftovers.reused,
+                    "dropped": metrics.leftovers.dropped,
+                },
+                "publishers": metrics.publishers.iter().map(|(id, p)| (id.clone(), json!({
+                    "messages": p.messages,
+                    "bytes": p.bytes,
+                    "overflowed": p.overflowed,
+                    "wrong_size": p.wrong_size,
+                    "constant": p.constant,
+                    "repeated": p.repeated,
+                    "retained": p.retained,
+                }))).collect::<serde_json::Map<_, _>>(),
+                "quality": self.trends().and_then(|t| t.latest(&slot.id)).map(|p| json!({ "taken_at": p.taken_at, "values": p.values() })),
+            }));
+        }
+        let (bytes_served, requests_served) = self.get_stats();
+        let (bus_outages, bus_outage_ms) = bus::outages();
+        let (memory_used, memory_peak, memory_ceiling, memory_refused) = self.memory.stats();
+        let hashes = &self.config_hashes;
+        json!({
+            "version": snapshot::VERSION,
+            "service": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION"), "pid": std::process::id() },
+            "taken_at_ms": SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64),
+            "uptime_ms": self.started.elapsed().as_millis() as u64,
+            "counters": {
+                "bytes_served": bytes_served,
+                "requests_served": requests_served,
+                "supervisor_incidents": supervisor::incident_count(),
+                "bus_outages": bus_outages,
+                "bus_outage_ms": bus_outage_ms,
+                "bus_reconnect_attempts": bus::reconnect_attempts(),
+                "memory_refused": memory_refused,
+            },
+            "memory": { "used": memory_used, "peak": memory_peak, "ceiling": memory_ceiling },
+            "drbg": self.drbg_stats().map(|stats| stats.into_iter().map(|(k, v)| (k.to_string(), Value::from(v))).collect::<serde_json::Map<_, _>>()),
+            "pool": self.pool.as_ref().map(|pool| json!({
+                "kind": pool.name(),
+                "stats": pool.stats().into_iter().map(|(k, v)| (k, Value::from(v))).collect::<serde_json::Map<_, _>>(),
+                "absorbed_bytes": self.sources.iter().enumerate().map(|(i, s)| (s.id.clone(), Value::from(pool.absorbed(i)))).collect::<serde_json::Map<_, _>>(),
+            })),
+            "state": {
+                "ready": self.ready.load(Ordering::SeqCst),
+                "watchdog_tripped": self.watchdog_tripped.load(Ordering::SeqCst),
+                "fallback_active": self.fallback_active.load(Ordering::Relaxed),
+            },
+            "sources": sources,
+            "config": { "path": hashes.path, "sha256": hashes.file, "sections": hashes.sections },
+        })
+    }
+
+    /// The `pool`'s fill levels and counters, if there is one.
+    pub fn pool_stats(&self) -> Option<Vec<(String, u64)>> {
+        self.pool.as_ref().map(|pool| pool.stats())
+    }
+
+    /// The `[drbg]` output stage's counters, if it is enabled.
+    pub fn drbg_stats(&self) -> Option<Vec<(&'static str, u64)>> {
+        self.output_drbg.as_ref().map(OutputDrbg::stats)
+    }
+
     pub fn get_stats(&self) -> (u64, u64) {
         let bytes = self.bytes_served.load(Ordering::Relaxed);
         let requests = self.requests_served.load(Ordering::Relaxed);
         (bytes, requests)
     }
+
+    /// `[sources] combine`.
+    pub fn combine_mode(&self) -> CombineMode {
+        self.combine
+    }
+
+    pub fn source_count(&self) -> usize {
+        self.sources.len()
+    }
+
+    /// Kinds of the configured sources, each once, in config order.
+    pub fn configured_kinds(&self) -> Vec<&'static str> {
+        let mut kinds = Vec::new();
+        for slot in &self.sources {
+            if !kinds.contains(&slot.profile.kind) {
+                kinds.push(slot.profile.kind);
+            }
+        }
+        kinds
+    }
+
+    /// Kinds of source this build can read, as `GetSourceInfo` names them.
+    pub fn source_kinds() -> Vec<&'static str> {
+        let mut kinds = vec!["lrng", "file", "serial", "tcp", "http", "cpu", "jitter", "mqtt", "hwrng", "exec", "pkcs11", "audio", "dbus"];
+        if cfg!(feature = "nats") {
+            kinds.push("nats");
+        }
+        if cfg!(feature = "grpc") {
+            kinds.push("grpc");
+        }
+        kinds
+    }
+
+    /// How full the buffered sources are together, in whole percent; 0
+    /// without buffered sources.
+    pub async fn buffer_fill_percent(&self) -> u8 {
+        let (mut current, mut max) = (0u64, 0u64);
+        for slot in &self.sources {
+            if let Some((c, m)) = slot.source.get_buffer_status().await.1 {
+                current += c as u64;
+                max += m as u64;
+            }
+        }
+        if max == 0 {
+            return 0;
+        }
+        (current * 100 / max).min(100) as u8
+    }
+    
+    /// Reports buffered sources whose fill drops below `percent`, once each
+    /// time until they are back at it.
+    async fn watch_low_buffers(sources: Vec<Arc<SourceSlot>>, events: EventSender, percent: f64, heartbeat: Heartbeat) {
+        let mut low: HashSet<String> = HashSet::new();
+        let mut interval = interval(LOW_BUFFER_INTERVAL);
+        loop {
+            interval.tick().await;
+            heartbeat.beat();
+            for slot in &sources {
+                let (id, buffer_status) = slot.source.get_buffer_status().await;
+                let Some((current, max)) = buffer_status.filter(|&(_, max)| max > 0) else { continue };
+                let fill_percent = current as f64 * 100.0 / max as f64;
+                if fill_percent >= percent {
+                    low.remove(&id);
+                } else if low.insert(id.clone()) {
+                    let _ = events.send(ServiceEvent::EntropyLow { source_id: id, fill_percent });
+                }
+            }
+        }
+    }
+
+    /// Reports buffered sources that stay empty for longer than `threshold`.
+    async fn watch_empty_buffers(sources: Vec<Arc<SourceSlot>>, events: EventSender, threshold: Duration, heartbeat: Heartbeat) {
+        let mut empty_since: HashMap<String, Instant> = HashMap::new();
+        let mut interval = interval(Duration::from_secs(1));
+        loop {
+            interval.tick().await;
+            heartbeat.beat();
+            for slot in &sources {
+                let (id, buffer_status) = slot.source.get_buffer_status().await;
+                match buffer_status {
+                    // A drained buffer during maintenance is expected
+                    Some((0, max)) if max > 0 && !slot.in_maintenance() => {
+                        let since = *empty_since.entry(id.clone()).or_insert_with(Instant::now);
+                        if since.elapsed() >= threshold {
+                            let _ = events.send(ServiceEvent::BufferEmpty { source_id: id, empty_seconds: since.elapsed().as_secs() });
+                        }
+                    }
+                    _ => {
+                        empty_since.remove(&id);
+                    }
+                }
+            }
+        }
+    }
     
-    async fn periodic_logging(sources: Vec<Arc<dyn EntropySource>>, bytes_served: Arc<AtomicU64>, requests_served: Arc<AtomicU64>) {
+    /// Pauses each source while one of its maintenance windows is open and
+    /// resumes it afterwards.
+    async fn run_maintenance(sources: Vec<Arc<SourceSlot>>, heartbeat: Heartbeat) {
+        let mut check = interval(MAINTENANCE_CHECK_INTERVAL);
+        let mut clocks = ClockWatch::new(SystemClock);
+        loop {
+            check.tick().await;
+            heartbeat.beat();
+            let (now, changes) = clocks.check();
+            clock::log_changes("Maintenance windows", &changes);
+            for slot in &sources {
+                match (slot.maintenance.active(now), slot.in_maintenance()) {
+                    (Some((window, opened)), false) => {
+                        if slot.last_window.swap(opened, Ordering::SeqCst) == opened {
+                            continue;
+                        }
+                        // Out of requests first, so a drain cannot race a read
+                        slot.in_maintenance.store(true, Ordering::SeqCst);
+                        slot.source.set_paused(true, window.drain).await;
+                        log::info!(
+                            "Source {}: maintenance window '{}' open for {} min, paused{}",
+                            slot.id,
+                            window.cron,
+                            window.minutes,
+                            if window.drain { " and buffer drained" } else { "" }
+                        );
+                    }
+                    (None, true) => {
+                        slot.source.set_paused(false, false).await;
+                        slot.in_maintenance.store(false, Ordering::SeqCst);
+                        log::info!("Source {}: maintenance window closed, resumed", slot.id);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    async fn periodic_logging(sources: Vec<Arc<SourceSlot>>, bytes_served: Arc<AtomicU64>, requests_served: Arc<AtomicU64>, heartbeat: Heartbeat) {
         let mut interval = interval(Duration::from_secs(10));
         loop {
             interval.tick().await;
+            heartbeat.beat();
             
             let total_bytes = bytes_served.load(Ordering::Relaxed);
             let total_requests = requests_served.load(Ordering::Relaxed);
             let total_mb = total_bytes as f64 / (1024.0 * 1024.0);
             
-            log::info!("Statistics: {} requests served, {:.2} MB total", total_requests, total_mb);
+            log::info!(
+                "Statistics: {} requests served, {:.2} MB total, {} supervisor incidents",
+                total_requests,
+                total_mb,
+                supervisor::incident_count()
+            );
             
-            for source in &sources {
+            for slot in &sources {
+                let source = &slot.source;
                 let (id, buffer_status) = source.get_buffer_status().await;
+                if !source.is_available() {
+                    log::warn!("Source {}: unavailable (degraded)", id);
+                    continue;
+                }
+                if slot.in_maintenance() {
+                    log::info!("Source {}: paused for maintenance", id);
+                    continue;
+                }
+                let metrics = source.metrics();
                 match buffer_status {
                     Some((current, max)) => {
                         let current_mb = current as f64 / (1024.0 * 1024.0);
                         let max_mb = max as f64 / (1024.0 * 1024.0);
                         let percentage = if max > 0 { (current as f64 / max as f64) * 100.0 } else { 0.0 };
-                        log::info!("Source {}: buffer {:.2}/{:.2} MB ({:.1}%)", id, current_mb, max_mb, percentage);
+                        log::info!(
+                            "Source {}: buffer {:.2}/{:.2} MB ({:.1}%), {} retries, {}, {}",
+                            id,
+                            current_mb,
+                            max_mb,
+                            percentage,
+                            metrics.retries,
+                            rate_label(metrics.estimated_rate),
+                            leftovers_label(&metrics.leftovers)
+                        );
                     }
                     None => {
-                        log::info!("Source {}: no buffer", id);
+                        log::info!(
+                            "Source {}: no buffer, {} retries, {}, {}",
+                            id,
+                            metrics.retries,
+                            rate_label(metrics.estimated_rate),
+                            leftovers_label(&metrics.leftovers)
+                        );
                     }
                 }
+                if !metrics.publishers.is_empty() {
+                    log::info!("Source {}: {}", id, publishers_label(&metrics.publishers));
+                }
             }
         }
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{load_config, PoolKind};
+    use crate::ledger::Spans;
+    use crate::sources::SourceMetrics;
+    use async_trait::async_trait;
+    use std::sync::atomic::AtomicUsize;
+
+    /// Answers up to `limit` bytes of `byte` a read, or fails while
+    /// `failing`, keeping what it is given back and what it was asked for.
+    /// Buffered mocks report `limit` bytes held, rated ones a benchmarked
+    /// rate.
+    struct Mock {
+        byte: u8,
+        limit: usize,
+        buffered: bool,
+        rate: Option<f64>,
+        failing: AtomicBool,
+        returned: std::sync::Mutex<Vec<u8>>,
+        asked: std::sync::Mutex<Vec<usize>>,
+    }
+
+    impl Mock {
+        fn new(byte: u8, limit: usize) -> Arc<Self> {
+            let (returned, asked) = (std::sync::Mutex::new(Vec::new()), std::sync::Mutex::new(Vec::new()));
+            Arc::new(Self { byte, limit, buffered: false, rate: None, failing: AtomicBool::new(false), returned, asked })
+        }
+
+        fn buffered(byte: u8, held: usize) -> Arc<Self> {
+            let mut mock = Arc::into_inner(Self::new(byte, held)).unwrap();
+            mock.buffered = true;
+            Arc::new(mock)
+        }
+
+        fn rated(byte: u8, rate: f64) -> Arc<Self> {
+            let mut mock = Arc::into_inner(Self::new(byte, usize::MAX)).unwrap();
+            mock.rate = Some(rate);
+            Arc::new(mock)
+        }
+
+        fn failing(byte: u8) -> Arc<Self> {
+            let mock = Self::new(byte, usize::MAX);
+            mock.failing.store(true, Ordering::SeqCst);
+            mock
+        }
+
+        fn returned(&self) -> usize {
+            self.returned.lock().unwrap().len()
+        }
+
+        fn asked(&self) -> Vec<usize> {
+            std::mem::take(&mut *self.asked.lock().unwrap())
+        }
+    }
+
+    #[async_trait]
+    impl EntropySource for Mock {
+        async fn read_traced(&self, num_bytes: usize, _timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+            self.asked.lock().unwrap().push(num_bytes);
+            if self.failing.load(Ordering::SeqCst) {
+                return Err(Error::OsError(5));
+            }
+            Ok((vec![self.byte; num_bytes.min(self.limit)], Spans::default()))
+        }
+        async fn return_leftover(&self, leftover: Vec<u8>, _spans: Spans) {
+            self.returned.lock().unwrap().extend(leftover);
+        }
+        async fn get_buffer_status(&self) -> (String, Option<(usize, usize)>) {
+            ("mock".to_string(), self.buffered.then_some((self.limit, 1024)))
+        }
+        fn metrics(&self) -> SourceMetrics {
+            SourceMetrics { estimated_rate: self.rate, ..SourceMetrics::default() }
+        }
+        async fn benchmark(&self, _budget: Duration) -> Option<f64> {
+            self.rate
+        }
+    }
+
+    /// `toml` loaded as the service's config file.
+    fn config(toml: &str) -> FlattenedConfig {
+        static CONFIGS: AtomicUsize = AtomicUsize::new(0);
+        let n = CONFIGS.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("trng-aggregator-{}-{}.toml", std::process::id(), n));
+        std::fs::write(&path, toml).unwrap();
+        let cfg = load_config(path.to_str().unwrap()).unwrap();
+        let _ = std::fs::remove_file(&path);
+        cfg
+    }
+
+    /// An aggregator combining by `combine` over `sources`, each given as its
+    /// id, its common settings in TOML and the source.
+    async fn aggregator(combine: &str, sources: Vec<(&str, &str, Arc<Mock>)>) -> Aggregator {
+        let cfg = config(&format!("[sources]\ncombine = \"{}\"\nbenchmark_ms = 0\n", combine));
+        let mut agg = Aggregator::from_config(cfg).await.unwrap();
+        let mut slots = Slots {
+            sources: Vec::new(),
+            standby_links: Vec::new(),
+            #[cfg(feature = "chaos")]
+            chaos: None,
+        };
+        for (id, common, source) in sources {
+            slots.add("mock", id.to_string(), &toml::from_str(common).unwrap(), source).unwrap();
+        }
+        agg.pairs = standby_pairs(&slots.sources, &slots.standby_links).unwrap();
+        agg.sources = slots.sources;
+        agg
+    }
+
+    #[test]
+    fn test_draw_ratios() {
+        let ratio = |entropy_per_byte| Profile::new("mock", None, None, Some(entropy_per_byte), None).draw_ratio();
+        assert_eq!([ratio(8.0), ratio(4.0), ratio(3.0), ratio(2.0), ratio(MIN_ENTROPY_PER_BYTE)], [1, 2, 3, 4, 64]);
+        // Unassessed sources draw by their credit
+        assert_eq!(Profile::new("mock", None, Some(0.5), None, None).draw_ratio(), 2);
+
+        // A trailing partial chunk is not folded in
+        let mut out = [0x0f; 2];
+        xor_folded(&mut out, &[0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0xff], 4);
+        assert_eq!(out, [0x00, 0xff]);
+    }
+
+    #[tokio::test]
+    async fn test_weighted_xor_credits_the_weakest() {
+        let strong = Mock::new(0x5a, usize::MAX);
+        let weak = Mock::new(0x01, 40);
+        let agg = aggregator("weighted_xor", vec![
+            ("strong", "entropy_per_byte = 8.0", strong.clone()),
+            ("weak", "entropy_per_byte = 2.0", weak.clone()),
+        ])
+        .await;
+        assert_eq!((agg.draw_ratio(0), agg.draw_ratio(1)), (1, 4));
+
+        // The weak source is asked for 64 bytes and its 40 cover 10 of the
+        // 16; its 0x01s fold away in fours
+        let (bytes, contributors) = agg.read_combined(16, 100, Purpose::Request).await.unwrap();
+        assert_eq!(contributors, [0, 1]);
+        assert_eq!(bytes, [0x5a; 10]);
+        assert_eq!((strong.returned(), weak.returned()), (6, 0));
+
+        // Plain XOR takes them byte for byte
+        let (bytes, _) = combining(Some(CombineMode::Xor), agg.read_combined(16, 100, Purpose::Request)).await.unwrap();
+        assert_eq!(bytes, [0x5b; 16]);
+    }
+
+    #[tokio::test]
+    async fn test_optional_source_errors_leave_it_out() {
+        let agg = aggregator("xor", vec![("os", "", Mock::new(0x5a, usize::MAX)), ("qrng", "required = false", Mock::failing(0x01))]).await;
+        let (bytes, contributors) = agg.read_combined(16, 100, Purpose::Request).await.unwrap();
+        assert_eq!((bytes, contributors), (vec![0x5a; 16], vec![0]));
+
+        let agg = aggregator("xor", vec![("os", "", Mock::new(0x5a, usize::MAX)), ("qrng", "required = true", Mock::failing(0x01))]).await;
+        assert_eq!(agg.read_combined(16, 100, Purpose::Request).await, Err(Error::OsError(5)));
+
+        // With nothing but optional sources, their error
+        let agg = aggregator("xor", vec![("qrng", "required = false", Mock::failing(0x01))]).await;
+        assert_eq!(agg.read_combined(16, 100, Purpose::Request).await, Err(Error::OsError(5)));
+    }
+
+    #[tokio::test]
+    async fn test_min_sources_refuses_short_answers() {
+        let (full, short) = (Mock::new(0x5a, usize::MAX), Mock::new(0x01, 4));
+        let agg = aggregator("xor", vec![("os", "", full.clone()), ("qrng", "", short.clone())]).await;
+        // Without min_sources the short answer truncates the XOR
+        assert_eq!(agg.read_combined(16, 100, Purpose::Request).await.unwrap().0, [0x5b; 4]);
+        assert_eq!(full.returned(), 12);
+
+        // With it, nothing is served and all that was read goes back
+        let read = requiring(Some(2), agg.read_combined(16, 100, Purpose::Request)).await;
+        assert_eq!(read, Err(Error::TooFewSources));
+        assert_eq!((full.returned(), short.returned()), (12 + 16, 4));
+
+        // One timely source is enough for min_sources = 1
+        let (bytes, contributors) = requiring(Some(1), agg.read_combined(16, 100, Purpose::Request)).await.unwrap();
+        assert_eq!((bytes, contributors), (vec![0x5a; 16], vec![0]));
+        assert_eq!(short.returned(), 8);
+    }
+
+    #[tokio::test]
+    async fn test_startup_policies_for_an_unopenable_file() {
+        let path = std::env::temp_dir().join(format!("trng-startup-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let cfg = |startup: &str| {
+            config(&format!(
+                "[sources]\nbenchmark_ms = 0\nstartup = \"{}\"\nstartup_timeout_ms = 100\n\n[[sources.file]]\nid = \"qrng\"\nenabled = true\npath = \"{}\"\nretry = {{ max_attempts = 1, initial_backoff_ms = 10 }}\n",
+                startup,
+                path.display()
+            ))
+        };
+        assert_eq!(Aggregator::from_config(cfg("fail_fast")).await.err(), Some(Error::OsError(2)));
+        assert_eq!(Aggregator::from_config(cfg("wait_for_source")).await.err(), Some(Error::SourcesUnavailable));
+
+        // Degraded, the source is skipped until its file turns up
+        let agg = Aggregator::from_config(cfg("start_degraded")).await.unwrap();
+        assert!(!agg.sources[0].source.is_available());
+        assert_eq!(agg.read_bytes(16, 100).await, Err(Error::SourcesUnavailable));
+        std::fs::write(&path, [0x5a; 64]).unwrap();
+        for _ in 0..100 {
+            if agg.sources[0].source.is_available() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(agg.read_bytes(16, 100).await.unwrap(), [0x5a; 16]);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_not_ready_until_buffers_fill() {
+        let (low, full) = (Mock::buffered(0x5a, 100), Mock::buffered(0x01, 600));
+        let mut agg = aggregator("xor", vec![("low", "", low.clone()), ("full", "", full.clone())]).await;
+        agg.readiness = Some(toml::from_str("min_fill_percent = 50\ntimeout_ms = 300").unwrap());
+        agg.ready.store(false, Ordering::SeqCst);
+        assert_eq!(agg.read_bytes(16, 100).await, Err(Error::NotReady));
+        assert!(low.asked().is_empty());
+        assert_eq!(agg.unready_sources(50.0).await, ["low"]);
+
+        // Still held back below the fill level, until the readiness timeout
+        assert!(tokio::time::timeout(Duration::from_millis(150), agg.wait_until_ready()).await.is_err());
+        assert_eq!(agg.read_bytes(16, 100).await, Err(Error::NotReady));
+        agg.wait_until_ready().await;
+        assert_eq!(agg.read_bytes(16, 100).await.unwrap(), [0x5b; 16]);
+    }
+
+    #[tokio::test]
+    async fn test_infeasible_requests_warned_about() {
+        let agg = aggregator("xor", vec![("slow", "", Mock::rated(0x5a, 100.0)), ("os", "", Mock::new(0x01, usize::MAX))]).await;
+        assert_eq!(agg.run_benchmark().await, HashMap::from([("slow".to_string(), 100.0)]));
+        let warned = || *agg.last_infeasible_warning.lock().unwrap();
+
+        // 100 B/s cover 16 bytes within a second, but not 1000
+        agg.warn_if_infeasible(16, 1000).await;
+        assert!(warned().is_none());
+        agg.warn_if_infeasible(1000, 1000).await;
+        let first = warned().unwrap();
+
+        // At most one warning per INFEASIBLE_WARNING_INTERVAL
+        agg.warn_if_infeasible(1000, 1000).await;
+        assert_eq!(warned(), Some(first));
+    }
+
+    #[tokio::test]
+    async fn test_large_requests_read_in_slices() {
+        let os = Mock::new(0x5a, usize::MAX);
+        let agg = aggregator("xor", vec![("os", "", os.clone())]).await;
+        let bytes = agg.read_bytes(2 * REQUEST_SLICE + 10, 1000).await.unwrap();
+        assert_eq!(bytes.len(), 2 * REQUEST_SLICE + 10);
+        assert_eq!(os.asked()[..3], [REQUEST_SLICE, REQUEST_SLICE, 10]);
+
+        // A short slice ends the answer
+        let short = Mock::new(0x5a, REQUEST_SLICE / 2);
+        let agg = aggregator("xor", vec![("os", "", short.clone())]).await;
+        assert_eq!(agg.read_bytes(2 * REQUEST_SLICE, 1000).await.unwrap().len(), REQUEST_SLICE / 2);
+        assert_eq!(short.asked(), [REQUEST_SLICE]);
+    }
+
+    #[tokio::test]
+    async fn test_read_bytes_exact_reads_until_filled() {
+        let short = Mock::new(0x5a, 6);
+        let agg = aggregator("xor", vec![("qrng", "", short.clone())]).await;
+        assert_eq!(agg.read_bytes_exact(16, 1000).await.unwrap(), [0x5a; 16]);
+        // The padding DRBG is seeded once the answer is full
+        assert_eq!(short.asked(), [16, 10, 4, SEED_LEN]);
+
+        // Nothing but a timeout when the sources cannot keep up
+        let slow = Mock::new(0x5a, 1);
+        let agg = aggregator("xor", vec![("qrng", "", slow.clone())]).await;
+        let started = Instant::now();
+        assert_eq!(agg.read_bytes_exact(16, 50).await, Err(Error::Timeout));
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert!(slow.asked().len() < 16);
+    }
+
+    #[tokio::test]
+    async fn test_read_available_serves_what_is_buffered() {
+        let (os, qrng) = (Mock::buffered(0x5a, 40), Mock::buffered(0x01, 100));
+        let agg = aggregator("weighted_xor", vec![("os", "", os.clone()), ("qrng", "entropy_per_byte = 4.0", qrng.clone())]).await;
+        // The 0x01s fold away in pairs (later reads seed the padding DRBG)
+        assert_eq!(agg.read_available(16).await.unwrap(), [0x5a; 16]);
+        assert_eq!((os.asked()[0], qrng.asked()[0]), (16, 32));
+
+        // At most the share every source holds, without waiting for more
+        assert_eq!(agg.read_available(1024).await.unwrap(), [0x5a; 40]);
+        assert_eq!(qrng.asked()[0], 80);
+
+        // An unbuffered source holds nothing to serve at once
+        let (os, direct) = (Mock::buffered(0x5a, 40), Mock::new(0x01, usize::MAX));
+        let agg = aggregator("xor", vec![("os", "", os.clone()), ("qrng", "", direct.clone())]).await;
+        assert_eq!(agg.read_available(16).await.unwrap(), Vec::<u8>::new());
+        assert!(os.asked().is_empty() && direct.asked().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_entropy_estimate() {
+        // 80 of qrng's 100 bytes go with os's 40 for 320 + 320 bits, of
+        // which the 40 bytes combined pass on 320; so do plain XOR's
+        let (os, qrng) = (Mock::buffered(0x5a, 40), Mock::buffered(0x01, 100));
+        let sources = || vec![("os", "", os.clone()), ("qrng", "entropy_per_byte = 4.0", qrng.clone())];
+        let mut agg = aggregator("weighted_xor", sources()).await;
+        assert_eq!(agg.entropy_estimate().await, 320);
+        assert_eq!(aggregator("xor", sources()).await.entropy_estimate().await, 320);
+
+        // An unbuffered source leaves nothing to combine
+        let direct = aggregator("xor", vec![("os", "", os.clone()), ("qrng", "", Mock::new(0x01, usize::MAX))]).await;
+        assert_eq!(direct.entropy_estimate().await, 0);
+
+        // A sponge takes all of it, plus its own credit
+        let sponge = Pool::new(PoolKind::Keccak, 2).unwrap();
+        if let Pool::Sponge(sponge) = &sponge {
+            sponge.absorb(0, &[0x5a; 16], 8.0);
+        }
+        agg.pool = Some(Arc::new(sponge));
+        assert_eq!(agg.entropy_estimate().await, 40 * 8 + 100 * 4 + 16 * 8);
+    }
+
+    #[tokio::test]
+    async fn test_read_bytes_multi_fills_in_order() {
+        let agg = aggregator("xor", vec![("qrng", "", Mock::new(0x5a, 10))]).await;
+        let buffers = agg.read_bytes_multi(&[4, 0, 8], 100).await.unwrap();
+        assert_eq!(buffers, [vec![0x5a; 4], vec![], vec![0x5a; 6]]);
+
+        let invalid = Err(Error::InvalidOption("sizes".to_string()));
+        assert_eq!(agg.read_bytes_multi(&vec![1; MAX_BATCH_BUFFERS + 1], 100).await, invalid);
+        assert_eq!(agg.read_bytes_multi(&[usize::MAX, 1], 100).await, invalid);
+    }
+
+    #[tokio::test]
+    async fn test_read_bytes_into_writes_in_place() {
+        let agg = aggregator("xor", vec![("qrng", "", Mock::new(0x5a, usize::MAX))]).await;
+        let path = std::env::temp_dir().join(format!("trng-into-{}", std::process::id()));
+        std::fs::write(&path, [0; 32]).unwrap();
+        let target = || std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        assert_eq!(agg.read_bytes_into(target(), 8, 16, 100).await, Ok(16));
+        let written = std::fs::read(&path).unwrap();
+        assert_eq!((&written[..8], &written[8..24], &written[24..]), (&[0; 8][..], &[0x5a; 16][..], &[0; 8][..]));
+
+        // The region is never grown, and only regular files are written
+        assert_eq!(agg.read_bytes_into(target(), 24, 16, 100).await, Err(Error::InvalidOption("len".to_string())));
+        assert_eq!(agg.read_bytes_into(target(), u64::MAX, 1, 100).await, Err(Error::InvalidOption("len".to_string())));
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 32);
+        let dir = File::open(std::env::temp_dir()).unwrap();
+        assert_eq!(agg.read_bytes_into(dir, 0, 1, 100).await, Err(Error::InvalidOption("fd".to_string())));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_requests_refused_up_front() {
+        let mut agg = aggregator("xor", vec![("os", "", Mock::new(0x5a, usize::MAX))]).await;
+        agg.max_request_bytes = 1024;
+        // Refused before memory is reserved or a buffer allocated, so even
+        // usize::MAX bytes are answered at once
+        assert_eq!(agg.read_bytes(1025, 100).await, Err(Error::RequestTooLarge));
+        assert_eq!(agg.read_bytes(usize::MAX, 100).await, Err(Error::RequestTooLarge));
+        assert_eq!(agg.read_bytes_exact(usize::MAX, 100).await, Err(Error::RequestTooLarge));
+        assert_eq!(agg.read_bytes_multi(&[1024, 1], 100).await, Err(Error::RequestTooLarge));
+        assert_eq!(agg.memory.stats().1, 0);
+
+        assert_eq!(agg.read_bytes(1024, 100).await.unwrap().len(), 1024);
+        assert!(agg.memory.stats().1 > 0);
+    }
+
+    #[tokio::test]
+    async fn test_standby_promoted_when_primary_breaks() {
+        let (primary, standby) = (Mock::failing(0x01), Mock::new(0x5a, usize::MAX));
+        let agg = aggregator("xor", vec![
+            ("main", "breaker = { failure_threshold = 1, open_ms = 60000 }", primary.clone()),
+            ("spare", "standby_for = \"main\"", standby.clone()),
+        ])
+        .await;
+        let mut events = agg.event_sender().subscribe();
+        assert!(agg.is_idle(1));
+
+        // The failure opens the primary's breaker; the next read fails over
+        assert_eq!(agg.read_combined(16, 100, Purpose::Request).await, Err(Error::OsError(5)));
+        let (bytes, contributors) = agg.read_combined(16, 100, Purpose::Request).await.unwrap();
+        assert_eq!((bytes, contributors), (vec![0x5a; 16], vec![1]));
+        let promoted = std::iter::from_fn(|| events.try_recv().ok()).find_map(|e| match e {
+            ServiceEvent::SourcePromoted { source_id, replaced, reason } => Some((source_id, replaced, reason)),
+            _ => None,
+        });
+        assert_eq!(promoted, Some(("spare".to_string(), "main".to_string(), "quarantined".to_string())));
+        assert!(agg.is_idle(0));
+
+        // A quarantined primary is not promoted back, nor is an unknown or unpaired source
+        assert_eq!(agg.promote("main"), Err(Error::SourcesUnavailable));
+        assert_eq!(agg.promote("nope"), Err(Error::InvalidOption("source_id".to_string())));
+        assert_eq!(agg.promote("spare"), Ok(()));
+        let agg = aggregator("xor", vec![("os", "", Mock::new(0x5a, usize::MAX))]).await;
+        assert_eq!(agg.promote("os"), Err(Error::InvalidOption("source_id".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_buffer_fill_percent() {
+        let agg = aggregator("xor", vec![("os", "", Mock::new(0x5a, usize::MAX))]).await;
+        assert_eq!(agg.buffer_fill_percent().await, 0);
+
+        // Buffered sources count together, unbuffered ones not at all
+        let agg = aggregator("xor", vec![
+            ("low", "", Mock::buffered(0x5a, 256)),
+            ("full", "", Mock::buffered(0x01, 1024)),
+            ("os", "", Mock::new(0x02, usize::MAX)),
+        ])
+        .await;
+        assert_eq!(agg.buffer_fill_percent().await, 62);
+
+        // Never above 100
+        let agg = aggregator("xor", vec![("over", "", Mock::buffered(0x5a, 4096))]).await;
+        assert_eq!(agg.buffer_fill_percent().await, 100);
+    }
+
+    #[tokio::test]
+    async fn test_source_status() {
+        let agg = aggregator("xor", vec![("qrng", "", Mock::buffered(0x5a, 256)), ("os", "", Mock::new(0x01, usize::MAX))]).await;
+        assert_eq!(agg.source_status().await, [
+            ("qrng".to_string(), "mock".to_string(), 256, 1024, true),
+            ("os".to_string(), "mock".to_string(), 0, 0, true),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_source_failed_sent_once_per_outage() {
+        let qrng = Mock::failing(0x01);
+        let agg = aggregator("xor", vec![
+            ("os", "", Mock::new(0x5a, usize::MAX)),
+            ("qrng", "required = false\nbreaker = { failure_threshold = 100 }", qrng.clone()),
+        ])
+        .await;
+        let mut events = agg.event_sender().subscribe();
+        let mut failures = || {
+            std::iter::from_fn(|| events.try_recv().ok())
+                .filter(|e| matches!(e, ServiceEvent::SourceFailed { source_id, .. } if source_id == "qrng"))
+                .count()
+        };
+
+        // Once for the first failing read, not again until one succeeds
+        for _ in 0..3 {
+            agg.read_combined(16, 100, Purpose::Request).await.unwrap();
+        }
+        assert_eq!(failures(), 1);
+        qrng.failing.store(false, Ordering::SeqCst);
+        agg.read_combined(16, 100, Purpose::Request).await.unwrap();
+        qrng.failing.store(true, Ordering::SeqCst);
+        agg.read_combined(16, 100, Purpose::Request).await.unwrap();
+        assert_eq!(failures(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_entropy_low_sent_once_per_low_buffer() {
+        let agg = aggregator("xor", vec![
+            ("low", "", Mock::buffered(0x5a, 100)),
+            ("os", "", Mock::new(0x02, usize::MAX)),
+        ])
+        .await;
+        let mut events = agg.event_sender().subscribe();
+        let (sources, sender) = (agg.sources.clone(), agg.event_sender());
+        let watch = supervisor::spawn("entropy-watch", None, move |heartbeat| Aggregator::watch_low_buffers(sources.clone(), sender.clone(), 50.0, heartbeat));
+
+        // Several checks pass, but the low source (named by its buffer
+        // status) is reported once and the unbuffered one not at all
+        tokio::time::sleep(LOW_BUFFER_INTERVAL * 3).await;
+        watch.abort();
+        let low: Vec<_> = std::iter::from_fn(|| events.try_recv().ok())
+            .filter_map(|e| match e {
+                ServiceEvent::EntropyLow { source_id, fill_percent } => Some((source_id, fill_percent)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(low, [("mock".to_string(), 100.0 * 100.0 / 1024.0)]);
+    }
+
+    #[tokio::test]
+    async fn test_combining_overrides_the_combine_mode() {
+        let agg = aggregator("xor", vec![("os", "", Mock::new(0x5a, usize::MAX)), ("qrng", "", Mock::new(0x01, usize::MAX))]).await;
+        let (xored, _) = combining(None, agg.read_combined(16, 100, Purpose::Request)).await.unwrap();
+        assert_eq!(xored, [0x5b; 16]);
+        assert_eq!(agg.provenance(Some(&[0, 1]), 16, false).conditioning, "xor");
+
+        // The endpoint's mode applies to its reads and what they report
+        let (hashed, _) = combining(Some(CombineMode::Sha3), agg.read_combined(16, 100, Purpose::Request)).await.unwrap();
+        assert_eq!(hashed.len(), 16);
+        assert_ne!(hashed, xored);
+        let conditioning = combining(Some(CombineMode::Sha3), async { agg.provenance(Some(&[0, 1]), 16, false).conditioning }).await;
+        assert_eq!(conditioning, "sha3-256");
+    }
+
+    #[tokio::test]
+    async fn test_source_kinds() {
+        let kinds = Aggregator::source_kinds();
+        assert!(kinds.contains(&"lrng") && kinds.contains(&"dbus"));
+        assert_eq!(kinds.contains(&"nats"), cfg!(feature = "nats"));
+        assert_eq!(kinds.contains(&"grpc"), cfg!(feature = "grpc"));
+
+        // Configured kinds are named once each
+        let cfg = config("[sources]\nbenchmark_ms = 0\n\n[[sources.lrng]]\nid = \"os\"\nenabled = true\n\n[[sources.lrng]]\nid = \"os2\"\nenabled = true\n");
+        let agg = Aggregator::from_config(cfg).await.unwrap();
+        assert_eq!(agg.configured_kinds(), ["lrng"]);
+        assert!(agg.configured_kinds().iter().all(|kind| kinds.contains(kind)));
+    }
+}
@@ -0,0 +1,131 @@
+use crate::config::AlertConfig;
+use crate::events::{EventSender, ServiceEvent};
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::broadcast;
+use tokio::time::{timeout, Duration};
+
+pub const SOURCE_QUARANTINED: &str = "source_quarantined";
+pub const BUFFER_EMPTY: &str = "buffer_empty";
+pub const SELF_TEST_FAILED: &str = "self_test_failed";
+pub const WATCHDOG_FAILED: &str = "watchdog_failed";
+
+const HOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Turns health events into alerts: runs the configured hook command and/or
+/// re-broadcasts them as `ServiceEvent::Alert` (emitted as a D-Bus signal).
+/// Repeats of the same alert for the same source are suppressed for
+/// `repeat_seconds`.
+pub async fn run(cfg: AlertConfig, mut events: broadcast::Receiver<ServiceEvent>, sender: EventSender) {
+    let repeat = Duration::from_secs(cfg.repeat_seconds.unwrap_or(3600));
+    let mut last_fired: HashMap<(&'static str, String), Instant> = HashMap::new();
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                log::warn!("Alert hook missed {} service events", n);
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        let Some((kind, source_id, details)) = classify(&event) else { continue };
+        if !cfg.events.is_empty() && !cfg.events.iter().any(|e| e == kind) {
+            continue;
+        }
+        let key = (kind, source_id.clone());
+        if last_fired.get(&key).is_some_and(|at| at.elapsed() < repeat) {
+            continue;
+        }
+        last_fired.insert(key, Instant::now());
+
+        log::warn!("ALERT {} for source {}: {:?}", kind, source_id, details);
+        if cfg.signal.unwrap_or(true) {
+            let _ = sender.send(ServiceEvent::Alert { kind, source_id: source_id.clone(), details: details.clone() });
+        }
+        if let Some(command) = cfg.command.clone() {
+            tokio::spawn(run_hook(command, kind, source_id, details));
+        }
+    }
+}
+
+fn classify(event: &ServiceEvent) -> Option<(&'static str, String, HashMap<String, String>)> {
+    match event {
+        ServiceEvent::BreakerStateChanged { source_id, state } if *state == "open" => {
+            let details = HashMap::from([("breaker_state".to_string(), state.to_string())]);
+            Some((SOURCE_QUARANTINED, source_id.clone(), details))
+        }
+        ServiceEvent::BufferEmpty { source_id, empty_seconds } => {
+            let details = HashMap::from([("empty_seconds".to_string(), empty_seconds.to_string())]);
+            Some((BUFFER_EMPTY, source_id.clone(), details))
+        }
+        ServiceEvent::SelfTestFailed { source_id, reason } => {
+            let details = HashMap::from([("reason".to_string(), reason.clone())]);
+            Some((SELF_TEST_FAILED, source_id.clone(), details))
+        }
+        // Not tied to one source
+        ServiceEvent::WatchdogChanged { healthy: false, reason } => {
+            let details = HashMap::from([("reason".to_string(), reason.clone())]);
+            Some((WATCHDOG_FAILED, String::new(), details))
+        }
+        _ => None,
+    }
+}
+
+/// Runs the hook with the alert in `TRNG_ALERT_*` environment variables and
+/// as a JSON document on stdin.
+async fn run_hook(command: Vec<String>, kind: &'static str, source_id: String, details: HashMap<String, String>) {
+    let Some((program, args)) = command.split_first() else { return };
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let payload = serde_json::json!({
+        "event": kind,
+        "source_id": source_id,
+        "timestamp": timestamp,
+        "details": details,
+    });
+    let child = Command::new(program)
+        .args(args)
+        .env("TRNG_ALERT_EVENT", kind)
+        .env("TRNG_ALERT_SOURCE", &source_id)
+        .stdin(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            log::error!("Failed to run alert hook {}: {}", program, e);
+            return;
+        }
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        if let Err(e) = stdin.write_all(payload.to_string().as_bytes()).await {
+            log::warn!("Alert hook {} did not read its payload: {}", program, e);
+        }
+    }
+    match timeout(HOOK_TIMEOUT, child.wait()).await {
+        Ok(Ok(status)) if status.success() => log::debug!("Alert hook {} finished", program),
+        Ok(Ok(status)) => log::error!("Alert hook {} exited with {}", program, status),
+        Ok(Err(e)) => log::error!("Alert hook {} failed: {}", program, e),
+        Err(_) => log::error!("Alert hook {} timed out after {:?}", program, HOOK_TIMEOUT),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let opened = ServiceEvent::BreakerStateChanged { source_id: "a".into(), state: "open" };
+        assert_eq!(classify(&opened).map(|a| a.0), Some(SOURCE_QUARANTINED));
+        let closed = ServiceEvent::BreakerStateChanged { source_id: "a".into(), state: "closed" };
+        assert!(classify(&closed).is_none());
+        let failed = ServiceEvent::SelfTestFailed { source_id: "b".into(), reason: "manifest".into() };
+        assert_eq!(classify(&failed).map(|a| (a.0, a.1)), Some((SELF_TEST_FAILED, "b".to_string())));
+        let wedged = ServiceEvent::WatchdogChanged { healthy: false, reason: "slow".into() };
+        assert_eq!(classify(&wedged).map(|a| a.0), Some(WATCHDOG_FAILED));
+        assert!(classify(&ServiceEvent::WatchdogChanged { healthy: true, reason: String::new() }).is_none());
+    }
+}
@@ -0,0 +1,119 @@
+use crate::config::AttestationConfig;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const DOMAIN: &[u8] = b"trng-dbus attestation v1\0";
+
+/// Shortest service key accepted, in bytes.
+const MIN_KEY_LEN: usize = 32;
+
+/// What an attestation vouches for: the reply bytes and their metadata.
+pub struct Statement<'a> {
+    pub request_id: u64,
+    pub timestamp_ms: u64,
+    pub sources: &'a [String],
+    pub secure_len: u64,
+    pub drbg_len: u64,
+    pub insecure_len: u64,
+    pub fallback_len: u64,
+    pub bytes: &'a [u8],
+}
+
+impl Statement<'_> {
+    /// Unambiguous byte encoding the HMAC is computed over (see the readme).
+    fn encode(&self, mac: &mut HmacSha256) {
+        mac.update(DOMAIN);
+        mac.update(&self.request_id.to_be_bytes());
+        mac.update(&self.timestamp_ms.to_be_bytes());
+        mac.update(&(self.sources.len() as u32).to_be_bytes());
+        for id in self.sources {
+            mac.update(&(id.len() as u32).to_be_bytes());
+            mac.update(id.as_bytes());
+        }
+        for len in [self.secure_len, self.drbg_len, self.insecure_len, self.fallback_len] {
+            mac.update(&len.to_be_bytes());
+        }
+        mac.update(&(self.bytes.len() as u64).to_be_bytes());
+        mac.update(self.bytes);
+    }
+}
+
+/// Signs `ReadBytesEx` replies with the service key.
+pub struct Attestor {
+    key: Zeroizing<Vec<u8>>,
+    key_id: String,
+}
+
+impl Attestor {
+    /// `None` without an `[attestation]` section; an error if its key cannot be used.
+    pub fn from_config(cfg: Option<&AttestationConfig>) -> io::Result<Option<Self>> {
+        let Some(cfg) = cfg else { return Ok(None) };
+        let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", cfg.key_file, what));
+        let meta = std::fs::metadata(&cfg.key_file)?;
+        if meta.permissions().mode() & 0o077 != 0 {
+            log::warn!("Attestation key {} is readable by other users", cfg.key_file);
+        }
+        let text = Zeroizing::new(std::fs::read_to_string(&cfg.key_file)?);
+        let key = Zeroizing::new(hex::decode(text.trim()).map_err(|_| invalid("not hex"))?);
+        if key.len() < MIN_KEY_LEN {
+            return Err(invalid("key must be at least 32 bytes"));
+        }
+        log::info!("Attesting ReadBytesEx replies with key {}", cfg.key_id.as_deref().unwrap_or("(unnamed)"));
+        Ok(Some(Self {
+            key,
+            key_id: cfg.key_id.clone().unwrap_or_default(),
+        }))
+    }
+
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    /// The current time in ms for the next statement.
+    pub fn timestamp_ms(&self) -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64)
+    }
+
+    pub fn sign(&self, statement: &Statement<'_>) -> Vec<u8> {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC accepts any key length");
+        statement.encode(&mut mac);
+        mac.finalize().into_bytes().to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature_covers_metadata() {
+        let path = std::env::temp_dir().join(format!("trng-attest-{}.key", std::process::id()));
+        std::fs::write(&path, "11".repeat(32)).unwrap();
+        let cfg = AttestationConfig { key_file: path.to_string_lossy().into_owned(), key_id: Some("k1".to_string()) };
+        let attestor = Attestor::from_config(Some(&cfg)).unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let sources = ["lrng".to_string(), "qrng".to_string()];
+        let statement = Statement {
+            request_id: 1,
+            timestamp_ms: 1000,
+            sources: &sources,
+            secure_len: 4,
+            drbg_len: 0,
+            insecure_len: 0,
+            fallback_len: 0,
+            bytes: b"abcd",
+        };
+        let tag = attestor.sign(&statement);
+        assert_eq!(tag.len(), 32);
+        assert_ne!(tag, attestor.sign(&Statement { sources: &sources[..1], ..statement }));
+        assert_ne!(tag, attestor.sign(&Statement { request_id: 2, ..statement }));
+        assert_ne!(tag, attestor.sign(&Statement { bytes: b"abce", ..statement }));
+    }
+}
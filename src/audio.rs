@@ -0,0 +1,200 @@
+use std::ffi::{c_char, c_int, c_long, c_uint, c_ulong, c_void, CStr, CString};
+use std::sync::OnceLock;
+use zeroize::Zeroizing;
+
+// The few alsa-lib calls a capture needs, bound by hand against
+// libasound.so.2 at runtime, so the service neither links nor needs it
+// unless an audio source is configured.
+const LIBRARY: &CStr = c"libasound.so.2";
+
+const SND_PCM_STREAM_CAPTURE: c_int = 1;
+const SND_PCM_FORMAT_S16_LE: c_int = 2;
+const SND_PCM_ACCESS_RW_INTERLEAVED: c_int = 3;
+
+/// Buffer latency asked for, in µs; what the device grants is fine.
+const LATENCY_US: c_uint = 500_000;
+
+type Open = unsafe extern "C" fn(*mut *mut c_void, *const c_char, c_int, c_int) -> c_int;
+type SetParams = unsafe extern "C" fn(*mut c_void, c_int, c_int, c_uint, c_uint, c_int, c_uint) -> c_int;
+type ReadI = unsafe extern "C" fn(*mut c_void, *mut c_void, c_ulong) -> c_long;
+type Recover = unsafe extern "C" fn(*mut c_void, c_int, c_int) -> c_int;
+type Close = unsafe extern "C" fn(*mut c_void) -> c_int;
+type StrError = unsafe extern "C" fn(c_int) -> *const c_char;
+
+struct Alsa {
+    open: Open,
+    set_params: SetParams,
+    readi: ReadI,
+    recover: Recover,
+    close: Close,
+    strerror: StrError,
+}
+
+impl Alsa {
+    /// The library, loaded on first use and never unloaded.
+    fn get() -> Result<&'static Alsa, String> {
+        static ALSA: OnceLock<Result<Alsa, String>> = OnceLock::new();
+        ALSA.get_or_init(Self::load).as_ref().map_err(Clone::clone)
+    }
+
+    fn load() -> Result<Self, String> {
+        // SAFETY: alsa-lib's constructors only set up its own state
+        let handle = unsafe { libc::dlopen(LIBRARY.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+        if handle.is_null() {
+            return Err("cannot load libasound.so.2 (is alsa-lib installed?)".to_string());
+        }
+        let symbol = |name: &CStr| {
+            let symbol = unsafe { libc::dlsym(handle, name.as_ptr()) };
+            if symbol.is_null() {
+                return Err(format!("libasound.so.2 has no {}", name.to_string_lossy()));
+            }
+            Ok(symbol)
+        };
+        // SAFETY: each symbol has the signature of alsa-lib 1.x
+        unsafe {
+            Ok(Self {
+                open: std::mem::transmute::<*mut c_void, Open>(symbol(c"snd_pcm_open")?),
+                set_params: std::mem::transmute::<*mut c_void, SetParams>(symbol(c"snd_pcm_set_params")?),
+                readi: std::mem::transmute::<*mut c_void, ReadI>(symbol(c"snd_pcm_readi")?),
+                recover: std::mem::transmute::<*mut c_void, Recover>(symbol(c"snd_pcm_recover")?),
+                close: std::mem::transmute::<*mut c_void, Close>(symbol(c"snd_pcm_close")?),
+                strerror: std::mem::transmute::<*mut c_void, StrError>(symbol(c"snd_strerror")?),
+            })
+        }
+    }
+
+    fn error(&self, what: &str, err: c_int) -> String {
+        // SAFETY: snd_strerror returns a static NUL-terminated message
+        let reason = unsafe { (self.strerror)(err) };
+        if reason.is_null() {
+            return format!("{} failed: error {}", what, err);
+        }
+        format!("{} failed: {}", what, unsafe { CStr::from_ptr(reason) }.to_string_lossy())
+    }
+}
+
+/// An open ALSA capture device delivering signed 16-bit interleaved frames.
+pub struct Capture {
+    alsa: &'static Alsa,
+    pcm: *mut c_void,
+    channels: usize,
+}
+
+// SAFETY: a PCM handle may be used from any thread, one at a time, which
+// `&mut self` ensures
+unsafe impl Send for Capture {}
+
+impl Capture {
+    /// Opens `device` (e.g. `default`, `hw:1,0` or `plughw:1,0`) at `rate`
+    /// without resampling, which would smooth away the noise.
+    pub fn open(device: &str, rate: u32, channels: u32) -> Result<Self, String> {
+        let alsa = Alsa::get()?;
+        let name = CString::new(device).map_err(|_| format!("{}: name contains NUL", device))?;
+        let mut pcm = std::ptr::null_mut();
+        // SAFETY: `pcm` is written on success only
+        let err = unsafe { (alsa.open)(&mut pcm, name.as_ptr(), SND_PCM_STREAM_CAPTURE, 0) };
+        if err < 0 {
+            return Err(alsa.error(&format!("opening {}", device), err));
+        }
+        let capture = Self { alsa, pcm, channels: channels as usize };
+        let err = unsafe { (alsa.set_params)(pcm, SND_PCM_FORMAT_S16_LE, SND_PCM_ACCESS_RW_INTERLEAVED, channels, rate, 0, LATENCY_US) };
+        if err < 0 {
+            return Err(alsa.error(&format!("setting {} to {} Hz, {} channels, S16_LE", device, rate, channels), err));
+        }
+        Ok(capture)
+    }
+
+    /// Blocks for `frames` frames; an overrun is recovered from once, as the
+    /// samples lost to it only cost time.
+    pub fn read(&mut self, frames: usize) -> Result<Zeroizing<Vec<i16>>, String> {
+        let mut samples = Zeroizing::new(vec![0i16; frames * self.channels]);
+        let mut done = 0;
+        let mut recovered = false;
+        while done < frames {
+            let at = samples[done * self.channels..].as_mut_ptr() as *mut c_void;
+            // SAFETY: `at` has room for `frames - done` frames of `channels` samples
+            let n = unsafe { (self.alsa.readi)(self.pcm, at, (frames - done) as c_ulong) };
+            if n >= 0 {
+                done += n as usize;
+                continue;
+            }
+            if recovered || unsafe { (self.alsa.recover)(self.pcm, n as c_int, 1) } < 0 {
+                return Err(self.alsa.error("snd_pcm_readi", n as c_int));
+            }
+            recovered = true;
+        }
+        Ok(samples)
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        unsafe { (self.alsa.close)(self.pcm) };
+    }
+}
+
+/// Takes the `bits` lowest bits of every sample and debiases them with von
+/// Neumann's method, each bit of a frame paired with the same bit of the
+/// same channel in the next frame: 01 gives 0, 10 gives 1, equal pairs
+/// nothing. A muted or clipped input therefore yields no output at all
+/// rather than constant bytes.
+pub struct Harvester {
+    bits: u32,
+    channels: usize,
+    /// First frame of an unfinished pair.
+    held: Option<Zeroizing<Vec<i16>>>,
+    byte: u8,
+    filled: u32,
+}
+
+impl Harvester {
+    pub fn new(bits: u32, channels: usize) -> Self {
+        Self { bits, channels: channels.max(1), held: None, byte: 0, filled: 0 }
+    }
+
+    /// Appends the whitened bytes of `samples`, interleaved frames, to `out`.
+    pub fn feed(&mut self, samples: &[i16], out: &mut Vec<u8>) {
+        for frame in samples.chunks_exact(self.channels) {
+            let Some(first) = self.held.take() else {
+                self.held = Some(Zeroizing::new(frame.to_vec()));
+                continue;
+            };
+            for (&a, &b) in first.iter().zip(frame) {
+                for bit in 0..self.bits {
+                    let (x, y) = ((a >> bit) & 1, (b >> bit) & 1);
+                    if x == y {
+                        continue;
+                    }
+                    self.byte = (self.byte << 1) | x as u8;
+                    self.filled += 1;
+                    if self.filled == 8 {
+                        out.push(self.byte);
+                        (self.byte, self.filled) = (0, 0);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_harvester() {
+        // Silence and a stuck low bit give nothing
+        let mut out = Vec::new();
+        Harvester::new(2, 1).feed(&[0i16; 4096], &mut out);
+        Harvester::new(1, 2).feed(&[7, 1].repeat(1024), &mut out);
+        assert!(out.is_empty());
+
+        // Low bits 01 then 10 on each of two channels: one bit per pair
+        let mut h = Harvester::new(1, 2);
+        h.feed(&[0, 1, 1, 0, 1, 1, 0, 1], &mut out);
+        h.feed(&[1, 0, 0, 0, 0, 1, 1, 0, 0, 0, 1, 1, 0, 1, 1, 0], &mut out);
+        // Bits of the frame pairs: 0 1 | 1 | 1 | 0 1 | 0 0, then a byte begun
+        assert_eq!(out, vec![0b0111_0100]);
+        assert!(Capture::open("trng-no-such-device", 48_000, 1).is_err());
+    }
+}
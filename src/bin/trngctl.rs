@@ -0,0 +1,21 @@
+//! `trngctl`: commands run against a running trngdbus service.
+
+// Shared with the service, which uses the rest of them
+#[allow(dead_code)]
+#[path = "../config.rs"]
+mod config;
+#[allow(dead_code)]
+#[path = "../snapshot.rs"]
+mod snapshot;
+
+const USAGE: &str = "usage: trngctl snapshot [--output PATH] [--force] [--system] [--name NAME] [--object-path PATH]";
+
+fn main() {
+    env_logger::init();
+    let mut args = std::env::args().skip(1).peekable();
+    if args.next_if_eq("snapshot").is_some() {
+        std::process::exit(snapshot::run(args));
+    }
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
+}
@@ -0,0 +1,144 @@
+use crate::config::BreakerConfig;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BreakerState {
+    /// Source is used normally.
+    Closed,
+    /// Source is skipped until `open_for` has elapsed.
+    Open,
+    /// One probe request is let through to test recovery.
+    HalfOpen,
+}
+
+impl BreakerState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BreakerState::Closed => "closed",
+            BreakerState::Open => "open",
+            BreakerState::HalfOpen => "half_open",
+        }
+    }
+}
+
+/// Circuit breaker guarding one source: after `failure_threshold`
+/// consecutive failures the source is skipped for `open_for`, then a single
+/// probe request decides whether it closes again.
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    open_for: Duration,
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    state: BreakerState,
+    failures: u32,
+    opened_at: Instant,
+    probing: bool,
+}
+
+impl CircuitBreaker {
+    pub fn new(cfg: Option<&BreakerConfig>) -> Self {
+        let cfg = cfg.cloned().unwrap_or_default();
+        Self {
+            failure_threshold: cfg.failure_threshold.unwrap_or(3).max(1),
+            open_for: Duration::from_millis(cfg.open_ms.unwrap_or(30_000)),
+            inner: Mutex::new(Inner {
+                state: BreakerState::Closed,
+                failures: 0,
+                opened_at: Instant::now(),
+                probing: false,
+            }),
+        }
+    }
+
+    pub fn state(&self) -> BreakerState {
+        self.inner.lock().unwrap().state
+    }
+
+    /// Whether the source is being skipped at `now`: open and not yet due a
+    /// probe.
+    pub fn is_quarantined(&self, now: Instant) -> bool {
+        let inner = self.inner.lock().unwrap();
+        inner.state == BreakerState::Open && now.duration_since(inner.opened_at) < self.open_for
+    }
+
+    /// Whether a request may use the source, plus the new state if this call
+    /// moved the breaker to half-open.
+    pub fn try_acquire(&self, now: Instant) -> (bool, Option<BreakerState>) {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.state {
+            BreakerState::Closed => (true, None),
+            BreakerState::Open if now.duration_since(inner.opened_at) >= self.open_for => {
+                inner.state = BreakerState::HalfOpen;
+                inner.probing = true;
+                (true, Some(BreakerState::HalfOpen))
+            }
+            BreakerState::Open => (false, None),
+            BreakerState::HalfOpen if inner.probing => (false, None),
+            BreakerState::HalfOpen => {
+                inner.probing = true;
+                (true, None)
+            }
+        }
+    }
+
+    /// Records the outcome of a request that used the source. Returns the
+    /// new state if it changed.
+    pub fn on_result(&self, ok: bool, now: Instant) -> Option<BreakerState> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.probing = false;
+        let previous = inner.state;
+        if ok {
+            inner.failures = 0;
+            inner.state = BreakerState::Closed;
+        } else {
+            inner.failures = inner.failures.saturating_add(1);
+            if previous == BreakerState::HalfOpen || inner.failures >= self.failure_threshold {
+                inner.state = BreakerState::Open;
+                inner.opened_at = now;
+            }
+        }
+        (inner.state != previous).then_some(inner.state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker::new(Some(&BreakerConfig { failure_threshold: Some(2), open_ms: Some(100) }))
+    }
+
+    #[test]
+    fn test_opens_after_threshold() {
+        let b = breaker();
+        let now = Instant::now();
+        assert_eq!(b.on_result(false, now), None);
+        assert_eq!(b.on_result(false, now), Some(BreakerState::Open));
+        assert_eq!(b.try_acquire(now), (false, None));
+        assert!(b.is_quarantined(now));
+        assert!(!b.is_quarantined(now + Duration::from_millis(150)));
+    }
+
+    #[test]
+    fn test_half_open_probe() {
+        let b = breaker();
+        let now = Instant::now();
+        b.on_result(false, now);
+        b.on_result(false, now);
+
+        let later = now + Duration::from_millis(150);
+        assert_eq!(b.try_acquire(later), (true, Some(BreakerState::HalfOpen)));
+        // Only one probe at a time
+        assert_eq!(b.try_acquire(later), (false, None));
+        assert_eq!(b.on_result(false, later), Some(BreakerState::Open));
+
+        let much_later = later + Duration::from_millis(150);
+        assert_eq!(b.try_acquire(much_later), (true, Some(BreakerState::HalfOpen)));
+        assert_eq!(b.on_result(true, much_later), Some(BreakerState::Closed));
+        assert_eq!(b.try_acquire(much_later), (true, None));
+    }
+}
@@ -0,0 +1,77 @@
+use crate::config::{LatencyBudgetConfig, DEFAULT_CONDITION_PERCENT, DEFAULT_GATHER_PERCENT};
+use std::future::Future;
+use std::time::Instant;
+
+tokio::task_local! {
+    static CONDITION_DEADLINE: Option<Instant>;
+}
+
+/// Runs `fut` with the conditioning and DRBG output it does stopping at
+/// `deadline`, if set.
+pub async fn conditioning_until<F: Future>(deadline: Option<Instant>, fut: F) -> F::Output {
+    CONDITION_DEADLINE.scope(deadline, fut).await
+}
+
+/// The end of the conditioning slice of the request being served, if it
+/// has one.
+pub fn condition_deadline() -> Option<Instant> {
+    CONDITION_DEADLINE.try_with(|d| *d).ok().flatten()
+}
+
+/// Whether the request being served has used up its conditioning slice.
+pub fn overrun() -> bool {
+    condition_deadline().is_some_and(|deadline| Instant::now() >= deadline)
+}
+
+/// `[latency_budget]`: splits what is left of a client's timeout once a
+/// request is admitted into slices for gathering from the sources,
+/// conditioning, and the reply, so that slow conditioning ends the answer
+/// short rather than make it miss the client's deadline.
+#[derive(Debug, Clone, Copy)]
+pub struct LatencyBudget {
+    gather: u8,
+    condition: u8,
+}
+
+impl LatencyBudget {
+    /// Expects `cfg` as validated by the config, summing to at most 100.
+    pub fn new(cfg: &LatencyBudgetConfig) -> Self {
+        Self {
+            gather: cfg.gather_percent.unwrap_or(DEFAULT_GATHER_PERCENT),
+            condition: cfg.condition_percent.unwrap_or(DEFAULT_CONDITION_PERCENT),
+        }
+    }
+
+    /// The timeout to read the sources with and the end of the conditioning
+    /// slice, for a request admitted at `now` that must be answered by
+    /// `deadline`.
+    pub fn split(&self, now: Instant, deadline: Instant) -> (u64, Instant) {
+        let left = deadline.saturating_duration_since(now);
+        let share = |percent: u8| left.mul_f64(f64::from(percent) / 100.0);
+        let gather = share(self.gather);
+        (gather.as_millis() as u64, now + gather + share(self.condition))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_split() {
+        let budget = LatencyBudget::new(&LatencyBudgetConfig::default());
+        let now = Instant::now();
+        let (gather_ms, condition_by) = budget.split(now, now + Duration::from_millis(1000));
+        assert_eq!(gather_ms, 800);
+        assert_eq!(condition_by, now + Duration::from_millis(950));
+
+        let cfg = LatencyBudgetConfig { gather_percent: Some(50), condition_percent: Some(50) };
+        let (gather_ms, condition_by) = LatencyBudget::new(&cfg).split(now, now + Duration::from_millis(10));
+        assert_eq!((gather_ms, condition_by), (5, now + Duration::from_millis(10)));
+
+        assert!(!overrun());
+        assert!(conditioning_until(Some(now), async { overrun() }).await);
+        assert!(!conditioning_until(None, async { overrun() }).await);
+    }
+}
@@ -0,0 +1,94 @@
+use crate::config::RetryConfig;
+use crate::retry::RetryPolicy;
+use futures::StreamExt;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::time::{sleep, Duration};
+use zbus::Connection;
+
+/// Longest wait between attempts to get back on the bus.
+const MAX_RECONNECT_BACKOFF_MS: u64 = 5000;
+
+static OUTAGES: AtomicU64 = AtomicU64::new(0);
+static OUTAGE_MS: AtomicU64 = AtomicU64::new(0);
+static RECONNECT_ATTEMPTS: AtomicU64 = AtomicU64::new(0);
+
+/// Bus outages since startup and how long they lasted in total, in ms.
+pub fn outages() -> (u64, u64) {
+    (OUTAGES.load(Ordering::Relaxed), OUTAGE_MS.load(Ordering::Relaxed))
+}
+
+/// Failed attempts to reconnect since startup.
+pub fn reconnect_attempts() -> u64 {
+    RECONNECT_ATTEMPTS.load(Ordering::Relaxed)
+}
+
+/// Counts an outage that lasted `duration`; returns the outages so far.
+pub fn record_outage(duration: Duration) -> u64 {
+    OUTAGE_MS.fetch_add(duration.as_millis() as u64, Ordering::Relaxed);
+    OUTAGES.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+fn reconnect_policy() -> RetryPolicy {
+    RetryPolicy::from(&RetryConfig {
+        max_attempts: Some(0),
+        max_backoff_ms: Some(MAX_RECONNECT_BACKOFF_MS),
+        ..RetryConfig::default()
+    })
+}
+
+/// Resolves once `connection` closes or the bus takes `name` away from it,
+/// telling which.
+pub async fn lost(connection: &Connection, name: &str) -> String {
+    let lost = match zbus::fdo::DBusProxy::new(connection).await {
+        Ok(proxy) => proxy.receive_name_lost().await,
+        Err(e) => Err(e),
+    };
+    let mut lost = match lost {
+        Ok(lost) => lost,
+        Err(e) => return format!("cannot watch the bus name: {}", e),
+    };
+    while let Some(signal) = lost.next().await {
+        if signal.args().is_ok_and(|args| args.name() == name) {
+            return format!("lost the name {}", name);
+        }
+    }
+    "connection closed".to_string()
+}
+
+/// Runs `connect` with backoff until it succeeds. The first attempt also
+/// waits, so a bus that drops every new connection is not hammered.
+pub async fn reconnect<T, F, Fut>(mut connect: F) -> T
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = zbus::Result<T>>,
+{
+    let policy = reconnect_policy();
+    sleep(policy.backoff(1)).await;
+    match policy.run("Reconnecting to the bus", &RECONNECT_ATTEMPTS, &mut connect).await {
+        Ok(value) => value,
+        Err(_) => unreachable!("reconnects are retried forever"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reconnect_retries() {
+        let before = reconnect_attempts();
+        let mut attempts = 0;
+        let value = reconnect(|| {
+            attempts += 1;
+            let res = if attempts < 3 { Err(zbus::Error::Failure("bus down".to_string())) } else { Ok(attempts) };
+            async move { res }
+        })
+        .await;
+        assert_eq!(value, 3);
+        assert!(reconnect_attempts() >= before + 2);
+        let (count, _) = outages();
+        assert_eq!(record_outage(Duration::from_millis(250)), count + 1);
+        assert!(outages().1 >= 250);
+    }
+}
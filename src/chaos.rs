@@ -0,0 +1,193 @@
+use crate::config::ChaosConfig;
+use crate::error::Error;
+use crate::ledger::Spans;
+use crate::lrng::os_fill_insecure_octets;
+use crate::sources::{EntropySource, SourceMetrics, Tuning};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Fault probabilities and the generator that decides when to inject them.
+pub struct Chaos {
+    failure: f64,
+    delay: f64,
+    delay_for: Duration,
+    short_read: f64,
+    corruption: f64,
+    /// Source ids to inject into; empty means all.
+    sources: Vec<String>,
+    /// SplitMix64 state. Not for anything but picking faults.
+    state: AtomicU64,
+}
+
+impl Chaos {
+    /// `None` without a `[chaos]` section.
+    pub fn from_config(cfg: Option<&ChaosConfig>) -> Result<Option<Arc<Self>>, String> {
+        let Some(cfg) = cfg else { return Ok(None) };
+        let probability = |name: &str, p: Option<f64>| {
+            let p = p.unwrap_or(0.0);
+            if (0.0..=1.0).contains(&p) {
+                Ok(p)
+            } else {
+                Err(format!("{} must be between 0 and 1", name))
+            }
+        };
+        let seed = match cfg.seed {
+            Some(seed) => seed,
+            None => os_fill_insecure_octets(8)
+                .ok()
+                .and_then(|b| b.try_into().ok())
+                .map(u64::from_le_bytes)
+                .unwrap_or_default(),
+        };
+        let chaos = Self {
+            failure: probability("failure_probability", cfg.failure_probability)?,
+            delay: probability("delay_probability", cfg.delay_probability)?,
+            delay_for: Duration::from_millis(cfg.delay_ms.unwrap_or(1000)),
+            short_read: probability("short_read_probability", cfg.short_read_probability)?,
+            corruption: probability("corruption_probability", cfg.corruption_probability)?,
+            sources: cfg.sources.clone(),
+            state: AtomicU64::new(seed),
+        };
+        log::warn!(
+            "CHAOS MODE: injecting faults into {} (failure {}, delay {} for {:?}, short read {}, corruption {})",
+            if chaos.sources.is_empty() { "all sources".to_string() } else { chaos.sources.join(", ") },
+            chaos.failure,
+            chaos.delay,
+            chaos.delay_for,
+            chaos.short_read,
+            chaos.corruption
+        );
+        Ok(Some(Arc::new(chaos)))
+    }
+
+    /// Wraps `source` if faults are to be injected into it.
+    pub fn wrap(self: &Arc<Self>, id: &str, source: Arc<dyn EntropySource>) -> Arc<dyn EntropySource> {
+        if !self.sources.is_empty() && !self.sources.iter().any(|s| s == id) {
+            return source;
+        }
+        Arc::new(ChaosSource { id: id.to_string(), inner: source, chaos: self.clone() })
+    }
+
+    fn next(&self) -> u64 {
+        let mut z = self.state.fetch_add(0x9E37_79B9_7F4A_7C15, Ordering::Relaxed).wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn roll(&self, probability: f64) -> bool {
+        probability > 0.0 && ((self.next() >> 11) as f64 / (1u64 << 53) as f64) < probability
+    }
+}
+
+/// A source with faults injected into its reads.
+struct ChaosSource {
+    id: String,
+    inner: Arc<dyn EntropySource>,
+    chaos: Arc<Chaos>,
+}
+
+#[async_trait]
+impl EntropySource for ChaosSource {
+    async fn read_traced(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+        if self.chaos.roll(self.chaos.failure) {
+            log::warn!("Chaos: failing read from source {}", self.id);
+            return Err(Error::OsError(libc::EIO as u32));
+        }
+        if self.chaos.roll(self.chaos.corruption) {
+            log::warn!("Chaos: reporting corrupted data from source {}", self.id);
+            return Err(Error::IntegrityFailure);
+        }
+        if self.chaos.roll(self.chaos.delay) {
+            log::warn!("Chaos: delaying read from source {} by {:?}", self.id, self.chaos.delay_for);
+            tokio::time::sleep(self.chaos.delay_for).await;
+        }
+        let (mut bytes, mut spans) = self.inner.read_traced(num_bytes, timeout_ms).await?;
+        if !bytes.is_empty() && self.chaos.roll(self.chaos.short_read) {
+            let keep = (self.chaos.next() % bytes.len() as u64) as usize;
+            log::warn!("Chaos: cutting read from source {} to {} of {} bytes", self.id, keep, bytes.len());
+            // The cut bytes were never served, so the source may keep them
+            self.inner.return_leftover(bytes.split_off(keep), spans.split_off(keep)).await;
+        }
+        Ok((bytes, spans))
+    }
+
+    async fn return_leftover(&self, leftover: Vec<u8>, spans: Spans) {
+        self.inner.return_leftover(leftover, spans).await
+    }
+
+    async fn get_buffer_status(&self) -> (String, Option<(usize, usize)>) {
+        self.inner.get_buffer_status().await
+    }
+
+    fn metrics(&self) -> SourceMetrics {
+        self.inner.metrics()
+    }
+
+    async fn benchmark(&self, budget: Duration) -> Option<f64> {
+        self.inner.benchmark(budget).await
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn is_healthy(&self) -> bool {
+        self.inner.is_healthy()
+    }
+
+    async fn resize_buffer(&self, bytes: usize) -> Result<usize, Error> {
+        self.inner.resize_buffer(bytes).await
+    }
+
+    fn tuning(&self) -> Option<&Tuning> {
+        self.inner.tuning()
+    }
+
+    async fn set_paused(&self, paused: bool, drain: bool) {
+        self.inner.set_paused(paused, drain).await
+    }
+
+    fn info(&self) -> HashMap<String, String> {
+        self.inner.info()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Zeros;
+
+    #[async_trait]
+    impl EntropySource for Zeros {
+        async fn read_traced(&self, num_bytes: usize, _timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+            Ok((vec![0; num_bytes], Spans::default()))
+        }
+        async fn return_leftover(&self, _leftover: Vec<u8>, _spans: Spans) {}
+        async fn get_buffer_status(&self) -> (String, Option<(usize, usize)>) {
+            ("zeros".to_string(), None)
+        }
+    }
+
+    fn chaos(cfg: ChaosConfig) -> Arc<Chaos> {
+        Chaos::from_config(Some(&ChaosConfig { seed: Some(7), ..cfg })).unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_injects_configured_faults() {
+        let failing = chaos(ChaosConfig { failure_probability: Some(1.0), ..Default::default() }).wrap("zeros", Arc::new(Zeros));
+        assert_eq!(failing.read_bytes(16, 0).await, Err(Error::OsError(libc::EIO as u32)));
+
+        let short = chaos(ChaosConfig { short_read_probability: Some(1.0), ..Default::default() }).wrap("zeros", Arc::new(Zeros));
+        assert!(short.read_bytes(16, 0).await.unwrap().len() < 16);
+
+        let other = chaos(ChaosConfig { failure_probability: Some(1.0), sources: vec!["qrng".to_string()], ..Default::default() });
+        assert_eq!(other.wrap("zeros", Arc::new(Zeros)).read_bytes(16, 0).await.unwrap().len(), 16);
+
+        assert!(Chaos::from_config(Some(&ChaosConfig { delay_probability: Some(1.5), ..Default::default() })).is_err());
+    }
+}
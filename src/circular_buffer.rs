@@ -1,3 +1,44 @@
+use crate::clock::BootInstant;
+use crate::ledger::{Positions, Span, Spans};
+use std::collections::VecDeque;
+use std::time::Duration;
+use zeroize::Zeroize;
+
+/// Bytes added within this long of the newest run share its timestamp,
+/// the earlier one, so they expire no later than they should.
+const AGE_GRANULARITY: Duration = Duration::from_millis(100);
+
+/// Filler for consumed regions in debug builds.
+#[cfg(debug_assertions)]
+const POISON: u8 = 0xA5;
+
+/// A run of `POISON` this long is taken as already-served bytes coming back.
+/// Random data contains one with probability 2^-128, but a source may well
+/// answer with one (a file of constant bytes), so it is only logged.
+#[cfg(debug_assertions)]
+const POISON_RUN: usize = 16;
+
+/// Overwrites bytes that have been served, so that handing them to a buffer
+/// again trips a warning in `extend`. No-op in release builds.
+pub fn poison(data: &mut [u8]) {
+    #[cfg(debug_assertions)]
+    data.fill(POISON);
+    #[cfg(not(debug_assertions))]
+    let _ = data;
+}
+
+#[cfg(debug_assertions)]
+fn contains_poison(data: &[u8]) -> bool {
+    let mut run = 0;
+    for &b in data {
+        run = if b == POISON { run + 1 } else { 0 };
+        if run >= POISON_RUN {
+            return true;
+        }
+    }
+    false
+}
+
 /// High-performance circular buffer for bytes
 pub struct CircularBuffer {
     buffer: Vec<u8>,
@@ -5,6 +46,24 @@ pub struct CircularBuffer {
     write_pos: usize,
     len: usize,
     capacity: usize,
+    /// Runs of buffered bytes, oldest first.
+    runs: VecDeque<Run>,
+    /// Numbers the bytes added, see `ledger.rs`.
+    positions: Positions,
+    /// Bytes older than this are discarded by `expire` instead of served;
+    /// time suspended counts.
+    max_age: Option<Duration>,
+    /// Debug builds track which slots hold unconsumed bytes
+    #[cfg(debug_assertions)]
+    live: Vec<bool>,
+}
+
+/// Bytes added together: when, and where they start in the read stream.
+#[derive(Debug, Clone, Copy)]
+struct Run {
+    at: BootInstant,
+    start: u64,
+    len: usize,
 }
 
 impl CircularBuffer {
@@ -15,8 +74,20 @@ impl CircularBuffer {
             write_pos: 0,
             len: 0,
             capacity,
+            runs: VecDeque::new(),
+            positions: Positions::default(),
+            max_age: None,
+            #[cfg(debug_assertions)]
+            live: vec![false; capacity],
         }
     }
+
+    /// A buffer whose bytes expire `max_age` after they were added.
+    pub fn with_max_age(capacity: usize, max_age: Option<Duration>) -> Self {
+        let mut buffer = Self::new(capacity);
+        buffer.max_age = max_age;
+        buffer
+    }
     
     pub fn len(&self) -> usize {
         self.len
@@ -25,14 +96,64 @@ impl CircularBuffer {
     pub fn available_space(&self) -> usize {
         self.capacity - self.len
     }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The counter `extend` numbers bytes with, for bytes a source serves
+    /// without buffering them.
+    pub fn positions(&self) -> Positions {
+        self.positions.clone()
+    }
+
+    /// Changes the capacity, keeping the oldest bytes that fit (and their
+    /// ages). Returns how many bytes had to be discarded (they are zeroized).
+    pub fn resize(&mut self, capacity: usize) -> usize {
+        let runs = std::mem::take(&mut self.runs);
+        let mut kept = self.take(self.len);
+        let discarded = kept.len().saturating_sub(capacity);
+        let mut resized = CircularBuffer::with_max_age(capacity, self.max_age);
+        resized.positions = self.positions.clone();
+        let mut offset = 0;
+        for run in runs {
+            let fits = run.len.min(resized.available_space());
+            resized.insert(&kept[offset..offset + fits], run.at, run.start);
+            offset += run.len;
+        }
+        kept.zeroize();
+        // The old storage is zeroized when dropped
+        *self = resized;
+        discarded
+    }
+
+    /// Zeroizes and drops the bytes older than the buffer's `max_age`.
+    /// Returns how many were discarded.
+    pub fn expire(&mut self) -> usize {
+        let Some(max_age) = self.max_age else { return 0 };
+        let mut expired = 0;
+        while let Some(&run) = self.runs.front() {
+            if run.at.elapsed() <= max_age {
+                break;
+            }
+            self.take(run.len).zeroize();
+            expired += run.len;
+        }
+        expired
+    }
     
     /// Take up to `count` bytes from the buffer
     pub fn take(&mut self, count: usize) -> Vec<u8> {
+        self.take_traced(count).0
+    }
+
+    /// `take`, also returning where the bytes sit in the read stream.
+    pub fn take_traced(&mut self, count: usize) -> (Vec<u8>, Spans) {
         let to_take = count.min(self.len);
         let mut result = Vec::with_capacity(to_take);
         
         if to_take == 0 {
-            return result;
+            return (result, Spans::default());
         }
         
         // Handle wrap-around case
@@ -47,20 +168,44 @@ impl CircularBuffer {
             result.extend_from_slice(&self.buffer[self.read_pos..]);
             result.extend_from_slice(&self.buffer[..second_chunk]);
         }
+        #[cfg(debug_assertions)]
+        self.debug_consume(to_take);
+        let spans = self.consume_runs(to_take);
         
         self.read_pos = (self.read_pos + to_take) % self.capacity;
         self.len -= to_take;
         
-        result
+        (result, spans)
     }
     
-    /// Add bytes to the buffer
-    pub fn extend(&mut self, data: &[u8]) {
-        let to_add = data.len().min(self.available_space());
+    /// Add bytes to the buffer. Returns the read-stream positions given to
+    /// the bytes that fit; the rest are not numbered.
+    pub fn extend(&mut self, data: &[u8]) -> Span {
+        self.extend_at(data, BootInstant::now())
+    }
+
+    fn extend_at(&mut self, data: &[u8], at: BootInstant) -> Span {
+        let span = self.positions.claim(data.len().min(self.available_space()));
+        self.insert(&data[..span.len as usize], at, span.start);
+        span
+    }
+
+    /// Appends `data` (which must fit) as read at `at`, starting at `start`
+    /// in the read stream.
+    fn insert(&mut self, data: &[u8], at: BootInstant, start: u64) {
+        let to_add = data.len();
         
         if to_add == 0 {
             return;
         }
+        match self.runs.back_mut() {
+            Some(last) if at >= last.at
+                && at.saturating_duration_since(last.at) < AGE_GRANULARITY
+                && last.start + last.len as u64 == start => last.len += to_add,
+            _ => self.runs.push_back(Run { at, start, len: to_add }),
+        }
+        #[cfg(debug_assertions)]
+        self.debug_fill(&data[..to_add]);
         
         // Handle wrap-around case
         if self.write_pos + to_add <= self.capacity {
@@ -79,20 +224,73 @@ impl CircularBuffer {
         self.len += to_add;
     }
     
-    /// Add bytes from a Vec (more efficient than extend for Vec<u8>)
-    pub fn extend_from_vec(&mut self, mut data: Vec<u8>) {
-        let to_add = data.len().min(self.available_space());
-        
-        if to_add == 0 {
-            return;
+    /// Add bytes from a Vec (more efficient than extend for Vec<u8>).
+    /// The Vec is zeroized afterwards, including bytes that did not fit.
+    pub fn extend_from_vec(&mut self, mut data: Vec<u8>) -> Span {
+        let span = self.extend(&data);
+        data.zeroize();
+        span
+    }
+
+    /// Zeroize the whole backing storage and empty the buffer
+    pub fn wipe(&mut self) {
+        self.buffer.zeroize();
+        self.buffer.resize(self.capacity, 0);
+        self.read_pos = 0;
+        self.write_pos = 0;
+        self.len = 0;
+        self.runs.clear();
+        #[cfg(debug_assertions)]
+        self.live.fill(false);
+    }
+
+    fn consume_runs(&mut self, mut count: usize) -> Spans {
+        let mut spans = Spans::default();
+        while count > 0 {
+            let Some(front) = self.runs.front_mut() else { break };
+            if front.len > count {
+                spans.push(Span { start: front.start, len: count as u64 });
+                front.start += count as u64;
+                front.len -= count;
+                break;
+            }
+            spans.push(Span { start: front.start, len: front.len as u64 });
+            count -= front.len;
+            self.runs.pop_front();
         }
-        
-        // Truncate if we can't fit everything
-        if to_add < data.len() {
-            data.truncate(to_add);
+        spans
+    }
+}
+
+#[cfg(debug_assertions)]
+impl CircularBuffer {
+    /// Checks that the `count` slots at `read_pos` are live, then poisons them.
+    fn debug_consume(&mut self, count: usize) {
+        for k in 0..count {
+            let pos = (self.read_pos + k) % self.capacity;
+            assert!(self.live[pos], "circular buffer served consumed slot {}", pos);
+            self.live[pos] = false;
+            self.buffer[pos] = POISON;
         }
-        
-        self.extend(&data);
+    }
+
+    /// Warns if `data` looks like served entropy, and checks that it only
+    /// overwrites consumed slots.
+    fn debug_fill(&mut self, data: &[u8]) {
+        if contains_poison(data) {
+            log::warn!("{} bytes returned to a circular buffer hold a run of {} poison bytes, as served bytes do", data.len(), POISON_RUN);
+        }
+        for k in 0..data.len() {
+            let pos = (self.write_pos + k) % self.capacity;
+            assert!(!self.live[pos], "circular buffer overwrote unconsumed slot {}", pos);
+            self.live[pos] = true;
+        }
+    }
+}
+
+impl Drop for CircularBuffer {
+    fn drop(&mut self) {
+        self.buffer.zeroize();
     }
 }
 
@@ -141,4 +339,85 @@ mod tests {
         assert_eq!(data, b"345ab");
         assert_eq!(buf.len(), 0);
     }
+
+    #[test]
+    fn test_wipe() {
+        let mut buf = CircularBuffer::new(5);
+        buf.extend(b"12345");
+        buf.take(2);
+        buf.wipe();
+        assert_eq!(buf.len(), 0);
+        assert_eq!(buf.available_space(), 5);
+        assert!(buf.buffer.iter().all(|&b| b == 0));
+
+        buf.extend(b"ab");
+        assert_eq!(buf.take(5), b"ab");
+    }
+
+    #[test]
+    fn test_resize_keeps_contents() {
+        let mut buf = CircularBuffer::new(5);
+        buf.extend(b"12345");
+        buf.take(3);
+        buf.extend(b"abc");
+        assert_eq!(buf.resize(8), 0);
+        assert_eq!(buf.capacity(), 8);
+        buf.extend(b"xyz");
+        assert_eq!(buf.resize(4), 4);
+        assert_eq!(buf.take(8), b"45ab");
+    }
+
+    #[test]
+    fn test_expire_drops_old_bytes() {
+        let old = BootInstant::now().checked_sub(Duration::from_secs(10)).unwrap();
+        let mut buf = CircularBuffer::with_max_age(8, Some(Duration::from_secs(5)));
+        buf.extend_at(b"old", old);
+        buf.extend(b"new");
+        // Resizing keeps the ages
+        assert_eq!(buf.resize(16), 0);
+        assert_eq!(buf.expire(), 3);
+        assert_eq!(buf.len(), 3);
+        assert_eq!(buf.take(8), b"new");
+        assert!(buf.runs.is_empty());
+    }
+
+    #[test]
+    fn test_bytes_keep_their_positions() {
+        let mut buf = CircularBuffer::new(8);
+        assert_eq!(buf.extend(b"abcd"), Span { start: 0, len: 4 });
+        // Bytes served around the buffer take positions too
+        assert_eq!(buf.positions().claim(2), Span { start: 4, len: 2 });
+        assert_eq!(buf.extend(b"efghijk"), Span { start: 6, len: 4 });
+        buf.take(1);
+        assert_eq!(buf.resize(16), 0);
+        let (bytes, spans) = buf.take_traced(5);
+        assert_eq!(bytes, b"bcdef");
+        let mut expected = Spans::from(Span { start: 1, len: 3 });
+        expected.push(Span { start: 6, len: 2 });
+        assert_eq!(spans, expected);
+        assert_eq!(buf.extend(b"x"), Span { start: 10, len: 1 });
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn test_consumed_bytes_are_poisoned() {
+        let mut buf = CircularBuffer::new(4);
+        buf.extend(b"abcd");
+        buf.take(3);
+        assert_eq!(&buf.buffer[..3], &[POISON; 3]);
+        assert_eq!(buf.buffer[3], b'd');
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn test_served_bytes_are_flagged() {
+        let mut served = vec![7u8; 32];
+        poison(&mut served);
+        assert!(contains_poison(&served));
+        assert!(!contains_poison(&[POISON; POISON_RUN - 1]));
+        // Data that happens to look poisoned is still taken
+        let mut buf = CircularBuffer::new(64);
+        buf.extend_from_vec(served);
+        assert_eq!(buf.take(32), [POISON; 32]);
+    }
 }
\ No newline at end of file
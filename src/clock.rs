@@ -0,0 +1,204 @@
+use std::time::{Duration, Instant, SystemTime};
+
+/// Differences between the clocks below this are just scheduling noise.
+const STEP_TOLERANCE: Duration = Duration::from_secs(2);
+
+/// A reading of the boot clock: time since boot including time spent
+/// suspended, which neither NTP nor an administrator can step. Ages that
+/// must count suspend, such as buffered entropy TTLs, are measured on it;
+/// `Instant` stops while the machine sleeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BootInstant(Duration);
+
+impl BootInstant {
+    pub fn now() -> Self {
+        SystemClock.boot()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        Self::now().saturating_duration_since(*self)
+    }
+
+    pub fn saturating_duration_since(&self, earlier: BootInstant) -> Duration {
+        self.0.saturating_sub(earlier.0)
+    }
+
+    #[cfg(test)]
+    pub fn checked_sub(&self, duration: Duration) -> Option<Self> {
+        self.0.checked_sub(duration).map(Self)
+    }
+}
+
+/// The three clocks the service reads.
+pub trait Clock {
+    /// Never goes backwards and counts suspend.
+    fn boot(&self) -> BootInstant;
+    /// Never goes backwards; stops during suspend.
+    fn monotonic(&self) -> Instant;
+    /// Local wall-clock time; jumps when it is stepped.
+    fn wall(&self) -> SystemTime;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[cfg(target_os = "linux")]
+    fn boot(&self) -> BootInstant {
+        // SAFETY: timespec is plain old data that clock_gettime fills in
+        let mut ts: libc::timespec = unsafe { std::mem::zeroed() };
+        if unsafe { libc::clock_gettime(libc::CLOCK_BOOTTIME, &mut ts) } != 0 {
+            return BootInstant(process_uptime());
+        }
+        BootInstant(Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn boot(&self) -> BootInstant {
+        BootInstant(process_uptime())
+    }
+
+    fn monotonic(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn wall(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Monotonic time since the clock was first read, where there is no boot clock.
+fn process_uptime() -> Duration {
+    static START: std::sync::OnceLock<Instant> = std::sync::OnceLock::new();
+    START.get_or_init(Instant::now).elapsed()
+}
+
+/// What happened to time between two `ClockWatch::check`s besides passing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClockChange {
+    /// The wall clock was stepped by this many ms (negative: backwards).
+    Stepped(i64),
+    /// The machine was suspended for about this long.
+    Resumed(Duration),
+}
+
+/// Tells wall-clock steps and suspends apart from time passing, by comparing
+/// how far each clock moved since the last check.
+pub struct ClockWatch<C: Clock> {
+    clock: C,
+    last: (BootInstant, Instant, SystemTime),
+}
+
+impl<C: Clock> ClockWatch<C> {
+    pub fn new(clock: C) -> Self {
+        let last = (clock.boot(), clock.monotonic(), clock.wall());
+        Self { clock, last }
+    }
+
+    /// The wall-clock time now, and what changed since the last check.
+    pub fn check(&mut self) -> (SystemTime, Vec<ClockChange>) {
+        let now = (self.clock.boot(), self.clock.monotonic(), self.clock.wall());
+        let (boot, monotonic, wall) = self.last;
+        self.last = now;
+        let booted = now.0.saturating_duration_since(boot);
+        let mut changes = Vec::new();
+        let ran = now.1.saturating_duration_since(monotonic);
+        if booted.saturating_sub(ran) > STEP_TOLERANCE {
+            changes.push(ClockChange::Resumed(booted - ran));
+        }
+        let walked = match now.2.duration_since(wall) {
+            Ok(forward) => forward.as_millis() as i64,
+            Err(e) => -(e.duration().as_millis() as i64),
+        };
+        let step = walked - booted.as_millis() as i64;
+        if step.unsigned_abs() > STEP_TOLERANCE.as_millis() as u64 {
+            changes.push(ClockChange::Stepped(step));
+        }
+        (now.2, changes)
+    }
+}
+
+/// Logs what `ClockWatch::check` found, on behalf of `what`.
+pub fn log_changes(what: &str, changes: &[ClockChange]) {
+    for change in changes {
+        match change {
+            ClockChange::Stepped(ms) => log::warn!("{}: wall clock stepped by {:+} ms", what, ms),
+            ClockChange::Resumed(slept) => log::info!("{}: resumed after about {:?} suspended", what, slept),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    /// Clocks that only move when told to.
+    struct ManualClock {
+        boot: Cell<Duration>,
+        monotonic: Instant,
+        ran: Cell<Duration>,
+        wall: Cell<SystemTime>,
+    }
+
+    impl ManualClock {
+        fn new() -> Self {
+            Self { boot: Cell::new(Duration::ZERO), monotonic: Instant::now(), ran: Cell::new(Duration::ZERO), wall: Cell::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1 << 30)) }
+        }
+
+        fn pass(&self, d: Duration) {
+            self.boot.set(self.boot.get() + d);
+            self.ran.set(self.ran.get() + d);
+            self.wall.set(self.wall.get() + d);
+        }
+
+        fn suspend(&self, d: Duration) {
+            self.boot.set(self.boot.get() + d);
+            self.wall.set(self.wall.get() + d);
+        }
+    }
+
+    impl Clock for &ManualClock {
+        fn boot(&self) -> BootInstant {
+            BootInstant(self.boot.get())
+        }
+        fn monotonic(&self) -> Instant {
+            self.monotonic + self.ran.get()
+        }
+        fn wall(&self) -> SystemTime {
+            self.wall.get()
+        }
+    }
+
+    #[test]
+    fn test_clock_jumps() {
+        let clock = ManualClock::new();
+        let mut watch = ClockWatch::new(&clock);
+        clock.pass(Duration::from_secs(5));
+        assert!(watch.check().1.is_empty());
+
+        // NTP steps the wall clock back an hour: only the wall clock moves
+        clock.pass(Duration::from_secs(5));
+        clock.wall.set(clock.wall.get() - Duration::from_secs(3600));
+        let (now, changes) = watch.check();
+        assert_eq!(now, clock.wall.get());
+        assert_eq!(changes, [ClockChange::Stepped(-3_600_000)]);
+
+        // A night suspended moves boot and wall clock alike, not the monotonic one
+        clock.suspend(Duration::from_secs(8 * 3600));
+        clock.pass(Duration::from_secs(1));
+        assert_eq!(watch.check().1, [ClockChange::Resumed(Duration::from_secs(8 * 3600))]);
+
+        // Resuming into a wall clock that was also stepped forward
+        clock.suspend(Duration::from_secs(60));
+        clock.wall.set(clock.wall.get() + Duration::from_secs(30));
+        assert_eq!(watch.check().1, [ClockChange::Resumed(Duration::from_secs(60)), ClockChange::Stepped(30_000)]);
+    }
+
+    #[test]
+    fn test_boot_clock() {
+        let earlier = BootInstant::now();
+        assert!(BootInstant::now() >= earlier);
+        assert_eq!(earlier.saturating_duration_since(BootInstant::now()), Duration::ZERO);
+        assert!(earlier.checked_sub(Duration::from_secs(1 << 40)).is_none());
+    }
+}
@@ -0,0 +1,144 @@
+use crate::aes::{self, Aes256};
+use crate::config::ConditionerKind;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Instant;
+use zeroize::Zeroizing;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Combined bytes conditioned into each output byte, so every output block
+/// is computed from twice its length of input (SP 800-90B 3.1.5.1.2 credits
+/// a vetted function's output with full entropy only given more input
+/// entropy than output).
+const INPUT_RATIO: usize = 2;
+
+/// Input blocks conditioned between looks at the deadline.
+const DEADLINE_CHECK_BLOCKS: usize = 64;
+
+tokio::task_local! {
+    static CONDITIONER: Option<Arc<Conditioner>>;
+}
+
+/// Runs `fut` with the combined output it reads passed through `conditioner`.
+pub async fn applying<F: Future>(conditioner: Option<Arc<Conditioner>>, fut: F) -> F::Output {
+    CONDITIONER.scope(conditioner, fut).await
+}
+
+/// The conditioner of the endpoint being served, if it has one.
+pub fn current() -> Option<Arc<Conditioner>> {
+    CONDITIONER.try_with(|c| c.clone()).ok().flatten()
+}
+
+/// A vetted conditioning component (SP 800-90B 3.1.5.1.1) applied to
+/// combined source output. Keys may be public there; they are derived from
+/// the group name so each endpoint conditions differently.
+pub enum Conditioner {
+    CbcMacAes(Box<Aes256>),
+    HmacSha256(Zeroizing<[u8; 32]>),
+    HashDf,
+}
+
+impl Conditioner {
+    /// The conditioner `kind` names for the group `group`; `None` for `none`.
+    pub fn new(kind: ConditionerKind, group: &str) -> Option<Self> {
+        let key = Zeroizing::new(<[u8; 32]>::from(Sha256::new().chain_update(b"trng-dbus conditioner key ").chain_update(group).finalize()));
+        match kind {
+            ConditionerKind::None => None,
+            ConditionerKind::CbcMacAes => Some(Self::CbcMacAes(Box::new(Aes256::new(&key)))),
+            ConditionerKind::HmacSha256 => Some(Self::HmacSha256(key)),
+            ConditionerKind::HashDf => Some(Self::HashDf),
+        }
+    }
+
+    /// How provenance names the conditioning.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::CbcMacAes(_) => "sp800-90b-cbc-mac-aes256",
+            Self::HmacSha256(_) => "sp800-90b-hmac-sha256",
+            Self::HashDf => "sp800-90b-hash-df-sha256",
+        }
+    }
+
+    /// Output bytes of one invocation.
+    fn output_block(&self) -> usize {
+        match self {
+            Self::CbcMacAes(_) => aes::BLOCK,
+            Self::HmacSha256(_) | Self::HashDf => 32,
+        }
+    }
+
+    /// Combined bytes to read for `output` conditioned bytes.
+    pub fn input_len(&self, output: usize) -> usize {
+        output.div_ceil(self.output_block()).saturating_mul(self.output_block() * INPUT_RATIO)
+    }
+
+    /// Conditions `input` block by block; a trailing partial block is
+    /// dropped, as the functions are only vetted for fixed-length input.
+    /// Stops early, on a block boundary, once `deadline` passes.
+    pub fn condition(&self, input: &[u8], deadline: Option<Instant>) -> Zeroizing<Vec<u8>> {
+        let block = self.output_block();
+        let mut out = Zeroizing::new(Vec::with_capacity(input.len() / INPUT_RATIO));
+        for (n, chunk) in input.chunks_exact(block * INPUT_RATIO).enumerate() {
+            if n % DEADLINE_CHECK_BLOCKS == 0 && n > 0 && deadline.is_some_and(|d| Instant::now() >= d) {
+                break;
+            }
+            match self {
+                Self::CbcMacAes(aes) => {
+                    let mut state = Zeroizing::new([0u8; aes::BLOCK]);
+                    for part in chunk.chunks_exact(aes::BLOCK) {
+                        for (s, b) in state.iter_mut().zip(part) {
+                            *s ^= b;
+                        }
+                        aes.encrypt_block(&mut state);
+                    }
+                    out.extend_from_slice(&*state);
+                }
+                Self::HmacSha256(key) => {
+                    let mut mac = HmacSha256::new_from_slice(&**key).expect("HMAC takes any key length");
+                    mac.update(chunk);
+                    out.extend_from_slice(&*Zeroizing::new(<[u8; 32]>::from(mac.finalize().into_bytes())));
+                }
+                Self::HashDf => {
+                    // Hash_df (SP 800-90A 10.3.1) for 256 bits: a single round, counter 1
+                    let digest = Sha256::new().chain_update([1]).chain_update(256u32.to_be_bytes()).chain_update(chunk).finalize();
+                    out.extend_from_slice(&*Zeroizing::new(<[u8; 32]>::from(digest)));
+                }
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conditioners() {
+        assert!(Conditioner::new(ConditionerKind::None, "ha").is_none());
+        let input: Vec<u8> = (0..200u8).collect();
+        for kind in [ConditionerKind::CbcMacAes, ConditionerKind::HmacSha256, ConditionerKind::HashDf] {
+            let conditioner = Conditioner::new(kind, "ha").unwrap();
+            let block = conditioner.output_block();
+            assert_eq!(conditioner.input_len(1), 2 * block);
+            assert_eq!(conditioner.input_len(block + 1), 4 * block);
+            // 200 bytes hold 3 whole input blocks of 64, or 6 of 32
+            let out = conditioner.condition(&input, None);
+            assert_eq!(out.len(), 96);
+            assert_ne!(out[..block], out[block..2 * block]);
+            assert_eq!(*out, *conditioner.condition(&input[..input.len() / (2 * block) * 2 * block], None));
+            if kind != ConditionerKind::HashDf {
+                assert_ne!(*out, *Conditioner::new(kind, "other").unwrap().condition(&input, None));
+            }
+        }
+        // Hash_df of one block per SP 800-90A, independent of the group
+        let digest = Sha256::new().chain_update([1, 0, 0, 1, 0]).chain_update(&input[..64]).finalize();
+        assert_eq!(Conditioner::HashDf.condition(&input[..64], None)[..], digest[..]);
+        // A passed deadline stops after the first DEADLINE_CHECK_BLOCKS blocks
+        let long = vec![7u8; 64 * 1000];
+        assert_eq!(Conditioner::HashDf.condition(&long, Some(Instant::now())).len(), 32 * DEADLINE_CHECK_BLOCKS);
+    }
+}
@@ -1,23 +1,634 @@
 use serde::Deserialize;
-use std::collections::HashSet;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
 use std::path::Path;
 use log::error;
 
+/// Largest replenish step a buffered source takes, from config or `SetSourceTuning`.
+pub const MAX_REPLENISH_CHUNK: usize = 16 * 1024 * 1024;
+
+/// Longest `replenish_interval_ms`, well within the supervisor's stall timeout.
+const MAX_REPLENISH_INTERVAL_MS: u64 = 60_000;
+
+/// Largest `[drbg]` reseed_interval_bytes, far below SP 800-90A's limit
+/// of 2^48 generate calls between reseeds.
+const MAX_RESEED_INTERVAL: u64 = 1 << 40;
+
 #[derive(Debug, Deserialize, Default, Clone)]
 pub struct Config {
     #[serde(default)]
     pub sources: Sources,
+    #[serde(default)]
+    pub alerts: Option<AlertConfig>,
+    #[serde(default)]
+    pub subscriptions: Option<SubscriptionConfig>,
+    #[serde(default)]
+    pub quotas: Option<QuotaConfig>,
+    #[serde(default)]
+    pub access: Option<AccessConfig>,
+    #[serde(default)]
+    pub readiness: Option<ReadinessConfig>,
+    #[serde(default)]
+    pub scheduler: Option<SchedulerConfig>,
+    #[serde(default)]
+    pub runtime: Option<RuntimeConfig>,
+    #[serde(default)]
+    pub service: Option<ServiceConfig>,
+    #[serde(default)]
+    pub fallback: Option<FallbackConfig>,
+    #[serde(default)]
+    pub chaos: Option<ChaosConfig>,
+    #[serde(default)]
+    pub attestation: Option<AttestationConfig>,
+    #[serde(default)]
+    pub client_streams: Option<ClientStreamsConfig>,
+    #[serde(default)]
+    pub ledger: Option<LedgerConfig>,
+    #[serde(default)]
+    pub groups: Vec<GroupConfig>,
+    #[serde(default)]
+    pub watchdog: Option<WatchdogConfig>,
+    #[serde(default)]
+    pub latency_budget: Option<LatencyBudgetConfig>,
+    #[serde(default)]
+    pub reservations: Option<ReservationConfig>,
+    #[serde(default)]
+    pub relay: Option<RelayConfig>,
+    #[serde(default)]
+    pub trending: Option<TrendingConfig>,
+    #[serde(default)]
+    pub memory: Option<MemoryConfig>,
+    #[serde(default)]
+    pub drbg: Option<DrbgConfig>,
+}
+
+/// `[drbg]` section: serve requests from an AES-256 CTR_DRBG that combined
+/// source output only seeds and reseeds.
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct DrbgConfig {
+    /// Bytes generated before the DRBG is reseeded (default 1048576).
+    #[serde(default)]
+    pub reseed_interval_bytes: Option<u64>,
+}
+
+/// `[relay]` section: streams a bounded share of the combined output to
+/// downstream instances over mutual TLS, which read it with a
+/// `[[sources.tcp]]` source. Without it nothing listens.
+#[derive(Debug, Deserialize, Clone)]
+pub struct RelayConfig {
+    /// Address to listen on, e.g. `0.0.0.0:7400`.
+    pub listen: String,
+    /// PEM certificate chain and key presented to downstreams.
+    pub cert_file: String,
+    pub key_file: String,
+    /// PEM bundle of the CAs downstream client certificates must chain to.
+    pub client_ca_file: String,
+    /// Hex SHA-256 fingerprints of the only client certificates let in
+    /// (default every one the CA signed).
+    #[serde(default)]
+    pub client_pin_sha256: Vec<String>,
+    /// Across all downstreams (default 1048576).
+    #[serde(default)]
+    pub max_bytes_per_second: Option<u64>,
+    /// For each downstream (default 65536).
+    #[serde(default)]
+    pub max_bytes_per_second_per_downstream: Option<u64>,
+    /// Downstreams served at once; more are turned away (default 16).
+    #[serde(default)]
+    pub max_downstreams: Option<usize>,
+    /// Warn this many days before a configured certificate expires (default 30).
+    #[serde(default)]
+    pub expiry_warning_days: Option<u32>,
+}
+
+/// `[trending]` section: a small raw sample of each source captured on a
+/// schedule and run through the statistical battery, keeping the results as
+/// a time series per source (`GetQualityTrend`).
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct TrendingConfig {
+    /// Time between runs, in minutes (default 60).
+    #[serde(default)]
+    pub interval_minutes: Option<u64>,
+    /// Raw bytes captured from each source per run (default 65536, 4096 to 1048576).
+    #[serde(default)]
+    pub sample_bytes: Option<usize>,
+    /// How long one source's capture may take, in ms (default 30000).
+    #[serde(default)]
+    pub capture_timeout_ms: Option<u64>,
+    /// Results kept per source; older ones are dropped (default 720).
+    #[serde(default)]
+    pub history: Option<usize>,
+    /// JSON lines file the results are appended to and loaded from at
+    /// startup, so the series survives restarts (default kept in memory only).
+    #[serde(default)]
+    pub file: Option<String>,
+    /// Ids of the sources sampled (default all).
+    #[serde(default)]
+    pub sources: Vec<String>,
+}
+
+/// `[reservations]` section: limits for `Reserve`, which it enables.
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct ReservationConfig {
+    /// Most bytes one client may hold reserved on one endpoint (default 1 MiB).
+    #[serde(default)]
+    pub max_bytes_per_client: Option<u64>,
+    /// Most bytes held reserved across all clients (default 16 MiB).
+    #[serde(default)]
+    pub max_total_bytes: Option<u64>,
+    /// Longest `ttl_seconds` a client may ask for (default 3600).
+    #[serde(default)]
+    pub max_ttl_seconds: Option<u64>,
+}
+
+/// `[latency_budget]` section: shares of a client's timeout, in percent,
+/// for gathering from the sources and for conditioning; the reply keeps
+/// the rest. Without it the sources may take the whole timeout.
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct LatencyBudgetConfig {
+    /// Share for reading the sources (default 80).
+    #[serde(default)]
+    pub gather_percent: Option<u8>,
+    /// Share for conditioning, DRBG padding and `[drbg]` output (default 15).
+    #[serde(default)]
+    pub condition_percent: Option<u8>,
+}
+
+/// `[memory]` section: a ceiling on what source buffers and in-flight
+/// requests may hold together.
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct MemoryConfig {
+    /// Requests that would take the total above this are refused (default none).
+    #[serde(default)]
+    pub ceiling_mebibytes: Option<u64>,
+}
+
+/// `[watchdog]` section: periodic canary reads through the whole request path.
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct WatchdogConfig {
+    /// Time between canary reads, in ms (default 10000).
+    #[serde(default)]
+    pub interval_ms: Option<u64>,
+    /// Bytes per canary read (default 32).
+    #[serde(default)]
+    pub canary_bytes: Option<usize>,
+    /// A canary slower than this fails, in ms (default 1000).
+    #[serde(default)]
+    pub latency_budget_ms: Option<u64>,
+    /// Consecutive failed canaries before the service is marked unready (default 1).
+    #[serde(default)]
+    pub failures: Option<u32>,
+    /// Count canary reads in `GetStats` and the statistics log (default false).
+    #[serde(default)]
+    pub count_in_stats: Option<bool>,
+}
+
+/// `[[groups]]` entry: a further endpoint for one class of consumers, served
+/// next to `lv.lumii.trng.Rng` under its own interface name and access policy.
+#[derive(Debug, Deserialize, Clone)]
+pub struct GroupConfig {
+    pub name: String,
+    /// D-Bus interface name, e.g. "lv.lumii.trng.HighAssurance".
+    pub interface: String,
+    /// Default the interface name as a path, e.g. "/lv/lumii/trng/HighAssurance".
+    #[serde(default)]
+    pub object_path: Option<String>,
+    /// Uids that may call the endpoint at all (default everyone).
+    #[serde(default)]
+    pub uids: Option<Vec<u32>>,
+    /// Privileged methods on the endpoint (default the `[access]` section).
+    #[serde(default)]
+    pub access: Option<AccessConfig>,
+    /// Caps the bytes the endpoint serves (default unshaped).
+    #[serde(default)]
+    pub shaping: Option<ShapingConfig>,
+    /// Vetted conditioning applied to the combined bytes the endpoint serves
+    /// (default none; see `conditioning.rs`).
+    #[serde(default)]
+    pub conditioner: Option<ConditionerKind>,
+    /// Sources that must each deliver a request in full within its timeout,
+    /// or the request fails (default none: whatever responded is combined).
+    #[serde(default)]
+    pub min_sources: Option<usize>,
+    /// How the endpoint's reads combine the sources (default `[sources]
+    /// combine`), e.g. a raw XOR endpoint next to a `sha3` one.
+    #[serde(default)]
+    pub combine: Option<CombineMode>,
+}
+
+/// `conditioner = "..."` of a group.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConditionerKind {
+    None,
+    /// CBC-MAC with AES-256.
+    CbcMacAes,
+    HmacSha256,
+    /// Hash_df of SP 800-90A with SHA-256.
+    HashDf,
+}
+
+/// `shaping = { ... }` of a group: a token bucket shared by all its callers.
+#[derive(Debug, Deserialize, Clone)]
+pub struct ShapingConfig {
+    pub bytes_per_second: u64,
+    /// Bytes that may be served at once after a quiet spell (default one second's worth).
+    #[serde(default)]
+    pub burst_bytes: Option<u64>,
+}
+
+/// `[ledger]` section: a local record of which source bytes went into which request.
+#[derive(Debug, Deserialize, Clone)]
+pub struct LedgerConfig {
+    /// JSON-lines file appended to; created readable by the service only.
+    pub path: String,
+}
+
+/// `[client_streams]` section: a separate DRBG per client and per session.
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct ClientStreamsConfig {
+    /// Reseed a stream from the sources after this long (default 60).
+    #[serde(default)]
+    pub reseed_seconds: Option<u64>,
+    /// Reseed a stream after serving this many bytes (default 1 MiB).
+    #[serde(default)]
+    pub reseed_bytes: Option<u64>,
+    /// Most open sessions per client, besides its own stream (default 16).
+    #[serde(default)]
+    pub max_sessions_per_client: Option<usize>,
+    /// Most streams overall (default 1024).
+    #[serde(default)]
+    pub max_total: Option<usize>,
+}
+
+/// `[attestation]` section: HMAC over every `ReadBytesEx` reply.
+#[derive(Debug, Deserialize, Clone)]
+pub struct AttestationConfig {
+    /// File holding the hex service key (at least 32 bytes), e.g. unsealed
+    /// from a TPM by systemd-creds. Should be readable by the service only.
+    pub key_file: String,
+    /// Name echoed in replies so verifiers can pick the key (default empty).
+    #[serde(default)]
+    pub key_id: Option<String>,
+}
+
+/// `[chaos]` section: fault injection for staging, only honored by builds
+/// with the `chaos` feature. Probabilities are per source read (default 0).
+#[derive(Debug, Deserialize, Default, Clone)]
+#[cfg_attr(not(feature = "chaos"), allow(dead_code))]
+pub struct ChaosConfig {
+    /// Fail the read with an I/O error.
+    #[serde(default)]
+    pub failure_probability: Option<f64>,
+    /// Stall the read for `delay_ms` first.
+    #[serde(default)]
+    pub delay_probability: Option<f64>,
+    /// Default 1000.
+    #[serde(default)]
+    pub delay_ms: Option<u64>,
+    /// Return only part of the bytes read.
+    #[serde(default)]
+    pub short_read_probability: Option<f64>,
+    /// Fail the read as if the data had failed integrity verification.
+    #[serde(default)]
+    pub corruption_probability: Option<f64>,
+    /// Source ids to inject into (default all).
+    #[serde(default)]
+    pub sources: Vec<String>,
+    /// Fixed seed for reproducible runs (default random).
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+/// `[fallback]` section: what to do when every source is quarantined.
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct FallbackConfig {
+    /// Default `deny`.
+    #[serde(default)]
+    pub policy: Option<FallbackPolicy>,
+}
+
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FallbackPolicy {
+    /// Fail requests with `SourcesUnavailable`.
+    #[default]
+    Deny,
+    /// Answer `ReadBytes`/`ReadBytesEx` from the jitter generator, flagged as such.
+    ServeFlagged,
+}
+
+/// `[service]` section: where the service is reached.
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct ServiceConfig {
+    /// Bus the name is taken on (default `session`; `--system` overrides).
+    #[serde(default)]
+    pub bus: Option<BusKind>,
+    /// Well-known name to take (default `BUS_NAME`), so several instances
+    /// can share a bus.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Object path of `lv.lumii.trng.Rng` and `lv.lumii.trng.Monitor`
+    /// (default `DEFAULT_OBJECT_PATH`).
+    #[serde(default)]
+    pub object_path: Option<String>,
+    /// Largest request, in bytes, one call may make (default
+    /// `DEFAULT_MAX_REQUEST_BYTES`); larger ones are refused up front.
+    #[serde(default)]
+    pub max_request_bytes: Option<u64>,
+    /// `RequestBytesAsync` requests one client may have running at once
+    /// (default `DEFAULT_MAX_ASYNC_REQUESTS_PER_CLIENT`).
+    #[serde(default)]
+    pub max_async_requests_per_client: Option<usize>,
+}
+
+/// `bus = "..."` of `[service]`.
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BusKind {
+    #[default]
+    Session,
+    System,
+}
+
+impl std::fmt::Display for BusKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BusKind::Session => "session bus",
+            BusKind::System => "system bus",
+        })
+    }
+}
+
+/// `[runtime]` section: tokio runtime the service runs on. The matching
+/// command line flags take precedence.
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct RuntimeConfig {
+    /// Default `multi_thread`.
+    #[serde(default)]
+    pub flavor: Option<RuntimeFlavor>,
+    /// Worker threads of `multi_thread` (default one per core).
+    #[serde(default)]
+    pub worker_threads: Option<usize>,
+    /// Upper bound on threads for blocking reads (default 512).
+    #[serde(default)]
+    pub max_blocking_threads: Option<usize>,
+    /// Cores every runtime thread is pinned to (default unpinned).
+    #[serde(default)]
+    pub cpus: Vec<usize>,
+}
+
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuntimeFlavor {
+    #[default]
+    MultiThread,
+    /// Everything on the main thread, for small targets.
+    CurrentThread,
+}
+
+/// `[scheduler]` section: which waiting request reads from the sources next.
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct SchedulerConfig {
+    /// Default `fifo`.
+    #[serde(default)]
+    pub policy: Option<SchedulerPolicy>,
+    /// Request slices read from the sources at the same time (default 4).
+    #[serde(default)]
+    pub slots: Option<usize>,
+    /// Client requests served at the same time; the others queue in arrival
+    /// order (default unbounded).
+    #[serde(default)]
+    pub max_concurrent_requests: Option<usize>,
+    /// Requests that may queue for `max_concurrent_requests`; further ones
+    /// fail with `Busy` (default unbounded).
+    #[serde(default)]
+    pub max_queue_depth: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SchedulerPolicy {
+    #[default]
+    Fifo,
+    /// Highest request `priority` first.
+    Priority,
+    /// Clients share throughput in proportion to `priority + 1`.
+    FairShare,
+}
+
+/// `[readiness]` section: hold back service after startup until the sources
+/// are primed. Without it requests are served at once.
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct ReadinessConfig {
+    /// Fill level every buffered source must reach, in percent (default 50).
+    #[serde(default)]
+    pub min_fill_percent: Option<f64>,
+    /// How clients are held back until then (default `delay_name`).
+    #[serde(default)]
+    pub mode: Option<ReadinessMode>,
+    /// Serve anyway after this long, in ms (default 0, wait indefinitely).
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadinessMode {
+    /// Acquire the bus name only once ready.
+    #[default]
+    DelayName,
+    /// Take the name at once and answer requests with `NotReady`.
+    NotReady,
+}
+
+/// `[access]` section: callers allowed to use each interface and the
+/// privileged methods.
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct AccessConfig {
+    /// Uids that may call the consuming interface, `lv.lumii.trng.Rng`
+    /// (default everyone). Also accepted as `allow_uids`.
+    #[serde(default, alias = "allow_uids")]
+    pub consume_uids: Option<Vec<u32>>,
+    /// User names that may call it too, resolved to uids at startup; listing
+    /// any restricts the interface like `consume_uids` (default none).
+    #[serde(default)]
+    pub allow_users: Vec<String>,
+    /// Uids that may call `lv.lumii.trng.Monitor` (default everyone). Only
+    /// read from the top-level section.
+    #[serde(default)]
+    pub monitor_uids: Option<Vec<u32>>,
+    /// Uids that may call `CaptureRawSample` (default none).
+    #[serde(default)]
+    pub capture_uids: Vec<u32>,
+    /// Uids that may resize, retune and promote sources (default none).
+    #[serde(default)]
+    pub tune_uids: Vec<u32>,
+    /// Whether callers not listed for a privileged method may still call it
+    /// when polkit authorizes its action (default false).
+    #[serde(default)]
+    pub polkit: Option<bool>,
+}
+
+/// `[quotas]` section: limits on the bytes each D-Bus client is served
+/// through the read methods (default unlimited).
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct QuotaConfig {
+    /// Sustained rate per client (default unlimited).
+    #[serde(default)]
+    pub bytes_per_second: Option<u64>,
+    /// Bytes a client may take at once after a quiet spell (default one second's worth).
+    #[serde(default)]
+    pub burst_bytes: Option<u64>,
+    /// Bytes per client in any 24 hours from its first request (default unlimited).
+    #[serde(default)]
+    pub bytes_per_day: Option<u64>,
+}
+
+/// `[subscriptions]` section: limits for push delivery (`Subscribe`).
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct SubscriptionConfig {
+    /// Highest rate a single subscription may ask for (default 1 MiB/s).
+    #[serde(default)]
+    pub max_bytes_per_second: Option<u64>,
+    /// Most concurrent subscriptions per client (default 8).
+    #[serde(default)]
+    pub max_per_client: Option<usize>,
+    /// Most concurrent subscriptions overall (default 256).
+    #[serde(default)]
+    pub max_total: Option<usize>,
+}
+
+/// `[alerts]` section: how health events page an operator.
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct AlertConfig {
+    /// Hook command (argv); gets the alert as JSON on stdin and in `TRNG_ALERT_*` env vars.
+    #[serde(default)]
+    pub command: Option<Vec<String>>,
+    /// Emit the `Alert` D-Bus signal (default true).
+    #[serde(default)]
+    pub signal: Option<bool>,
+    /// Alert when a buffered source stays empty this long.
+    #[serde(default)]
+    pub buffer_empty_seconds: Option<u64>,
+    /// Suppress repeats of the same alert for the same source (default 3600).
+    #[serde(default)]
+    pub repeat_seconds: Option<u64>,
+    /// Alert kinds to act on (default all): source_quarantined, buffer_empty, self_test_failed, watchdog_failed.
+    #[serde(default)]
+    pub events: Vec<String>,
 }
 
 #[derive(Debug, Deserialize, Default, Clone)]
 pub struct Sources {
     #[serde(default)]
     pub combine: Option<String>,
+    /// Persistent entropy pool requests are served from (default none:
+    /// each request combines what the sources answer it).
+    #[serde(default)]
+    pub pool: Option<PoolKind>,
     #[serde(default)]
     pub lrng: Vec<LrngConfig>,
     #[serde(default)]
     pub file: Vec<FileConfig>,
+    #[serde(default)]
+    pub serial: Vec<SerialConfig>,
+    #[serde(default)]
+    pub tcp: Vec<TcpConfig>,
+    #[serde(default)]
+    pub http: Vec<HttpConfig>,
+    #[serde(default)]
+    pub cpu: Vec<CpuConfig>,
+    #[serde(default)]
+    pub jitter: Vec<JitterConfig>,
+    #[serde(default)]
+    pub mqtt: Vec<MqttConfig>,
+    #[serde(default)]
+    pub hwrng: Vec<HwrngConfig>,
+    /// Only used by builds with the `nats` feature.
+    #[serde(default)]
+    pub nats: Vec<NatsConfig>,
+    #[serde(default)]
+    pub exec: Vec<ExecConfig>,
+    #[serde(default)]
+    pub pkcs11: Vec<Pkcs11Config>,
+    #[serde(default)]
+    pub audio: Vec<AudioConfig>,
+    #[serde(default)]
+    pub dbus: Vec<DbusConfig>,
+    /// Only used by builds with the `grpc` feature.
+    #[serde(default)]
+    pub grpc: Vec<GrpcConfig>,
+    /// Per-source throughput benchmark run at startup, in ms (default 1000, 0 disables).
+    #[serde(default)]
+    pub benchmark_ms: Option<u64>,
+    /// What to do when a source cannot be opened at startup (default `fail_fast`).
+    #[serde(default)]
+    pub startup: Option<StartupPolicy>,
+    /// How long `wait_for_source` waits, in ms (default 60000).
+    #[serde(default)]
+    pub startup_timeout_ms: Option<u64>,
+    /// Emit `EntropyLow` when a buffered source's fill drops below this
+    /// percentage (default none: never).
+    #[serde(default)]
+    pub entropy_low_percent: Option<f64>,
+}
+
+/// Startup failure policy (`startup = "..."` in `[sources]` or a source block).
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StartupPolicy {
+    /// Abort the daemon.
+    #[default]
+    FailFast,
+    /// Start without the source and keep trying to open it in the background.
+    StartDegraded,
+    /// Keep retrying before serving, up to `startup_timeout_ms`, then abort.
+    WaitForSource,
+}
+
+/// Settings every kind of source takes, flattened into its own table.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct SourceCommon {
+    /// Recurring windows during which the source is paused (see `maintenance.rs`).
+    #[serde(default)]
+    pub maintenance: Vec<MaintenanceConfig>,
+    /// Device, firmware or microcode version reported in reply provenance
+    /// (PKCS#11 default: the token's model).
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Entropy per output bit credited to this source, 0-1 (default 1; for
+    /// hwrng `rng_quality` / 1024 at startup when the kernel says).
+    #[serde(default)]
+    pub entropy_credit: Option<f64>,
+    /// Assessed entropy per byte, 0.125-8, that `combine = "weighted_xor"`
+    /// draws bytes by (default 8 times the entropy credit).
+    #[serde(default)]
+    pub entropy_per_byte: Option<f64>,
+    /// Whether an error from this source fails the request; the errors of
+    /// an optional source only leave it out (default true).
+    #[serde(default)]
+    pub required: Option<bool>,
+    #[serde(default)]
+    pub breaker: Option<BreakerConfig>,
+    /// Id of a source of the same kind that this one is the warm standby
+    /// for: kept replenished but idle until promoted.
+    #[serde(default)]
+    pub standby_for: Option<String>,
+    /// Delay added to each read, for staging setups imitating slower
+    /// hardware (default none).
+    #[serde(default)]
+    pub simulate_latency_ms: Option<u64>,
+    /// Most bytes per second the source serves, likewise (default unlimited).
+    #[serde(default)]
+    pub simulate_rate_bytes_per_sec: Option<u64>,
+    /// Buffered bytes older than this are zeroized instead of served (default unlimited).
+    #[serde(default)]
+    pub max_age_seconds: Option<u64>,
+    /// Cores for this source's own replenish (jitter: collector) thread (see `affinity.rs`).
+    #[serde(default)]
+    pub cpus: Option<Vec<usize>>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -27,6 +638,27 @@ pub struct LrngConfig {
     pub enabled: bool,
     #[serde(default)]
     pub buffer_mebibytes: Option<u32>,
+    /// How often the replenisher checks the buffer, in ms (default 10, at most 60000).
+    #[serde(default)]
+    pub replenish_interval_ms: Option<u64>,
+    /// Most bytes read per replenish step (default 65536, at most 16 MiB).
+    #[serde(default)]
+    pub replenish_chunk_bytes: Option<usize>,
+    /// Refilling starts once the buffer is below this percentage, 1-100 (default 100).
+    #[serde(default)]
+    pub low_watermark_percent: Option<u8>,
+    /// Most bytes per second drawn from the source, by the replenisher and
+    /// direct reads together (default unlimited).
+    #[serde(default)]
+    pub max_bytes_per_sec: Option<u64>,
+    /// Once started, refilling goes on up to this percentage, 1-100 (default 100).
+    #[serde(default)]
+    pub high_watermark_percent: Option<u8>,
+    /// Settings every kind of source takes.
+    #[serde(flatten)]
+    pub common: SourceCommon,
+    #[serde(default)]
+    pub retry: Option<RetryConfig>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -35,22 +667,806 @@ pub struct FileConfig {
     pub path: String,
     #[serde(default, rename = "loop")]
     pub loop_: Option<bool>,
+    /// Read the path as a stream, e.g. a FIFO or character device: never
+    /// seeked, waiting for bytes instead of ending (default false).
+    #[serde(default)]
+    pub stream: Option<bool>,
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub buffer_mebibytes: Option<u32>,
+    /// How often the replenisher checks the buffer, in ms (default 1000, at most 60000).
+    #[serde(default)]
+    pub replenish_interval_ms: Option<u64>,
+    /// Most bytes read per replenish step (default 65536, at most 16 MiB).
+    #[serde(default)]
+    pub replenish_chunk_bytes: Option<usize>,
+    /// Refilling starts once the buffer is below this percentage, 1-100 (default 50).
+    #[serde(default)]
+    pub low_watermark_percent: Option<u8>,
+    /// Most bytes per second read from the file, by the replenisher and
+    /// direct reads together (default unlimited).
+    #[serde(default)]
+    pub max_bytes_per_sec: Option<u64>,
+    /// Optional path to a chunk digest manifest (see `manifest.rs`).
+    #[serde(default)]
+    pub manifest: Option<String>,
+    /// Detached minisign signature, defaults to `<path>.minisig` when `public_keys` is set.
+    #[serde(default)]
+    pub signature: Option<String>,
+    /// Base64 minisign public keys trusted to sign this file.
+    #[serde(default)]
+    pub public_keys: Vec<String>,
+    /// Use the file even if its start looks like compressed or structured
+    /// data rather than entropy (default false; see `sniff.rs`).
+    #[serde(default)]
+    pub force: Option<bool>,
+    /// Destroy what is read from the file before serving it, so its bytes are
+    /// used once even across restarts (default off; see `wipe.rs`).
+    #[serde(default)]
+    pub wipe: Option<FileWipe>,
+    /// Debias the file's bytes as they are read (default off).
+    #[serde(default)]
+    pub debias: Option<DebiasKind>,
+    /// Transforms applied in order after `debias`, e.g. `["xor_fold", "sha256"]`.
+    #[serde(default)]
+    pub transforms: Vec<String>,
+    /// Overrides `[sources] startup` for this source.
+    #[serde(default)]
+    pub startup: Option<StartupPolicy>,
+    /// Overrides `[sources] startup_timeout_ms` for this source.
+    #[serde(default)]
+    pub startup_timeout_ms: Option<u64>,
+    /// Settings every kind of source takes.
+    #[serde(flatten)]
+    pub common: SourceCommon,
+    #[serde(default)]
+    pub retry: Option<RetryConfig>,
+}
+
+/// A filter applied to a source's raw bytes before they are buffered
+/// (`debias = "..."`, see `transform.rs`).
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DebiasKind {
+    VonNeumann,
+}
+
+/// One entry of a source's `transforms = [...]` (see `transform.rs`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransformKind {
+    VonNeumann,
+    /// `xor_fold[:n]`: XOR of each `n` bytes (2-64, default 2).
+    XorFold(usize),
+    /// SHA-256 of each 64-byte block.
+    Sha256,
+    /// `lsb:n`: the `n` least significant bits of each byte (1-7).
+    Lsb(u8),
+}
+
+impl std::str::FromStr for TransformKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (name, arg) = match s.split_once(':') {
+            Some((name, arg)) => (name, Some(arg.parse::<usize>().map_err(|_| format!("invalid argument in transform '{}'", s))?)),
+            None => (s, None),
+        };
+        match (name, arg) {
+            ("von_neumann", None) => Ok(Self::VonNeumann),
+            ("sha256", None) => Ok(Self::Sha256),
+            ("xor_fold", None) => Ok(Self::XorFold(2)),
+            ("xor_fold", Some(n)) if (2..=64).contains(&n) => Ok(Self::XorFold(n)),
+            ("lsb", Some(n)) if (1..=7).contains(&n) => Ok(Self::Lsb(n as u8)),
+            ("von_neumann" | "sha256" | "xor_fold" | "lsb", _) => Err(format!("transform '{}' is out of range", s)),
+            _ => Err(format!("unknown transform '{}'", s)),
+        }
+    }
+}
+
+/// The first invalid entry of `transforms`, if any.
+fn invalid_transform(transforms: &[String]) -> Option<String> {
+    transforms.iter().find_map(|t| t.parse::<TransformKind>().err())
+}
+
+/// How a file source destroys the bytes it reads (`wipe = "..."`).
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FileWipe {
+    /// Overwrite them with zeros.
+    Zero,
+    /// Deallocate them; the file keeps its size and reads zeros there.
+    PunchHole,
+}
+
+/// A QRNG on a serial line or USB-serial adapter (`[[sources.serial]]`),
+/// read raw with the given framing.
+#[derive(Debug, Deserialize, Clone)]
+pub struct SerialConfig {
+    pub id: String,
+    /// Device node, e.g. `/dev/ttyUSB0` or a `/dev/serial/by-id/...` link.
+    pub path: String,
+    #[serde(default)]
+    pub enabled: bool,
+    /// Line speed (default 115200); USB CDC devices ignore it.
+    #[serde(default)]
+    pub baud_rate: Option<u32>,
+    /// 5-8 (default 8).
+    #[serde(default)]
+    pub data_bits: Option<u8>,
+    /// Default `none`.
+    #[serde(default)]
+    pub parity: Option<Parity>,
+    /// 1 or 2 (default 1).
+    #[serde(default)]
+    pub stop_bits: Option<u8>,
+    /// Debias the line's bytes as they are read (default off).
+    #[serde(default)]
+    pub debias: Option<DebiasKind>,
+    /// Transforms applied in order after `debias`, e.g. `["lsb:4"]`.
+    #[serde(default)]
+    pub transforms: Vec<String>,
+    #[serde(default)]
+    pub buffer_mebibytes: Option<u32>,
+    /// Settings every kind of source takes.
+    #[serde(flatten)]
+    pub common: SourceCommon,
+    #[serde(default)]
+    pub retry: Option<RetryConfig>,
+}
+
+/// A QRNG web API polled over HTTP(S) (`[[sources.http]]`).
+#[derive(Debug, Deserialize, Clone)]
+pub struct HttpConfig {
+    pub id: String,
+    /// `http://` or `https://` URL; `{bytes}` is replaced by the request size.
+    pub url: String,
+    #[serde(default)]
+    pub enabled: bool,
+    /// Time between fetches, in ms (default 1000).
+    #[serde(default)]
+    pub poll_interval_ms: Option<u64>,
+    /// Bytes asked for per fetch (default 1024).
+    #[serde(default)]
+    pub request_bytes: Option<usize>,
+    /// Default `raw`.
+    #[serde(default)]
+    pub format: Option<HttpFormat>,
+    /// Where a `json` response keeps the bytes (default "/data").
+    #[serde(default)]
+    pub json_pointer: Option<String>,
+    /// Sent with every request, e.g. "Authorization: Bearer <token>".
+    #[serde(default)]
+    pub auth_header: Option<String>,
+    /// Per fetch, in ms (default 10000).
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// Default 1.
+    #[serde(default)]
+    pub buffer_mebibytes: Option<u32>,
+    /// Settings every kind of source takes.
+    #[serde(flatten)]
+    pub common: SourceCommon,
+    #[serde(default)]
+    pub retry: Option<RetryConfig>,
+}
+
+/// How an HTTP source's response body carries the bytes.
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HttpFormat {
+    /// The body is the bytes.
+    #[default]
+    Raw,
+    /// A JSON document with a base64 string or an array of byte values at `json_pointer`.
+    Json,
+}
+
+/// The CPU's own generator (`[[sources.cpu]]`), x86-64 only.
+#[derive(Debug, Deserialize, Clone)]
+pub struct CpuConfig {
+    pub id: String,
+    #[serde(default)]
+    pub enabled: bool,
+    /// Default `rdseed`, which falls back to `rdrand` on CPUs without it.
+    #[serde(default)]
+    pub instruction: Option<CpuInstruction>,
+    #[serde(default)]
+    pub buffer_mebibytes: Option<u32>,
+    /// Settings every kind of source takes.
+    #[serde(flatten)]
+    pub common: SourceCommon,
+}
+
+/// Timer jitter of the host CPU (`[[sources.jitter]]`), for deployments
+/// without a second hardware source.
+#[derive(Debug, Deserialize, Clone)]
+pub struct JitterConfig {
+    pub id: String,
+    #[serde(default)]
+    pub enabled: bool,
+    /// Default 1; the source is always buffered.
+    #[serde(default)]
+    pub buffer_mebibytes: Option<u32>,
+    /// Settings every kind of source takes.
+    #[serde(flatten)]
+    pub common: SourceCommon,
+    #[serde(default)]
+    pub retry: Option<RetryConfig>,
+}
+
+/// The kernel's hw_random device (`[[sources.hwrng]]`), e.g. a TPM or
+/// virtio-rng, together with what sysfs says about the driver behind it.
+#[derive(Debug, Deserialize, Clone)]
+pub struct HwrngConfig {
+    pub id: String,
+    #[serde(default)]
+    pub enabled: bool,
+    /// Default `/dev/hwrng`.
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Directory with `rng_current` and `rng_quality` (default `/sys/class/misc/hw_random`).
+    #[serde(default)]
+    pub sysfs: Option<String>,
+    #[serde(default)]
+    pub buffer_mebibytes: Option<u32>,
+    /// Settings every kind of source takes.
+    #[serde(flatten)]
+    pub common: SourceCommon,
+    #[serde(default)]
+    pub retry: Option<RetryConfig>,
+}
+
+/// The stdout of an external command (`[[sources.exec]]`), such as a vendor
+/// tool that writes random bytes until it is stopped.
+#[derive(Debug, Deserialize, Clone)]
+pub struct ExecConfig {
+    pub id: String,
+    #[serde(default)]
+    pub enabled: bool,
+    /// Program and arguments (argv, no shell).
+    pub command: Vec<String>,
+    /// Default 1; the source is always buffered.
+    #[serde(default)]
+    pub buffer_mebibytes: Option<u32>,
+    /// Settings every kind of source takes.
+    #[serde(flatten)]
+    pub common: SourceCommon,
+    /// Backoff between restarts of the command; `max_attempts` does not apply.
+    #[serde(default)]
+    pub retry: Option<RetryConfig>,
+}
+
+/// `C_GenerateRandom` of a PKCS#11 token (`[[sources.pkcs11]]`), such as an HSM.
+#[derive(Debug, Deserialize, Clone)]
+pub struct Pkcs11Config {
+    pub id: String,
+    #[serde(default)]
+    pub enabled: bool,
+    /// Path of the vendor's PKCS#11 module, e.g. `/usr/lib/softhsm/libsofthsm2.so`.
+    pub module: String,
+    /// Slot id of the token.
+    pub slot: u64,
+    /// File holding the user PIN; without one the session is not logged in.
+    #[serde(default)]
+    pub pin_file: Option<String>,
+    /// Default 1; the source is always buffered.
+    #[serde(default)]
+    pub buffer_mebibytes: Option<u32>,
+    /// Settings every kind of source takes.
+    #[serde(flatten)]
+    pub common: SourceCommon,
+    /// Backoff between attempts to reopen the session; `max_attempts` does not apply.
+    #[serde(default)]
+    pub retry: Option<RetryConfig>,
+}
+
+/// `ReadBytes` of another `lv.lumii.trng.Rng`-compatible service
+/// (`[[sources.dbus]]`), to chain services across hosts.
+#[derive(Debug, Deserialize, Clone)]
+pub struct DbusConfig {
+    pub id: String,
+    #[serde(default)]
+    pub enabled: bool,
+    /// `session` (default), `system`, or a D-Bus address such as
+    /// `tcp:host=qrng.example.org,port=55556`.
+    #[serde(default)]
+    pub bus: Option<String>,
+    /// Default `external`; buses reached over TCP usually need `anonymous`.
+    #[serde(default)]
+    pub auth: Option<DbusAuth>,
+    /// Bus name of the upstream service (default "lv.lumii.trng").
+    #[serde(default)]
+    pub service: Option<String>,
+    /// Default `/lv/lumii/trng/SourceXorAggregator`.
+    #[serde(default)]
+    pub object_path: Option<String>,
+    /// Default `lv.lumii.trng.Rng`, or a group interface of the upstream.
+    #[serde(default)]
+    pub interface: Option<String>,
+    /// Bytes asked for per call (default 4096).
+    #[serde(default)]
+    pub request_bytes: Option<usize>,
+    /// Passed to the upstream's `ReadBytes`, in ms (default 3000).
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// Default 1; the source is always buffered.
+    #[serde(default)]
+    pub buffer_mebibytes: Option<u32>,
+    /// Settings every kind of source takes.
+    #[serde(flatten)]
+    pub common: SourceCommon,
+    /// Backoff between failed calls; `max_attempts` does not apply.
+    #[serde(default)]
+    pub retry: Option<RetryConfig>,
+}
+
+/// SASL mechanism a D-Bus source authenticates to its bus with.
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DbusAuth {
+    /// The peer's uid, which only Unix sockets can pass.
+    #[default]
+    External,
+    /// No authentication, for buses that `<allow_anonymous/>`.
+    Anonymous,
+}
+
+/// Instruction a CPU source draws from.
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CpuInstruction {
+    /// Conditioned seed output, meant for seeding other generators.
+    #[default]
+    Rdseed,
+    /// The output of a DRBG reseeded from the same source as RDSEED.
+    Rdrand,
+}
+
+/// Parity bit of a serial source's framing.
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Parity {
+    #[default]
+    None,
+    Even,
+    Odd,
+}
+
+/// A recurring maintenance window (`maintenance = [{ ... }]` inside a source
+/// block). While it is open the source is paused and left out of requests,
+/// readiness and empty-buffer alerts.
+#[derive(Debug, Deserialize, Clone)]
+pub struct MaintenanceConfig {
+    /// Window start times in local time: "minute hour day month weekday".
+    pub cron: String,
+    /// Window length in minutes (1-1440).
+    pub duration_minutes: u32,
+    /// Default `retain`.
+    #[serde(default)]
+    pub buffer: Option<MaintenanceBuffer>,
+}
+
+/// What happens to a source's buffered bytes when a maintenance window opens.
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MaintenanceBuffer {
+    /// Keep them for after the window.
+    #[default]
+    Retain,
+    /// Zeroize them, e.g. when recalibration invalidates earlier output.
+    Drain,
+}
+
+/// Retry/backoff settings for re-opening or re-initializing a source
+/// (`retry = { ... }` inside a source block). Unset fields use the defaults
+/// noted below; `max_attempts = 0` retries forever.
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct RetryConfig {
+    /// Default 5.
+    #[serde(default)]
+    pub max_attempts: Option<u32>,
+    /// Default 100.
+    #[serde(default)]
+    pub initial_backoff_ms: Option<u64>,
+    /// Default 30000.
+    #[serde(default)]
+    pub max_backoff_ms: Option<u64>,
+    /// Default 2.0.
+    #[serde(default)]
+    pub multiplier: Option<f64>,
+    /// Default 0.2.
+    #[serde(default)]
+    pub jitter: Option<f64>,
+}
+
+/// A remote entropy server, e.g. a QRNG appliance on the LAN, that streams
+/// raw bytes to whoever connects (`[[sources.tcp]]`).
+#[derive(Debug, Deserialize, Clone)]
+pub struct TcpConfig {
+    pub id: String,
+    #[serde(default)]
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// Per connection attempt, TLS handshake included, in ms (default 5000).
+    #[serde(default)]
+    pub connect_timeout_ms: Option<u64>,
+    /// A connection that delivers nothing for this long while the buffer has
+    /// room is reopened, in ms (default 10000).
+    #[serde(default)]
+    pub idle_timeout_ms: Option<u64>,
+    /// Default 1; the source is always buffered.
+    #[serde(default)]
+    pub buffer_mebibytes: Option<u32>,
+    /// Settings every kind of source takes.
+    #[serde(flatten)]
+    pub common: SourceCommon,
+    #[serde(default)]
+    pub retry: Option<RetryConfig>,
+}
+
+/// A topic on an MQTT broker that entropy beacons publish binary payloads
+/// to (`[[sources.mqtt]]`). Payloads that fail the sanity checks are
+/// dropped and counted against their publisher.
+#[derive(Debug, Deserialize, Clone)]
+pub struct MqttConfig {
+    pub id: String,
+    #[serde(default)]
+    pub enabled: bool,
+    /// `mqtt://host[:port]` (default port 1883) or `mqtts://host[:port]` (8883).
+    pub broker: String,
+    /// Topic filter; the levels matched by `+` or `#` name the publisher,
+    /// e.g. `beacons/+/entropy`.
+    pub topic: String,
+    /// 0, 1 or 2 (default 1).
+    #[serde(default)]
+    pub qos: Option<u8>,
+    /// Default `trng-dbus-<id>`.
+    #[serde(default)]
+    pub client_id: Option<String>,
+    #[serde(default)]
+    pub username: Option<String>,
+    /// File holding the password, which needs a username.
+    #[serde(default)]
+    pub password_file: Option<String>,
+    /// Default 30, 0 disables pings.
+    #[serde(default)]
+    pub keep_alive_seconds: Option<u16>,
+    /// Needed with `mqtts://`.
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// Per connection attempt, TLS handshake included, in ms (default 5000).
+    #[serde(default)]
+    pub connect_timeout_ms: Option<u64>,
+    /// Shorter payloads are rejected (default 16).
+    #[serde(default)]
+    pub min_payload_bytes: Option<usize>,
+    /// Longer payloads are rejected (default 65536).
+    #[serde(default)]
+    pub max_payload_bytes: Option<usize>,
+    /// Default 1; the source is always buffered.
+    #[serde(default)]
+    pub buffer_mebibytes: Option<u32>,
+    /// Settings every kind of source takes.
+    #[serde(flatten)]
+    pub common: SourceCommon,
+    #[serde(default)]
+    pub retry: Option<RetryConfig>,
+}
+
+/// Noise in the low bits of an ALSA capture device (`[[sources.audio]]`),
+/// e.g. an unconnected line or microphone input, debiased before it is
+/// buffered. Needs alsa-lib (`libasound.so.2`) at runtime.
+#[derive(Debug, Deserialize, Clone)]
+pub struct AudioConfig {
+    pub id: String,
     #[serde(default)]
     pub enabled: bool,
+    /// ALSA PCM name, e.g. `hw:1,0` (default `default`).
+    #[serde(default)]
+    pub device: Option<String>,
+    /// In Hz, 8000-384000 (default 48000); the device must support it, it is not resampled.
+    #[serde(default)]
+    pub sample_rate: Option<u32>,
+    /// Lowest bits of each 16-bit sample harvested, 1-8 (default 1).
+    #[serde(default)]
+    pub bits_per_sample: Option<u32>,
+    /// 1-8 (default 1).
+    #[serde(default)]
+    pub channels: Option<u32>,
+    /// Default 1; the source is always buffered.
+    #[serde(default)]
+    pub buffer_mebibytes: Option<u32>,
+    /// Settings every kind of source takes.
+    #[serde(flatten)]
+    pub common: SourceCommon,
+    /// Backoff between attempts to reopen the device; `max_attempts` does not apply.
+    #[serde(default)]
+    pub retry: Option<RetryConfig>,
+}
+
+/// A gRPC server streaming entropy from `GetRandom` (`docs/entropy.proto`),
+/// called over one HTTP/2 connection (`[[sources.grpc]]`). Only honored by
+/// builds with the `grpc` feature.
+#[derive(Debug, Deserialize, Clone)]
+#[cfg_attr(not(feature = "grpc"), allow(dead_code))]
+pub struct GrpcConfig {
+    pub id: String,
+    #[serde(default)]
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    /// `/package.Service/Method` of a method with the messages of
+    /// `GetRandom` (default `/trng.v1.Entropy/GetRandom`).
+    #[serde(default)]
+    pub method: Option<String>,
+    /// Sent with every call, e.g. "Authorization: Bearer <token>".
+    #[serde(default)]
+    pub auth_header: Option<String>,
+    /// `tls` makes it `https`, offering HTTP/2 by ALPN; without it calls
+    /// go in the clear over HTTP/2 with prior knowledge.
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// Per connection attempt, TLS handshake included, in ms (default 5000).
+    #[serde(default)]
+    pub connect_timeout_ms: Option<u64>,
+    /// Deadline of each call, passed to the server as `grpc-timeout`, in ms
+    /// (default 3000).
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// Bytes asked for per call (default 65536).
+    #[serde(default)]
+    pub request_bytes: Option<usize>,
+    /// Default 1; the source is always buffered.
+    #[serde(default)]
+    pub buffer_mebibytes: Option<u32>,
+    /// Settings every kind of source takes.
+    #[serde(flatten)]
+    pub common: SourceCommon,
+    #[serde(default)]
+    pub retry: Option<RetryConfig>,
+}
+
+/// A durable pull consumer on a NATS JetStream stream of entropy blocks
+/// (`[[sources.nats]]`), shared by every instance of the fleet so each
+/// block is handed to one of them. Only honored by builds with the `nats`
+/// feature.
+#[derive(Debug, Deserialize, Clone)]
+#[cfg_attr(not(feature = "nats"), allow(dead_code))]
+pub struct NatsConfig {
+    pub id: String,
+    #[serde(default)]
+    pub enabled: bool,
+    /// `nats://host[:port]` (default port 4222); `tls` upgrades the connection.
+    pub server: String,
+    /// JetStream stream holding the blocks.
+    pub stream: String,
+    /// Durable consumer name; created on the stream if missing (default `trng-dbus`).
+    #[serde(default)]
+    pub consumer: Option<String>,
+    /// Filter subject of a consumer this service creates (default the whole stream).
+    #[serde(default)]
+    pub subject: Option<String>,
+    /// Messages asked for per pull request (default 16).
+    #[serde(default)]
+    pub batch: Option<u32>,
+    #[serde(default)]
+    pub username: Option<String>,
+    /// File holding the password, which needs a username.
+    #[serde(default)]
+    pub password_file: Option<String>,
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// Per connection attempt, TLS upgrade included, in ms (default 5000).
+    #[serde(default)]
+    pub connect_timeout_ms: Option<u64>,
+    /// Default 1; the source is always buffered.
+    #[serde(default)]
+    pub buffer_mebibytes: Option<u32>,
+    /// Settings every kind of source takes.
+    #[serde(flatten)]
+    pub common: SourceCommon,
+    #[serde(default)]
+    pub retry: Option<RetryConfig>,
+}
+
+/// TLS settings shared by network sources (`tls = { ... }` inside a source block).
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct TlsConfig {
+    /// PEM bundle of the only CAs trusted for the server certificate.
+    #[serde(default)]
+    pub ca_file: Option<String>,
+    /// Hex SHA-256 fingerprints of acceptable server leaf certificates (DER).
+    #[serde(default)]
+    pub pin_sha256: Vec<String>,
+    /// PEM client certificate chain and key for mutual authentication.
+    #[serde(default)]
+    pub client_cert: Option<String>,
+    #[serde(default)]
+    pub client_key: Option<String>,
+    /// Name to verify instead of the connect host.
+    #[serde(default)]
+    pub server_name: Option<String>,
+    /// Warn this many days before a configured certificate expires (default 30).
+    #[serde(default)]
+    pub expiry_warning_days: Option<u32>,
+}
+
+/// Circuit breaker settings (`breaker = { ... }` inside a source block).
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct BreakerConfig {
+    /// Consecutive failed requests before the source is skipped (default 3).
+    #[serde(default)]
+    pub failure_threshold: Option<u32>,
+    /// How long the source is skipped before a probe request (default 30000).
     #[serde(default)]
-    pub buffer_mebibytes: Option<u32>,
+    pub open_ms: Option<u64>,
 }
 
+/// How the aggregator combines what the sources answered (`combine = "..."`).
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
 pub enum CombineMode {
+    /// XOR of the common prefix.
     Xor,
+    /// SHA3-256 in counter mode over the common prefixes (see `sha3.rs`).
+    Sha3,
+    /// XOR, with each source's bytes first XOR-folded by as many as it takes
+    /// to carry 8 bits of assessed entropy (`entropy_per_byte`).
+    WeightedXor,
+}
+
+impl CombineMode {
+    pub const ALL: [CombineMode; 3] = [CombineMode::Xor, CombineMode::Sha3, CombineMode::WeightedXor];
+
+    /// As written in the config.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CombineMode::Xor => "xor",
+            CombineMode::Sha3 => "sha3",
+            CombineMode::WeightedXor => "weighted_xor",
+        }
+    }
+}
+
+/// `pool = "..."` of `[sources]`.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolKind {
+    None,
+    /// A Keccak-f[1600] sponge (see `pool.rs`).
+    Keccak,
+    /// A Fortuna accumulator and generator (see `fortuna.rs`).
+    Fortuna,
 }
 
 pub struct FlattenedConfig {
     pub combine: CombineMode,
+    /// Set unless `none`.
+    pub pool: Option<PoolKind>,
     pub lrng_sources: Vec<LrngConfig>,
     pub file_sources: Vec<FileConfig>,
+    pub serial_sources: Vec<SerialConfig>,
+    pub tcp_sources: Vec<TcpConfig>,
+    pub http_sources: Vec<HttpConfig>,
+    pub cpu_sources: Vec<CpuConfig>,
+    pub jitter_sources: Vec<JitterConfig>,
+    pub mqtt_sources: Vec<MqttConfig>,
+    pub hwrng_sources: Vec<HwrngConfig>,
+    pub nats_sources: Vec<NatsConfig>,
+    pub exec_sources: Vec<ExecConfig>,
+    pub pkcs11_sources: Vec<Pkcs11Config>,
+    pub audio_sources: Vec<AudioConfig>,
+    pub dbus_sources: Vec<DbusConfig>,
+    pub grpc_sources: Vec<GrpcConfig>,
+    pub benchmark_ms: u64,
+    pub startup: StartupPolicy,
+    pub startup_timeout_ms: u64,
+    pub entropy_low_percent: Option<f64>,
+    pub alerts: Option<AlertConfig>,
+    pub subscriptions: Option<SubscriptionConfig>,
+    pub quotas: Option<QuotaConfig>,
+    pub access: Option<AccessConfig>,
+    pub readiness: Option<ReadinessConfig>,
+    pub scheduler: Option<SchedulerConfig>,
+    pub runtime: Option<RuntimeConfig>,
+    pub bus: BusKind,
+    /// `[service] name`, or `BUS_NAME`.
+    pub bus_name: String,
+    /// `[service] object_path`, or `DEFAULT_OBJECT_PATH`.
+    pub object_path: String,
+    /// `[service] max_request_bytes`, or `DEFAULT_MAX_REQUEST_BYTES`.
+    pub max_request_bytes: usize,
+    /// `[service] max_async_requests_per_client`, or `DEFAULT_MAX_ASYNC_REQUESTS_PER_CLIENT`.
+    pub max_async_requests_per_client: usize,
+    pub fallback: FallbackPolicy,
+    pub chaos: Option<ChaosConfig>,
+    pub attestation: Option<AttestationConfig>,
+    pub client_streams: Option<ClientStreamsConfig>,
+    pub ledger: Option<LedgerConfig>,
+    /// Validated, each with its object path filled in.
+    pub groups: Vec<GroupConfig>,
+    pub watchdog: Option<WatchdogConfig>,
+    /// Validated, so the shares sum to at most 100.
+    pub latency_budget: Option<LatencyBudgetConfig>,
+    pub reservations: Option<ReservationConfig>,
+    pub relay: Option<RelayConfig>,
+    pub trending: Option<TrendingConfig>,
+    pub memory: Option<MemoryConfig>,
+    pub drbg: Option<DrbgConfig>,
+    pub hashes: ConfigHashes,
+}
+
+/// SHA-256 digests (hex) of the loaded config, which tell configs apart in
+/// stats snapshots without revealing them.
+#[derive(Debug, Default, Clone)]
+pub struct ConfigHashes {
+    pub path: String,
+    /// Of the file as read.
+    pub file: String,
+    /// Of each top-level section and each source block (`sources.<kind>.<id>`),
+    /// re-serialized so that comments and layout do not count.
+    pub sections: BTreeMap<String, String>,
+}
+
+impl ConfigHashes {
+    fn new(path: &str, content: &str) -> Self {
+        let digest = |text: &str| hex::encode(Sha256::digest(text.as_bytes()));
+        let mut sections = BTreeMap::new();
+        let table = content.parse::<toml::Table>().unwrap_or_default();
+        for (name, value) in &table {
+            sections.insert(name.clone(), digest(&toml::to_string(value).unwrap_or_default()));
+        }
+        if let Some(toml::Value::Table(sources)) = table.get("sources") {
+            for (kind, list) in sources.iter().filter_map(|(kind, v)| Some((kind, v.as_array()?))) {
+                for block in list.iter().filter_map(toml::Value::as_table) {
+                    let Some(id) = block.get("id").and_then(toml::Value::as_str) else { continue };
+                    sections.insert(format!("sources.{}.{}", kind, id), digest(&toml::to_string(block).unwrap_or_default()));
+                }
+            }
+        }
+        Self { path: path.to_string(), file: digest(content), sections }
+    }
 }
 
+/// Well-known name the service takes on its bus unless `[service] name` is set.
+pub const BUS_NAME: &str = "lv.lumii.trng";
+/// Interface every service is reachable on; `[[groups]]` add more.
+pub const DEFAULT_INTERFACE: &str = "lv.lumii.trng.Rng";
+/// Read-only stats, health and config introspection, at `DEFAULT_OBJECT_PATH`.
+pub const MONITOR_INTERFACE: &str = "lv.lumii.trng.Monitor";
+pub const DEFAULT_OBJECT_PATH: &str = "/lv/lumii/trng/SourceXorAggregator";
+/// `[latency_budget] gather_percent` unless set.
+pub const DEFAULT_GATHER_PERCENT: u8 = 80;
+/// `[latency_budget] condition_percent` unless set; the reply keeps the remaining 5.
+pub const DEFAULT_CONDITION_PERCENT: u8 = 15;
+/// MQTT `min_payload_bytes` unless set.
+pub const DEFAULT_MQTT_MIN_PAYLOAD_BYTES: usize = 16;
+/// MQTT `max_payload_bytes` unless set.
+pub const DEFAULT_MQTT_MAX_PAYLOAD_BYTES: usize = 64 * 1024;
+/// Audio `sample_rate` unless set.
+pub const DEFAULT_AUDIO_SAMPLE_RATE: u32 = 48_000;
+/// Audio `bits_per_sample` unless set.
+pub const DEFAULT_AUDIO_BITS_PER_SAMPLE: u32 = 1;
+
+/// Lowest `entropy_per_byte`, so `weighted_xor` reads at most 64 bytes from
+/// a source per output byte.
+pub const MIN_ENTROPY_PER_BYTE: f64 = 0.125;
+/// Largest request served unless `[service] max_request_bytes` is set: the
+/// longest byte array a D-Bus message can carry (2^26 bytes).
+pub const DEFAULT_MAX_REQUEST_BYTES: u64 = 64 * 1024 * 1024;
+/// `RequestBytesAsync` requests a client may have running unless
+/// `[service] max_async_requests_per_client` is set.
+pub const DEFAULT_MAX_ASYNC_REQUESTS_PER_CLIENT: usize = 8;
+
+/// Most `[[groups]]` one service exports.
+pub const MAX_GROUPS: usize = 8;
+
 pub fn load_config(path: &str) -> Result<FlattenedConfig, Box<dyn std::error::Error>> {
     
     if !Path::new(path).exists() {
@@ -62,24 +1478,58 @@ pub fn load_config(path: &str) -> Result<FlattenedConfig, Box<dyn std::error::Er
     
     let cfg: Config = toml::from_str(&content)
         .map_err(|e| format!("Failed to parse TOML config {}: {}", path, e))?;
+    let hashes = ConfigHashes::new(path, &content);
     
     log::info!("Config loaded from: {}", path);
     
     // Log what sources will be processed
-    let total_sources = cfg.sources.lrng.len() + cfg.sources.file.len();
+    let total_sources = cfg.sources.lrng.len() + cfg.sources.file.len() + cfg.sources.serial.len() + cfg.sources.tcp.len() + cfg.sources.http.len() + cfg.sources.cpu.len()
+        + cfg.sources.jitter.len() + cfg.sources.mqtt.len() + cfg.sources.hwrng.len() + cfg.sources.nats.len() + cfg.sources.exec.len()
+        + cfg.sources.pkcs11.len() + cfg.sources.audio.len()
+        + cfg.sources.dbus.len() + cfg.sources.grpc.len();
     log::info!("Found {} total sources in config", total_sources);
 
     // Process sources
     let mut combine = CombineMode::Xor;
     let mut lrng_sources = Vec::new();
     let mut file_sources = Vec::new();
+    let mut serial_sources = Vec::new();
+    let mut tcp_sources = Vec::new();
+    let mut http_sources = Vec::new();
+    let mut cpu_sources = Vec::new();
+    let mut jitter_sources = Vec::new();
+    let mut mqtt_sources = Vec::new();
+    let mut hwrng_sources = Vec::new();
+    let mut nats_sources = Vec::new();
+    let mut exec_sources = Vec::new();
+    let mut pkcs11_sources = Vec::new();
+    let mut audio_sources = Vec::new();
+    let mut dbus_sources = Vec::new();
+    let mut grpc_sources = Vec::new();
     let mut seen_ids: HashSet<String> = HashSet::new();
     
     if let Some(c) = cfg.sources.combine.as_deref() {
         if c.eq_ignore_ascii_case("xor") {
             combine = CombineMode::Xor;
+        } else if c.eq_ignore_ascii_case("sha3") {
+            combine = CombineMode::Sha3;
+        } else if c.eq_ignore_ascii_case("weighted_xor") {
+            combine = CombineMode::WeightedXor;
+        } else {
+            error!("Unknown combine mode '{}' - using xor", c);
         }
     }
+    let pool = cfg.sources.pool.filter(|p| *p != PoolKind::None);
+    if pool.is_some() && cfg.sources.combine.is_some() {
+        log::warn!("combine does not apply with a pool - ignoring it");
+    }
+    let entropy_low_percent = cfg.sources.entropy_low_percent.filter(|p| {
+        let valid = *p > 0.0 && *p < 100.0;
+        if !valid {
+            error!("entropy_low_percent must be above 0 and below 100 - ignoring it");
+        }
+        valid
+    });
     
     for s in cfg.sources.lrng.into_iter().filter(|s| s.enabled) {
         if !is_valid_id(&s.id) {
@@ -90,6 +1540,17 @@ pub fn load_config(path: &str) -> Result<FlattenedConfig, Box<dyn std::error::Er
             error!("Duplicate source id '{}' - skipping", s.id);
             continue;
         }
+        if !valid_common(&s.id, &s.common) {
+            continue;
+        }
+        let watermarks = (s.low_watermark_percent, s.high_watermark_percent);
+        if !valid_replenish(&s.id, s.replenish_interval_ms, s.replenish_chunk_bytes, watermarks) {
+            continue;
+        }
+        if s.max_bytes_per_sec == Some(0) {
+            error!("Source {}: max_bytes_per_sec must be above 0 - skipping", s.id);
+            continue;
+        }
         lrng_sources.push(s);
     }
     for s in cfg.sources.file.into_iter().filter(|s| s.enabled) {
@@ -101,19 +1562,445 @@ pub fn load_config(path: &str) -> Result<FlattenedConfig, Box<dyn std::error::Er
             error!("Duplicate source id '{}' - skipping", s.id);
             continue;
         }
+        if !valid_common(&s.id, &s.common) {
+            continue;
+        }
+        if s.wipe.is_some() && (s.loop_ == Some(true) || !s.public_keys.is_empty()) {
+            error!("File {}: wipe cannot be combined with loop or public_keys - skipping", s.id);
+            continue;
+        }
+        let needs_seeking = s.loop_ == Some(true) || s.manifest.is_some() || s.wipe.is_some() || !s.public_keys.is_empty();
+        if s.stream == Some(true) && needs_seeking {
+            error!("File {}: stream cannot be combined with loop, manifest, wipe or public_keys - skipping", s.id);
+            continue;
+        }
+        if !valid_replenish(&s.id, s.replenish_interval_ms, s.replenish_chunk_bytes, (s.low_watermark_percent, None)) {
+            continue;
+        }
+        if s.max_bytes_per_sec == Some(0) {
+            error!("Source {}: max_bytes_per_sec must be above 0 - skipping", s.id);
+            continue;
+        }
+        if let Some(e) = invalid_transform(&s.transforms) {
+            error!("File {}: {} - skipping", s.id, e);
+            continue;
+        }
         file_sources.push(s);
     }
+    for s in cfg.sources.serial.into_iter().filter(|s| s.enabled) {
+        if !is_valid_id(&s.id) {
+            error!("Invalid source id '{}'. Use [a-z0-9][a-z0-9_-]*", s.id);
+            continue;
+        }
+        if !seen_ids.insert(s.id.clone()) {
+            error!("Duplicate source id '{}' - skipping", s.id);
+            continue;
+        }
+        if !valid_common(&s.id, &s.common) {
+            continue;
+        }
+        if let Some(e) = invalid_transform(&s.transforms) {
+            error!("Serial {}: {} - skipping", s.id, e);
+            continue;
+        }
+        serial_sources.push(s);
+    }
+    for s in cfg.sources.tcp.into_iter().filter(|s| s.enabled) {
+        if !is_valid_id(&s.id) {
+            error!("Invalid source id '{}'. Use [a-z0-9][a-z0-9_-]*", s.id);
+            continue;
+        }
+        if !seen_ids.insert(s.id.clone()) {
+            error!("Duplicate source id '{}' - skipping", s.id);
+            continue;
+        }
+        if !valid_common(&s.id, &s.common) {
+            continue;
+        }
+        tcp_sources.push(s);
+    }
+    for s in cfg.sources.http.into_iter().filter(|s| s.enabled) {
+        if !is_valid_id(&s.id) {
+            error!("Invalid source id '{}'. Use [a-z0-9][a-z0-9_-]*", s.id);
+            continue;
+        }
+        if !seen_ids.insert(s.id.clone()) {
+            error!("Duplicate source id '{}' - skipping", s.id);
+            continue;
+        }
+        if !valid_common(&s.id, &s.common) {
+            continue;
+        }
+        http_sources.push(s);
+    }
+    for s in cfg.sources.cpu.into_iter().filter(|s| s.enabled) {
+        if !is_valid_id(&s.id) {
+            error!("Invalid source id '{}'. Use [a-z0-9][a-z0-9_-]*", s.id);
+            continue;
+        }
+        if !seen_ids.insert(s.id.clone()) {
+            error!("Duplicate source id '{}' - skipping", s.id);
+            continue;
+        }
+        if !valid_common(&s.id, &s.common) {
+            continue;
+        }
+        cpu_sources.push(s);
+    }
+    for s in cfg.sources.jitter.into_iter().filter(|s| s.enabled) {
+        if !is_valid_id(&s.id) {
+            error!("Invalid source id '{}'. Use [a-z0-9][a-z0-9_-]*", s.id);
+            continue;
+        }
+        if !seen_ids.insert(s.id.clone()) {
+            error!("Duplicate source id '{}' - skipping", s.id);
+            continue;
+        }
+        if !valid_common(&s.id, &s.common) {
+            continue;
+        }
+        jitter_sources.push(s);
+    }
+    for mut s in cfg.sources.mqtt.into_iter().filter(|s| s.enabled) {
+        if !is_valid_id(&s.id) {
+            error!("Invalid source id '{}'. Use [a-z0-9][a-z0-9_-]*", s.id);
+            continue;
+        }
+        if !seen_ids.insert(s.id.clone()) {
+            error!("Duplicate source id '{}' - skipping", s.id);
+            continue;
+        }
+        if !valid_common(&s.id, &s.common) {
+            continue;
+        }
+        let min = s.min_payload_bytes.unwrap_or(DEFAULT_MQTT_MIN_PAYLOAD_BYTES);
+        let max = s.max_payload_bytes.unwrap_or(DEFAULT_MQTT_MAX_PAYLOAD_BYTES);
+        if min == 0 || min > max {
+            error!("MQTT source {}: min_payload_bytes must be positive and at most max_payload_bytes - using the defaults", s.id);
+            s.min_payload_bytes = None;
+            s.max_payload_bytes = None;
+        }
+        mqtt_sources.push(s);
+    }
+    for s in cfg.sources.hwrng.into_iter().filter(|s| s.enabled) {
+        if !is_valid_id(&s.id) {
+            error!("Invalid source id '{}'. Use [a-z0-9][a-z0-9_-]*", s.id);
+            continue;
+        }
+        if !seen_ids.insert(s.id.clone()) {
+            error!("Duplicate source id '{}' - skipping", s.id);
+            continue;
+        }
+        if !valid_common(&s.id, &s.common) {
+            continue;
+        }
+        hwrng_sources.push(s);
+    }
+    for s in cfg.sources.nats.into_iter().filter(|s| s.enabled) {
+        if !is_valid_id(&s.id) {
+            error!("Invalid source id '{}'. Use [a-z0-9][a-z0-9_-]*", s.id);
+            continue;
+        }
+        if !seen_ids.insert(s.id.clone()) {
+            error!("Duplicate source id '{}' - skipping", s.id);
+            continue;
+        }
+        if !valid_common(&s.id, &s.common) {
+            continue;
+        }
+        nats_sources.push(s);
+    }
+    for s in cfg.sources.exec.into_iter().filter(|s| s.enabled) {
+        if !is_valid_id(&s.id) {
+            error!("Invalid source id '{}'. Use [a-z0-9][a-z0-9_-]*", s.id);
+            continue;
+        }
+        if !seen_ids.insert(s.id.clone()) {
+            error!("Duplicate source id '{}' - skipping", s.id);
+            continue;
+        }
+        if !valid_common(&s.id, &s.common) {
+            continue;
+        }
+        exec_sources.push(s);
+    }
+    for s in cfg.sources.pkcs11.into_iter().filter(|s| s.enabled) {
+        if !is_valid_id(&s.id) {
+            error!("Invalid source id '{}'. Use [a-z0-9][a-z0-9_-]*", s.id);
+            continue;
+        }
+        if !seen_ids.insert(s.id.clone()) {
+            error!("Duplicate source id '{}' - skipping", s.id);
+            continue;
+        }
+        if !valid_common(&s.id, &s.common) {
+            continue;
+        }
+        pkcs11_sources.push(s);
+    }
+    for mut s in cfg.sources.audio.into_iter().filter(|s| s.enabled) {
+        if !is_valid_id(&s.id) {
+            error!("Invalid source id '{}'. Use [a-z0-9][a-z0-9_-]*", s.id);
+            continue;
+        }
+        if !seen_ids.insert(s.id.clone()) {
+            error!("Duplicate source id '{}' - skipping", s.id);
+            continue;
+        }
+        if !valid_common(&s.id, &s.common) {
+            continue;
+        }
+        if s.sample_rate.is_some_and(|r| !(8_000..=384_000).contains(&r)) {
+            error!("Audio source {}: sample_rate must be 8000 to 384000 - using the default", s.id);
+            s.sample_rate = None;
+        }
+        if s.bits_per_sample.is_some_and(|b| !(1..=8).contains(&b)) {
+            error!("Audio source {}: bits_per_sample must be 1 to 8 - using the default", s.id);
+            s.bits_per_sample = None;
+        }
+        if s.channels.is_some_and(|c| !(1..=8).contains(&c)) {
+            error!("Audio source {}: channels must be 1 to 8 - using the default", s.id);
+            s.channels = None;
+        }
+        audio_sources.push(s);
+    }
+    for s in cfg.sources.dbus.into_iter().filter(|s| s.enabled) {
+        if !is_valid_id(&s.id) {
+            error!("Invalid source id '{}'. Use [a-z0-9][a-z0-9_-]*", s.id);
+            continue;
+        }
+        if !seen_ids.insert(s.id.clone()) {
+            error!("Duplicate source id '{}' - skipping", s.id);
+            continue;
+        }
+        if !valid_common(&s.id, &s.common) {
+            continue;
+        }
+        dbus_sources.push(s);
+    }
+    for mut s in cfg.sources.grpc.into_iter().filter(|s| s.enabled) {
+        if !is_valid_id(&s.id) {
+            error!("Invalid source id '{}'. Use [a-z0-9][a-z0-9_-]*", s.id);
+            continue;
+        }
+        if !seen_ids.insert(s.id.clone()) {
+            error!("Duplicate source id '{}' - skipping", s.id);
+            continue;
+        }
+        if !valid_common(&s.id, &s.common) {
+            continue;
+        }
+        if s.timeout_ms == Some(0) {
+            error!("gRPC source {}: timeout_ms must be positive - using the default", s.id);
+            s.timeout_ms = None;
+        }
+        grpc_sources.push(s);
+    }
+
+    log::info!(
+        "Enabled sources: {} lrng, {} file, {} serial, {} tcp, {} http, {} cpu, {} jitter, {} mqtt, {} hwrng, {} nats, {} exec, {} pkcs11, {} audio, {} dbus, {} grpc",
+        lrng_sources.len(),
+        file_sources.len(),
+        serial_sources.len(),
+        tcp_sources.len(),
+        http_sources.len(),
+        cpu_sources.len(),
+        jitter_sources.len(),
+        mqtt_sources.len(),
+        hwrng_sources.len(),
+        nats_sources.len(),
+        exec_sources.len(),
+        pkcs11_sources.len(),
+        audio_sources.len(),
+        dbus_sources.len(),
+        grpc_sources.len()
+    );
+
+    let service = cfg.service.unwrap_or_default();
+    let bus_name = service.name.unwrap_or_else(|| BUS_NAME.to_string());
+    if zbus::names::WellKnownName::try_from(bus_name.as_str()).is_err() {
+        return Err(format!("[service] name '{}' is not a valid well-known bus name", bus_name).into());
+    }
+    let object_path = service.object_path.unwrap_or_else(|| DEFAULT_OBJECT_PATH.to_string());
+    if zbus::zvariant::ObjectPath::try_from(object_path.as_str()).is_err() {
+        return Err(format!("[service] object_path '{}' is not a valid object path", object_path).into());
+    }
+    let groups = validate_groups(cfg.groups, &object_path);
+    let mut latency_budget = cfg.latency_budget;
+    if let Some(b) = latency_budget.as_mut() {
+        let gather = b.gather_percent.unwrap_or(DEFAULT_GATHER_PERCENT);
+        let condition = b.condition_percent.unwrap_or(DEFAULT_CONDITION_PERCENT);
+        if gather == 0 || u16::from(gather) + u16::from(condition) > 100 {
+            error!("[latency_budget] gather_percent must be positive and sum to at most 100 with condition_percent - using the defaults");
+            *b = LatencyBudgetConfig::default();
+        }
+    }
+    let mut max_request_bytes = service.max_request_bytes.unwrap_or(DEFAULT_MAX_REQUEST_BYTES);
+    if max_request_bytes == 0 {
+        error!("[service] max_request_bytes must be positive - using the default");
+        max_request_bytes = DEFAULT_MAX_REQUEST_BYTES;
+    }
+    let mut max_async_requests_per_client = service.max_async_requests_per_client.unwrap_or(DEFAULT_MAX_ASYNC_REQUESTS_PER_CLIENT);
+    if max_async_requests_per_client == 0 {
+        error!("[service] max_async_requests_per_client must be positive - using the default");
+        max_async_requests_per_client = DEFAULT_MAX_ASYNC_REQUESTS_PER_CLIENT;
+    }
 
-    log::info!("Enabled sources: {} lrng, {} file", lrng_sources.len(), file_sources.len());
+    let mut drbg = cfg.drbg;
+    if let Some(d) = drbg.as_mut().filter(|d| d.reseed_interval_bytes.is_some_and(|n| n == 0 || n > MAX_RESEED_INTERVAL)) {
+        error!("[drbg] reseed_interval_bytes must be 1..={} - using the default", MAX_RESEED_INTERVAL);
+        d.reseed_interval_bytes = None;
+    }
     
-    let total_enabled = lrng_sources.len() + file_sources.len();
+    let total_enabled = lrng_sources.len() + file_sources.len() + serial_sources.len() + tcp_sources.len() + http_sources.len() + cpu_sources.len()
+        + jitter_sources.len() + mqtt_sources.len() + hwrng_sources.len() + nats_sources.len() + exec_sources.len()
+        + pkcs11_sources.len() + audio_sources.len()
+        + dbus_sources.len() + grpc_sources.len();
     if total_enabled == 0 {
         log::warn!("No enabled entropy sources found in config - service will fail on requests");
     } else if total_enabled == 1 {
         log::warn!("Only one entropy source enabled - consider enabling multiple sources for better security");
     }
+    for g in groups.iter().filter(|g| g.combine.is_some() && pool.is_some()) {
+        log::warn!("Group {}: combine does not apply with a pool - ignoring it", g.name);
+    }
+    for g in groups.iter().filter(|g| g.min_sources.is_some_and(|n| n > total_enabled)) {
+        log::warn!("Group {}: min_sources is {} but only {} sources are enabled - its reads will fail", g.name, g.min_sources.unwrap_or(0), total_enabled);
+    }
     
-    Ok(FlattenedConfig { combine, lrng_sources, file_sources })
+    Ok(FlattenedConfig {
+        combine,
+        pool,
+        lrng_sources,
+        file_sources,
+        serial_sources,
+        tcp_sources,
+        http_sources,
+        cpu_sources,
+        jitter_sources,
+        mqtt_sources,
+        hwrng_sources,
+        nats_sources,
+        exec_sources,
+        pkcs11_sources,
+        audio_sources,
+        dbus_sources,
+        grpc_sources,
+        benchmark_ms: cfg.sources.benchmark_ms.unwrap_or(1000),
+        startup: cfg.sources.startup.unwrap_or_default(),
+        startup_timeout_ms: cfg.sources.startup_timeout_ms.unwrap_or(60_000),
+        entropy_low_percent,
+        alerts: cfg.alerts,
+        subscriptions: cfg.subscriptions,
+        quotas: cfg.quotas,
+        access: cfg.access,
+        readiness: cfg.readiness,
+        scheduler: cfg.scheduler,
+        runtime: cfg.runtime,
+        bus: service.bus.unwrap_or_default(),
+        bus_name,
+        object_path,
+        max_request_bytes: usize::try_from(max_request_bytes).unwrap_or(usize::MAX),
+        max_async_requests_per_client,
+        fallback: cfg.fallback.and_then(|f| f.policy).unwrap_or_default(),
+        chaos: cfg.chaos,
+        attestation: cfg.attestation,
+        client_streams: cfg.client_streams,
+        ledger: cfg.ledger,
+        groups,
+        watchdog: cfg.watchdog,
+        latency_budget,
+        reservations: cfg.reservations,
+        relay: cfg.relay,
+        trending: cfg.trending,
+        memory: cfg.memory,
+        drbg,
+        hashes,
+    })
+}
+
+/// Checks a source's replenish settings, logging the first one out of range.
+/// `watermarks` are the low and high watermark percentages.
+fn valid_replenish(id: &str, interval_ms: Option<u64>, chunk_bytes: Option<usize>, watermarks: (Option<u8>, Option<u8>)) -> bool {
+    let bad = if interval_ms.is_some_and(|ms| ms == 0 || ms > MAX_REPLENISH_INTERVAL_MS) {
+        "replenish_interval_ms must be 1-60000"
+    } else if chunk_bytes.is_some_and(|c| c == 0 || c > MAX_REPLENISH_CHUNK) {
+        "replenish_chunk_bytes must be 1 byte to 16 MiB"
+    } else if watermarks.0.is_some_and(|w| !(1..=100).contains(&w)) {
+        "low_watermark_percent must be 1-100"
+    } else if watermarks.1.is_some_and(|w| !(1..=100).contains(&w)) {
+        "high_watermark_percent must be 1-100"
+    } else if matches!(watermarks, (Some(low), Some(high)) if low > high) {
+        "high_watermark_percent must not be below low_watermark_percent"
+    } else {
+        return true;
+    };
+    error!("Source {}: {} - skipping", id, bad);
+    false
+}
+
+/// Checks the settings every kind of source takes, logging the first one
+/// out of range.
+fn valid_common(id: &str, common: &SourceCommon) -> bool {
+    let bad = if common.entropy_credit.is_some_and(|c| !(c > 0.0 && c <= 1.0)) {
+        "entropy_credit must be above 0 and at most 1"
+    } else if common.entropy_per_byte.is_some_and(|e| !(MIN_ENTROPY_PER_BYTE..=8.0).contains(&e)) {
+        "entropy_per_byte must be 0.125 to 8"
+    } else {
+        return true;
+    };
+    error!("Source {}: {} - skipping", id, bad);
+    false
+}
+
+/// Drops groups with invalid or clashing names and fills in object paths.
+/// `object_path` is where the default and monitor interfaces are served.
+fn validate_groups(groups: Vec<GroupConfig>, object_path: &str) -> Vec<GroupConfig> {
+    let mut names: HashSet<String> = HashSet::new();
+    let mut endpoints: HashSet<(String, String)> =
+        HashSet::from([
+            (object_path.to_string(), DEFAULT_INTERFACE.to_string()),
+            (object_path.to_string(), MONITOR_INTERFACE.to_string()),
+        ]);
+    let mut valid = Vec::new();
+    for mut g in groups {
+        if !is_valid_id(&g.name) {
+            error!("Invalid group name '{}'. Use [a-z0-9][a-z0-9_-]*", g.name);
+            continue;
+        }
+        if !names.insert(g.name.clone()) {
+            error!("Duplicate group name '{}' - skipping", g.name);
+            continue;
+        }
+        if zbus::names::InterfaceName::try_from(g.interface.as_str()).is_err() {
+            error!("Group {}: invalid interface name '{}' - skipping", g.name, g.interface);
+            continue;
+        }
+        let path = g.object_path.take().unwrap_or_else(|| format!("/{}", g.interface.replace('.', "/")));
+        if zbus::zvariant::ObjectPath::try_from(path.as_str()).is_err() {
+            error!("Group {}: invalid object path '{}' - skipping", g.name, path);
+            continue;
+        }
+        if !endpoints.insert((path.clone(), g.interface.clone())) {
+            error!("Group {}: {} is already served at {} - skipping", g.name, g.interface, path);
+            continue;
+        }
+        if g.shaping.as_ref().is_some_and(|s| s.bytes_per_second == 0 || s.burst_bytes == Some(0)) {
+            error!("Group {}: shaping needs a positive bytes_per_second and burst_bytes - skipping", g.name);
+            continue;
+        }
+        if g.min_sources == Some(0) {
+            error!("Group {}: min_sources must be at least 1 - skipping", g.name);
+            continue;
+        }
+        if valid.len() == MAX_GROUPS {
+            error!("Group {}: at most {} groups are supported - skipping", g.name, MAX_GROUPS);
+            continue;
+        }
+        g.object_path = Some(path);
+        valid.push(g);
+    }
+    valid
 }
 
 fn is_valid_id(s: &str) -> bool {
@@ -132,3 +2019,175 @@ fn is_lc_alnum(c: char) -> bool {
     matches!(c, 'a'..='z' | '0'..='9')
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(name: &str, interface: &str, object_path: Option<&str>) -> GroupConfig {
+        GroupConfig {
+            name: name.to_string(),
+            interface: interface.to_string(),
+            object_path: object_path.map(str::to_string),
+            uids: None,
+            access: None,
+            shaping: None,
+            conditioner: None,
+            min_sources: None,
+            combine: None,
+        }
+    }
+
+    #[test]
+    fn test_hashes_ignore_layout() {
+        let a = ConfigHashes::new("a", "[sources]\ncombine = \"xor\"\n\n[[sources.lrng]]\nid = \"os\"\n");
+        let b = ConfigHashes::new("b", "# same\n[sources]\ncombine=\"xor\"\n[[sources.lrng]]\nid=\"os\"\nenabled=true\n");
+        assert_ne!(a.file, b.file);
+        assert_ne!(a.sections["sources.lrng.os"], b.sections["sources.lrng.os"]);
+        let c = ConfigHashes::new("c", "[[sources.lrng]]\n  id = \"os\"   # comment\n[sources]\ncombine = 'xor'\n");
+        assert_eq!(a.sections, c.sections);
+    }
+
+    #[test]
+    fn test_groups_validated() {
+        let groups = validate_groups(vec![
+            group("ha", "lv.lumii.trng.HighAssurance", None),
+            group("ha", "lv.lumii.trng.Other", None),
+            group("bad", "not an interface", None),
+            group("default", DEFAULT_INTERFACE, None),
+            group("clash", DEFAULT_INTERFACE, Some(DEFAULT_OBJECT_PATH)),
+            group("lab", "lv.lumii.trng.Lab", Some("/lv/lumii/trng/SourceXorAggregator")),
+            GroupConfig {
+                shaping: Some(ShapingConfig { bytes_per_second: 0, burst_bytes: None }),
+                ..group("raw", "lv.lumii.trng.Raw", None)
+            },
+            GroupConfig { min_sources: Some(0), ..group("any", "lv.lumii.trng.Any", None) },
+        ], DEFAULT_OBJECT_PATH);
+        let served: Vec<_> = groups.iter().map(|g| (g.name.as_str(), g.object_path.as_deref().unwrap())).collect();
+        assert_eq!(served, [
+            ("ha", "/lv/lumii/trng/HighAssurance"),
+            ("default", "/lv/lumii/trng/Rng"),
+            ("lab", DEFAULT_OBJECT_PATH),
+        ]);
+
+        // Only the endpoint's own object path is taken
+        assert_eq!(validate_groups(vec![group("clash", DEFAULT_INTERFACE, Some(DEFAULT_OBJECT_PATH))], "/tenant/a").len(), 1);
+    }
+
+    #[test]
+    fn test_transforms_parsed() {
+        assert_eq!("xor_fold".parse(), Ok(TransformKind::XorFold(2)));
+        assert_eq!("xor_fold:8".parse(), Ok(TransformKind::XorFold(8)));
+        assert_eq!("lsb:4".parse(), Ok(TransformKind::Lsb(4)));
+        assert_eq!("sha256".parse(), Ok(TransformKind::Sha256));
+        assert_eq!(invalid_transform(&["von_neumann".to_string(), "sha256".to_string()]), None);
+        for bad in ["lsb", "lsb:8", "xor_fold:1", "sha256:2", "lsb:x", "md5"] {
+            assert!(invalid_transform(&[bad.to_string()]).is_some(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_replenish_validated() {
+        let lrng: LrngConfig = toml::from_str("id = \"os\"\nreplenish_interval_ms = 250\nreplenish_chunk_bytes = 4096\nlow_watermark_percent = 20\n").unwrap();
+        assert!(valid_replenish(&lrng.id, lrng.replenish_interval_ms, lrng.replenish_chunk_bytes, (lrng.low_watermark_percent, None)));
+        assert!(valid_replenish("os", None, None, (None, None)));
+        assert!(!valid_replenish("os", Some(0), None, (None, None)));
+        assert!(!valid_replenish("os", Some(MAX_REPLENISH_INTERVAL_MS + 1), None, (None, None)));
+        assert!(!valid_replenish("os", None, Some(MAX_REPLENISH_CHUNK + 1), (None, None)));
+        assert!(!valid_replenish("os", None, None, (Some(0), None)));
+        assert!(valid_replenish("os", None, None, (Some(20), Some(80))));
+        assert!(!valid_replenish("os", None, None, (Some(80), Some(20))));
+    }
+
+    #[test]
+    fn test_common_source_settings() {
+        let file: FileConfig = toml::from_str(concat!(
+            "id = \"qrng\"\npath = \"/dev/qrng\"\nentropy_credit = 1\nrequired = false\nstandby_for = \"main\"\n",
+            "simulate_rate_bytes_per_sec = 4096\nmaintenance = [{ cron = \"0 3 * * 0\", duration_minutes = 30 }]\n",
+            "max_age_seconds = 60\ncpus = [2, 3]\n",
+        ))
+        .unwrap();
+        assert_eq!(file.path, "/dev/qrng");
+        assert_eq!(file.common.entropy_credit, Some(1.0));
+        assert_eq!(file.common.required, Some(false));
+        assert_eq!(file.common.standby_for.as_deref(), Some("main"));
+        assert_eq!(file.common.simulate_rate_bytes_per_sec, Some(4096));
+        assert_eq!(file.common.maintenance.len(), 1);
+        assert_eq!(file.common.max_age_seconds, Some(60));
+        assert_eq!(file.common.cpus, Some(vec![2, 3]));
+    }
+
+    #[test]
+    fn test_entropy_per_byte_validated() {
+        let common = |toml: &str| toml::from_str::<SourceCommon>(toml).unwrap();
+        assert!(valid_common("os", &common("")));
+        assert!(valid_common("os", &common("entropy_per_byte = 0.125")));
+        assert!(valid_common("os", &common("entropy_per_byte = 8")));
+        for bad in ["entropy_per_byte = -1", "entropy_per_byte = 0", "entropy_per_byte = 8.5", "entropy_per_byte = nan", "entropy_credit = 0", "entropy_credit = 1.5"] {
+            assert!(!valid_common("os", &common(bad)), "{}", bad);
+        }
+
+        // Skipped at load, leaving the valid source
+        let path = std::env::temp_dir().join(format!("trng-config-entropy-{}.toml", std::process::id()));
+        fs::write(&path, "[[sources.lrng]]\nid = \"os\"\nenabled = true\nentropy_per_byte = 9\n\n[[sources.lrng]]\nid = \"os2\"\nenabled = true\nentropy_per_byte = 2\n").unwrap();
+        let cfg = load_config(path.to_str().unwrap()).unwrap();
+        let _ = fs::remove_file(&path);
+        let ids: Vec<_> = cfg.lrng_sources.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, ["os2"]);
+    }
+
+    /// `toml` loaded as a config file, the path told apart by `name`.
+    fn load(name: &str, toml: &str) -> Result<FlattenedConfig, String> {
+        let path = std::env::temp_dir().join(format!("trng-config-{}-{}.toml", name, std::process::id()));
+        fs::write(&path, toml).unwrap();
+        let cfg = load_config(path.to_str().unwrap()).map_err(|e| e.to_string());
+        let _ = fs::remove_file(&path);
+        cfg
+    }
+
+    #[test]
+    fn test_service_bus() {
+        let source = "[[sources.lrng]]\nid = \"os\"\nenabled = true\n";
+        assert_eq!(load("bus-default", source).unwrap().bus, BusKind::Session);
+        assert_eq!(load("bus-system", &format!("[service]\nbus = \"system\"\n\n{}", source)).unwrap().bus, BusKind::System);
+        assert!(load("bus-bad", &format!("[service]\nbus = \"tcp\"\n\n{}", source)).is_err());
+        assert_eq!(BusKind::System.to_string(), "system bus");
+    }
+
+    #[test]
+    fn test_service_name_and_object_path() {
+        let source = "[[sources.lrng]]\nid = \"os\"\nenabled = true\n";
+        let cfg = load("service-default", source).unwrap();
+        assert_eq!((cfg.bus_name.as_str(), cfg.object_path.as_str()), (BUS_NAME, DEFAULT_OBJECT_PATH));
+        let cfg = load("service-tenant", &format!("[service]\nname = \"lv.lumii.trng.Tenant\"\nobject_path = \"/tenant/a\"\n\n{}", source)).unwrap();
+        assert_eq!((cfg.bus_name.as_str(), cfg.object_path.as_str()), ("lv.lumii.trng.Tenant", "/tenant/a"));
+
+        // Invalid ones are refused rather than failing at connect
+        assert!(load("service-bad-name", &format!("[service]\nname = \"no dots\"\n\n{}", source)).is_err());
+        assert!(load("service-bad-path", &format!("[service]\nobject_path = \"tenant/a\"\n\n{}", source)).is_err());
+    }
+
+    #[test]
+    fn test_group_combine() {
+        let cfg = load("group-combine", concat!(
+            "[sources]\ncombine = \"xor\"\n\n[[sources.lrng]]\nid = \"os\"\nenabled = true\n\n",
+            "[[groups]]\nname = \"hashed\"\ninterface = \"lv.lumii.trng.Hashed\"\ncombine = \"sha3\"\n\n",
+            "[[groups]]\nname = \"plain\"\ninterface = \"lv.lumii.trng.Plain\"\n",
+        ))
+        .unwrap();
+        let combine: Vec<_> = cfg.groups.iter().map(|g| (g.name.as_str(), g.combine)).collect();
+        assert_eq!(combine, [("hashed", Some(CombineMode::Sha3)), ("plain", None)]);
+        assert!(load("group-combine-bad", "[[groups]]\nname = \"x\"\ninterface = \"lv.lumii.trng.X\"\ncombine = \"and\"\n").is_err());
+    }
+
+    #[test]
+    fn test_combine_modes_named_as_configured() {
+        for mode in CombineMode::ALL {
+            let name = mode.as_str();
+            let cfg = load(&format!("combine-{}", name), &format!("[sources]\ncombine = \"{}\"\n\n[[sources.lrng]]\nid = \"os\"\nenabled = true\n", name)).unwrap();
+            assert_eq!(cfg.combine, mode);
+            let group: GroupConfig = toml::from_str(&format!("name = \"g\"\ninterface = \"lv.lumii.trng.G\"\ncombine = \"{}\"", name)).unwrap();
+            assert_eq!(group.combine, Some(mode));
+        }
+    }
+}
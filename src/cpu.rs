@@ -0,0 +1,138 @@
+use crate::config::CpuInstruction;
+use crate::error::Error;
+use std::time::Instant;
+
+/// Tries per 64-bit word before the instruction counts as drained. RDRAND
+/// practically never fails this often; RDSEED does under sustained load.
+const RETRIES: u32 = 10;
+
+/// How often `fill` looks at its deadline, in words.
+const DEADLINE_CHECK_WORDS: usize = 512;
+
+pub fn name(instruction: CpuInstruction) -> &'static str {
+    match instruction {
+        CpuInstruction::Rdseed => "rdseed",
+        CpuInstruction::Rdrand => "rdrand",
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn supported(instruction: CpuInstruction) -> bool {
+    match instruction {
+        CpuInstruction::Rdseed => std::arch::is_x86_feature_detected!("rdseed"),
+        CpuInstruction::Rdrand => std::arch::is_x86_feature_detected!("rdrand"),
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn supported(_instruction: CpuInstruction) -> bool {
+    false
+}
+
+/// The instruction a source configured for `wanted` uses on this CPU:
+/// `wanted` itself, RDRAND when only RDSEED is missing, or `None`.
+pub fn detect(wanted: CpuInstruction) -> Option<CpuInstruction> {
+    [wanted, CpuInstruction::Rdrand].into_iter().find(|&instruction| supported(instruction))
+}
+
+/// Model name and microcode revision, for reply provenance.
+pub fn model() -> Option<String> {
+    let info = std::fs::read_to_string("/proc/cpuinfo").ok()?;
+    let field = |key: &str| {
+        info.lines()
+            .find(|line| line.split(':').next().is_some_and(|k| k.trim() == key))
+            .and_then(|line| line.split_once(':'))
+            .map(|(_, value)| value.trim().to_string())
+    };
+    let model = field("model name")?;
+    Some(match field("microcode") {
+        Some(microcode) => format!("{} (microcode {})", model, microcode),
+        None => model,
+    })
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "rdseed")]
+unsafe fn rdseed() -> Option<u64> {
+    let mut word = 0;
+    for _ in 0..RETRIES {
+        if std::arch::x86_64::_rdseed64_step(&mut word) == 1 {
+            return Some(word);
+        }
+        std::hint::spin_loop();
+    }
+    None
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "rdrand")]
+unsafe fn rdrand() -> Option<u64> {
+    let mut word = 0;
+    for _ in 0..RETRIES {
+        if std::arch::x86_64::_rdrand64_step(&mut word) == 1 {
+            return Some(word);
+        }
+        std::hint::spin_loop();
+    }
+    None
+}
+
+#[cfg(target_arch = "x86_64")]
+fn step(instruction: CpuInstruction) -> Option<u64> {
+    // SAFETY: callers only use instructions that `detect` found on this CPU
+    unsafe {
+        match instruction {
+            CpuInstruction::Rdseed => rdseed(),
+            CpuInstruction::Rdrand => rdrand(),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn step(_instruction: CpuInstruction) -> Option<u64> {
+    None
+}
+
+/// Up to `len` bytes from `instruction`, which must be one `detect` returned.
+/// Stops early when the instruction is drained or at `deadline`. Two equal
+/// words in a row fail with `IntegrityFailure`: a working generator all but
+/// never repeats, while broken ones (such as RDRAND returning all ones after
+/// resume on some AMD parts) do nothing else.
+pub fn fill(instruction: CpuInstruction, len: usize, deadline: Option<Instant>) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::with_capacity(len);
+    let mut previous = None;
+    let mut words = 0usize;
+    while out.len() < len {
+        if words > 0 && words.is_multiple_of(DEADLINE_CHECK_WORDS) && deadline.is_some_and(|d| Instant::now() >= d) {
+            break;
+        }
+        let Some(word) = step(instruction) else { break };
+        if previous == Some(word) {
+            return Err(Error::IntegrityFailure);
+        }
+        previous = Some(word);
+        words += 1;
+        let take = (len - out.len()).min(8);
+        out.extend_from_slice(&word.to_le_bytes()[..take]);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fill() {
+        let Some(instruction) = detect(CpuInstruction::Rdseed) else { return };
+        let bytes = fill(instruction, 1001, None).unwrap();
+        // RDSEED may run dry under load, but RDRAND does not
+        if instruction == CpuInstruction::Rdrand {
+            assert_eq!(bytes.len(), 1001);
+        }
+        assert!(bytes.len() <= 1001);
+        assert!(fill(CpuInstruction::Rdrand, 0, None).unwrap().is_empty());
+        let past = Instant::now();
+        assert!(fill(instruction, 1 << 20, Some(past)).unwrap().len() < 1 << 20);
+    }
+}
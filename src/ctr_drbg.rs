@@ -0,0 +1,163 @@
+use crate::aes::{self, Aes256};
+use crate::config::DrbgConfig;
+use crate::drbg::SEED_LEN;
+use std::sync::atomic::{AtomicU64, Ordering};
+use zeroize::{Zeroize, Zeroizing};
+
+/// Largest output of a single generate call (SP 800-90A table 3, 2^19 bits).
+pub const MAX_REQUEST: usize = 64 * 1024;
+
+/// Default `reseed_interval_bytes`.
+const DEFAULT_RESEED_INTERVAL: u64 = 1024 * 1024;
+
+/// CTR_DRBG with AES-256 and no derivation function (NIST SP 800-90A
+/// 10.2.1), so every seed must be `SEED_LEN` bytes of full-entropy input.
+pub struct CtrDrbg {
+    cipher: Aes256,
+    v: [u8; aes::BLOCK],
+}
+
+impl Drop for CtrDrbg {
+    fn drop(&mut self) {
+        self.v.zeroize();
+    }
+}
+
+impl CtrDrbg {
+    pub fn new(entropy: &[u8; SEED_LEN]) -> Self {
+        let mut drbg = Self { cipher: Aes256::new(&[0; 32]), v: [0; aes::BLOCK] };
+        drbg.update(entropy);
+        drbg
+    }
+
+    pub fn reseed(&mut self, entropy: &[u8; SEED_LEN]) {
+        self.update(entropy);
+    }
+
+    /// Generates `num_bytes` in calls of at most `MAX_REQUEST` bytes.
+    pub fn generate(&mut self, num_bytes: usize) -> Vec<u8> {
+        let mut out = Vec::with_capacity(num_bytes);
+        while out.len() < num_bytes {
+            let end = out.len() + (num_bytes - out.len()).min(MAX_REQUEST);
+            while out.len() < end {
+                let block = self.next_block();
+                let take = (end - out.len()).min(aes::BLOCK);
+                out.extend_from_slice(&block[..take]);
+            }
+            self.update(&[0; SEED_LEN]);
+        }
+        out
+    }
+
+    fn next_block(&mut self) -> Zeroizing<[u8; aes::BLOCK]> {
+        let counter = u128::from_be_bytes(self.v).wrapping_add(1);
+        self.v = counter.to_be_bytes();
+        let mut block = Zeroizing::new(self.v);
+        self.cipher.encrypt_block(&mut block);
+        block
+    }
+
+    fn update(&mut self, provided: &[u8; SEED_LEN]) {
+        let mut temp = Zeroizing::new([0u8; SEED_LEN]);
+        for chunk in temp.chunks_mut(aes::BLOCK) {
+            chunk.copy_from_slice(&*self.next_block());
+        }
+        for (t, p) in temp.iter_mut().zip(provided) {
+            *t ^= p;
+        }
+        let mut key = Zeroizing::new([0u8; 32]);
+        key.copy_from_slice(&temp[..32]);
+        self.cipher = Aes256::new(&key);
+        self.v.copy_from_slice(&temp[32..]);
+    }
+}
+
+/// The `[drbg]` output stage: the generator with the indices of the sources
+/// of its last seed, and what it generated and how often it reseeded, for
+/// `GetDrbgStats` and the snapshot. The aggregator does the (re)seeding, as
+/// the seeds are combined source output.
+pub struct OutputDrbg {
+    pub reseed_interval: u64,
+    pub state: tokio::sync::Mutex<Option<(CtrDrbg, Vec<usize>)>>,
+    since_reseed: AtomicU64,
+    generated: AtomicU64,
+    reseeds: AtomicU64,
+    reseed_failures: AtomicU64,
+}
+
+impl OutputDrbg {
+    pub fn new(cfg: &DrbgConfig) -> Self {
+        Self {
+            reseed_interval: cfg.reseed_interval_bytes.unwrap_or(DEFAULT_RESEED_INTERVAL),
+            state: tokio::sync::Mutex::new(None),
+            since_reseed: AtomicU64::new(0),
+            generated: AtomicU64::new(0),
+            reseeds: AtomicU64::new(0),
+            reseed_failures: AtomicU64::new(0),
+        }
+    }
+
+    /// Bytes that may still be generated before the next reseed.
+    pub fn budget(&self) -> u64 {
+        self.reseed_interval.saturating_sub(self.since_reseed.load(Ordering::Relaxed))
+    }
+
+    pub fn record_generated(&self, bytes: usize) {
+        self.since_reseed.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.generated.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_reseed(&self, ok: bool) {
+        if ok {
+            self.since_reseed.store(0, Ordering::Relaxed);
+            self.reseeds.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.reseed_failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Counters by name: "reseeds" (including the first seeding),
+    /// "reseed_failures", "bytes_generated", "bytes_since_reseed" and
+    /// "reseed_interval_bytes".
+    pub fn stats(&self) -> Vec<(&'static str, u64)> {
+        vec![
+            ("reseeds", self.reseeds.load(Ordering::Relaxed)),
+            ("reseed_failures", self.reseed_failures.load(Ordering::Relaxed)),
+            ("bytes_generated", self.generated.load(Ordering::Relaxed)),
+            ("bytes_since_reseed", self.since_reseed.load(Ordering::Relaxed)),
+            ("reseed_interval_bytes", self.reseed_interval),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_answer() {
+        // From a separate implementation of SP 800-90A 10.2.1 over another AES
+        let mut drbg = CtrDrbg::new(&std::array::from_fn(|i| i as u8));
+        assert_eq!(
+            hex::encode(drbg.generate(64)),
+            "061550234d158c5ec95595fe04ef7a25767f2e24cc2bc479d09d86dc9abcfde7056a8c266f9ef97ed08541dbd2e1ffa19810f5392d076276ef41277c3ab6e94a"
+        );
+        drbg.reseed(&std::array::from_fn(|i| 0x80 + i as u8));
+        assert_eq!(hex::encode(drbg.generate(20)), "c9e0e4263043280e2e93e18e2022579c67141e08");
+        assert_eq!(drbg.generate(MAX_REQUEST + 5).len(), MAX_REQUEST + 5);
+    }
+
+    #[test]
+    fn test_reseed_accounting() {
+        let layer = OutputDrbg::new(&DrbgConfig { reseed_interval_bytes: Some(100) });
+        layer.record_reseed(true);
+        layer.record_generated(60);
+        assert_eq!(layer.budget(), 40);
+        layer.record_generated(60);
+        assert_eq!(layer.budget(), 0);
+        layer.record_reseed(false);
+        layer.record_reseed(true);
+        assert_eq!(layer.budget(), 100);
+        assert_eq!(layer.stats()[..4], [("reseeds", 2), ("reseed_failures", 1), ("bytes_generated", 120), ("bytes_since_reseed", 0)]);
+    }
+}
@@ -0,0 +1,128 @@
+use crate::error::Error;
+use crate::quotas::ClientQuotas;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::task::AbortHandle;
+
+struct Delivery {
+    owner: String,
+    task: AbortHandle,
+}
+
+/// `RequestBytesAsync` requests still being delivered, each by its own
+/// task. Requests are checked against `[service] max_request_bytes` and
+/// the caller's `[quotas]` as a whole before anything is spawned, and each
+/// client may only have so many of them running.
+pub struct Deliveries {
+    max_request_bytes: usize,
+    max_per_client: usize,
+    quotas: Option<Arc<ClientQuotas>>,
+    active: Mutex<HashMap<u64, Delivery>>,
+}
+
+impl Deliveries {
+    pub fn new(max_request_bytes: usize, max_per_client: usize, quotas: Option<Arc<ClientQuotas>>) -> Self {
+        Self { max_request_bytes, max_per_client, quotas, active: Mutex::new(HashMap::new()) }
+    }
+
+    /// Charges `num_bytes` to `owner` and spawns `deliver(num_bytes)`, which
+    /// returns how many bytes it sent; the rest are refunded once it ends.
+    /// Fails with `RequestTooLarge` above `max_request_bytes`, with
+    /// `QuotaExceeded` while `owner` has `max_per_client` deliveries running,
+    /// and with `RateLimited` when its quota does not cover the request,
+    /// spawning nothing.
+    pub fn start<F, Fut>(self: &Arc<Self>, owner: &str, request_id: u64, num_bytes: u64, deliver: F) -> Result<(), Error>
+    where
+        F: FnOnce(usize) -> Fut,
+        Fut: Future<Output = usize> + Send + 'static,
+    {
+        // Saturating, so huge sizes cannot wrap past `max_request_bytes`
+        let bytes = usize::try_from(num_bytes).unwrap_or(usize::MAX);
+        if bytes > self.max_request_bytes {
+            log::warn!("Refused an async request for {} bytes from {}, above [service] max_request_bytes of {}", num_bytes, owner, self.max_request_bytes);
+            return Err(Error::RequestTooLarge);
+        }
+        let mut active = self.lock();
+        if active.values().filter(|d| d.owner == owner).count() >= self.max_per_client {
+            log::warn!("Refused an async request from {}: {} already running", owner, self.max_per_client);
+            return Err(Error::QuotaExceeded);
+        }
+        if let Some(quotas) = &self.quotas {
+            quotas.admit(owner, bytes)?;
+        }
+        let delivery = deliver(bytes);
+        let (this, client) = (self.clone(), owner.to_string());
+        // Holding `active` keeps the task from removing itself before it is inserted
+        let task = tokio::spawn(async move {
+            let sent = delivery.await;
+            if let Some(quotas) = &this.quotas {
+                quotas.refund(&client, bytes.saturating_sub(sent));
+            }
+            this.lock().remove(&request_id);
+        });
+        active.insert(request_id, Delivery { owner: owner.to_string(), task: task.abort_handle() });
+        Ok(())
+    }
+
+    /// Ends the deliveries of a client that left the bus.
+    pub fn remove_owner(&self, owner: &str) {
+        self.lock().retain(|_, d| {
+            let keep = d.owner != owner;
+            if !keep {
+                d.task.abort();
+            }
+            keep
+        });
+    }
+
+    /// Ends every delivery; used when the bus goes away.
+    pub fn stop_all(&self) {
+        for (_, delivery) in self.lock().drain() {
+            delivery.task.abort();
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<u64, Delivery>> {
+        self.active.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[tokio::test]
+    async fn test_refuses_before_spawning() {
+        let deliveries = Arc::new(Deliveries::new(1024, 2, None));
+        let spawned = Arc::new(AtomicBool::new(false));
+        let deliver = |spawned: Arc<AtomicBool>| move |_| {
+            spawned.store(true, Ordering::Relaxed);
+            std::future::pending::<usize>()
+        };
+        // Oversized, also past the end of usize
+        assert_eq!(deliveries.start(":1.1", 1, 1025, deliver(spawned.clone())), Err(Error::RequestTooLarge));
+        assert_eq!(deliveries.start(":1.1", 2, u64::MAX, deliver(spawned.clone())), Err(Error::RequestTooLarge));
+        assert!(!spawned.load(Ordering::Relaxed));
+
+        // Two running per client; leaving releases them
+        assert_eq!(deliveries.start(":1.1", 3, 1024, deliver(spawned.clone())), Ok(()));
+        assert_eq!(deliveries.start(":1.1", 4, 1024, deliver(spawned.clone())), Ok(()));
+        assert_eq!(deliveries.start(":1.1", 5, 1, deliver(spawned.clone())), Err(Error::QuotaExceeded));
+        assert_eq!(deliveries.start(":1.2", 6, 1, deliver(spawned.clone())), Ok(()));
+        deliveries.remove_owner(":1.1");
+        assert_eq!(deliveries.start(":1.1", 7, 1, deliver(spawned.clone())), Ok(()));
+
+        // A finished delivery frees its place
+        let deliveries = Arc::new(Deliveries::new(1024, 1, None));
+        assert_eq!(deliveries.start(":1.1", 8, 16, |n| async move { n }), Ok(()));
+        for _ in 0..100 {
+            if deliveries.lock().is_empty() {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(deliveries.start(":1.1", 9, 16, |n| async move { n }), Ok(()));
+    }
+}
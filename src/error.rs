@@ -2,6 +2,7 @@ use std::fmt;
 
 /// Custom `Error` type for handling RNG-related errors.
 #[derive(Debug, PartialEq)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     /// Indicates that no positive errno was set.
     ErrnoNotPositive,
@@ -9,6 +10,32 @@ pub enum Error {
     Unexpected,
     /// Captures OS-specific error codes.
     OsError(u32),
+    /// Source data did not match its integrity manifest; the source is disabled.
+    IntegrityFailure,
+    /// Every source is currently skipped by its circuit breaker.
+    SourcesUnavailable,
+    /// A request option was unknown or had the wrong type.
+    InvalidOption(String),
+    /// The sources delivered fewer bytes than an operation requires.
+    InsufficientEntropy,
+    /// A per-client or service-wide limit would be exceeded.
+    QuotaExceeded,
+    /// The caller has used up its `[quotas]` rate or daily allowance.
+    RateLimited,
+    /// Too many requests are already waiting for the sources.
+    Busy,
+    /// The caller is not allowed to use this method.
+    AccessDenied,
+    /// The service is still priming its sources after startup.
+    NotReady,
+    /// Serving the request would exceed the `[memory]` ceiling.
+    MemoryExhausted,
+    /// Fewer sources than the endpoint's `min_sources` delivered in time.
+    TooFewSources,
+    /// A request that must be filled completely was not within its timeout.
+    Timeout,
+    /// The request asks for more than `[service] max_request_bytes`.
+    RequestTooLarge,
 }
 
 impl fmt::Display for Error {
@@ -17,6 +44,19 @@ impl fmt::Display for Error {
             Error::ErrnoNotPositive => write!(f, "No positive errno set"),
             Error::Unexpected => write!(f, "Unexpected error occurred"),
             Error::OsError(code) => write!(f, "OS error with code: {}", code),
+            Error::IntegrityFailure => write!(f, "Source failed integrity verification"),
+            Error::SourcesUnavailable => write!(f, "No entropy source is currently available"),
+            Error::InvalidOption(name) => write!(f, "Invalid request option: {}", name),
+            Error::InsufficientEntropy => write!(f, "Sources did not deliver enough entropy in time"),
+            Error::QuotaExceeded => write!(f, "Request exceeds a configured limit"),
+            Error::RateLimited => write!(f, "Client has used up its quota"),
+            Error::Busy => write!(f, "Too many requests are waiting for the sources"),
+            Error::AccessDenied => write!(f, "Caller is not allowed to use this method"),
+            Error::NotReady => write!(f, "Sources are not ready yet"),
+            Error::MemoryExhausted => write!(f, "Request would exceed the memory ceiling"),
+            Error::TooFewSources => write!(f, "Fewer sources than min_sources delivered in time"),
+            Error::Timeout => write!(f, "Request was not filled within its timeout"),
+            Error::RequestTooLarge => write!(f, "Request is larger than max_request_bytes"),
         }
     }
 }
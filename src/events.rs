@@ -0,0 +1,31 @@
+use std::collections::HashMap;
+use tokio::sync::broadcast;
+
+/// Notable state changes inside the service, forwarded as D-Bus signals by
+/// `main.rs` (and to any other subscriber).
+#[derive(Debug, Clone)]
+pub enum ServiceEvent {
+    BreakerStateChanged { source_id: String, state: &'static str },
+    /// A buffered source has stayed empty for `empty_seconds`.
+    BufferEmpty { source_id: String, empty_seconds: u64 },
+    /// A buffered source's fill dropped below `entropy_low_percent`.
+    EntropyLow { source_id: String, fill_percent: f64 },
+    /// A source started returning errors after serving without.
+    SourceFailed { source_id: String, error: String },
+    /// A source failed its integrity/self checks and was disabled.
+    SelfTestFailed { source_id: String, reason: String },
+    /// The watchdog's canary reads started failing (`healthy` false, with
+    /// `reason`) or succeed again.
+    WatchdogChanged { healthy: bool, reason: String },
+    /// A warm standby took over from the source it stands by for, or the
+    /// other way round; `reason` is "quarantined", "unavailable" or "manual".
+    SourcePromoted { source_id: String, replaced: String, reason: String },
+    /// Raised by `alerts.rs` for operator paging.
+    Alert { kind: &'static str, source_id: String, details: HashMap<String, String> },
+}
+
+pub type EventSender = broadcast::Sender<ServiceEvent>;
+
+pub fn channel() -> EventSender {
+    broadcast::channel(64).0
+}
@@ -0,0 +1,183 @@
+use crate::aes::{self, Aes256};
+use sha2::{Digest, Sha256};
+use std::time::{Duration, Instant};
+use zeroize::{Zeroize, Zeroizing};
+
+/// Pools events are spread over.
+pub const POOLS: usize = 32;
+
+/// Bytes pool 0 must have taken in since the last reseed for the next one.
+const MIN_POOL_SIZE: u64 = 64;
+
+/// Least time between reseeds, so an attacker flooding the pools with
+/// known events cannot drain them faster than the sources refill them.
+const RESEED_SPACING: Duration = Duration::from_millis(100);
+
+/// Bytes from one source per event, at most.
+const EVENT_BYTES: usize = 32;
+
+/// Largest output before the generator rekeys (2^20 bytes, as in the design).
+const MAX_REQUEST: usize = 1 << 20;
+
+/// Bytes each source is read for when the generator is not seeded yet:
+/// enough events for pool 0 to reach `MIN_POOL_SIZE` on their own.
+pub const SEED_READ: usize = POOLS * MIN_POOL_SIZE as usize;
+
+/// Bytes each source is read for per background tick.
+pub const FEED_BYTES: usize = 256;
+
+/// The Fortuna accumulator and generator (Ferguson and Schneier, "Practical
+/// Cryptography" ch. 10). Each source's events go round-robin into 32
+/// SHA-256 pools; reseed `r` uses pool `i` only if `2^i` divides `r`, so
+/// the later pools collect entropy over ever longer spans. After a one-time
+/// compromise of the state the generator thus recovers once any one pool
+/// has gathered enough unseen entropy, without an entropy estimate. The
+/// generator is AES-256 in counter mode, rekeyed after every request.
+pub struct Fortuna {
+    pools: Vec<Sha256>,
+    /// Bytes added to each pool since it was last used for a reseed.
+    pool_bytes: [u64; POOLS],
+    /// Next pool of each source.
+    next_pool: Vec<usize>,
+    /// Bytes each source has added.
+    absorbed: Vec<u64>,
+    key: Zeroizing<[u8; 32]>,
+    counter: u128,
+    reseeds: u64,
+    last_reseed: Option<Instant>,
+    generated: u64,
+}
+
+impl Drop for Fortuna {
+    fn drop(&mut self) {
+        self.counter.zeroize();
+    }
+}
+
+impl Fortuna {
+    pub fn new(sources: usize) -> Self {
+        Self {
+            pools: vec![Sha256::new(); POOLS],
+            pool_bytes: [0; POOLS],
+            next_pool: vec![0; sources],
+            absorbed: vec![0; sources],
+            key: Zeroizing::new([0; 32]),
+            counter: 0,
+            reseeds: 0,
+            last_reseed: None,
+            generated: 0,
+        }
+    }
+
+    /// Adds `data` from `source` as events of up to `EVENT_BYTES`, each to
+    /// that source's next pool.
+    pub fn add_events(&mut self, source: usize, data: &[u8]) {
+        for event in data.chunks(EVENT_BYTES) {
+            let pool = self.next_pool[source];
+            self.pools[pool].update((source as u32).to_le_bytes());
+            self.pools[pool].update([event.len() as u8]);
+            self.pools[pool].update(event);
+            self.pool_bytes[pool] += event.len() as u64;
+            self.next_pool[source] = (pool + 1) % POOLS;
+        }
+        self.absorbed[source] += data.len() as u64;
+    }
+
+    /// Generates `num_bytes`, reseeding first when pool 0 holds enough and
+    /// the last reseed is long enough ago. Empty until the first reseed.
+    pub fn generate(&mut self, num_bytes: usize, now: Instant) -> Zeroizing<Vec<u8>> {
+        let due = self.last_reseed.is_none_or(|at| now.duration_since(at) >= RESEED_SPACING);
+        if self.pool_bytes[0] >= MIN_POOL_SIZE && due {
+            self.reseed(now);
+        }
+        let mut out = Zeroizing::new(Vec::with_capacity(num_bytes));
+        if self.reseeds == 0 {
+            return out;
+        }
+        while out.len() < num_bytes {
+            let take = (num_bytes - out.len()).min(MAX_REQUEST);
+            let cipher = Aes256::new(&self.key);
+            let bytes = self.blocks(&cipher, take.div_ceil(aes::BLOCK));
+            out.extend_from_slice(&bytes[..take]);
+            // Rekey, so this output cannot be recomputed from a later state
+            let key = self.blocks(&cipher, 2);
+            self.key.copy_from_slice(&key);
+        }
+        self.generated += num_bytes as u64;
+        out
+    }
+
+    fn blocks(&mut self, cipher: &Aes256, count: usize) -> Zeroizing<Vec<u8>> {
+        let mut out = Zeroizing::new(Vec::with_capacity(count * aes::BLOCK));
+        for _ in 0..count {
+            let mut block = Zeroizing::new(self.counter.to_le_bytes());
+            cipher.encrypt_block(&mut block);
+            out.extend_from_slice(&*block);
+            self.counter = self.counter.wrapping_add(1);
+        }
+        out
+    }
+
+    fn reseed(&mut self, now: Instant) {
+        self.reseeds += 1;
+        let mut seed = Sha256::new();
+        seed.update(*self.key);
+        for i in (0..POOLS).take_while(|&i| self.reseeds.is_multiple_of(1 << i)) {
+            let pool = std::mem::replace(&mut self.pools[i], Sha256::new());
+            seed.update(Zeroizing::new(<[u8; 32]>::from(pool.finalize())));
+            self.pool_bytes[i] = 0;
+        }
+        let seed = Zeroizing::new(<[u8; 32]>::from(seed.finalize()));
+        self.key.copy_from_slice(&Sha256::digest(*seed));
+        self.counter = self.counter.wrapping_add(1);
+        self.last_reseed = Some(now);
+    }
+
+    pub fn absorbed(&self, source: usize) -> u64 {
+        self.absorbed[source]
+    }
+
+    /// Counters by name: "reseeds", "bytes_generated" and "pool_00" to
+    /// "pool_31", the bytes each pool has taken in since it was last used.
+    pub fn stats(&self) -> Vec<(String, u64)> {
+        let mut stats = vec![("reseeds".to_string(), self.reseeds), ("bytes_generated".to_string(), self.generated)];
+        stats.extend(self.pool_bytes.iter().enumerate().map(|(i, &n)| (format!("pool_{:02}", i), n)));
+        stats
+    }
+
+    /// Forgets the key and every pool, at shutdown.
+    pub fn wipe(&mut self) {
+        self.key.zeroize();
+        self.pools = vec![Sha256::new(); POOLS];
+        self.pool_bytes = [0; POOLS];
+        self.reseeds = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reseed_schedule() {
+        let mut fortuna = Fortuna::new(1);
+        let mut now = Instant::now();
+        assert!(fortuna.generate(16, now).is_empty());
+
+        // Two rounds of events put 64 bytes in every pool
+        fortuna.add_events(0, &[1; SEED_READ]);
+        let first = fortuna.generate(16, now);
+        assert_eq!(first.len(), 16);
+        assert_eq!(fortuna.pool_bytes[..2], [0, 64]);
+        assert_ne!(*fortuna.generate(16, now), *first);
+
+        // Reseed 2 uses pools 0 and 1, not before the spacing is over
+        fortuna.add_events(0, &[2; SEED_READ]);
+        fortuna.generate(16, now);
+        assert_eq!(fortuna.reseeds, 1);
+        now += RESEED_SPACING;
+        fortuna.generate(16, now);
+        assert_eq!((fortuna.reseeds, fortuna.pool_bytes[..3].to_vec()), (2, vec![0, 0, 128]));
+        assert_eq!(fortuna.generate(MAX_REQUEST + 5, now).len(), MAX_REQUEST + 5);
+    }
+}
@@ -0,0 +1,126 @@
+use crate::access;
+use crate::config::{DEFAULT_INTERFACE, MAX_GROUPS};
+use crate::SourceXorAggregator;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::OnceLock;
+use zbus::connection;
+use zbus::message::{Header, Message};
+use zbus::names::{InterfaceName, MemberName};
+use zbus::object_server::{DispatchResult2, Interface, SignalEmitter};
+use zbus::zvariant::{OwnedValue, Value};
+use zbus::{fdo, Connection, ObjectServer};
+
+/// Interface name of each `[[groups]]` slot. zbus names interfaces per type,
+/// so every slot is a type of its own that looks its name up here.
+static INTERFACES: [OnceLock<InterfaceName<'static>>; MAX_GROUPS] = [const { OnceLock::new() }; MAX_GROUPS];
+
+/// The `lv.lumii.trng.Rng` methods and signals under the interface name of
+/// group slot `SLOT`, refusing callers its access policy does not admit.
+pub struct Branded<const SLOT: usize>(SourceXorAggregator);
+
+/// Adds the endpoint of group `slot` (below `MAX_GROUPS`) to the connection
+/// being built, serving `service` under its interface name at `path`.
+pub fn serve_at<'a>(
+    builder: connection::Builder<'a>,
+    slot: usize,
+    path: String,
+    service: SourceXorAggregator,
+) -> zbus::Result<connection::Builder<'a>> {
+    let _ = INTERFACES[slot].set(service.interface.clone());
+    match slot {
+        0 => builder.serve_at(path, Branded::<0>(service)),
+        1 => builder.serve_at(path, Branded::<1>(service)),
+        2 => builder.serve_at(path, Branded::<2>(service)),
+        3 => builder.serve_at(path, Branded::<3>(service)),
+        4 => builder.serve_at(path, Branded::<4>(service)),
+        5 => builder.serve_at(path, Branded::<5>(service)),
+        6 => builder.serve_at(path, Branded::<6>(service)),
+        7 => builder.serve_at(path, Branded::<7>(service)),
+        _ => unreachable!("config allows at most {} groups", MAX_GROUPS),
+    }
+}
+
+#[async_trait::async_trait]
+impl<const SLOT: usize> Interface for Branded<SLOT> {
+    fn name() -> InterfaceName<'static> {
+        INTERFACES[SLOT].get().cloned().expect("group interface named before serving")
+    }
+
+    fn spawn_tasks_for_methods(&self) -> bool {
+        self.0.spawn_tasks_for_methods()
+    }
+
+    async fn get(
+        &self,
+        property_name: &str,
+        server: &ObjectServer,
+        connection: &Connection,
+        header: Option<&Header<'_>>,
+        emitter: &SignalEmitter<'_>,
+    ) -> Option<fdo::Result<OwnedValue>> {
+        Interface::get(&self.0, property_name, server, connection, header, emitter).await
+    }
+
+    async fn get_all(
+        &self,
+        server: &ObjectServer,
+        connection: &Connection,
+        header: Option<&Header<'_>>,
+        emitter: &SignalEmitter<'_>,
+    ) -> fdo::Result<HashMap<String, OwnedValue>> {
+        self.0.get_all(server, connection, header, emitter).await
+    }
+
+    fn set<'call>(
+        &'call self,
+        property_name: &'call str,
+        value: &'call Value<'_>,
+        server: &'call ObjectServer,
+        connection: &'call Connection,
+        header: Option<&'call Header<'_>>,
+        emitter: &'call SignalEmitter<'_>,
+    ) -> DispatchResult2<'call> {
+        Interface::set(&self.0, property_name, value, server, connection, header, emitter)
+    }
+
+    async fn set_mut(
+        &mut self,
+        property_name: &str,
+        value: &Value<'_>,
+        server: &ObjectServer,
+        connection: &Connection,
+        header: Option<&Header<'_>>,
+        emitter: &SignalEmitter<'_>,
+    ) -> Option<fdo::Result<()>> {
+        self.0.set_mut(property_name, value, server, connection, header, emitter).await
+    }
+
+    fn call<'call>(
+        &'call self,
+        server: &'call ObjectServer,
+        connection: &'call Connection,
+        msg: &'call Message,
+        name: MemberName<'call>,
+    ) -> DispatchResult2<'call> {
+        access::admit(&self.0.access, Self::name(), connection, msg, self.0.call(server, connection, msg, name))
+    }
+
+    fn call_mut<'call>(
+        &'call mut self,
+        server: &'call ObjectServer,
+        connection: &'call Connection,
+        msg: &'call Message,
+        name: MemberName<'call>,
+    ) -> DispatchResult2<'call> {
+        // Every method takes `&self`, so this never dispatches anything
+        self.0.call_mut(server, connection, msg, name)
+    }
+
+    fn introspect_to_writer(&self, writer: &mut dyn Write, level: usize) {
+        let mut xml = String::new();
+        self.0.introspect_to_writer(&mut xml, level);
+        let tag = |name: &str| format!(r#"<interface name="{}">"#, name);
+        writer.write_str(&xml.replacen(&tag(DEFAULT_INTERFACE), &tag(&Self::name()), 1)).unwrap();
+    }
+}
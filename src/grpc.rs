@@ -0,0 +1,545 @@
+use crate::config::GrpcConfig;
+use crate::hpack;
+use crate::tcp::{self, Connector};
+use std::io;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::Mutex;
+use tokio::time::{timeout, Duration};
+use zeroize::Zeroizing;
+
+/// Called unless `method` names another; see `docs/entropy.proto`.
+pub const DEFAULT_METHOD: &str = "/trng.v1.Entropy/GetRandom";
+
+/// Default for `timeout_ms`.
+const TIMEOUT_MS: u64 = 3000;
+
+/// Slack beyond the deadline passed to the server before a call is given
+/// up on, for its status to arrive.
+const CALL_SLACK: Duration = Duration::from_secs(2);
+
+const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// SETTINGS_MAX_FRAME_SIZE, left at its default.
+const MAX_FRAME: usize = 16 * 1024;
+
+/// Flow control window granted to the server, per stream and for the
+/// connection.
+const WINDOW: u32 = 4 * 1024 * 1024;
+
+/// HTTP/2's initial window, which `WINDOW` is raised from.
+const DEFAULT_WINDOW: u32 = 65_535;
+
+/// Largest gRPC message accepted, gRPC's own default.
+const MAX_MESSAGE: usize = 4 * 1024 * 1024;
+
+/// Largest header block accepted, CONTINUATION frames included.
+const MAX_HEADER_BLOCK: usize = 64 * 1024;
+
+// Frame types, flags and settings (RFC 9113 section 6)
+const DATA: u8 = 0x0;
+const HEADERS: u8 = 0x1;
+const RST_STREAM: u8 = 0x3;
+const SETTINGS: u8 = 0x4;
+const PUSH_PROMISE: u8 = 0x5;
+const PING: u8 = 0x6;
+const GOAWAY: u8 = 0x7;
+const WINDOW_UPDATE: u8 = 0x8;
+const CONTINUATION: u8 = 0x9;
+const END_STREAM: u8 = 0x1;
+const ACK: u8 = 0x1;
+const END_HEADERS: u8 = 0x4;
+const PADDED: u8 = 0x8;
+const PRIORITY: u8 = 0x20;
+const SETTINGS_ENABLE_PUSH: u16 = 0x2;
+const SETTINGS_INITIAL_WINDOW_SIZE: u16 = 0x4;
+
+/// gRPC status codes, by number.
+const STATUS_NAMES: [&str; 17] = [
+    "OK", "CANCELLED", "UNKNOWN", "INVALID_ARGUMENT", "DEADLINE_EXCEEDED", "NOT_FOUND", "ALREADY_EXISTS",
+    "PERMISSION_DENIED", "RESOURCE_EXHAUSTED", "FAILED_PRECONDITION", "ABORTED", "OUT_OF_RANGE",
+    "UNIMPLEMENTED", "INTERNAL", "UNAVAILABLE", "DATA_LOSS", "UNAUTHENTICATED",
+];
+const DEADLINE_EXCEEDED: u32 = 4;
+
+/// `method` must be `/package.Service/Method`.
+fn check_method(method: &str) -> Result<(), String> {
+    let valid = method.strip_prefix('/').and_then(|m| m.split_once('/')).is_some_and(|(service, name)| {
+        !service.is_empty() && !name.is_empty() && !name.contains('/') && !method.contains(|c: char| c.is_whitespace() || c.is_control())
+    });
+    match valid {
+        true => Ok(()),
+        false => Err(format!("method '{}' must be /package.Service/Method", method)),
+    }
+}
+
+/// Calls the server-streaming `GetRandom` of a gRPC server over HTTP/2,
+/// connecting on first use and again after the connection fails. One call
+/// is in flight at a time; the deadline of each is passed to the server as
+/// `grpc-timeout`, so it stops working on a call this side gave up on.
+pub struct Client {
+    connector: Connector,
+    timeout_ms: u64,
+    /// HEADERS block of every call, which may carry `auth_header`.
+    request_headers: Zeroizing<Vec<u8>>,
+    /// The connection's address and the method, for logs and the ledger.
+    display: String,
+    connection: Mutex<Option<Connection>>,
+}
+
+impl Client {
+    pub fn new(cfg: &GrpcConfig) -> Result<Self, String> {
+        let connector = Connector::new(&cfg.host, cfg.port, cfg.tls.as_ref(), cfg.connect_timeout_ms)?.with_alpn(b"h2");
+        let method = cfg.method.as_deref().unwrap_or(DEFAULT_METHOD);
+        check_method(method)?;
+        // A deadline of at most 8 digits, as grpc-timeout allows
+        let timeout_ms = cfg.timeout_ms.unwrap_or(TIMEOUT_MS).clamp(1, 99_999_999);
+        let authority = match cfg.host.contains(':') {
+            true => format!("[{}]:{}", cfg.host, cfg.port),
+            false => format!("{}:{}", cfg.host, cfg.port),
+        };
+        let (scheme, deadline) = (if connector.is_tls() { "https" } else { "http" }, format!("{}m", timeout_ms));
+        let user_agent = format!("trng-dbus/{}", env!("CARGO_PKG_VERSION"));
+        let auth = match cfg.auth_header.as_deref().map(|header| (header.split_once(':'), header.contains(['\r', '\n']))) {
+            Some((Some((name, value)), false)) => Some((name.trim().to_ascii_lowercase(), value.trim())),
+            Some(_) => return Err("auth_header must be a single 'Name: value' line".to_string()),
+            None => None,
+        };
+        let mut headers = vec![
+            (":method", "POST"),
+            (":scheme", scheme),
+            (":path", method),
+            (":authority", authority.as_str()),
+            ("te", "trailers"),
+            ("content-type", "application/grpc"),
+            ("grpc-timeout", deadline.as_str()),
+            ("user-agent", user_agent.as_str()),
+        ];
+        if let Some((name, value)) = &auth {
+            headers.push((name.as_str(), value));
+        }
+        let request_headers = Zeroizing::new(hpack::encode(&headers));
+        if request_headers.len() > MAX_FRAME {
+            return Err("auth_header is too long".to_string());
+        }
+        let display = format!("{}{}", connector.display(), method);
+        Ok(Self { connector, timeout_ms, request_headers, display, connection: Mutex::new(None) })
+    }
+
+    pub fn display(&self) -> &str {
+        &self.display
+    }
+
+    /// Up to `bytes` from one call, fewer if the server's stream ended
+    /// early or the deadline cut it short. Calls the server failed fail.
+    pub async fn read(&self, bytes: usize) -> Result<Zeroizing<Vec<u8>>, String> {
+        let mut connection = self.connection.lock().await;
+        let mut conn = match connection.take() {
+            Some(conn) => conn,
+            None => {
+                let conn = Connection::open(&self.connector).await.map_err(|e| format!("cannot connect: {}", e))?;
+                log::info!("Connected to gRPC server {}", self.connector.display());
+                conn
+            }
+        };
+        let limit = Duration::from_millis(self.timeout_ms) + CALL_SLACK;
+        let request = encode_request(bytes as u64);
+        let result = timeout(limit, conn.call(&self.request_headers, &request, bytes)).await;
+        let outcome = match result {
+            Ok(Ok(outcome)) => outcome,
+            Ok(Err(Failure::Refused(e))) => {
+                // The call failed on a connection that is still good
+                *connection = Some(conn);
+                return Err(e);
+            }
+            Ok(Err(Failure::Broken(e))) => return Err(e),
+            Err(_) => return Err(format!("no answer within {:?}", limit)),
+        };
+        *connection = Some(conn);
+        drop(connection);
+        match outcome.status {
+            0 => Ok(outcome.data),
+            // The server kept to the deadline passed on: what came is good
+            DEADLINE_EXCEEDED if !outcome.data.is_empty() => Ok(outcome.data),
+            status => {
+                let name = STATUS_NAMES.get(status as usize).unwrap_or(&"unknown status");
+                match outcome.message.is_empty() {
+                    true => Err(format!("server answered {} ({})", name, status)),
+                    false => Err(format!("server answered {} ({}): {}", name, status, outcome.message)),
+                }
+            }
+        }
+    }
+}
+
+/// `GetRandomRequest { uint64 num_bytes = 1; }`
+fn encode_request(num_bytes: u64) -> Vec<u8> {
+    let mut message = vec![0x08];
+    let mut n = num_bytes;
+    while n >= 0x80 {
+        message.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    message.push(n as u8);
+    message
+}
+
+/// Appends the `data` of `GetRandomResponse { bytes data = 1; }` to `out`,
+/// skipping fields it does not know.
+fn decode_response(mut message: &[u8], out: &mut Vec<u8>) -> Result<(), String> {
+    fn varint(message: &mut &[u8]) -> Result<u64, String> {
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = message.split_first().ok_or("truncated varint")?;
+            *message = rest;
+            n |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err("varint too long".to_string())
+    }
+    while !message.is_empty() {
+        let key = varint(&mut message)?;
+        let skip = match key & 7 {
+            0 => {
+                varint(&mut message)?;
+                0
+            }
+            1 => 8,
+            2 => {
+                let len = varint(&mut message)? as usize;
+                if key >> 3 == 1 {
+                    out.extend_from_slice(message.get(..len).ok_or("truncated data field")?);
+                }
+                len
+            }
+            5 => 4,
+            wire_type => return Err(format!("unsupported wire type {}", wire_type)),
+        };
+        message = message.get(skip..).ok_or("truncated field")?;
+    }
+    Ok(())
+}
+
+/// How a call failed.
+#[derive(Debug)]
+enum Failure {
+    /// The server refused or failed the call; the connection is still good.
+    Refused(String),
+    /// The connection failed or broke the protocol and must be replaced.
+    Broken(String),
+}
+
+impl From<io::Error> for Failure {
+    fn from(e: io::Error) -> Self {
+        Failure::Broken(e.to_string())
+    }
+}
+
+fn broken(what: &str) -> Failure {
+    Failure::Broken(format!("HTTP/2 protocol error: {}", what))
+}
+
+/// A call that ran to its end, with the status of its trailers.
+struct Outcome {
+    data: Zeroizing<Vec<u8>>,
+    status: u32,
+    message: String,
+}
+
+struct Frame {
+    kind: u8,
+    flags: u8,
+    stream: u32,
+    payload: Zeroizing<Vec<u8>>,
+}
+
+impl Frame {
+    /// The payload of a DATA or HEADERS frame without its padding.
+    fn unpadded(&self) -> Result<&[u8], Failure> {
+        if self.flags & PADDED == 0 {
+            return Ok(&self.payload);
+        }
+        let pad = *self.payload.first().ok_or_else(|| broken("padded frame without a pad length"))? as usize;
+        self.payload.get(1..self.payload.len().saturating_sub(pad)).ok_or_else(|| broken("padding longer than the frame"))
+    }
+}
+
+fn frame_header(len: usize, kind: u8, flags: u8, stream: u32) -> Vec<u8> {
+    let mut header = (len as u32).to_be_bytes()[1..].to_vec();
+    header.extend_from_slice(&[kind, flags]);
+    header.extend_from_slice(&stream.to_be_bytes());
+    header
+}
+
+fn window_update(stream: u32, increment: u32) -> Vec<u8> {
+    let mut frame = frame_header(4, WINDOW_UPDATE, 0, stream);
+    frame.extend_from_slice(&increment.to_be_bytes());
+    frame
+}
+
+/// An HTTP/2 connection carrying one call at a time. Requests are a few
+/// bytes, far below any flow control window, so only the server's sending
+/// is flow controlled.
+struct Connection {
+    stream: Box<dyn tcp::Stream>,
+    /// Received bytes not yet parsed into frames.
+    buffer: Zeroizing<Vec<u8>>,
+    decoder: hpack::Decoder,
+    next_stream: u32,
+}
+
+impl Connection {
+    async fn open(connector: &Connector) -> io::Result<Self> {
+        let mut conn = Self { stream: connector.connect().await?, buffer: Zeroizing::new(Vec::new()), decoder: hpack::Decoder::new(), next_stream: 1 };
+        // The server's SETTINGS need not be waited for, they are acknowledged as they come
+        let mut hello = PREFACE.to_vec();
+        hello.extend(frame_header(12, SETTINGS, 0, 0));
+        for (id, value) in [(SETTINGS_ENABLE_PUSH, 0), (SETTINGS_INITIAL_WINDOW_SIZE, WINDOW)] {
+            hello.extend_from_slice(&id.to_be_bytes());
+            hello.extend_from_slice(&value.to_be_bytes());
+        }
+        hello.extend(window_update(0, WINDOW - DEFAULT_WINDOW));
+        conn.write(&hello).await?;
+        Ok(conn)
+    }
+
+    /// Makes one call and reads its response to the end.
+    async fn call(&mut self, headers: &[u8], message: &[u8], max_bytes: usize) -> Result<Outcome, Failure> {
+        let id = self.next_stream;
+        if id > i32::MAX as u32 {
+            return Err(Failure::Broken("stream ids used up".to_string()));
+        }
+        self.next_stream += 2;
+        let mut request = Zeroizing::new(frame_header(headers.len(), HEADERS, END_HEADERS, id));
+        request.extend_from_slice(headers);
+        request.extend(frame_header(5 + message.len(), DATA, END_STREAM, id));
+        request.push(0);
+        request.extend_from_slice(&(message.len() as u32).to_be_bytes());
+        request.extend_from_slice(message);
+        self.write(&request).await?;
+
+        let mut response = Response::default();
+        loop {
+            let frame = self.receive().await?;
+            match frame.kind {
+                SETTINGS if frame.flags & ACK == 0 => self.write(&frame_header(0, SETTINGS, ACK, 0)).await?,
+                PING if frame.flags & ACK == 0 => {
+                    let mut pong = frame_header(frame.payload.len(), PING, ACK, 0);
+                    pong.extend_from_slice(&frame.payload);
+                    self.write(&pong).await?;
+                }
+                GOAWAY => {
+                    let code = frame.payload.get(4..8).map_or(0, |c| u32::from_be_bytes([c[0], c[1], c[2], c[3]]));
+                    return Err(Failure::Broken(format!("server closed the connection (GOAWAY error {})", code)));
+                }
+                PUSH_PROMISE => return Err(broken("PUSH_PROMISE though push is disabled")),
+                RST_STREAM if frame.stream == id => {
+                    let code = frame.payload.get(..4).map_or(0, |c| u32::from_be_bytes([c[0], c[1], c[2], c[3]]));
+                    return Err(Failure::Refused(format!("server reset the call (error {})", code)));
+                }
+                HEADERS => {
+                    // Every block is decoded, to keep the dynamic table in step
+                    let block = self.header_block(&frame).await?;
+                    let headers = self.decoder.decode(&block).map_err(Failure::Broken)?;
+                    if frame.stream != id {
+                        continue;
+                    }
+                    if let Some(outcome) = response.headers(&headers, frame.flags & END_STREAM != 0)? {
+                        return Ok(outcome);
+                    }
+                }
+                DATA => {
+                    let len = frame.payload.len() as u32;
+                    let end = frame.flags & END_STREAM != 0;
+                    // Flow control counts padding too; a stream that ended needs no window
+                    let mut updates = Vec::new();
+                    if len > 0 {
+                        updates.extend(window_update(0, len));
+                        if frame.stream == id && !end {
+                            updates.extend(window_update(id, len));
+                        }
+                        self.write(&updates).await?;
+                    }
+                    if frame.stream != id {
+                        continue;
+                    }
+                    response.data(frame.unpadded()?, max_bytes)?;
+                    if end {
+                        return Err(Failure::Refused("the response ended without a status".to_string()));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The header block a HEADERS frame starts, with the CONTINUATION
+    /// frames that must follow it.
+    async fn header_block(&mut self, frame: &Frame) -> Result<Zeroizing<Vec<u8>>, Failure> {
+        let mut fragment = frame.unpadded()?;
+        if frame.flags & PRIORITY != 0 {
+            fragment = fragment.get(5..).ok_or_else(|| broken("HEADERS too short for its priority"))?;
+        }
+        let mut block = Zeroizing::new(fragment.to_vec());
+        let mut flags = frame.flags;
+        while flags & END_HEADERS == 0 {
+            let next = self.receive().await?;
+            if next.kind != CONTINUATION || next.stream != frame.stream {
+                return Err(broken("header block not continued"));
+            }
+            block.extend_from_slice(&next.payload);
+            if block.len() > MAX_HEADER_BLOCK {
+                return Err(broken("header block too large"));
+            }
+            flags = next.flags;
+        }
+        Ok(block)
+    }
+
+    async fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.stream.write_all(bytes).await?;
+        self.stream.flush().await
+    }
+
+    async fn receive(&mut self) -> io::Result<Frame> {
+        loop {
+            if self.buffer.len() >= 9 {
+                let header = &self.buffer[..9];
+                let len = u32::from_be_bytes([0, header[0], header[1], header[2]]) as usize;
+                if len > MAX_FRAME {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "HTTP/2 frame larger than SETTINGS_MAX_FRAME_SIZE"));
+                }
+                if self.buffer.len() >= 9 + len {
+                    let frame = Frame {
+                        kind: header[3],
+                        flags: header[4],
+                        stream: u32::from_be_bytes([header[5], header[6], header[7], header[8]]) & 0x7fff_ffff,
+                        payload: Zeroizing::new(self.buffer[9..9 + len].to_vec()),
+                    };
+                    self.buffer.drain(..9 + len);
+                    return Ok(frame);
+                }
+            }
+            let mut chunk = Zeroizing::new(vec![0u8; 16 * 1024]);
+            let n = self.stream.read(&mut chunk).await?;
+            if n == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "server closed the connection"));
+            }
+            self.buffer.extend_from_slice(&chunk[..n]);
+        }
+    }
+}
+
+/// The response of a call as it arrives.
+#[derive(Default)]
+struct Response {
+    started: bool,
+    /// Received bytes of a gRPC message not yet complete.
+    pending: Zeroizing<Vec<u8>>,
+    data: Zeroizing<Vec<u8>>,
+}
+
+impl Response {
+    /// Takes the response headers, then the trailers, which end the call.
+    fn headers(&mut self, headers: &[(String, String)], end: bool) -> Result<Option<Outcome>, Failure> {
+        let get = |name: &str| headers.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str());
+        if !self.started {
+            self.started = true;
+            match get(":status") {
+                Some("200") => {}
+                Some(status) => return Err(Failure::Refused(format!("server answered HTTP status {}", status))),
+                None => return Err(broken("response without :status")),
+            }
+            // A response of trailers only may leave the content type out
+            if !end && !get("content-type").is_some_and(|t| t.starts_with("application/grpc")) {
+                return Err(Failure::Refused("not a gRPC response".to_string()));
+            }
+            if !end {
+                return Ok(None);
+            }
+        } else if !end {
+            return Err(broken("headers in the middle of a response"));
+        }
+        if !self.pending.is_empty() {
+            return Err(Failure::Refused("the response ended in the middle of a message".to_string()));
+        }
+        let status = get("grpc-status").and_then(|s| s.parse().ok()).ok_or_else(|| Failure::Refused("the response has no grpc-status".to_string()))?;
+        Ok(Some(Outcome { data: std::mem::take(&mut self.data), status, message: percent_decode(get("grpc-message").unwrap_or_default()) }))
+    }
+
+    /// Takes DATA, which carries length-prefixed messages across frames.
+    fn data(&mut self, bytes: &[u8], max_bytes: usize) -> Result<(), Failure> {
+        if !self.started {
+            return Err(broken("DATA before the response headers"));
+        }
+        self.pending.extend_from_slice(bytes);
+        while self.pending.len() >= 5 {
+            // Compression was not offered, so none may be used
+            if self.pending[0] != 0 {
+                return Err(Failure::Broken("server sent a compressed message".to_string()));
+            }
+            let len = u32::from_be_bytes([self.pending[1], self.pending[2], self.pending[3], self.pending[4]]) as usize;
+            if len > MAX_MESSAGE {
+                return Err(Failure::Broken(format!("server sent a message of {} bytes, above {}", len, MAX_MESSAGE)));
+            }
+            if self.pending.len() < 5 + len {
+                break;
+            }
+            decode_response(&self.pending[5..5 + len], &mut self.data).map_err(|e| Failure::Broken(format!("malformed GetRandomResponse: {}", e)))?;
+            self.pending.drain(..5 + len);
+        }
+        if self.data.len() > max_bytes {
+            return Err(Failure::Broken(format!("server sent more than the {} bytes asked for", max_bytes)));
+        }
+        Ok(())
+    }
+}
+
+/// `grpc-message` is percent-encoded.
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok()).and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages() {
+        assert_eq!(encode_request(300), vec![0x08, 0xac, 0x02]);
+        // An unknown varint field, then data split over two fields of the same number
+        let mut out = Vec::new();
+        decode_response(&[0x10, 0x96, 0x01, 0x0a, 0x02, 0xaa, 0xbb, 0x0a, 0x01, 0xcc], &mut out).unwrap();
+        assert_eq!(out, vec![0xaa, 0xbb, 0xcc]);
+        assert!(decode_response(&[0x0a, 0x05, 0x01], &mut out).is_err());
+
+        let mut response = Response::default();
+        let headers = |pairs: &[(&str, &str)]| pairs.iter().map(|(n, v)| (n.to_string(), v.to_string())).collect::<Vec<_>>();
+        assert!(response.headers(&headers(&[(":status", "200"), ("content-type", "application/grpc")]), false).unwrap().is_none());
+        response.data(&[0, 0, 0, 0, 4, 0x0a, 0x02], 16).unwrap();
+        response.data(&[0x01, 0x02], 16).unwrap();
+        let outcome = response.headers(&headers(&[("grpc-status", "4"), ("grpc-message", "too%20slow")]), true).unwrap().unwrap();
+        assert_eq!((outcome.data.to_vec(), outcome.status, outcome.message.as_str()), (vec![1, 2], 4, "too slow"));
+
+        assert!(check_method(DEFAULT_METHOD).is_ok());
+        assert!(check_method("trng.v1.Entropy/GetRandom").is_err());
+        assert!(check_method("/trng.v1.Entropy/").is_err());
+    }
+}
@@ -0,0 +1,323 @@
+use std::collections::VecDeque;
+use std::sync::OnceLock;
+
+// Just enough HPACK (RFC 7541) for the gRPC client: header blocks are sent
+// as plain literals, which every decoder takes, and received ones are
+// decoded in full, dynamic table and Huffman coding included, as servers
+// use both.
+
+/// Dynamic table size of the decoder; the SETTINGS default, which the
+/// client never changes.
+const TABLE_SIZE: usize = 4096;
+
+/// Most header bytes a block may decode to.
+const MAX_DECODED: usize = 64 * 1024;
+
+/// Code length of every symbol of the Huffman code (Appendix B), 256 being
+/// EOS. The code is canonical, so the lengths are all it takes to rebuild it.
+const HUFFMAN_LENGTHS: [u8; 257] = [
+    13, 23, 28, 28, 28, 28, 28, 28, 28, 24, 30, 28, 28, 30, 28, 28,
+    28, 28, 28, 28, 28, 28, 30, 28, 28, 28, 28, 28, 28, 28, 28, 28,
+    6, 10, 10, 12, 13, 6, 8, 11, 10, 10, 8, 11, 8, 6, 6, 6,
+    5, 5, 5, 6, 6, 6, 6, 6, 6, 6, 7, 8, 15, 6, 12, 10,
+    13, 6, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7,
+    7, 7, 7, 7, 7, 7, 7, 7, 8, 7, 8, 13, 19, 13, 14, 6,
+    15, 5, 6, 5, 6, 5, 6, 6, 6, 5, 7, 7, 6, 6, 6, 5,
+    6, 7, 6, 5, 5, 6, 7, 7, 7, 7, 7, 15, 11, 14, 13, 28,
+    20, 22, 20, 20, 22, 22, 22, 23, 22, 23, 23, 23, 23, 23, 24, 23,
+    24, 24, 22, 23, 24, 23, 23, 23, 23, 21, 22, 23, 22, 23, 23, 24,
+    22, 21, 20, 22, 22, 23, 23, 21, 23, 22, 22, 24, 21, 22, 23, 23,
+    21, 21, 22, 21, 23, 22, 23, 23, 20, 22, 22, 22, 23, 22, 22, 23,
+    26, 26, 20, 19, 22, 23, 22, 25, 26, 26, 26, 27, 27, 26, 24, 25,
+    19, 21, 26, 27, 27, 26, 27, 24, 21, 21, 26, 26, 28, 27, 27, 27,
+    20, 24, 20, 21, 22, 21, 21, 23, 22, 22, 25, 25, 24, 24, 26, 23,
+    26, 27, 26, 26, 27, 27, 27, 27, 27, 28, 27, 27, 27, 27, 27, 26,
+    30,
+];
+
+const STATIC_TABLE: [(&str, &str); 61] = [
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
+
+
+/// Encodes `headers` as literals without indexing and with literal names,
+/// which need no state on either side.
+pub fn encode(headers: &[(&str, &str)]) -> Vec<u8> {
+    let mut block = Vec::new();
+    for (name, value) in headers {
+        block.push(0);
+        literal(&mut block, name.as_bytes());
+        literal(&mut block, value.as_bytes());
+    }
+    block
+}
+
+fn literal(block: &mut Vec<u8>, s: &[u8]) {
+    integer(block, 0, 7, s.len());
+    block.extend_from_slice(s);
+}
+
+/// Appends `n` with an `prefix_bits` prefix, the first byte's other bits
+/// set to `flags`.
+fn integer(block: &mut Vec<u8>, flags: u8, prefix_bits: u32, mut n: usize) {
+    let max = (1usize << prefix_bits) - 1;
+    if n < max {
+        block.push(flags | n as u8);
+        return;
+    }
+    block.push(flags | max as u8);
+    n -= max;
+    while n >= 128 {
+        block.push((n % 128) as u8 | 0x80);
+        n /= 128;
+    }
+    block.push(n as u8);
+}
+
+/// Decodes the header blocks of one connection, in the order received.
+pub struct Decoder {
+    /// Newest entry first.
+    table: VecDeque<(String, String)>,
+    size: usize,
+    max_size: usize,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self { table: VecDeque::new(), size: 0, max_size: TABLE_SIZE }
+    }
+
+    pub fn decode(&mut self, block: &[u8]) -> Result<Vec<(String, String)>, String> {
+        let mut headers = Vec::new();
+        let mut decoded = 0;
+        let mut at = 0;
+        while at < block.len() {
+            let first = block[at];
+            let (name, value, index) = if first & 0x80 != 0 {
+                let (name, value) = self.entry(read_integer(block, &mut at, 7)?)?;
+                (name, value, false)
+            } else if first & 0x20 != 0 && first & 0x40 == 0 {
+                let size = read_integer(block, &mut at, 5)?;
+                if size > TABLE_SIZE {
+                    return Err(format!("HPACK table size {} above {}", size, TABLE_SIZE));
+                }
+                self.max_size = size;
+                self.evict(0);
+                continue;
+            } else {
+                // With incremental indexing (01), without (0000) or never (0001)
+                let index = first & 0x40 != 0;
+                let name = match read_integer(block, &mut at, if index { 6 } else { 4 })? {
+                    0 => read_string(block, &mut at)?,
+                    n => self.entry(n)?.0,
+                };
+                (name, read_string(block, &mut at)?, index)
+            };
+            decoded += name.len() + value.len();
+            if decoded > MAX_DECODED {
+                return Err(format!("header block larger than {} bytes", MAX_DECODED));
+            }
+            if index {
+                self.insert(name.clone(), value.clone());
+            }
+            headers.push((name, value));
+        }
+        Ok(headers)
+    }
+
+    fn entry(&self, index: usize) -> Result<(String, String), String> {
+        match index {
+            1..=61 => {
+                let (name, value) = STATIC_TABLE[index - 1];
+                Ok((name.to_string(), value.to_string()))
+            }
+            _ => self.table.get(index.wrapping_sub(62)).cloned().ok_or_else(|| format!("HPACK index {} out of range", index)),
+        }
+    }
+
+    fn insert(&mut self, name: String, value: String) {
+        let size = 32 + name.len() + value.len();
+        self.evict(size);
+        // An entry larger than the table empties it and is not kept
+        if size <= self.max_size {
+            self.size += size;
+            self.table.push_front((name, value));
+        }
+    }
+
+    /// Drops the oldest entries until `room` more bytes fit.
+    fn evict(&mut self, room: usize) {
+        while self.size + room > self.max_size {
+            let Some((name, value)) = self.table.pop_back() else { break };
+            self.size -= 32 + name.len() + value.len();
+        }
+    }
+}
+
+fn read_integer(block: &[u8], at: &mut usize, prefix_bits: u32) -> Result<usize, String> {
+    let max = (1usize << prefix_bits) - 1;
+    let mut n = block[*at] as usize & max;
+    *at += 1;
+    if n < max {
+        return Ok(n);
+    }
+    for shift in (0..28).step_by(7) {
+        let byte = *block.get(*at).ok_or("truncated HPACK integer")?;
+        *at += 1;
+        n += (byte as usize & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+    Err("HPACK integer too large".to_string())
+}
+
+fn read_string(block: &[u8], at: &mut usize) -> Result<String, String> {
+    let first = *block.get(*at).ok_or("truncated HPACK string")?;
+    let len = read_integer(block, at, 7)?;
+    let bytes = block.get(*at..*at + len).ok_or("truncated HPACK string")?;
+    *at += len;
+    let bytes = if first & 0x80 != 0 { huffman_decode(bytes)? } else { bytes.to_vec() };
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// The canonical code by length: symbols ordered by code, and per length
+/// the first code, its position in that order and how many codes it has.
+struct Code {
+    symbols: Vec<u16>,
+    first_code: [u32; 31],
+    first_index: [usize; 31],
+    count: [u32; 31],
+}
+
+fn code() -> &'static Code {
+    static CODE: OnceLock<Code> = OnceLock::new();
+    CODE.get_or_init(|| {
+        let mut symbols: Vec<u16> = (0..257).collect();
+        symbols.sort_by_key(|&s| (HUFFMAN_LENGTHS[s as usize], s));
+        let mut code = Code { symbols, first_code: [0; 31], first_index: [0; 31], count: [0; 31] };
+        let (mut next, mut len) = (0u32, HUFFMAN_LENGTHS[code.symbols[0] as usize] as usize);
+        for (i, &s) in code.symbols.iter().enumerate() {
+            let l = HUFFMAN_LENGTHS[s as usize] as usize;
+            next <<= l - len;
+            len = l;
+            if code.count[l] == 0 {
+                code.first_code[l] = next;
+                code.first_index[l] = i;
+            }
+            code.count[l] += 1;
+            next += 1;
+        }
+        code
+    })
+}
+
+fn huffman_decode(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    let code = code();
+    let mut out = Vec::with_capacity(bytes.len() * 8 / 5);
+    let (mut bits, mut len) = (0u32, 0usize);
+    for byte in bytes {
+        for shift in (0..8).rev() {
+            bits = (bits << 1) | ((byte >> shift) & 1) as u32;
+            len += 1;
+            if len > 30 {
+                return Err("invalid Huffman code".to_string());
+            }
+            let Some(offset) = bits.checked_sub(code.first_code[len]).filter(|&o| o < code.count[len]) else { continue };
+            match code.symbols[code.first_index[len] + offset as usize] {
+                256 => return Err("EOS in a Huffman string".to_string()),
+                symbol => out.push(symbol as u8),
+            }
+            (bits, len) = (0, 0);
+        }
+    }
+    // Padding is the most significant bits of EOS, all ones
+    if len > 7 || bits != (1 << len) - 1 {
+        return Err("invalid Huffman padding".to_string());
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        // RFC 7541 C.4: requests with Huffman coding, sharing one dynamic table
+        let mut decoder = Decoder::new();
+        let first = hex::decode("828684418cf1e3c2e5f23a6ba0ab90f4ff").unwrap();
+        let headers = decoder.decode(&first).unwrap();
+        assert_eq!(headers[3], (":authority".to_string(), "www.example.com".to_string()));
+        let second = hex::decode("828684be5886a8eb10649cbf").unwrap();
+        let headers = decoder.decode(&second).unwrap();
+        assert_eq!(headers[3], (":authority".to_string(), "www.example.com".to_string()));
+        assert_eq!(headers[4], ("cache-control".to_string(), "no-cache".to_string()));
+
+        let block = encode(&[(":status", "200"), ("grpc-message", "x".repeat(200).as_str())]);
+        let headers = Decoder::new().decode(&block).unwrap();
+        assert_eq!(headers[1].1.len(), 200);
+        assert!(Decoder::new().decode(&[0xbf]).is_err());
+        assert!(huffman_decode(&[0xff, 0xff]).is_err());
+    }
+}
@@ -0,0 +1,287 @@
+use crate::config::{HttpConfig, HttpFormat};
+use crate::tls::TlsClient;
+use serde_json::Value;
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Duration};
+use zeroize::Zeroizing;
+
+/// Replaced in the URL by the number of bytes asked for.
+const BYTES_PLACEHOLDER: &str = "{bytes}";
+
+/// Largest response accepted, headers included.
+const MAX_RESPONSE_BYTES: usize = 16 * 1024 * 1024 + 64 * 1024;
+
+#[derive(Debug, PartialEq)]
+struct Url {
+    https: bool,
+    /// `host[:port]` as written, for the `Host` header.
+    authority: String,
+    host: String,
+    port: u16,
+    /// Path and query.
+    target: String,
+}
+
+fn parse_url(url: &str) -> Result<Url, String> {
+    let (https, rest) = match (url.strip_prefix("https://"), url.strip_prefix("http://")) {
+        (Some(rest), _) => (true, rest),
+        (None, Some(rest)) => (false, rest),
+        _ => return Err(format!("unsupported URL '{}', use http:// or https://", url)),
+    };
+    let (authority, target) = match rest.find(['/', '?']) {
+        Some(i) if rest[i..].starts_with('?') => (&rest[..i], format!("/{}", &rest[i..])),
+        Some(i) => (&rest[..i], rest[i..].to_string()),
+        None => (rest, "/".to_string()),
+    };
+    if authority.contains('@') {
+        return Err("credentials in the URL are not supported, use auth_header".to_string());
+    }
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => {
+            (host, port.parse().map_err(|_| format!("invalid port in URL '{}'", url))?)
+        }
+        _ => (authority, if https { 443 } else { 80 }),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() {
+        return Err(format!("no host in URL '{}'", url));
+    }
+    Ok(Url { https, authority: authority.to_string(), host: host.to_string(), port, target })
+}
+
+/// Fetches the bytes of an HTTP source, one `Connection: close` GET at a time.
+pub struct Client {
+    url: Url,
+    /// The URL without its query, which may hold credentials.
+    display: String,
+    tls: Option<TlsClient>,
+    auth_header: Option<Zeroizing<String>>,
+    format: HttpFormat,
+    json_pointer: String,
+    timeout: Duration,
+}
+
+impl Client {
+    pub fn new(cfg: &HttpConfig) -> Result<Self, String> {
+        let url = parse_url(&cfg.url)?;
+        let tls = match (&cfg.tls, url.https) {
+            (Some(tls), true) => Some(TlsClient::new(tls)?),
+            (None, true) => return Err("https URLs need a tls table with ca_file or pin_sha256".to_string()),
+            (Some(_), false) => return Err("tls is set but the URL is not https".to_string()),
+            (None, false) => {
+                log::warn!("HTTP source {} fetches its bytes in the clear - use https", cfg.id);
+                None
+            }
+        };
+        if let Some(header) = &cfg.auth_header {
+            if !header.contains(':') || header.contains(['\r', '\n']) {
+                return Err("auth_header must be a single 'Name: value' line".to_string());
+            }
+        }
+        Ok(Self {
+            display: cfg.url.split('?').next().unwrap_or_default().to_string(),
+            url,
+            tls,
+            auth_header: cfg.auth_header.clone().map(Zeroizing::new),
+            format: cfg.format.unwrap_or_default(),
+            json_pointer: cfg.json_pointer.clone().unwrap_or_else(|| "/data".to_string()),
+            timeout: Duration::from_millis(cfg.timeout_ms.unwrap_or(10_000)),
+        })
+    }
+
+    pub fn display(&self) -> &str {
+        &self.display
+    }
+
+    /// Asks for `bytes` and returns the bytes the response carries, which
+    /// may be more or fewer.
+    pub async fn fetch(&self, bytes: usize) -> Result<Zeroizing<Vec<u8>>, String> {
+        let response = timeout(self.timeout, self.exchange(bytes))
+            .await
+            .map_err(|_| format!("no response within {:?}", self.timeout))?
+            .map_err(|e| e.to_string())?;
+        extract(self.format, &self.json_pointer, parse_response(&response)?)
+    }
+
+    async fn exchange(&self, bytes: usize) -> io::Result<Zeroizing<Vec<u8>>> {
+        let target = self.url.target.replace(BYTES_PLACEHOLDER, &bytes.to_string());
+        let mut request = Zeroizing::new(format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nAccept: */*\r\nConnection: close\r\nUser-Agent: trngdbus/{}\r\n",
+            target,
+            self.url.authority,
+            env!("CARGO_PKG_VERSION")
+        ));
+        if let Some(header) = &self.auth_header {
+            request.push_str(header);
+            request.push_str("\r\n");
+        }
+        request.push_str("\r\n");
+        let stream = TcpStream::connect((self.url.host.as_str(), self.url.port)).await?;
+        match &self.tls {
+            Some(tls) => exchange(tls.connect(&self.url.host, stream).await?, request.as_bytes()).await,
+            None => exchange(stream, request.as_bytes()).await,
+        }
+    }
+}
+
+async fn exchange<S: AsyncRead + AsyncWrite + Unpin>(mut stream: S, request: &[u8]) -> io::Result<Zeroizing<Vec<u8>>> {
+    stream.write_all(request).await?;
+    stream.flush().await?;
+    let mut response = Zeroizing::new(Vec::with_capacity(64 * 1024));
+    let mut chunk = Zeroizing::new([0u8; 16 * 1024]);
+    loop {
+        match stream.read(&mut chunk[..]).await {
+            Ok(0) => break,
+            Ok(n) if response.len() + n > MAX_RESPONSE_BYTES => {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "response too large"));
+            }
+            Ok(n) => response.extend_from_slice(&chunk[..n]),
+            // Many servers close without a TLS close_notify; framing catches truncation
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(response)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// The body of a complete 2xx response.
+fn parse_response(response: &[u8]) -> Result<Zeroizing<Vec<u8>>, String> {
+    let end = find(response, b"\r\n\r\n").ok_or("incomplete response headers")?;
+    let head = std::str::from_utf8(&response[..end]).map_err(|_| "response headers are not UTF-8")?;
+    let mut lines = head.split("\r\n");
+    let status_line = lines.next().unwrap_or_default();
+    let status: u16 = match status_line.split(' ').collect::<Vec<_>>()[..] {
+        [version, status, ..] if version.starts_with("HTTP/1.") => status.parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| format!("malformed status line '{}'", status_line))?;
+    if !(200..300).contains(&status) {
+        return Err(format!("server answered '{}'", status_line));
+    }
+    let (mut chunked, mut length) = (false, None);
+    for (name, value) in lines.filter_map(|line| line.split_once(':')) {
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("transfer-encoding") {
+            chunked = value.to_ascii_lowercase().ends_with("chunked");
+        } else if name.eq_ignore_ascii_case("content-length") {
+            length = Some(value.parse::<usize>().map_err(|_| format!("invalid Content-Length '{}'", value))?);
+        }
+    }
+    let body = &response[end + 4..];
+    match length {
+        _ if chunked => dechunk(body),
+        Some(n) if body.len() < n => Err(format!("response truncated at {} of {} bytes", body.len(), n)),
+        Some(n) => Ok(Zeroizing::new(body[..n].to_vec())),
+        None => Ok(Zeroizing::new(body.to_vec())),
+    }
+}
+
+fn dechunk(mut body: &[u8]) -> Result<Zeroizing<Vec<u8>>, String> {
+    let mut out = Zeroizing::new(Vec::with_capacity(body.len()));
+    loop {
+        let line_end = find(body, b"\r\n").ok_or("truncated chunked body")?;
+        let line = String::from_utf8_lossy(&body[..line_end]);
+        let size = line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16).map_err(|_| format!("invalid chunk size '{}'", line))?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Ok(out);
+        }
+        if body.len() < size + 2 {
+            return Err("truncated chunked body".to_string());
+        }
+        out.extend_from_slice(&body[..size]);
+        body = &body[size + 2..];
+    }
+}
+
+fn extract(format: HttpFormat, pointer: &str, body: Zeroizing<Vec<u8>>) -> Result<Zeroizing<Vec<u8>>, String> {
+    if format == HttpFormat::Raw {
+        return Ok(body);
+    }
+    let doc: Value = serde_json::from_slice(&body).map_err(|e| format!("invalid JSON response: {}", e))?;
+    let bytes = match doc.pointer(pointer) {
+        Some(Value::String(s)) => base64_decode(s).ok_or_else(|| format!("{} is not valid base64", pointer))?,
+        Some(Value::Array(values)) => values
+            .iter()
+            .map(|v| v.as_u64().and_then(|b| u8::try_from(b).ok()))
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(|| format!("{} is not an array of byte values", pointer))?,
+        Some(_) => return Err(format!("{} is neither a base64 string nor an array of bytes", pointer)),
+        None => return Err(format!("response has nothing at {}", pointer)),
+    };
+    Ok(Zeroizing::new(bytes))
+}
+
+/// Standard or URL-safe base64, padding optional.
+fn base64_decode(s: &str) -> Option<Vec<u8>> {
+    let s = s.trim_end_matches('=');
+    if s.len() % 4 == 1 {
+        return None;
+    }
+    let mut out = Vec::with_capacity(s.len() * 3 / 4);
+    let (mut acc, mut bits) = (0u32, 0u32);
+    for c in s.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+        acc = ((acc << 6) | value as u32) & 0xffff;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url() {
+        let url = parse_url("https://qrng.example:8443/api/random?length={bytes}").unwrap();
+        assert_eq!((url.https, url.host.as_str(), url.port), (true, "qrng.example", 8443));
+        assert_eq!(url.target, "/api/random?length={bytes}");
+        let url = parse_url("http://[::1]?n=1").unwrap();
+        assert_eq!((url.host.as_str(), url.port, url.target.as_str(), url.authority.as_str()), ("::1", 80, "/?n=1", "[::1]"));
+        assert!(parse_url("ftp://host/").is_err());
+        assert!(parse_url("https://user:pw@host/").is_err());
+    }
+
+    #[test]
+    fn test_response_framing() {
+        let body = parse_response(b"HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\nabcdef").unwrap();
+        assert_eq!(&body[..], b"abc");
+        let chunked = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2;x=y\r\nde\r\n0\r\n\r\n";
+        assert_eq!(&parse_response(chunked).unwrap()[..], b"abcde");
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n").is_err());
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\nContent-Length: 9\r\n\r\nabc").is_err());
+        assert!(parse_response(b"HTTP/1.1 429 Too Many Requests\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn test_json_bodies() {
+        let json = |s: &str| Zeroizing::new(s.as_bytes().to_vec());
+        let bytes = extract(HttpFormat::Json, "/data", json(r#"{"data":"AAEC/w=="}"#)).unwrap();
+        assert_eq!(&bytes[..], [0, 1, 2, 255]);
+        let bytes = extract(HttpFormat::Json, "/result/bytes", json(r#"{"result":{"bytes":[7,8,255]}}"#)).unwrap();
+        assert_eq!(&bytes[..], [7, 8, 255]);
+        assert!(extract(HttpFormat::Json, "/data", json(r#"{"data":[256]}"#)).is_err());
+        assert!(extract(HttpFormat::Json, "/data", json(r#"{"other":"AA"}"#)).is_err());
+        assert_eq!(base64_decode("-_8").unwrap(), [0xfb, 0xff]);
+        assert!(base64_decode("A").is_none());
+    }
+}
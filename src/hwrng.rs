@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+pub const DEFAULT_PATH: &str = "/dev/hwrng";
+
+/// Where the kernel's hw_random core describes the device behind `/dev/hwrng`.
+pub const DEFAULT_SYSFS: &str = "/sys/class/misc/hw_random";
+
+/// What hw_random reports about the driver that `/dev/hwrng` passes through.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Backend {
+    /// `rng_current`, e.g. `tpm-rng-0`; `None` when no driver is bound.
+    pub current: Option<String>,
+    /// `rng_quality`: entropy the kernel credits per 1024 bits read.
+    pub quality: Option<u32>,
+    /// `rng_available`, space separated.
+    pub available: Option<String>,
+}
+
+fn read_attribute(sysfs: &Path, name: &str) -> Option<String> {
+    let value = std::fs::read_to_string(sysfs.join(name)).ok()?;
+    Some(value.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+impl Backend {
+    /// Reads the attributes under `sysfs`; missing ones stay `None`.
+    pub fn probe(sysfs: &Path) -> Self {
+        Self {
+            current: read_attribute(sysfs, "rng_current").filter(|c| c != "none"),
+            quality: read_attribute(sysfs, "rng_quality").and_then(|q| q.parse().ok()),
+            available: read_attribute(sysfs, "rng_available"),
+        }
+    }
+
+    /// Entropy credit matching the kernel's own: `quality` / 1024, if above 0.
+    pub fn entropy_credit(&self) -> Option<f64> {
+        self.quality.filter(|&q| q > 0).map(|q| (q.min(1024) as f64) / 1024.0)
+    }
+
+    pub fn describe(&self) -> HashMap<String, String> {
+        let mut info = HashMap::new();
+        info.insert("backend".to_string(), self.current.clone().unwrap_or_else(|| "none".to_string()));
+        if let Some(quality) = self.quality {
+            info.insert("quality".to_string(), quality.to_string());
+        }
+        if let Some(available) = &self.available {
+            info.insert("available".to_string(), available.clone());
+        }
+        info
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe() {
+        let dir = std::env::temp_dir().join(format!("trng-hwrng-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(Backend::probe(&dir), Backend::default());
+        std::fs::write(dir.join("rng_current"), "tpm-rng-0\n").unwrap();
+        std::fs::write(dir.join("rng_quality"), "512\n").unwrap();
+        std::fs::write(dir.join("rng_available"), "tpm-rng-0 virtio_rng.0\n").unwrap();
+        let backend = Backend::probe(&dir);
+        assert_eq!(backend.current.as_deref(), Some("tpm-rng-0"));
+        assert_eq!(backend.entropy_credit(), Some(0.5));
+        assert_eq!(backend.describe()["available"], "tpm-rng-0 virtio_rng.0");
+        std::fs::write(dir.join("rng_current"), "none\n").unwrap();
+        std::fs::write(dir.join("rng_quality"), "0\n").unwrap();
+        let backend = Backend::probe(&dir);
+        assert_eq!(backend.current, None);
+        assert_eq!(backend.entropy_credit(), None);
+        assert_eq!(backend.describe()["backend"], "none");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,48 @@
+use hkdf::Hkdf;
+use sha2::Sha256;
+
+/// Longest key HKDF-SHA256 can produce (255 hash lengths).
+pub const MAX_KEY_LEN: usize = 255 * 32;
+
+/// Fixed salt so derived keys are specific to this service.
+const SALT: &[u8] = b"trng-dbus DeriveKey v1";
+
+/// Input keying material to gather for a key of `length` bytes: at least a
+/// full hash block, more for long keys.
+pub fn ikm_len(length: usize) -> usize {
+    length.max(32)
+}
+
+/// HKDF-SHA256 (RFC 5869) over fresh entropy, with `label` as the info
+/// string. Returns `None` for lengths HKDF cannot produce.
+pub fn derive_key(ikm: &[u8], label: &str, length: usize) -> Option<Vec<u8>> {
+    if length == 0 || length > MAX_KEY_LEN {
+        return None;
+    }
+    let mut okm = vec![0u8; length];
+    Hkdf::<Sha256>::new(Some(SALT), ikm).expand(label.as_bytes(), &mut okm).ok()?;
+    Some(okm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_labels_separate_keys() {
+        let ikm = [3u8; 32];
+        let a = derive_key(&ikm, "disk", 32).unwrap();
+        assert_eq!(a, derive_key(&ikm, "disk", 32).unwrap());
+        assert_ne!(a, derive_key(&ikm, "session", 32).unwrap());
+        assert_eq!(derive_key(&ikm, "disk", 16).unwrap(), a[..16]);
+    }
+
+    #[test]
+    fn test_length_bounds() {
+        assert!(derive_key(&[0; 32], "x", 0).is_none());
+        assert!(derive_key(&[0; 32], "x", MAX_KEY_LEN + 1).is_none());
+        assert_eq!(derive_key(&[0; 32], "x", MAX_KEY_LEN).unwrap().len(), MAX_KEY_LEN);
+        assert_eq!(ikm_len(16), 32);
+        assert_eq!(ikm_len(64), 64);
+    }
+}
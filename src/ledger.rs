@@ -0,0 +1,246 @@
+use crate::config::LedgerConfig;
+use crate::signature::FileIdentity;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Bytes `start..start + len` of a source's read stream, which numbers
+/// everything the source read from its file or device from 0 in read order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: u64,
+    pub len: u64,
+}
+
+/// Where each of a run of source bytes sits in the read stream, in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Spans(Vec<Span>);
+
+impl Spans {
+    /// Appends `span`, merging it into the last one when they are adjacent.
+    pub fn push(&mut self, span: Span) {
+        if span.len == 0 {
+            return;
+        }
+        match self.0.last_mut() {
+            Some(last) if last.start + last.len == span.start => last.len += span.len,
+            _ => self.0.push(span),
+        }
+    }
+
+    pub fn append(&mut self, other: Spans) {
+        for span in other.0 {
+            self.push(span);
+        }
+    }
+
+    /// Keeps the spans of the first `at` bytes and returns those of the rest.
+    pub fn split_off(&mut self, at: usize) -> Spans {
+        let mut left = at as u64;
+        for i in 0..self.0.len() {
+            let span = self.0[i];
+            if left < span.len {
+                let mut rest = self.0.split_off(i);
+                if left > 0 {
+                    rest[0] = Span { start: span.start + left, len: span.len - left };
+                    self.0.push(Span { start: span.start, len: left });
+                }
+                return Spans(rest);
+            }
+            left -= span.len;
+        }
+        Spans::default()
+    }
+
+    fn pairs(&self) -> Vec<[u64; 2]> {
+        self.0.iter().map(|s| [s.start, s.len]).collect()
+    }
+}
+
+impl From<Span> for Spans {
+    fn from(span: Span) -> Self {
+        let mut spans = Spans::default();
+        spans.push(span);
+        spans
+    }
+}
+
+/// Hands out the positions of a source's read stream.
+#[derive(Debug, Clone, Default)]
+pub struct Positions(Arc<AtomicU64>);
+
+impl Positions {
+    /// The positions of the next `len` bytes read.
+    pub fn claim(&self, len: usize) -> Span {
+        Span { start: self.0.fetch_add(len as u64, Ordering::Relaxed), len: len as u64 }
+    }
+}
+
+/// What a stretch of the read stream was read from.
+pub enum Origin<'a> {
+    /// An OS interface such as `getrandom`, or a serial device node.
+    Device(&'a str),
+    /// A web API, by its URL without the query, a network server or an upstream D-Bus service.
+    Remote(&'a str),
+    /// `/dev/hwrng`, with the hw_random driver behind it at the time.
+    Hwrng { path: &'a str, backend: Option<&'a str> },
+    /// The stdout of a command, by its program and process id.
+    Process { command: &'a str, pid: Option<u32> },
+    /// A PKCS#11 token, by its module, slot and serial number.
+    Token { module: &'a str, slot: u64, serial: &'a str },
+    /// `(offset, len)` extents of a file, in read order.
+    File { path: &'a str, identity: Option<FileIdentity>, extents: &'a [(u64, u64)] },
+}
+
+/// What combined bytes were read for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Purpose {
+    /// Served to the requester.
+    Request,
+    /// Seeding the padding DRBG, whose output later pads short answers.
+    DrbgSeed,
+    /// Seeding the requester's client stream, which serves its later reads.
+    StreamSeed,
+    /// Seeding the `[drbg]` output stage, whose output is served to requesters.
+    OutputDrbgSeed,
+    /// A watchdog canary, discarded.
+    Canary,
+    /// Absorbed into the `pool`, whose output is served to requesters.
+    PoolAbsorb,
+}
+
+impl Purpose {
+    fn as_str(self) -> &'static str {
+        match self {
+            Purpose::Request => "request",
+            Purpose::DrbgSeed => "drbg_seed",
+            Purpose::StreamSeed => "stream_seed",
+            Purpose::OutputDrbgSeed => "output_drbg_seed",
+            Purpose::Canary => "canary",
+            Purpose::PoolAbsorb => "pool_absorb",
+        }
+    }
+}
+
+/// Append-only JSON-lines record (`[ledger]`) of what each source read and
+/// which read-stream spans went into which request, so that bytes later
+/// found defective can be traced to the requests they were served into.
+/// Served bytes only ever appear as a SHA-256 digest.
+pub struct Ledger {
+    path: String,
+    out: Mutex<File>,
+}
+
+static LEDGER: OnceLock<Ledger> = OnceLock::new();
+
+/// Opens the ledger, if configured; must come before any source is read.
+pub fn open(cfg: Option<&LedgerConfig>) -> io::Result<()> {
+    let Some(cfg) = cfg else { return Ok(()) };
+    let out = OpenOptions::new().create(true).append(true).mode(0o600).open(&cfg.path)?;
+    log::info!("Recording source provenance in {}", cfg.path);
+    let _ = LEDGER.set(Ledger { path: cfg.path.clone(), out: Mutex::new(out) });
+    Ok(())
+}
+
+pub fn enabled() -> bool {
+    LEDGER.get().is_some()
+}
+
+/// Records that `source` read `span` of its stream from `origin`.
+pub fn record_read(source: &str, span: Span, origin: Origin<'_>) {
+    let Some(ledger) = LEDGER.get() else { return };
+    if span.len == 0 {
+        return;
+    }
+    let mut record = json!({
+        "kind": "read",
+        "at_ms": now_ms(),
+        "source": source,
+        "start": span.start,
+        "len": span.len,
+    });
+    match origin {
+        Origin::Device(device) => record["device"] = json!(device),
+        Origin::Remote(url) => record["url"] = json!(url),
+        Origin::Hwrng { path, backend } => {
+            record["device"] = json!(path);
+            record["backend"] = json!(backend);
+        }
+        Origin::Process { command, pid } => {
+            record["command"] = json!(command);
+            record["pid"] = json!(pid);
+        }
+        Origin::Token { module, slot, serial } => {
+            record["module"] = json!(module);
+            record["slot"] = json!(slot);
+            record["serial"] = json!(serial);
+        }
+        Origin::File { path, identity, extents } => {
+            record["path"] = json!(path);
+            record["identity"] = json!(identity);
+            record["extents"] = json!(extents);
+        }
+    }
+    ledger.append(&record);
+}
+
+/// Records that `bytes`, combined from the given spans of each source,
+/// were read for `purpose` during request `request_id` of `client` (0 and
+/// empty for the service's own reads).
+pub fn record_serve(request_id: u64, client: &str, purpose: Purpose, bytes: &[u8], sources: &[(&str, &Spans)]) {
+    let Some(ledger) = LEDGER.get() else { return };
+    let sources: serde_json::Map<String, serde_json::Value> =
+        sources.iter().map(|(id, spans)| (id.to_string(), json!(spans.pairs()))).collect();
+    ledger.append(&json!({
+        "kind": "serve",
+        "at_ms": now_ms(),
+        "request_id": request_id,
+        "client": client,
+        "purpose": purpose.as_str(),
+        "len": bytes.len(),
+        "sha256": hex::encode(Sha256::digest(bytes)),
+        "sources": sources,
+    }));
+}
+
+impl Ledger {
+    fn append(&self, record: &serde_json::Value) {
+        let line = format!("{}\n", record);
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = out.write_all(line.as_bytes()) {
+            log::error!("Cannot append to provenance ledger {}: {}", self.path, e);
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spans_split_and_merge() {
+        let mut spans = Spans::default();
+        spans.push(Span { start: 0, len: 4 });
+        spans.push(Span { start: 4, len: 2 });
+        spans.push(Span { start: 10, len: 3 });
+        assert_eq!(spans.pairs(), [[0, 6], [10, 3]]);
+
+        let rest = spans.split_off(5);
+        assert_eq!(spans.pairs(), [[0, 5]]);
+        assert_eq!(rest.pairs(), [[5, 1], [10, 3]]);
+
+        let mut whole = Spans::from(Span { start: 0, len: 5 });
+        whole.append(rest);
+        assert_eq!(whole.clone().split_off(6).pairs(), [[10, 3]]);
+        assert_eq!(whole.split_off(9), Spans::default());
+    }
+}
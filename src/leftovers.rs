@@ -0,0 +1,184 @@
+use crate::error::Error;
+use crate::ledger::Spans;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use zeroize::Zeroizing;
+
+/// Most handed-back bytes a source keeps. Every request returns at most one
+/// request slice per source and takes from the lane first, so only a large
+/// crowd of concurrent short reads could get here.
+const MAX_LANE_BYTES: usize = 16 * 1024 * 1024;
+
+/// Leftover bytes the aggregator handed back unused. They are served before
+/// anything the source buffered or reads afresh, in the order they came
+/// back, and are kept apart from its buffer so a full buffer costs none.
+#[derive(Default)]
+pub struct ReturnLane {
+    segments: Mutex<Lane>,
+    returned: AtomicU64,
+    reused: AtomicU64,
+    dropped: AtomicU64,
+}
+
+#[derive(Default)]
+struct Lane {
+    /// Each with its read-stream spans, see `ledger.rs`.
+    segments: VecDeque<(Zeroizing<Vec<u8>>, Spans)>,
+    len: usize,
+}
+
+/// What a source's return lane took in, served again and had to drop.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LaneStats {
+    pub returned: u64,
+    pub reused: u64,
+    pub dropped: u64,
+}
+
+impl ReturnLane {
+    /// Keeps `leftover` to be served next, behind earlier leftovers.
+    pub fn put(&self, leftover: Vec<u8>, mut spans: Spans) {
+        let mut leftover = Zeroizing::new(leftover);
+        let mut lane = self.lock();
+        let room = MAX_LANE_BYTES - lane.len;
+        if leftover.len() > room {
+            log::warn!("Return lane full: dropping {} leftover bytes", leftover.len() - room);
+            self.dropped.fetch_add((leftover.len() - room) as u64, Ordering::Relaxed);
+            leftover.truncate(room);
+            spans.split_off(room);
+        }
+        if leftover.is_empty() {
+            return;
+        }
+        self.returned.fetch_add(leftover.len() as u64, Ordering::Relaxed);
+        lane.len += leftover.len();
+        lane.segments.push_back((leftover, spans));
+    }
+
+    /// Zeroizes `leftover` instead of keeping it, e.g. when its age matters.
+    pub fn discard(&self, leftover: Vec<u8>) {
+        self.dropped.fetch_add(Zeroizing::new(leftover).len() as u64, Ordering::Relaxed);
+    }
+
+    /// Zeroizes everything in the lane.
+    pub fn wipe(&self) {
+        let mut lane = self.lock();
+        self.dropped.fetch_add(lane.len as u64, Ordering::Relaxed);
+        *lane = Lane::default();
+    }
+
+    /// Takes up to `num_bytes` from the lane, oldest first.
+    fn take(&self, num_bytes: usize) -> (Vec<u8>, Spans) {
+        let mut lane = self.lock();
+        let mut out = Vec::with_capacity(num_bytes.min(lane.len));
+        let mut spans = Spans::default();
+        while out.len() < num_bytes {
+            let Some((mut segment, mut segment_spans)) = lane.segments.pop_front() else { break };
+            let want = num_bytes - out.len();
+            if segment.len() > want {
+                let rest = Zeroizing::new(segment.split_off(want));
+                lane.segments.push_front((rest, segment_spans.split_off(want)));
+            }
+            out.extend_from_slice(&segment);
+            spans.append(segment_spans);
+        }
+        lane.len -= out.len();
+        (out, spans)
+    }
+
+    /// Puts bytes taken by `take` back in front, as if never taken.
+    fn unread(&self, bytes: Vec<u8>, spans: Spans) {
+        if bytes.is_empty() {
+            return;
+        }
+        let mut lane = self.lock();
+        lane.len += bytes.len();
+        lane.segments.push_front((Zeroizing::new(bytes), spans));
+    }
+
+    /// Serves `num_bytes` from the lane first and the rest from `fresh`.
+    /// If `fresh` fails the lane bytes stay in the lane for the next caller.
+    pub async fn serve<F, Fut>(&self, num_bytes: usize, fresh: F) -> Result<(Vec<u8>, Spans), Error>
+    where
+        F: FnOnce(usize) -> Fut,
+        Fut: Future<Output = Result<(Vec<u8>, Spans), Error>>,
+    {
+        let (mut result, mut spans) = self.take(num_bytes);
+        let from_lane = result.len();
+        if from_lane < num_bytes {
+            match fresh(num_bytes - from_lane).await {
+                Ok((bytes, fresh_spans)) => {
+                    result.extend_from_slice(&Zeroizing::new(bytes));
+                    spans.append(fresh_spans);
+                }
+                Err(e) => {
+                    self.unread(result, spans);
+                    return Err(e);
+                }
+            }
+        }
+        self.reused.fetch_add(from_lane as u64, Ordering::Relaxed);
+        Ok((result, spans))
+    }
+
+    pub fn stats(&self) -> LaneStats {
+        LaneStats {
+            returned: self.returned.load(Ordering::Relaxed),
+            reused: self.reused.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Lane> {
+        self.segments.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::Span;
+
+    fn spans(start: u64, len: u64) -> Spans {
+        Spans::from(Span { start, len })
+    }
+
+    #[tokio::test]
+    async fn test_lane_served_first_in_order() {
+        let lane = ReturnLane::default();
+        let fresh = |n| async move { Ok((vec![9; n], spans(100, n as u64))) };
+        lane.put(vec![1, 2, 3], spans(10, 3));
+        lane.put(vec![4, 5], spans(20, 2));
+        let (bytes, served) = lane.serve(4, fresh).await.unwrap();
+        assert_eq!(bytes, [1, 2, 3, 4]);
+        let mut expected = spans(10, 3);
+        expected.push(Span { start: 20, len: 1 });
+        assert_eq!(served, expected);
+        let (bytes, served) = lane.serve(3, fresh).await.unwrap();
+        assert_eq!(bytes, [5, 9, 9]);
+        let mut expected = spans(21, 1);
+        expected.push(Span { start: 100, len: 2 });
+        assert_eq!(served, expected);
+
+        // A failed read leaves the lane as it was
+        lane.put(vec![6, 7], spans(30, 2));
+        assert!(lane.serve(4, |_| async { Err(Error::Unexpected) }).await.is_err());
+        let (bytes, served) = lane.serve(2, |_| async { Err(Error::Unexpected) }).await.unwrap();
+        assert_eq!((bytes, served), (vec![6, 7], spans(30, 2)));
+
+        let stats = lane.stats();
+        assert_eq!((stats.returned, stats.reused, stats.dropped), (7, 7, 0));
+    }
+
+    #[test]
+    fn test_lane_bounded() {
+        let lane = ReturnLane::default();
+        lane.put(vec![0; MAX_LANE_BYTES - 1], Spans::default());
+        lane.put(vec![0; 3], spans(0, 3));
+        assert_eq!(lane.stats().dropped, 2);
+        lane.wipe();
+        assert_eq!(lane.take(1).0.len(), 0);
+    }
+}
@@ -1,21 +1,88 @@
 use crate::error::Error;
-use libc;
 use std::convert::TryFrom;
 use std::mem::MaybeUninit;
 
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "netbsd",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "openbsd"
+)))]
+compile_error!("no OS entropy backend for this target: trng-dbus needs getrandom (Linux, Android, FreeBSD, DragonFly, NetBSD) or getentropy (macOS, iOS, OpenBSD)");
+
 /// Retrieves the last OS error.
 fn last_os_error() -> Error {
-    #[cfg(target_os = "linux")]
-    {
-        let errno: libc::c_int = unsafe { *libc::__errno_location() };
-        match u32::try_from(errno) {
-            Ok(code) if code != 0 => Error::OsError(code),
-            _ => Error::ErrnoNotPositive,
-        }
-    }
-    #[cfg(not(target_os = "linux"))]
-    // For non-Linux systems, this function should not be called.
-    Error::Unexpected
+    match std::io::Error::last_os_error().raw_os_error().map(u32::try_from) {
+        Some(Ok(code)) if code != 0 => Error::OsError(code),
+        _ => Error::ErrnoNotPositive,
+    }
+}
+
+/// The operating system's CSPRNG, through the call the platform offers.
+pub struct OsRandomSource;
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd", target_os = "dragonfly", target_os = "netbsd"))]
+impl OsRandomSource {
+    pub const BACKEND: &'static str = "getrandom";
+
+    /// `insecure` asks for `GRND_INSECURE`, which never blocks and may return
+    /// bytes from an RNG that is not yet fully seeded. Kernels without the
+    /// flag (Linux before 5.6) reject it; the regular pool is used then.
+    fn fill(buf: &mut [MaybeUninit<u8>], insecure: bool) -> Result<(), Error> {
+        let getrandom = |flags: libc::c_uint| {
+            move |buf: &mut [MaybeUninit<u8>]| unsafe { libc::getrandom(buf.as_mut_ptr() as *mut libc::c_void, buf.len(), flags) }
+        };
+        if !insecure {
+            return sys_fill_exact(buf, getrandom(0));
+        }
+        match sys_fill_exact(buf, getrandom(libc::GRND_INSECURE)) {
+            Err(Error::OsError(code)) if code == libc::EINVAL as u32 => sys_fill_exact(buf, getrandom(0)),
+            res => res,
+        }
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "openbsd"))]
+impl OsRandomSource {
+    pub const BACKEND: &'static str = "getentropy";
+
+    /// Largest request getentropy accepts.
+    const MAX_CALL: usize = 256;
+
+    /// getentropy has no insecure mode and does not block once the system
+    /// is seeded, so `insecure` changes nothing.
+    fn fill(buf: &mut [MaybeUninit<u8>], _insecure: bool) -> Result<(), Error> {
+        sys_fill_exact(buf, |buf| {
+            let len = buf.len().min(Self::MAX_CALL);
+            match unsafe { libc::getentropy(buf.as_mut_ptr() as *mut libc::c_void, len) } {
+                0 => len as libc::ssize_t,
+                _ => -1,
+            }
+        })
+    }
+}
+
+impl OsRandomSource {
+    fn octets(num_octets: usize, insecure: bool) -> Result<Vec<u8>, Error> {
+        // Allocate a buffer with uninitialized memory
+        let mut buffer: Vec<MaybeUninit<u8>> = Vec::with_capacity(num_octets);
+        // It's safe to assume the capacity is set correctly
+        unsafe { buffer.set_len(num_octets) }
+
+        // Fill the buffer with random bytes
+        Self::fill(&mut buffer, insecure)?;
+
+        // Convert to initialized bytes
+        // Safety: We just filled the entire buffer with valid random bytes
+        let initialized: Vec<u8> = unsafe {
+            std::mem::transmute::<Vec<MaybeUninit<u8>>, Vec<u8>>(buffer)
+        };
+        Ok(initialized)
+    }
 }
 
 /// Fill a buffer by repeatedly invoking `sys_fill`.
@@ -50,7 +117,8 @@ fn sys_fill_exact(
     Ok(())
 }
 
-/// Fills the buffer with random octets using the Linux `getrandom` syscall.
+/// Fills the buffer with random octets from the OS entropy source
+/// (`getrandom` or `getentropy`, see `OsRandomSource`).
 ///
 /// # Arguments
 ///
@@ -60,26 +128,25 @@ fn sys_fill_exact(
 ///
 /// A `Result` containing the vector of random octets on success, or an `Error` on failure.
 pub fn os_fill_rand_octets(num_octets: usize) -> Result<Vec<u8>, Error> {
-    // Allocate a buffer with uninitialized memory
-    let mut buffer: Vec<MaybeUninit<u8>> = Vec::with_capacity(num_octets);
-    // It's safe to assume the capacity is set correctly
-    unsafe { buffer.set_len(num_octets) }
-
-    // Fill the buffer with random bytes
-    sys_fill_exact(&mut buffer, |buffer| unsafe {
-        libc::getrandom(
-            buffer.as_mut_ptr() as *mut libc::c_void,
-            buffer.len(),
-            0, // Flags: 0 to use the default entropy pool
-        )
-    })?;
-
-    // Convert to initialized bytes
-    // Safety: We just filled the entire buffer with valid random bytes
-    let initialized: Vec<u8> = unsafe {
-        std::mem::transmute::<Vec<MaybeUninit<u8>>, Vec<u8>>(buffer)
-    };
-    Ok(initialized)
+    OsRandomSource::octets(num_octets, false)
+}
+
+/// Fills the buffer with best-effort bytes that may come from an RNG that
+/// is not yet fully seeded (`getrandom(GRND_INSECURE)` where available).
+/// Only for clients that explicitly asked for best-effort bytes.
+pub fn os_fill_insecure_octets(num_octets: usize) -> Result<Vec<u8>, Error> {
+    OsRandomSource::octets(num_octets, true)
+}
+
+/// Release of the running kernel, e.g. "6.8.0-45-generic".
+pub fn kernel_release() -> Option<String> {
+    // SAFETY: utsname is plain old data that uname fills in
+    let mut uts: libc::utsname = unsafe { std::mem::zeroed() };
+    if unsafe { libc::uname(&mut uts) } != 0 {
+        return None;
+    }
+    let release = unsafe { std::ffi::CStr::from_ptr(uts.release.as_ptr()) };
+    Some(release.to_string_lossy().into_owned())
 }
 
 #[cfg(test)]
@@ -104,6 +171,38 @@ mod tests {
         assert_eq!(octets.len(), num_octets);
     }
 
+    #[test]
+    fn test_fill_insecure_octets() {
+        let octets = os_fill_insecure_octets(64).unwrap();
+        assert_eq!(octets.len(), 64);
+    }
+
+    #[test]
+    fn test_fill_beyond_single_call() {
+        // getentropy serves at most 256 bytes per call
+        let octets = os_fill_rand_octets(1000).unwrap();
+        assert_eq!(octets.len(), 1000);
+        assert!(octets[256..].iter().any(|&b| b != 0));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_linux_backend() {
+        assert_eq!(OsRandomSource::BACKEND, "getrandom");
+    }
+
+    #[test]
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "openbsd"))]
+    fn test_getentropy_backend() {
+        assert_eq!(OsRandomSource::BACKEND, "getentropy");
+        assert_eq!(os_fill_insecure_octets(300).unwrap().len(), 300);
+    }
+
+    #[test]
+    fn test_kernel_release() {
+        assert!(kernel_release().is_some_and(|r| !r.is_empty()));
+    }
+
     #[test]
     fn test_fill_random_octets_max() {
         let num_octets = 1024;
@@ -1,16 +1,123 @@
 mod error;
+mod aes;
+mod affinity;
+mod audio;
+mod access;
+mod attestation;
 mod lrng;
 mod config;
+mod drbg;
 mod sources;
 mod aggregator;
+mod alerts;
+mod breaker;
+mod budget;
+mod bus;
+mod cpu;
+#[cfg(feature = "chaos")]
+mod chaos;
 mod circular_buffer;
+mod clock;
+mod conditioning;
+mod ctr_drbg;
+mod deliveries;
+mod events;
+mod fortuna;
+mod groups;
+#[cfg(feature = "grpc")]
+mod grpc;
+#[cfg(feature = "grpc")]
+mod hpack;
+mod http;
+mod hwrng;
+mod jitter;
+mod kdf;
+mod ledger;
+mod leftovers;
+mod maintenance;
+mod manifest;
+mod memory;
+mod migrate;
+mod monitor;
+mod mqtt;
+#[cfg(feature = "nats")]
+mod nats;
+mod pkcs11;
+mod polkit;
+mod pool;
+mod quotas;
+mod relay;
+mod reservations;
+mod retry;
+mod runtime;
+mod sampling;
+mod scheduler;
+mod sha3;
+mod shaping;
+mod serial;
+mod shutdown;
+mod signature;
+mod simulate;
+mod snapshot;
+mod sniff;
+mod streams;
+mod subscriptions;
+mod supervisor;
+mod tcp;
+mod tls;
+mod transform;
+mod trending;
+mod upstream;
+mod watchdog;
+mod wipe;
 
-use std::{error::Error, future::pending};
+use std::{borrow::Cow, collections::HashMap, error::Error, future::Future, sync::Arc};
+use std::sync::atomic::{AtomicU64, Ordering};
+use futures::StreamExt;
+use tokio::time::{Duration, Instant};
+use zbus::message::Header;
+use zbus::names::{BusName, InterfaceName};
+use zbus::zvariant::{OwnedFd, OwnedValue, Str, Value};
+use tokio::sync::{broadcast, watch};
+use zbus::object_server::SignalEmitter;
 use zbus::{connection, interface};
+use zeroize::Zeroizing;
 // use lrng::os_fill_rand_octets;
 use log::{error, info};
-use aggregator::Aggregator;
-use config::load_config;
+use access::{AccessPolicy, Endpoint, Restricted};
+use attestation::{Attestor, Statement};
+use aggregator::{Aggregator, Provenance, ReadOptions};
+use config::{load_config, BusKind, CombineMode, FlattenedConfig, GroupConfig, ReadinessMode, DEFAULT_INTERFACE, MONITOR_INTERFACE};
+use events::ServiceEvent;
+use monitor::Monitor;
+use scheduler::Requester;
+use shaping::Shaper;
+use conditioning::Conditioner;
+use deliveries::Deliveries;
+use streams::ClientStreams;
+use quotas::ClientQuotas;
+use reservations::Reservations;
+use budget::LatencyBudget;
+use relay::Relay;
+use subscriptions::{Sink, Subscriptions};
+
+/// Deadline for the sampling methods, which take no timeout argument.
+const SAMPLING_TIMEOUT_MS: u64 = 1000;
+
+/// Deadline for `CaptureRawSample`.
+const CAPTURE_TIMEOUT_MS: u64 = 5000;
+
+/// Deadline for reading what `Reserve` earmarks.
+const RESERVE_TIMEOUT_MS: u64 = 10_000;
+
+/// Status of an answer from the low-assurance jitter fallback.
+const STATUS_FALLBACK: i32 = 1;
+
+/// Largest `BytesReady` chunk, well below the bus's message size limits.
+const ASYNC_CHUNK_BYTES: usize = 1024 * 1024;
+
+/// How often changes to the counter properties are announced.
+const PROPERTIES_INTERVAL: Duration = Duration::from_secs(1);
 
 fn get_config_path() -> String {
     if let Ok(home) = std::env::var("HOME") {
@@ -20,61 +127,1431 @@ fn get_config_path() -> String {
     }
 }
 
-struct SourceXorAggregator(Aggregator);
+/// Maps service errors to the status codes returned over D-Bus.
+fn status_code(e: &crate::error::Error) -> i32 {
+    match e {
+        crate::error::Error::OsError(_) => -1,
+        crate::error::Error::ErrnoNotPositive => -2,
+        crate::error::Error::Unexpected => -3,
+        crate::error::Error::RateLimited => -4,
+        crate::error::Error::Busy => -5,
+        crate::error::Error::IntegrityFailure => -6,
+        crate::error::Error::SourcesUnavailable => -7,
+        crate::error::Error::InvalidOption(_) => -8,
+        crate::error::Error::InsufficientEntropy => -9,
+        crate::error::Error::QuotaExceeded => -10,
+        crate::error::Error::AccessDenied => -11,
+        crate::error::Error::NotReady => -12,
+        crate::error::Error::MemoryExhausted => -13,
+        crate::error::Error::TooFewSources => -14,
+        crate::error::Error::Timeout => -15,
+        crate::error::Error::RequestTooLarge => -16,
+    }
+}
+
+/// Converts a metadata value that holds no file descriptors, which cannot fail.
+fn owned(value: Value<'_>) -> OwnedValue {
+    OwnedValue::try_from(value).expect("values without fds are ownable")
+}
+
+/// The "provenance" (a{sv}) metadata of `ReadBytesEx`.
+fn provenance_value(provenance: &Provenance) -> OwnedValue {
+    let sources: Vec<HashMap<&str, Value<'_>>> = provenance
+        .sources
+        .iter()
+        .map(|s| {
+            HashMap::from([
+                ("id", Value::from(s.id.as_str())),
+                ("kind", Value::from(s.kind)),
+                ("version", Value::from(s.version.as_str())),
+                ("healthy", Value::from(s.healthy)),
+                ("breaker", Value::from(s.breaker)),
+                ("entropy_credit", Value::from(s.entropy_credit)),
+                ("entropy_per_byte", Value::from(s.entropy_per_byte)),
+            ])
+        })
+        .collect();
+    owned(Value::from(HashMap::from([
+        ("sources", Value::from(sources)),
+        ("conditioning", Value::from(provenance.conditioning.as_str())),
+        ("entropy_bits", Value::from(provenance.entropy_bits)),
+        ("service_version", Value::from(env!("CARGO_PKG_VERSION"))),
+    ])))
+}
+
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Scheduling identity of the caller of a method, with a fresh request id.
+fn requester(header: &Header<'_>, priority: u8) -> Requester {
+    Requester {
+        client: header.sender().map(|s| s.to_string()).unwrap_or_default(),
+        priority,
+        request_id: NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed),
+    }
+}
+
+/// `bytes` read after `reserved` ones, which come first.
+fn after_reserved(reserved: &[u8], bytes: Vec<u8>) -> Vec<u8> {
+    if reserved.is_empty() {
+        return bytes;
+    }
+    let bytes = Zeroizing::new(bytes);
+    [reserved, &bytes[..]].concat()
+}
+
+/// Parses the `a{sv}` options of `ReadBytesEx`.
+fn parse_read_options(options: &HashMap<String, OwnedValue>) -> Result<ReadOptions, crate::error::Error> {
+    let mut opts = ReadOptions::default();
+    for (name, value) in options {
+        let invalid = || crate::error::Error::InvalidOption(name.clone());
+        match name.as_str() {
+            "drbg_fill" => opts.drbg_fill = bool::try_from(value).map_err(|_| invalid())?,
+            "insecure_fill" => opts.insecure_fill = bool::try_from(value).map_err(|_| invalid())?,
+            "priority" => opts.priority = u8::try_from(value).map_err(|_| invalid())?,
+            "personalization" => {
+                // Accepts a string (s) or raw bytes (ay)
+                let bytes = match <&str>::try_from(value) {
+                    Ok(s) => s.as_bytes().to_vec(),
+                    Err(_) => value
+                        .try_clone()
+                        .ok()
+                        .and_then(|v| Vec::<u8>::try_from(v).ok())
+                        .ok_or_else(invalid)?,
+                };
+                if bytes.len() > drbg::MAX_PERSONALIZATION {
+                    return Err(invalid());
+                }
+                opts.personalization = Some(bytes);
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(opts)
+}
+
+/// What every endpoint serves from, shared between them.
+#[derive(Clone)]
+struct Shared {
+    aggregator: Arc<Aggregator>,
+    subscriptions: Arc<Subscriptions>,
+    attestor: Option<Arc<Attestor>>,
+    streams: Option<Arc<ClientStreams>>,
+    quotas: Option<Arc<ClientQuotas>>,
+    latency_budget: Option<LatencyBudget>,
+    reservations: Option<Arc<Reservations>>,
+    deliveries: Arc<Deliveries>,
+}
+
+/// One endpoint: `lv.lumii.trng.Rng` itself or a `[[groups]]` entry served
+/// through `groups::Branded`.
+#[derive(Clone)]
+struct SourceXorAggregator {
+    aggregator: Arc<Aggregator>,
+    subscriptions: Arc<Subscriptions>,
+    access: AccessPolicy,
+    attestor: Option<Arc<Attestor>>,
+    streams: Option<Arc<ClientStreams>>,
+    /// `[quotas]`, shared by every endpoint.
+    quotas: Option<Arc<ClientQuotas>>,
+    /// `[latency_budget]`, shared by every endpoint.
+    latency_budget: Option<LatencyBudget>,
+    /// `[reservations]`, shared by every endpoint but kept per endpoint.
+    reservations: Option<Arc<Reservations>>,
+    /// `RequestBytesAsync` deliveries, shared by every endpoint.
+    deliveries: Arc<Deliveries>,
+    /// Interface this endpoint's signals are emitted on.
+    interface: InterfaceName<'static>,
+    /// The group's `shaping`; the default interface is never shaped.
+    shaper: Option<Arc<Shaper>>,
+    /// The group's `conditioner`; the default interface is not conditioned.
+    conditioner: Option<Arc<Conditioner>>,
+    /// The group's `min_sources`; the default interface has none.
+    min_sources: Option<usize>,
+    /// The group's `combine`; the default interface uses `[sources] combine`.
+    combine: Option<CombineMode>,
+}
 
 impl SourceXorAggregator {
-    fn new(aggregator: Aggregator) -> Self {
-        Self(aggregator)
+    fn new(shared: Shared, access: AccessPolicy, interface: InterfaceName<'static>) -> Self {
+        let Shared { aggregator, subscriptions, attestor, streams, quotas, latency_budget, reservations, deliveries } = shared;
+        Self {
+            aggregator,
+            subscriptions,
+            access,
+            attestor,
+            streams,
+            quotas,
+            latency_budget,
+            reservations,
+            deliveries,
+            interface,
+            shaper: None,
+            conditioner: None,
+            min_sources: None,
+            combine: None,
+        }
+    }
+
+    /// The endpoint of `group`, whose access falls back to `[access]`.
+    fn for_group(shared: Shared, group: &GroupConfig, access: Option<&config::AccessConfig>) -> Self {
+        let mut policy = AccessPolicy::new(group.access.as_ref().or(access));
+        if let Some(uids) = &group.uids {
+            policy = policy.restrict_callers(uids.clone());
+        }
+        let interface = InterfaceName::try_from(group.interface.clone()).expect("validated with the config");
+        let shaper = group.shaping.as_ref().map(|cfg| Arc::new(Shaper::new(cfg)));
+        let conditioner = group.conditioner.and_then(|kind| Conditioner::new(kind, &group.name)).map(Arc::new);
+        Self { shaper, conditioner, min_sources: group.min_sources, combine: group.combine, ..Self::new(shared, policy, interface) }
+    }
+
+    /// This endpoint's combine mode, or the pool that serves it instead.
+    fn combine_name(&self) -> &'static str {
+        match self.aggregator.pool_name() {
+            Some(pool) => pool,
+            None => self.combine.unwrap_or(self.aggregator.combine_mode()).as_str(),
+        }
+    }
+
+    /// Optional features this endpoint offers, as `GetCapabilities` names them.
+    fn features(&self) -> Vec<&'static str> {
+        // Compiled into every build
+        let mut features = vec!["fd_passing", "streaming", "subscriptions", "sampling", "key_derivation"];
+        let configured = [
+            ("attestation", self.attestor.is_some()),
+            ("sessions", self.streams.is_some()),
+            ("reservations", self.reservations.is_some()),
+            ("quotas", self.quotas.is_some()),
+            ("latency_budget", self.latency_budget.is_some()),
+            ("drbg", self.aggregator.drbg_stats().is_some()),
+            ("shaping", self.shaper.is_some()),
+            ("conditioning", self.conditioner.is_some()),
+        ];
+        features.extend(configured.iter().filter(|(_, on)| *on).map(|(name, _)| *name));
+        features
+    }
+
+    /// One line for the log at startup: what is served and how.
+    fn banner(&self) -> String {
+        format!(
+            "{} {}: {} sources ({}), {}, requests up to {} bytes; features: {}; source kinds built in: {}",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+            self.aggregator.source_count(),
+            self.aggregator.configured_kinds().join(", "),
+            self.combine_name(),
+            self.aggregator.max_request_bytes(),
+            self.features().join(", "),
+            Aggregator::source_kinds().join(", ")
+        )
+    }
+
+    /// Runs `read` with what is left of `timeout_ms` once the caller's
+    /// `[quotas]` and the endpoint's shaper admit `bytes`, under its
+    /// conditioner, `min_sources` and `combine`; `served` tells how many of
+    /// them went out. More than `[service] max_request_bytes` fails with
+    /// `RequestTooLarge` before anything is charged or allocated. Under
+    /// `[latency_budget]` `read` only gets the gathering slice of what is
+    /// left, and its conditioning stops at the end of the next slice.
+    async fn shaped<T, F, Fut>(&self, bytes: usize, timeout_ms: u64, read: F, served: impl Fn(&T) -> usize) -> Result<T, crate::error::Error>
+    where
+        F: FnOnce(u64) -> Fut,
+        Fut: Future<Output = Result<T, crate::error::Error>>,
+    {
+        self.aggregator.check_request_size(bytes)?;
+        let client = scheduler::current().client;
+        // The service's own reads are not a client's
+        let quotas = self.quotas.as_deref().filter(|_| !client.is_empty());
+        if let Some(quotas) = quotas {
+            quotas.admit(&client, bytes)?;
+        }
+        let res = self.prepaid(bytes, timeout_ms, read, &served).await;
+        if let Some(quotas) = quotas {
+            quotas.refund(&client, bytes.saturating_sub(res.as_ref().map_or(0, served)));
+        }
+        res
+    }
+
+    /// `shaped` for bytes already checked against `max_request_bytes` and
+    /// charged to the caller's `[quotas]`.
+    async fn prepaid<T, F, Fut>(&self, bytes: usize, timeout_ms: u64, read: F, served: impl Fn(&T) -> usize) -> Result<T, crate::error::Error>
+    where
+        F: FnOnce(u64) -> Fut,
+        Fut: Future<Output = Result<T, crate::error::Error>>,
+    {
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        if let Some(shaper) = &self.shaper {
+            shaper.admit(bytes, deadline).await?;
+        }
+        let res = match self.aggregator.admit().await {
+            Ok(_permit) => {
+                let now = Instant::now();
+                let (timeout_ms, condition_by) = match &self.latency_budget {
+                    Some(budget) if timeout_ms > 0 => {
+                        let (gather_ms, condition_by) = budget.split(now.into_std(), deadline.into_std());
+                        (gather_ms, Some(condition_by))
+                    }
+                    _ => (deadline.saturating_duration_since(now).as_millis() as u64, None),
+                };
+                self.scoped(budget::conditioning_until(condition_by, read(timeout_ms))).await
+            }
+            Err(e) => Err(e),
+        };
+        if let Some(shaper) = &self.shaper {
+            shaper.refund(bytes.saturating_sub(res.as_ref().map_or(0, served)));
+        }
+        res
+    }
+
+    /// Runs `fut` under the endpoint's conditioner, `min_sources` and `combine`.
+    async fn scoped<F: Future>(&self, fut: F) -> F::Output {
+        let fut = conditioning::applying(self.conditioner.clone(), fut);
+        aggregator::requiring(self.min_sources, aggregator::combining(self.combine, fut)).await
+    }
+
+    /// The client streams, if `[client_streams]` enabled them.
+    fn streams(&self) -> Result<&ClientStreams, crate::error::Error> {
+        self.streams.as_deref().ok_or_else(|| crate::error::Error::InvalidOption("client_streams".to_string()))
+    }
+
+    /// The reservations, if `[reservations]` enabled them.
+    fn reservations(&self) -> Result<&Reservations, crate::error::Error> {
+        self.reservations.as_deref().ok_or_else(|| crate::error::Error::InvalidOption("reservations".to_string()))
+    }
+
+    /// Reads `num_bytes` exactly, as the caller's request, and adds them to
+    /// its reservation on this endpoint. Limits are checked, and memory is
+    /// charged, before anything is read. Returns the bytes now reserved.
+    async fn reserve_with(&self, header: &Header<'_>, num_bytes: u64, ttl_seconds: u64) -> Result<usize, crate::error::Error> {
+        let owner = header.sender().ok_or(crate::error::Error::Unexpected)?;
+        let reservations = self.reservations()?;
+        let n = num_bytes as usize;
+        let ttl = reservations.admit(owner.as_str(), self.interface.as_str(), n, ttl_seconds)?;
+        let charge = self.aggregator.reserve(n, "a reservation")?;
+        let read = self.shaped(n, RESERVE_TIMEOUT_MS, |t| self.aggregator.read_bytes_exact(n, t), Vec::len);
+        let bytes = Zeroizing::new(scheduler::on_behalf_of(requester(header, 0), read).await?);
+        reservations.add(owner.as_str(), self.interface.as_str(), bytes, ttl, charge)
+    }
+
+    /// Takes up to `n` of the bytes the caller holds reserved on this endpoint.
+    fn take_reserved(&self, header: &Header<'_>, n: usize) -> Zeroizing<Vec<u8>> {
+        match (&self.reservations, header.sender()) {
+            (Some(reservations), Some(owner)) => reservations.take(owner.as_str(), self.interface.as_str(), n),
+            _ => Zeroizing::new(Vec::new()),
+        }
+    }
+
+    /// Hands reserved bytes taken for a read that failed back to the caller's reservation.
+    fn restore_reserved(&self, header: &Header<'_>, bytes: &[u8]) {
+        if let (Some(reservations), Some(owner)) = (&self.reservations, header.sender()) {
+            reservations.restore(owner.as_str(), self.interface.as_str(), bytes);
+        }
+    }
+
+    async fn read_session_with(&self, header: &Header<'_>, session_id: u64, num_bytes: u64, timeout_ms: u64) -> Result<Vec<u8>, crate::error::Error> {
+        let owner = header.sender().ok_or(crate::error::Error::Unexpected)?;
+        let streams = self.streams()?;
+        let n = num_bytes as usize;
+        let read = self.shaped(n, timeout_ms, move |t| streams.read_session(&self.aggregator, session_id, owner.as_str(), n, t), Vec::len);
+        scheduler::on_behalf_of(requester(header, 0), read).await
+    }
+
+    async fn capture_with(
+        &self,
+        connection: &zbus::Connection,
+        header: &Header<'_>,
+        source_id: &str,
+        bytes: u64,
+    ) -> Result<Vec<u8>, crate::error::Error> {
+        let what = format!("raw capture from source {}", source_id);
+        let uid = self.access.authorize(polkit::Action::Capture, &what, connection, header).await?;
+        let n = bytes as usize;
+        let capture = move |t| self.aggregator.capture_raw(source_id, n, t);
+        let sample = self.shaped(n, CAPTURE_TIMEOUT_MS, capture, Vec::len).await?;
+        log::warn!("Raw capture: {} bytes from source {} diverted to uid {}", sample.len(), source_id, uid);
+        Ok(sample)
+    }
+
+    async fn authorize_tuning(&self, connection: &zbus::Connection, header: &Header<'_>, source_id: &str) -> Result<u32, crate::error::Error> {
+        let what = format!("tuning of source {}", source_id);
+        self.access.authorize(polkit::Action::Manage, &what, connection, header).await
+    }
+
+    async fn tune_with(
+        &self,
+        connection: &zbus::Connection,
+        header: &Header<'_>,
+        source_id: &str,
+        options: &HashMap<String, OwnedValue>,
+    ) -> Result<(), crate::error::Error> {
+        let uid = self.authorize_tuning(connection, header, source_id).await?;
+        let mut low_watermark = None;
+        let mut high_watermark = None;
+        let mut chunk = None;
+        for (name, value) in options {
+            let invalid = || crate::error::Error::InvalidOption(name.clone());
+            match name.as_str() {
+                "low_watermark_percent" => low_watermark = Some(u8::try_from(value).map_err(|_| invalid())?),
+                "high_watermark_percent" => high_watermark = Some(u8::try_from(value).map_err(|_| invalid())?),
+                "replenish_chunk" => chunk = Some(u64::try_from(value).map_err(|_| invalid())? as usize),
+                _ => return Err(invalid()),
+            }
+        }
+        self.aggregator.set_tuning(source_id, (low_watermark, high_watermark), chunk)?;
+        let mut changed: Vec<&str> = options.keys().map(String::as_str).collect();
+        changed.sort_unstable();
+        log::info!("Source {} retuned by uid {}: {}", source_id, uid, changed.join(", "));
+        Ok(())
+    }
+
+    fn subscribe_with(&self, header: &Header<'_>, bytes_per_interval: u64, interval_ms: u64, sink: Sink) -> Result<u64, crate::error::Error> {
+        let owner = header.sender().ok_or(crate::error::Error::Unexpected)?;
+        self.subscriptions.start(
+            self.aggregator.clone(),
+            owner.as_str(),
+            bytes_per_interval as usize,
+            Duration::from_millis(interval_ms),
+            sink,
+            self.shaper.clone(),
+        )
+    }
+
+    /// Serves a `RequestBytesAsync` request, already charged as a whole, as
+    /// `BytesReady` chunks until `num_bytes` went out or `deadline` passed;
+    /// the last one is `final`. Returns the bytes sent.
+    async fn deliver_chunks(self, emitter: SignalEmitter<'static>, request_id: u64, num_bytes: usize, deadline: Instant) -> usize {
+        let mut sent = 0;
+        while sent < num_bytes {
+            let n = (num_bytes - sent).min(ASYNC_CHUNK_BYTES);
+            let timeout_ms = deadline.saturating_duration_since(Instant::now()).as_millis() as u64;
+            let chunk = match self.prepaid(n, timeout_ms, |t| self.aggregator.read_bytes(n, t), Vec::len).await {
+                Ok(bytes) => Zeroizing::new(bytes),
+                Err(e) => {
+                    error!("Async request {} failed after {} bytes: {:?}", request_id, sent, e);
+                    Zeroizing::new(Vec::new())
+                }
+            };
+            sent += chunk.len();
+            // A short or failed read ends the request with what was sent so far
+            let last = sent == num_bytes || chunk.len() < n;
+            if let Err(e) = emitter.emit(self.interface.as_ref(), "BytesReady", &(request_id, &chunk[..], last)).await {
+                info!("Async request {} delivery stopped: {}", request_id, e);
+                return sent - chunk.len();
+            }
+            if last {
+                break;
+            }
+        }
+        info!("Async request {} done: {} of {} bytes", request_id, sent, num_bytes);
+        sent
+    }
+}
+
+impl Endpoint for SourceXorAggregator {
+    fn access(&self) -> &AccessPolicy {
+        &self.access
     }
 }
 
 #[interface(name = "lv.lumii.trng.Rng")]
 impl SourceXorAggregator {
     /// ReadBytes returns up to `num_bytes` of data within `timeout_ms`.
-    /// Returns (status, bytes) where status is 0 for success, negative for errors.
-    async fn read_bytes(&mut self, num_bytes: u64, timeout_ms: u64) -> (i32, Vec<u8>) {
-        match self.0.read_bytes(num_bytes as usize, timeout_ms).await {
-            Ok(bytes) => (0, bytes),
+    /// Returns (status, bytes) where status is 0 for success, negative for errors,
+    /// and 1 when every source is quarantined and the bytes come from the
+    /// low-assurance jitter fallback (only with `[fallback] policy = "serve_flagged"`).
+    /// With `[client_streams]` the bytes come from the caller's own DRBG
+    /// stream. Bytes the caller holds reserved here (`Reserve`) come first.
+    async fn read_bytes(&self, num_bytes: u64, timeout_ms: u64, #[zbus(header)] header: Header<'_>) -> (i32, Vec<u8>) {
+        let reserved = self.take_reserved(&header, num_bytes as usize);
+        let n = num_bytes as usize - reserved.len();
+        if n == 0 && !reserved.is_empty() {
+            return (0, reserved.to_vec());
+        }
+        let res = match (&self.streams, header.sender()) {
+            (Some(streams), Some(owner)) => {
+                let read = self.shaped(n, timeout_ms, move |t| streams.read_own(&self.aggregator, owner.as_str(), n, t), Vec::len);
+                scheduler::on_behalf_of(requester(&header, 0), read).await.map(|bytes| (bytes, false))
+            }
+            _ => {
+                let read = self.shaped(n, timeout_ms, |t| self.aggregator.read_bytes_or_fallback(n, t), |(bytes, _)| bytes.len());
+                scheduler::on_behalf_of(requester(&header, 0), read).await
+            }
+        };
+        match res {
+            Ok((bytes, false)) => (0, after_reserved(&reserved, bytes)),
+            Ok((bytes, true)) => (STATUS_FALLBACK, after_reserved(&reserved, bytes)),
+            // A short answer is still an answer
+            Err(e) if !reserved.is_empty() => {
+                log::warn!("Serving {} reserved bytes only: {:?}", reserved.len(), e);
+                (0, reserved.to_vec())
+            }
             Err(e) => {
                 error!("Error reading random bytes: {:?}", e);
-                let status = match e {
-                    crate::error::Error::OsError(_) => -1,
-                    crate::error::Error::ErrnoNotPositive => -2,
-                    crate::error::Error::Unexpected => -3,
+                (status_code(&e), Vec::new())
+            }
+        }
+    }
+
+    /// ReadBytesExact returns exactly `num_bytes`, reading again while the
+    /// sources come back short, or status -15 and nothing once `timeout_ms`
+    /// passes; never a short read. With `[client_streams]` the bytes come
+    /// from the caller's own DRBG stream. Bytes the caller holds reserved
+    /// here come first, and stay reserved if the rest cannot be read.
+    /// Returns (status, bytes).
+    async fn read_bytes_exact(&self, num_bytes: u64, timeout_ms: u64, #[zbus(header)] header: Header<'_>) -> (i32, Vec<u8>) {
+        let reserved = self.take_reserved(&header, num_bytes as usize);
+        let n = num_bytes as usize - reserved.len();
+        if n == 0 && !reserved.is_empty() {
+            return (0, reserved.to_vec());
+        }
+        let res = match (&self.streams, header.sender()) {
+            (Some(streams), Some(owner)) => {
+                let read = self.shaped(n, timeout_ms, move |t| streams.read_own(&self.aggregator, owner.as_str(), n, t), Vec::len);
+                let res = scheduler::on_behalf_of(requester(&header, 0), read).await;
+                res.and_then(|bytes| if bytes.len() == n { Ok(bytes) } else { Err(crate::error::Error::Timeout) })
+            }
+            _ => {
+                let read = self.shaped(n, timeout_ms, |t| self.aggregator.read_bytes_exact(n, t), Vec::len);
+                scheduler::on_behalf_of(requester(&header, 0), read).await
+            }
+        };
+        match res {
+            Ok(bytes) => (0, after_reserved(&reserved, bytes)),
+            Err(e) => {
+                self.restore_reserved(&header, &reserved);
+                error!("Error reading exact random bytes: {:?}", e);
+                (status_code(&e), Vec::new())
+            }
+        }
+    }
+
+    /// ReadAvailable returns at once up to `max_bytes` of what the sources
+    /// hold buffered, without reading any device or file or waiting; an
+    /// empty answer with status 0 when nothing is buffered. Returns (status, bytes).
+    async fn read_available(&self, max_bytes: u64, #[zbus(header)] header: Header<'_>) -> (i32, Vec<u8>) {
+        let n = max_bytes as usize;
+        let read = self.shaped(n, 0, |_| self.aggregator.read_available(n), Vec::len);
+        match scheduler::on_behalf_of(requester(&header, 0), read).await {
+            Ok(bytes) => (0, bytes),
+            Err(e) => {
+                error!("Error reading available random bytes: {:?}", e);
+                (status_code(&e), Vec::new())
+            }
+        }
+    }
+
+    /// ReadBytesEx is ReadBytes with request options and response metadata.
+    /// Options: "drbg_fill" (b) pads a short answer from a DRBG seeded by earlier
+    /// source output; "insecure_fill" (b) pads it with non-cryptographic
+    /// `GRND_INSECURE` bytes. Metadata: "secure_bytes" (t), "drbg_bytes" (t),
+    /// "insecure_bytes" (t), "fallback_bytes" (t) and "tier" (s: "secure",
+    /// "drbg", "mixed" or "fallback"); padding always follows the source
+    /// bytes, DRBG padding first. Status is 1 when jitter fallback bytes lead.
+    /// "personalization" (s or ay, up to 256 bytes) conditions the source bytes
+    /// and DRBG padding for this request only; echoed as "personalized" (b).
+    /// "priority" (y) is the request's weight under the configured scheduler.
+    /// "provenance" (a{sv}) records the sources combined (id, kind, version,
+    /// health and breaker state at serve time, entropy credit), the
+    /// conditioning applied and the entropy credited in bits.
+    /// With `[attestation]` or `[ledger]` configured the metadata also has
+    /// "request_id" (t), the id the provenance ledger records the request
+    /// under. With `[attestation]` it also has "timestamp_ms" (t), "sources"
+    /// (as), "attestation_key_id" (s) and "attestation" (ay), an HMAC-SHA256
+    /// over the bytes and that metadata.
+    async fn read_bytes_ex(
+        &self,
+        num_bytes: u64,
+        timeout_ms: u64,
+        options: HashMap<String, OwnedValue>,
+        #[zbus(header)] header: Header<'_>,
+    ) -> (i32, Vec<u8>, HashMap<String, OwnedValue>) {
+        let mut personalized = false;
+        let mut request_id = 0;
+        let res = match parse_read_options(&options) {
+            Ok(opts) => {
+                personalized = opts.personalization.as_ref().is_some_and(|p| !p.is_empty());
+                let requester = requester(&header, opts.priority);
+                request_id = requester.request_id;
+                let (n, opts) = (num_bytes as usize, &opts);
+                let read = self.shaped(n, timeout_ms, move |t| self.aggregator.read_bytes_ex(n, t, opts), |r| r.bytes.len());
+                scheduler::on_behalf_of(requester, read).await
+            }
+            Err(e) => Err(e),
+        };
+        match res {
+            Ok(response) => {
+                let insecure = response.bytes.len() - response.secure_len - response.drbg_len - response.fallback_len;
+                let tier = match (response.drbg_len, insecure) {
+                    _ if response.fallback_len > 0 => "fallback",
+                    (_, 1..) => "mixed",
+                    (1.., 0) => "drbg",
+                    (0, 0) => "secure",
                 };
-                (status, Vec::new())
+                let mut metadata = HashMap::from([
+                    ("secure_bytes".to_string(), OwnedValue::from(response.secure_len as u64)),
+                    ("drbg_bytes".to_string(), OwnedValue::from(response.drbg_len as u64)),
+                    ("personalized".to_string(), OwnedValue::from(personalized)),
+                    ("insecure_bytes".to_string(), OwnedValue::from(insecure as u64)),
+                    ("fallback_bytes".to_string(), OwnedValue::from(response.fallback_len as u64)),
+                    ("tier".to_string(), OwnedValue::from(Str::from(tier))),
+                    ("provenance".to_string(), provenance_value(&response.provenance)),
+                ]);
+                if self.attestor.is_some() || ledger::enabled() {
+                    metadata.insert("request_id".to_string(), OwnedValue::from(request_id));
+                }
+                if let Some(attestor) = &self.attestor {
+                    let source_ids: Vec<String> = response.provenance.sources.iter().map(|s| s.id.clone()).collect();
+                    let timestamp_ms = attestor.timestamp_ms();
+                    let tag = attestor.sign(&Statement {
+                        request_id,
+                        timestamp_ms,
+                        sources: &source_ids,
+                        secure_len: response.secure_len as u64,
+                        drbg_len: response.drbg_len as u64,
+                        insecure_len: insecure as u64,
+                        fallback_len: response.fallback_len as u64,
+                        bytes: &response.bytes,
+                    });
+                    metadata.extend([
+                        ("timestamp_ms".to_string(), OwnedValue::from(timestamp_ms)),
+                        ("sources".to_string(), owned(Value::from(source_ids))),
+                        ("attestation_key_id".to_string(), OwnedValue::from(Str::from(attestor.key_id().to_string()))),
+                        ("attestation".to_string(), owned(Value::from(tag))),
+                    ]);
+                }
+                let status = if response.fallback_len > 0 { STATUS_FALLBACK } else { 0 };
+                (status, response.bytes, metadata)
+            }
+            Err(e) => {
+                error!("Error reading random bytes: {:?}", e);
+                (status_code(&e), Vec::new(), HashMap::new())
+            }
+        }
+    }
+
+    /// ReadBytesMulti returns one buffer per entry of `sizes` (at most 4096)
+    /// in a single round trip, filled in order within one `timeout_ms`.
+    /// Returns (status, buffers); on a short read the last buffers are short.
+    async fn read_bytes_multi(
+        &self,
+        sizes: Vec<u64>,
+        timeout_ms: u64,
+        #[zbus(header)] header: Header<'_>,
+    ) -> (i32, Vec<Vec<u8>>) {
+        let sizes: Vec<usize> = sizes.into_iter().map(|n| n as usize).collect();
+        // Saturating, so huge sizes cannot wrap past `max_request_bytes`
+        let (total, sizes) = (sizes.iter().fold(0usize, |sum, n| sum.saturating_add(*n)), &sizes);
+        let read = self.shaped(total, timeout_ms, move |t| self.aggregator.read_bytes_multi(sizes, t), |b: &Vec<Vec<u8>>| {
+            b.iter().map(Vec::len).sum()
+        });
+        match scheduler::on_behalf_of(requester(&header, 0), read).await {
+            Ok(buffers) => (0, buffers),
+            Err(e) => {
+                error!("Error reading random bytes: {:?}", e);
+                (status_code(&e), Vec::new())
             }
         }
     }
 
-    /// GetStats returns (total_bytes_served, total_requests_served).
-    async fn get_stats(&self) -> (u64, u64) {
-        self.0.get_stats()
+    /// ReadBytesInto writes up to `len` bytes of entropy directly into the
+    /// caller's memfd (or other regular file) at `offset`, skipping the copy
+    /// through the reply. The region must already exist; it is never grown.
+    /// Returns (status, bytes_written).
+    async fn read_bytes_into(
+        &self,
+        fd: OwnedFd,
+        offset: u64,
+        len: u64,
+        timeout_ms: u64,
+        #[zbus(header)] header: Header<'_>,
+    ) -> (i32, u64) {
+        let target = std::fs::File::from(std::os::fd::OwnedFd::from(fd));
+        let read = self.shaped(len as usize, timeout_ms, |t| self.aggregator.read_bytes_into(target, offset, len as usize, t), |n| *n);
+        match scheduler::on_behalf_of(requester(&header, 0), read).await {
+            Ok(written) => (0, written as u64),
+            Err(e) => {
+                error!("Error writing random bytes into client fd: {:?}", e);
+                (status_code(&e), 0)
+            }
+        }
+    }
+
+    /// OpenSession starts a DRBG stream of the caller's own, seeded from the
+    /// sources and separate from every other stream; `label` (up to 256
+    /// bytes) is mixed into its instantiation. Needs `[client_streams]`.
+    /// Returns (status, session_id).
+    async fn open_session(&self, label: &str, #[zbus(header)] header: Header<'_>) -> (i32, u64) {
+        let res = match header.sender() {
+            Some(owner) => self.streams().and_then(|s| s.open(owner.as_str(), label)),
+            None => Err(crate::error::Error::Unexpected),
+        };
+        match res {
+            Ok(id) => (0, id),
+            Err(e) => {
+                error!("Error opening session: {:?}", e);
+                (status_code(&e), 0)
+            }
+        }
+    }
+
+    /// ReadSession returns up to `num_bytes` from one of the caller's
+    /// sessions within `timeout_ms`. Returns (status, bytes).
+    async fn read_session(&self, session_id: u64, num_bytes: u64, timeout_ms: u64, #[zbus(header)] header: Header<'_>) -> (i32, Vec<u8>) {
+        match self.read_session_with(&header, session_id, num_bytes, timeout_ms).await {
+            Ok(bytes) => (0, bytes),
+            Err(e) => {
+                error!("Error reading session bytes: {:?}", e);
+                (status_code(&e), Vec::new())
+            }
+        }
+    }
+
+    /// CloseSession ends one of the caller's sessions and zeroizes its DRBG;
+    /// sessions also end when the caller leaves the bus. Returns status.
+    async fn close_session(&self, session_id: u64, #[zbus(header)] header: Header<'_>) -> i32 {
+        let res = match header.sender() {
+            Some(owner) => self.streams().and_then(|s| s.close(session_id, owner.as_str())),
+            None => Err(crate::error::Error::Unexpected),
+        };
+        match res {
+            Ok(()) => 0,
+            Err(e) => {
+                error!("Error closing session: {:?}", e);
+                status_code(&e)
+            }
+        }
+    }
+
+    /// Reserve reads `num_bytes` of combined output now and holds them for
+    /// the caller alone on this endpoint for `ttl_seconds`, so that a key
+    /// ceremony knows its entropy is there before it starts irreversible
+    /// steps: its ReadBytes and ReadBytesExact calls here are served from
+    /// them first, and no other client's reads can get them. A further call
+    /// adds to the reservation and restarts its TTL; what is left when that
+    /// passes, or the caller leaves the bus, is zeroized. Nothing is
+    /// reserved, with status -15, if the sources fall short within 10
+    /// seconds. Needs `[reservations]`. Returns (status, bytes reserved).
+    async fn reserve(&self, num_bytes: u64, ttl_seconds: u64, #[zbus(header)] header: Header<'_>) -> (i32, u64) {
+        match self.reserve_with(&header, num_bytes, ttl_seconds).await {
+            Ok(held) => (0, held as u64),
+            Err(e) => {
+                error!("Error reserving random bytes: {:?}", e);
+                (status_code(&e), 0)
+            }
+        }
+    }
+
+    /// DeriveKey returns `length` bytes (1-8160) of HKDF-SHA256 key material
+    /// derived from fresh combined entropy with `label` as the info string.
+    /// Returns (status, key); fails with -9 if the sources fall short in time.
+    async fn derive_key(&self, label: &str, length: u64, timeout_ms: u64, #[zbus(header)] header: Header<'_>) -> (i32, Vec<u8>) {
+        let read = self.shaped(length as usize, timeout_ms, |t| self.aggregator.derive_key(label, length as usize, t), Vec::len);
+        match scheduler::on_behalf_of(requester(&header, 0), read).await {
+            Ok(key) => (0, key),
+            Err(e) => {
+                error!("Error deriving key: {:?}", e);
+                (status_code(&e), Vec::new())
+            }
+        }
+    }
+
+    /// ReadFloats returns `count` floats uniform in [0, 1), each built from
+    /// 53 random bits. Returns (status, floats).
+    async fn read_floats(&self, count: u64, #[zbus(header)] header: Header<'_>) -> (i32, Vec<f64>) {
+        let bytes = (count as usize).saturating_mul(8);
+        let read = self.shaped(bytes, SAMPLING_TIMEOUT_MS, |t| self.aggregator.read_floats(count as usize, t), |f| f.len() * 8);
+        match scheduler::on_behalf_of(requester(&header, 0), read).await {
+            Ok(floats) => (0, floats),
+            Err(e) => {
+                error!("Error reading floats: {:?}", e);
+                (status_code(&e), Vec::new())
+            }
+        }
+    }
+
+    /// ReadGaussians returns `count` samples from N(mean, stddev^2) using
+    /// the Box-Muller transform. Returns (status, samples).
+    async fn read_gaussians(&self, count: u64, mean: f64, stddev: f64, #[zbus(header)] header: Header<'_>) -> (i32, Vec<f64>) {
+        let bytes = (count as usize).saturating_mul(8);
+        let read = self.shaped(bytes, SAMPLING_TIMEOUT_MS, |t| self.aggregator.read_gaussians(count as usize, mean, stddev, t), |s| s.len() * 8);
+        match scheduler::on_behalf_of(requester(&header, 0), read).await {
+            Ok(samples) => (0, samples),
+            Err(e) => {
+                error!("Error reading gaussians: {:?}", e);
+                (status_code(&e), Vec::new())
+            }
+        }
+    }
+
+    /// RequestBytesAsync starts reading `num_bytes` within `timeout_ms` and
+    /// returns at once; the bytes follow as unicast `BytesReady` signals of
+    /// up to 1 MiB, so large requests do not hit message size limits.
+    /// Returns (status, request_id).
+    async fn request_bytes_async(
+        &self,
+        num_bytes: u64,
+        timeout_ms: u64,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> (i32, u64) {
+        let emitter = match header.sender() {
+            Some(sender) => emitter.to_owned().set_destination(BusName::Unique(sender.to_owned())),
+            None => return (status_code(&crate::error::Error::Unexpected), 0),
+        };
+        if num_bytes == 0 {
+            return (status_code(&crate::error::Error::InvalidOption("num_bytes".to_string())), 0);
+        }
+        let requester = requester(&header, 0);
+        let (request_id, owner) = (requester.request_id, requester.client.clone());
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        let this = self.clone();
+        let started = self.deliveries.start(&owner, request_id, num_bytes, move |bytes| {
+            scheduler::on_behalf_of(requester, this.deliver_chunks(emitter, request_id, bytes, deadline))
+        });
+        match started {
+            Ok(()) => (0, request_id),
+            Err(e) => (status_code(&e), 0),
+        }
+    }
+
+    /// Subscribe starts pushing `bytes_per_interval` bytes every `interval_ms`
+    /// to the caller as unicast `Entropy` signals until `Unsubscribe` or until
+    /// the caller leaves the bus. Returns (status, subscription_id).
+    async fn subscribe(
+        &self,
+        bytes_per_interval: u64,
+        interval_ms: u64,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> (i32, u64) {
+        let emitter = match header.sender() {
+            Some(sender) => emitter.to_owned().set_destination(BusName::Unique(sender.to_owned())),
+            None => return (status_code(&crate::error::Error::Unexpected), 0),
+        };
+        match self.subscribe_with(&header, bytes_per_interval, interval_ms, Sink::Signal(emitter, self.interface.clone())) {
+            Ok(id) => (0, id),
+            Err(e) => {
+                error!("Error subscribing: {:?}", e);
+                (status_code(&e), 0)
+            }
+        }
+    }
+
+    /// SubscribePipe is Subscribe with the chunks written to the returned
+    /// socket instead of signals. Returns (status, subscription_id, fd).
+    async fn subscribe_pipe(
+        &self,
+        bytes_per_interval: u64,
+        interval_ms: u64,
+        #[zbus(header)] header: Header<'_>,
+    ) -> zbus::fdo::Result<(i32, u64, OwnedFd)> {
+        let (ours, theirs) = std::os::unix::net::UnixStream::pair()
+            .map_err(|e| zbus::fdo::Error::IOError(e.to_string()))?;
+        ours.set_nonblocking(true).map_err(|e| zbus::fdo::Error::IOError(e.to_string()))?;
+        let ours = tokio::net::UnixStream::from_std(ours).map_err(|e| zbus::fdo::Error::IOError(e.to_string()))?;
+        let fd = OwnedFd::from(std::os::fd::OwnedFd::from(theirs));
+        match self.subscribe_with(&header, bytes_per_interval, interval_ms, Sink::Pipe(ours)) {
+            Ok(id) => Ok((0, id, fd)),
+            Err(e) => {
+                error!("Error subscribing: {:?}", e);
+                Ok((status_code(&e), 0, fd))
+            }
+        }
+    }
+
+    /// OpenStream returns a socket the service keeps writing random bytes
+    /// into, at most `rate_limit_bytes_per_sec` a second (0 for the
+    /// `[subscriptions]` limit), as fast as the caller reads them.
+    /// Returns (status, stream_id, fd); Unsubscribe or closing the socket ends it.
+    async fn open_stream(&self, rate_limit_bytes_per_sec: u64, #[zbus(header)] header: Header<'_>) -> zbus::fdo::Result<(i32, u64, OwnedFd)> {
+        let (ours, theirs) = std::os::unix::net::UnixStream::pair()
+            .map_err(|e| zbus::fdo::Error::IOError(e.to_string()))?;
+        ours.set_nonblocking(true).map_err(|e| zbus::fdo::Error::IOError(e.to_string()))?;
+        let ours = tokio::net::UnixStream::from_std(ours).map_err(|e| zbus::fdo::Error::IOError(e.to_string()))?;
+        let fd = OwnedFd::from(std::os::fd::OwnedFd::from(theirs));
+        let res = header.sender().ok_or(crate::error::Error::Unexpected).and_then(|owner| {
+            self.subscriptions.open_stream(
+                self.aggregator.clone(),
+                owner.as_str(),
+                rate_limit_bytes_per_sec,
+                Sink::Pipe(ours),
+                self.shaper.clone(),
+            )
+        });
+        match res {
+            Ok(id) => Ok((0, id, fd)),
+            Err(e) => {
+                error!("Error opening stream: {:?}", e);
+                Ok((status_code(&e), 0, fd))
+            }
+        }
+    }
+
+    /// Unsubscribe ends one of the caller's subscriptions.
+    /// Returns (status, bytes_delivered).
+    async fn unsubscribe(&self, subscription_id: u64, #[zbus(header)] header: Header<'_>) -> (i32, u64) {
+        let Some(owner) = header.sender() else {
+            return (status_code(&crate::error::Error::Unexpected), 0);
+        };
+        match self.subscriptions.stop(subscription_id, owner.as_str()) {
+            Ok(delivered) => (0, delivered),
+            Err(e) => {
+                error!("Error unsubscribing: {:?}", e);
+                (status_code(&e), 0)
+            }
+        }
+    }
+
+    /// RunBenchmark re-measures each source's sustainable rate and returns
+    /// it in bytes/s per source id (sources that could not be read are omitted).
+    async fn run_benchmark(&self) -> HashMap<String, f64> {
+        self.aggregator.run_benchmark().await
+    }
+
+    /// GetEntropyEstimate returns the assessed entropy in bits the sources
+    /// hold buffered for this endpoint, as its combine mode can pass it on.
+    async fn get_entropy_estimate(&self) -> u64 {
+        self.scoped(self.aggregator.entropy_estimate()).await
+    }
+
+    /// GetCapabilities returns what this endpoint supports, so clients can
+    /// adapt without trial calls: "service_version" (s), "interface" (s),
+    /// "combine" (s, this endpoint's mode or the pool serving it),
+    /// "combine_modes" (as), "source_kinds" (as, those built in),
+    /// "source_count" (u), "min_sources" (u, 0 for none),
+    /// "max_request_bytes" (t), "max_timeout_ms" (t, 0 as timeouts are not
+    /// capped), "max_capture_bytes" (t), "max_samples" (t) and "features"
+    /// (as). Keys may be added; clients ignore those they do not know.
+    async fn get_capabilities(&self) -> HashMap<String, OwnedValue> {
+        let combine_modes: Vec<&str> = CombineMode::ALL.iter().map(|m| m.as_str()).collect();
+        let capabilities = [
+            ("service_version", Value::from(env!("CARGO_PKG_VERSION"))),
+            ("interface", Value::from(self.interface.as_str())),
+            ("combine", Value::from(self.combine_name())),
+            ("combine_modes", Value::from(combine_modes)),
+            ("source_kinds", Value::from(Aggregator::source_kinds())),
+            ("source_count", Value::from(self.aggregator.source_count() as u32)),
+            ("min_sources", Value::from(self.min_sources.unwrap_or(0) as u32)),
+            ("max_request_bytes", Value::from(self.aggregator.max_request_bytes() as u64)),
+            ("max_timeout_ms", Value::from(0u64)),
+            ("max_capture_bytes", Value::from(aggregator::MAX_CAPTURE_BYTES as u64)),
+            ("max_samples", Value::from(sampling::MAX_SAMPLES as u64)),
+            ("features", Value::from(self.features())),
+        ];
+        capabilities.into_iter().map(|(k, v)| (k.to_string(), owned(v))).collect()
+    }
+
+    /// SetSourceBufferSize resizes a live source's buffer to `bytes` (at most
+    /// 1 GiB), keeping as much buffered entropy as fits. Restricted to the uids
+    /// in `[access] tune_uids`. Returns (status, bytes_discarded).
+    async fn set_source_buffer_size(
+        &self,
+        source_id: &str,
+        bytes: u64,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &zbus::Connection,
+    ) -> (i32, u64) {
+        let res = match self.authorize_tuning(connection, &header, source_id).await {
+            Ok(_) => self.aggregator.set_buffer_size(source_id, bytes as usize).await,
+            Err(e) => Err(e),
+        };
+        match res {
+            Ok(discarded) => (0, discarded as u64),
+            Err(e) => {
+                error!("Error resizing source buffer: {:?}", e);
+                (status_code(&e), 0)
+            }
+        }
+    }
+
+    /// SetSourceTuning changes how a live buffered source replenishes.
+    /// Options: "low_watermark_percent" (y, 1-100) where refilling starts,
+    /// "high_watermark_percent" (y, 1-100, LRNG only) where it stops and
+    /// "replenish_chunk" (t, bytes per step, at most 16 MiB). Restricted like
+    /// SetSourceBufferSize. Returns status.
+    async fn set_source_tuning(
+        &self,
+        source_id: &str,
+        options: HashMap<String, OwnedValue>,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &zbus::Connection,
+    ) -> i32 {
+        match self.tune_with(connection, &header, source_id, &options).await {
+            Ok(()) => 0,
+            Err(e) => {
+                error!("Error tuning source: {:?}", e);
+                status_code(&e)
+            }
+        }
+    }
+
+    /// CaptureRawSample diverts up to `bytes` (at most 16 MiB) raw bytes of
+    /// one source, before combining, for offline analysis. Restricted to the
+    /// uids in `[access] capture_uids`; captured bytes are never served to
+    /// anyone else. Returns (status, sample).
+    async fn capture_raw_sample(
+        &self,
+        source_id: &str,
+        bytes: u64,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &zbus::Connection,
+    ) -> (i32, Vec<u8>) {
+        match self.capture_with(connection, &header, source_id, bytes).await {
+            Ok(sample) => (0, sample),
+            Err(e) => {
+                error!("Error capturing raw sample: {:?}", e);
+                (status_code(&e), Vec::new())
+            }
+        }
+    }
+
+    /// Promote makes `source_id` the serving half of its warm standby pair
+    /// (`standby_for`), for manual failover or fail-back. Restricted like
+    /// SetSourceBufferSize. Returns status.
+    async fn promote(
+        &self,
+        source_id: &str,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &zbus::Connection,
+    ) -> i32 {
+        let res = match self.authorize_tuning(connection, &header, source_id).await {
+            Ok(uid) => self.aggregator.promote(source_id).map(|()| uid),
+            Err(e) => Err(e),
+        };
+        match res {
+            Ok(uid) => {
+                log::info!("Source {} promoted by uid {}", source_id, uid);
+                0
+            }
+            Err(e) => {
+                error!("Error promoting source: {:?}", e);
+                status_code(&e)
+            }
+        }
+    }
+
+    /// Version is the service's version.
+    #[zbus(property(emits_changed_signal = "const"))]
+    async fn version(&self) -> String {
+        env!("CARGO_PKG_VERSION").to_string()
+    }
+
+    /// SourceCount is the number of configured sources.
+    #[zbus(property(emits_changed_signal = "const"))]
+    async fn source_count(&self) -> u32 {
+        self.aggregator.source_count() as u32
+    }
+
+    /// BytesServed is the total bytes served, as `GetStats`; changes are
+    /// announced at most once a second, as are the properties below.
+    #[zbus(property)]
+    async fn bytes_served(&self) -> u64 {
+        self.aggregator.get_stats().0
+    }
+
+    /// RequestsServed is the total requests served, as `GetStats`.
+    #[zbus(property)]
+    async fn requests_served(&self) -> u64 {
+        self.aggregator.get_stats().1
+    }
+
+    /// BufferFillPercent is how full the buffered sources are together,
+    /// in whole percent.
+    #[zbus(property)]
+    async fn buffer_fill_percent(&self) -> u8 {
+        self.aggregator.buffer_fill_percent().await
+    }
+
+    /// BreakerStateChanged is emitted when a source's circuit breaker moves
+    /// between "closed", "open" and "half_open".
+    #[zbus(signal)]
+    async fn breaker_state_changed(emitter: &SignalEmitter<'_>, source_id: &str, state: &str) -> zbus::Result<()>;
+
+    /// SourcePromoted is emitted when a warm standby takes over from its
+    /// primary or the other way round; `reason` is "quarantined",
+    /// "unavailable" or "manual".
+    #[zbus(signal)]
+    async fn source_promoted(emitter: &SignalEmitter<'_>, source_id: &str, replaced: &str, reason: &str) -> zbus::Result<()>;
+
+    /// Entropy carries one chunk of a `Subscribe` subscription; it is
+    /// unicast to the subscriber only.
+    #[zbus(signal)]
+    async fn entropy(emitter: &SignalEmitter<'_>, subscription_id: u64, bytes: &[u8]) -> zbus::Result<()>;
+
+    /// BytesReady carries one chunk of a `RequestBytesAsync` request; it is
+    /// unicast to the caller only. `is_final` marks the last chunk, which
+    /// comes early (and may be empty) if the request failed or timed out.
+    #[zbus(signal)]
+    async fn bytes_ready(emitter: &SignalEmitter<'_>, request_id: u64, chunk: &[u8], is_final: bool) -> zbus::Result<()>;
+
+    /// Alert is emitted for operator paging: `kind` is one of
+    /// "source_quarantined", "buffer_empty", "self_test_failed",
+    /// "watchdog_failed" (with an empty `source_id`).
+    #[zbus(signal)]
+    async fn alert(emitter: &SignalEmitter<'_>, kind: &str, source_id: &str, details: HashMap<String, String>) -> zbus::Result<()>;
+
+    /// WatchdogStateChanged is emitted when `[watchdog]` canary reads start
+    /// failing (`healthy` false, the service then answers `NotReady`) and
+    /// when they succeed again (`reason` empty).
+    #[zbus(signal)]
+    async fn watchdog_state_changed(emitter: &SignalEmitter<'_>, healthy: bool, reason: &str) -> zbus::Result<()>;
+
+    /// EntropyLow is emitted when a buffered source's fill drops below
+    /// `entropy_low_percent`, once until it is back above.
+    #[zbus(signal)]
+    async fn entropy_low(emitter: &SignalEmitter<'_>, source_id: &str, fill_percent: f64) -> zbus::Result<()>;
+
+    /// SourceFailed is emitted when a source's reads start returning errors,
+    /// once until one succeeds again.
+    #[zbus(signal)]
+    async fn source_failed(emitter: &SignalEmitter<'_>, source_id: &str, error: &str) -> zbus::Result<()>;
+}
+
+/// Where signals go: each endpoint's object path on the current connection,
+/// with its interface.
+type Emitters = Arc<Vec<(SignalEmitter<'static>, InterfaceName<'static>)>>;
+
+/// Forwards internal service events to D-Bus signals on every endpoint,
+/// each on its own interface. Events while off the bus are not emitted.
+async fn forward_events(mut events: broadcast::Receiver<ServiceEvent>, endpoints: watch::Receiver<Emitters>) {
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                log::warn!("Dropped {} service events", n);
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        let endpoints = endpoints.borrow().clone();
+        for (emitter, interface) in endpoints.iter() {
+            let res = match &event {
+                ServiceEvent::BreakerStateChanged { source_id, state } => {
+                    emitter.emit(interface, "BreakerStateChanged", &(source_id, state)).await
+                }
+                ServiceEvent::SourcePromoted { source_id, replaced, reason } => {
+                    emitter.emit(interface, "SourcePromoted", &(source_id, replaced, reason)).await
+                }
+                ServiceEvent::Alert { kind, source_id, details } => {
+                    emitter.emit(interface, "Alert", &(kind, source_id, details)).await
+                }
+                ServiceEvent::WatchdogChanged { healthy, reason } => {
+                    emitter.emit(interface, "WatchdogStateChanged", &(healthy, reason)).await
+                }
+                ServiceEvent::EntropyLow { source_id, fill_percent } => {
+                    emitter.emit(interface, "EntropyLow", &(source_id, fill_percent)).await
+                }
+                ServiceEvent::SourceFailed { source_id, error } => {
+                    emitter.emit(interface, "SourceFailed", &(source_id, error)).await
+                }
+                _ => break,
+            };
+            if let Err(e) = res {
+                error!("Failed to emit signal on {}: {}", interface, e);
+            }
+        }
+    }
+}
+
+/// Emits `PropertiesChanged` for the counter properties that changed since
+/// the last tick, on every endpoint but the monitor's.
+async fn publish_properties(aggregator: Arc<Aggregator>, endpoints: watch::Receiver<Emitters>, heartbeat: supervisor::Heartbeat) {
+    let mut last = None;
+    let mut ticker = tokio::time::interval(PROPERTIES_INTERVAL);
+    loop {
+        ticker.tick().await;
+        heartbeat.beat();
+        let (bytes, requests) = aggregator.get_stats();
+        let current = (bytes, requests, aggregator.buffer_fill_percent().await);
+        let before = last.replace(current);
+        let mut changed = HashMap::new();
+        if before.map(|b| b.0) != Some(current.0) {
+            changed.insert("BytesServed", Value::from(current.0));
+        }
+        if before.map(|b| b.1) != Some(current.1) {
+            changed.insert("RequestsServed", Value::from(current.1));
+        }
+        if before.map(|b| b.2) != Some(current.2) {
+            changed.insert("BufferFillPercent", Value::from(current.2));
+        }
+        if changed.is_empty() {
+            continue;
+        }
+        let endpoints = endpoints.borrow().clone();
+        for (emitter, interface) in endpoints.iter().filter(|(_, interface)| interface.as_str() != MONITOR_INTERFACE) {
+            let res = zbus::fdo::Properties::properties_changed(emitter, interface.as_ref(), changed.clone(), Cow::Borrowed(&[])).await;
+            if let Err(e) = res {
+                error!("Failed to emit PropertiesChanged on {}: {}", interface, e);
+            }
+        }
+    }
+}
+
+/// Ends the subscriptions and streams of clients that disconnect from the bus.
+async fn drop_departed_clients(
+    connection: zbus::Connection,
+    subscriptions: Arc<Subscriptions>,
+    streams: Option<Arc<ClientStreams>>,
+    quotas: Option<Arc<ClientQuotas>>,
+    reservations: Option<Arc<Reservations>>,
+    deliveries: Arc<Deliveries>,
+) {
+    let changes = match zbus::fdo::DBusProxy::new(&connection).await {
+        Ok(proxy) => proxy.receive_name_owner_changed().await,
+        Err(e) => Err(e),
+    };
+    let mut changes = match changes {
+        Ok(changes) => changes,
+        Err(e) => {
+            error!("Cannot watch for departing clients: {}", e);
+            return;
+        }
+    };
+    while let Some(change) = changes.next().await {
+        let Ok(args) = change.args() else { continue };
+        if args.new_owner().is_none() {
+            subscriptions.remove_owner(args.name().as_str());
+            if let Some(streams) = &streams {
+                streams.remove_owner(args.name().as_str());
+            }
+            if let Some(quotas) = &quotas {
+                quotas.remove_owner(args.name().as_str());
+            }
+            if let Some(reservations) = &reservations {
+                reservations.remove_owner(args.name().as_str());
+            }
+            deliveries.remove_owner(args.name().as_str());
+        }
+    }
+}
+
+/// Every object the service exports, kept across reconnects so group
+/// shapers carry on where they were.
+struct Objects {
+    /// `[service] bus`, or the system bus with `--system`.
+    bus: BusKind,
+    /// `[service] name`, the well-known name taken on `bus`.
+    name: String,
+    /// `[service] object_path` of `rng` and `monitor`.
+    path: String,
+    rng: SourceXorAggregator,
+    monitor: Monitor,
+    /// Object path and endpoint of each `[[groups]]` entry.
+    groups: Vec<(String, SourceXorAggregator)>,
+}
+
+impl Objects {
+    /// Connects to the bus with `name` taken and every object exported, and
+    /// makes the emitters for their signals.
+    async fn connect(&self) -> zbus::Result<(zbus::Connection, Emitters)> {
+        let builder = match self.bus {
+            BusKind::Session => connection::Builder::session()?,
+            BusKind::System => connection::Builder::system()?,
+        };
+        let mut builder = builder
+            .name(self.name.as_str())?
+            .serve_at(self.path.as_str(), Restricted(self.rng.clone()))?
+            .serve_at(self.path.as_str(), Restricted(self.monitor.clone()))?;
+        let monitor_interface = InterfaceName::from_static_str_unchecked(MONITOR_INTERFACE);
+        let mut endpoints = vec![(self.path.clone(), self.rng.interface.clone()), (self.path.clone(), monitor_interface)];
+        for (slot, (path, service)) in self.groups.iter().enumerate() {
+            endpoints.push((path.clone(), service.interface.clone()));
+            builder = groups::serve_at(builder, slot, path.clone(), service.clone())?;
+        }
+        let connection = builder.build().await?;
+        let emitters = endpoints
+            .into_iter()
+            .map(|(path, interface)| Ok((SignalEmitter::new(&connection, path)?, interface)))
+            .collect::<zbus::Result<Vec<_>>>()?;
+        Ok((connection, Arc::new(emitters)))
+    }
+}
+
+/// Keeps the service on the bus: when `connection` closes or loses the name,
+/// ends what belonged to clients of the old bus and reconnects with backoff.
+async fn stay_on_bus(mut connection: zbus::Connection, objects: Objects, emitters: watch::Sender<Emitters>, shared: Shared) {
+    loop {
+        let (owners, subscriptions, streams) = (connection.clone(), shared.subscriptions.clone(), shared.streams.clone());
+        let (quotas, reservations, deliveries) = (shared.quotas.clone(), shared.reservations.clone(), shared.deliveries.clone());
+        supervisor::spawn("departed-clients", None, move |_| {
+            drop_departed_clients(owners.clone(), subscriptions.clone(), streams.clone(), quotas.clone(), reservations.clone(), deliveries.clone())
+        });
+        let reason = bus::lost(&connection, &objects.name).await;
+        let since = Instant::now();
+        error!("Off the {} ({}), reconnecting", objects.bus, reason);
+        emitters.send_replace(Arc::new(Vec::new()));
+        if let Err(e) = connection.close().await {
+            log::debug!("Closing the old connection: {}", e);
+        }
+        // A new bus hands out the same unique names again, so nothing a
+        // client of the old one set up may carry over
+        shared.subscriptions.stop_all();
+        shared.deliveries.stop_all();
+        if let Some(streams) = &shared.streams {
+            streams.clear();
+        }
+        if let Some(quotas) = &shared.quotas {
+            quotas.clear();
+        }
+        if let Some(reservations) = &shared.reservations {
+            reservations.clear();
+        }
+        let (new, new_emitters) = bus::reconnect(|| objects.connect()).await;
+        connection = new;
+        emitters.send_replace(new_emitters);
+        let outages = bus::record_outage(since.elapsed());
+        log::warn!("Back on the {} as '{}' after {:?} ({} outages so far)", objects.bus, objects.name, since.elapsed(), outages);
     }
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
+fn main() -> Result<(), Box<dyn Error>> {
     // Initialize logging
     env_logger::init();
+    shutdown::install_panic_hook();
 
     let config_path = get_config_path();
-    let cfg = load_config(&config_path)
+    let mut args = std::env::args().skip(1).peekable();
+    if args.next_if_eq("migrate").is_some() {
+        std::process::exit(migrate::run(args, config_path));
+    }
+    if args.next_if_eq("snapshot").is_some() {
+        std::process::exit(snapshot::run(args));
+    }
+    let mut cfg = load_config(&config_path)
         .expect("Failed to load config");
-    let aggregator = Aggregator::from_config(cfg)
+    let (system, args): (Vec<String>, Vec<String>) = args.partition(|arg| arg == "--system");
+    if !system.is_empty() {
+        cfg.bus = BusKind::System;
+    }
+    let runtime_cfg = match runtime::with_args(cfg.runtime.as_ref(), args) {
+        Ok(cfg) => cfg,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            std::process::exit(2);
+        }
+    };
+    runtime::build(&runtime_cfg)?.block_on(serve(cfg))
+}
+
+async fn serve(cfg: FlattenedConfig) -> Result<(), Box<dyn Error>> {
+    // Wipe entropy from memory however the process ends
+    let _teardown = shutdown::Guard;
+    shutdown::register(shutdown::Stage::StopTasks, "supervised tasks", supervisor::stop_all);
+    shutdown::register(shutdown::Stage::Flush, "log output", || log::logger().flush());
+
+    let subscriptions = Arc::new(Subscriptions::new(cfg.subscriptions.as_ref()));
+    let attestor = Attestor::from_config(cfg.attestation.as_ref())
+        .map_err(|e| format!("Cannot load attestation key: {}", e))?;
+    ledger::open(cfg.ledger.as_ref()).map_err(|e| format!("Cannot open provenance ledger: {}", e))?;
+    let stop = subscriptions.clone();
+    shutdown::register(shutdown::Stage::StopTasks, "subscriptions", move || stop.stop_all());
+    let streams = cfg.client_streams.as_ref().map(|c| Arc::new(ClientStreams::new(c)));
+    if let Some(streams) = &streams {
+        // Dropping a stream zeroizes its DRBG
+        let wipe = streams.clone();
+        shutdown::register(shutdown::Stage::Zeroize, "client DRBG streams", move || wipe.clear());
+    }
+    let quotas = cfg.quotas.as_ref().map(|c| Arc::new(ClientQuotas::new(c)));
+    let reservations = cfg.reservations.as_ref().map(|c| Arc::new(Reservations::new(c)));
+    if let Some(reservations) = &reservations {
+        let wipe = reservations.clone();
+        shutdown::register(shutdown::Stage::Zeroize, "reservations", move || wipe.clear());
+        let expiring = reservations.clone();
+        supervisor::spawn("reservation-expiry", None, move |_| {
+            let expiring = expiring.clone();
+            async move {
+                let mut every = tokio::time::interval(Duration::from_secs(1));
+                loop {
+                    every.tick().await;
+                    expiring.expire();
+                }
+            }
+        });
+    }
+    let latency_budget = cfg.latency_budget.as_ref().map(LatencyBudget::new);
+    let max_async_requests = cfg.max_async_requests_per_client;
+    let access = cfg.access.clone();
+    let (bus, name, path) = (cfg.bus, cfg.bus_name.clone(), cfg.object_path.clone());
+    let group_cfgs = cfg.groups.clone();
+    let watchdog_cfg = cfg.watchdog.clone();
+    let relay_cfg = cfg.relay.clone();
+    let trending_cfg = cfg.trending.clone();
+    let aggregator = Arc::new(Aggregator::from_config(cfg)
         .await
-        .expect("Failed to initialize aggregator from config");
-    let rng_service = SourceXorAggregator::new(aggregator);
-    let _connection = connection::Builder::session()?
-        .name("lv.lumii.trng")?
-        .serve_at("/lv/lumii/trng/SourceXorAggregator", rng_service)?
-        .build()
-        .await?;
-
-    info!("D-Bus service 'lv.lumii.trng' is running.");
-
-    // Keep the application running indefinitely
-    pending::<()>().await;
+        .expect("Failed to initialize aggregator from config"));
+    let events = aggregator.event_sender();
+    if let Some(watchdog_cfg) = watchdog_cfg {
+        let (watched, sender) = (aggregator.clone(), events.clone());
+        supervisor::spawn("watchdog", Some(supervisor::STALL_TIMEOUT), move |heartbeat| {
+            watchdog::run(watched.clone(), watchdog_cfg.clone(), sender.clone(), heartbeat)
+        });
+    }
+    if let Some(relay_cfg) = relay_cfg {
+        let relay = Arc::new(Relay::bind(&relay_cfg).await.map_err(|e| format!("Cannot start relay: {}", e))?);
+        info!("Relay listening on {}", relay.local_addr().map_or(relay_cfg.listen.clone(), |addr| addr.to_string()));
+        let relayed = aggregator.clone();
+        supervisor::spawn("relay", None, move |_| relay.clone().run(relayed.clone()));
+    }
+    if let Some(trending_cfg) = trending_cfg {
+        let sampled = aggregator.clone();
+        supervisor::spawn("trending", None, move |_| trending::run(sampled.clone(), trending_cfg.clone()));
+    }
+    if aggregator.has_pool() {
+        let pooled = aggregator.clone();
+        supervisor::spawn("pool-absorb", Some(supervisor::STALL_TIMEOUT), move |heartbeat| {
+            let pooled = pooled.clone();
+            async move { pooled.feed_pool(heartbeat).await }
+        });
+    }
+    match aggregator.readiness_mode() {
+        Some(ReadinessMode::DelayName) => {
+            info!("Waiting for sources to become ready before taking the bus name");
+            tokio::select! {
+                _ = aggregator.wait_until_ready() => {}
+                signal = shutdown::wait_for_signal() => {
+                    info!("Received {} before becoming ready, shutting down", signal?);
+                    return Ok(());
+                }
+            }
+        }
+        Some(ReadinessMode::NotReady) => {
+            let priming = aggregator.clone();
+            supervisor::spawn("readiness", None, move |_| {
+                let priming = priming.clone();
+                async move { priming.wait_until_ready().await }
+            });
+        }
+        None => {}
+    }
+    let deliveries = Arc::new(Deliveries::new(aggregator.max_request_bytes(), max_async_requests, quotas.clone()));
+    let shared = Shared { aggregator, subscriptions, attestor: attestor.map(Arc::new), streams, quotas, latency_budget, reservations, deliveries };
+    let default_interface = InterfaceName::from_static_str_unchecked(DEFAULT_INTERFACE);
+    let rng = SourceXorAggregator::new(shared.clone(), AccessPolicy::new(access.as_ref()), default_interface);
+    info!("{}", rng.banner());
+    let monitor = Monitor::new(shared.aggregator.clone(), AccessPolicy::monitor(access.as_ref()));
+    let mut groups = Vec::with_capacity(group_cfgs.len());
+    for group in &group_cfgs {
+        let service = SourceXorAggregator::for_group(shared.clone(), group, access.as_ref());
+        let path = group.object_path.clone().expect("filled in with the config");
+        info!("Serving group {} as {} at {}", group.name, group.interface, path);
+        groups.push((path, service));
+    }
+    let objects = Objects { bus, name: name.clone(), path, rng, monitor, groups };
+    let (connection, emitters) = objects.connect().await?;
+    let (emitters, endpoints) = watch::channel(emitters);
+    let (published, watched) = (shared.aggregator.clone(), endpoints.clone());
+    supervisor::spawn("dbus-properties", Some(supervisor::STALL_TIMEOUT), move |heartbeat| {
+        publish_properties(published.clone(), watched.clone(), heartbeat)
+    });
+    supervisor::spawn("dbus-events", None, move |_| forward_events(events.subscribe(), endpoints.clone()));
+
+    info!("D-Bus service '{}' is running on the {}.", name, bus);
+
+    // Serve until asked to stop; teardown runs when `_teardown` is dropped
+    let signal = tokio::select! {
+        signal = shutdown::wait_for_signal() => signal?,
+        () = stay_on_bus(connection, objects, emitters, shared) => unreachable!("the service stays on the bus"),
+    };
+    info!("Received {}, shutting down", signal);
 
     Ok(())
 }
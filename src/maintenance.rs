@@ -0,0 +1,222 @@
+use crate::config::{MaintenanceBuffer, MaintenanceConfig};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Longest window one `maintenance` entry may describe.
+const MAX_WINDOW_MINUTES: u32 = 24 * 60;
+
+/// Set of allowed values of one cron field, as a bit per value.
+#[derive(Debug, Clone, Copy)]
+struct Field {
+    bits: u64,
+    /// The field was `*`; matters for the day-of-month/day-of-week rule.
+    any: bool,
+}
+
+impl Field {
+    fn parse(spec: &str, min: u32, max: u32) -> Result<Self, String> {
+        let mut bits = 0u64;
+        for part in spec.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => (range, step.parse::<u32>().ok().filter(|s| *s > 0).ok_or_else(|| format!("bad step in '{}'", part))?),
+                None => (part, 1),
+            };
+            let value = |v: &str| v.parse::<u32>().map_err(|_| format!("bad value in '{}'", part));
+            let (lo, hi) = match range {
+                "*" => (min, max),
+                _ => match range.split_once('-') {
+                    Some((lo, hi)) => (value(lo)?, value(hi)?),
+                    // `5/15` means every 15 from 5 on
+                    None if step > 1 => (value(range)?, max),
+                    None => (value(range)?, value(range)?),
+                },
+            };
+            if lo < min || hi > max || lo > hi {
+                return Err(format!("'{}' is outside {}-{}", part, min, max));
+            }
+            for v in (lo..=hi).step_by(step as usize) {
+                bits |= 1 << v;
+            }
+        }
+        Ok(Self { bits, any: spec == "*" })
+    }
+
+    fn has(&self, value: u32) -> bool {
+        self.bits & (1 << value) != 0
+    }
+}
+
+/// Broken-down local time, as far as cron looks at it.
+#[derive(Debug, Clone, Copy)]
+struct Moment {
+    minute: u32,
+    hour: u32,
+    day: u32,
+    month: u32,
+    /// 0 is Sunday.
+    weekday: u32,
+}
+
+impl Moment {
+    fn local(unix_seconds: i64) -> Option<Self> {
+        let t = unix_seconds as libc::time_t;
+        // SAFETY: tm is plain old data that localtime_r fills in
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        if unsafe { libc::localtime_r(&t, &mut tm) }.is_null() {
+            return None;
+        }
+        Some(Self {
+            minute: tm.tm_min as u32,
+            hour: tm.tm_hour as u32,
+            day: tm.tm_mday as u32,
+            month: tm.tm_mon as u32 + 1,
+            weekday: tm.tm_wday as u32,
+        })
+    }
+}
+
+/// A five-field cron expression: minute, hour, day of month, month, day of week.
+#[derive(Debug, Clone)]
+struct Cron {
+    minute: Field,
+    hour: Field,
+    day: Field,
+    month: Field,
+    weekday: Field,
+}
+
+impl Cron {
+    fn parse(expr: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("'{}' needs 5 fields (minute hour day month weekday)", expr));
+        };
+        let mut weekday = Field::parse(weekday, 0, 7)?;
+        // 7 is Sunday too
+        if weekday.has(7) {
+            weekday.bits |= 1;
+        }
+        Ok(Self {
+            minute: Field::parse(minute, 0, 59)?,
+            hour: Field::parse(hour, 0, 23)?,
+            day: Field::parse(day, 1, 31)?,
+            month: Field::parse(month, 1, 12)?,
+            weekday,
+        })
+    }
+
+    fn matches(&self, t: &Moment) -> bool {
+        // As in cron: with both day fields restricted, either may match
+        let day = match (self.day.any, self.weekday.any) {
+            (true, true) => true,
+            (false, true) => self.day.has(t.day),
+            (true, false) => self.weekday.has(t.weekday),
+            (false, false) => self.day.has(t.day) || self.weekday.has(t.weekday),
+        };
+        day && self.minute.has(t.minute) && self.hour.has(t.hour) && self.month.has(t.month)
+    }
+}
+
+/// One recurring maintenance window of a source.
+#[derive(Debug, Clone)]
+pub struct Window {
+    pub cron: String,
+    schedule: Cron,
+    pub minutes: u32,
+    /// Zeroize the buffer when the window starts instead of keeping it.
+    pub drain: bool,
+}
+
+impl Window {
+    /// The latest start time of this window within its length before
+    /// `unix_seconds`, in minutes since the epoch.
+    fn opened(&self, unix_seconds: i64) -> Option<i64> {
+        let now = unix_seconds.div_euclid(60);
+        (0..self.minutes as i64)
+            .map(|ago| now - ago)
+            .find(|&minute| Moment::local(minute * 60).is_some_and(|start| self.schedule.matches(&start)))
+    }
+}
+
+/// The maintenance windows configured for one source.
+#[derive(Debug, Clone, Default)]
+pub struct Schedule {
+    windows: Vec<Window>,
+}
+
+impl Schedule {
+    pub fn from_config(cfg: &[MaintenanceConfig]) -> Result<Self, String> {
+        let mut windows = Vec::with_capacity(cfg.len());
+        for w in cfg {
+            if w.duration_minutes == 0 || w.duration_minutes > MAX_WINDOW_MINUTES {
+                return Err(format!("duration_minutes must be 1-{}", MAX_WINDOW_MINUTES));
+            }
+            windows.push(Window {
+                cron: w.cron.clone(),
+                schedule: Cron::parse(&w.cron)?,
+                minutes: w.duration_minutes,
+                drain: w.buffer.unwrap_or_default() == MaintenanceBuffer::Drain,
+            });
+        }
+        Ok(Self { windows })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    /// The first window that is open at `now`, if any, with the minute (since
+    /// the epoch) it opened, which tells its occurrences apart.
+    pub fn active(&self, now: SystemTime) -> Option<(&Window, i64)> {
+        let unix_seconds = now.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+        self.windows.iter().find_map(|w| Some((w, w.opened(unix_seconds)?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn at(minute: u32, hour: u32, day: u32, month: u32, weekday: u32) -> Moment {
+        Moment { minute, hour, day, month, weekday }
+    }
+
+    #[test]
+    fn test_cron_matching() {
+        let sunday_3am = Cron::parse("0 3 * * 7").unwrap();
+        assert!(sunday_3am.matches(&at(0, 3, 12, 5, 0)));
+        assert!(!sunday_3am.matches(&at(0, 3, 13, 5, 1)));
+
+        let quarterly = Cron::parse("*/15 1-2 1,15 */3 *").unwrap();
+        assert!(quarterly.matches(&at(45, 2, 15, 4, 3)));
+        assert!(!quarterly.matches(&at(50, 2, 15, 4, 3)));
+        assert!(!quarterly.matches(&at(45, 2, 15, 5, 3)));
+
+        // Restricted day of month and day of week: either one matches
+        let either = Cron::parse("0 0 1 * 1").unwrap();
+        assert!(either.matches(&at(0, 0, 1, 6, 4)));
+        assert!(either.matches(&at(0, 0, 9, 6, 1)));
+        assert!(!either.matches(&at(0, 0, 9, 6, 2)));
+
+        for bad in ["0 3 * *", "60 * * * *", "* * 0 * *", "*/0 * * * *", "5-1 * * * *", "a * * * *"] {
+            assert!(Cron::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_window_covers_duration() {
+        let started = SystemTime::now() - Duration::from_secs(10 * 60);
+        let unix = started.duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+        let start = Moment::local(unix).unwrap();
+        let cron = format!("{} {} * * *", start.minute, start.hour);
+        let window = |minutes| MaintenanceConfig { cron: cron.clone(), duration_minutes: minutes, buffer: None };
+
+        let schedule = Schedule::from_config(&[window(30)]).unwrap();
+        let (_, opened) = schedule.active(SystemTime::now()).unwrap();
+        assert_eq!(opened, unix.div_euclid(60));
+        // The same occurrence, as seen again after the clock was stepped back
+        assert_eq!(schedule.active(started + Duration::from_secs(60)).unwrap().1, opened);
+        assert!(Schedule::from_config(&[window(5)]).unwrap().active(SystemTime::now()).is_none());
+        assert!(Schedule::from_config(&[window(0)]).is_err());
+    }
+}
@@ -0,0 +1,93 @@
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+
+/// Integrity manifest for an entropy file: the file is split into
+/// consecutive `chunk_bytes` sized chunks (the last one may be shorter)
+/// and each chunk has its SHA-256 digest listed in order.
+///
+/// ```toml
+/// chunk_bytes = 1048576
+/// sha256 = ["9f86d081884c7d65...", "..."]
+/// ```
+#[derive(Debug)]
+pub struct Manifest {
+    chunk_bytes: usize,
+    digests: Vec<[u8; 32]>,
+}
+
+#[derive(Deserialize)]
+struct ManifestFile {
+    chunk_bytes: usize,
+    sha256: Vec<String>,
+}
+
+impl Manifest {
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read manifest {}: {}", path, e))?;
+        Self::parse(&content).map_err(|e| format!("Invalid manifest {}: {}", path, e).into())
+    }
+
+    fn parse(content: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let raw: ManifestFile = toml::from_str(content)?;
+        if raw.chunk_bytes == 0 {
+            return Err("chunk_bytes must be positive".into());
+        }
+        let mut digests = Vec::with_capacity(raw.sha256.len());
+        for (i, hex_digest) in raw.sha256.iter().enumerate() {
+            let mut digest = [0u8; 32];
+            hex::decode_to_slice(hex_digest, &mut digest)
+                .map_err(|e| format!("digest #{} is not a SHA-256 hex string: {}", i, e))?;
+            digests.push(digest);
+        }
+        Ok(Self { chunk_bytes: raw.chunk_bytes, digests })
+    }
+
+    pub fn chunk_bytes(&self) -> usize {
+        self.chunk_bytes
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.digests.len()
+    }
+
+    /// Checks `chunk` against the digest recorded for chunk number `index`.
+    pub fn verify(&self, index: usize, chunk: &[u8]) -> bool {
+        match self.digests.get(index) {
+            Some(expected) => Sha256::digest(chunk).as_slice() == expected,
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest_for(data: &[u8], chunk_bytes: usize) -> String {
+        let digests: Vec<String> = data
+            .chunks(chunk_bytes)
+            .map(|c| format!("\"{}\"", hex::encode(Sha256::digest(c))))
+            .collect();
+        format!("chunk_bytes = {}\nsha256 = [{}]\n", chunk_bytes, digests.join(", "))
+    }
+
+    #[test]
+    fn test_verify_chunks() {
+        let data = b"0123456789abcdefXYZ";
+        let manifest = Manifest::parse(&manifest_for(data, 8)).unwrap();
+        assert_eq!(manifest.chunk_bytes(), 8);
+        assert_eq!(manifest.chunk_count(), 3);
+        assert!(manifest.verify(0, &data[..8]));
+        assert!(manifest.verify(2, &data[16..]));
+        assert!(!manifest.verify(1, &data[..8]));
+        assert!(!manifest.verify(3, &data[..8]));
+    }
+
+    #[test]
+    fn test_rejects_malformed() {
+        assert!(Manifest::parse("chunk_bytes = 0\nsha256 = []\n").is_err());
+        assert!(Manifest::parse("chunk_bytes = 8\nsha256 = [\"abcd\"]\n").is_err());
+    }
+}
@@ -0,0 +1,84 @@
+use crate::config::MemoryConfig;
+use crate::error::Error;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Accounts for the memory the service holds for entropy, source buffers
+/// and what in-flight requests allocate, against the `[memory]` ceiling.
+pub struct Accountant {
+    /// `None` only accounts.
+    ceiling: Option<usize>,
+    used: AtomicUsize,
+    peak: AtomicUsize,
+    rejected: AtomicU64,
+}
+
+/// Memory charged to the accountant until dropped.
+pub struct Reservation {
+    accountant: Arc<Accountant>,
+    bytes: usize,
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.accountant.used.fetch_sub(self.bytes, Ordering::Relaxed);
+    }
+}
+
+impl Accountant {
+    pub fn new(cfg: Option<&MemoryConfig>) -> Arc<Self> {
+        let ceiling = cfg.and_then(|c| c.ceiling_mebibytes).map(|mib| (mib as usize).saturating_mul(1024 * 1024));
+        Arc::new(Self { ceiling, used: AtomicUsize::new(0), peak: AtomicUsize::new(0), rejected: AtomicU64::new(0) })
+    }
+
+    /// Charges `bytes` for `what`, or fails with `MemoryExhausted` if that
+    /// would take the total above the ceiling.
+    pub fn reserve(self: &Arc<Self>, bytes: usize, what: &str) -> Result<Reservation, Error> {
+        let charged = self.used.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+            let total = used.checked_add(bytes)?;
+            self.ceiling.is_none_or(|ceiling| total <= ceiling).then_some(total)
+        });
+        match charged {
+            Ok(used) => {
+                self.peak.fetch_max(used + bytes, Ordering::Relaxed);
+                Ok(Reservation { accountant: self.clone(), bytes })
+            }
+            Err(used) => {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+                log::warn!(
+                    "Refused {} of {} bytes: {} bytes in use, ceiling {}",
+                    what,
+                    bytes,
+                    used,
+                    self.ceiling.map_or("none".to_string(), |c| c.to_string())
+                );
+                Err(Error::MemoryExhausted)
+            }
+        }
+    }
+
+    /// (bytes in use, highest use so far, ceiling, refused reservations).
+    pub fn stats(&self) -> (usize, usize, Option<usize>, u64) {
+        (self.used.load(Ordering::Relaxed), self.peak.load(Ordering::Relaxed), self.ceiling, self.rejected.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserve() {
+        let accountant = Accountant::new(Some(&MemoryConfig { ceiling_mebibytes: Some(1) }));
+        let buffers = accountant.reserve(768 * 1024, "buffers").unwrap();
+        let request = accountant.reserve(256 * 1024, "a request").unwrap();
+        assert_eq!(accountant.reserve(1, "a request").err(), Some(Error::MemoryExhausted));
+        drop(request);
+        assert!(accountant.reserve(256 * 1024, "a request").is_ok());
+        drop(buffers);
+        assert_eq!(accountant.stats(), (0, 1024 * 1024, Some(1024 * 1024), 1));
+        let unlimited = Accountant::new(None);
+        let _held = unlimited.reserve(1 << 40, "a request").unwrap();
+        assert_eq!(unlimited.stats().0, 1 << 40);
+    }
+}
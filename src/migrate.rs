@@ -0,0 +1,206 @@
+use crate::config::load_config;
+use std::path::Path;
+use toml::{Table, Value};
+
+const USAGE: &str = "usage: trngdbus migrate [--config LEGACY.toml] [--file PATH]... [--output PATH] [--force]";
+
+/// What `trngdbus migrate` was asked to convert.
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    /// A config in an older layout, e.g. `[[sources]]` blocks.
+    config: Option<String>,
+    /// Files XORed together, as the two-file prototypes were invoked.
+    files: Vec<String>,
+    output: Option<String>,
+    force: bool,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut opts = Options::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{} needs a value\n{}", name, USAGE));
+        match arg.as_str() {
+            "--config" => opts.config = Some(value(&arg)?),
+            "--file" => opts.files.push(value(&arg)?),
+            "--output" => opts.output = Some(value(&arg)?),
+            "--force" => opts.force = true,
+            _ => return Err(format!("unknown argument {}\n{}", arg, USAGE)),
+        }
+    }
+    if opts.config.is_none() && opts.files.is_empty() {
+        return Err(format!("nothing to migrate\n{}", USAGE));
+    }
+    Ok(opts)
+}
+
+/// Runs `trngdbus migrate`: writes the current schema for a legacy setup to
+/// `--output` (default the service's config path), then loads it back the way
+/// the service would. Returns the process exit code.
+pub fn run(args: impl IntoIterator<Item = String>, default_output: String) -> i32 {
+    let opts = match parse_args(args) {
+        Ok(opts) => opts,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            return 2;
+        }
+    };
+    let output = opts.output.clone().unwrap_or(default_output);
+    match migrate_to(&opts, &output) {
+        Ok(enabled) => {
+            println!("Wrote {} with {} enabled sources", output, enabled);
+            0
+        }
+        Err(msg) => {
+            eprintln!("Migration failed: {}", msg);
+            1
+        }
+    }
+}
+
+fn migrate_to(opts: &Options, output: &str) -> Result<usize, String> {
+    if Path::new(output).exists() && !opts.force {
+        return Err(format!("{} already exists; pass --force to overwrite it", output));
+    }
+    let legacy = match &opts.config {
+        Some(path) => {
+            let content = std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+            content.parse::<Table>().map_err(|e| format!("cannot parse {}: {}", path, e))?
+        }
+        None => Table::new(),
+    };
+    let mut notes = Vec::new();
+    let migrated = migrate(legacy, &opts.files, &mut notes)?;
+    for note in &notes {
+        println!("{}", note);
+    }
+    if let Some(dir) = Path::new(output).parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {}", dir.display(), e))?;
+    }
+    let content = toml::to_string(&migrated).map_err(|e| e.to_string())?;
+    std::fs::write(output, content).map_err(|e| format!("cannot write {}: {}", output, e))?;
+    let cfg = load_config(output).map_err(|e| e.to_string())?;
+    Ok(cfg.lrng_sources.len() + cfg.file_sources.len() + cfg.serial_sources.len() + cfg.tcp_sources.len() + cfg.http_sources.len() + cfg.cpu_sources.len()
+        + cfg.jitter_sources.len() + cfg.mqtt_sources.len() + cfg.hwrng_sources.len()
+        + cfg.nats_sources.len() + cfg.exec_sources.len() + cfg.pkcs11_sources.len() + cfg.audio_sources.len()
+        + cfg.dbus_sources.len() + cfg.grpc_sources.len())
+}
+
+/// Converts `legacy` to the current schema and adds `files` as enabled file
+/// sources, noting anything the user should check.
+fn migrate(mut legacy: Table, files: &[String], notes: &mut Vec<String>) -> Result<Table, String> {
+    let mut sources = match legacy.remove("sources") {
+        None => Table::new(),
+        Some(Value::Table(sources)) => sources,
+        // Older docs had `[[sources]]`, whose `[[sources.lrng]]` belong to the block above them
+        Some(Value::Array(blocks)) => {
+            let mut merged = Table::new();
+            for block in blocks {
+                let Value::Table(block) = block else { return Err("[[sources]] entries must be tables".to_string()) };
+                for (key, value) in block {
+                    match (merged.get_mut(&key), value) {
+                        (Some(Value::Array(have)), Value::Array(more)) => have.extend(more),
+                        (Some(_), _) => return Err(format!("`{}` is set in more than one [[sources]] block", key)),
+                        (None, value) => {
+                            merged.insert(key, value);
+                        }
+                    }
+                }
+            }
+            notes.push("Merged the [[sources]] blocks into one [sources] table".to_string());
+            merged
+        }
+        Some(_) => return Err("`sources` must be a table".to_string()),
+    };
+    sources.entry("combine").or_insert_with(|| Value::from("xor"));
+
+    let mut ids: Vec<String> = Vec::new();
+    for kind in ["lrng", "file", "serial", "tcp", "http", "cpu", "jitter", "mqtt", "hwrng", "nats", "exec", "pkcs11", "audio", "dbus", "grpc"] {
+        let Some(Value::Array(list)) = sources.get(kind) else { continue };
+        for source in list.iter().filter_map(Value::as_table) {
+            let id = source.get("id").and_then(Value::as_str).unwrap_or("?").to_string();
+            if !source.contains_key("enabled") {
+                notes.push(format!("Source {} has no `enabled` and stays disabled; add `enabled = true` to use it", id));
+            }
+            ids.push(id);
+        }
+    }
+
+    if !files.is_empty() {
+        let list = sources.entry("file").or_insert_with(|| Value::Array(Vec::new()));
+        let Value::Array(list) = list else { return Err("`sources.file` must be an array".to_string()) };
+        let mut n = 0;
+        for path in files {
+            let id = loop {
+                n += 1;
+                let id = format!("file-{}", n);
+                if !ids.contains(&id) {
+                    break id;
+                }
+            };
+            notes.push(format!("Added file source {} for {}", id, path));
+            let mut source = Table::new();
+            source.insert("id".to_string(), Value::from(id.clone()));
+            source.insert("enabled".to_string(), Value::from(true));
+            source.insert("path".to_string(), Value::from(path.clone()));
+            list.push(Value::Table(source));
+            ids.push(id);
+        }
+    }
+    legacy.insert("sources".to_string(), Value::Table(sources));
+    Ok(legacy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_args() {
+        let opts = parse_args(args(&["--file", "a.bin", "--file", "b.bin", "--output", "out.toml"])).unwrap();
+        assert_eq!(opts.files, ["a.bin", "b.bin"]);
+        assert_eq!(opts.output.as_deref(), Some("out.toml"));
+        assert!(parse_args(args(&[])).is_err());
+        assert!(parse_args(args(&["--file"])).is_err());
+        assert!(parse_args(args(&["--verbose"])).is_err());
+    }
+
+    #[test]
+    fn test_legacy_sources_blocks_merged() {
+        let legacy: Table = r#"
+            [[sources]]
+            combine = "xor"
+
+            [[sources.lrng]]
+            id = "linux-dev-random"
+            enabled = true
+
+            [[sources.file]]
+            id = "file-1"
+            path = "/dev/qrandom0"
+
+            [access]
+            capture_uids = [0]
+        "#
+        .parse()
+        .unwrap();
+        let mut notes = Vec::new();
+        let migrated = migrate(legacy, &args(&["a.bin"]), &mut notes).unwrap();
+        let sources = migrated["sources"].as_table().unwrap();
+        assert_eq!(sources["combine"].as_str(), Some("xor"));
+        assert_eq!(sources["lrng"].as_array().unwrap().len(), 1);
+        let files = sources["file"].as_array().unwrap();
+        assert_eq!(files[1]["id"].as_str(), Some("file-2"));
+        assert_eq!(files[1]["enabled"].as_bool(), Some(true));
+        assert!(migrated.contains_key("access"));
+        // The legacy file source had no `enabled`
+        assert_eq!(notes.iter().filter(|n| n.contains("stays disabled")).count(), 1);
+
+        let cfg: crate::config::Config = toml::from_str(&toml::to_string(&migrated).unwrap()).unwrap();
+        assert_eq!(cfg.sources.file.len(), 2);
+    }
+}
@@ -0,0 +1,133 @@
+use crate::access::{AccessPolicy, Endpoint};
+use crate::aggregator::Aggregator;
+use crate::error::Error;
+use crate::{snapshot, status_code};
+use std::collections::HashMap;
+use std::sync::Arc;
+use zbus::interface;
+use zbus::object_server::SignalEmitter;
+
+/// `lv.lumii.trng.Monitor`: what a monitoring agent needs, without a way to
+/// read or divert entropy, under an access policy separate from the
+/// consuming interfaces.
+#[derive(Clone)]
+pub struct Monitor {
+    aggregator: Arc<Aggregator>,
+    access: AccessPolicy,
+}
+
+impl Monitor {
+    pub fn new(aggregator: Arc<Aggregator>, access: AccessPolicy) -> Self {
+        Self { aggregator, access }
+    }
+}
+
+impl Endpoint for Monitor {
+    fn access(&self) -> &AccessPolicy {
+        &self.access
+    }
+}
+
+#[interface(name = "lv.lumii.trng.Monitor")]
+impl Monitor {
+    /// GetStats returns (total_bytes_served, total_requests_served).
+    async fn get_stats(&self) -> (u64, u64) {
+        self.aggregator.get_stats()
+    }
+
+    /// GetHealth returns (ready, states): whether requests are served now,
+    /// and per source id one of "ok", "standby", "unavailable",
+    /// "maintenance", "quarantined" or "failed".
+    async fn get_health(&self) -> (bool, HashMap<String, String>) {
+        self.aggregator.health()
+    }
+
+    /// GetSourceInfo returns (status, info) for `source_id`: its "kind" and
+    /// "version", and what the source reports about its device, e.g. the
+    /// "backend" and "quality" a hwrng source currently reads from.
+    async fn get_source_info(&self, source_id: &str) -> (i32, HashMap<String, String>) {
+        match self.aggregator.source_info(source_id) {
+            Ok(info) => (0, info),
+            Err(e) => {
+                log::error!("Error getting source info: {:?}", e);
+                (status_code(&e), HashMap::new())
+            }
+        }
+    }
+
+    /// GetSourceStatus returns (id, kind, buffer_fill, buffer_size, healthy)
+    /// for each source, buffer sizes in bytes and 0 for unbuffered sources.
+    async fn get_source_status(&self) -> Vec<(String, String, u64, u64, bool)> {
+        self.aggregator.source_status().await
+    }
+
+    /// GetConfigHashes returns (path, sha256, sections): the loaded config
+    /// file and SHA-256 digests of it and of each section and source block,
+    /// which tell configs apart without revealing them.
+    async fn get_config_hashes(&self) -> (String, String, HashMap<String, String>) {
+        let hashes = self.aggregator.config_hashes();
+        let sections = hashes.sections.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        (hashes.path.clone(), hashes.file.clone(), sections)
+    }
+
+    /// GetQualityTrend returns (status, points) for `source_id`: the
+    /// `[trending]` results kept for it, oldest first, each its unix time and
+    /// "bytes", "chi_square", "bits_per_byte", "min_entropy", "monobit_z",
+    /// "runs_z", "serial_correlation" and "failed" (tests failed). Fails with
+    /// -8 for an unknown source or without `[trending]`.
+    async fn get_quality_trend(&self, source_id: &str) -> (i32, Vec<(u64, HashMap<String, f64>)>) {
+        let known = self.aggregator.source_info(source_id).is_ok();
+        match self.aggregator.trends() {
+            Some(trends) if known => (0, trends.series(source_id).iter().map(|p| (p.taken_at, p.values())).collect()),
+            _ => (status_code(&Error::InvalidOption("source_id".to_string())), Vec::new()),
+        }
+    }
+
+    /// GetDrbgStats returns (enabled, stats): whether the `[drbg]` output
+    /// stage serves requests, and its "reseeds", "reseed_failures",
+    /// "bytes_generated", "bytes_since_reseed" and "reseed_interval_bytes".
+    async fn get_drbg_stats(&self) -> (bool, HashMap<String, u64>) {
+        match self.aggregator.drbg_stats() {
+            Some(stats) => (true, stats.into_iter().map(|(k, v)| (k.to_string(), v)).collect()),
+            None => (false, HashMap::new()),
+        }
+    }
+
+    /// GetPoolStats returns (enabled, stats): whether a `pool` serves
+    /// requests, and its fill levels: "credit_bytes", "max_credit_bytes" and
+    /// "squeezed_bytes" for `keccak`, "reseeds", "bytes_generated" and
+    /// "pool_00" to "pool_31" for `fortuna`.
+    async fn get_pool_stats(&self) -> (bool, HashMap<String, u64>) {
+        match self.aggregator.pool_stats() {
+            Some(stats) => (true, stats.into_iter().collect()),
+            None => (false, HashMap::new()),
+        }
+    }
+
+    /// ExportStatsSnapshot returns (status, snapshot): every counter, health
+    /// state and config hash as tagged CBOR with a "version" field, for
+    /// incident tickets and for diffing service state (see `trngdbus snapshot`).
+    async fn export_stats_snapshot(&self) -> (i32, Vec<u8>) {
+        (0, snapshot::encode(&self.aggregator.snapshot().await))
+    }
+
+    /// BreakerStateChanged, SourcePromoted, Alert, WatchdogStateChanged,
+    /// EntropyLow and SourceFailed are emitted here as on `lv.lumii.trng.Rng`.
+    #[zbus(signal)]
+    async fn breaker_state_changed(emitter: &SignalEmitter<'_>, source_id: &str, state: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn source_promoted(emitter: &SignalEmitter<'_>, source_id: &str, replaced: &str, reason: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn alert(emitter: &SignalEmitter<'_>, kind: &str, source_id: &str, details: HashMap<String, String>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn watchdog_state_changed(emitter: &SignalEmitter<'_>, healthy: bool, reason: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn entropy_low(emitter: &SignalEmitter<'_>, source_id: &str, fill_percent: f64) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn source_failed(emitter: &SignalEmitter<'_>, source_id: &str, error: &str) -> zbus::Result<()>;
+}
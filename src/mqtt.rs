@@ -0,0 +1,433 @@
+use crate::config::MqttConfig;
+use crate::tcp::{self, Connector};
+use std::collections::HashSet;
+use std::io;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::{timeout, Duration, Instant};
+use zeroize::Zeroizing;
+
+/// Default for `keep_alive_seconds`.
+const KEEP_ALIVE_SECONDS: u16 = 30;
+
+/// Room for the fixed header, topic and packet id around a payload.
+const PACKET_OVERHEAD: usize = 64 * 1024;
+
+/// How long the broker has to answer CONNECT and SUBSCRIBE.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const PUBACK: u8 = 0x40;
+const PUBREC: u8 = 0x50;
+const PUBREL: u8 = 0x60;
+const PUBCOMP: u8 = 0x70;
+const SUBSCRIBE: u8 = 0x80;
+const SUBACK: u8 = 0x90;
+const PINGREQ: u8 = 0xc0;
+const PINGRESP: u8 = 0xd0;
+
+/// Packet id of the one SUBSCRIBE sent per connection.
+const SUBSCRIBE_ID: u16 = 1;
+
+#[derive(Debug, PartialEq)]
+struct Broker {
+    tls: bool,
+    host: String,
+    port: u16,
+}
+
+fn parse_broker(url: &str) -> Result<Broker, String> {
+    let (tls, rest) = match (url.strip_prefix("mqtts://"), url.strip_prefix("mqtt://")) {
+        (Some(rest), _) => (true, rest),
+        (None, Some(rest)) => (false, rest),
+        _ => return Err(format!("unsupported broker URL '{}', use mqtt:// or mqtts://", url)),
+    };
+    let authority = rest.strip_suffix('/').unwrap_or(rest);
+    if authority.contains(['/', '?', '@']) {
+        return Err(format!("broker URL '{}' must be only scheme, host and port - use username and password_file", url));
+    }
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => {
+            (host, port.parse().map_err(|_| format!("invalid port in broker URL '{}'", url))?)
+        }
+        _ => (authority, if tls { 8883 } else { 1883 }),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() {
+        return Err(format!("no host in broker URL '{}'", url));
+    }
+    Ok(Broker { tls, host: host.to_string(), port })
+}
+
+/// Checks a subscription topic filter (MQTT 3.1.1, 4.7): `+` takes a whole
+/// level and `#` only the last one.
+fn check_filter(filter: &str) -> Result<(), String> {
+    if filter.is_empty() || filter.len() > u16::MAX as usize || filter.contains('\0') {
+        return Err(format!("invalid topic '{}'", filter));
+    }
+    let levels: Vec<&str> = filter.split('/').collect();
+    for (i, level) in levels.iter().enumerate() {
+        let wildcard = level.contains(['+', '#']);
+        if (wildcard && level.len() > 1) || (*level == "#" && i + 1 < levels.len()) {
+            return Err(format!("topic '{}' has a wildcard that is not a whole level, or a '#' before the last level", filter));
+        }
+    }
+    Ok(())
+}
+
+/// Which publisher a message on `topic` came from: the levels `filter`
+/// matched with wildcards, or the whole topic when it has none.
+pub fn publisher(filter: &str, topic: &str) -> String {
+    let mut topic_levels = topic.split('/');
+    let mut matched = Vec::new();
+    for level in filter.split('/') {
+        match level {
+            "#" => {
+                matched.extend(topic_levels.by_ref());
+                break;
+            }
+            "+" => matched.extend(topic_levels.next()),
+            _ => {
+                topic_levels.next();
+            }
+        }
+    }
+    match matched.is_empty() {
+        true => topic.to_string(),
+        false => matched.join("/"),
+    }
+}
+
+/// One application message received.
+pub struct Message {
+    pub topic: String,
+    pub payload: Zeroizing<Vec<u8>>,
+    /// Kept by the broker from before we subscribed, and sent again on
+    /// every subscription.
+    pub retained: bool,
+}
+
+/// Connects to the broker of an MQTT source and subscribes to its topic.
+pub struct Subscriber {
+    connector: Connector,
+    url: String,
+    topic: String,
+    qos: u8,
+    client_id: String,
+    username: Option<String>,
+    password: Option<Zeroizing<Vec<u8>>>,
+    keep_alive: u16,
+    max_packet: usize,
+}
+
+impl Subscriber {
+    pub fn new(cfg: &MqttConfig) -> Result<Self, String> {
+        let broker = parse_broker(&cfg.broker)?;
+        let tls = match (&cfg.tls, broker.tls) {
+            (Some(tls), true) => Some(tls),
+            (None, true) => return Err("mqtts URLs need a tls table with ca_file or pin_sha256".to_string()),
+            (Some(_), false) => return Err("tls is set but the broker URL is not mqtts".to_string()),
+            (None, false) => {
+                log::warn!("MQTT source {} receives its bytes in the clear - use mqtts", cfg.id);
+                None
+            }
+        };
+        check_filter(&cfg.topic)?;
+        let qos = cfg.qos.unwrap_or(1);
+        if qos > 2 {
+            return Err(format!("qos must be 0, 1 or 2, not {}", qos));
+        }
+        let client_id = cfg.client_id.clone().unwrap_or_else(|| format!("trng-dbus-{}", cfg.id));
+        if client_id.is_empty() || client_id.len() > u16::MAX as usize {
+            return Err("client_id must be 1 to 65535 bytes".to_string());
+        }
+        let password = cfg.password_file.as_deref().map(crate::pkcs11::read_pin).transpose()?;
+        if password.is_some() && cfg.username.is_none() {
+            return Err("password_file needs a username".to_string());
+        }
+        Ok(Self {
+            connector: Connector::new(&broker.host, broker.port, tls, cfg.connect_timeout_ms)?,
+            url: cfg.broker.clone(),
+            topic: cfg.topic.clone(),
+            qos,
+            client_id,
+            username: cfg.username.clone(),
+            password,
+            keep_alive: cfg.keep_alive_seconds.unwrap_or(KEEP_ALIVE_SECONDS),
+            max_packet: cfg.max_payload_bytes.unwrap_or(crate::config::DEFAULT_MQTT_MAX_PAYLOAD_BYTES) + PACKET_OVERHEAD,
+        })
+    }
+
+    /// The broker URL, for logs and the ledger.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    /// Connects with a clean session and subscribes, returning once the
+    /// broker granted the subscription.
+    pub async fn subscribe(&self) -> io::Result<Session> {
+        let stream = self.connector.connect().await?;
+        let mut session = Session {
+            stream,
+            inbox: Zeroizing::new(Vec::new()),
+            max_packet: self.max_packet,
+            keep_alive: Duration::from_secs(self.keep_alive.into()),
+            last_sent: Instant::now(),
+            last_received: Instant::now(),
+            ping_sent: false,
+            qos2_pending: HashSet::new(),
+        };
+        let mut flags = 0x02; // clean session
+        let mut payload = Zeroizing::new(Vec::new());
+        put_str(&mut payload, self.client_id.as_bytes());
+        if let Some(username) = &self.username {
+            flags |= 0x80;
+            put_str(&mut payload, username.as_bytes());
+        }
+        if let Some(password) = &self.password {
+            flags |= 0x40;
+            put_str(&mut payload, password);
+        }
+        let mut body = Zeroizing::new(Vec::with_capacity(10 + payload.len()));
+        put_str(&mut body, b"MQTT");
+        body.extend_from_slice(&[4, flags]);
+        body.extend_from_slice(&self.keep_alive.to_be_bytes());
+        body.extend_from_slice(&payload);
+        session.send(CONNECT, &body).await?;
+        let (header, body) = timeout(HANDSHAKE_TIMEOUT, session.receive()).await.map_err(|_| timed_out("CONNACK"))??;
+        if header & 0xf0 != CONNACK || body.len() != 2 {
+            return Err(protocol("expected CONNACK"));
+        }
+        if body[1] != 0 {
+            let reason = match body[1] {
+                1 => "unacceptable protocol version",
+                2 => "client id rejected",
+                3 => "server unavailable",
+                4 => "bad username or password",
+                5 => "not authorized",
+                _ => "unknown reason",
+            };
+            return Err(io::Error::new(io::ErrorKind::ConnectionRefused, format!("broker refused the connection: {}", reason)));
+        }
+
+        let mut body = Vec::with_capacity(5 + self.topic.len());
+        body.extend_from_slice(&SUBSCRIBE_ID.to_be_bytes());
+        put_str(&mut body, self.topic.as_bytes());
+        body.push(self.qos);
+        session.send(SUBSCRIBE | 0x02, &body).await?;
+        // Retained messages may come before the SUBACK
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        loop {
+            let (header, body) = tokio::time::timeout_at(deadline, session.receive()).await.map_err(|_| timed_out("SUBACK"))??;
+            if header & 0xf0 != SUBACK {
+                continue;
+            }
+            if body.len() != 3 || body[..2] != SUBSCRIBE_ID.to_be_bytes() {
+                return Err(protocol("malformed SUBACK"));
+            }
+            if body[2] == 0x80 {
+                return Err(io::Error::new(io::ErrorKind::PermissionDenied, format!("broker refused the subscription to '{}'", self.topic)));
+            }
+            if body[2] < self.qos {
+                log::warn!("MQTT broker {} granted QoS {} instead of {} for '{}'", self.url, body[2], self.qos, self.topic);
+            }
+            return Ok(session);
+        }
+    }
+}
+
+/// A subscribed connection to the broker.
+pub struct Session {
+    stream: Box<dyn tcp::Stream>,
+    /// Received bytes not yet parsed into packets.
+    inbox: Zeroizing<Vec<u8>>,
+    max_packet: usize,
+    keep_alive: Duration,
+    last_sent: Instant,
+    last_received: Instant,
+    ping_sent: bool,
+    /// QoS 2 messages delivered whose PUBREL has not come yet; the broker
+    /// sends them again until we answer PUBREC, and those are not new.
+    qos2_pending: HashSet<u16>,
+}
+
+impl Session {
+    /// Waits up to `wait` for the next message, acknowledging it as its
+    /// QoS needs and keeping the connection alive. Returns `None` if no
+    /// message came in time; fails when the broker closes the connection
+    /// or stops answering pings.
+    pub async fn next(&mut self, wait: Duration) -> io::Result<Option<Message>> {
+        let deadline = Instant::now() + wait;
+        loop {
+            if !self.keep_alive.is_zero() {
+                if self.ping_sent && self.last_received.elapsed() > self.keep_alive + self.keep_alive / 2 {
+                    return Err(timed_out("PINGRESP"));
+                }
+                if !self.ping_sent && self.last_sent.elapsed() >= self.keep_alive / 2 {
+                    self.send(PINGREQ, &[]).await?;
+                    self.ping_sent = true;
+                }
+            }
+            let slice = match self.keep_alive.is_zero() {
+                true => deadline,
+                false => deadline.min(Instant::now() + self.keep_alive / 2),
+            };
+            // Socket and TLS reads can be cancelled without losing bytes
+            let Ok(packet) = tokio::time::timeout_at(slice, self.receive()).await else {
+                if Instant::now() >= deadline {
+                    return Ok(None);
+                }
+                continue;
+            };
+            let (header, body) = packet?;
+            match header & 0xf0 {
+                PUBLISH => {
+                    if let Some(message) = self.publish(header, body).await? {
+                        return Ok(Some(message));
+                    }
+                }
+                PUBREL => {
+                    let id = packet_id(&body)?;
+                    self.qos2_pending.remove(&id);
+                    self.send(PUBCOMP, &id.to_be_bytes()).await?;
+                }
+                PINGRESP => {}
+                other => log::debug!("Ignoring MQTT packet type {:#x}", other >> 4),
+            }
+            if Instant::now() >= deadline {
+                return Ok(None);
+            }
+        }
+    }
+
+    async fn publish(&mut self, header: u8, body: Zeroizing<Vec<u8>>) -> io::Result<Option<Message>> {
+        let qos = (header >> 1) & 0x03;
+        let retained = header & 0x01 != 0;
+        let topic_len = body.get(..2).map(|b| u16::from_be_bytes([b[0], b[1]]) as usize).ok_or_else(|| protocol("short PUBLISH"))?;
+        let topic = body.get(2..2 + topic_len).ok_or_else(|| protocol("short PUBLISH"))?;
+        let topic = String::from_utf8(topic.to_vec()).map_err(|_| protocol("PUBLISH topic is not UTF-8"))?;
+        let mut at = 2 + topic_len;
+        let id = match qos {
+            0 => None,
+            1 | 2 => {
+                let id = packet_id(body.get(at..).unwrap_or_default())?;
+                at += 2;
+                Some(id)
+            }
+            _ => return Err(protocol("PUBLISH with QoS 3")),
+        };
+        let payload = Zeroizing::new(body[at..].to_vec());
+        match (qos, id) {
+            (1, Some(id)) => self.send(PUBACK, &id.to_be_bytes()).await?,
+            (2, Some(id)) => {
+                let new = self.qos2_pending.insert(id);
+                self.send(PUBREC, &id.to_be_bytes()).await?;
+                if !new {
+                    return Ok(None);
+                }
+            }
+            _ => {}
+        }
+        Ok(Some(Message { topic, payload, retained }))
+    }
+
+    async fn send(&mut self, header: u8, body: &[u8]) -> io::Result<()> {
+        let mut packet = Zeroizing::new(Vec::with_capacity(5 + body.len()));
+        packet.push(header);
+        let mut len = body.len();
+        loop {
+            let byte = (len % 128) as u8;
+            len /= 128;
+            packet.push(if len > 0 { byte | 0x80 } else { byte });
+            if len == 0 {
+                break;
+            }
+        }
+        packet.extend_from_slice(body);
+        self.stream.write_all(&packet).await?;
+        self.stream.flush().await?;
+        self.last_sent = Instant::now();
+        Ok(())
+    }
+
+    /// Reads the next packet, as its fixed header byte and its body.
+    /// Cancelling it keeps what was read for the next call.
+    async fn receive(&mut self) -> io::Result<(u8, Zeroizing<Vec<u8>>)> {
+        loop {
+            if let Some((header, start, len)) = self.frame()? {
+                let body = Zeroizing::new(self.inbox[start..start + len].to_vec());
+                self.inbox.drain(..start + len);
+                self.last_received = Instant::now();
+                self.ping_sent = false;
+                return Ok((header, body));
+            }
+            let mut chunk = Zeroizing::new(vec![0u8; 16 * 1024]);
+            let n = self.stream.read(&mut chunk).await?;
+            if n == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "broker closed the connection"));
+            }
+            self.inbox.extend_from_slice(&chunk[..n]);
+        }
+    }
+
+    /// Where the first complete packet in `inbox` is, if there is one.
+    fn frame(&self) -> io::Result<Option<(u8, usize, usize)>> {
+        let mut len = 0usize;
+        for i in 0..4 {
+            let Some(&byte) = self.inbox.get(1 + i) else { return Ok(None) };
+            len |= ((byte & 0x7f) as usize) << (7 * i);
+            if byte & 0x80 == 0 {
+                if len > self.max_packet {
+                    return Err(protocol("packet larger than max_payload_bytes allows"));
+                }
+                let start = 2 + i;
+                return Ok((self.inbox.len() >= start + len).then_some((self.inbox[0], start, len)));
+            }
+        }
+        Err(protocol("malformed remaining length"))
+    }
+}
+
+fn put_str(out: &mut Vec<u8>, s: &[u8]) {
+    out.extend_from_slice(&(s.len() as u16).to_be_bytes());
+    out.extend_from_slice(s);
+}
+
+fn packet_id(body: &[u8]) -> io::Result<u16> {
+    body.get(..2).map(|b| u16::from_be_bytes([b[0], b[1]])).ok_or_else(|| protocol("missing packet id"))
+}
+
+fn protocol(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("MQTT protocol error: {}", what))
+}
+
+fn timed_out(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, format!("no {} from the broker", what))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_broker_and_publisher() {
+        assert_eq!(parse_broker("mqtt://broker.lan").unwrap(), Broker { tls: false, host: "broker.lan".to_string(), port: 1883 });
+        assert_eq!(parse_broker("mqtts://[::1]:9000/").unwrap(), Broker { tls: true, host: "::1".to_string(), port: 9000 });
+        assert!(parse_broker("mqtt://user:pw@broker").is_err());
+        assert!(parse_broker("tcp://broker").is_err());
+
+        assert!(check_filter("beacons/+/entropy").is_ok());
+        assert!(check_filter("beacons/#").is_ok());
+        assert!(check_filter("beacons/#/entropy").is_err());
+        assert!(check_filter("beacons/a+").is_err());
+
+        assert_eq!(publisher("beacons/+/entropy", "beacons/riga-3/entropy"), "riga-3");
+        assert_eq!(publisher("beacons/#", "beacons/lv/riga-3"), "lv/riga-3");
+        assert_eq!(publisher("beacons/riga-3", "beacons/riga-3"), "beacons/riga-3");
+    }
+}
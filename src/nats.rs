@@ -0,0 +1,508 @@
+use crate::config::NatsConfig;
+use crate::tcp;
+use crate::tls::TlsClient;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{timeout, timeout_at, Duration, Instant};
+use zeroize::Zeroizing;
+
+/// Default for `connect_timeout_ms`, covering the TLS upgrade too.
+const CONNECT_TIMEOUT_MS: u64 = 5000;
+
+/// Default for `batch`.
+const BATCH: u32 = 16;
+
+/// How long one pull request waits on the server for messages.
+const PULL_EXPIRES: Duration = Duration::from_secs(5);
+
+/// How long the server has to answer CONNECT and JetStream API requests.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `ack_wait` of consumers this service creates.
+const ACK_WAIT: Duration = Duration::from_secs(30);
+
+/// Largest INFO line accepted before the connection is set up.
+const MAX_INFO_BYTES: usize = 64 * 1024;
+
+/// Room for the control line and headers around a payload.
+const FRAME_OVERHEAD: usize = 64 * 1024;
+
+/// JetStream API error code for a stream without the consumer asked for.
+const CONSUMER_NOT_FOUND: u64 = 10014;
+
+/// Subscription id of the connection's one inbox subscription.
+const INBOX_SID: &str = "1";
+
+#[derive(Debug, PartialEq)]
+struct Server {
+    host: String,
+    port: u16,
+}
+
+fn parse_server(url: &str) -> Result<Server, String> {
+    let rest = url.strip_prefix("nats://").ok_or_else(|| format!("unsupported server URL '{}', use nats://", url))?;
+    let authority = rest.strip_suffix('/').unwrap_or(rest);
+    if authority.contains(['/', '?', '@']) {
+        return Err(format!("server URL '{}' must be only scheme, host and port - use username and password_file", url));
+    }
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => {
+            (host, port.parse().map_err(|_| format!("invalid port in server URL '{}'", url))?)
+        }
+        _ => (authority, 4222),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() {
+        return Err(format!("no host in server URL '{}'", url));
+    }
+    Ok(Server { host: host.to_string(), port })
+}
+
+/// Stream and consumer names go into API subjects, so they must be single
+/// subject tokens.
+fn check_name(what: &str, name: &str) -> Result<(), String> {
+    if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c.is_control() || matches!(c, '.' | '*' | '>')) {
+        return Err(format!("{} '{}' must be one subject token, without '.', '*', '>' or whitespace", what, name));
+    }
+    Ok(())
+}
+
+/// Joins the durable JetStream pull consumer of a NATS source, creating it
+/// if the stream does not have it yet. Every instance of the fleet joins
+/// the same consumer, and the server hands each message to one of them.
+pub struct Consumer {
+    server: Server,
+    tls: Option<TlsClient>,
+    connect_timeout: Duration,
+    /// `nats://host:port` as configured, for logs and the ledger.
+    url: String,
+    stream: String,
+    name: String,
+    filter_subject: Option<String>,
+    username: Option<String>,
+    password: Option<Zeroizing<Vec<u8>>>,
+    batch: u32,
+}
+
+impl Consumer {
+    pub fn new(cfg: &NatsConfig) -> Result<Self, String> {
+        let server = parse_server(&cfg.server)?;
+        let tls = cfg.tls.as_ref().map(TlsClient::new).transpose()?;
+        if tls.is_none() {
+            log::warn!("NATS source {} receives its bytes in the clear - use tls", cfg.id);
+        }
+        check_name("stream", &cfg.stream)?;
+        let name = cfg.consumer.clone().unwrap_or_else(|| "trng-dbus".to_string());
+        check_name("consumer", &name)?;
+        let password = cfg.password_file.as_deref().map(crate::pkcs11::read_pin).transpose()?;
+        if password.is_some() && cfg.username.is_none() {
+            return Err("password_file needs a username".to_string());
+        }
+        let batch = cfg.batch.unwrap_or(BATCH);
+        if batch == 0 {
+            return Err("batch must be positive".to_string());
+        }
+        Ok(Self {
+            server,
+            tls,
+            connect_timeout: Duration::from_millis(cfg.connect_timeout_ms.unwrap_or(CONNECT_TIMEOUT_MS).max(1)),
+            url: cfg.server.clone(),
+            stream: cfg.stream.clone(),
+            name,
+            filter_subject: cfg.subject.clone(),
+            username: cfg.username.clone(),
+            password,
+            batch,
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn stream(&self) -> &str {
+        &self.stream
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Connects, authenticates and looks up (or creates) the consumer.
+    pub async fn join(&self) -> io::Result<Session> {
+        let (mut session, info) = timeout(self.connect_timeout, self.connect())
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, format!("no connection within {:?}", self.connect_timeout)))??;
+        let mut connect = json!({
+            "verbose": false,
+            "pedantic": false,
+            "tls_required": self.tls.is_some(),
+            "name": "trng-dbus",
+            "lang": "rust",
+            "version": env!("CARGO_PKG_VERSION"),
+            "protocol": 1,
+            "headers": true,
+            "no_responders": true,
+        });
+        if let Some(user) = &self.username {
+            connect["user"] = user.clone().into();
+        }
+        if let Some(password) = &self.password {
+            connect["pass"] = String::from_utf8_lossy(password).into_owned().into();
+        }
+        let line = Zeroizing::new(format!("CONNECT {}\r\nPING\r\nSUB {}.* {}\r\n", connect, session.inbox, INBOX_SID));
+        session.write(line.as_bytes()).await?;
+        // A refused CONNECT is answered with -ERR instead of the PONG
+        let deadline = Instant::now() + REQUEST_TIMEOUT;
+        loop {
+            match timeout_at(deadline, session.receive()).await.map_err(|_| timed_out("PONG"))?? {
+                Frame::Pong => break,
+                Frame::Ping => session.write(b"PONG\r\n").await?,
+                _ => {}
+            }
+        }
+        if let Some(max) = info["max_payload"].as_u64() {
+            session.max_frame = max as usize + FRAME_OVERHEAD;
+        }
+
+        let api = format!("$JS.API.CONSUMER.INFO.{}.{}", self.stream, self.name);
+        let mut reply = session.request(&api, b"").await?;
+        if reply["error"]["err_code"].as_u64() == Some(CONSUMER_NOT_FOUND) {
+            let mut config = json!({
+                "durable_name": self.name,
+                "ack_policy": "explicit",
+                "ack_wait": ACK_WAIT.as_nanos() as u64,
+                "deliver_policy": "all",
+            });
+            if let Some(subject) = &self.filter_subject {
+                config["filter_subject"] = subject.clone().into();
+            }
+            let api = format!("$JS.API.CONSUMER.DURABLE.CREATE.{}.{}", self.stream, self.name);
+            let body = json!({ "stream_name": self.stream, "config": config }).to_string();
+            reply = session.request(&api, body.as_bytes()).await?;
+            if reply["error"].is_null() {
+                log::info!("Created JetStream consumer {} on stream {}", self.name, self.stream);
+            }
+        }
+        if let Some(description) = reply["error"]["description"].as_str() {
+            return Err(io::Error::other(format!("JetStream consumer {} on stream {}: {}", self.name, self.stream, description)));
+        }
+        let config = &reply["config"];
+        if config["ack_policy"].as_str() != Some("explicit") {
+            return Err(io::Error::other(format!(
+                "JetStream consumer {} must have ack_policy explicit, not {}",
+                self.name, config["ack_policy"]
+            )));
+        }
+        if config["deliver_subject"].is_string() {
+            return Err(io::Error::other(format!("JetStream consumer {} is a push consumer, use a pull consumer", self.name)));
+        }
+        session.ack_wait = config["ack_wait"].as_u64().map_or(ACK_WAIT, Duration::from_nanos);
+        session.pull_subject = format!("$JS.API.CONSUMER.MSG.NEXT.{}.{}", self.stream, self.name);
+        session.batch = self.batch;
+        Ok(session)
+    }
+
+    /// Opens the connection and reads the server's INFO, upgrading to TLS
+    /// after it as NATS does.
+    async fn connect(&self) -> io::Result<(Session, Value)> {
+        let mut tcp_stream = TcpStream::connect((self.server.host.as_str(), self.server.port)).await?;
+        tcp_stream.set_nodelay(true)?;
+        let mut line = Vec::new();
+        while !line.ends_with(b"\r\n") {
+            let mut byte = [0u8; 1];
+            if tcp_stream.read(&mut byte).await? == 0 || line.len() > MAX_INFO_BYTES {
+                return Err(protocol("no INFO from the server"));
+            }
+            line.push(byte[0]);
+        }
+        let info = line
+            .strip_prefix(b"INFO ")
+            .and_then(|json| serde_json::from_slice::<Value>(json).ok())
+            .ok_or_else(|| protocol("malformed INFO"))?;
+        let stream: Box<dyn tcp::Stream> = match &self.tls {
+            Some(tls) => Box::new(tls.connect(&self.server.host, tcp_stream).await?),
+            None if info["tls_required"].as_bool() == Some(true) => {
+                return Err(io::Error::new(io::ErrorKind::PermissionDenied, "the server requires TLS, add a tls table"));
+            }
+            None => Box::new(tcp_stream),
+        };
+        let inbox = format!("_INBOX.{}", hex::encode(crate::lrng::os_fill_rand_octets(12).map_err(|_| io::Error::other("no random inbox name"))?));
+        let session = Session {
+            stream,
+            buffer: Zeroizing::new(Vec::new()),
+            max_frame: 1024 * 1024 + FRAME_OVERHEAD,
+            inbox,
+            last_received: Instant::now(),
+            ping_sent: false,
+            next_reply: 0,
+            unconfirmed: HashMap::new(),
+            ack_wait: ACK_WAIT,
+            pull_subject: String::new(),
+            batch: BATCH,
+            pull: None,
+        };
+        Ok((session, info))
+    }
+}
+
+enum Frame {
+    Message {
+        subject: String,
+        reply: Option<String>,
+        /// Status code of a headers-only message, e.g. 408 when a pull expired.
+        status: Option<u16>,
+        payload: Zeroizing<Vec<u8>>,
+    },
+    Ping,
+    Pong,
+    Other,
+}
+
+/// A connection joined to the consumer.
+pub struct Session {
+    stream: Box<dyn tcp::Stream>,
+    /// Received bytes not yet parsed into frames.
+    buffer: Zeroizing<Vec<u8>>,
+    max_frame: usize,
+    /// Subject prefix every reply to us goes to.
+    inbox: String,
+    last_received: Instant,
+    ping_sent: bool,
+    next_reply: u64,
+    /// Messages whose acknowledgement the server has not confirmed yet, by
+    /// the reply token of the ack, with when they arrived.
+    unconfirmed: HashMap<u64, (Zeroizing<Vec<u8>>, Instant)>,
+    /// The consumer's; after it the server hands a message to someone else.
+    ack_wait: Duration,
+    pull_subject: String,
+    batch: u32,
+    /// Messages still due on the open pull request, and when it expires.
+    pull: Option<(u32, Instant)>,
+}
+
+impl Session {
+    /// Whether a pull request is open.
+    pub fn pulling(&self) -> bool {
+        self.pull.is_some_and(|(_, expires)| Instant::now() < expires)
+    }
+
+    /// Asks the server for up to `batch` messages of at most `max_bytes` in all.
+    pub async fn pull(&mut self, max_bytes: usize) -> io::Result<()> {
+        let expires = PULL_EXPIRES.as_nanos() as u64;
+        let body = json!({ "batch": self.batch, "max_bytes": max_bytes, "expires": expires }).to_string();
+        let reply = format!("{}.pull", self.inbox);
+        let line = format!("PUB {} {} {}\r\n{}\r\n", self.pull_subject, reply, body.len(), body);
+        self.write(line.as_bytes()).await?;
+        // A little longer than the server waits, so its 408 is not missed
+        self.pull = Some((self.batch, Instant::now() + PULL_EXPIRES + Duration::from_secs(1)));
+        Ok(())
+    }
+
+    /// Waits up to `wait` for the next message whose acknowledgement the
+    /// server confirmed in time. Messages are acknowledged before they are
+    /// used: one this instance loses to a crash or a late confirmation is
+    /// gone, but none is used by two instances.
+    pub async fn next(&mut self, wait: Duration) -> io::Result<Option<Zeroizing<Vec<u8>>>> {
+        let deadline = Instant::now() + wait;
+        loop {
+            let quiet = self.last_received.elapsed();
+            if quiet > 3 * PULL_EXPIRES {
+                return Err(timed_out("PONG"));
+            }
+            if quiet > 2 * PULL_EXPIRES && !self.ping_sent {
+                self.write(b"PING\r\n").await?;
+                self.ping_sent = true;
+            }
+            let late = self.ack_wait / 2;
+            let before = self.unconfirmed.len();
+            self.unconfirmed.retain(|_, (_, at)| at.elapsed() < late);
+            if self.unconfirmed.len() < before {
+                log::warn!("NATS: {} messages dropped, their acks were not confirmed within {:?}", before - self.unconfirmed.len(), late);
+            }
+            // Socket and TLS reads can be cancelled without losing bytes
+            let slice = deadline.min(Instant::now() + PULL_EXPIRES);
+            let Ok(frame) = timeout_at(slice, self.receive()).await else {
+                if Instant::now() >= deadline {
+                    return Ok(None);
+                }
+                continue;
+            };
+            match frame? {
+                Frame::Ping => self.write(b"PONG\r\n").await?,
+                Frame::Message { subject, reply, status, payload } => {
+                    if let Some(block) = self.message(subject, reply, status, payload).await? {
+                        return Ok(Some(block));
+                    }
+                }
+                Frame::Pong | Frame::Other => {}
+            }
+            if Instant::now() >= deadline {
+                return Ok(None);
+            }
+        }
+    }
+
+    async fn message(
+        &mut self,
+        subject: String,
+        reply: Option<String>,
+        status: Option<u16>,
+        payload: Zeroizing<Vec<u8>>,
+    ) -> io::Result<Option<Zeroizing<Vec<u8>>>> {
+        if let Some(token) = subject.strip_prefix(&self.inbox).and_then(|s| s.strip_prefix(".ack.")) {
+            // Confirmation of an ack; an error status means the server did not take it
+            let entry = token.parse().ok().and_then(|n| self.unconfirmed.remove(&n));
+            return Ok(entry.filter(|_| status.is_none()).map(|(block, _)| block));
+        }
+        if subject.strip_prefix(&self.inbox) == Some(".pull") {
+            // 404 no messages, 408 expired, 409 e.g. max_bytes reached: the pull is over
+            if let Some(code) = status {
+                if !matches!(code, 404 | 408 | 409) {
+                    log::warn!("NATS pull on {} ended with status {}", self.pull_subject, code);
+                }
+                self.pull = None;
+            }
+            return Ok(None);
+        }
+        // A stream message, acknowledged on its reply subject
+        let Some(ack_subject) = reply.filter(|r| r.starts_with("$JS.ACK.")) else { return Ok(None) };
+        if let Some((due, _)) = self.pull.as_mut() {
+            *due = due.saturating_sub(1);
+            if *due == 0 {
+                self.pull = None;
+            }
+        }
+        if payload.is_empty() {
+            self.write(format!("PUB {} 4\r\n+ACK\r\n", ack_subject).as_bytes()).await?;
+            return Ok(None);
+        }
+        self.next_reply += 1;
+        let line = format!("PUB {} {}.ack.{} 4\r\n+ACK\r\n", ack_subject, self.inbox, self.next_reply);
+        self.write(line.as_bytes()).await?;
+        self.unconfirmed.insert(self.next_reply, (payload, Instant::now()));
+        Ok(None)
+    }
+
+    /// Sends a JetStream API request and returns its JSON reply.
+    async fn request(&mut self, subject: &str, body: &[u8]) -> io::Result<Value> {
+        self.next_reply += 1;
+        let reply = format!("{}.api.{}", self.inbox, self.next_reply);
+        let mut line = format!("PUB {} {} {}\r\n", subject, reply, body.len()).into_bytes();
+        line.extend_from_slice(body);
+        line.extend_from_slice(b"\r\n");
+        self.write(&line).await?;
+        let deadline = Instant::now() + REQUEST_TIMEOUT;
+        loop {
+            match timeout_at(deadline, self.receive()).await.map_err(|_| timed_out(subject))?? {
+                Frame::Ping => self.write(b"PONG\r\n").await?,
+                Frame::Message { subject: to, status: Some(503), .. } if to == reply => {
+                    return Err(io::Error::other("JetStream is not enabled on the server"));
+                }
+                Frame::Message { subject: to, payload, .. } if to == reply => {
+                    return serde_json::from_slice(&payload).map_err(|_| protocol("malformed JetStream API reply"));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    async fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.stream.write_all(bytes).await?;
+        self.stream.flush().await
+    }
+
+    /// Reads the next frame. Cancelling it keeps what was read for the
+    /// next call.
+    async fn receive(&mut self) -> io::Result<Frame> {
+        loop {
+            if let Some(frame) = self.frame()? {
+                self.last_received = Instant::now();
+                self.ping_sent = false;
+                return Ok(frame);
+            }
+            let mut chunk = Zeroizing::new(vec![0u8; 16 * 1024]);
+            let n = self.stream.read(&mut chunk).await?;
+            if n == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "server closed the connection"));
+            }
+            self.buffer.extend_from_slice(&chunk[..n]);
+        }
+    }
+
+    /// Takes the first complete frame out of `buffer`, if there is one.
+    fn frame(&mut self) -> io::Result<Option<Frame>> {
+        let Some(eol) = self.buffer.windows(2).position(|w| w == b"\r\n") else {
+            if self.buffer.len() > self.max_frame {
+                return Err(protocol("control line too long"));
+            }
+            return Ok(None);
+        };
+        let line = String::from_utf8_lossy(&self.buffer[..eol]).into_owned();
+        let mut words = line.split_ascii_whitespace();
+        let op = words.next().unwrap_or_default().to_ascii_uppercase();
+        let args: Vec<&str> = words.collect();
+        let (frame, len) = match op.as_str() {
+            "MSG" | "HMSG" => {
+                // MSG <subject> <sid> [reply] <size>, HMSG <subject> <sid> [reply] <header size> <size>
+                let sizes = if op == "HMSG" { 2 } else { 1 };
+                if !(args.len() == 2 + sizes || args.len() == 3 + sizes) {
+                    return Err(protocol("malformed MSG"));
+                }
+                let numbers: Vec<usize> = args[args.len() - sizes..].iter().map(|n| n.parse()).collect::<Result<_, _>>().map_err(|_| protocol("malformed MSG size"))?;
+                let (header_len, total) = if sizes == 2 { (numbers[0], numbers[1]) } else { (0, numbers[0]) };
+                if total > self.max_frame || header_len > total {
+                    return Err(protocol("message larger than the server's max_payload"));
+                }
+                let start = eol + 2;
+                if self.buffer.len() < start + total + 2 {
+                    return Ok(None);
+                }
+                let body = &self.buffer[start..start + total];
+                let status = match header_len {
+                    0 => None,
+                    _ => String::from_utf8_lossy(&body[..header_len]).lines().next().and_then(|l| l.split_ascii_whitespace().nth(1)?.parse().ok()),
+                };
+                let frame = Frame::Message {
+                    subject: args[0].to_string(),
+                    reply: (args.len() == 3 + sizes).then(|| args[2].to_string()),
+                    status,
+                    payload: Zeroizing::new(body[header_len..].to_vec()),
+                };
+                (frame, start + total + 2)
+            }
+            "PING" => (Frame::Ping, eol + 2),
+            "PONG" => (Frame::Pong, eol + 2),
+            "-ERR" => return Err(io::Error::other(format!("server error: {}", line[4..].trim().trim_matches('\'')))),
+            _ => (Frame::Other, eol + 2),
+        };
+        self.buffer.drain(..len);
+        Ok(Some(frame))
+    }
+}
+
+fn protocol(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("NATS protocol error: {}", what))
+}
+
+fn timed_out(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, format!("no reply to {} from the server", what))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_server() {
+        assert_eq!(parse_server("nats://nats.lan").unwrap(), Server { host: "nats.lan".to_string(), port: 4222 });
+        assert_eq!(parse_server("nats://[::1]:4333/").unwrap(), Server { host: "::1".to_string(), port: 4333 });
+        assert!(parse_server("nats://user:pw@nats.lan").is_err());
+        assert!(parse_server("tls://nats.lan").is_err());
+        assert!(check_name("stream", "ENTROPY").is_ok());
+        assert!(check_name("consumer", "trng.dbus").is_err());
+    }
+}
@@ -0,0 +1,384 @@
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::c_ulong;
+use std::os::unix::fs::PermissionsExt;
+use zeroize::Zeroizing;
+
+// The handful of Cryptoki 2.40 definitions this service needs, bound by hand
+// against the module's `C_GetFunctionList`. The `cryptoki` crate would cover
+// them, but it (with `cryptoki-sys` and `libloading`) is not among the
+// vendored dependencies this service builds from offline, so it has to wait
+// until it is. Until then every unsafe call is made by `Module`, which only
+// lends `Token` safe methods; the rest of this file and its callers are safe
+// code. `cargo test` runs sessions against a stub module (`pkcs11_stub.c`)
+// built with the system C compiler, and skips them on hosts without one.
+type Rv = c_ulong;
+
+const CKR_OK: Rv = 0;
+const CKR_USER_ALREADY_LOGGED_IN: Rv = 0x100;
+const CKR_CRYPTOKI_ALREADY_INITIALIZED: Rv = 0x191;
+
+const CKF_OS_LOCKING_OK: c_ulong = 0x2;
+const CKF_SERIAL_SESSION: c_ulong = 0x4;
+const CKF_RNG: c_ulong = 0x1;
+const CKU_USER: c_ulong = 1;
+
+/// Positions in `CK_FUNCTION_LIST`, which has 68 entries after its version.
+const C_INITIALIZE: usize = 0;
+const C_GET_TOKEN_INFO: usize = 6;
+const C_OPEN_SESSION: usize = 12;
+const C_CLOSE_SESSION: usize = 13;
+const C_LOGIN: usize = 18;
+const C_GENERATE_RANDOM: usize = 64;
+
+/// Largest single `C_GenerateRandom` call. Some HSMs reject larger ones, and
+/// the calls of a network HSM are cheap enough that nothing is lost.
+const GENERATE_CALL_BYTES: usize = 4096;
+
+#[repr(C)]
+struct FunctionList {
+    version: [u8; 2],
+    functions: [*const c_void; 68],
+}
+
+#[repr(C)]
+struct InitializeArgs {
+    create_mutex: *const c_void,
+    destroy_mutex: *const c_void,
+    lock_mutex: *const c_void,
+    unlock_mutex: *const c_void,
+    flags: c_ulong,
+    reserved: *mut c_void,
+}
+
+#[repr(C)]
+struct TokenInfo {
+    label: [u8; 32],
+    manufacturer_id: [u8; 32],
+    model: [u8; 16],
+    serial_number: [u8; 16],
+    flags: c_ulong,
+    /// Session counts, PIN lengths and memory sizes.
+    counts: [c_ulong; 10],
+    hardware_version: [u8; 2],
+    firmware_version: [u8; 2],
+    utc_time: [u8; 16],
+}
+
+type GetFunctionList = unsafe extern "C" fn(*mut *const FunctionList) -> Rv;
+type Initialize = unsafe extern "C" fn(*mut c_void) -> Rv;
+type GetTokenInfo = unsafe extern "C" fn(c_ulong, *mut TokenInfo) -> Rv;
+type OpenSession = unsafe extern "C" fn(c_ulong, c_ulong, *mut c_void, *const c_void, *mut c_ulong) -> Rv;
+type CloseSession = unsafe extern "C" fn(c_ulong) -> Rv;
+type Login = unsafe extern "C" fn(c_ulong, c_ulong, *const u8, c_ulong) -> Rv;
+type GenerateRandom = unsafe extern "C" fn(c_ulong, *mut u8, c_ulong) -> Rv;
+
+/// Name of a `CKR_` code a token is likely to answer with while serving
+/// random bytes, for logs.
+fn rv_name(rv: Rv) -> String {
+    let name = match rv {
+        0x3 => "CKR_SLOT_ID_INVALID",
+        0x5 => "CKR_GENERAL_ERROR",
+        0x30 => "CKR_DEVICE_ERROR",
+        0x31 => "CKR_DEVICE_MEMORY",
+        0x32 => "CKR_DEVICE_REMOVED",
+        0x54 => "CKR_FUNCTION_NOT_SUPPORTED",
+        0xa0 => "CKR_PIN_INCORRECT",
+        0xa4 => "CKR_PIN_LOCKED",
+        0xb0 => "CKR_SESSION_CLOSED",
+        0xb3 => "CKR_SESSION_HANDLE_INVALID",
+        0xe0 => "CKR_TOKEN_NOT_PRESENT",
+        0xe1 => "CKR_TOKEN_NOT_RECOGNIZED",
+        0x101 => "CKR_USER_NOT_LOGGED_IN",
+        0x121 => "CKR_RANDOM_NO_RNG",
+        0x190 => "CKR_CRYPTOKI_NOT_INITIALIZED",
+        _ => return format!("CKR 0x{:x}", rv),
+    };
+    name.to_string()
+}
+
+fn check(function: &str, rv: Rv) -> Result<(), String> {
+    match rv {
+        CKR_OK => Ok(()),
+        rv => Err(format!("{} failed: {}", function, rv_name(rv))),
+    }
+}
+
+/// A blank-padded `CK_UTF8CHAR` field as a string.
+fn padded(field: &[u8]) -> String {
+    String::from_utf8_lossy(field).trim_end_matches([' ', '\0']).to_string()
+}
+
+/// Reads the user PIN from `path`, without its trailing newline.
+pub fn read_pin(path: &str) -> Result<Zeroizing<Vec<u8>>, String> {
+    let meta = std::fs::metadata(path).map_err(|e| format!("{}: {}", path, e))?;
+    if meta.permissions().mode() & 0o077 != 0 {
+        log::warn!("PIN file {} is readable by other users", path);
+    }
+    let mut pin = Zeroizing::new(std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?);
+    while pin.last().is_some_and(|&b| b == b'\n' || b == b'\r') {
+        pin.pop();
+    }
+    Ok(pin)
+}
+
+/// A loaded and initialized PKCS#11 module. It is never unloaded or
+/// finalized: sources on several slots of one module share the library, and
+/// `C_Initialize` again only reports it as initialized already. Its methods
+/// are the only calls into the module.
+struct Module {
+    path: String,
+    functions: *const FunctionList,
+}
+
+// SAFETY: modules are initialized with CKF_OS_LOCKING_OK, and the function
+// list is static data of the library, which stays loaded
+unsafe impl Send for Module {}
+unsafe impl Sync for Module {}
+
+impl Module {
+    fn load(path: &str) -> Result<Self, String> {
+        let cpath = CString::new(path).map_err(|_| format!("{}: path contains NUL", path))?;
+        // SAFETY: loading a library runs its constructors, which is what the
+        // configured module is trusted to do
+        let handle = unsafe { libc::dlopen(cpath.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+        if handle.is_null() {
+            // SAFETY: dlerror returns NULL or a NUL-terminated message
+            let reason = unsafe { libc::dlerror() };
+            if reason.is_null() {
+                return Err(format!("cannot load {}", path));
+            }
+            return Err(unsafe { CStr::from_ptr(reason) }.to_string_lossy().into_owned());
+        }
+        // SAFETY: `handle` is a library loaded above and never unloaded
+        let symbol = unsafe { libc::dlsym(handle, c"C_GetFunctionList".as_ptr()) };
+        if symbol.is_null() {
+            return Err(format!("{} is not a PKCS#11 module (no C_GetFunctionList)", path));
+        }
+        // SAFETY: C_GetFunctionList has this signature in every Cryptoki version
+        let get_function_list: GetFunctionList = unsafe { std::mem::transmute(symbol) };
+        let mut functions = std::ptr::null();
+        check("C_GetFunctionList", unsafe { get_function_list(&mut functions) })?;
+        if functions.is_null() {
+            return Err(format!("{} returned no function list", path));
+        }
+        let module = Self { path: path.to_string(), functions };
+
+        let initialize: Initialize = module.function(C_INITIALIZE, "C_Initialize")?;
+        let mut args = InitializeArgs {
+            create_mutex: std::ptr::null(),
+            destroy_mutex: std::ptr::null(),
+            lock_mutex: std::ptr::null(),
+            unlock_mutex: std::ptr::null(),
+            flags: CKF_OS_LOCKING_OK,
+            reserved: std::ptr::null_mut(),
+        };
+        // SAFETY: `args` outlives the call, which only reads it
+        match unsafe { initialize(&mut args as *mut InitializeArgs as *mut c_void) } {
+            CKR_OK | CKR_CRYPTOKI_ALREADY_INITIALIZED => Ok(module),
+            rv => Err(format!("C_Initialize failed: {}", rv_name(rv))),
+        }
+    }
+
+    fn token_info(&self, slot: c_ulong) -> Result<TokenInfo, String> {
+        let get_token_info: GetTokenInfo = self.function(C_GET_TOKEN_INFO, "C_GetTokenInfo")?;
+        // SAFETY: TokenInfo is plain bytes and integers, filled in by the call
+        let mut info: TokenInfo = unsafe { std::mem::zeroed() };
+        check("C_GetTokenInfo", unsafe { get_token_info(slot, &mut info) })?;
+        Ok(info)
+    }
+
+    fn open_session(&self, slot: c_ulong) -> Result<c_ulong, String> {
+        let open_session: OpenSession = self.function(C_OPEN_SESSION, "C_OpenSession")?;
+        let mut session = 0;
+        // SAFETY: no notification callback is passed, so the null application
+        // pointer is never handed back
+        check("C_OpenSession", unsafe {
+            open_session(slot, CKF_SERIAL_SESSION, std::ptr::null_mut(), std::ptr::null(), &mut session)
+        })?;
+        Ok(session)
+    }
+
+    /// Logs `session` in as the user; logged in already counts as success.
+    fn login(&self, session: c_ulong, pin: &[u8]) -> Result<(), String> {
+        let login: Login = self.function(C_LOGIN, "C_Login")?;
+        // SAFETY: the call reads `pin.len()` bytes of `pin`
+        match unsafe { login(session, CKU_USER, pin.as_ptr(), pin.len() as c_ulong) } {
+            CKR_OK | CKR_USER_ALREADY_LOGGED_IN => Ok(()),
+            rv => Err(format!("C_Login failed: {}", rv_name(rv))),
+        }
+    }
+
+    fn close_session(&self, session: c_ulong) -> Result<(), String> {
+        let close_session: CloseSession = self.function(C_CLOSE_SESSION, "C_CloseSession")?;
+        // SAFETY: takes only the session handle
+        check("C_CloseSession", unsafe { close_session(session) })
+    }
+
+    /// Fills `out` with one `C_GenerateRandom` call.
+    fn generate_random(&self, session: c_ulong, out: &mut [u8]) -> Result<(), String> {
+        let generate_random: GenerateRandom = self.function(C_GENERATE_RANDOM, "C_GenerateRandom")?;
+        // SAFETY: the call writes at most `out.len()` bytes to `out`
+        check("C_GenerateRandom", unsafe { generate_random(session, out.as_mut_ptr(), out.len() as c_ulong) })
+    }
+
+    /// Entry `index` of the function list, as the function type `F`.
+    fn function<F: Copy>(&self, index: usize, name: &str) -> Result<F, String> {
+        // SAFETY: `functions` points at a list of 68 entries, and callers
+        // pass the type matching `index`
+        let entry = unsafe { (*self.functions).functions[index] };
+        if entry.is_null() {
+            return Err(format!("{} does not implement {}", self.path, name));
+        }
+        Ok(unsafe { std::mem::transmute_copy(&entry) })
+    }
+}
+
+/// What the token in a slot says about itself.
+#[derive(Debug, Clone, Default)]
+pub struct Description {
+    pub label: String,
+    pub manufacturer: String,
+    pub model: String,
+    pub serial: String,
+    pub firmware: String,
+}
+
+/// A session on a token, logged in when it has a PIN.
+pub struct Token {
+    module: Module,
+    slot: c_ulong,
+    pin: Option<Zeroizing<Vec<u8>>>,
+    session: Option<c_ulong>,
+    description: Description,
+}
+
+impl Token {
+    /// Loads `module` and opens a session on `slot`, which must hold a token
+    /// with a random number generator.
+    pub fn open(module: &str, slot: u64, pin: Option<Zeroizing<Vec<u8>>>) -> Result<Self, String> {
+        let module = Module::load(module)?;
+        let slot = slot as c_ulong;
+        let info = module.token_info(slot)?;
+        if info.flags & CKF_RNG == 0 {
+            return Err(format!("the token in slot {} has no random number generator", slot));
+        }
+        let description = Description {
+            label: padded(&info.label),
+            manufacturer: padded(&info.manufacturer_id),
+            model: padded(&info.model),
+            serial: padded(&info.serial_number),
+            firmware: format!("{}.{}", info.firmware_version[0], info.firmware_version[1]),
+        };
+        let mut token = Self { module, slot, pin, session: None, description };
+        token.reopen()?;
+        Ok(token)
+    }
+
+    pub fn description(&self) -> &Description {
+        &self.description
+    }
+
+    /// Closes the session, if any, and opens and logs in a new one.
+    pub fn reopen(&mut self) -> Result<(), String> {
+        self.close();
+        let session = self.module.open_session(self.slot)?;
+        self.session = Some(session);
+        if let Some(pin) = &self.pin {
+            if let Err(e) = self.module.login(session, pin) {
+                self.close();
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Closes the session, which also logs out once it was the last one.
+    pub fn close(&mut self) {
+        let Some(session) = self.session.take() else { return };
+        if let Err(e) = self.module.close_session(session) {
+            log::warn!("PKCS#11 slot {} of {}: {}", self.slot, self.module.path, e);
+        }
+    }
+
+    /// `len` bytes from `C_GenerateRandom`. Fails without an open session, and
+    /// closes it when a call fails, so it is reopened before the next one.
+    pub fn generate(&mut self, len: usize) -> Result<Zeroizing<Vec<u8>>, String> {
+        let Some(session) = self.session else { return Err("no session".to_string()) };
+        let mut out = Zeroizing::new(vec![0u8; len]);
+        for chunk in out.chunks_mut(GENERATE_CALL_BYTES) {
+            if let Err(e) = self.module.generate_random(session, chunk) {
+                self.close();
+                return Err(e);
+            }
+        }
+        Ok(out)
+    }
+}
+
+impl Drop for Token {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_errors() {
+        let err = Token::open("/nonexistent/libpkcs11.so", 0, None).err().unwrap();
+        assert!(err.contains("/nonexistent/libpkcs11.so"), "{}", err);
+        // libc is loadable but no module
+        let err = Token::open("libc.so.6", 0, None).err().unwrap();
+        assert!(err.contains("no C_GetFunctionList"), "{}", err);
+        assert_eq!(rv_name(0x32), "CKR_DEVICE_REMOVED");
+        assert_eq!(rv_name(0x1234), "CKR 0x1234");
+        assert_eq!(padded(b"SoftHSM v2      "), "SoftHSM v2");
+    }
+
+    #[test]
+    fn test_read_pin() {
+        let path = std::env::temp_dir().join(format!("trng-pin-{}", std::process::id()));
+        std::fs::write(&path, "1234\r\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        assert_eq!(read_pin(path.to_str().unwrap()).unwrap().as_slice(), b"1234");
+        std::fs::remove_file(&path).unwrap();
+        assert!(read_pin(path.to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_stub_module() {
+        let dir = std::env::temp_dir().join(format!("trng-pkcs11-stub-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (source, library) = (dir.join("stub.c"), dir.join("libstub.so"));
+        std::fs::write(&source, include_str!("pkcs11_stub.c")).unwrap();
+        let built = match std::process::Command::new("cc").args(["-shared", "-fPIC", "-o"]).arg(&library).arg(&source).status() {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                eprintln!("test_stub_module skipped: no cc to build the stub module");
+                std::fs::remove_dir_all(&dir).unwrap();
+                return;
+            }
+            built => built.unwrap(),
+        };
+        assert!(built.success());
+        let module = library.to_str().unwrap();
+        let pin = |pin: &[u8]| Some(Zeroizing::new(pin.to_vec()));
+
+        let mut token = Token::open(module, 0, pin(b"1234")).unwrap();
+        assert_eq!((token.description().model.as_str(), token.description().firmware.as_str()), ("Stub v1", "2.1"));
+        // The stub counts from 0 in each call
+        let bytes = token.generate(GENERATE_CALL_BYTES + 2).unwrap();
+        assert_eq!(bytes[GENERATE_CALL_BYTES - 1..], [0xff, 0, 1]);
+
+        let err = |slot, pin| Token::open(module, slot, pin).err().unwrap();
+        assert_eq!(err(0, pin(b"0000")), "C_Login failed: CKR_PIN_INCORRECT");
+        assert_eq!(err(1, None), "the token in slot 1 has no random number generator");
+        // A failed call closes the session until it is reopened
+        let mut token = Token::open(module, 2, None).unwrap();
+        assert_eq!(token.generate(16).err().unwrap(), "C_GenerateRandom failed: CKR_DEVICE_REMOVED");
+        assert_eq!(token.generate(16).err().unwrap(), "no session");
+        token.reopen().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,98 @@
+/* A PKCS#11 module just capable enough for the tests in pkcs11.rs: slot 0
+ * holds a token with PIN 1234, slot 1 one without an RNG, and the token in
+ * slot 2 fails every C_GenerateRandom. Random bytes count up from 0 in each
+ * call. */
+#include <string.h>
+
+typedef unsigned long CK_ULONG;
+
+#define CKR_OK 0x0UL
+#define CKR_SLOT_ID_INVALID 0x3UL
+#define CKR_DEVICE_REMOVED 0x32UL
+#define CKR_PIN_INCORRECT 0xa0UL
+#define CKR_SESSION_HANDLE_INVALID 0xb3UL
+#define CKR_CRYPTOKI_ALREADY_INITIALIZED 0x191UL
+#define CKF_RNG 0x1UL
+
+struct token_info {
+    unsigned char label[32], manufacturer_id[32], model[16], serial_number[16];
+    CK_ULONG flags, counts[10];
+    unsigned char hardware_version[2], firmware_version[2], utc_time[16];
+};
+
+struct function_list {
+    unsigned char version[2];
+    void *functions[68];
+};
+
+static int initialized;
+
+static CK_ULONG initialize(void *args) {
+    (void)args;
+    if (initialized)
+        return CKR_CRYPTOKI_ALREADY_INITIALIZED;
+    initialized = 1;
+    return CKR_OK;
+}
+
+static void pad(unsigned char *field, size_t len, const char *text) {
+    memset(field, ' ', len);
+    memcpy(field, text, strlen(text));
+}
+
+static CK_ULONG get_token_info(CK_ULONG slot, struct token_info *info) {
+    if (slot > 2)
+        return CKR_SLOT_ID_INVALID;
+    memset(info, 0, sizeof *info);
+    pad(info->label, sizeof info->label, "stub");
+    pad(info->manufacturer_id, sizeof info->manufacturer_id, "trng-dbus");
+    pad(info->model, sizeof info->model, "Stub v1");
+    pad(info->serial_number, sizeof info->serial_number, "0001");
+    info->flags = slot == 1 ? 0 : CKF_RNG;
+    info->firmware_version[0] = 2;
+    info->firmware_version[1] = 1;
+    return CKR_OK;
+}
+
+/* Sessions are numbered by slot, from 1 */
+static CK_ULONG open_session(CK_ULONG slot, CK_ULONG flags, void *app, const void *notify, CK_ULONG *session) {
+    (void)flags, (void)app, (void)notify;
+    if (slot > 2)
+        return CKR_SLOT_ID_INVALID;
+    *session = slot + 1;
+    return CKR_OK;
+}
+
+static CK_ULONG close_session(CK_ULONG session) {
+    return session >= 1 && session <= 3 ? CKR_OK : CKR_SESSION_HANDLE_INVALID;
+}
+
+static CK_ULONG login(CK_ULONG session, CK_ULONG user, const unsigned char *pin, CK_ULONG len) {
+    (void)session, (void)user;
+    return len == 4 && memcmp(pin, "1234", 4) == 0 ? CKR_OK : CKR_PIN_INCORRECT;
+}
+
+static CK_ULONG generate_random(CK_ULONG session, unsigned char *out, CK_ULONG len) {
+    if (session == 3)
+        return CKR_DEVICE_REMOVED;
+    for (CK_ULONG i = 0; i < len; i++)
+        out[i] = (unsigned char)i;
+    return CKR_OK;
+}
+
+static struct function_list functions = {
+    .version = {2, 40},
+    .functions = {
+        [0] = initialize,
+        [6] = get_token_info,
+        [12] = open_session,
+        [13] = close_session,
+        [18] = login,
+        [64] = generate_random,
+    },
+};
+
+CK_ULONG C_GetFunctionList(struct function_list **list) {
+    *list = &functions;
+    return CKR_OK;
+}
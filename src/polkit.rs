@@ -0,0 +1,77 @@
+use crate::error::Error;
+use std::collections::HashMap;
+use tokio::sync::OnceCell;
+use zbus::zvariant::Value;
+use zbus::Connection;
+
+const AUTHORITY: &str = "org.freedesktop.PolicyKit1";
+const AUTHORITY_PATH: &str = "/org/freedesktop/PolicyKit1/Authority";
+const AUTHORITY_INTERFACE: &str = "org.freedesktop.PolicyKit1.Authority";
+
+/// `CheckAuthorization` flag letting polkit ask the user to authenticate.
+const ALLOW_USER_INTERACTION: u32 = 1;
+
+/// What a privileged method asks polkit to authorize, as declared in
+/// `docs/lv.lumii.trng.policy`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    /// `CaptureRawSample`.
+    Capture,
+    /// `SetSourceBufferSize`, `SetSourceTuning` and `Promote`.
+    Manage,
+}
+
+impl Action {
+    pub fn id(self) -> &'static str {
+        match self {
+            Action::Capture => "lv.lumii.trng.capture",
+            Action::Manage => "lv.lumii.trng.manage",
+        }
+    }
+}
+
+/// The system bus, where polkit is, whichever bus the service is on.
+static SYSTEM_BUS: OnceCell<Connection> = OnceCell::const_new();
+
+/// A `unix-process` subject for the caller's `pid` and `uid` as the bus
+/// daemon vouched for them; a start time of 0 has polkit look it up.
+fn subject(pid: u32, uid: u32) -> (&'static str, HashMap<&'static str, Value<'static>>) {
+    let details = HashMap::from([("pid", Value::from(pid)), ("start-time", Value::from(0u64)), ("uid", Value::from(uid as i32))]);
+    ("unix-process", details)
+}
+
+/// Asks polkit whether the process `pid` of `uid` may perform `action`,
+/// letting it prompt for authentication if the caller allowed that.
+/// Fails with `Unexpected` when polkit cannot be asked.
+pub async fn is_authorized(pid: u32, uid: u32, action: Action, interactive: bool) -> Result<bool, Error> {
+    let unreachable = |e: zbus::Error| {
+        log::error!("Cannot ask polkit to authorize {}: {}", action.id(), e);
+        Error::Unexpected
+    };
+    let connection = SYSTEM_BUS.get_or_try_init(Connection::system).await.map_err(unreachable)?;
+    let flags = if interactive { ALLOW_USER_INTERACTION } else { 0 };
+    let args = (subject(pid, uid), action.id(), HashMap::<&str, &str>::new(), flags, "");
+    let reply = connection
+        .call_method(Some(AUTHORITY), AUTHORITY_PATH, Some(AUTHORITY_INTERFACE), "CheckAuthorization", &args)
+        .await
+        .map_err(unreachable)?;
+    let (authorized, _challenge, _details): (bool, bool, HashMap<String, String>) =
+        reply.body().deserialize().map_err(unreachable)?;
+    Ok(authorized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zbus::zvariant::Type;
+
+    #[test]
+    fn test_subject() {
+        let (kind, details) = subject(1234, 1000);
+        assert_eq!(kind, "unix-process");
+        assert_eq!(details["pid"], Value::from(1234u32));
+        assert_eq!(details["uid"], Value::from(1000i32));
+        assert_eq!(<(&str, HashMap<&str, Value>) as Type>::SIGNATURE, "(sa{sv})");
+        assert_eq!(Action::Manage.id(), "lv.lumii.trng.manage");
+    }
+}
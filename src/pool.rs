@@ -0,0 +1,287 @@
+use crate::config::PoolKind;
+use crate::fortuna::{self, Fortuna};
+use crate::sha3::{self, RATE};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use zeroize::{Zeroize, Zeroizing};
+
+/// Most credited bytes the sponge holds. The background absorber keeps it
+/// topped up to this; larger requests absorb what they miss as they go.
+pub const MAX_CREDIT: u64 = 64 * 1024;
+
+/// How often the background absorber feeds the pool.
+pub const ABSORB_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Domain bits closing an absorbing phase, as SHAKE's, so squeezed output
+/// is never a SHA3-256 digest of what was absorbed.
+const PAD: u64 = 0x1f;
+
+/// `pool = "keccak"`: a Keccak-f[1600] sponge with SHA3-256's rate that
+/// every read from the sources is absorbed into and requests squeeze from.
+/// Each read credits the pool with its assessed entropy (`entropy_per_byte`)
+/// and each squeezed byte debits one, so the pool never serves more than it
+/// took in. Every squeeze ends by zeroing the rate and permuting, so a view
+/// of the state later on does not give away output already served.
+pub struct SpongePool {
+    sponge: Mutex<Sponge>,
+    /// Bytes absorbed from each source.
+    absorbed: Vec<AtomicU64>,
+    squeezed: AtomicU64,
+}
+
+struct Sponge {
+    state: [u64; 25],
+    /// Position in the rate of the next byte absorbed or squeezed.
+    pos: usize,
+    squeezing: bool,
+    /// Credited bytes not squeezed yet.
+    credit: u64,
+}
+
+impl Drop for Sponge {
+    fn drop(&mut self) {
+        self.state.zeroize();
+    }
+}
+
+impl Sponge {
+    fn absorb(&mut self, data: &[u8]) {
+        if self.squeezing {
+            // The state was permuted when the last squeeze ended
+            self.squeezing = false;
+            self.pos = 0;
+        }
+        for &byte in data {
+            self.state[self.pos / 8] ^= (byte as u64) << (8 * (self.pos % 8));
+            self.pos += 1;
+            if self.pos == RATE {
+                sha3::keccak_f(&mut self.state);
+                self.pos = 0;
+            }
+        }
+    }
+
+    fn squeeze(&mut self, out: &mut [u8]) {
+        if !self.squeezing {
+            self.state[self.pos / 8] ^= PAD << (8 * (self.pos % 8));
+            self.state[(RATE - 1) / 8] ^= 0x80u64 << (8 * ((RATE - 1) % 8));
+            sha3::keccak_f(&mut self.state);
+            self.pos = 0;
+            self.squeezing = true;
+        }
+        for byte in out {
+            if self.pos == RATE {
+                sha3::keccak_f(&mut self.state);
+                self.pos = 0;
+            }
+            *byte = (self.state[self.pos / 8] >> (8 * (self.pos % 8))) as u8;
+            self.pos += 1;
+        }
+        self.state[..RATE / 8].zeroize();
+        sha3::keccak_f(&mut self.state);
+        self.pos = 0;
+    }
+}
+
+impl SpongePool {
+    pub fn new(sources: usize) -> Self {
+        Self {
+            sponge: Mutex::new(Sponge { state: [0; 25], pos: 0, squeezing: false, credit: 0 }),
+            absorbed: (0..sources).map(|_| AtomicU64::new(0)).collect(),
+            squeezed: AtomicU64::new(0),
+        }
+    }
+
+    fn sponge(&self) -> MutexGuard<'_, Sponge> {
+        self.sponge.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Absorbs `data` read from source `source`, crediting `entropy_per_byte`
+    /// bits for each byte up to `MAX_CREDIT`. Returns the bytes credited.
+    pub fn absorb(&self, source: usize, data: &[u8], entropy_per_byte: f64) -> u64 {
+        let mut sponge = self.sponge();
+        // Framed, so bytes cannot be moved between reads or sources
+        sponge.absorb(&(source as u32).to_le_bytes());
+        sponge.absorb(&(data.len() as u64).to_le_bytes());
+        sponge.absorb(data);
+        let before = sponge.credit;
+        sponge.credit = before.saturating_add((data.len() as f64 * entropy_per_byte / 8.0) as u64).min(MAX_CREDIT);
+        self.absorbed[source].fetch_add(data.len() as u64, Ordering::Relaxed);
+        sponge.credit - before
+    }
+
+    /// Squeezes up to `max` bytes, as many as the pool holds credit for.
+    pub fn squeeze(&self, max: usize) -> Zeroizing<Vec<u8>> {
+        let mut sponge = self.sponge();
+        let n = max.min(sponge.credit.try_into().unwrap_or(usize::MAX));
+        let mut out = Zeroizing::new(vec![0; n]);
+        if n > 0 {
+            sponge.squeeze(&mut out);
+            sponge.credit -= n as u64;
+            self.squeezed.fetch_add(n as u64, Ordering::Relaxed);
+        }
+        out
+    }
+
+    pub fn credit(&self) -> u64 {
+        self.sponge().credit
+    }
+
+    pub fn squeezed(&self) -> u64 {
+        self.squeezed.load(Ordering::Relaxed)
+    }
+
+    pub fn absorbed(&self, source: usize) -> u64 {
+        self.absorbed[source].load(Ordering::Relaxed)
+    }
+
+    /// Zeroizes the state and drops all credit, at shutdown.
+    pub fn wipe(&self) {
+        let mut sponge = self.sponge();
+        sponge.state.zeroize();
+        sponge.credit = 0;
+    }
+}
+
+/// What `pool = "..."` serves requests from.
+pub enum Pool {
+    Sponge(SpongePool),
+    Fortuna(Mutex<Fortuna>),
+}
+
+impl Pool {
+    pub fn new(kind: PoolKind, sources: usize) -> Option<Self> {
+        match kind {
+            PoolKind::None => None,
+            PoolKind::Keccak => Some(Pool::Sponge(SpongePool::new(sources))),
+            PoolKind::Fortuna => Some(Pool::Fortuna(Mutex::new(Fortuna::new(sources)))),
+        }
+    }
+
+    fn fortuna(fortuna: &Mutex<Fortuna>) -> MutexGuard<'_, Fortuna> {
+        fortuna.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Adds a read from `source`. Returns the bytes it lets requests squeeze:
+    /// what the sponge credited, or all of them for Fortuna, whose output is
+    /// not bounded by an entropy estimate.
+    pub fn absorb(&self, source: usize, data: &[u8], entropy_per_byte: f64) -> u64 {
+        match self {
+            Pool::Sponge(sponge) => sponge.absorb(source, data, entropy_per_byte),
+            Pool::Fortuna(fortuna) => {
+                Self::fortuna(fortuna).add_events(source, data);
+                data.len() as u64
+            }
+        }
+    }
+
+    /// Up to `max` bytes: as many as the sponge has credit for, or all of
+    /// them once Fortuna's generator is seeded.
+    pub fn squeeze(&self, max: usize) -> Zeroizing<Vec<u8>> {
+        match self {
+            Pool::Sponge(sponge) => sponge.squeeze(max),
+            Pool::Fortuna(fortuna) => Self::fortuna(fortuna).generate(max, Instant::now()),
+        }
+    }
+
+    /// Bytes to read from each source for a request still `missing` bytes.
+    pub fn read_size(&self, missing: usize) -> usize {
+        match self {
+            Pool::Sponge(_) => missing.min(MAX_CREDIT as usize),
+            Pool::Fortuna(_) => fortuna::SEED_READ,
+        }
+    }
+
+    /// Bytes to read from each source on a background tick; 0 for none.
+    pub fn feed_size(&self) -> usize {
+        match self {
+            Pool::Sponge(sponge) => MAX_CREDIT.saturating_sub(sponge.credit()) as usize,
+            Pool::Fortuna(_) => fortuna::FEED_BYTES,
+        }
+    }
+
+    pub fn absorbed(&self, source: usize) -> u64 {
+        match self {
+            Pool::Sponge(sponge) => sponge.absorbed(source),
+            Pool::Fortuna(fortuna) => Self::fortuna(fortuna).absorbed(source),
+        }
+    }
+
+    /// Indices of the sources that have fed the pool.
+    pub fn contributors(&self, sources: usize) -> Vec<usize> {
+        (0..sources).filter(|&i| self.absorbed(i) > 0).collect()
+    }
+
+    /// Fill levels and counters by name: the sponge's "credit_bytes",
+    /// "max_credit_bytes" and "squeezed_bytes", or Fortuna's (see there).
+    pub fn stats(&self) -> Vec<(String, u64)> {
+        match self {
+            Pool::Sponge(sponge) => vec![
+                ("credit_bytes".to_string(), sponge.credit()),
+                ("max_credit_bytes".to_string(), MAX_CREDIT),
+                ("squeezed_bytes".to_string(), sponge.squeezed()),
+            ],
+            Pool::Fortuna(fortuna) => Self::fortuna(fortuna).stats(),
+        }
+    }
+
+    /// As reported in provenance.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Pool::Sponge(_) => "keccak-pool",
+            Pool::Fortuna(_) => "fortuna-aes256",
+        }
+    }
+
+    /// Whether every byte served is backed by credited entropy.
+    pub fn full_credit(&self) -> bool {
+        matches!(self, Pool::Sponge(_))
+    }
+
+    /// Zeroizes the state, at shutdown.
+    pub fn wipe(&self) {
+        match self {
+            Pool::Sponge(sponge) => sponge.wipe(),
+            Pool::Fortuna(fortuna) => Self::fortuna(fortuna).wipe(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_credit_accounting() {
+        let pool = SpongePool::new(2);
+        assert!(pool.squeeze(16).is_empty());
+        assert_eq!(pool.absorb(0, &[7; 100], 4.0), 50);
+        assert_eq!(pool.absorb(1, &[9; 10], 8.0), 10);
+        assert_eq!(pool.squeeze(40).len(), 40);
+        assert_eq!(pool.squeeze(40).len(), 20);
+        assert!(pool.squeeze(1).is_empty());
+        assert_eq!((pool.squeezed(), pool.absorbed(0), pool.absorbed(1)), (60, 100, 10));
+
+        // Credit stops at the cap, however much is absorbed
+        assert_eq!(pool.absorb(0, &vec![1; MAX_CREDIT as usize + 10], 8.0), MAX_CREDIT);
+        assert_eq!(pool.absorb(0, &[1], 8.0), 0);
+    }
+
+    #[test]
+    fn test_squeeze_framing() {
+        let squeezed = |reads: &[(usize, &[u8])]| {
+            let pool = SpongePool::new(2);
+            for (source, data) in reads {
+                pool.absorb(*source, data, 8.0);
+            }
+            let first = pool.squeeze(4).to_vec();
+            (first, pool.squeeze(4).to_vec())
+        };
+        let (first, second) = squeezed(&[(0, b"abcd"), (1, b"efgh")]);
+        assert_eq!((first.clone(), second.clone()), squeezed(&[(0, b"abcd"), (1, b"efgh")]));
+        assert_ne!(first, second);
+        assert_ne!(first, squeezed(&[(0, b"abcdefgh")]).0);
+        assert_ne!(first, squeezed(&[(1, b"abcd"), (0, b"efgh")]).0);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::config::QuotaConfig;
+use crate::error::Error;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// The window `bytes_per_day` counts over, from a client's first request.
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// What one client has used: a token bucket for `bytes_per_second` and the
+/// bytes served in its current day.
+struct Usage {
+    tokens: f64,
+    updated: Instant,
+    day_started: Instant,
+    day_bytes: u64,
+}
+
+/// `[quotas]`: caps on the bytes each D-Bus client (by unique name) is
+/// served, across every endpoint. Unlike a group's shaper, which delays
+/// requests, a request over a quota fails at once with `RateLimited`.
+pub struct ClientQuotas {
+    rate: Option<f64>,
+    burst: f64,
+    per_day: Option<u64>,
+    clients: Mutex<HashMap<String, Usage>>,
+}
+
+impl ClientQuotas {
+    pub fn new(cfg: &QuotaConfig) -> Self {
+        let rate = cfg.bytes_per_second.map(|rate| rate.max(1) as f64);
+        let burst = cfg.burst_bytes.or(cfg.bytes_per_second).unwrap_or(0).max(1) as f64;
+        Self { rate, burst, per_day: cfg.bytes_per_day, clients: Mutex::new(HashMap::new()) }
+    }
+
+    /// Charges `bytes` to `client`, or fails with `RateLimited` (charging
+    /// nothing) if its bucket holds less than them, up to a full bucket for
+    /// larger requests, or they would take it past `bytes_per_day`.
+    pub fn admit(&self, client: &str, bytes: usize) -> Result<(), Error> {
+        self.admit_at(client, bytes, Instant::now())
+    }
+
+    fn admit_at(&self, client: &str, bytes: usize, now: Instant) -> Result<(), Error> {
+        let mut clients = self.lock();
+        let usage = clients
+            .entry(client.to_string())
+            .or_insert_with(|| Usage { tokens: self.burst, updated: now, day_started: now, day_bytes: 0 });
+        if now.saturating_duration_since(usage.day_started) >= DAY {
+            usage.day_started = now;
+            usage.day_bytes = 0;
+        }
+        if let Some(rate) = self.rate {
+            let elapsed = now.saturating_duration_since(usage.updated).as_secs_f64();
+            usage.tokens = (usage.tokens + elapsed * rate).min(self.burst);
+            usage.updated = now;
+        }
+        let over_rate = self.rate.is_some() && usage.tokens < (bytes as f64).min(self.burst);
+        let over_day = self.per_day.is_some_and(|max| usage.day_bytes.saturating_add(bytes as u64) > max);
+        if over_rate || over_day {
+            return Err(Error::RateLimited);
+        }
+        if self.rate.is_some() {
+            usage.tokens -= bytes as f64;
+        }
+        usage.day_bytes += bytes as u64;
+        Ok(())
+    }
+
+    /// Gives back admitted bytes that were not served.
+    pub fn refund(&self, client: &str, bytes: usize) {
+        if let Some(usage) = self.lock().get_mut(client) {
+            usage.tokens = (usage.tokens + bytes as f64).min(self.burst);
+            usage.day_bytes = usage.day_bytes.saturating_sub(bytes as u64);
+        }
+    }
+
+    /// Forgets a client that left the bus.
+    pub fn remove_owner(&self, owner: &str) {
+        self.lock().remove(owner);
+    }
+
+    /// Forgets every client, when the bus goes away.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Usage>> {
+        self.clients.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_and_daily_quota() {
+        let quotas = ClientQuotas::new(&QuotaConfig { bytes_per_second: Some(1000), burst_bytes: Some(500), bytes_per_day: Some(3000) });
+        let now = Instant::now();
+        assert_eq!(quotas.admit_at(":1.1", 500, now), Ok(()));
+        assert_eq!(quotas.admit_at(":1.1", 1, now), Err(Error::RateLimited));
+        // Clients are charged separately
+        assert_eq!(quotas.admit_at(":1.2", 500, now), Ok(()));
+        // Larger than the burst: admitted on a full bucket, then in debt
+        let later = now + Duration::from_millis(500);
+        assert_eq!(quotas.admit_at(":1.1", 2000, later), Ok(()));
+        assert_eq!(quotas.admit_at(":1.1", 1, later + Duration::from_secs(1)), Err(Error::RateLimited));
+        // 2500 of 3000 used today; refunds count back
+        let much_later = later + Duration::from_secs(10);
+        assert_eq!(quotas.admit_at(":1.1", 501, much_later), Err(Error::RateLimited));
+        quotas.refund(":1.1", 1000);
+        assert_eq!(quotas.admit_at(":1.1", 500, much_later), Ok(()));
+        assert_eq!(quotas.admit_at(":1.1", 500, now + DAY), Ok(()));
+    }
+}
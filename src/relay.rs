@@ -0,0 +1,150 @@
+use crate::aggregator::Aggregator;
+use crate::config::{RelayConfig, ShapingConfig};
+use crate::scheduler::{self, Requester};
+use crate::shaping::Shaper;
+use crate::tls::TlsServer;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinSet;
+use tokio::time::{sleep, timeout, Duration, Instant};
+use zeroize::Zeroizing;
+
+/// Largest piece of output read and sent to a downstream at once.
+const CHUNK: usize = 4096;
+
+/// How long a downstream has for the TLS handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Per read of the combined output; a downstream gets what came meanwhile.
+const READ_TIMEOUT_MS: u64 = 1000;
+
+/// How far ahead the shapers may book a chunk before it is tried again.
+const ADMIT_HORIZON: Duration = Duration::from_secs(60);
+
+/// Serves a share of the combined output to downstream instances
+/// (`[relay]`). Each downstream connected over mutual TLS gets a stream of
+/// fresh output, paced by its own rate and by the rate across all of them;
+/// a downstream that stops reading is held back by TCP flow control.
+pub struct Relay {
+    listener: TcpListener,
+    tls: TlsServer,
+    total: Arc<Shaper>,
+    per_downstream: ShapingConfig,
+    max_downstreams: usize,
+    connected: Arc<AtomicUsize>,
+}
+
+impl Relay {
+    /// Loads the certificates and binds the listen address.
+    pub async fn bind(cfg: &RelayConfig) -> Result<Self, String> {
+        let tls = TlsServer::new(&cfg.cert_file, &cfg.key_file, &cfg.client_ca_file, &cfg.client_pin_sha256, cfg.expiry_warning_days)?;
+        let listener = TcpListener::bind(&cfg.listen).await.map_err(|e| format!("cannot listen on {}: {}", cfg.listen, e))?;
+        let rate = |bytes_per_second: Option<u64>, default: u64| ShapingConfig {
+            bytes_per_second: bytes_per_second.unwrap_or(default).max(1),
+            burst_bytes: None,
+        };
+        Ok(Self {
+            listener,
+            tls,
+            total: Arc::new(Shaper::new(&rate(cfg.max_bytes_per_second, 1024 * 1024))),
+            per_downstream: rate(cfg.max_bytes_per_second_per_downstream, 64 * 1024),
+            max_downstreams: cfg.max_downstreams.unwrap_or(16),
+            connected: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.listener.local_addr().ok()
+    }
+
+    /// Accepts downstreams until the task is stopped, which also ends
+    /// every delivery.
+    pub async fn run(self: Arc<Self>, aggregator: Arc<Aggregator>) {
+        let mut deliveries = JoinSet::new();
+        loop {
+            let (stream, peer) = tokio::select! {
+                accepted = self.listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        log::warn!("Relay: accept failed: {}", e);
+                        sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                },
+                Some(_) = deliveries.join_next() => continue,
+            };
+            if self.connected.load(Ordering::Relaxed) >= self.max_downstreams {
+                log::warn!("Relay: turning away {}, {} downstreams are connected", peer, self.max_downstreams);
+                continue;
+            }
+            self.connected.fetch_add(1, Ordering::Relaxed);
+            let (relay, aggregator) = (self.clone(), aggregator.clone());
+            deliveries.spawn(async move {
+                relay.deliver(aggregator, stream, peer).await;
+                relay.connected.fetch_sub(1, Ordering::Relaxed);
+            });
+        }
+    }
+
+    async fn deliver(&self, aggregator: Arc<Aggregator>, stream: TcpStream, peer: SocketAddr) {
+        let _ = stream.set_nodelay(true);
+        let (mut stream, fingerprint) = match timeout(HANDSHAKE_TIMEOUT, self.tls.accept(stream)).await {
+            Ok(Ok(accepted)) => accepted,
+            Ok(Err(e)) => {
+                log::warn!("Relay: TLS handshake with {} failed: {}", peer, e);
+                return;
+            }
+            Err(_) => {
+                log::warn!("Relay: no TLS handshake from {} within {:?}", peer, HANDSHAKE_TIMEOUT);
+                return;
+            }
+        };
+        // Short enough for logs, and what the scheduler knows the downstream by
+        let name = format!("relay:{}", &fingerprint[..16]);
+        log::info!("Relay: downstream {} connected from {}", name, peer);
+        let shaper = Shaper::new(&self.per_downstream);
+        let chunk = CHUNK.min(self.per_downstream.bytes_per_second as usize);
+        let requester = Requester { client: name.clone(), priority: 0, request_id: 0 };
+        scheduler::on_behalf_of(requester, async {
+            let mut sent = 0u64;
+            loop {
+                let horizon = Instant::now() + ADMIT_HORIZON;
+                let admitted = match shaper.admit(chunk, horizon).await {
+                    Ok(()) if self.total.admit(chunk, horizon).await.is_ok() => true,
+                    Ok(()) => {
+                        shaper.refund(chunk);
+                        false
+                    }
+                    Err(_) => false,
+                };
+                if !admitted {
+                    // Booked up past the horizon by the other downstreams
+                    sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+                let res = aggregator.read_bytes(chunk, READ_TIMEOUT_MS).await;
+                let unsent = chunk - res.as_ref().map_or(0, Vec::len);
+                shaper.refund(unsent);
+                self.total.refund(unsent);
+                let bytes = match res {
+                    Ok(bytes) => Zeroizing::new(bytes),
+                    Err(e) => {
+                        log::warn!("Relay: read for {} failed: {}", name, e);
+                        sleep(Duration::from_millis(READ_TIMEOUT_MS)).await;
+                        continue;
+                    }
+                };
+                // Bytes that do not make it out are lost with the connection, never sent twice
+                if let Err(e) = stream.write_all(&bytes).await {
+                    log::info!("Relay: downstream {} disconnected after {} bytes: {}", name, sent, e);
+                    return;
+                }
+                sent += bytes.len() as u64;
+            }
+        })
+        .await
+    }
+}
@@ -0,0 +1,187 @@
+use crate::clock::BootInstant;
+use crate::config::ReservationConfig;
+use crate::error::Error;
+use crate::memory;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+/// Combined bytes earmarked for one client on one endpoint.
+struct Earmark {
+    bytes: Zeroizing<Vec<u8>>,
+    /// On the boot clock, so time suspended counts towards the TTL.
+    since: BootInstant,
+    ttl: Duration,
+    /// Memory charged for each `Reserve` that added to it, until it is
+    /// used up or ends.
+    charges: Vec<memory::Reservation>,
+}
+
+/// `[reservations]`: combined output read ahead for a client by `Reserve`
+/// and held for it alone, keyed by its unique name and the endpoint's
+/// interface, until its own reads there consume it or the TTL passes. The
+/// bytes have left the sources, so other clients' reads can never get them;
+/// what expires is zeroized rather than handed to anyone else.
+pub struct Reservations {
+    max_per_client: usize,
+    max_total: usize,
+    max_ttl: Duration,
+    held: Mutex<HashMap<(String, String), Earmark>>,
+}
+
+impl Reservations {
+    pub fn new(cfg: &ReservationConfig) -> Self {
+        let limit = |bytes: Option<u64>, default: usize| bytes.map_or(default, |b| usize::try_from(b).unwrap_or(usize::MAX));
+        Self {
+            max_per_client: limit(cfg.max_bytes_per_client, 1024 * 1024),
+            max_total: limit(cfg.max_total_bytes, 16 * 1024 * 1024),
+            max_ttl: Duration::from_secs(cfg.max_ttl_seconds.unwrap_or(3600)),
+            held: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Checks that `owner` may reserve `bytes` more on `endpoint` for
+    /// `ttl_seconds`, before anything is read for it: `InvalidOption` for a
+    /// TTL of 0 or above `max_ttl_seconds`, `QuotaExceeded` past a limit.
+    pub fn admit(&self, owner: &str, endpoint: &str, bytes: usize, ttl_seconds: u64) -> Result<Duration, Error> {
+        let ttl = Duration::from_secs(ttl_seconds);
+        if ttl.is_zero() || ttl > self.max_ttl {
+            return Err(Error::InvalidOption("ttl_seconds".to_string()));
+        }
+        let mut held = self.lock();
+        Self::expire_in(&mut held);
+        self.fits(&held, owner, endpoint, bytes)?;
+        Ok(ttl)
+    }
+
+    fn fits(&self, held: &HashMap<(String, String), Earmark>, owner: &str, endpoint: &str, bytes: usize) -> Result<(), Error> {
+        let own = held.get(&key(owner, endpoint)).map_or(0, |e| e.bytes.len());
+        let total: usize = held.values().map(|e| e.bytes.len()).sum();
+        if own.saturating_add(bytes) > self.max_per_client || total.saturating_add(bytes) > self.max_total {
+            log::warn!("Refused reserving {} more bytes for {}: {} held by it, {} in all", bytes, owner, own, total);
+            return Err(Error::QuotaExceeded);
+        }
+        Ok(())
+    }
+
+    /// Adds `bytes` to what `owner` holds on `endpoint` and restarts its
+    /// TTL. Returns the bytes it now holds. Fails with `QuotaExceeded`,
+    /// zeroizing `bytes`, if concurrent reservations took the room meanwhile.
+    pub fn add(&self, owner: &str, endpoint: &str, bytes: Zeroizing<Vec<u8>>, ttl: Duration, charge: memory::Reservation) -> Result<usize, Error> {
+        let mut held = self.lock();
+        Self::expire_in(&mut held);
+        self.fits(&held, owner, endpoint, bytes.len())?;
+        let earmark = held.entry(key(owner, endpoint)).or_insert_with(|| Earmark {
+            bytes: Zeroizing::new(Vec::new()),
+            since: BootInstant::now(),
+            ttl,
+            charges: Vec::new(),
+        });
+        earmark.bytes.extend_from_slice(&bytes);
+        earmark.since = BootInstant::now();
+        earmark.ttl = ttl;
+        earmark.charges.push(charge);
+        log::info!("Reserved {} bytes for {} on {} for {:?}, {} held", bytes.len(), owner, endpoint, ttl, earmark.bytes.len());
+        Ok(earmark.bytes.len())
+    }
+
+    /// Takes up to `n` of the bytes `owner` holds on `endpoint`, oldest
+    /// first. A used-up reservation is no longer charged for.
+    pub fn take(&self, owner: &str, endpoint: &str, n: usize) -> Zeroizing<Vec<u8>> {
+        let mut held = self.lock();
+        Self::expire_in(&mut held);
+        let Some(earmark) = held.get_mut(&key(owner, endpoint)) else { return Zeroizing::new(Vec::new()) };
+        let n = n.min(earmark.bytes.len());
+        let taken = Zeroizing::new(earmark.bytes.drain(..n).collect::<Vec<u8>>());
+        if earmark.bytes.is_empty() {
+            earmark.charges.clear();
+        }
+        taken
+    }
+
+    /// Puts `bytes` taken from `owner`'s reservation on `endpoint` back in
+    /// front of it, as an exact read they were to start failed. Dropped if
+    /// the reservation has expired meanwhile.
+    pub fn restore(&self, owner: &str, endpoint: &str, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        let mut held = self.lock();
+        let Some(earmark) = held.get_mut(&key(owner, endpoint)) else { return };
+        let mut restored = Zeroizing::new(bytes.to_vec());
+        restored.extend_from_slice(&earmark.bytes);
+        earmark.bytes = restored;
+    }
+
+    /// Zeroizes reservations whose TTL has passed.
+    pub fn expire(&self) {
+        Self::expire_in(&mut self.lock());
+    }
+
+    fn expire_in(held: &mut HashMap<(String, String), Earmark>) {
+        held.retain(|(owner, endpoint), e| {
+            let live = e.since.elapsed() < e.ttl;
+            if !live && !e.bytes.is_empty() {
+                log::info!("Reservation of {} bytes for {} on {} expired - zeroized", e.bytes.len(), owner, endpoint);
+            }
+            live
+        });
+    }
+
+    /// Drops the reservations of a client that left the bus.
+    pub fn remove_owner(&self, owner: &str) {
+        self.lock().retain(|(o, _), _| o != owner);
+    }
+
+    /// Drops every reservation; used at shutdown and on reconnects.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<(String, String), Earmark>> {
+        self.held.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn key(owner: &str, endpoint: &str) -> (String, String) {
+    (owner.to_string(), endpoint.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::Accountant;
+
+    #[test]
+    fn test_reservations() {
+        let cfg = ReservationConfig { max_bytes_per_client: Some(100), max_total_bytes: Some(150), max_ttl_seconds: Some(60) };
+        let reservations = Reservations::new(&cfg);
+        let memory = Accountant::new(None);
+        let charge = || memory.reserve(0, "a reservation").unwrap();
+        assert_eq!(reservations.admit(":1.1", "rng", 10, 0), Err(Error::InvalidOption("ttl_seconds".to_string())));
+        assert_eq!(reservations.admit(":1.1", "rng", 10, 61), Err(Error::InvalidOption("ttl_seconds".to_string())));
+        let ttl = reservations.admit(":1.1", "rng", 80, 60).unwrap();
+        let held = reservations.add(":1.1", "rng", Zeroizing::new((0..80).collect()), ttl, charge()).unwrap();
+        assert_eq!(held, 80);
+        assert_eq!(reservations.admit(":1.1", "rng", 21, 60), Err(Error::QuotaExceeded));
+        // Per endpoint and client, within the total
+        reservations.add(":1.2", "rng", Zeroizing::new(vec![9; 60]), ttl, charge()).unwrap();
+        assert_eq!(reservations.admit(":1.1", "group", 11, 60), Err(Error::QuotaExceeded));
+
+        // Only the owner's reads on that endpoint get its bytes, oldest first
+        assert!(reservations.take(":1.3", "rng", 10).is_empty());
+        assert!(reservations.take(":1.1", "group", 10).is_empty());
+        assert_eq!(*reservations.take(":1.1", "rng", 30), (0..30).collect::<Vec<u8>>());
+        let rest = reservations.take(":1.1", "rng", 100);
+        assert_eq!(*rest, (30..80).collect::<Vec<u8>>());
+        reservations.restore(":1.1", "rng", &rest[..20]);
+        assert_eq!(*reservations.take(":1.1", "rng", 100), (30..50).collect::<Vec<u8>>());
+
+        reservations.add(":1.1", "rng", Zeroizing::new(vec![1; 10]), Duration::from_millis(1), charge()).unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(reservations.take(":1.1", "rng", 10).is_empty());
+        reservations.remove_owner(":1.2");
+        assert!(reservations.take(":1.2", "rng", 10).is_empty());
+    }
+}
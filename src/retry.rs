@@ -0,0 +1,130 @@
+use crate::config::RetryConfig;
+use crate::lrng::os_fill_rand_octets;
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::time::{sleep, Duration};
+
+/// Exponential backoff with jitter, shared by every source path that
+/// re-establishes access to its device (file reopen, reconnects, re-init).
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total attempts including the first one; `None` retries forever.
+    max_attempts: Option<u32>,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: f64,
+    /// Fraction of the delay that is randomized, 0.0-1.0.
+    jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::from(&RetryConfig::default())
+    }
+}
+
+impl From<&RetryConfig> for RetryPolicy {
+    fn from(cfg: &RetryConfig) -> Self {
+        Self {
+            max_attempts: match cfg.max_attempts {
+                Some(0) => None,
+                Some(n) => Some(n),
+                None => Some(5),
+            },
+            initial_backoff: Duration::from_millis(cfg.initial_backoff_ms.unwrap_or(100)),
+            max_backoff: Duration::from_millis(cfg.max_backoff_ms.unwrap_or(30_000)),
+            multiplier: cfg.multiplier.unwrap_or(2.0).max(1.0),
+            jitter: cfg.jitter.unwrap_or(0.2).clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn from_config(cfg: Option<&RetryConfig>) -> Self {
+        cfg.map(Self::from).unwrap_or_default()
+    }
+
+    /// Delay before retry number `attempt` (1-based).
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self.multiplier.powi(attempt.saturating_sub(1).min(63) as i32);
+        let base = (self.initial_backoff.as_secs_f64() * exp).min(self.max_backoff.as_secs_f64());
+        let spread = match os_fill_rand_octets(8) {
+            Ok(bytes) => {
+                let r = u64::from_le_bytes(bytes.try_into().unwrap_or([0; 8])) as f64 / u64::MAX as f64;
+                1.0 - self.jitter + 2.0 * self.jitter * r
+            }
+            Err(_) => 1.0,
+        };
+        Duration::from_secs_f64(base * spread)
+    }
+
+    /// Runs `op` until it succeeds or attempts are exhausted, counting each
+    /// retry in `retries`.
+    pub async fn run<T, E, F, Fut>(&self, what: &str, retries: &AtomicU64, mut op: F) -> Result<T, E>
+    where
+        E: Display,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut attempt = 0u32;
+        loop {
+            match op().await {
+                Ok(value) => return Ok(value),
+                Err(e) => {
+                    attempt = attempt.saturating_add(1);
+                    if self.max_attempts.is_some_and(|max| attempt >= max) {
+                        log::error!("{} failed after {} attempts: {}", what, attempt, e);
+                        return Err(e);
+                    }
+                    let delay = self.backoff(attempt);
+                    log::warn!("{} failed (attempt {}): {} - retrying in {:?}", what, attempt, e, delay);
+                    retries.fetch_add(1, Ordering::Relaxed);
+                    sleep(delay).await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_jitter(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy::from(&RetryConfig {
+            max_attempts: Some(max_attempts),
+            initial_backoff_ms: Some(1),
+            max_backoff_ms: Some(4),
+            multiplier: Some(2.0),
+            jitter: Some(0.0),
+        })
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let policy = no_jitter(3);
+        assert_eq!(policy.backoff(1), Duration::from_millis(1));
+        assert_eq!(policy.backoff(2), Duration::from_millis(2));
+        assert_eq!(policy.backoff(5), Duration::from_millis(4));
+    }
+
+    #[tokio::test]
+    async fn test_run_counts_retries() {
+        let retries = AtomicU64::new(0);
+        let mut calls = 0;
+        let res: Result<u32, String> = no_jitter(5)
+            .run("test op", &retries, || {
+                calls += 1;
+                let ok = calls == 3;
+                async move { if ok { Ok(7) } else { Err("nope".to_string()) } }
+            })
+            .await;
+        assert_eq!(res, Ok(7));
+        assert_eq!(retries.load(Ordering::Relaxed), 2);
+
+        let res: Result<(), String> = no_jitter(2).run("test op", &retries, || async { Err("nope".to_string()) }).await;
+        assert!(res.is_err());
+        assert_eq!(retries.load(Ordering::Relaxed), 3);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::affinity;
+use crate::config::{RuntimeConfig, RuntimeFlavor};
+use std::io;
+
+const USAGE: &str = "usage: trngdbus [--system] [--current-thread] [--worker-threads N] [--max-blocking-threads N]";
+
+/// Applies command line overrides (`--current-thread`, `--worker-threads N`,
+/// `--max-blocking-threads N`) on top of the `[runtime]` section.
+pub fn with_args(cfg: Option<&RuntimeConfig>, args: impl IntoIterator<Item = String>) -> Result<RuntimeConfig, String> {
+    let mut cfg = cfg.cloned().unwrap_or_default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut count = |name: &str| -> Result<usize, String> {
+            args.next()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0)
+                .ok_or_else(|| format!("{} needs a positive number\n{}", name, USAGE))
+        };
+        match arg.as_str() {
+            "--current-thread" => cfg.flavor = Some(RuntimeFlavor::CurrentThread),
+            "--worker-threads" => cfg.worker_threads = Some(count(&arg)?),
+            "--max-blocking-threads" => cfg.max_blocking_threads = Some(count(&arg)?),
+            _ => return Err(format!("unknown argument {}\n{}", arg, USAGE)),
+        }
+    }
+    Ok(cfg)
+}
+
+/// Builds the tokio runtime the service runs on. Unset values keep tokio's
+/// defaults (one worker per core, 512 blocking threads).
+pub fn build(cfg: &RuntimeConfig) -> io::Result<tokio::runtime::Runtime> {
+    let mut builder = match cfg.flavor.unwrap_or_default() {
+        RuntimeFlavor::MultiThread => {
+            let mut builder = tokio::runtime::Builder::new_multi_thread();
+            if let Some(n) = cfg.worker_threads.filter(|n| *n > 0) {
+                builder.worker_threads(n);
+            }
+            builder
+        }
+        RuntimeFlavor::CurrentThread => tokio::runtime::Builder::new_current_thread(),
+    };
+    if let Some(n) = cfg.max_blocking_threads.filter(|n| *n > 0) {
+        builder.max_blocking_threads(n);
+    }
+    if !cfg.cpus.is_empty() {
+        // The main thread drives `block_on` and, with `current_thread`, everything else
+        affinity::pin_or_warn(&cfg.cpus, "main thread");
+        let cpus = cfg.cpus.clone();
+        builder.on_thread_start(move || affinity::pin_or_warn(&cpus, "runtime thread"));
+    }
+    log::info!(
+        "Runtime: {:?}, {} worker threads, {} blocking threads, cpus {:?}",
+        cfg.flavor.unwrap_or_default(),
+        cfg.worker_threads.map_or("default".to_string(), |n| n.to_string()),
+        cfg.max_blocking_threads.map_or("default".to_string(), |n| n.to_string()),
+        cfg.cpus
+    );
+    builder.enable_all().build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_args_override_config() {
+        let file = RuntimeConfig { worker_threads: Some(4), max_blocking_threads: Some(16), ..Default::default() };
+        let cfg = with_args(Some(&file), args(&["--worker-threads", "2"])).unwrap();
+        assert_eq!(cfg.worker_threads, Some(2));
+        assert_eq!(cfg.max_blocking_threads, Some(16));
+
+        let cfg = with_args(None, args(&["--current-thread"])).unwrap();
+        assert_eq!(cfg.flavor, Some(RuntimeFlavor::CurrentThread));
+
+        assert!(with_args(None, args(&["--worker-threads", "0"])).is_err());
+        assert!(with_args(None, args(&["--worker-threads"])).is_err());
+        assert!(with_args(None, args(&["--verbose"])).is_err());
+    }
+}
@@ -0,0 +1,66 @@
+use std::f64::consts::TAU;
+
+/// Largest number of samples a single call may ask for.
+pub const MAX_SAMPLES: usize = 1 << 20;
+
+/// 2^-53, the spacing of the floats `uniform` produces.
+const SCALE: f64 = 1.0 / (1u64 << 53) as f64;
+
+/// Uniform float in [0, 1) from the top 53 bits of `bits`: every output is
+/// an exact multiple of 2^-53 and equally likely.
+pub fn uniform(bits: u64) -> f64 {
+    (bits >> 11) as f64 * SCALE
+}
+
+/// `bytes.len() / 8` uniform floats in [0, 1).
+pub fn uniforms(bytes: &[u8]) -> Vec<f64> {
+    bytes
+        .chunks_exact(8)
+        .map(|c| uniform(u64::from_le_bytes(c.try_into().unwrap())))
+        .collect()
+}
+
+/// Bytes needed for `count` normal samples (Box-Muller yields two per 16 bytes).
+pub fn gaussian_bytes(count: usize) -> usize {
+    count.div_ceil(2) * 16
+}
+
+/// `count` samples from N(mean, stddev^2) by the Box-Muller transform.
+pub fn gaussians(bytes: &[u8], count: usize, mean: f64, stddev: f64) -> Vec<f64> {
+    let mut out = Vec::with_capacity(count);
+    for pair in uniforms(bytes).chunks_exact(2) {
+        // 1 - u is in (0, 1], so the logarithm stays finite
+        let radius = (-2.0 * (1.0 - pair[0]).ln()).sqrt();
+        let angle = TAU * pair[1];
+        out.push(mean + stddev * radius * angle.cos());
+        out.push(mean + stddev * radius * angle.sin());
+    }
+    out.truncate(count);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uniform_range() {
+        assert_eq!(uniform(0), 0.0);
+        assert_eq!(uniform(u64::MAX), 1.0 - SCALE);
+        assert!(uniform(u64::MAX) < 1.0);
+        assert_eq!(uniform(1 << 63), 0.5);
+        // The low 11 bits are discarded
+        assert_eq!(uniform(0x7ff), 0.0);
+    }
+
+    #[test]
+    fn test_gaussian_moments() {
+        let bytes = crate::lrng::os_fill_rand_octets(gaussian_bytes(20_001)).unwrap();
+        let samples = gaussians(&bytes, 20_001, 5.0, 2.0);
+        assert_eq!(samples.len(), 20_001);
+        let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+        let var = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / samples.len() as f64;
+        assert!((mean - 5.0).abs() < 0.1, "mean {}", mean);
+        assert!((var.sqrt() - 2.0).abs() < 0.1, "stddev {}", var.sqrt());
+    }
+}
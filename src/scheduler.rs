@@ -0,0 +1,394 @@
+use crate::config::{SchedulerConfig, SchedulerPolicy};
+use crate::error::Error;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use tokio::sync::{oneshot, Semaphore, SemaphorePermit};
+
+/// Who a request is served for; set per D-Bus call with `on_behalf_of`.
+#[derive(Debug, Clone)]
+pub struct Requester {
+    pub client: String,
+    /// Higher is more urgent (`priority`) or a larger share (`fair_share`).
+    pub priority: u8,
+    /// Numbers the D-Bus call for attestation and the provenance ledger.
+    pub request_id: u64,
+}
+
+impl Requester {
+    /// Work the service does for itself (DRBG reseeds, readiness checks).
+    fn internal() -> Self {
+        Self { client: String::new(), priority: 0, request_id: 0 }
+    }
+}
+
+tokio::task_local! {
+    static REQUESTER: Requester;
+}
+
+/// Runs `fut` with every source read it makes scheduled for `requester`.
+pub async fn on_behalf_of<F: Future>(requester: Requester, fut: F) -> F::Output {
+    REQUESTER.scope(requester, fut).await
+}
+
+/// The requester of the D-Bus call being served, or the service itself.
+pub fn current() -> Requester {
+    REQUESTER.try_with(|r| r.clone()).unwrap_or_else(|_| Requester::internal())
+}
+
+/// A request waiting for its next slice of source time.
+pub struct Waiter {
+    seq: u64,
+    requester: Requester,
+    wake: oneshot::Sender<()>,
+}
+
+/// Decides which waiting request reads from the sources next.
+pub trait Scheduler: Send {
+    fn push(&mut self, waiter: Waiter);
+    fn pop(&mut self) -> Option<Waiter>;
+    fn is_empty(&self) -> bool;
+    /// Called after `bytes` were read for `requester`.
+    fn served(&mut self, _requester: &Requester, _bytes: usize) {}
+}
+
+/// First come, first served.
+#[derive(Default)]
+pub struct Fifo {
+    queue: VecDeque<Waiter>,
+}
+
+impl Scheduler for Fifo {
+    fn push(&mut self, waiter: Waiter) {
+        self.queue.push_back(waiter);
+    }
+
+    fn pop(&mut self) -> Option<Waiter> {
+        self.queue.pop_front()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+struct ByPriority(Waiter);
+
+impl ByPriority {
+    fn key(&self) -> (u8, Reverse<u64>) {
+        (self.0.requester.priority, Reverse(self.0.seq))
+    }
+}
+
+impl PartialEq for ByPriority {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for ByPriority {}
+
+impl PartialOrd for ByPriority {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ByPriority {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// Highest priority first, FIFO within a priority. Low priorities can starve.
+#[derive(Default)]
+pub struct StrictPriority {
+    heap: BinaryHeap<ByPriority>,
+}
+
+impl Scheduler for StrictPriority {
+    fn push(&mut self, waiter: Waiter) {
+        self.heap.push(ByPriority(waiter));
+    }
+
+    fn pop(&mut self) -> Option<Waiter> {
+        self.heap.pop().map(|w| w.0)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+}
+
+/// Clients whose usage is remembered while they have nothing queued.
+const MAX_IDLE_CLIENTS: usize = 4096;
+
+/// Serves the waiting client that has used the least source bytes,
+/// weighted by `priority + 1`, so clients share throughput in proportion.
+#[derive(Default)]
+pub struct FairShare {
+    queue: VecDeque<Waiter>,
+    /// Weighted bytes served per client.
+    usage: HashMap<String, f64>,
+}
+
+impl FairShare {
+    fn usage(&self, client: &str) -> f64 {
+        self.usage.get(client).copied().unwrap_or(0.0)
+    }
+}
+
+impl Scheduler for FairShare {
+    fn push(&mut self, waiter: Waiter) {
+        // A newcomer starts level with the least-served waiting client
+        // instead of at zero, so it cannot monopolize the sources.
+        if !self.usage.contains_key(&waiter.requester.client) {
+            let floor = self.queue.iter().map(|w| self.usage(&w.requester.client)).fold(f64::INFINITY, f64::min);
+            self.usage.insert(waiter.requester.client.clone(), if floor.is_finite() { floor } else { 0.0 });
+        }
+        self.queue.push_back(waiter);
+    }
+
+    fn pop(&mut self) -> Option<Waiter> {
+        let (index, _) = self
+            .queue
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| self.usage(&a.requester.client).total_cmp(&self.usage(&b.requester.client)))?;
+        self.queue.remove(index)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    fn served(&mut self, requester: &Requester, bytes: usize) {
+        if self.usage.len() > MAX_IDLE_CLIENTS {
+            let queue = &self.queue;
+            self.usage.retain(|c, _| queue.iter().any(|w| &w.requester.client == c));
+        }
+        let weight = 1.0 + requester.priority as f64;
+        *self.usage.entry(requester.client.clone()).or_insert(0.0) += bytes as f64 / weight;
+    }
+}
+
+pub fn from_config(cfg: Option<&SchedulerConfig>) -> Dispatcher {
+    let cfg = cfg.cloned().unwrap_or_default();
+    let policy: Box<dyn Scheduler> = match cfg.policy.unwrap_or_default() {
+        SchedulerPolicy::Fifo => Box::<Fifo>::default(),
+        SchedulerPolicy::Priority => Box::<StrictPriority>::default(),
+        SchedulerPolicy::FairShare => Box::<FairShare>::default(),
+    };
+    Dispatcher::new(policy, cfg.slots.unwrap_or(4).max(1))
+}
+
+/// Bounds the client requests in progress at once to
+/// `max_concurrent_requests`, queueing the others in arrival order, and
+/// refuses requests beyond `max_queue_depth` waiting ones. Unlike the
+/// `Dispatcher`, which hands out turns per slice, it admits whole requests.
+pub struct Admission {
+    permits: Option<Semaphore>,
+    max_waiting: Option<usize>,
+    waiting: AtomicUsize,
+}
+
+/// A place in the admission queue, given up when dropped.
+struct Queued<'a>(&'a AtomicUsize);
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Admission {
+    pub fn new(cfg: Option<&SchedulerConfig>) -> Self {
+        let cfg = cfg.cloned().unwrap_or_default();
+        Self {
+            permits: cfg.max_concurrent_requests.map(|n| Semaphore::new(n.max(1))),
+            max_waiting: cfg.max_queue_depth,
+            waiting: AtomicUsize::new(0),
+        }
+    }
+
+    /// Waits until a request may go ahead; it is served while the returned
+    /// permit is held. Fails with `Busy` at once when the queue is full.
+    pub async fn enter(&self) -> Result<Option<SemaphorePermit<'_>>, Error> {
+        let Some(permits) = &self.permits else { return Ok(None) };
+        if let Ok(permit) = permits.try_acquire() {
+            return Ok(Some(permit));
+        }
+        let ahead = self.waiting.fetch_add(1, Ordering::SeqCst);
+        let queued = Queued(&self.waiting);
+        if self.max_waiting.is_some_and(|max| ahead >= max) {
+            return Err(Error::Busy);
+        }
+        let permit = permits.acquire().await.map_err(|_| Error::Unexpected)?;
+        drop(queued);
+        Ok(Some(permit))
+    }
+}
+
+struct State {
+    running: usize,
+    next_seq: u64,
+    policy: Box<dyn Scheduler>,
+}
+
+/// Hands out up to `slots` concurrent turns at the sources, in the order the
+/// configured `Scheduler` picks.
+pub struct Dispatcher {
+    slots: usize,
+    state: Mutex<State>,
+}
+
+impl Dispatcher {
+    pub fn new(policy: Box<dyn Scheduler>, slots: usize) -> Self {
+        Self { slots, state: Mutex::new(State { running: 0, next_seq: 0, policy }) }
+    }
+
+    /// Waits for a turn to read `bytes` from the sources for the current requester.
+    pub async fn turn(&self, bytes: usize) -> Turn<'_> {
+        let requester = current();
+        let rx = {
+            let mut state = self.lock();
+            if state.running < self.slots && state.policy.is_empty() {
+                state.running += 1;
+                return Turn { dispatcher: self, requester, bytes };
+            }
+            let (wake, rx) = oneshot::channel();
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.policy.push(Waiter { seq, requester: requester.clone(), wake });
+            rx
+        };
+        let mut pending = Pending { dispatcher: self, rx: Some(rx) };
+        if let Some(rx) = pending.rx.as_mut() {
+            let _ = rx.await;
+        }
+        pending.rx = None;
+        Turn { dispatcher: self, requester, bytes }
+    }
+
+    /// Passes a finished turn on to the next waiter, or frees the slot.
+    fn release(&self, served: Option<(&Requester, usize)>) {
+        let mut state = self.lock();
+        if let Some((requester, bytes)) = served {
+            state.policy.served(requester, bytes);
+        }
+        while let Some(waiter) = state.policy.pop() {
+            // Skip waiters whose request was dropped meanwhile
+            if waiter.wake.send(()).is_ok() {
+                return;
+            }
+        }
+        state.running -= 1;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Permission to read one slice; hands the slot on when dropped.
+pub struct Turn<'a> {
+    dispatcher: &'a Dispatcher,
+    requester: Requester,
+    bytes: usize,
+}
+
+impl Drop for Turn<'_> {
+    fn drop(&mut self) {
+        self.dispatcher.release(Some((&self.requester, self.bytes)));
+    }
+}
+
+/// Gives back a turn granted to a request that was dropped before it noticed.
+struct Pending<'a> {
+    dispatcher: &'a Dispatcher,
+    rx: Option<oneshot::Receiver<()>>,
+}
+
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        if let Some(mut rx) = self.rx.take() {
+            rx.close();
+            if rx.try_recv().is_ok() {
+                self.dispatcher.release(None);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn waiter(seq: u64, client: &str, priority: u8) -> (Waiter, oneshot::Receiver<()>) {
+        let (wake, rx) = oneshot::channel();
+        (Waiter { seq, requester: Requester { client: client.to_string(), priority, request_id: 0 }, wake }, rx)
+    }
+
+    fn order(policy: &mut dyn Scheduler, waiters: &[(&str, u8)]) -> Vec<String> {
+        let mut receivers = Vec::new();
+        for (seq, (client, priority)) in waiters.iter().enumerate() {
+            let (w, rx) = waiter(seq as u64, client, *priority);
+            policy.push(w);
+            receivers.push(rx);
+        }
+        let mut served = Vec::new();
+        while let Some(w) = policy.pop() {
+            policy.served(&w.requester, 100);
+            served.push(w.requester.client);
+        }
+        served
+    }
+
+    #[test]
+    fn test_fifo_and_priority_order() {
+        let waiters = [("a", 0), ("b", 5), ("c", 1), ("d", 5)];
+        assert_eq!(order(&mut Fifo::default(), &waiters), ["a", "b", "c", "d"]);
+        assert_eq!(order(&mut StrictPriority::default(), &waiters), ["b", "d", "c", "a"]);
+    }
+
+    #[test]
+    fn test_fair_share_interleaves_clients() {
+        let mut fair = FairShare::default();
+        fair.served(&Requester { client: "batch".to_string(), priority: 0, request_id: 0 }, 1000);
+        let waiters = [("batch", 0), ("batch", 0), ("batch", 0), ("desktop", 0), ("desktop", 0)];
+        // The newcomer starts level with the waiting batch client, then they alternate
+        assert_eq!(order(&mut fair, &waiters), ["batch", "desktop", "batch", "desktop", "batch"]);
+    }
+
+    #[tokio::test]
+    async fn test_admission_queue_depth() {
+        let cfg = SchedulerConfig { max_concurrent_requests: Some(1), max_queue_depth: Some(1), ..Default::default() };
+        let admission = Admission::new(Some(&cfg));
+        let first = admission.enter().await.unwrap();
+        let mut second = Box::pin(admission.enter());
+        assert!(futures::poll!(second.as_mut()).is_pending());
+        assert_eq!(admission.enter().await.unwrap_err(), Error::Busy);
+        // A request given up frees its place in the queue
+        drop(second);
+        let third = admission.enter();
+        tokio::pin!(third);
+        assert!(futures::poll!(third.as_mut()).is_pending());
+        drop(first);
+        assert!(third.await.unwrap().is_some());
+        assert!(Admission::new(None).enter().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_dispatcher_limits_concurrency() {
+        let dispatcher = Dispatcher::new(Box::<Fifo>::default(), 1);
+        let first = dispatcher.turn(10).await;
+        let second = dispatcher.turn(10);
+        tokio::pin!(second);
+        assert!(futures::poll!(second.as_mut()).is_pending());
+        drop(first);
+        let _second = second.await;
+        assert_eq!(dispatcher.lock().running, 1);
+    }
+}
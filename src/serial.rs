@@ -0,0 +1,121 @@
+use crate::config::{Parity, SerialConfig};
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::OpenOptionsExt;
+
+pub const DEFAULT_BAUD_RATE: u32 = 115_200;
+
+/// How long a read waits for the device before returning nothing, in tenths
+/// of a second (`VTIME`), so that a silent device cannot hold up a reader.
+const READ_TIMEOUT_DECISECONDS: libc::cc_t = 1;
+
+fn speed(baud: u32) -> Option<libc::speed_t> {
+    Some(match baud {
+        9600 => libc::B9600,
+        19200 => libc::B19200,
+        38400 => libc::B38400,
+        57600 => libc::B57600,
+        115_200 => libc::B115200,
+        230_400 => libc::B230400,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        460_800 => libc::B460800,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        921_600 => libc::B921600,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        1_000_000 => libc::B1000000,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        2_000_000 => libc::B2000000,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        3_000_000 => libc::B3000000,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        4_000_000 => libc::B4000000,
+        _ => return None,
+    })
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+/// `c_cflag` bits for the configured character size, parity and stop bits.
+fn framing(cfg: &SerialConfig) -> io::Result<libc::tcflag_t> {
+    let size = match cfg.data_bits.unwrap_or(8) {
+        5 => libc::CS5,
+        6 => libc::CS6,
+        7 => libc::CS7,
+        8 => libc::CS8,
+        n => return Err(invalid(format!("unsupported data_bits {}", n))),
+    };
+    let parity = match cfg.parity.unwrap_or_default() {
+        Parity::None => 0,
+        Parity::Even => libc::PARENB,
+        Parity::Odd => libc::PARENB | libc::PARODD,
+    };
+    let stop = match cfg.stop_bits.unwrap_or(1) {
+        1 => 0,
+        2 => libc::CSTOPB,
+        n => return Err(invalid(format!("unsupported stop_bits {}", n))),
+    };
+    Ok(size | parity | stop)
+}
+
+fn check(rc: libc::c_int) -> io::Result<()> {
+    if rc == 0 { Ok(()) } else { Err(io::Error::last_os_error()) }
+}
+
+/// Opens the device of a serial source in raw mode with its framing. Reads
+/// return what has arrived, or nothing after `READ_TIMEOUT_DECISECONDS`.
+pub fn open(cfg: &SerialConfig) -> io::Result<File> {
+    let baud = cfg.baud_rate.unwrap_or(DEFAULT_BAUD_RATE);
+    let speed = speed(baud).ok_or_else(|| invalid(format!("unsupported baud_rate {}", baud)))?;
+    let framing = framing(cfg)?;
+    // Non-blocking so that opening does not wait for carrier detect
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOCTTY | libc::O_NONBLOCK)
+        .open(&cfg.path)?;
+    let fd = file.as_raw_fd();
+    let mut tio: libc::termios = unsafe { std::mem::zeroed() };
+    check(unsafe { libc::tcgetattr(fd, &mut tio) })?;
+    unsafe { libc::cfmakeraw(&mut tio) };
+    tio.c_cflag &= !(libc::CSIZE | libc::PARENB | libc::PARODD | libc::CSTOPB | libc::CRTSCTS);
+    tio.c_cflag |= framing | libc::CREAD | libc::CLOCAL;
+    if framing & libc::PARENB != 0 {
+        // Drop bytes with parity errors rather than read them as 0
+        tio.c_iflag |= libc::INPCK | libc::IGNPAR;
+    }
+    tio.c_cc[libc::VMIN] = 0;
+    tio.c_cc[libc::VTIME] = READ_TIMEOUT_DECISECONDS;
+    check(unsafe { libc::cfsetispeed(&mut tio, speed) })?;
+    check(unsafe { libc::cfsetospeed(&mut tio, speed) })?;
+    check(unsafe { libc::tcsetattr(fd, libc::TCSANOW, &tio) })?;
+    // Whatever arrived before the line was set up may be garbled
+    check(unsafe { libc::tcflush(fd, libc::TCIFLUSH) })?;
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags & !libc::O_NONBLOCK) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_framing() {
+        let cfg = |extra: &str| -> SerialConfig { toml::from_str(&format!("id = \"q\"\npath = \"/dev/null\"\n{}", extra)).unwrap() };
+        assert_eq!(framing(&cfg("")).unwrap(), libc::CS8);
+        assert_eq!(
+            framing(&cfg("data_bits = 7\nparity = \"odd\"\nstop_bits = 2")).unwrap(),
+            libc::CS7 | libc::PARENB | libc::PARODD | libc::CSTOPB
+        );
+        assert!(framing(&cfg("data_bits = 9")).is_err());
+        assert!(framing(&cfg("stop_bits = 3")).is_err());
+        assert!(speed(DEFAULT_BAUD_RATE).is_some());
+        assert!(speed(12345).is_none());
+        // Not a terminal
+        assert!(open(&cfg("")).is_err());
+    }
+}
@@ -0,0 +1,161 @@
+use zeroize::{Zeroize, Zeroizing};
+
+/// SHA3-256 absorbs this many bytes per permutation.
+pub const RATE: usize = 136;
+
+/// Output bytes per counter block of `combine`.
+const BLOCK: usize = 32;
+
+/// Domain separation for `combine`, so its blocks are never equal to a
+/// SHA3-256 of anything else the service hashes.
+const COMBINE_LABEL: &[u8] = b"trng-dbus sha3 combine";
+
+const ROUND_CONSTANTS: [u64; 24] = [
+    0x0000000000000001, 0x0000000000008082, 0x800000000000808a, 0x8000000080008000,
+    0x000000000000808b, 0x0000000080000001, 0x8000000080008081, 0x8000000000008009,
+    0x000000000000008a, 0x0000000000000088, 0x0000000080008009, 0x000000008000000a,
+    0x000000008000808b, 0x800000000000008b, 0x8000000000008089, 0x8000000000008003,
+    0x8000000000008002, 0x8000000000000080, 0x000000000000800a, 0x800000008000000a,
+    0x8000000080008081, 0x8000000000008080, 0x0000000080000001, 0x8000000080008008,
+];
+
+/// Rotation offsets of rho, in the lane order pi visits them.
+const ROTATIONS: [u32; 24] = [1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 2, 14, 27, 41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44];
+
+/// Lanes in the order pi moves them.
+const PI_LANES: [usize; 24] = [10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23, 19, 13, 12, 2, 20, 14, 22, 9, 6, 1];
+
+/// The Keccak-f[1600] permutation.
+pub fn keccak_f(a: &mut [u64; 25]) {
+    for rc in ROUND_CONSTANTS {
+        // Theta
+        let mut c = [0u64; 5];
+        for x in 0..5 {
+            c[x] = a[x] ^ a[x + 5] ^ a[x + 10] ^ a[x + 15] ^ a[x + 20];
+        }
+        for x in 0..5 {
+            let d = c[(x + 4) % 5] ^ c[(x + 1) % 5].rotate_left(1);
+            for y in 0..5 {
+                a[x + 5 * y] ^= d;
+            }
+        }
+        // Rho and pi
+        let mut last = a[1];
+        for (&lane, &rotation) in PI_LANES.iter().zip(&ROTATIONS) {
+            let next = a[lane];
+            a[lane] = last.rotate_left(rotation);
+            last = next;
+        }
+        // Chi
+        for y in 0..5 {
+            let row = [a[5 * y], a[5 * y + 1], a[5 * y + 2], a[5 * y + 3], a[5 * y + 4]];
+            for x in 0..5 {
+                a[5 * y + x] = row[x] ^ (!row[(x + 1) % 5] & row[(x + 2) % 5]);
+            }
+        }
+        // Iota
+        a[0] ^= rc;
+    }
+}
+
+/// SHA3-256 (FIPS 202). The state is zeroized when dropped, as it holds
+/// entropy being conditioned.
+pub struct Sha3_256 {
+    state: [u64; 25],
+    /// Bytes absorbed into the current block.
+    pos: usize,
+}
+
+impl Drop for Sha3_256 {
+    fn drop(&mut self) {
+        self.state.zeroize();
+    }
+}
+
+impl Sha3_256 {
+    pub fn new() -> Self {
+        Self { state: [0; 25], pos: 0 }
+    }
+
+    fn xor_byte(&mut self, byte: u8) {
+        self.state[self.pos / 8] ^= (byte as u64) << (8 * (self.pos % 8));
+        self.pos += 1;
+        if self.pos == RATE {
+            keccak_f(&mut self.state);
+            self.pos = 0;
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.xor_byte(byte);
+        }
+    }
+
+    pub fn finalize(mut self) -> [u8; 32] {
+        // SHA-3 domain bits, then the last bit of pad10*1
+        self.state[self.pos / 8] ^= 0x06u64 << (8 * (self.pos % 8));
+        self.state[(RATE - 1) / 8] ^= 0x80u64 << (8 * ((RATE - 1) % 8));
+        keccak_f(&mut self.state);
+        let mut out = [0u8; 32];
+        for (chunk, lane) in out.chunks_mut(8).zip(&self.state) {
+            chunk.copy_from_slice(&lane.to_le_bytes());
+        }
+        out
+    }
+}
+
+/// Conditions equal-length `contributions` (one per source) into as many
+/// bytes: output block `i` is SHA3-256 over the label, `i` and the `i`th
+/// 32 bytes of every contribution in turn, so each block is full entropy as
+/// long as one contribution's bytes are, however biased the others.
+pub fn combine(contributions: &[&[u8]]) -> Zeroizing<Vec<u8>> {
+    let len = contributions.iter().map(|c| c.len()).min().unwrap_or(0);
+    let mut out = Zeroizing::new(Vec::with_capacity(len));
+    for (i, start) in (0..len).step_by(BLOCK).enumerate() {
+        let end = (start + BLOCK).min(len);
+        let mut hasher = Sha3_256::new();
+        hasher.update(COMBINE_LABEL);
+        hasher.update(&(i as u64).to_be_bytes());
+        for contribution in contributions {
+            hasher.update(&contribution[start..end]);
+        }
+        let block = Zeroizing::new(hasher.finalize());
+        out.extend_from_slice(&block[..end - start]);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sha3(data: &[u8]) -> String {
+        let mut hasher = Sha3_256::new();
+        hasher.update(data);
+        hex::encode(hasher.finalize())
+    }
+
+    #[test]
+    fn test_sha3_256_vectors() {
+        assert_eq!(sha3(b""), "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a");
+        assert_eq!(sha3(b"abc"), "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532");
+        // Spans two blocks of the rate
+        assert_eq!(sha3(&[b'a'; 200]), "cce34485baf2bf2aca99b94833892a4f52896d3d153f7b840cc4f9fe695f1387");
+    }
+
+    #[test]
+    fn test_combine() {
+        let biased = [0u8; 100];
+        let good: Vec<u8> = (0..100u8).map(|b| b.wrapping_mul(151).wrapping_add(7)).collect();
+        let out = combine(&[&biased, &good]);
+        assert_eq!(out.len(), 100);
+        // Blocks differ although one source repeats, and every block depends on both sources
+        assert_ne!(out[..32], out[32..64]);
+        assert_ne!(*combine(&[&biased[..64], &biased[..64]]), *combine(&[&biased[..64], &good[..64]]));
+        let first = [COMBINE_LABEL, &0u64.to_be_bytes(), &biased[..32], &good[..32]].concat();
+        assert_eq!(hex::encode(&out[..32]), sha3(&first));
+        assert_eq!(combine(&[&good, &biased[..10]]).len(), 10);
+        assert!(combine(&[]).is_empty());
+    }
+}
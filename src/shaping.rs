@@ -0,0 +1,130 @@
+use crate::config::ShapingConfig;
+use crate::error::Error;
+use std::sync::Mutex;
+use tokio::time::{sleep_until, Duration, Instant};
+
+/// Token bucket state: `tokens` bytes may be served at `updated`. Negative
+/// while admitted requests are still waiting for their turn.
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Caps the bytes one `[[groups]]` endpoint serves per second across all of
+/// its callers. Requests are delayed in arrival order until the bucket
+/// covers them, so a request larger than the burst still goes through at
+/// the configured rate.
+pub struct Shaper {
+    rate: f64,
+    burst: f64,
+    bucket: Mutex<Bucket>,
+}
+
+impl Shaper {
+    pub fn new(cfg: &ShapingConfig) -> Self {
+        let rate = cfg.bytes_per_second.max(1) as f64;
+        let burst = cfg.burst_bytes.unwrap_or(cfg.bytes_per_second).max(1) as f64;
+        Self { rate, burst, bucket: Mutex::new(Bucket { tokens: burst, updated: Instant::now() }) }
+    }
+
+    /// How long until `bytes` may be served, reserving them, or `None` (and
+    /// nothing reserved) when that would be after `deadline`.
+    fn reserve(&self, bytes: usize, now: Instant, deadline: Instant) -> Option<Duration> {
+        let mut bucket = self.lock();
+        let tokens = self.refill(&mut bucket, now);
+        // Beyond a burst a request waits for a full bucket, then drains it
+        let wait = Duration::from_secs_f64(((bytes as f64).min(self.burst) - tokens).max(0.0) / self.rate);
+        if now + wait > deadline {
+            return None;
+        }
+        bucket.tokens -= bytes as f64;
+        Some(wait)
+    }
+
+    /// Reserves as many of `bytes` as the bucket covers by `deadline`,
+    /// returning them and how long until they may be served. Beyond a burst
+    /// that is everything accrued by then, as if the bucket had no cap.
+    fn reserve_some(&self, bytes: usize, now: Instant, deadline: Instant) -> (usize, Duration) {
+        let mut bucket = self.lock();
+        let tokens = self.refill(&mut bucket, now);
+        let by_deadline = tokens + deadline.saturating_duration_since(now).as_secs_f64() * self.rate;
+        let granted = (bytes as f64).min(by_deadline).max(0.0) as usize;
+        if granted == 0 {
+            return (0, Duration::ZERO);
+        }
+        bucket.tokens -= granted as f64;
+        (granted, Duration::from_secs_f64((granted as f64 - tokens).max(0.0) / self.rate))
+    }
+
+    fn refill(&self, bucket: &mut Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.updated = now;
+        bucket.tokens
+    }
+
+    /// Waits until `bytes` may be served. Fails with `QuotaExceeded` at once
+    /// when that would be after `deadline`.
+    pub async fn admit(&self, bytes: usize, deadline: Instant) -> Result<(), Error> {
+        let now = Instant::now();
+        let wait = self.reserve(bytes, now, deadline).ok_or(Error::QuotaExceeded)?;
+        sleep_until(now + wait).await;
+        Ok(())
+    }
+
+    /// Waits until part of `bytes` may be served and returns how much: what
+    /// the bucket covers by `deadline`, possibly nothing.
+    pub async fn admit_some(&self, bytes: usize, deadline: Instant) -> usize {
+        let now = Instant::now();
+        let (granted, wait) = self.reserve_some(bytes, now, deadline);
+        sleep_until(now + wait).await;
+        granted
+    }
+
+    /// Gives back admitted bytes that were not served.
+    pub fn refund(&self, bytes: usize) {
+        let mut bucket = self.lock();
+        bucket.tokens = (bucket.tokens + bytes as f64).min(self.burst);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Bucket> {
+        self.bucket.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_delays_then_refuses() {
+        let shaper = Shaper::new(&ShapingConfig { bytes_per_second: 1000, burst_bytes: Some(500) });
+        let now = Instant::now();
+        let later = now + Duration::from_secs(10);
+        assert_eq!(shaper.reserve(500, now, later), Some(Duration::ZERO));
+        // Empty: 250 bytes take a quarter second
+        assert_eq!(shaper.reserve(250, now, later), Some(Duration::from_millis(250)));
+        // Queued behind the reservation above
+        assert_eq!(shaper.reserve(250, now, now + Duration::from_millis(400)), None);
+        assert_eq!(shaper.reserve(250, now, later), Some(Duration::from_millis(500)));
+        shaper.refund(250);
+        assert_eq!(shaper.reserve(250, now, later), Some(Duration::from_millis(500)));
+        // Larger than the burst: waits for a full bucket, then goes into debt
+        let shaper = Shaper::new(&ShapingConfig { bytes_per_second: 1000, burst_bytes: Some(500) });
+        assert_eq!(shaper.reserve(2000, now, later), Some(Duration::ZERO));
+        assert_eq!(shaper.reserve(1, now + Duration::from_secs(1), later), Some(Duration::from_millis(501)));
+    }
+
+    #[test]
+    fn test_partial_grants() {
+        let shaper = Shaper::new(&ShapingConfig { bytes_per_second: 1000, burst_bytes: None });
+        let now = Instant::now();
+        assert_eq!(shaper.reserve_some(600, now, now), (600, Duration::ZERO));
+        // 400 left now, 500 more by the deadline
+        assert_eq!(shaper.reserve_some(4000, now, now + Duration::from_millis(500)), (900, Duration::from_millis(500)));
+        assert_eq!(shaper.reserve_some(10, now, now + Duration::from_millis(400)), (0, Duration::ZERO));
+        // A full bucket plus what accrues until the deadline
+        let later = now + Duration::from_secs(3);
+        assert_eq!(shaper.reserve_some(9000, later, later + Duration::from_secs(2)), (3000, Duration::from_secs(2)));
+    }
+}
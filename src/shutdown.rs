@@ -0,0 +1,153 @@
+use crate::circular_buffer::CircularBuffer;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
+
+/// Teardown steps run in this order, whatever the exit path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    /// Stop background tasks so nothing refills the buffers.
+    StopTasks,
+    /// Wipe every buffer holding entropy.
+    Zeroize,
+    /// Seal and flush anything written to disk, then the logs.
+    Flush,
+}
+
+type Step = (Stage, String, Box<dyn FnOnce() + Send>);
+
+/// Ordered set of one-shot cleanup steps.
+pub struct Teardown {
+    steps: Mutex<Vec<Step>>,
+    started: AtomicBool,
+}
+
+impl Teardown {
+    pub const fn new() -> Self {
+        Self { steps: Mutex::new(Vec::new()), started: AtomicBool::new(false) }
+    }
+
+    pub fn register(&self, stage: Stage, name: impl Into<String>, step: impl FnOnce() + Send + 'static) {
+        self.lock().push((stage, name.into(), Box::new(step)));
+    }
+
+    /// Runs all registered steps once, by stage and then registration order.
+    /// Returns false if teardown already ran.
+    pub fn run(&self, reason: &str) -> bool {
+        if self.started.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+        let mut steps = std::mem::take(&mut *self.lock());
+        steps.sort_by_key(|(stage, _, _)| *stage);
+        log::info!("Shutting down ({}): running {} teardown steps", reason, steps.len());
+        for (stage, name, step) in steps {
+            log::debug!("Teardown {:?}: {}", stage, name);
+            // A failing step must not keep the later ones from running
+            if panic::catch_unwind(AssertUnwindSafe(step)).is_err() {
+                log::error!("Teardown step {} panicked", name);
+            }
+        }
+        true
+    }
+
+    fn in_progress(&self) -> bool {
+        self.started.load(Ordering::SeqCst)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Step>> {
+        self.steps.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+static TEARDOWN: Teardown = Teardown::new();
+
+pub fn register(stage: Stage, name: impl Into<String>, step: impl FnOnce() + Send + 'static) {
+    TEARDOWN.register(stage, name, step);
+}
+
+pub fn run(reason: &str) -> bool {
+    TEARDOWN.run(reason)
+}
+
+/// Whether the service has started tearing down; requests are refused from then on.
+pub fn in_progress() -> bool {
+    TEARDOWN.in_progress()
+}
+
+/// Runs teardown on normal return from `main`, including early `?` exits.
+pub struct Guard;
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        run("exit");
+    }
+}
+
+/// Runs teardown before a panic on the main thread ends the process.
+/// Panics inside tasks are left to the supervisor; with a current-thread
+/// runtime those run on the main thread too.
+pub fn install_panic_hook() {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        previous(info);
+        if std::thread::current().name() == Some("main") && tokio::task::try_id().is_none() {
+            run("panic");
+        }
+    }));
+}
+
+/// Waits for SIGTERM or SIGINT and returns the signal name.
+pub async fn wait_for_signal() -> std::io::Result<&'static str> {
+    let mut term = signal(SignalKind::terminate())?;
+    let mut int = signal(SignalKind::interrupt())?;
+    Ok(tokio::select! {
+        _ = term.recv() => "SIGTERM",
+        _ = int.recv() => "SIGINT",
+    })
+}
+
+/// Zeroizes a buffer from synchronous teardown code. Gives the current
+/// holder of the lock a short while to finish.
+pub fn wipe_buffer(buffer: &tokio::sync::Mutex<CircularBuffer>, what: &str) {
+    for _ in 0..100 {
+        if let Ok(mut buf) = buffer.try_lock() {
+            buf.wipe();
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(2));
+    }
+    log::error!("Could not lock {} for zeroization", what);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_teardown_order() {
+        let teardown = Teardown::new();
+        let order = Arc::new(Mutex::new(Vec::new()));
+        for (stage, name) in [(Stage::Flush, "flush"), (Stage::Zeroize, "wipe a"), (Stage::StopTasks, "stop"), (Stage::Zeroize, "wipe b")] {
+            let order = order.clone();
+            teardown.register(stage, name, move || order.lock().unwrap().push(name));
+        }
+        teardown.register(Stage::Zeroize, "broken", || panic!("step fails"));
+
+        assert!(teardown.run("test"));
+        assert_eq!(*order.lock().unwrap(), vec!["stop", "wipe a", "wipe b", "flush"]);
+        // Only the first exit path tears down
+        assert!(!teardown.run("test again"));
+        assert_eq!(order.lock().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_wipe_buffer() {
+        let buffer = tokio::sync::Mutex::new(CircularBuffer::new(8));
+        buffer.try_lock().unwrap().extend(b"secret");
+        wipe_buffer(&buffer, "test buffer");
+        assert_eq!(buffer.try_lock().unwrap().len(), 0);
+    }
+}
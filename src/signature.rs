@@ -0,0 +1,130 @@
+use minisign_verify::{PublicKey, Signature};
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::os::unix::fs::MetadataExt;
+
+/// Detached minisign (ed25519) signature check for an entropy file.
+pub struct SignatureCheck {
+    sig_path: String,
+    keys: Vec<PublicKey>,
+}
+
+/// Identity of a file on disk, used to notice when it has been replaced.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct FileIdentity {
+    dev: u64,
+    ino: u64,
+    len: u64,
+    mtime: i64,
+    mtime_nsec: i64,
+}
+
+pub fn file_identity(path: &str) -> io::Result<FileIdentity> {
+    let meta = fs::metadata(path)?;
+    Ok(FileIdentity {
+        dev: meta.dev(),
+        ino: meta.ino(),
+        len: meta.len(),
+        mtime: meta.mtime(),
+        mtime_nsec: meta.mtime_nsec(),
+    })
+}
+
+impl SignatureCheck {
+    /// Builds the check for a file source. Returns `None` when no public keys
+    /// are configured; the signature defaults to `<path>.minisig`.
+    pub fn new(path: &str, signature: Option<&str>, public_keys: &[String]) -> Result<Option<Self>, String> {
+        if public_keys.is_empty() {
+            if signature.is_some() {
+                return Err(format!("signature configured for {} but no public_keys to verify it", path));
+            }
+            return Ok(None);
+        }
+        let mut keys = Vec::with_capacity(public_keys.len());
+        for key in public_keys {
+            keys.push(PublicKey::from_base64(key).map_err(|e| format!("invalid public key '{}': {}", key, e))?);
+        }
+        let sig_path = signature.map(str::to_string).unwrap_or_else(|| format!("{}.minisig", path));
+        Ok(Some(Self { sig_path, keys }))
+    }
+
+    pub fn signature_path(&self) -> &str {
+        &self.sig_path
+    }
+
+    /// Verifies `path` against the detached signature. Blocking: reads the whole file.
+    pub fn verify(&self, path: &str) -> Result<(), String> {
+        let sig = Signature::from_file(&self.sig_path)
+            .map_err(|e| format!("cannot load signature {}: {}", self.sig_path, e))?;
+        for key in &self.keys {
+            match verify_with_key(path, key, &sig) {
+                Err(minisign_verify::Error::UnexpectedKeyId) => continue,
+                Err(e) => return Err(format!("signature {} rejected: {}", self.sig_path, e)),
+                Ok(()) => {
+                    log::info!("Signature {} valid ({})", self.sig_path, sig.trusted_comment());
+                    return Ok(());
+                }
+            }
+        }
+        Err(format!("signature {} was not made by any configured public key", self.sig_path))
+    }
+}
+
+fn verify_with_key(path: &str, key: &PublicKey, sig: &Signature) -> Result<(), minisign_verify::Error> {
+    let mut verifier = match key.verify_stream(sig) {
+        Ok(v) => v,
+        // Legacy (non-prehashed) signatures sign the raw file contents
+        Err(minisign_verify::Error::UnsupportedLegacyMode) => {
+            return key.verify(&fs::read(path)?, sig, true);
+        }
+        Err(e) => return Err(e),
+    };
+    let mut file = File::open(path)?;
+    let mut chunk = vec![0u8; 64 * 1024];
+    loop {
+        match file.read(&mut chunk)? {
+            0 => break,
+            n => verifier.update(&chunk[..n]),
+        }
+    }
+    verifier.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test vector from the minisign-verify crate: prehashed signature over b"test"
+    const PUBLIC_KEY: &str = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+    const SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=
+trusted comment: timestamp:1556193335\tfile:test
+y/rUw2y8/hOUYjZU71eHp/Wo1KZ40fGy2VJEDl34XMJM+TX48Ss/17u3IvIfbVR1FkZZSNCisQbuQY+bHwhEBg==
+";
+
+    fn write_pair(name: &str, contents: &[u8]) -> String {
+        let path = std::env::temp_dir().join(format!("trng-dbus-sig-{}-{}", std::process::id(), name));
+        let path = path.to_str().unwrap().to_string();
+        fs::write(&path, contents).unwrap();
+        fs::write(format!("{}.minisig", path), SIGNATURE).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_verify_signed_file() {
+        let path = write_pair("good", b"test");
+        let check = SignatureCheck::new(&path, None, &[PUBLIC_KEY.to_string()]).unwrap().unwrap();
+        assert!(check.verify(&path).is_ok());
+
+        let tampered = write_pair("bad", b"Test");
+        let check = SignatureCheck::new(&tampered, None, &[PUBLIC_KEY.to_string()]).unwrap().unwrap();
+        assert!(check.verify(&tampered).is_err());
+    }
+
+    #[test]
+    fn test_signature_requires_keys() {
+        assert!(SignatureCheck::new("x", None, &[]).unwrap().is_none());
+        assert!(SignatureCheck::new("x", Some("x.minisig"), &[]).is_err());
+    }
+}
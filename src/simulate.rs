@@ -0,0 +1,164 @@
+use crate::config::ShapingConfig;
+use crate::error::Error;
+use crate::ledger::Spans;
+use crate::shaping::Shaper;
+use crate::sources::{EntropySource, SourceMetrics, Tuning};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::{sleep_until, Instant};
+
+/// How slow a source is made to look, for staging setups reproducing the
+/// timing of slower production hardware on fast kernel entropy.
+pub struct Simulation {
+    latency: Duration,
+    rate: Option<u64>,
+}
+
+impl Simulation {
+    pub fn new(latency_ms: Option<u64>, rate_bytes_per_sec: Option<u64>) -> Self {
+        Self { latency: Duration::from_millis(latency_ms.unwrap_or(0)), rate: rate_bytes_per_sec }
+    }
+
+    /// Wraps `source` if anything is simulated for it.
+    pub fn wrap(self, id: &str, source: Arc<dyn EntropySource>) -> Result<Arc<dyn EntropySource>, String> {
+        if self.latency.is_zero() && self.rate.is_none() {
+            return Ok(source);
+        }
+        if self.rate == Some(0) {
+            return Err("simulate_rate_bytes_per_sec must be above 0".to_string());
+        }
+        log::warn!(
+            "SIMULATION: slowing source {} down to {:?} per read and {}",
+            id,
+            self.latency,
+            self.rate.map_or("unlimited bytes/s".to_string(), |r| format!("{} bytes/s", r))
+        );
+        let shaper = self.rate.map(|bytes_per_second| Shaper::new(&ShapingConfig { bytes_per_second, burst_bytes: None }));
+        Ok(Arc::new(SimulatedSource { inner: source, latency: self.latency, rate: self.rate, shaper }))
+    }
+}
+
+/// A source whose reads each take `latency` first, then yield no more than
+/// `shaper` lets through by the request deadline.
+struct SimulatedSource {
+    inner: Arc<dyn EntropySource>,
+    latency: Duration,
+    rate: Option<u64>,
+    shaper: Option<Shaper>,
+}
+
+impl SimulatedSource {
+    fn cap(&self, rate: f64) -> f64 {
+        self.rate.map_or(rate, |cap| rate.min(cap as f64))
+    }
+}
+
+#[async_trait]
+impl EntropySource for SimulatedSource {
+    async fn read_traced(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(timeout_ms);
+        // A device this slow has nothing by a deadline before its latency
+        if start + self.latency > deadline {
+            sleep_until(deadline).await;
+            return Ok((Vec::new(), Spans::default()));
+        }
+        sleep_until(start + self.latency).await;
+        let want = match &self.shaper {
+            Some(shaper) => shaper.admit_some(num_bytes, deadline).await,
+            None => num_bytes,
+        };
+        if want == 0 {
+            return Ok((Vec::new(), Spans::default()));
+        }
+        let left = deadline.saturating_duration_since(Instant::now()).as_millis() as u64;
+        let read = self.inner.read_traced(want, left).await;
+        if let Some(shaper) = &self.shaper {
+            shaper.refund(want - read.as_ref().map_or(0, |(bytes, _)| bytes.len()));
+        }
+        read
+    }
+
+    async fn return_leftover(&self, leftover: Vec<u8>, spans: Spans) {
+        self.inner.return_leftover(leftover, spans).await
+    }
+
+    async fn get_buffer_status(&self) -> (String, Option<(usize, usize)>) {
+        self.inner.get_buffer_status().await
+    }
+
+    fn metrics(&self) -> SourceMetrics {
+        let metrics = self.inner.metrics();
+        SourceMetrics { estimated_rate: metrics.estimated_rate.map(|r| self.cap(r)), ..metrics }
+    }
+
+    async fn benchmark(&self, budget: Duration) -> Option<f64> {
+        self.inner.benchmark(budget).await.map(|r| self.cap(r))
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    fn is_healthy(&self) -> bool {
+        self.inner.is_healthy()
+    }
+
+    async fn resize_buffer(&self, bytes: usize) -> Result<usize, Error> {
+        self.inner.resize_buffer(bytes).await
+    }
+
+    fn tuning(&self) -> Option<&Tuning> {
+        self.inner.tuning()
+    }
+
+    async fn set_paused(&self, paused: bool, drain: bool) {
+        self.inner.set_paused(paused, drain).await
+    }
+
+    fn info(&self) -> HashMap<String, String> {
+        let mut info = self.inner.info();
+        info.insert("simulate_latency_ms".to_string(), self.latency.as_millis().to_string());
+        if let Some(rate) = self.rate {
+            info.insert("simulate_rate_bytes_per_sec".to_string(), rate.to_string());
+        }
+        info
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Zeros;
+
+    #[async_trait]
+    impl EntropySource for Zeros {
+        async fn read_traced(&self, num_bytes: usize, _timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+            Ok((vec![0; num_bytes], Spans::default()))
+        }
+        async fn return_leftover(&self, _leftover: Vec<u8>, _spans: Spans) {}
+        async fn get_buffer_status(&self) -> (String, Option<(usize, usize)>) {
+            ("zeros".to_string(), None)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slows_reads_down() {
+        let source = Simulation::new(Some(50), Some(1000)).wrap("zeros", Arc::new(Zeros)).unwrap();
+        // Nothing before the latency is up
+        assert!(source.read_bytes(100, 20).await.unwrap().is_empty());
+        let start = Instant::now();
+        assert_eq!(source.read_bytes(100, 200).await.unwrap().len(), 100);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        // The rest of the second's worth, then what accrues by the deadline
+        let n = source.read_bytes(5000, 550).await.unwrap().len();
+        let allowed = 900 + start.elapsed().as_millis() as usize + 1;
+        assert!((1300..=allowed).contains(&n), "{} of {}", n, allowed);
+
+        assert_eq!(source.info()["simulate_rate_bytes_per_sec"], "1000");
+        assert!(Simulation::new(None, Some(0)).wrap("zeros", Arc::new(Zeros)).is_err());
+    }
+}
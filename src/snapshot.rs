@@ -0,0 +1,196 @@
+use crate::config::{BUS_NAME, DEFAULT_OBJECT_PATH, MONITOR_INTERFACE};
+use serde_json::Value;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Layout version of `ExportStatsSnapshot` output. Bumped whenever a field
+/// is renamed, removed or changes meaning; new fields do not bump it.
+pub const VERSION: u64 = 1;
+
+/// CBOR self-describe tag (RFC 8949, 3.4.6), so tools recognize the file.
+const SELF_DESCRIBE: [u8; 3] = [0xd9, 0xd9, 0xf7];
+
+const USAGE: &str = "usage: trngctl snapshot [--output PATH] [--force] [--system] [--name NAME] [--object-path PATH]";
+
+/// A major type and argument, in the shortest form.
+fn head(out: &mut Vec<u8>, major: u8, n: u64) {
+    let major = major << 5;
+    match n {
+        0..=23 => out.push(major | n as u8),
+        24..=0xff => out.extend_from_slice(&[major | 24, n as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend_from_slice(&(n as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend_from_slice(&(n as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend_from_slice(&n.to_be_bytes());
+        }
+    }
+}
+
+/// Appends `value` as CBOR. Map keys come out sorted, so equal snapshots
+/// encode to equal bytes.
+fn encode_value(out: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => out.push(0xf6),
+        Value::Bool(false) => out.push(0xf4),
+        Value::Bool(true) => out.push(0xf5),
+        Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+            (Some(n), _) => head(out, 0, n),
+            (None, Some(n)) => head(out, 1, !(n as u64)),
+            _ => {
+                out.push(0xfb);
+                out.extend_from_slice(&n.as_f64().unwrap_or(f64::NAN).to_be_bytes());
+            }
+        },
+        Value::String(s) => {
+            head(out, 3, s.len() as u64);
+            out.extend_from_slice(s.as_bytes());
+        }
+        Value::Array(items) => {
+            head(out, 4, items.len() as u64);
+            items.iter().for_each(|item| encode_value(out, item));
+        }
+        Value::Object(map) => {
+            head(out, 5, map.len() as u64);
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+            for (key, item) in entries {
+                head(out, 3, key.len() as u64);
+                out.extend_from_slice(key.as_bytes());
+                encode_value(out, item);
+            }
+        }
+    }
+}
+
+/// The wire form of a snapshot: tagged CBOR of `snapshot`.
+pub fn encode(snapshot: &Value) -> Vec<u8> {
+    let mut out = SELF_DESCRIBE.to_vec();
+    encode_value(&mut out, snapshot);
+    out
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    output: Option<String>,
+    force: bool,
+    /// Ask the service on the system bus instead of the session bus.
+    system: bool,
+    /// Bus name and object path of the service, for one with `[service]`
+    /// settings of its own.
+    name: Option<String>,
+    object_path: Option<String>,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut opts = Options::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" => opts.output = Some(args.next().ok_or_else(|| format!("--output needs a value\n{}", USAGE))?),
+            "--force" => opts.force = true,
+            "--system" => opts.system = true,
+            "--name" => opts.name = Some(args.next().ok_or_else(|| format!("--name needs a value\n{}", USAGE))?),
+            "--object-path" => opts.object_path = Some(args.next().ok_or_else(|| format!("--object-path needs a value\n{}", USAGE))?),
+            _ => return Err(format!("unknown argument {}\n{}", arg, USAGE)),
+        }
+    }
+    Ok(opts)
+}
+
+/// Runs `trngctl snapshot` (or `trngdbus snapshot`): asks the running service for a stats snapshot
+/// and writes it to `--output` (default `trng-snapshot-<unix time>.cbor`).
+/// Returns the process exit code.
+pub fn run(args: impl IntoIterator<Item = String>) -> i32 {
+    let opts = match parse_args(args) {
+        Ok(opts) => opts,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            return 2;
+        }
+    };
+    let output = opts.output.clone().unwrap_or_else(|| {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        format!("trng-snapshot-{}.cbor", now)
+    });
+    if Path::new(&output).exists() && !opts.force {
+        eprintln!("{} already exists; pass --force to overwrite it", output);
+        return 1;
+    }
+    let fetched = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| e.to_string())
+        .and_then(|runtime| runtime.block_on(fetch(&opts)));
+    match fetched.and_then(|bytes| std::fs::write(&output, &bytes).map(|_| bytes.len()).map_err(|e| format!("cannot write {}: {}", output, e))) {
+        Ok(len) => {
+            println!("Wrote {} ({} bytes, snapshot version {})", output, len, VERSION);
+            0
+        }
+        Err(msg) => {
+            eprintln!("Snapshot failed: {}", msg);
+            1
+        }
+    }
+}
+
+async fn fetch(opts: &Options) -> Result<Vec<u8>, String> {
+    let connection = if opts.system { zbus::Connection::system().await } else { zbus::Connection::session().await };
+    let connection = connection.map_err(|e| e.to_string())?;
+    let name = opts.name.as_deref().unwrap_or(BUS_NAME);
+    let path = opts.object_path.as_deref().unwrap_or(DEFAULT_OBJECT_PATH);
+    let reply = connection
+        .call_method(Some(name), path, Some(MONITOR_INTERFACE), "ExportStatsSnapshot", &())
+        .await
+        .map_err(|e| e.to_string())?;
+    let (status, bytes): (i32, Vec<u8>) = reply.body().deserialize().map_err(|e| e.to_string())?;
+    if status != 0 {
+        return Err(format!("service answered status {}", status));
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn cbor(value: Value) -> String {
+        let mut out = Vec::new();
+        encode_value(&mut out, &value);
+        hex::encode(out)
+    }
+
+    #[test]
+    fn test_rfc8949_vectors() {
+        assert_eq!(cbor(json!(0)), "00");
+        assert_eq!(cbor(json!(23)), "17");
+        assert_eq!(cbor(json!(24)), "1818");
+        assert_eq!(cbor(json!(1000)), "1903e8");
+        assert_eq!(cbor(json!(1_000_000)), "1a000f4240");
+        assert_eq!(cbor(json!(u64::MAX)), "1bffffffffffffffff");
+        assert_eq!(cbor(json!(-1)), "20");
+        assert_eq!(cbor(json!(-1000)), "3903e7");
+        assert_eq!(cbor(json!(1.1)), "fb3ff199999999999a");
+        assert_eq!(cbor(json!([true, false, null])), "83f5f4f6");
+        assert_eq!(cbor(json!({"b": [2, 3], "a": "a"})), "a2616161616162820203");
+        assert!(hex::encode(encode(&json!({}))).starts_with("d9d9f7a0"));
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = |list: &[&str]| parse_args(list.iter().map(|s| s.to_string()));
+        assert_eq!(args(&[]).unwrap(), Options::default());
+        assert_eq!(args(&["--output", "x.cbor", "--force"]).unwrap(), Options { output: Some("x.cbor".to_string()), force: true, ..Options::default() });
+        assert_eq!(args(&["--name", "lv.lumii.trng.Tenant"]).unwrap().name.as_deref(), Some("lv.lumii.trng.Tenant"));
+        assert!(args(&["--system"]).unwrap().system);
+        assert!(args(&["--output"]).is_err());
+        assert!(args(&["--bogus"]).is_err());
+    }
+}
@@ -0,0 +1,86 @@
+/// Bytes at the start of a file source inspected before it is used.
+pub const SAMPLE_BYTES: usize = 1024 * 1024;
+
+/// Fewest bytes the byte distribution is judged on: 16 expected per value.
+const MIN_STATISTICAL_BYTES: usize = 4096;
+
+/// Chi-square of the byte counts above which a sample is not taken for
+/// random. With 255 degrees of freedom random data averages 255 with a
+/// standard deviation of about 22.6, while 1 MiB of deflate or bzip2 output
+/// scores in the thousands and text in the millions.
+pub const CHI_SQUARE_LIMIT: f64 = 500.0;
+
+/// Magic numbers of file formats someone may point a file source at by
+/// mistake, with where they sit. Compressors whose output is as uniform as
+/// random data (xz, zstd, 7z) are only caught here.
+const FORMATS: &[(&str, usize, &[u8])] = &[
+    ("gzip", 0, &[0x1f, 0x8b, 0x08]),
+    ("zstd", 0, &[0x28, 0xb5, 0x2f, 0xfd]),
+    ("xz", 0, &[0xfd, b'7', b'z', b'X', b'Z', 0x00]),
+    ("lz4", 0, &[0x04, 0x22, 0x4d, 0x18]),
+    ("7z", 0, &[b'7', b'z', 0xbc, 0xaf, 0x27, 0x1c]),
+    ("zip", 0, b"PK\x03\x04"),
+    ("tar", 257, b"ustar"),
+    ("ELF", 0, b"\x7fELF"),
+    ("PNG", 0, b"\x89PNG"),
+    ("JPEG", 0, &[0xff, 0xd8, 0xff]),
+    ("PDF", 0, b"%PDF-"),
+];
+
+/// Why `sample`, the start of a file, does not look like random data, if it
+/// does not: it starts like a known file format or, with `statistical`, its
+/// bytes are far from uniformly distributed (e.g. deflate output, text or
+/// an uncompressed archive).
+pub fn suspicious(sample: &[u8], statistical: bool) -> Option<String> {
+    if let Some((name, _, _)) = FORMATS.iter().find(|(_, at, magic)| sample.get(*at..at + magic.len()) == Some(magic)) {
+        return Some(format!("it starts like {} data", name));
+    }
+    if sample.len() >= 4 && sample.starts_with(b"BZh") && (b'1'..=b'9').contains(&sample[3]) {
+        return Some("it starts like bzip2 data".to_string());
+    }
+    if !statistical || sample.len() < MIN_STATISTICAL_BYTES {
+        return None;
+    }
+    let mut counts = [0u64; 256];
+    for &b in sample {
+        counts[b as usize] += 1;
+    }
+    let n = sample.len() as f64;
+    let expected = n / 256.0;
+    let chi_square: f64 = counts.iter().map(|&c| (c as f64 - expected).powi(2) / expected).sum();
+    if chi_square <= CHI_SQUARE_LIMIT {
+        return None;
+    }
+    let bits_per_byte: f64 = counts.iter().filter(|&&c| c > 0).map(|&c| -(c as f64 / n) * (c as f64 / n).log2()).sum();
+    Some(format!(
+        "its first {} bytes are far from uniform (chi-square {:.0}, {:.2} bits per byte), as compressed or structured data is",
+        sample.len(),
+        chi_square,
+        bits_per_byte
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suspicious() {
+        let random = crate::lrng::os_fill_rand_octets(64 * 1024).unwrap();
+        assert_eq!(suspicious(&random, true), None);
+
+        let mut gzip = random.clone();
+        gzip[..3].copy_from_slice(&[0x1f, 0x8b, 0x08]);
+        assert_eq!(suspicious(&gzip, false).as_deref(), Some("it starts like gzip data"));
+        let mut tar = random.clone();
+        tar[257..262].copy_from_slice(b"ustar");
+        assert!(suspicious(&tar, true).unwrap().contains("tar"));
+
+        let text = b"[sources]\ncombine = \"xor\"\n".repeat(4096);
+        assert!(suspicious(&text, true).unwrap().contains("far from uniform"));
+        // Only the magic numbers are checked for sources that debias
+        assert_eq!(suspicious(&text, false), None);
+        // Too short to judge the distribution
+        assert_eq!(suspicious(&text[..1000], true), None);
+    }
+}
@@ -1,329 +1,4178 @@
-use crate::config::{FileConfig, LrngConfig};
+use crate::affinity;
+use crate::audio;
+#[cfg(feature = "grpc")]
+use crate::config::GrpcConfig;
+#[cfg(feature = "nats")]
+use crate::config::NatsConfig;
+use crate::config::{
+    AudioConfig, CpuConfig, CpuInstruction, DbusConfig, ExecConfig, FileConfig, FileWipe, HttpConfig, HwrngConfig, JitterConfig, LrngConfig,
+    MqttConfig, Pkcs11Config, SerialConfig, ShapingConfig, SourceCommon, TcpConfig, DEFAULT_AUDIO_BITS_PER_SAMPLE, DEFAULT_AUDIO_SAMPLE_RATE,
+    DEFAULT_MQTT_MAX_PAYLOAD_BYTES, DEFAULT_MQTT_MIN_PAYLOAD_BYTES, MAX_REPLENISH_CHUNK,
+};
+use crate::cpu;
 use crate::error::Error;
-use crate::lrng::os_fill_rand_octets;
+#[cfg(feature = "grpc")]
+use crate::grpc;
+use crate::http;
+use crate::hwrng;
+use crate::jitter;
+use crate::ledger::{self, Origin, Positions, Span, Spans};
+use crate::leftovers::{LaneStats, ReturnLane};
+use crate::lrng::{os_fill_rand_octets, OsRandomSource};
 use crate::circular_buffer::CircularBuffer;
+use crate::manifest::Manifest;
+use crate::mqtt::{self, Subscriber};
+#[cfg(feature = "nats")]
+use crate::nats;
+use crate::pkcs11;
+use crate::retry::RetryPolicy;
+use crate::serial;
+use crate::shaping::Shaper;
+use crate::signature::{file_identity, FileIdentity, SignatureCheck};
+use crate::shutdown::{self, Stage};
+use crate::sniff;
+use crate::supervisor::{self, Heartbeat, STALL_TIMEOUT};
+use crate::tcp::{self, Connector};
+use crate::transform::Pipeline;
+use crate::upstream::Upstream;
+use crate::wipe;
 use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
 use std::io;
-use std::sync::Arc;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
 use std::time::Duration;
 use tokio::fs::File;
 use tokio::io::{AsyncReadExt, AsyncSeekExt};
-use tokio::time::{sleep_until, Instant, interval};
+use tokio::net::unix::pipe;
+use tokio::sync::Notify;
+use zeroize::Zeroizing;
+use tokio::time::{sleep, sleep_until, Instant, interval, MissedTickBehavior};
 
 #[async_trait]
 pub trait EntropySource: Send + Sync {
-    async fn read_bytes(&self, num_bytes: usize, timeout_ms: u64) -> Result<Vec<u8>, Error>;
-    async fn return_leftover(&self, leftover: Vec<u8>);
+    /// `read_bytes`, also saying where in the source's read stream each byte
+    /// sits (see `ledger.rs`).
+    async fn read_traced(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error>;
+    async fn read_bytes(&self, num_bytes: usize, timeout_ms: u64) -> Result<Vec<u8>, Error> {
+        Ok(self.read_traced(num_bytes, timeout_ms).await?.0)
+    }
+    /// The buffer, return lane and replenish settings of a buffered source,
+    /// which the methods below that deal with them default to.
+    fn core(&self) -> Option<&BufferCore> {
+        None
+    }
+    /// Hands back bytes of a `read_traced` answer, with their spans.
+    async fn return_leftover(&self, leftover: Vec<u8>, spans: Spans) {
+        match self.core() {
+            Some(core) => core.return_leftover(leftover, spans),
+            None => drop(Zeroizing::new(leftover)),
+        }
+    }
     async fn get_buffer_status(&self) -> (String, Option<(usize, usize)>); // (id, Some(current_size, max_size)) or None
+    fn metrics(&self) -> SourceMetrics {
+        SourceMetrics::default()
+    }
+    /// Measures the sustainable rate of the underlying device in bytes/s by
+    /// reading from it directly for about `budget`; the bytes read go into
+    /// the buffer where there is room. `None` if nothing could be read.
+    async fn benchmark(&self, _budget: Duration) -> Option<f64> {
+        None
+    }
+    /// False while the source cannot serve at all; the aggregator skips it.
+    fn is_available(&self) -> bool {
+        true
+    }
+    /// False once the source has failed its integrity or self checks.
+    fn is_healthy(&self) -> bool {
+        true
+    }
+    /// Changes the buffer capacity, keeping as much buffered entropy as fits.
+    /// Returns the number of bytes that had to be discarded.
+    async fn resize_buffer(&self, bytes: usize) -> Result<usize, Error> {
+        match self.core() {
+            Some(core) => core.resize(bytes).await,
+            None => Err(Error::InvalidOption("source_id".to_string())),
+        }
+    }
+    /// Live replenish settings, for buffered sources.
+    fn tuning(&self) -> Option<&Tuning> {
+        self.core().and_then(BufferCore::tuning)
+    }
+    /// Stops or resumes background replenishing for a maintenance window;
+    /// `drain` also zeroizes what is buffered.
+    async fn set_paused(&self, paused: bool, drain: bool) {
+        if let Some(core) = self.core() {
+            core.set_paused(paused, drain).await
+        }
+    }
+    /// What the source knows about the device behind it beyond its config,
+    /// such as the driver a pass-through device currently reads from.
+    fn info(&self) -> HashMap<String, String> {
+        HashMap::new()
+    }
+}
+
+/// Largest buffer `SetSourceBufferSize` accepts.
+const MAX_BUFFER_BYTES: usize = 1 << 30;
+
+/// Replenish settings of a buffered source that can be changed while it runs.
+pub struct Tuning {
+    /// Refilling starts once the buffer is below this percentage (1-100).
+    low_watermark: AtomicU8,
+    /// Once started, refilling goes on up to this percentage (1-100, never
+    /// below the low watermark); 0 for sources that refill step by step.
+    high_watermark: AtomicU8,
+    /// Most bytes read per replenish step.
+    chunk: AtomicUsize,
+    /// Set during a maintenance window; nothing is replenished.
+    paused: AtomicBool,
+    /// Wakes a replenisher that waits for it when the buffer was drained or
+    /// the settings changed.
+    drained: Notify,
+}
+
+impl Tuning {
+    fn new(low_watermark: u8, chunk: usize) -> Self {
+        Self {
+            low_watermark: AtomicU8::new(low_watermark),
+            high_watermark: AtomicU8::new(0),
+            chunk: AtomicUsize::new(chunk),
+            paused: AtomicBool::new(false),
+            drained: Notify::new(),
+        }
+    }
+
+    fn with_high_watermark(self, high_watermark: u8) -> Self {
+        self.high_watermark.store(high_watermark, Ordering::Relaxed);
+        self
+    }
+
+    fn wants_refill(&self, len: usize, capacity: usize) -> bool {
+        below(len, capacity, self.low_watermark.load(Ordering::Relaxed)) && !self.is_paused()
+    }
+
+    /// Whether a refill that has started should go on at this fill level.
+    fn keeps_refilling(&self, len: usize, capacity: usize) -> bool {
+        let high = self.high_watermark.load(Ordering::Relaxed).max(self.low_watermark.load(Ordering::Relaxed));
+        below(len, capacity, high) && !self.is_paused()
+    }
+
+    fn notify_drained(&self) {
+        self.drained.notify_one();
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    fn chunk(&self) -> usize {
+        self.chunk.load(Ordering::Relaxed)
+    }
+
+    /// Applies the given settings after checking all of them.
+    pub fn set(&self, low_watermark: Option<u8>, high_watermark: Option<u8>, chunk: Option<usize>) -> Result<(), Error> {
+        if low_watermark.is_some_and(|w| !(1..=100).contains(&w)) {
+            return Err(Error::InvalidOption("low_watermark_percent".to_string()));
+        }
+        let hysteresis = self.high_watermark.load(Ordering::Relaxed) > 0;
+        if high_watermark.is_some_and(|w| !hysteresis || !(1..=100).contains(&w)) {
+            return Err(Error::InvalidOption("high_watermark_percent".to_string()));
+        }
+        if chunk.is_some_and(|c| c == 0 || c > MAX_REPLENISH_CHUNK) {
+            return Err(Error::InvalidOption("replenish_chunk".to_string()));
+        }
+        if let Some(w) = low_watermark {
+            self.low_watermark.store(w, Ordering::Relaxed);
+        }
+        if let Some(w) = high_watermark {
+            self.high_watermark.store(w, Ordering::Relaxed);
+        }
+        if let Some(c) = chunk {
+            self.chunk.store(c, Ordering::Relaxed);
+        }
+        self.notify_drained();
+        Ok(())
+    }
+}
+
+/// Whether `len` bytes are below `percent` of `capacity`.
+fn below(len: usize, capacity: usize, percent: u8) -> bool {
+    len < capacity && (len as u128) * 100 < capacity as u128 * percent as u128
+}
+
+/// Locks a source buffer, first discarding bytes older than its `max_age_seconds`.
+async fn lock_fresh<'a>(buffer: &'a tokio::sync::Mutex<CircularBuffer>, id: &str) -> tokio::sync::MutexGuard<'a, CircularBuffer> {
+    let mut guard = buffer.lock().await;
+    let expired = guard.expire();
+    if expired > 0 {
+        log::debug!("Source {} discarded {} buffered bytes past their max age", id, expired);
+    }
+    guard
+}
+
+/// `lock_fresh` for the request path: gives up at `deadline` (after one
+/// attempt if it has passed), so a replenisher or another request holding
+/// the buffer cannot make a request miss its timeout.
+async fn lock_fresh_until<'a>(
+    buffer: &'a tokio::sync::Mutex<CircularBuffer>,
+    id: &str,
+    deadline: Instant,
+) -> Option<tokio::sync::MutexGuard<'a, CircularBuffer>> {
+    let guard = tokio::time::timeout_at(deadline, lock_fresh(buffer, id)).await.ok();
+    if guard.is_none() {
+        log::debug!("Source {} buffer still locked at the request deadline", id);
+    }
+    guard
+}
+
+/// `(len, capacity)` of a source buffer, not counting expired bytes.
+async fn buffer_fill(buffer: &tokio::sync::Mutex<CircularBuffer>, id: &str) -> (usize, usize) {
+    let buffer = lock_fresh(buffer, id).await;
+    (buffer.len(), buffer.capacity())
+}
+
+fn max_age(seconds: Option<u64>) -> Option<Duration> {
+    seconds.filter(|s| *s > 0).map(Duration::from_secs)
+}
+
+/// Hands leftovers to the return lane, unless the source has a max age:
+/// how old they are is not known, so they are zeroized then.
+fn return_to_lane(lane: &ReturnLane, id: &str, max_age_seconds: Option<u64>, leftover: Vec<u8>, spans: Spans) {
+    if leftover.is_empty() {
+        return;
+    }
+    if max_age(max_age_seconds).is_some() {
+        lane.discard(leftover);
+        return;
+    }
+    log::debug!("Source {} got {} leftover bytes back", id, leftover.len());
+    lane.put(leftover, spans);
+}
+
+/// What every buffered source is built around: the buffer its background
+/// task fills, the lane for leftovers handed back and the live replenish
+/// settings. The buffer and the lane are zeroized at shutdown.
+pub struct BufferCore {
+    id: String,
+    max_age_seconds: Option<u64>,
+    buffer: Arc<tokio::sync::Mutex<CircularBuffer>>,
+    /// Leftovers handed back by the aggregator, served before the buffer.
+    lane: Arc<ReturnLane>,
+    /// Whether `buffer` is in use; its capacity can change at runtime.
+    enabled: bool,
+    tuning: Arc<Tuning>,
+    /// Woken whenever bytes land in `buffer`, for readers that wait for them.
+    filled: Arc<Notify>,
+}
+
+impl BufferCore {
+    /// A buffer of `capacity` bytes for source `id`, whose kind `what` names
+    /// in logs; sources without a buffer configured pass `enabled` false.
+    fn new(what: &str, id: &str, common: &SourceCommon, capacity: usize, enabled: bool, tuning: Tuning) -> Self {
+        let buffer = CircularBuffer::with_max_age(capacity, max_age(common.max_age_seconds));
+        Self::with_buffer(what, id, common, buffer, enabled, tuning)
+    }
+
+    /// `new` around a buffer the source already holds a handle of.
+    fn with_buffer(what: &str, id: &str, common: &SourceCommon, buffer: CircularBuffer, enabled: bool, tuning: Tuning) -> Self {
+        let buffer = Arc::new(tokio::sync::Mutex::new(buffer));
+        let wipe = buffer.clone();
+        let name = format!("{} {} buffer", what, id);
+        shutdown::register(Stage::Zeroize, name.clone(), move || shutdown::wipe_buffer(&wipe, &name));
+        let lane = Arc::new(ReturnLane::default());
+        let wipe = lane.clone();
+        shutdown::register(Stage::Zeroize, format!("{} {} return lane", what, id), move || wipe.wipe());
+        Self {
+            id: id.to_string(),
+            max_age_seconds: common.max_age_seconds,
+            buffer,
+            lane,
+            enabled,
+            tuning: Arc::new(tuning),
+            filled: Arc::new(Notify::new()),
+        }
+    }
+
+    fn return_leftover(&self, leftover: Vec<u8>, spans: Spans) {
+        return_to_lane(&self.lane, &self.id, self.max_age_seconds, leftover, spans);
+    }
+
+    /// `get_buffer_status` of the source.
+    async fn status(&self) -> (String, Option<(usize, usize)>) {
+        let fill = if self.enabled { Some(buffer_fill(&self.buffer, &self.id).await) } else { None };
+        (self.id.clone(), fill)
+    }
+
+    async fn resize(&self, bytes: usize) -> Result<usize, Error> {
+        if !self.enabled {
+            return Err(Error::InvalidOption("source_id".to_string()));
+        }
+        if bytes == 0 || bytes > MAX_BUFFER_BYTES {
+            return Err(Error::InvalidOption("bytes".to_string()));
+        }
+        let mut buffer = self.buffer.lock().await;
+        let old = buffer.capacity();
+        let discarded = buffer.resize(bytes);
+        log::info!("Source {} buffer resized: {} -> {} bytes ({} discarded)", self.id, old, bytes, discarded);
+        Ok(discarded)
+    }
+
+    fn tuning(&self) -> Option<&Tuning> {
+        self.enabled.then_some(&*self.tuning)
+    }
+
+    async fn set_paused(&self, paused: bool, drain: bool) {
+        self.tuning.paused.store(paused, Ordering::Relaxed);
+        if drain {
+            self.buffer.lock().await.wipe();
+            self.lane.wipe();
+        }
+        if !paused {
+            self.tuning.notify_drained();
+        }
+    }
+
+    /// Serves leftovers, then the buffer, waiting for bytes to land in it
+    /// until the deadline (never for timeout 0), for sources whose
+    /// background task is the only reader of their device.
+    async fn serve_buffered(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+        self.lane.serve(num_bytes, |n| self.take_waiting(n, timeout_ms)).await
+    }
+
+    async fn take_waiting(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        let mut result = Vec::with_capacity(num_bytes);
+        let mut spans = Spans::default();
+        loop {
+            // Registered before looking, so bytes landing in between wake us
+            let filled = self.filled.notified();
+            tokio::pin!(filled);
+            filled.as_mut().enable();
+            let Some(mut buffer) = lock_fresh_until(&self.buffer, &self.id, deadline).await else { break };
+            let (bytes, taken) = buffer.take_traced(num_bytes - result.len());
+            drop(buffer);
+            result.extend_from_slice(&Zeroizing::new(bytes));
+            spans.append(taken);
+            if result.len() == num_bytes || timeout_ms == 0 {
+                break;
+            }
+            if tokio::time::timeout_at(deadline, filled).await.is_err() {
+                break;
+            }
+        }
+        Ok((result, spans))
+    }
+}
+
+/// Counters a source reports alongside its buffer status.
+#[derive(Debug, Default, Clone)]
+pub struct SourceMetrics {
+    /// Retries performed by the source's `RetryPolicy` (reopen, re-init, reconnect).
+    pub retries: u64,
+    /// Sustainable rate in bytes/s from the last benchmark, if one ran.
+    pub estimated_rate: Option<f64>,
+    /// Leftover bytes handed back, served again and dropped.
+    pub leftovers: LaneStats,
+    /// Messages taken from each publisher, for sources several feed (MQTT).
+    pub publishers: BTreeMap<String, PublisherStats>,
+}
+
+/// What a message-based source accepted from one publisher and why it
+/// dropped the rest.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PublisherStats {
+    pub messages: u64,
+    pub bytes: u64,
+    /// Accepted, but the buffer was full or paused.
+    pub overflowed: u64,
+    /// Shorter than `min_payload_bytes` or longer than `max_payload_bytes`.
+    pub wrong_size: u64,
+    /// One byte value throughout, as a stuck sensor sends.
+    pub constant: u64,
+    /// The same payload as this publisher's previous one.
+    pub repeated: u64,
+    /// Retained by the broker, and so sent on every subscription.
+    pub retained: u64,
+}
+
+/// Bytes read per step while benchmarking.
+const BENCHMARK_CHUNK: usize = 64 * 1024;
+
+/// Calls `read` for about `budget` and returns the achieved rate in bytes/s,
+/// storing it in `rate` (as `f64` bits). `read` returns the bytes it read
+/// and offers them to the source's buffer.
+async fn measure<F, Fut>(rate: &AtomicU64, budget: Duration, mut read: F) -> Option<f64>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<usize, Error>>,
+{
+    let start = Instant::now();
+    let mut total = 0usize;
+    while start.elapsed() < budget {
+        match read().await {
+            Ok(n) if n > 0 => total += n,
+            _ => break,
+        }
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+    let measured = (total > 0 && elapsed > 0.0).then(|| total as f64 / elapsed)?;
+    rate.store(measured.to_bits(), Ordering::Relaxed);
+    Some(measured)
+}
+
+fn estimated_rate(rate: &AtomicU64) -> Option<f64> {
+    let rate = f64::from_bits(rate.load(Ordering::Relaxed));
+    (rate > 0.0).then_some(rate)
+}
+
+/// Longest a throttled replenisher waits for its next chunk.
+const THROTTLE_STEP: Duration = Duration::from_millis(500);
+
+/// The bucket behind a source's `max_bytes_per_sec`, shared by its
+/// replenisher and its direct reads.
+fn throttle(max_bytes_per_sec: Option<u64>) -> Option<Arc<Shaper>> {
+    let cfg = |bytes_per_second| ShapingConfig { bytes_per_second, burst_bytes: None };
+    max_bytes_per_sec.map(|rate| Arc::new(Shaper::new(&cfg(rate))))
+}
+
+/// How many of `want` bytes may be read from a source by `deadline`,
+/// waiting for them: all of them unless it is throttled.
+async fn allowance(throttle: Option<&Shaper>, want: usize, deadline: Instant) -> usize {
+    match throttle {
+        Some(throttle) => throttle.admit_some(want, deadline).await,
+        None => want,
+    }
 }
 
 pub struct LrngSource {
     cfg: LrngConfig,
-    buffer: Arc<tokio::sync::Mutex<CircularBuffer>>,
-    max_buffer_size: Option<usize>,
+    core: BufferCore,
+    /// The buffer's read-stream numbering, shared with unbuffered reads.
+    positions: Positions,
+    throttle: Option<Arc<Shaper>>,
+    retries: Arc<AtomicU64>,
+    rate: AtomicU64,
 }
 
 impl LrngSource {
     pub fn new(cfg: LrngConfig) -> Self {
         let max_buffer_size = cfg.buffer_mebibytes.map(|mb| mb as usize * 1024 * 1024);
-        let buffer = Arc::new(tokio::sync::Mutex::new(
-            CircularBuffer::new(max_buffer_size.unwrap_or(1024))
-        ));
+        let buffer = CircularBuffer::with_max_age(max_buffer_size.unwrap_or(1024), max_age(cfg.common.max_age_seconds));
+        let positions = buffer.positions();
+        let retries = Arc::new(AtomicU64::new(0));
         
+        // Refill whenever the buffer is not full, up to full, in 64 KiB steps, unless configured otherwise
+        let tuning = Tuning::new(cfg.low_watermark_percent.unwrap_or(100), cfg.replenish_chunk_bytes.unwrap_or(64 * 1024));
+        let tuning = tuning.with_high_watermark(cfg.high_watermark_percent.unwrap_or(100));
+        let core = BufferCore::with_buffer("LRNG", &cfg.id, &cfg.common, buffer, max_buffer_size.is_some(), tuning);
+        let every = Duration::from_millis(cfg.replenish_interval_ms.unwrap_or(10));
+        let throttle = throttle(cfg.max_bytes_per_sec);
+
         // Start background replenishing if buffer is configured
-        if let Some(max_size) = max_buffer_size {
-            let buffer_clone = buffer.clone();
+        if max_buffer_size.is_some() {
+            let buffer_clone = core.buffer.clone();
+            let tuning_clone = core.tuning.clone();
+            let throttle_clone = throttle.clone();
             let id = cfg.id.clone();
-            tokio::spawn(async move {
-                Self::background_replenish(buffer_clone, max_size, id).await;
+            let policy = RetryPolicy::from_config(cfg.retry.as_ref());
+            let retries_clone = retries.clone();
+            let cpus = cfg.common.cpus.clone();
+            supervisor::spawn(format!("lrng-replenish:{}", id), Some(STALL_TIMEOUT), move |heartbeat| {
+                let work = Self::background_replenish(
+                    buffer_clone.clone(),
+                    tuning_clone.clone(),
+                    throttle_clone.clone(),
+                    every,
+                    id.clone(),
+                    policy.clone(),
+                    retries_clone.clone(),
+                    heartbeat,
+                );
+                affinity::on_cpus(format!("lrng-{}", id), cpus.clone(), work)
             });
         }
         
-        Self { 
+        Self {
             cfg,
-            buffer,
-            max_buffer_size,
+            core,
+            positions,
+            throttle,
+            retries,
+            rate: AtomicU64::new(0),
         }
     }
     
-    async fn background_replenish(buffer: Arc<tokio::sync::Mutex<CircularBuffer>>, max_size: usize, id: String) {
-        let mut interval = interval(Duration::from_millis(10)); // Check more frequently
+    #[allow(clippy::too_many_arguments)]
+    async fn background_replenish(
+        buffer: Arc<tokio::sync::Mutex<CircularBuffer>>,
+        tuning: Arc<Tuning>,
+        throttle: Option<Arc<Shaper>>,
+        every: Duration,
+        id: String,
+        policy: RetryPolicy,
+        retries: Arc<AtomicU64>,
+        heartbeat: Heartbeat,
+    ) {
+        let mut interval = interval(every);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
         loop {
-            interval.tick().await;
-            let current_size = {
-                let buf = buffer.lock().await;
-                buf.len()
-            };
-            
-            while current_size < max_size {
-                let needed = max_size - current_size;
+            // Requests wake the replenisher as they drain the buffer; the
+            // interval catches bytes that expired meanwhile
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = tuning.drained.notified() => {}
+            }
+            heartbeat.beat();
+            let (mut current_size, mut max_size) = buffer_fill(&buffer, &id).await;
+            if !tuning.wants_refill(current_size, max_size) {
+                continue;
+            }
+            // Once below the low watermark, fill up to the high one
+            while tuning.keeps_refilling(current_size, max_size) {
                 // Generate in chunks to avoid blocking too long
-                let chunk_size = (needed).min(64 * 1024); // 64KB chunks
-                if let Ok(new_bytes) = tokio::task::spawn_blocking(move || os_fill_rand_octets(chunk_size)).await {
-                    if let Ok(bytes) = new_bytes {
-                        let mut buf = buffer.lock().await;
-                        buf.extend_from_vec(bytes);
-                        log::debug!("LRNG {} replenished buffer: {} -> {} bytes", id, current_size, buf.len());
+                let chunk_size = (max_size - current_size).min(tuning.chunk());
+                let chunk_size = allowance(throttle.as_deref(), chunk_size, Instant::now() + THROTTLE_STEP).await;
+                if chunk_size == 0 {
+                    break;
+                }
+                let fill = policy.run("LRNG refill", &retries, || async move {
+                    tokio::task::spawn_blocking(move || os_fill_rand_octets(chunk_size))
+                        .await
+                        .map_err(|_| Error::Unexpected)?
+                }).await;
+                if let (Err(_), Some(throttle)) = (&fill, &throttle) {
+                    throttle.refund(chunk_size);
+                }
+                match fill {
+                    Ok(bytes) => {
+                        // Requests may have drained the buffer meanwhile, or resized it
+                        let mut buf = lock_fresh(&buffer, &id).await;
+                        let before = buf.len();
+                        record_lrng_read(&id, buf.extend_from_vec(bytes));
+                        heartbeat.beat();
+                        log::debug!("LRNG {} replenished buffer: {} -> {} bytes", id, before, buf.len());
+                        (current_size, max_size) = (buf.len(), buf.capacity());
                     }
+                    Err(_) => break,
                 }
             }
         }
     }
 }
 
-#[async_trait]
-impl EntropySource for LrngSource {
-    async fn read_bytes(&self, num_bytes: usize, timeout_ms: u64) -> Result<Vec<u8>, Error> {
-        // If buffer is disabled, directly generate from Linux (ignore timeout)
-        if self.max_buffer_size.is_none() {
-            return tokio::task::spawn_blocking(move || os_fill_rand_octets(num_bytes))
+/// Records an LRNG read of `span` in the provenance ledger.
+fn record_lrng_read(id: &str, span: Span) {
+    ledger::record_read(id, span, Origin::Device(OsRandomSource::BACKEND));
+}
+
+impl LrngSource {
+    /// Numbers bytes served straight from the OS source.
+    fn claim(&self, len: usize) -> Spans {
+        let span = self.positions.claim(len);
+        record_lrng_read(&self.cfg.id, span);
+        span.into()
+    }
+
+    /// Serves from the buffer, then straight from the OS source.
+    async fn read_fresh(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        // If buffer is disabled, directly generate from Linux (ignoring the
+        // timeout unless throttled)
+        if !self.core.enabled {
+            let num_bytes = allowance(self.throttle.as_deref(), num_bytes, deadline).await;
+            if num_bytes == 0 {
+                return Ok((Vec::new(), Spans::default()));
+            }
+            let bytes = tokio::task::spawn_blocking(move || os_fill_rand_octets(num_bytes))
                 .await
-                .map_err(|_| Error::Unexpected)?;
+                .map_err(|_| Error::Unexpected)??;
+            let spans = self.claim(bytes.len());
+            return Ok((bytes, spans));
         }
         
-        // Buffer is enabled - use buffered approach
-        // Fast path: try to satisfy from buffer first
-        {
-            let mut buffer = self.buffer.lock().await;
-            if buffer.len() >= num_bytes {
-                let result = buffer.take(num_bytes);
-                return Ok(result);
-            }
+        // Buffer is enabled - serve from it first; for timeout 0 only from it
+        let Some(mut buffer) = lock_fresh_until(&self.core.buffer, &self.cfg.id, deadline).await else {
+            return Ok((Vec::new(), Spans::default()));
+        };
+        let (mut result, mut spans) = buffer.take_traced(num_bytes);
+        drop(buffer);
+        if !result.is_empty() {
+            self.core.tuning.notify_drained();
         }
-        
-        // For timeout 0, return only what's in buffer (don't generate)
-        if timeout_ms == 0 {
-            let mut buffer = self.buffer.lock().await;
-            let result = buffer.take(num_bytes);
-            return Ok(result);
+        if result.len() == num_bytes || timeout_ms == 0 {
+            return Ok((result, spans));
         }
-        
-        // For non-zero timeout, use buffer
-        let mut result = {
-            let mut buffer = self.buffer.lock().await;
-            let buf_len = buffer.len();
-            buffer.take(buf_len) // Take everything from buffer
-        };
-        let remaining = num_bytes.saturating_sub(result.len());
-        
-        if remaining > 0 {
-            // Buffer already dropped from scope
-            let deadline = Instant::now() + Duration::from_millis(timeout_ms);
-            let sleep = sleep_until(deadline);
-            tokio::pin!(sleep);
-            let task = tokio::task::spawn_blocking(move || os_fill_rand_octets(remaining));
-            tokio::select! {
-                res = task => {
-                    let bytes = res.map_err(|_| Error::Unexpected)??;
-                    result.extend(bytes);
-                }
-                _ = &mut sleep => {
-                    // Timeout reached, return what we have
+        let remaining = allowance(self.throttle.as_deref(), num_bytes - result.len(), deadline).await;
+        if remaining == 0 {
+            return Ok((result, spans));
+        }
+        let sleep = sleep_until(deadline);
+        tokio::pin!(sleep);
+        let (tx, mut rx) = tokio::sync::oneshot::channel();
+        let (buffer, lane, tuning) = (self.core.buffer.clone(), self.core.lane.clone(), self.core.tuning.clone());
+        let (id, max_age_seconds, positions) = (self.cfg.id.clone(), self.cfg.common.max_age_seconds, self.positions.clone());
+        tokio::task::spawn_blocking(move || {
+            // Output that comes too late for this request is kept for the next ones
+            if let Err(Ok(bytes)) = tx.send(os_fill_rand_octets(remaining)) {
+                if tuning.is_paused() {
+                    lane.discard(bytes);
+                    return;
                 }
+                let mut bytes = Zeroizing::new(bytes);
+                let mut buffer = buffer.blocking_lock();
+                let kept = bytes.len().min(buffer.available_space());
+                record_lrng_read(&id, buffer.extend(&bytes[..kept]));
+                drop(buffer);
+                log::debug!("LRNG {} kept {} bytes generated past a request deadline", &id, bytes.len());
+                let rest = positions.claim(bytes.len() - kept);
+                record_lrng_read(&id, rest);
+                return_to_lane(&lane, &id, max_age_seconds, bytes.split_off(kept), rest.into());
+            }
+        });
+        let res = tokio::select! {
+            res = &mut rx => res.ok(),
+            _ = &mut sleep => {
+                // Timeout reached; take the bytes only if they arrived meanwhile
+                rx.close();
+                rx.try_recv().ok()
             }
+        };
+        if let Some(res) = res {
+            let bytes = Zeroizing::new(res?);
+            result.extend_from_slice(&bytes);
+            spans.append(self.claim(bytes.len()));
         }
         
-        Ok(result)
+        Ok((result, spans))
     }
+}
 
-    async fn return_leftover(&self, leftover: Vec<u8>) {
-        // If buffer is disabled, we can't return leftover bytes - just drop them
-        if self.max_buffer_size.is_none() {
-            return;
-        }
-        
-        if !leftover.is_empty() {
-            let leftover_len = leftover.len();
-            let mut buffer = self.buffer.lock().await;
-            buffer.extend_from_vec(leftover);
-            log::debug!("LRNG returned {} leftover bytes to buffer (total: {})", leftover_len, buffer.len());
-        }
+#[async_trait]
+impl EntropySource for LrngSource {
+    async fn read_traced(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+        self.core.lane.serve(num_bytes, |n| self.read_fresh(n, timeout_ms)).await
     }
-    
+
+    fn core(&self) -> Option<&BufferCore> {
+        Some(&self.core)
+    }
+
     async fn get_buffer_status(&self) -> (String, Option<(usize, usize)>) {
-        let id = self.cfg.id.clone();
-        if let Some(max_size) = self.max_buffer_size {
-            let current_size = self.buffer.lock().await.len();
-            (id, Some((current_size, max_size)))
-        } else {
-            (id, None)
+        self.core.status().await
+    }
+
+    async fn resize_buffer(&self, bytes: usize) -> Result<usize, Error> {
+        let discarded = self.core.resize(bytes).await?;
+        self.core.tuning.notify_drained();
+        Ok(discarded)
+    }
+
+    fn metrics(&self) -> SourceMetrics {
+        SourceMetrics {
+            retries: self.retries.load(Ordering::Relaxed),
+            estimated_rate: estimated_rate(&self.rate),
+            leftovers: self.core.lane.stats(),
+            ..SourceMetrics::default()
         }
     }
+
+    async fn benchmark(&self, budget: Duration) -> Option<f64> {
+        measure(&self.rate, budget, || async {
+            let n = allowance(self.throttle.as_deref(), BENCHMARK_CHUNK, Instant::now() + THROTTLE_STEP).await;
+            if n == 0 {
+                return Ok(0);
+            }
+            let chunk = tokio::task::spawn_blocking(move || os_fill_rand_octets(n))
+                .await
+                .map_err(|_| Error::Unexpected)??;
+            let n = chunk.len();
+            if self.core.enabled {
+                record_lrng_read(&self.cfg.id, self.core.buffer.lock().await.extend_from_vec(chunk));
+            }
+            Ok(n)
+        })
+        .await
+    }
 }
 
-pub struct FileSource {
-    cfg: FileConfig,
-    file: tokio::sync::Mutex<File>,
-    offset: tokio::sync::Mutex<u64>,
+/// Sequential reader over a file, optionally verified against a `Manifest`.
+///
+/// With a manifest the file is read one whole chunk at a time and bytes of a
+/// chunk are only handed out after its digest has been checked.
+struct FileCursor {
+    path: String,
+    file: File,
+    /// The FIFO behind `file` under `stream`, read instead of it.
+    pipe: Option<pipe::Receiver>,
+    /// What was opened, for the provenance ledger.
+    identity: Option<FileIdentity>,
+    /// Bytes read so far; the position in a stream, which is never seeked.
+    offset: u64,
     loop_on_eof: bool,
-    buffer: Arc<tokio::sync::Mutex<CircularBuffer>>,
-    max_buffer_size: Option<usize>,
+    /// Set for `stream = true`: the path is read as it comes, never seeked.
+    stream: bool,
+    manifest: Option<Arc<Manifest>>,
+    chunk_index: usize,
+    verified: Vec<u8>,
+    verified_pos: usize,
+    /// Destroys everything read before it is handed out.
+    wipe: Option<FileWipe>,
+    /// Transforms what is read before it is buffered.
+    transforms: Option<Pipeline>,
 }
 
-impl FileSource {
-    pub async fn new(cfg: FileConfig) -> io::Result<Self> {
-        let file = File::open(&cfg.path).await?;
-        let max_buffer_size = cfg.buffer_mebibytes.map(|mb| mb as usize * 1024 * 1024);
-        let buffer = Arc::new(tokio::sync::Mutex::new(
-            CircularBuffer::new(max_buffer_size.unwrap_or(1024))
-        ));
-        
-        // Start background replenishing if buffer is configured
-        if let Some(max_size) = max_buffer_size {
-            let buffer_clone = buffer.clone();
-            let path = cfg.path.clone();
-            let id = cfg.id.clone();
-            let loop_on_eof = cfg.loop_.unwrap_or(false);
-            tokio::spawn(async move {
-                Self::background_replenish(buffer_clone, max_size, path, id, loop_on_eof).await;
-            });
+impl FileCursor {
+    /// Opens `path` at its start or, with `wipe`, where the part that
+    /// earlier runs did not wipe starts.
+    async fn open(path: &str, loop_on_eof: bool, stream: bool, manifest: Option<Arc<Manifest>>, wipe: Option<FileWipe>) -> io::Result<Self> {
+        let (file, pipe) = Self::open_file(path, wipe, stream).await?;
+        let mut offset = 0;
+        if wipe.is_some() {
+            let chunk_bytes = manifest.as_ref().map(|m| m.chunk_bytes() as u64);
+            let std_file = file.try_clone().await?.into_std().await;
+            offset = tokio::task::spawn_blocking(move || wipe::unwiped_start(&std_file, chunk_bytes)).await.map_err(io::Error::other)??;
+            if offset > 0 {
+                log::info!("File {} resumes at offset {}, past what earlier runs wiped", path, offset);
+            }
         }
-        
+        let chunk_index = manifest.as_ref().map_or(0, |m| (offset / m.chunk_bytes() as u64) as usize);
         Ok(Self {
-            cfg: cfg.clone(),
-            file: tokio::sync::Mutex::new(file),
-            offset: tokio::sync::Mutex::new(0),
-            loop_on_eof: cfg.loop_.unwrap_or(false),
-            buffer,
-            max_buffer_size,
+            path: path.to_string(),
+            file,
+            pipe,
+            identity: file_identity(path).ok(),
+            offset,
+            loop_on_eof,
+            stream,
+            manifest,
+            chunk_index,
+            verified: Vec::new(),
+            verified_pos: 0,
+            wipe,
+            transforms: None,
         })
     }
-    
-    async fn background_replenish(buffer: Arc<tokio::sync::Mutex<CircularBuffer>>, max_size: usize, path: String, id: String, loop_on_eof: bool) {
-        let mut interval = interval(Duration::from_secs(1));
-        let mut file = match File::open(&path).await {
-            Ok(f) => f,
-            Err(_) => return,
-        };
-        let mut offset = 0u64;
-        
-        loop {
-            interval.tick().await;
-            let current_size = buffer.lock().await.len();
-            if current_size < max_size / 2 { // Replenish when below 50%
-                let needed = max_size - current_size;
-                let mut buf = vec![0u8; needed];
-                let mut bytes_read = 0;
-                
-                while bytes_read < needed {
-                    file.seek(tokio::io::SeekFrom::Start(offset)).await.ok();
-                    match file.read(&mut buf[bytes_read..]).await {
-                        Ok(0) if loop_on_eof => {
-                            file.seek(tokio::io::SeekFrom::Start(0)).await.ok();
-                            offset = 0;
-                        }
-                        Ok(0) => break,
-                        Ok(n) => {
-                            offset += n as u64;
-                            bytes_read += n;
-                        }
-                        Err(_) => break,
-                    }
-                }
-                
-                if bytes_read > 0 {
-                    buf.truncate(bytes_read);
-                    let mut buffer_guard = buffer.lock().await;
-                    buffer_guard.extend_from_vec(buf);
-                    log::debug!("File {} replenished buffer: {} -> {} bytes", id, current_size, buffer_guard.len());
-                }
-            }
+
+    /// Opens `path` for reading, and for writing if it is wiped. A FIFO
+    /// under `stream` is opened for writing too, which neither waits for a
+    /// writer nor sees end of file between writers, and is also returned
+    /// for reading without a blocking thread.
+    async fn open_file(path: &str, wipe: Option<FileWipe>, stream: bool) -> io::Result<(File, Option<pipe::Receiver>)> {
+        use std::os::unix::fs::{FileTypeExt, OpenOptionsExt};
+        if stream && tokio::fs::metadata(path).await?.file_type().is_fifo() {
+            let fifo = std::fs::OpenOptions::new().read(true).write(true).custom_flags(libc::O_NONBLOCK).open(path)?;
+            let pipe = pipe::Receiver::from_file(fifo.try_clone()?)?;
+            return Ok((File::from_std(fifo), Some(pipe)));
         }
+        let file = match wipe {
+            Some(_) => tokio::fs::OpenOptions::new().read(true).write(true).open(path).await?,
+            None => File::open(path).await?,
+        };
+        Ok((file, None))
+    }
+
+    /// Replace the file handle (e.g. after a device error) keeping the position.
+    async fn reopen(&mut self) -> io::Result<()> {
+        (self.file, self.pipe) = Self::open_file(&self.path, self.wipe, self.stream).await?;
+        self.identity = file_identity(&self.path).ok();
+        Ok(())
     }
 
-    async fn read_inner(file: &mut File, offset: &mut u64, buf: &mut [u8], loop_on_eof: bool) -> Result<usize, Error> {
-        // Seek to saved offset
-        file.seek(tokio::io::SeekFrom::Start(*offset))
+    /// Destroys `len` bytes at `offset` if the source wipes what it reads.
+    async fn wipe_read(&self, offset: u64, len: usize) -> Result<(), Error> {
+        let Some(mode) = self.wipe.filter(|_| len > 0) else { return Ok(()) };
+        let file = self.file.try_clone().await.map_err(io_error)?.into_std().await;
+        tokio::task::spawn_blocking(move || wipe::wipe(&file, mode, offset, len as u64))
             .await
-            .map_err(|e| Error::OsError(e.raw_os_error().unwrap_or(0) as u32))?;
+            .map_err(|_| Error::Unexpected)?
+            .map_err(|e| {
+                log::error!("File {} could not be wiped at offset {}: {}", self.path, offset, e);
+                io_error(e)
+            })
+    }
 
+    /// Read until `buf` is full or a non-looping file hits EOF, or under
+    /// `stream` until the first bytes arrive. Appends the `(offset, len)`
+    /// file extents read to `extents`.
+    async fn fill(&mut self, buf: &mut [u8], extents: &mut Vec<(u64, u64)>) -> Result<usize, Error> {
         let mut bytes_read = 0usize;
         while bytes_read < buf.len() {
-            match file.read(&mut buf[bytes_read..]).await {
-                Ok(0) if loop_on_eof => {
-                    file.seek(tokio::io::SeekFrom::Start(0))
-                        .await
-                        .map_err(|e| Error::OsError(e.raw_os_error().unwrap_or(0) as u32))?;
-                    *offset = 0;
-                }
-                Ok(0) => break, // EOF without loop
-                Ok(n) => {
-                    *offset += n as u64;
-                    bytes_read += n;
-                }
-                Err(e) => return Err(Error::OsError(e.raw_os_error().unwrap_or(0) as u32)),
+            let n = if self.manifest.is_some() {
+                self.read_verified(&mut buf[bytes_read..]).await?
+            } else {
+                self.read_raw(&mut buf[bytes_read..]).await?
+            };
+            if n == 0 { break; } // EOF without loop
+            bytes_read += n;
+            let start = self.position() - n as u64;
+            match extents.last_mut() {
+                Some((offset, len)) if *offset + *len == start => *len += n as u64,
+                _ => extents.push((start, n as u64)),
+            }
+            if self.stream {
+                break;
             }
         }
         Ok(bytes_read)
     }
-}
 
-#[async_trait]
-impl EntropySource for FileSource {
-    async fn read_bytes(&self, num_bytes: usize, timeout_ms: u64) -> Result<Vec<u8>, Error> {
-        let mut buffer = self.buffer.lock().await;
-        
-        // First, try to satisfy request from buffer
-        if buffer.len() >= num_bytes {
-            let result = buffer.take(num_bytes);
-            return Ok(result);
+    /// File offset of the next byte `fill` hands out.
+    fn position(&self) -> u64 {
+        self.offset - (self.verified.len() - self.verified_pos) as u64
+    }
+
+    /// Records in the provenance ledger that `span` of the read stream was
+    /// read from `extents` of this file.
+    fn record_read(&self, id: &str, span: Span, extents: &[(u64, u64)]) {
+        let origin = Origin::File { path: &self.path, identity: self.identity, extents };
+        ledger::record_read(id, span, origin);
+    }
+
+    async fn read_raw(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if self.stream {
+            // Waits for bytes; a FIFO is never at its end, a device may be
+            let n = match &mut self.pipe {
+                Some(pipe) => pipe.read(buf).await,
+                None => self.file.read(buf).await,
+            }
+            .map_err(io_error)?;
+            self.offset += n as u64;
+            return Ok(n);
         }
-        
-        // For timeout 0, return only what's in buffer (don't read file)
-        if timeout_ms == 0 {
-            let result = buffer.take(num_bytes);
-            return Ok(result);
+        loop {
+            // Seek to saved offset
+            self.file.seek(tokio::io::SeekFrom::Start(self.offset))
+                .await
+                .map_err(io_error)?;
+            match self.file.read(buf).await.map_err(io_error)? {
+                0 if self.loop_on_eof && self.offset > 0 => self.offset = 0,
+                n => {
+                    self.wipe_read(self.offset, n).await?;
+                    self.offset += n as u64;
+                    return Ok(n);
+                }
+            }
         }
-        
-        // Take what we have from buffer and read more
-        let mut result = {
-            let buf_len = buffer.len();
-            buffer.take(buf_len)
-        };
-        let bytes_from_buffer = result.len();
-        let remaining = num_bytes - bytes_from_buffer;
-        
-        drop(buffer); // Release buffer lock while reading from file
-        
-        let mut file = self.file.lock().await;
-        let mut offset = self.offset.lock().await;
+    }
 
-        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
-        let sleep = sleep_until(deadline);
-        tokio::pin!(sleep);
+    async fn read_verified(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if self.verified_pos == self.verified.len() && !self.next_chunk().await? {
+            return Ok(0);
+        }
+        let n = buf.len().min(self.verified.len() - self.verified_pos);
+        buf[..n].copy_from_slice(&self.verified[self.verified_pos..self.verified_pos + n]);
+        self.verified_pos += n;
+        Ok(n)
+    }
 
-        let mut buf = vec![0u8; remaining];
-        let mut bytes_read = 0usize;
+    /// Load and verify the next manifest chunk. Returns false on a clean EOF.
+    async fn next_chunk(&mut self) -> Result<bool, Error> {
+        let manifest = self.manifest.clone().ok_or(Error::Unexpected)?;
         loop {
-            tokio::select! {
-                res = Self::read_inner(&mut *file, &mut *offset, &mut buf[bytes_read..], self.loop_on_eof), if bytes_read < remaining => {
-                    let n = res?;
-                    bytes_read += n;
-                    if bytes_read >= remaining || n == 0 { break; }
+            let mut chunk = vec![0u8; manifest.chunk_bytes()];
+            self.file.seek(tokio::io::SeekFrom::Start(self.offset))
+                .await
+                .map_err(io_error)?;
+            let mut got = 0usize;
+            while got < chunk.len() {
+                match self.file.read(&mut chunk[got..]).await.map_err(io_error)? {
+                    0 => break,
+                    n => got += n,
                 }
-                _ = &mut sleep => { break; }
             }
+
+            if got == 0 {
+                if self.chunk_index < manifest.chunk_count() {
+                    log::error!("File is shorter than its manifest ({} of {} chunks)", self.chunk_index, manifest.chunk_count());
+                    return Err(Error::IntegrityFailure);
+                }
+                if self.loop_on_eof && self.offset > 0 {
+                    self.offset = 0;
+                    self.chunk_index = 0;
+                    continue;
+                }
+                return Ok(false);
+            }
+
+            chunk.truncate(got);
+            if !manifest.verify(self.chunk_index, &chunk) {
+                log::error!("Chunk {} at offset {} does not match manifest", self.chunk_index, self.offset);
+                return Err(Error::IntegrityFailure);
+            }
+            // Wiped whole, so that a later run finds the chunk boundary
+            self.wipe_read(self.offset, got).await?;
+            self.offset += got as u64;
+            self.chunk_index += 1;
+            self.verified = chunk;
+            self.verified_pos = 0;
+            return Ok(true);
         }
-        buf.truncate(bytes_read);
-        result.extend(buf);
-        Ok(result)
     }
+}
+
+fn io_error(e: io::Error) -> Error {
+    Error::OsError(e.raw_os_error().unwrap_or(0) as u32)
+}
+
+type SharedCursor = Arc<tokio::sync::Mutex<FileCursor>>;
 
-    async fn return_leftover(&self, leftover: Vec<u8>) {
-        if !leftover.is_empty() {
-            let mut buffer = self.buffer.lock().await;
-            buffer.extend_from_vec(leftover);
+/// Adds bytes read from a file or device to its buffer, through the
+/// source's transforms if it has any. Returns where they went and how many
+/// there were after transforming.
+fn store_read(buffer: &mut CircularBuffer, transforms: Option<&mut Pipeline>, raw: &[u8]) -> (Span, usize) {
+    match transforms {
+        Some(transforms) => {
+            let transformed = transforms.apply(raw);
+            (buffer.extend(&transformed), transformed.len())
         }
+        None => (buffer.extend(raw), raw.len()),
     }
-    
-    async fn get_buffer_status(&self) -> (String, Option<(usize, usize)>) {
-        let id = self.cfg.id.clone();
-        if let Some(max_size) = self.max_buffer_size {
-            let current_size = self.buffer.lock().await.len();
-            (id, Some((current_size, max_size)))
-        } else {
-            (id, None)
-        }
+}
+
+/// Moves up to `max` bytes from the file into `buffer`. This is the only way
+/// file bytes reach a `FileSource`'s readers, so each one is served at most
+/// once; holding the cursor lock keeps concurrent pulls from overfilling the
+/// buffer. A throttled source reads what its `throttle` allows by
+/// `deadline`. Returns the file bytes read, which a source with transforms
+/// buffers fewer of: 0 at the end of a non-looping file, when the buffer
+/// is full or when the throttle allows none.
+async fn pull(
+    id: &str,
+    cursor: &SharedCursor,
+    buffer: &tokio::sync::Mutex<CircularBuffer>,
+    max: usize,
+    throttle: Option<&Shaper>,
+    deadline: Instant,
+) -> Result<usize, Error> {
+    let mut cursor = cursor.lock().await;
+    let room = buffer.lock().await.available_space().min(max);
+    if room == 0 {
+        return Ok(0);
+    }
+    let raw = cursor.transforms.as_ref().map_or(room, |t| room.saturating_mul(t.raw_per_byte()));
+    let raw = allowance(throttle, raw, deadline).await;
+    if raw == 0 {
+        return Ok(0);
     }
+    let mut chunk = Zeroizing::new(vec![0u8; raw]);
+    let mut extents = Vec::new();
+    let filled = cursor.fill(&mut chunk, &mut extents).await;
+    if let Some(throttle) = throttle {
+        throttle.refund(raw - filled.as_ref().map_or(0, |&n| n));
+    }
+    let n = filled?;
+    let (span, _) = store_read(&mut *buffer.lock().await, cursor.transforms.as_mut(), &chunk[..n]);
+    cursor.record_read(id, span, &extents);
+    Ok(n)
+}
+
+/// Largest single read the file replenisher performs.
+const FILE_REPLENISH_CHUNK: usize = 64 * 1024;
+
+/// How often a signed file is checked for replacement on disk.
+const SIGNATURE_RECHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+pub struct FileSource {
+    cfg: FileConfig,
+    cursor: SharedCursor,
+    core: BufferCore,
+    throttle: Option<Arc<Shaper>>,
+    failed: Arc<AtomicBool>,
+    retry: RetryPolicy,
+    retries: Arc<AtomicU64>,
+    rate: AtomicU64,
 }
 
+impl FileSource {
+    /// Opens the source, starting over after the retry policy gives up,
+    /// until it succeeds. Used by the non-fail-fast startup policies.
+    pub async fn open_until_ready(cfg: FileConfig) -> Self {
+        let retry = RetryPolicy::from_config(cfg.retry.as_ref());
+        let mut round = 0u32;
+        loop {
+            match Self::new(cfg.clone()).await {
+                Ok(src) => return src,
+                Err(e) => {
+                    round = round.saturating_add(1);
+                    let delay = retry.backoff(round);
+                    log::warn!("File source {} still unavailable: {} - trying again in {:?}", cfg.id, e, delay);
+                    sleep(delay).await;
+                }
+            }
+        }
+    }
+
+    pub async fn new(cfg: FileConfig) -> io::Result<Self> {
+        let signature = SignatureCheck::new(&cfg.path, cfg.signature.as_deref(), &cfg.public_keys)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+            .map(Arc::new);
+        let mut identity = None;
+        if let Some(check) = &signature {
+            identity = Some(Self::verify_signature(&cfg.path, check).await?);
+            log::info!("File {} signature verified ({})", cfg.id, check.signature_path());
+        }
+
+        let max_buffer_size = cfg.buffer_mebibytes.map(|mb| mb as usize * 1024 * 1024);
+        let retry = RetryPolicy::from_config(cfg.retry.as_ref());
+        let retries = Arc::new(AtomicU64::new(0));
+        let cursor = retry
+            .run(&format!("Opening file source {}", cfg.id), &retries, || Self::open_cursor(&cfg))
+            .await?;
+        let cursor = Arc::new(tokio::sync::Mutex::new(cursor));
+        let failed = Arc::new(AtomicBool::new(false));
+        
+        // Refill below half full, in FILE_REPLENISH_CHUNK steps once a second, unless configured otherwise
+        let tuning = Tuning::new(cfg.low_watermark_percent.unwrap_or(50), cfg.replenish_chunk_bytes.unwrap_or(FILE_REPLENISH_CHUNK));
+        let core = BufferCore::new("file", &cfg.id, &cfg.common, max_buffer_size.unwrap_or(1024), max_buffer_size.is_some(), tuning);
+        let every = Duration::from_millis(cfg.replenish_interval_ms.unwrap_or(1000));
+        let throttle = throttle(cfg.max_bytes_per_sec);
+
+        // Start background replenishing if buffer is configured
+        if max_buffer_size.is_some() {
+            let buffer_clone = core.buffer.clone();
+            let cursor_clone = cursor.clone();
+            let tuning_clone = core.tuning.clone();
+            let throttle_clone = throttle.clone();
+            let failed_clone = failed.clone();
+            let id = cfg.id.clone();
+            let retry_clone = retry.clone();
+            let retries_clone = retries.clone();
+            let cpus = cfg.common.cpus.clone();
+            let stream = cfg.stream.unwrap_or(false);
+            supervisor::spawn(format!("file-replenish:{}", id), Some(STALL_TIMEOUT), move |heartbeat| {
+                let work = Self::background_replenish(
+                    buffer_clone.clone(),
+                    tuning_clone.clone(),
+                    throttle_clone.clone(),
+                    every,
+                    cursor_clone.clone(),
+                    stream,
+                    id.clone(),
+                    failed_clone.clone(),
+                    retry_clone.clone(),
+                    retries_clone.clone(),
+                    heartbeat,
+                );
+                affinity::on_cpus(format!("file-{}", id), cpus.clone(), work)
+            });
+        }
+
+        // Re-verify whenever the signed file (or its signature) is replaced
+        if let (Some(check), Some(identity)) = (signature, identity) {
+            let cfg_clone = cfg.clone();
+            let cursor_clone = cursor.clone();
+            let failed_clone = failed.clone();
+            supervisor::spawn(format!("file-watch:{}", cfg.id), Some(STALL_TIMEOUT), move |heartbeat| {
+                Self::watch_signed_file(
+                    cfg_clone.clone(),
+                    check.clone(),
+                    identity,
+                    cursor_clone.clone(),
+                    failed_clone.clone(),
+                    heartbeat,
+                )
+            });
+        }
+        
+        Ok(Self {
+            cfg,
+            cursor,
+            core,
+            throttle,
+            failed,
+            retry,
+            retries,
+            rate: AtomicU64::new(0),
+        })
+    }
+
+    /// Reopens the file behind `cursor` (keeping its position) under the retry policy.
+    async fn reopen_cursor(cursor: &SharedCursor, id: &str, policy: &RetryPolicy, retries: &AtomicU64) -> io::Result<()> {
+        policy
+            .run(&format!("Reopening file source {}", id), retries, || {
+                let cursor = cursor.clone();
+                async move { cursor.lock().await.reopen().await }
+            })
+            .await
+    }
+
+    /// Opens the one cursor that both the replenisher and requests pull through.
+    async fn open_cursor(cfg: &FileConfig) -> io::Result<FileCursor> {
+        let loop_on_eof = cfg.loop_.unwrap_or(false);
+        let manifest = match &cfg.manifest {
+            Some(path) => {
+                let manifest = Manifest::load(path)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+                log::info!("File {} verified against manifest {} ({} chunks)", cfg.id, path, manifest.chunk_count());
+                Some(Arc::new(manifest))
+            }
+            None => None,
+        };
+        let mut cursor = FileCursor::open(&cfg.path, loop_on_eof, cfg.stream.unwrap_or(false), manifest, cfg.wipe).await?;
+        if !cfg.force.unwrap_or(false) {
+            Self::screen(cfg, &cursor).await?;
+        }
+        cursor.transforms = Pipeline::from_config(cfg.debias, &cfg.transforms);
+        Ok(cursor)
+    }
+
+    /// Refuses a regular file whose start (from where `cursor` reads on)
+    /// looks like compressed or structured data rather than entropy, so a
+    /// mis-pointed path does not quietly weaken the mix. Sources that debias
+    /// or transform expect biased input and are only checked for known file
+    /// formats.
+    async fn screen(cfg: &FileConfig, cursor: &FileCursor) -> io::Result<()> {
+        let file = cursor.file.try_clone().await?.into_std().await;
+        let offset = cursor.offset;
+        let sample = tokio::task::spawn_blocking(move || -> io::Result<Option<Zeroizing<Vec<u8>>>> {
+            use std::os::unix::fs::FileExt;
+            if !file.metadata()?.is_file() {
+                return Ok(None);
+            }
+            let mut sample = Zeroizing::new(vec![0u8; sniff::SAMPLE_BYTES]);
+            let mut got = 0;
+            while got < sample.len() {
+                match file.read_at(&mut sample[got..], offset + got as u64)? {
+                    0 => break,
+                    n => got += n,
+                }
+            }
+            sample.truncate(got);
+            Ok(Some(sample))
+        })
+        .await
+        .map_err(io::Error::other)??;
+        let statistical = cfg.debias.is_none() && cfg.transforms.is_empty();
+        match sample.and_then(|sample| sniff::suspicious(&sample, statistical)) {
+            Some(reason) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} does not look like entropy: {} - set force = true to use it anyway", cfg.path, reason),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Verifies the detached signature and returns the identities of the
+    /// file and signature that were checked.
+    async fn verify_signature(path: &str, check: &Arc<SignatureCheck>) -> io::Result<(FileIdentity, FileIdentity)> {
+        let identity = (file_identity(path)?, file_identity(check.signature_path())?);
+        let path_clone = path.to_string();
+        let check_clone = check.clone();
+        tokio::task::spawn_blocking(move || check_clone.verify(&path_clone))
+            .await
+            .map_err(io::Error::other)?
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(identity)
+    }
+
+    async fn watch_signed_file(
+        cfg: FileConfig,
+        check: Arc<SignatureCheck>,
+        mut identity: (FileIdentity, FileIdentity),
+        cursor: SharedCursor,
+        failed: Arc<AtomicBool>,
+        heartbeat: Heartbeat,
+    ) {
+        let mut interval = interval(SIGNATURE_RECHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            heartbeat.beat();
+            let current = match (file_identity(&cfg.path), file_identity(check.signature_path())) {
+                (Ok(file), Ok(sig)) => (file, sig),
+                _ => continue, // Mid-replacement; check again later
+            };
+            if current == identity {
+                continue;
+            }
+
+            // Nothing is served from the source until the new contents check out
+            log::info!("File {} changed on disk - re-verifying signature", cfg.id);
+            failed.store(true, Ordering::Relaxed);
+            identity = current;
+            let verified = match Self::verify_signature(&cfg.path, &check).await {
+                Ok(verified) => verified,
+                Err(e) => {
+                    log::error!("File {} failed signature verification - disabling source: {}", cfg.id, e);
+                    continue;
+                }
+            };
+            match Self::open_cursor(&cfg).await {
+                Ok(new_cursor) if verified == current => {
+                    *cursor.lock().await = new_cursor;
+                    failed.store(false, Ordering::Relaxed);
+                    log::info!("File {} re-enabled after signature verification", cfg.id);
+                }
+                Ok(_) => log::warn!("File {} changed again during verification - retrying", cfg.id),
+                Err(e) => log::error!("File {} could not be reopened: {}", cfg.id, e),
+            }
+        }
+    }
+    
+    #[allow(clippy::too_many_arguments)]
+    async fn background_replenish(
+        buffer: Arc<tokio::sync::Mutex<CircularBuffer>>,
+        tuning: Arc<Tuning>,
+        throttle: Option<Arc<Shaper>>,
+        every: Duration,
+        cursor: SharedCursor,
+        stream: bool,
+        id: String,
+        failed: Arc<AtomicBool>,
+        retry: RetryPolicy,
+        retries: Arc<AtomicU64>,
+        heartbeat: Heartbeat,
+    ) {
+        let mut interval = interval(every);
+        
+        loop {
+            interval.tick().await;
+            heartbeat.beat();
+            if failed.load(Ordering::Relaxed) {
+                continue;
+            }
+            let (current_size, max_size) = buffer_fill(&buffer, &id).await;
+            if tuning.wants_refill(current_size, max_size) {
+                // Read in chunks so slow devices still show progress to the supervisor
+                let needed = (max_size - current_size).min(tuning.chunk());
+                let pulling = pull(&id, &cursor, &buffer, needed, throttle.as_deref(), Instant::now() + THROTTLE_STEP);
+                let pulled = match stream {
+                    // A stream may stay quiet indefinitely; keep the heartbeat going meanwhile
+                    true => match tokio::time::timeout(Duration::from_secs(1), pulling).await {
+                        Ok(res) => res,
+                        Err(_) => continue,
+                    },
+                    false => pulling.await,
+                };
+                let bytes_read = match pulled {
+                    Ok(n) => n,
+                    Err(Error::IntegrityFailure) => {
+                        log::error!("File {} failed integrity check - disabling source", id);
+                        failed.store(true, Ordering::Relaxed);
+                        continue;
+                    }
+                    Err(e) => {
+                        log::warn!("File {} read failed: {} - reopening", id, e);
+                        if Self::reopen_cursor(&cursor, &id, &retry, &retries).await.is_err() {
+                            log::error!("File {} could not be reopened - disabling source", id);
+                            failed.store(true, Ordering::Relaxed);
+                        }
+                        continue;
+                    }
+                };
+                
+                if bytes_read > 0 {
+                    log::debug!("File {} replenished buffer: {} -> {} bytes", id, current_size, buffer.lock().await.len());
+                }
+            }
+        }
+    }
+}
+
+impl FileSource {
+    async fn read_fresh(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+        // Serve from the buffer, pulling more of the file into it as needed
+        // (never for timeout 0); the file is never read around the buffer
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        let sleep = sleep_until(deadline);
+        tokio::pin!(sleep);
+
+        let mut result = Vec::with_capacity(num_bytes);
+        let mut spans = Spans::default();
+        let mut take = |buffer: &mut CircularBuffer, result: &mut Vec<u8>| {
+            let (bytes, taken) = buffer.take_traced(num_bytes - result.len());
+            result.extend_from_slice(&Zeroizing::new(bytes));
+            spans.append(taken);
+        };
+        let mut read_error = None;
+        loop {
+            let want = num_bytes - result.len();
+            let Some(mut buffer) = lock_fresh_until(&self.core.buffer, &self.cfg.id, deadline).await else { break };
+            take(&mut buffer, &mut result);
+            drop(buffer);
+            if result.len() == num_bytes || timeout_ms == 0 {
+                break;
+            }
+            tokio::select! {
+                res = pull(&self.cfg.id, &self.cursor, &self.core.buffer, want, self.throttle.as_deref(), deadline) => match res {
+                    Ok(0) => {
+                        // End of file, or another reader filled the buffer: take what is there
+                        if let Some(mut buffer) = lock_fresh_until(&self.core.buffer, &self.cfg.id, deadline).await {
+                            take(&mut buffer, &mut result);
+                        }
+                        break;
+                    }
+                    Ok(_) => {}
+                    Err(e) => { read_error = Some(e); break; }
+                },
+                _ = &mut sleep => break,
+            }
+        }
+        match read_error {
+            Some(Error::IntegrityFailure) => {
+                log::error!("File {} failed integrity check - disabling source", self.cfg.id);
+                self.failed.store(true, Ordering::Relaxed);
+                return Err(Error::IntegrityFailure);
+            }
+            Some(e) => {
+                // Reopen for the next request, within what is left of this one's deadline
+                let reopen = Self::reopen_cursor(&self.cursor, &self.cfg.id, &self.retry, &self.retries);
+                let _ = tokio::time::timeout_at(deadline, reopen).await;
+                return Err(e);
+            }
+            None => {}
+        }
+        Ok((result, spans))
+    }
+}
+
+#[async_trait]
+impl EntropySource for FileSource {
+    async fn read_traced(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+        if self.failed.load(Ordering::Relaxed) {
+            return Err(Error::IntegrityFailure);
+        }
+        self.core.lane.serve(num_bytes, |n| self.read_fresh(n, timeout_ms)).await
+    }
+
+    fn core(&self) -> Option<&BufferCore> {
+        Some(&self.core)
+    }
+
+    async fn get_buffer_status(&self) -> (String, Option<(usize, usize)>) {
+        self.core.status().await
+    }
+
+    fn metrics(&self) -> SourceMetrics {
+        SourceMetrics {
+            retries: self.retries.load(Ordering::Relaxed),
+            estimated_rate: estimated_rate(&self.rate),
+            leftovers: self.core.lane.stats(),
+            ..SourceMetrics::default()
+        }
+    }
+
+    async fn benchmark(&self, budget: Duration) -> Option<f64> {
+        // A stream's rate is its writer's, and a quiet one would hold up startup
+        if self.failed.load(Ordering::Relaxed) || self.cfg.stream.unwrap_or(false) {
+            return None;
+        }
+        measure(&self.rate, budget, || async {
+            let n = allowance(self.throttle.as_deref(), BENCHMARK_CHUNK, Instant::now() + THROTTLE_STEP).await;
+            if n == 0 {
+                return Ok(0);
+            }
+            let mut chunk = Zeroizing::new(vec![0u8; n]);
+            let mut extents = Vec::new();
+            let mut cursor = self.cursor.lock().await;
+            let filled = cursor.fill(&mut chunk, &mut extents).await;
+            if let Some(throttle) = &self.throttle {
+                throttle.refund(n - filled.as_ref().map_or(0, |&n| n));
+            }
+            let n = filled?;
+            let (span, stored) = store_read(&mut *self.core.buffer.lock().await, cursor.transforms.as_mut(), &chunk[..n]);
+            cursor.record_read(&self.cfg.id, span, &extents);
+            Ok(stored)
+        })
+        .await
+    }
+
+    fn is_healthy(&self) -> bool {
+        !self.failed.load(Ordering::Relaxed)
+    }
+}
+
+type SharedPort = Arc<tokio::sync::Mutex<File>>;
+
+/// Moves up to `max` bytes that the device has ready into `buffer`, waiting
+/// up to its read timeout for the first. As with `pull`, device bytes only
+/// reach readers through the buffer, passing `transforms` on the way if
+/// the source has any. Returns the device bytes read. A device node that is gone
+/// counts as an error rather than as a quiet line.
+async fn pull_serial(id: &str, path: &str, port: &SharedPort, transforms: Option<&std::sync::Mutex<Pipeline>>, buffer: &tokio::sync::Mutex<CircularBuffer>, max: usize) -> Result<usize, Error> {
+    let mut port = port.lock().await;
+    let room = buffer.lock().await.available_space().min(max);
+    if room == 0 {
+        return Ok(0);
+    }
+    let raw = match transforms {
+        Some(t) => room.saturating_mul(t.lock().unwrap_or_else(|e| e.into_inner()).raw_per_byte()),
+        None => room,
+    };
+    let mut chunk = Zeroizing::new(vec![0u8; raw]);
+    let n = port.read(&mut chunk).await.map_err(io_error)?;
+    if n == 0 && !Path::new(path).exists() {
+        return Err(Error::OsError(libc::ENODEV as u32));
+    }
+    let span = {
+        let mut buffer = buffer.lock().await;
+        // Only the port holder feeds the transforms, so their lock is never contended
+        let mut transforms = transforms.map(|t| t.lock().unwrap_or_else(|e| e.into_inner()));
+        store_read(&mut buffer, transforms.as_deref_mut(), &chunk[..n]).0
+    };
+    ledger::record_read(id, span, Origin::Device(path));
+    Ok(n)
+}
+
+/// Largest single read the serial replenisher performs.
+const SERIAL_REPLENISH_CHUNK: usize = 64 * 1024;
+
+pub struct SerialSource {
+    cfg: SerialConfig,
+    port: SharedPort,
+    /// Transforms what is read before it is buffered.
+    transforms: Option<Arc<std::sync::Mutex<Pipeline>>>,
+    core: BufferCore,
+    /// Cleared when the device fails or disappears, until it is reopened.
+    connected: Arc<AtomicBool>,
+    retries: Arc<AtomicU64>,
+    rate: AtomicU64,
+}
+
+impl SerialSource {
+    pub async fn new(cfg: SerialConfig) -> io::Result<Self> {
+        let max_buffer_size = cfg.buffer_mebibytes.map(|mb| mb as usize * 1024 * 1024);
+        let retry = RetryPolicy::from_config(cfg.retry.as_ref());
+        let retries = Arc::new(AtomicU64::new(0));
+        let port = retry
+            .run(&format!("Opening serial source {}", cfg.id), &retries, || async { serial::open(&cfg) })
+            .await?;
+        let port = Arc::new(tokio::sync::Mutex::new(File::from_std(port)));
+        let transforms = Pipeline::from_config(cfg.debias, &cfg.transforms).map(|p| Arc::new(std::sync::Mutex::new(p)));
+        let connected = Arc::new(AtomicBool::new(true));
+
+        // Refill below half full, in SERIAL_REPLENISH_CHUNK steps
+        let tuning = Tuning::new(50, SERIAL_REPLENISH_CHUNK);
+        let core = BufferCore::new("serial", &cfg.id, &cfg.common, max_buffer_size.unwrap_or(1024), max_buffer_size.is_some(), tuning);
+
+        // Reconnects the device, and replenishes if a buffer is configured
+        let task = (cfg.clone(), port.clone(), transforms.clone(), core.buffer.clone(), core.tuning.clone(), connected.clone(), retries.clone());
+        let buffered = max_buffer_size.is_some();
+        supervisor::spawn(format!("serial:{}", cfg.id), Some(STALL_TIMEOUT), move |heartbeat| {
+            let (cfg, port, transforms, buffer, tuning, connected, retries) = task.clone();
+            let cpus = cfg.common.cpus.clone();
+            let id = cfg.id.clone();
+            let work = Self::background(cfg, port, transforms, buffer, tuning, connected, buffered, retries, heartbeat);
+            affinity::on_cpus(format!("serial-{}", id), cpus, work)
+        });
+
+        Ok(Self {
+            cfg,
+            port,
+            transforms,
+            core,
+            connected,
+            retries,
+            rate: AtomicU64::new(0),
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn background(
+        cfg: SerialConfig,
+        port: SharedPort,
+        transforms: Option<Arc<std::sync::Mutex<Pipeline>>>,
+        buffer: Arc<tokio::sync::Mutex<CircularBuffer>>,
+        tuning: Arc<Tuning>,
+        connected: Arc<AtomicBool>,
+        buffered: bool,
+        retries: Arc<AtomicU64>,
+        heartbeat: Heartbeat,
+    ) {
+        let retry = RetryPolicy::from_config(cfg.retry.as_ref());
+        let mut interval = interval(Duration::from_millis(10));
+        let (mut attempt, mut next_attempt) = (0u32, Instant::now());
+        loop {
+            interval.tick().await;
+            heartbeat.beat();
+            if !connected.load(Ordering::Relaxed) {
+                // Unplugged devices come back, so this never gives up
+                if Instant::now() < next_attempt {
+                    continue;
+                }
+                match serial::open(&cfg) {
+                    Ok(file) => {
+                        *port.lock().await = File::from_std(file);
+                        connected.store(true, Ordering::Relaxed);
+                        attempt = 0;
+                        log::info!("Serial source {} reopened", cfg.id);
+                    }
+                    Err(e) => {
+                        attempt = attempt.saturating_add(1);
+                        let delay = retry.backoff(attempt);
+                        retries.fetch_add(1, Ordering::Relaxed);
+                        log::warn!("Serial source {} still unavailable: {} - trying again in {:?}", cfg.id, e, delay);
+                        next_attempt = Instant::now() + delay;
+                    }
+                }
+                continue;
+            }
+            if !buffered {
+                continue;
+            }
+            let (current_size, max_size) = buffer_fill(&buffer, &cfg.id).await;
+            if !tuning.wants_refill(current_size, max_size) {
+                continue;
+            }
+            let needed = (max_size - current_size).min(tuning.chunk());
+            match pull_serial(&cfg.id, &cfg.path, &port, transforms.as_deref(), &buffer, needed).await {
+                Ok(0) => {}
+                Ok(_) => log::debug!("Serial {} replenished buffer: {} -> {} bytes", cfg.id, current_size, buffer.lock().await.len()),
+                Err(e) => {
+                    log::warn!("Serial {} read failed: {} - reopening", cfg.id, e);
+                    connected.store(false, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    async fn read_fresh(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+        // Serve from the buffer, reading what the device has ready into it
+        // until the deadline (never for timeout 0)
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        let sleep = sleep_until(deadline);
+        tokio::pin!(sleep);
+
+        let mut result = Vec::with_capacity(num_bytes);
+        let mut spans = Spans::default();
+        loop {
+            let want = num_bytes - result.len();
+            let Some(mut buffer) = lock_fresh_until(&self.core.buffer, &self.cfg.id, deadline).await else { break };
+            let (bytes, taken) = buffer.take_traced(want);
+            drop(buffer);
+            result.extend_from_slice(&Zeroizing::new(bytes));
+            spans.append(taken);
+            if result.len() == num_bytes || timeout_ms == 0 {
+                break;
+            }
+            tokio::select! {
+                res = pull_serial(&self.cfg.id, &self.cfg.path, &self.port, self.transforms.as_deref(), &self.core.buffer, want) => if let Err(e) = res {
+                    // The background task reopens the device
+                    log::warn!("Serial {} read failed: {} - reopening", self.cfg.id, e);
+                    self.connected.store(false, Ordering::Relaxed);
+                    return Err(e);
+                },
+                _ = &mut sleep => break,
+            }
+        }
+        Ok((result, spans))
+    }
+}
+
+#[async_trait]
+impl EntropySource for SerialSource {
+    async fn read_traced(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+        if !self.connected.load(Ordering::Relaxed) {
+            return Err(Error::SourcesUnavailable);
+        }
+        self.core.lane.serve(num_bytes, |n| self.read_fresh(n, timeout_ms)).await
+    }
+
+    fn core(&self) -> Option<&BufferCore> {
+        Some(&self.core)
+    }
+
+    async fn get_buffer_status(&self) -> (String, Option<(usize, usize)>) {
+        self.core.status().await
+    }
+
+    fn metrics(&self) -> SourceMetrics {
+        SourceMetrics {
+            retries: self.retries.load(Ordering::Relaxed),
+            estimated_rate: estimated_rate(&self.rate),
+            leftovers: self.core.lane.stats(),
+            ..SourceMetrics::default()
+        }
+    }
+
+    async fn benchmark(&self, budget: Duration) -> Option<f64> {
+        if !self.connected.load(Ordering::Relaxed) {
+            return None;
+        }
+        measure(&self.rate, budget, || async {
+            let mut chunk = Zeroizing::new(vec![0u8; BENCHMARK_CHUNK]);
+            let mut port = self.port.lock().await;
+            let n = port.read(&mut chunk).await.map_err(io_error)?;
+            let (span, stored) = {
+                let mut buffer = self.core.buffer.lock().await;
+                let mut transforms = self.transforms.as_deref().map(|t| t.lock().unwrap_or_else(|e| e.into_inner()));
+                store_read(&mut buffer, transforms.as_deref_mut(), &chunk[..n])
+            };
+            ledger::record_read(&self.cfg.id, span, Origin::Device(&self.cfg.path));
+            Ok(stored)
+        })
+        .await
+    }
+
+    fn is_available(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+}
+
+/// Largest read from a TCP source's connection per replenish step.
+const TCP_READ_CHUNK: usize = 64 * 1024;
+
+/// Default for `idle_timeout_ms`.
+const TCP_IDLE_TIMEOUT_MS: u64 = 10_000;
+
+/// Fills the buffer of a TCP source from what its server streams, over a
+/// connection that is reopened with the `retry` backoff whenever it closes,
+/// fails or goes quiet. While the buffer is full nothing is read, so TCP
+/// flow control holds the server back. Readers only take what is buffered,
+/// waiting at most until their deadline.
+pub struct TcpSource {
+    connector: Arc<Connector>,
+    core: BufferCore,
+    /// Set while connected.
+    connected: Arc<AtomicBool>,
+    retries: Arc<AtomicU64>,
+}
+
+impl TcpSource {
+    pub fn new(cfg: TcpConfig) -> Result<Self, String> {
+        let connector = Arc::new(Connector::new(&cfg.host, cfg.port, cfg.tls.as_ref(), cfg.connect_timeout_ms)?);
+        if !connector.is_tls() {
+            log::warn!("TCP source {} receives its bytes in the clear - use tls", cfg.id);
+        }
+        let capacity = cfg.buffer_mebibytes.unwrap_or(1).max(1) as usize * 1024 * 1024;
+
+        // Refill whenever the buffer is not full
+        let tuning = Tuning::new(100, TCP_READ_CHUNK);
+        let core = BufferCore::new("TCP", &cfg.id, &cfg.common, capacity, true, tuning);
+        let connected = Arc::new(AtomicBool::new(false));
+        let retries = Arc::new(AtomicU64::new(0));
+
+        let task = (cfg.clone(), connector.clone(), core.buffer.clone(), core.tuning.clone(), core.filled.clone(), connected.clone(), retries.clone());
+        supervisor::spawn(format!("tcp:{}", cfg.id), Some(STALL_TIMEOUT), move |heartbeat| {
+            let (cfg, connector, buffer, tuning, filled, connected, retries) = task.clone();
+            let cpus = cfg.common.cpus.clone();
+            let id = cfg.id.clone();
+            let work = Self::supervise(cfg, connector, buffer, tuning, filled, connected, retries, heartbeat);
+            affinity::on_cpus(format!("tcp-{}", id), cpus, work)
+        });
+
+        Ok(Self { connector, core, connected, retries })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn supervise(
+        cfg: TcpConfig,
+        connector: Arc<Connector>,
+        buffer: Arc<tokio::sync::Mutex<CircularBuffer>>,
+        tuning: Arc<Tuning>,
+        filled: Arc<Notify>,
+        connected: Arc<AtomicBool>,
+        retries: Arc<AtomicU64>,
+        heartbeat: Heartbeat,
+    ) {
+        let retry = RetryPolicy::from_config(cfg.retry.as_ref());
+        let idle = Duration::from_millis(cfg.idle_timeout_ms.unwrap_or(TCP_IDLE_TIMEOUT_MS).max(1));
+        let mut failures = 0u32;
+        loop {
+            heartbeat.beat();
+            let mut received = 0u64;
+            match connector.connect().await {
+                Ok(stream) => {
+                    log::info!("TCP source {} connected to {}", cfg.id, connector.display());
+                    connected.store(true, Ordering::Relaxed);
+                    let res = Self::consume(&cfg, &connector, stream, idle, &buffer, &tuning, &filled, &heartbeat, &mut received).await;
+                    connected.store(false, Ordering::Relaxed);
+                    match res {
+                        Ok(()) => log::warn!("TCP source {}: {} closed the connection after {} bytes", cfg.id, connector.display(), received),
+                        Err(e) => log::warn!("TCP source {}: reading from {} failed after {} bytes: {}", cfg.id, connector.display(), received, e),
+                    }
+                }
+                Err(e) => log::warn!("TCP source {} cannot connect to {}: {}", cfg.id, connector.display(), e),
+            }
+            // Appliances reboot and links flap, so this never gives up; a
+            // connection that delivered starts the backoff over
+            failures = if received > 0 { 1 } else { failures.saturating_add(1) };
+            let delay = retry.backoff(failures);
+            retries.fetch_add(1, Ordering::Relaxed);
+            log::warn!("TCP source {} reconnecting in {:?}", cfg.id, delay);
+            sleep(delay).await;
+        }
+    }
+
+    /// Moves what the server sends into `buffer` until it closes the
+    /// connection, which fails, or it sends nothing for `idle` while the
+    /// buffer has room.
+    #[allow(clippy::too_many_arguments)]
+    async fn consume(
+        cfg: &TcpConfig,
+        connector: &Connector,
+        mut stream: Box<dyn tcp::Stream>,
+        idle: Duration,
+        buffer: &tokio::sync::Mutex<CircularBuffer>,
+        tuning: &Tuning,
+        filled: &Notify,
+        heartbeat: &Heartbeat,
+        received: &mut u64,
+    ) -> io::Result<()> {
+        let mut interval = interval(Duration::from_millis(10));
+        let mut last_data = Instant::now();
+        loop {
+            interval.tick().await;
+            heartbeat.beat();
+            let (current_size, max_size) = buffer_fill(buffer, &cfg.id).await;
+            if !tuning.wants_refill(current_size, max_size) {
+                // A server held back by a full buffer is not idle
+                last_data = Instant::now();
+                continue;
+            }
+            let mut chunk = Zeroizing::new(vec![0u8; (max_size - current_size).min(tuning.chunk())]);
+            // Socket and TLS reads can be cancelled without losing bytes, which keeps the heartbeat going
+            let n = match tokio::time::timeout(idle.min(Duration::from_secs(1)), stream.read(&mut chunk)).await {
+                Err(_) if last_data.elapsed() >= idle => {
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, format!("nothing received for {:?}", idle)));
+                }
+                Err(_) => continue,
+                Ok(res) => res?,
+            };
+            if n == 0 {
+                return Ok(());
+            }
+            last_data = Instant::now();
+            *received += n as u64;
+            let span = buffer.lock().await.extend(&chunk[..n]);
+            ledger::record_read(&cfg.id, span, Origin::Remote(connector.display()));
+            filled.notify_waiters();
+        }
+    }
+
+}
+
+#[async_trait]
+impl EntropySource for TcpSource {
+    async fn read_traced(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+        self.core.serve_buffered(num_bytes, timeout_ms).await
+    }
+
+    fn core(&self) -> Option<&BufferCore> {
+        Some(&self.core)
+    }
+
+    async fn get_buffer_status(&self) -> (String, Option<(usize, usize)>) {
+        self.core.status().await
+    }
+
+    fn metrics(&self) -> SourceMetrics {
+        SourceMetrics {
+            retries: self.retries.load(Ordering::Relaxed),
+            leftovers: self.core.lane.stats(),
+            ..SourceMetrics::default()
+        }
+    }
+
+    // No benchmark: the connection has a single reader, the replenisher
+
+    fn is_available(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    fn info(&self) -> HashMap<String, String> {
+        HashMap::from([("server".to_string(), self.connector.display().to_string())])
+    }
+}
+
+/// Bytes an HTTP source asks for per fetch unless `request_bytes` says otherwise.
+const HTTP_REQUEST_BYTES: usize = 1024;
+
+/// Fills the buffer of an HTTP source from a web API in the background.
+/// Readers only ever take what earlier fetches buffered, so a slow or rate
+/// limited API never holds up a request past its timeout.
+pub struct HttpSource {
+    cfg: HttpConfig,
+    client: Arc<http::Client>,
+    core: BufferCore,
+    /// Cleared when a fetch fails, until one succeeds.
+    reachable: Arc<AtomicBool>,
+    poll_interval: Duration,
+    retries: Arc<AtomicU64>,
+    rate: AtomicU64,
+}
+
+/// Buffers the bytes of one fetch and wakes readers waiting for them.
+async fn store_fetched(id: &str, client: &http::Client, buffer: &tokio::sync::Mutex<CircularBuffer>, filled: &Notify, bytes: &[u8]) -> usize {
+    let span = buffer.lock().await.extend(bytes);
+    let kept = span.len as usize;
+    ledger::record_read(id, span, Origin::Remote(client.display()));
+    filled.notify_waiters();
+    kept
+}
+
+impl HttpSource {
+    pub fn new(cfg: HttpConfig) -> Result<Self, String> {
+        let client = Arc::new(http::Client::new(&cfg)?);
+        let capacity = cfg.buffer_mebibytes.unwrap_or(1).max(1) as usize * 1024 * 1024;
+
+        // Fetch whenever the buffer is not full, request_bytes at a time
+        let request_bytes = cfg.request_bytes.unwrap_or(HTTP_REQUEST_BYTES).clamp(1, MAX_REPLENISH_CHUNK);
+        let tuning = Tuning::new(100, request_bytes);
+        let core = BufferCore::new("HTTP", &cfg.id, &cfg.common, capacity, true, tuning);
+        let poll_interval = Duration::from_millis(cfg.poll_interval_ms.unwrap_or(1000).max(10));
+        let reachable = Arc::new(AtomicBool::new(true));
+        let retries = Arc::new(AtomicU64::new(0));
+
+        let task = (cfg.clone(), client.clone(), core.buffer.clone(), core.tuning.clone(), core.filled.clone(), reachable.clone(), retries.clone());
+        supervisor::spawn(format!("http-poll:{}", cfg.id), Some(STALL_TIMEOUT), move |heartbeat| {
+            let (cfg, client, buffer, tuning, filled, reachable, retries) = task.clone();
+            let cpus = cfg.common.cpus.clone();
+            let id = cfg.id.clone();
+            let work = Self::poll(cfg, client, buffer, tuning, filled, reachable, retries, poll_interval, heartbeat);
+            affinity::on_cpus(format!("http-{}", id), cpus, work)
+        });
+
+        Ok(Self { cfg, client, core, reachable, poll_interval, retries, rate: AtomicU64::new(0) })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn poll(
+        cfg: HttpConfig,
+        client: Arc<http::Client>,
+        buffer: Arc<tokio::sync::Mutex<CircularBuffer>>,
+        tuning: Arc<Tuning>,
+        filled: Arc<Notify>,
+        reachable: Arc<AtomicBool>,
+        retries: Arc<AtomicU64>,
+        poll_interval: Duration,
+        heartbeat: Heartbeat,
+    ) {
+        let retry = RetryPolicy::from_config(cfg.retry.as_ref());
+        let mut ticker = interval(poll_interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut failures = 0u32;
+        loop {
+            ticker.tick().await;
+            heartbeat.beat();
+            let (current_size, max_size) = buffer_fill(&buffer, &cfg.id).await;
+            if !tuning.wants_refill(current_size, max_size) {
+                continue;
+            }
+            let wanted = (max_size - current_size).min(tuning.chunk());
+            match client.fetch(wanted).await {
+                Ok(bytes) => {
+                    failures = 0;
+                    if !reachable.swap(true, Ordering::Relaxed) {
+                        log::info!("HTTP source {} reachable again", cfg.id);
+                    }
+                    let kept = store_fetched(&cfg.id, &client, &buffer, &filled, &bytes).await;
+                    log::debug!("HTTP {} fetched {} of {} bytes asked for, {} buffered", cfg.id, bytes.len(), wanted, current_size + kept);
+                }
+                Err(e) => {
+                    // An API that keeps failing is polled less often, never given up on
+                    failures = failures.saturating_add(1);
+                    let delay = retry.backoff(failures).max(poll_interval);
+                    retries.fetch_add(1, Ordering::Relaxed);
+                    reachable.store(false, Ordering::Relaxed);
+                    log::warn!("HTTP source {} fetch from {} failed: {} - trying again in {:?}", cfg.id, client.display(), e, delay);
+                    sleep(delay).await;
+                }
+            }
+        }
+    }
+
+}
+
+#[async_trait]
+impl EntropySource for HttpSource {
+    async fn read_traced(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+        self.core.serve_buffered(num_bytes, timeout_ms).await
+    }
+
+    fn core(&self) -> Option<&BufferCore> {
+        Some(&self.core)
+    }
+
+    async fn get_buffer_status(&self) -> (String, Option<(usize, usize)>) {
+        self.core.status().await
+    }
+
+    fn metrics(&self) -> SourceMetrics {
+        SourceMetrics {
+            retries: self.retries.load(Ordering::Relaxed),
+            estimated_rate: estimated_rate(&self.rate),
+            leftovers: self.core.lane.stats(),
+            ..SourceMetrics::default()
+        }
+    }
+
+    async fn benchmark(&self, _budget: Duration) -> Option<f64> {
+        // A single fetch: the poll interval caps the rate however fast the
+        // API answers, and looping would only hammer it
+        let start = Instant::now();
+        let bytes = self.client.fetch(self.core.tuning.chunk()).await.ok()?;
+        let elapsed = start.elapsed().max(self.poll_interval).as_secs_f64();
+        store_fetched(&self.cfg.id, &self.client, &self.core.buffer, &self.core.filled, &bytes).await;
+        let measured = (!bytes.is_empty()).then(|| bytes.len() as f64 / elapsed)?;
+        self.rate.store(measured.to_bits(), Ordering::Relaxed);
+        Some(measured)
+    }
+
+    fn is_available(&self) -> bool {
+        self.reachable.load(Ordering::Relaxed)
+    }
+}
+
+/// Draws from the CPU's RDSEED or RDRAND instruction, optionally through a
+/// buffer replenished in the background. Requests the buffer cannot cover
+/// are generated directly, up to their deadline.
+pub struct CpuSource {
+    cfg: CpuConfig,
+    /// What this CPU has of `cfg.instruction`.
+    instruction: CpuInstruction,
+    core: BufferCore,
+    /// The buffer's read-stream numbering, shared with unbuffered reads.
+    positions: Positions,
+    /// Set once the instruction has failed its repetition check.
+    failed: Arc<AtomicBool>,
+    rate: AtomicU64,
+}
+
+/// Runs `cpu::fill` off the async workers, disabling the source if the
+/// output fails its check.
+async fn generate_cpu(id: &str, instruction: CpuInstruction, len: usize, deadline: Option<Instant>, failed: &AtomicBool) -> Result<Vec<u8>, Error> {
+    let deadline = deadline.map(Instant::into_std);
+    let res = tokio::task::spawn_blocking(move || cpu::fill(instruction, len, deadline))
+        .await
+        .map_err(|_| Error::Unexpected)?;
+    if let Err(Error::IntegrityFailure) = res {
+        log::error!("CPU source {}: {} repeated itself - disabling source", id, cpu::name(instruction));
+        failed.store(true, Ordering::Relaxed);
+    }
+    res
+}
+
+impl CpuSource {
+    pub fn new(cfg: CpuConfig) -> Result<Self, String> {
+        let wanted = cfg.instruction.unwrap_or_default();
+        let instruction = cpu::detect(wanted).ok_or_else(|| format!("this CPU has no {}", cpu::name(wanted)))?;
+        if instruction != wanted {
+            log::warn!("CPU source {}: no {} on this CPU - using {}", cfg.id, cpu::name(wanted), cpu::name(instruction));
+        }
+        let max_buffer_size = cfg.buffer_mebibytes.map(|mb| mb as usize * 1024 * 1024);
+        let buffer = CircularBuffer::with_max_age(max_buffer_size.unwrap_or(1024), max_age(cfg.common.max_age_seconds));
+        let positions = buffer.positions();
+        let failed = Arc::new(AtomicBool::new(false));
+
+        // Refill whenever the buffer is not full, in 64 KiB steps
+        let tuning = Tuning::new(100, 64 * 1024);
+        let core = BufferCore::with_buffer("CPU", &cfg.id, &cfg.common, buffer, max_buffer_size.is_some(), tuning);
+
+        if max_buffer_size.is_some() {
+            let task = (cfg.id.clone(), core.buffer.clone(), core.tuning.clone(), failed.clone());
+            let cpus = cfg.common.cpus.clone();
+            supervisor::spawn(format!("cpu-replenish:{}", cfg.id), Some(STALL_TIMEOUT), move |heartbeat| {
+                let (id, buffer, tuning, failed) = task.clone();
+                let name = format!("cpu-{}", id);
+                affinity::on_cpus(name, cpus.clone(), Self::background_replenish(id, instruction, buffer, tuning, failed, heartbeat))
+            });
+        }
+
+        Ok(Self { cfg, instruction, core, positions, failed, rate: AtomicU64::new(0) })
+    }
+
+    async fn background_replenish(
+        id: String,
+        instruction: CpuInstruction,
+        buffer: Arc<tokio::sync::Mutex<CircularBuffer>>,
+        tuning: Arc<Tuning>,
+        failed: Arc<AtomicBool>,
+        heartbeat: Heartbeat,
+    ) {
+        let mut interval = interval(Duration::from_millis(10));
+        loop {
+            interval.tick().await;
+            heartbeat.beat();
+            let (current_size, max_size) = buffer_fill(&buffer, &id).await;
+            if failed.load(Ordering::Relaxed) || !tuning.wants_refill(current_size, max_size) {
+                continue;
+            }
+            // Once triggered, fill the buffer up, unless the instruction runs dry
+            loop {
+                let (current_size, max_size) = buffer_fill(&buffer, &id).await;
+                if current_size >= max_size || tuning.is_paused() {
+                    break;
+                }
+                let chunk_size = (max_size - current_size).min(tuning.chunk());
+                match generate_cpu(&id, instruction, chunk_size, None, &failed).await {
+                    Ok(bytes) if !bytes.is_empty() => {
+                        let mut buf = buffer.lock().await;
+                        ledger::record_read(&id, buf.extend_from_vec(bytes), Origin::Device(cpu::name(instruction)));
+                        heartbeat.beat();
+                        log::debug!("CPU {} replenished buffer: {} -> {} bytes", id, current_size, buf.len());
+                    }
+                    _ => break,
+                }
+            }
+        }
+    }
+
+    /// Numbers bytes served straight from the instruction.
+    fn claim(&self, len: usize) -> Spans {
+        let span = self.positions.claim(len);
+        ledger::record_read(&self.cfg.id, span, Origin::Device(cpu::name(self.instruction)));
+        span.into()
+    }
+
+    /// Serves from the buffer, then straight from the instruction until the
+    /// deadline (never for timeout 0). Unbuffered sources ignore the timeout.
+    async fn read_fresh(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+        if !self.core.enabled {
+            let bytes = generate_cpu(&self.cfg.id, self.instruction, num_bytes, None, &self.failed).await?;
+            let spans = self.claim(bytes.len());
+            return Ok((bytes, spans));
+        }
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        let Some(mut buffer) = lock_fresh_until(&self.core.buffer, &self.cfg.id, deadline).await else {
+            return Ok((Vec::new(), Spans::default()));
+        };
+        let (mut result, mut spans) = buffer.take_traced(num_bytes);
+        drop(buffer);
+        if result.len() == num_bytes || timeout_ms == 0 {
+            return Ok((result, spans));
+        }
+        let bytes = Zeroizing::new(generate_cpu(&self.cfg.id, self.instruction, num_bytes - result.len(), Some(deadline), &self.failed).await?);
+        result.extend_from_slice(&bytes);
+        spans.append(self.claim(bytes.len()));
+        Ok((result, spans))
+    }
+}
+
+#[async_trait]
+impl EntropySource for CpuSource {
+    async fn read_traced(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+        if self.failed.load(Ordering::Relaxed) {
+            return Err(Error::IntegrityFailure);
+        }
+        self.core.lane.serve(num_bytes, |n| self.read_fresh(n, timeout_ms)).await
+    }
+
+    fn core(&self) -> Option<&BufferCore> {
+        Some(&self.core)
+    }
+
+    async fn get_buffer_status(&self) -> (String, Option<(usize, usize)>) {
+        self.core.status().await
+    }
+
+    fn metrics(&self) -> SourceMetrics {
+        SourceMetrics {
+            estimated_rate: estimated_rate(&self.rate),
+            leftovers: self.core.lane.stats(),
+            ..SourceMetrics::default()
+        }
+    }
+
+    async fn benchmark(&self, budget: Duration) -> Option<f64> {
+        measure(&self.rate, budget, || async {
+            let chunk = generate_cpu(&self.cfg.id, self.instruction, BENCHMARK_CHUNK, None, &self.failed).await?;
+            let n = chunk.len();
+            if self.core.enabled {
+                let span = self.core.buffer.lock().await.extend_from_vec(chunk);
+                ledger::record_read(&self.cfg.id, span, Origin::Device(cpu::name(self.instruction)));
+            }
+            Ok(n)
+        })
+        .await
+    }
+
+    fn is_healthy(&self) -> bool {
+        !self.failed.load(Ordering::Relaxed)
+    }
+}
+
+/// Bytes the jitter collector adds per step, about a millisecond of work.
+const JITTER_CHUNK: usize = 4 * 1024;
+
+/// Fills the buffer of a jitter source from timer jitter collected on a
+/// blocking thread, away from the async workers. Readers only take what is
+/// buffered, waiting for the collector at most until their deadline.
+pub struct JitterSource {
+    cfg: JitterConfig,
+    core: BufferCore,
+    /// Cleared while the timing deltas fail the health check, until a block passes.
+    passing: Arc<AtomicBool>,
+    retries: Arc<AtomicU64>,
+    rate: AtomicU64,
+}
+
+/// Collects up to `len` bytes of jitter output within `budget`.
+async fn collect_jitter(len: usize, budget: Duration) -> Result<Zeroizing<Vec<u8>>, Error> {
+    let deadline = std::time::Instant::now() + budget;
+    tokio::task::spawn_blocking(move || jitter::generate(len, deadline).map(Zeroizing::new))
+        .await
+        .map_err(|_| Error::Unexpected)?
+}
+
+impl JitterSource {
+    pub fn new(cfg: JitterConfig) -> Self {
+        let capacity = cfg.buffer_mebibytes.unwrap_or(1).max(1) as usize * 1024 * 1024;
+
+        // Refill whenever the buffer is not full
+        let tuning = Tuning::new(100, JITTER_CHUNK);
+        let core = BufferCore::new("jitter", &cfg.id, &cfg.common, capacity, true, tuning);
+        let passing = Arc::new(AtomicBool::new(true));
+        let retries = Arc::new(AtomicU64::new(0));
+
+        let task = (cfg.clone(), core.buffer.clone(), core.tuning.clone(), core.filled.clone(), passing.clone(), retries.clone());
+        supervisor::spawn(format!("jitter-collect:{}", cfg.id), Some(STALL_TIMEOUT), move |heartbeat| {
+            let (cfg, buffer, tuning, filled, passing, retries) = task.clone();
+            let cpus = cfg.common.cpus.clone();
+            let id = cfg.id.clone();
+            let work = Self::collect(cfg, buffer, tuning, filled, passing, retries, heartbeat);
+            affinity::on_cpus(format!("jitter-{}", id), cpus, work)
+        });
+
+        Self { cfg, core, passing, retries, rate: AtomicU64::new(0) }
+    }
+
+    async fn collect(
+        cfg: JitterConfig,
+        buffer: Arc<tokio::sync::Mutex<CircularBuffer>>,
+        tuning: Arc<Tuning>,
+        filled: Arc<Notify>,
+        passing: Arc<AtomicBool>,
+        retries: Arc<AtomicU64>,
+        heartbeat: Heartbeat,
+    ) {
+        let retry = RetryPolicy::from_config(cfg.retry.as_ref());
+        let mut interval = interval(Duration::from_millis(10));
+        let mut failures = 0u32;
+        loop {
+            interval.tick().await;
+            heartbeat.beat();
+            let (current_size, max_size) = buffer_fill(&buffer, &cfg.id).await;
+            if !tuning.wants_refill(current_size, max_size) {
+                continue;
+            }
+            let wanted = (max_size - current_size).min(tuning.chunk());
+            match collect_jitter(wanted, Duration::from_millis(100)).await {
+                Ok(bytes) => {
+                    failures = 0;
+                    if !passing.swap(true, Ordering::Relaxed) {
+                        log::info!("Jitter source {} passes its health check again", cfg.id);
+                    }
+                    let span = buffer.lock().await.extend(&bytes);
+                    ledger::record_read(&cfg.id, span, Origin::Device("jitter"));
+                    filled.notify_waiters();
+                }
+                Err(e) => {
+                    // A quiet or coarse clock may recover, so this never gives up
+                    failures = failures.saturating_add(1);
+                    let delay = retry.backoff(failures);
+                    retries.fetch_add(1, Ordering::Relaxed);
+                    passing.store(false, Ordering::Relaxed);
+                    log::warn!("Jitter source {} collection failed: {} - trying again in {:?}", cfg.id, e, delay);
+                    sleep(delay).await;
+                }
+            }
+        }
+    }
+
+}
+
+#[async_trait]
+impl EntropySource for JitterSource {
+    async fn read_traced(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+        self.core.serve_buffered(num_bytes, timeout_ms).await
+    }
+
+    fn core(&self) -> Option<&BufferCore> {
+        Some(&self.core)
+    }
+
+    async fn get_buffer_status(&self) -> (String, Option<(usize, usize)>) {
+        self.core.status().await
+    }
+
+    fn metrics(&self) -> SourceMetrics {
+        SourceMetrics {
+            retries: self.retries.load(Ordering::Relaxed),
+            estimated_rate: estimated_rate(&self.rate),
+            leftovers: self.core.lane.stats(),
+            ..SourceMetrics::default()
+        }
+    }
+
+    async fn benchmark(&self, budget: Duration) -> Option<f64> {
+        measure(&self.rate, budget, || async {
+            let bytes = collect_jitter(BENCHMARK_CHUNK, budget).await?;
+            let span = self.core.buffer.lock().await.extend(&bytes);
+            ledger::record_read(&self.cfg.id, span, Origin::Device("jitter"));
+            self.core.filled.notify_waiters();
+            Ok(bytes.len())
+        })
+        .await
+    }
+
+    fn is_available(&self) -> bool {
+        self.passing.load(Ordering::Relaxed)
+    }
+}
+
+/// Most publishers an MQTT source keeps apart; later ones share one entry.
+const MAX_PUBLISHERS: usize = 1024;
+
+/// Publisher entry for those beyond `MAX_PUBLISHERS`.
+const OTHER_PUBLISHERS: &str = "(other)";
+
+/// How long an MQTT source waits for a message before beating its heartbeat.
+const MQTT_POLL: Duration = Duration::from_secs(1);
+
+/// How long a NATS source waits for a block before beating its heartbeat.
+#[cfg(feature = "nats")]
+const NATS_POLL: Duration = Duration::from_secs(1);
+
+/// What an MQTT source knows about one publisher.
+#[derive(Default)]
+struct Publisher {
+    stats: PublisherStats,
+    /// SHA-256 of its last accepted payload, to catch repeats.
+    last_digest: Option<[u8; 32]>,
+}
+
+/// Fills the buffer of an MQTT source with the payloads beacons publish to
+/// its topic, over a subscription renewed with the `retry` backoff whenever
+/// the connection is lost. Each payload is checked on its own before it is
+/// buffered, and counted against its publisher. Brokers do not wait for a
+/// full buffer, so payloads that do not fit are dropped.
+pub struct MqttSource {
+    subscriber: Arc<Subscriber>,
+    core: BufferCore,
+    /// Set while subscribed.
+    connected: Arc<AtomicBool>,
+    retries: Arc<AtomicU64>,
+    publishers: Arc<std::sync::Mutex<HashMap<String, Publisher>>>,
+}
+
+impl MqttSource {
+    pub fn new(cfg: MqttConfig) -> Result<Self, String> {
+        let subscriber = Arc::new(Subscriber::new(&cfg)?);
+        let capacity = cfg.buffer_mebibytes.unwrap_or(1).max(1) as usize * 1024 * 1024;
+
+        // Store payloads whenever the buffer is not full
+        let tuning = Tuning::new(100, cfg.max_payload_bytes.unwrap_or(DEFAULT_MQTT_MAX_PAYLOAD_BYTES));
+        let core = BufferCore::new("MQTT", &cfg.id, &cfg.common, capacity, true, tuning);
+        let connected = Arc::new(AtomicBool::new(false));
+        let retries = Arc::new(AtomicU64::new(0));
+        let publishers = Arc::new(std::sync::Mutex::new(HashMap::new()));
+
+        let task = (cfg.clone(), subscriber.clone(), core.buffer.clone(), core.tuning.clone(), core.filled.clone(), connected.clone(), retries.clone(), publishers.clone());
+        supervisor::spawn(format!("mqtt:{}", cfg.id), Some(STALL_TIMEOUT), move |heartbeat| {
+            let (cfg, subscriber, buffer, tuning, filled, connected, retries, publishers) = task.clone();
+            let cpus = cfg.common.cpus.clone();
+            let id = cfg.id.clone();
+            let work = Self::supervise(cfg, subscriber, buffer, tuning, filled, connected, retries, publishers, heartbeat);
+            affinity::on_cpus(format!("mqtt-{}", id), cpus, work)
+        });
+
+        Ok(Self { subscriber, core, connected, retries, publishers })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn supervise(
+        cfg: MqttConfig,
+        subscriber: Arc<Subscriber>,
+        buffer: Arc<tokio::sync::Mutex<CircularBuffer>>,
+        tuning: Arc<Tuning>,
+        filled: Arc<Notify>,
+        connected: Arc<AtomicBool>,
+        retries: Arc<AtomicU64>,
+        publishers: Arc<std::sync::Mutex<HashMap<String, Publisher>>>,
+        heartbeat: Heartbeat,
+    ) {
+        let retry = RetryPolicy::from_config(cfg.retry.as_ref());
+        let mut failures = 0u32;
+        loop {
+            heartbeat.beat();
+            let mut received = 0u64;
+            match subscriber.subscribe().await {
+                Ok(mut session) => {
+                    log::info!("MQTT source {} subscribed to '{}' on {}", cfg.id, subscriber.topic(), subscriber.url());
+                    connected.store(true, Ordering::Relaxed);
+                    let e = loop {
+                        heartbeat.beat();
+                        match session.next(MQTT_POLL).await {
+                            Ok(Some(message)) => {
+                                received += 1;
+                                Self::store(&cfg, &subscriber, message, &buffer, &tuning, &filled, &publishers).await;
+                            }
+                            Ok(None) => {}
+                            Err(e) => break e,
+                        }
+                    };
+                    connected.store(false, Ordering::Relaxed);
+                    log::warn!("MQTT source {}: subscription on {} lost after {} messages: {}", cfg.id, subscriber.url(), received, e);
+                }
+                Err(e) => log::warn!("MQTT source {} cannot subscribe on {}: {}", cfg.id, subscriber.url(), e),
+            }
+            // Brokers restart and links flap, so this never gives up; a
+            // subscription that delivered starts the backoff over
+            failures = if received > 0 { 1 } else { failures.saturating_add(1) };
+            let delay = retry.backoff(failures);
+            retries.fetch_add(1, Ordering::Relaxed);
+            log::warn!("MQTT source {} reconnecting in {:?}", cfg.id, delay);
+            sleep(delay).await;
+        }
+    }
+
+    /// Checks `message` and buffers its payload if it passes and fits.
+    async fn store(
+        cfg: &MqttConfig,
+        subscriber: &Subscriber,
+        message: mqtt::Message,
+        buffer: &tokio::sync::Mutex<CircularBuffer>,
+        tuning: &Tuning,
+        filled: &Notify,
+        publishers: &std::sync::Mutex<HashMap<String, Publisher>>,
+    ) {
+        let mut id = mqtt::publisher(subscriber.topic(), &message.topic);
+        let payload = &message.payload;
+        let min = cfg.min_payload_bytes.unwrap_or(DEFAULT_MQTT_MIN_PAYLOAD_BYTES);
+        let max = cfg.max_payload_bytes.unwrap_or(DEFAULT_MQTT_MAX_PAYLOAD_BYTES);
+        let digest: [u8; 32] = Sha256::digest(payload.as_slice()).into();
+        {
+            let mut publishers = publishers.lock().unwrap_or_else(|e| e.into_inner());
+            if !publishers.contains_key(&id) && publishers.len() >= MAX_PUBLISHERS {
+                id = OTHER_PUBLISHERS.to_string();
+            }
+            let publisher = publishers.entry(id.clone()).or_default();
+            let stats = &mut publisher.stats;
+            let rejected = if message.retained {
+                stats.retained += 1;
+                Some("it is a retained message")
+            } else if payload.len() < min || payload.len() > max {
+                stats.wrong_size += 1;
+                Some("of its size")
+            } else if payload.windows(2).all(|w| w[0] == w[1]) {
+                stats.constant += 1;
+                Some("it is one byte value throughout")
+            } else if publisher.last_digest == Some(digest) {
+                stats.repeated += 1;
+                Some("it repeats the previous one")
+            } else {
+                None
+            };
+            if let Some(reason) = rejected {
+                log::debug!("MQTT source {}: dropped a {}-byte payload from {} because {}", cfg.id, payload.len(), id, reason);
+                return;
+            }
+            publisher.last_digest = Some(digest);
+            stats.messages += 1;
+        }
+        let (current_size, max_size) = buffer_fill(buffer, &cfg.id).await;
+        let span = match tuning.wants_refill(current_size, max_size) {
+            true => buffer.lock().await.extend(payload),
+            false => Span { start: 0, len: 0 },
+        };
+        if span.len > 0 {
+            ledger::record_read(&cfg.id, span, Origin::Remote(subscriber.url()));
+            filled.notify_waiters();
+        }
+        let mut publishers = publishers.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(publisher) = publishers.get_mut(&id) {
+            publisher.stats.bytes += span.len;
+            if (span.len as usize) < payload.len() {
+                publisher.stats.overflowed += 1;
+            }
+        }
+    }
+
+}
+
+#[async_trait]
+impl EntropySource for MqttSource {
+    async fn read_traced(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+        self.core.serve_buffered(num_bytes, timeout_ms).await
+    }
+
+    fn core(&self) -> Option<&BufferCore> {
+        Some(&self.core)
+    }
+
+    async fn get_buffer_status(&self) -> (String, Option<(usize, usize)>) {
+        self.core.status().await
+    }
+
+    fn metrics(&self) -> SourceMetrics {
+        let publishers = self.publishers.lock().unwrap_or_else(|e| e.into_inner());
+        SourceMetrics {
+            retries: self.retries.load(Ordering::Relaxed),
+            leftovers: self.core.lane.stats(),
+            publishers: publishers.iter().map(|(id, p)| (id.clone(), p.stats)).collect(),
+            ..SourceMetrics::default()
+        }
+    }
+
+    // No benchmark: publishers set the pace
+
+    fn is_available(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    fn info(&self) -> HashMap<String, String> {
+        HashMap::from([
+            ("broker".to_string(), self.subscriber.url().to_string()),
+            ("topic".to_string(), self.subscriber.topic().to_string()),
+        ])
+    }
+}
+
+/// Default replenish step of a hwrng source.
+const HWRNG_REPLENISH_CHUNK: usize = 16 * 1024;
+
+/// Largest single read from `/dev/hwrng`. The kernel only returns once the
+/// whole read is filled, which takes seconds for larger reads from slow
+/// backends such as TPMs, and the device stays locked meanwhile.
+const HWRNG_READ_BYTES: usize = 512;
+
+/// How often a hwrng source re-reads its sysfs attributes.
+const HWRNG_PROBE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Moves up to `max` bytes from `/dev/hwrng` into `buffer`, recording which
+/// backend they came from. As with `pull`, device bytes only reach readers
+/// through the buffer.
+async fn pull_hwrng(id: &str, path: &str, device: &SharedPort, backend: &std::sync::Mutex<hwrng::Backend>, buffer: &tokio::sync::Mutex<CircularBuffer>, max: usize) -> Result<usize, Error> {
+    let mut device = device.lock().await;
+    let room = buffer.lock().await.available_space().min(max).min(HWRNG_READ_BYTES);
+    if room == 0 {
+        return Ok(0);
+    }
+    let mut chunk = Zeroizing::new(vec![0u8; room]);
+    let n = device.read(&mut chunk).await.map_err(io_error)?;
+    let span = buffer.lock().await.extend(&chunk[..n]);
+    record_hwrng_read(id, path, backend, span);
+    Ok(n)
+}
+
+/// Records a hwrng read of `span` in the provenance ledger, with the backend
+/// it is believed to have come from.
+fn record_hwrng_read(id: &str, path: &str, backend: &std::sync::Mutex<hwrng::Backend>, span: Span) {
+    let current = backend.lock().unwrap_or_else(|e| e.into_inner()).current.clone();
+    ledger::record_read(id, span, Origin::Hwrng { path, backend: current.as_deref() });
+}
+
+/// Reads `/dev/hwrng`, keeping track of the driver the kernel passes it
+/// through to (`rng_current`) and how much entropy it credits that driver
+/// (`rng_quality`), which a plain file source cannot see.
+pub struct HwrngSource {
+    cfg: HwrngConfig,
+    path: String,
+    device: SharedPort,
+    backend: Arc<std::sync::Mutex<hwrng::Backend>>,
+    /// Times `rng_current` changed while the source ran.
+    backend_changes: Arc<AtomicU64>,
+    core: BufferCore,
+    /// Cleared when the device fails or loses its backend, until it is reopened.
+    connected: Arc<AtomicBool>,
+    retries: Arc<AtomicU64>,
+    rate: AtomicU64,
+}
+
+impl HwrngSource {
+    pub async fn new(cfg: HwrngConfig) -> io::Result<Self> {
+        let path = cfg.path.clone().unwrap_or_else(|| hwrng::DEFAULT_PATH.to_string());
+        let sysfs = cfg.sysfs.clone().unwrap_or_else(|| hwrng::DEFAULT_SYSFS.to_string());
+        let backend = hwrng::Backend::probe(Path::new(&sysfs));
+        match (&backend.current, backend.quality) {
+            (None, _) => log::warn!("Hwrng source {}: {} reports no backend", cfg.id, sysfs),
+            (Some(current), Some(0)) => log::warn!("Hwrng source {}: the kernel credits no entropy to {}", cfg.id, current),
+            (Some(current), quality) => log::info!("Hwrng source {}: backend {}, quality {:?}", cfg.id, current, quality),
+        }
+        let max_buffer_size = cfg.buffer_mebibytes.map(|mb| mb as usize * 1024 * 1024);
+        let retry = RetryPolicy::from_config(cfg.retry.as_ref());
+        let retries = Arc::new(AtomicU64::new(0));
+        let device = retry
+            .run(&format!("Opening hwrng source {}", cfg.id), &retries, || File::open(&path))
+            .await?;
+        let device = Arc::new(tokio::sync::Mutex::new(device));
+        let backend = Arc::new(std::sync::Mutex::new(backend));
+        let backend_changes = Arc::new(AtomicU64::new(0));
+        let connected = Arc::new(AtomicBool::new(true));
+
+        // Refill below half full, in HWRNG_REPLENISH_CHUNK steps
+        let tuning = Tuning::new(50, HWRNG_REPLENISH_CHUNK);
+        let core = BufferCore::new("hwrng", &cfg.id, &cfg.common, max_buffer_size.unwrap_or(1024), max_buffer_size.is_some(), tuning);
+
+        // Watches the backend and reconnects the device, and replenishes if a buffer is configured
+        let buffered = max_buffer_size.is_some();
+        let task = Arc::new(HwrngTask {
+            cfg: cfg.clone(),
+            path: path.clone(),
+            sysfs,
+            device: device.clone(),
+            backend: backend.clone(),
+            backend_changes: backend_changes.clone(),
+            buffer: core.buffer.clone(),
+            tuning: core.tuning.clone(),
+            connected: connected.clone(),
+            buffered,
+            retries: retries.clone(),
+        });
+        supervisor::spawn(format!("hwrng:{}", cfg.id), Some(STALL_TIMEOUT), move |heartbeat| {
+            let name = format!("hwrng-{}", task.cfg.id);
+            affinity::on_cpus(name, task.cfg.common.cpus.clone(), task.clone().run(heartbeat))
+        });
+
+        Ok(Self { cfg, path, device, backend, backend_changes, core, connected, retries, rate: AtomicU64::new(0) })
+    }
+
+    async fn read_fresh(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+        // Serve from the buffer, reading from the device into it until the
+        // deadline (never for timeout 0)
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        let sleep = sleep_until(deadline);
+        tokio::pin!(sleep);
+
+        let mut result = Vec::with_capacity(num_bytes);
+        let mut spans = Spans::default();
+        loop {
+            let want = num_bytes - result.len();
+            let Some(mut buffer) = lock_fresh_until(&self.core.buffer, &self.cfg.id, deadline).await else { break };
+            let (bytes, taken) = buffer.take_traced(want);
+            drop(buffer);
+            result.extend_from_slice(&Zeroizing::new(bytes));
+            spans.append(taken);
+            if result.len() == num_bytes || timeout_ms == 0 {
+                break;
+            }
+            tokio::select! {
+                res = pull_hwrng(&self.cfg.id, &self.path, &self.device, &self.backend, &self.core.buffer, want) => if let Err(e) = res {
+                    // The background task reopens the device
+                    log::warn!("Hwrng {} read failed: {} - reopening", self.cfg.id, e);
+                    self.connected.store(false, Ordering::Relaxed);
+                    return Err(e);
+                },
+                _ = &mut sleep => break,
+            }
+        }
+        Ok((result, spans))
+    }
+}
+
+/// State of a hwrng source's background task.
+struct HwrngTask {
+    cfg: HwrngConfig,
+    path: String,
+    sysfs: String,
+    device: SharedPort,
+    backend: Arc<std::sync::Mutex<hwrng::Backend>>,
+    backend_changes: Arc<AtomicU64>,
+    buffer: Arc<tokio::sync::Mutex<CircularBuffer>>,
+    tuning: Arc<Tuning>,
+    connected: Arc<AtomicBool>,
+    buffered: bool,
+    retries: Arc<AtomicU64>,
+}
+
+impl HwrngTask {
+    async fn run(self: Arc<Self>, heartbeat: Heartbeat) {
+        let id = &self.cfg.id;
+        let retry = RetryPolicy::from_config(self.cfg.retry.as_ref());
+        let mut interval = interval(Duration::from_millis(10));
+        let mut next_probe = Instant::now() + HWRNG_PROBE_INTERVAL;
+        let (mut attempt, mut next_attempt) = (0u32, Instant::now());
+        loop {
+            interval.tick().await;
+            heartbeat.beat();
+            if Instant::now() >= next_probe {
+                next_probe = Instant::now() + HWRNG_PROBE_INTERVAL;
+                self.probe();
+            }
+            if !self.connected.load(Ordering::Relaxed) {
+                // Drivers get rebound, so this never gives up
+                if Instant::now() < next_attempt {
+                    continue;
+                }
+                match File::open(&self.path).await {
+                    Ok(file) => {
+                        *self.device.lock().await = file;
+                        self.connected.store(true, Ordering::Relaxed);
+                        attempt = 0;
+                        log::info!("Hwrng source {} reopened", id);
+                    }
+                    Err(e) => {
+                        attempt = attempt.saturating_add(1);
+                        let delay = retry.backoff(attempt);
+                        self.retries.fetch_add(1, Ordering::Relaxed);
+                        log::warn!("Hwrng source {} still unavailable: {} - trying again in {:?}", id, e, delay);
+                        next_attempt = Instant::now() + delay;
+                    }
+                }
+                continue;
+            }
+            if !self.buffered {
+                continue;
+            }
+            let (current_size, max_size) = buffer_fill(&self.buffer, id).await;
+            if !self.tuning.wants_refill(current_size, max_size) {
+                continue;
+            }
+            // In small reads, so requests get their turn at the device
+            let mut needed = (max_size - current_size).min(self.tuning.chunk());
+            while needed > 0 && !self.tuning.is_paused() {
+                match pull_hwrng(id, &self.path, &self.device, &self.backend, &self.buffer, needed).await {
+                    Ok(0) => break,
+                    Ok(n) => needed = needed.saturating_sub(n),
+                    Err(e) => {
+                        log::warn!("Hwrng {} read failed: {} - reopening", id, e);
+                        self.connected.store(false, Ordering::Relaxed);
+                        break;
+                    }
+                }
+                heartbeat.beat();
+            }
+            log::debug!("Hwrng {} replenished buffer: {} -> {} bytes", id, current_size, self.buffer.lock().await.len());
+        }
+    }
+
+    /// Re-reads the sysfs attributes, reporting a change of backend.
+    fn probe(&self) {
+        let probed = hwrng::Backend::probe(Path::new(&self.sysfs));
+        let mut backend = self.backend.lock().unwrap_or_else(|e| e.into_inner());
+        if probed.current != backend.current {
+            self.backend_changes.fetch_add(1, Ordering::Relaxed);
+            log::warn!(
+                "Hwrng source {}: backend changed from {} to {} (quality {:?})",
+                self.cfg.id,
+                backend.current.as_deref().unwrap_or("none"),
+                probed.current.as_deref().unwrap_or("none"),
+                probed.quality
+            );
+        }
+        *backend = probed;
+    }
+}
+
+#[async_trait]
+impl EntropySource for HwrngSource {
+    async fn read_traced(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+        if !self.is_available() {
+            return Err(Error::SourcesUnavailable);
+        }
+        self.core.lane.serve(num_bytes, |n| self.read_fresh(n, timeout_ms)).await
+    }
+
+    fn core(&self) -> Option<&BufferCore> {
+        Some(&self.core)
+    }
+
+    async fn get_buffer_status(&self) -> (String, Option<(usize, usize)>) {
+        self.core.status().await
+    }
+
+    fn metrics(&self) -> SourceMetrics {
+        SourceMetrics {
+            retries: self.retries.load(Ordering::Relaxed),
+            estimated_rate: estimated_rate(&self.rate),
+            leftovers: self.core.lane.stats(),
+            ..SourceMetrics::default()
+        }
+    }
+
+    async fn benchmark(&self, budget: Duration) -> Option<f64> {
+        if !self.is_available() {
+            return None;
+        }
+        measure(&self.rate, budget, || async {
+            let mut chunk = Zeroizing::new(vec![0u8; HWRNG_READ_BYTES]);
+            let n = self.device.lock().await.read(&mut chunk).await.map_err(io_error)?;
+            let span = self.core.buffer.lock().await.extend(&chunk[..n]);
+            record_hwrng_read(&self.cfg.id, &self.path, &self.backend, span);
+            Ok(n)
+        })
+        .await
+    }
+
+    fn is_available(&self) -> bool {
+        self.connected.load(Ordering::Relaxed) && self.backend.lock().unwrap_or_else(|e| e.into_inner()).current.is_some()
+    }
+
+    fn info(&self) -> HashMap<String, String> {
+        let mut info = self.backend.lock().unwrap_or_else(|e| e.into_inner()).describe();
+        info.insert("path".to_string(), self.path.clone());
+        info.insert("backend_changes".to_string(), self.backend_changes.load(Ordering::Relaxed).to_string());
+        info
+    }
+}
+
+/// Fills the buffer of a NATS source with the blocks its JetStream consumer
+/// hands out, pulling only while the buffer has room so the server gives
+/// the rest of the stream to other instances meanwhile. The connection is
+/// reopened with the `retry` backoff whenever it is lost.
+#[cfg(feature = "nats")]
+pub struct NatsSource {
+    consumer: Arc<nats::Consumer>,
+    core: BufferCore,
+    /// Set while joined to the consumer.
+    connected: Arc<AtomicBool>,
+    retries: Arc<AtomicU64>,
+}
+
+#[cfg(feature = "nats")]
+impl NatsSource {
+    pub fn new(cfg: NatsConfig) -> Result<Self, String> {
+        let consumer = Arc::new(nats::Consumer::new(&cfg)?);
+        let capacity = cfg.buffer_mebibytes.unwrap_or(1).max(1) as usize * 1024 * 1024;
+
+        // Pull whenever the buffer is not full
+        let tuning = Tuning::new(100, MAX_REPLENISH_CHUNK);
+        let core = BufferCore::new("NATS", &cfg.id, &cfg.common, capacity, true, tuning);
+        let connected = Arc::new(AtomicBool::new(false));
+        let retries = Arc::new(AtomicU64::new(0));
+
+        let task = (cfg.clone(), consumer.clone(), core.buffer.clone(), core.tuning.clone(), core.filled.clone(), connected.clone(), retries.clone());
+        supervisor::spawn(format!("nats:{}", cfg.id), Some(STALL_TIMEOUT), move |heartbeat| {
+            let (cfg, consumer, buffer, tuning, filled, connected, retries) = task.clone();
+            let cpus = cfg.common.cpus.clone();
+            let id = cfg.id.clone();
+            let work = Self::supervise(cfg, consumer, buffer, tuning, filled, connected, retries, heartbeat);
+            affinity::on_cpus(format!("nats-{}", id), cpus, work)
+        });
+
+        Ok(Self { consumer, core, connected, retries })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn supervise(
+        cfg: NatsConfig,
+        consumer: Arc<nats::Consumer>,
+        buffer: Arc<tokio::sync::Mutex<CircularBuffer>>,
+        tuning: Arc<Tuning>,
+        filled: Arc<Notify>,
+        connected: Arc<AtomicBool>,
+        retries: Arc<AtomicU64>,
+        heartbeat: Heartbeat,
+    ) {
+        let retry = RetryPolicy::from_config(cfg.retry.as_ref());
+        let mut failures = 0u32;
+        loop {
+            heartbeat.beat();
+            let mut received = 0u64;
+            match consumer.join().await {
+                Ok(mut session) => {
+                    log::info!("NATS source {} joined consumer {} of stream {} on {}", cfg.id, consumer.name(), consumer.stream(), consumer.url());
+                    connected.store(true, Ordering::Relaxed);
+                    let e = loop {
+                        heartbeat.beat();
+                        let (current_size, max_size) = buffer_fill(&buffer, &cfg.id).await;
+                        if !session.pulling() && tuning.wants_refill(current_size, max_size) {
+                            if let Err(e) = session.pull(max_size - current_size).await {
+                                break e;
+                            }
+                        }
+                        let block = match session.next(NATS_POLL).await {
+                            Ok(Some(block)) => block,
+                            Ok(None) => continue,
+                            Err(e) => break e,
+                        };
+                        received += block.len() as u64;
+                        let span = buffer.lock().await.extend(&block);
+                        if (span.len as usize) < block.len() {
+                            log::warn!("NATS source {}: {} bytes of a block did not fit the buffer", cfg.id, block.len() - span.len as usize);
+                        }
+                        ledger::record_read(&cfg.id, span, Origin::Remote(consumer.url()));
+                        filled.notify_waiters();
+                    };
+                    connected.store(false, Ordering::Relaxed);
+                    log::warn!("NATS source {}: connection to {} lost after {} bytes: {}", cfg.id, consumer.url(), received, e);
+                }
+                Err(e) => log::warn!("NATS source {} cannot join consumer {} on {}: {}", cfg.id, consumer.name(), consumer.url(), e),
+            }
+            // A connection that delivered starts the backoff over
+            failures = if received > 0 { 1 } else { failures.saturating_add(1) };
+            let delay = retry.backoff(failures);
+            retries.fetch_add(1, Ordering::Relaxed);
+            log::warn!("NATS source {} reconnecting in {:?}", cfg.id, delay);
+            sleep(delay).await;
+        }
+    }
+
+}
+
+#[cfg(feature = "nats")]
+#[async_trait]
+impl EntropySource for NatsSource {
+    async fn read_traced(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+        self.core.serve_buffered(num_bytes, timeout_ms).await
+    }
+
+    fn core(&self) -> Option<&BufferCore> {
+        Some(&self.core)
+    }
+
+    async fn get_buffer_status(&self) -> (String, Option<(usize, usize)>) {
+        self.core.status().await
+    }
+
+    fn metrics(&self) -> SourceMetrics {
+        SourceMetrics {
+            retries: self.retries.load(Ordering::Relaxed),
+            leftovers: self.core.lane.stats(),
+            ..SourceMetrics::default()
+        }
+    }
+
+    // No benchmark: what is pulled is taken from the rest of the fleet
+
+    fn is_available(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    fn info(&self) -> HashMap<String, String> {
+        HashMap::from([
+            ("server".to_string(), self.consumer.url().to_string()),
+            ("stream".to_string(), self.consumer.stream().to_string()),
+            ("consumer".to_string(), self.consumer.name().to_string()),
+        ])
+    }
+}
+
+/// Largest read from an exec source's stdout per replenish step.
+const EXEC_READ_CHUNK: usize = 64 * 1024;
+
+/// Fills the buffer of an exec source from the stdout of its command, which
+/// is restarted with the `retry` backoff whenever it exits. While the buffer
+/// is full nothing is read, so the command blocks on its pipe. Readers only
+/// take what is buffered, waiting for the command at most until their
+/// deadline.
+pub struct ExecSource {
+    core: BufferCore,
+    /// Set while the command runs.
+    running: Arc<AtomicBool>,
+    retries: Arc<AtomicU64>,
+}
+
+/// A command that ran at least this long counts as healthy again, and its
+/// restart backoff starts over.
+const EXEC_HEALTHY_RUNTIME: Duration = Duration::from_secs(60);
+
+impl ExecSource {
+    pub fn new(cfg: ExecConfig) -> Result<Self, String> {
+        if cfg.command.is_empty() {
+            return Err("command is empty".to_string());
+        }
+        let capacity = cfg.buffer_mebibytes.unwrap_or(1).max(1) as usize * 1024 * 1024;
+
+        // Refill whenever the buffer is not full
+        let tuning = Tuning::new(100, EXEC_READ_CHUNK);
+        let core = BufferCore::new("exec", &cfg.id, &cfg.common, capacity, true, tuning);
+        let running = Arc::new(AtomicBool::new(false));
+        let retries = Arc::new(AtomicU64::new(0));
+
+        // The child is killed when the task is dropped, so a restarted or
+        // stopped task never leaves one behind
+        let task = (cfg.clone(), core.buffer.clone(), core.tuning.clone(), core.filled.clone(), running.clone(), retries.clone());
+        supervisor::spawn(format!("exec:{}", cfg.id), Some(STALL_TIMEOUT), move |heartbeat| {
+            let (cfg, buffer, tuning, filled, running, retries) = task.clone();
+            let cpus = cfg.common.cpus.clone();
+            let id = cfg.id.clone();
+            let work = Self::supervise(cfg, buffer, tuning, filled, running, retries, heartbeat);
+            affinity::on_cpus(format!("exec-{}", id), cpus, work)
+        });
+
+        Ok(Self { core, running, retries })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn supervise(
+        cfg: ExecConfig,
+        buffer: Arc<tokio::sync::Mutex<CircularBuffer>>,
+        tuning: Arc<Tuning>,
+        filled: Arc<Notify>,
+        running: Arc<AtomicBool>,
+        retries: Arc<AtomicU64>,
+        heartbeat: Heartbeat,
+    ) {
+        let retry = RetryPolicy::from_config(cfg.retry.as_ref());
+        let program = &cfg.command[0];
+        let mut failures = 0u32;
+        loop {
+            heartbeat.beat();
+            let started = Instant::now();
+            let child = tokio::process::Command::new(program)
+                .args(&cfg.command[1..])
+                .stdin(std::process::Stdio::null())
+                .stdout(std::process::Stdio::piped())
+                .kill_on_drop(true)
+                .spawn();
+            match child {
+                Ok(mut child) => {
+                    let pid = child.id();
+                    log::info!("Exec source {} started {} (pid {})", cfg.id, program, pid.unwrap_or(0));
+                    running.store(true, Ordering::Relaxed);
+                    let res = match child.stdout.take() {
+                        Some(stdout) => Self::consume(&cfg, pid, stdout, &buffer, &tuning, &filled, &heartbeat).await,
+                        None => Err(io::Error::other("stdout not captured")),
+                    };
+                    running.store(false, Ordering::Relaxed);
+                    let _ = child.start_kill();
+                    match (res, child.wait().await) {
+                        (Err(e), _) => log::warn!("Exec source {}: reading from {} failed: {}", cfg.id, program, e),
+                        (Ok(()), Ok(status)) => log::warn!("Exec source {}: {} exited with {}", cfg.id, program, status),
+                        (Ok(()), Err(e)) => log::warn!("Exec source {}: {} closed its stdout: {}", cfg.id, program, e),
+                    }
+                }
+                Err(e) => log::warn!("Exec source {} cannot start {}: {}", cfg.id, program, e),
+            }
+            // Vendor tools crash and get updated, so this never gives up
+            failures = if started.elapsed() >= EXEC_HEALTHY_RUNTIME { 1 } else { failures.saturating_add(1) };
+            let delay = retry.backoff(failures);
+            retries.fetch_add(1, Ordering::Relaxed);
+            log::warn!("Exec source {} restarting {} in {:?}", cfg.id, program, delay);
+            sleep(delay).await;
+        }
+    }
+
+    /// Moves the command's output into `buffer` until it closes its stdout.
+    async fn consume(
+        cfg: &ExecConfig,
+        pid: Option<u32>,
+        mut stdout: tokio::process::ChildStdout,
+        buffer: &tokio::sync::Mutex<CircularBuffer>,
+        tuning: &Tuning,
+        filled: &Notify,
+        heartbeat: &Heartbeat,
+    ) -> io::Result<()> {
+        let mut interval = interval(Duration::from_millis(10));
+        loop {
+            interval.tick().await;
+            heartbeat.beat();
+            let (current_size, max_size) = buffer_fill(buffer, &cfg.id).await;
+            if !tuning.wants_refill(current_size, max_size) {
+                continue;
+            }
+            let mut chunk = Zeroizing::new(vec![0u8; (max_size - current_size).min(tuning.chunk())]);
+            // Pipe reads can be cancelled without losing bytes, which keeps the heartbeat going
+            let n = match tokio::time::timeout(Duration::from_secs(1), stdout.read(&mut chunk)).await {
+                Err(_) => continue,
+                Ok(res) => res?,
+            };
+            if n == 0 {
+                return Ok(());
+            }
+            let span = buffer.lock().await.extend(&chunk[..n]);
+            ledger::record_read(&cfg.id, span, Origin::Process { command: &cfg.command[0], pid });
+            filled.notify_waiters();
+        }
+    }
+
+}
+
+#[async_trait]
+impl EntropySource for ExecSource {
+    async fn read_traced(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+        self.core.serve_buffered(num_bytes, timeout_ms).await
+    }
+
+    fn core(&self) -> Option<&BufferCore> {
+        Some(&self.core)
+    }
+
+    async fn get_buffer_status(&self) -> (String, Option<(usize, usize)>) {
+        self.core.status().await
+    }
+
+    fn metrics(&self) -> SourceMetrics {
+        SourceMetrics {
+            retries: self.retries.load(Ordering::Relaxed),
+            leftovers: self.core.lane.stats(),
+            ..SourceMetrics::default()
+        }
+    }
+
+    // No benchmark: the command's stdout has a single reader, the replenisher
+
+    fn is_available(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+}
+
+/// Default replenish step of a PKCS#11 source.
+const PKCS11_CHUNK: usize = 16 * 1024;
+
+type SharedToken = Arc<std::sync::Mutex<pkcs11::Token>>;
+
+/// Up to `len` bytes from the token, off the async workers as the module's
+/// calls block (for a network HSM, on a round trip).
+async fn generate_pkcs11(token: &SharedToken, len: usize) -> Result<Zeroizing<Vec<u8>>, String> {
+    let token = token.clone();
+    tokio::task::spawn_blocking(move || token.lock().unwrap_or_else(|e| e.into_inner()).generate(len))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+fn record_pkcs11_read(cfg: &Pkcs11Config, serial: &str, span: Span) {
+    ledger::record_read(&cfg.id, span, Origin::Token { module: &cfg.module, slot: cfg.slot, serial });
+}
+
+/// `C_GenerateRandom` of a PKCS#11 token. A background task keeps the buffer
+/// filled; when a call fails the session is reopened with the `retry` backoff.
+pub struct Pkcs11Source {
+    cfg: Pkcs11Config,
+    token: SharedToken,
+    description: pkcs11::Description,
+    core: BufferCore,
+    /// Set while a session is open.
+    connected: Arc<AtomicBool>,
+    retries: Arc<AtomicU64>,
+    rate: AtomicU64,
+}
+
+impl Pkcs11Source {
+    /// Opens the session, so a wrong module, slot or PIN fails startup.
+    pub fn new(cfg: Pkcs11Config) -> Result<Self, String> {
+        let pin = cfg.pin_file.as_deref().map(pkcs11::read_pin).transpose()?;
+        let token = pkcs11::Token::open(&cfg.module, cfg.slot, pin)?;
+        let description = token.description().clone();
+        let token = Arc::new(std::sync::Mutex::new(token));
+        // Closed (and so logged out) once the replenish task has stopped
+        let close = token.clone();
+        shutdown::register(Stage::Zeroize, format!("pkcs11 {} session", cfg.id), move || {
+            close.lock().unwrap_or_else(|e| e.into_inner()).close()
+        });
+
+        let capacity = cfg.buffer_mebibytes.unwrap_or(1).max(1) as usize * 1024 * 1024;
+
+        // Refill whenever the buffer is not full
+        let tuning = Tuning::new(100, PKCS11_CHUNK);
+        let core = BufferCore::new("pkcs11", &cfg.id, &cfg.common, capacity, true, tuning);
+        let connected = Arc::new(AtomicBool::new(true));
+        let retries = Arc::new(AtomicU64::new(0));
+
+        let task = (cfg.clone(), token.clone(), description.serial.clone(), core.buffer.clone(), core.tuning.clone(), core.filled.clone(), connected.clone(), retries.clone());
+        supervisor::spawn(format!("pkcs11-generate:{}", cfg.id), Some(STALL_TIMEOUT), move |heartbeat| {
+            let (cfg, token, serial, buffer, tuning, filled, connected, retries) = task.clone();
+            let cpus = cfg.common.cpus.clone();
+            let id = cfg.id.clone();
+            let work = Self::replenish(cfg, token, serial, buffer, tuning, filled, connected, retries, heartbeat);
+            affinity::on_cpus(format!("pkcs11-{}", id), cpus, work)
+        });
+
+        Ok(Self { cfg, token, description, core, connected, retries, rate: AtomicU64::new(0) })
+    }
+
+    /// Model and firmware of the token, for reply provenance.
+    pub fn model(&self) -> String {
+        format!("{} {} (firmware {})", self.description.manufacturer, self.description.model, self.description.firmware)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn replenish(
+        cfg: Pkcs11Config,
+        token: SharedToken,
+        serial: String,
+        buffer: Arc<tokio::sync::Mutex<CircularBuffer>>,
+        tuning: Arc<Tuning>,
+        filled: Arc<Notify>,
+        connected: Arc<AtomicBool>,
+        retries: Arc<AtomicU64>,
+        heartbeat: Heartbeat,
+    ) {
+        let retry = RetryPolicy::from_config(cfg.retry.as_ref());
+        let mut interval = interval(Duration::from_millis(10));
+        let mut failures = 0u32;
+        loop {
+            interval.tick().await;
+            heartbeat.beat();
+            if !connected.load(Ordering::Relaxed) {
+                let reopen = token.clone();
+                match tokio::task::spawn_blocking(move || reopen.lock().unwrap_or_else(|e| e.into_inner()).reopen()).await {
+                    Ok(Ok(())) => {
+                        log::info!("PKCS#11 source {} reopened its session", cfg.id);
+                        connected.store(true, Ordering::Relaxed);
+                    }
+                    Ok(Err(e)) => {
+                        failures = failures.saturating_add(1);
+                        let delay = retry.backoff(failures);
+                        retries.fetch_add(1, Ordering::Relaxed);
+                        log::warn!("PKCS#11 source {} cannot reopen its session: {} - trying again in {:?}", cfg.id, e, delay);
+                        sleep(delay).await;
+                        continue;
+                    }
+                    Err(_) => continue,
+                }
+            }
+            let (current_size, max_size) = buffer_fill(&buffer, &cfg.id).await;
+            if !tuning.wants_refill(current_size, max_size) {
+                continue;
+            }
+            let wanted = (max_size - current_size).min(tuning.chunk());
+            match generate_pkcs11(&token, wanted).await {
+                Ok(bytes) => {
+                    failures = 0;
+                    let span = buffer.lock().await.extend(&bytes);
+                    record_pkcs11_read(&cfg, &serial, span);
+                    filled.notify_waiters();
+                }
+                Err(e) => {
+                    // The failed call closed the session; the next pass reopens it
+                    failures = failures.saturating_add(1);
+                    let delay = retry.backoff(failures);
+                    retries.fetch_add(1, Ordering::Relaxed);
+                    connected.store(false, Ordering::Relaxed);
+                    log::warn!("PKCS#11 source {} failed: {} - reopening in {:?}", cfg.id, e, delay);
+                    sleep(delay).await;
+                }
+            }
+        }
+    }
+
+}
+
+#[async_trait]
+impl EntropySource for Pkcs11Source {
+    async fn read_traced(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+        self.core.serve_buffered(num_bytes, timeout_ms).await
+    }
+
+    fn core(&self) -> Option<&BufferCore> {
+        Some(&self.core)
+    }
+
+    async fn get_buffer_status(&self) -> (String, Option<(usize, usize)>) {
+        self.core.status().await
+    }
+
+    fn metrics(&self) -> SourceMetrics {
+        SourceMetrics {
+            retries: self.retries.load(Ordering::Relaxed),
+            estimated_rate: estimated_rate(&self.rate),
+            leftovers: self.core.lane.stats(),
+            ..SourceMetrics::default()
+        }
+    }
+
+    async fn benchmark(&self, budget: Duration) -> Option<f64> {
+        measure(&self.rate, budget, || async {
+            let bytes = generate_pkcs11(&self.token, BENCHMARK_CHUNK).await.map_err(|e| {
+                // The session is closed; the replenish task reopens it
+                self.connected.store(false, Ordering::Relaxed);
+                log::warn!("PKCS#11 source {} benchmark failed: {}", self.cfg.id, e);
+                Error::SourcesUnavailable
+            })?;
+            let span = self.core.buffer.lock().await.extend(&bytes);
+            record_pkcs11_read(&self.cfg, &self.description.serial, span);
+            self.core.filled.notify_waiters();
+            Ok(bytes.len())
+        })
+        .await
+    }
+
+    fn is_available(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    fn info(&self) -> HashMap<String, String> {
+        let mut info = HashMap::new();
+        info.insert("module".to_string(), self.cfg.module.clone());
+        info.insert("slot".to_string(), self.cfg.slot.to_string());
+        info.insert("label".to_string(), self.description.label.clone());
+        info.insert("serial".to_string(), self.description.serial.clone());
+        info.insert("logged_in".to_string(), self.cfg.pin_file.is_some().to_string());
+        info.insert("session".to_string(), if self.connected.load(Ordering::Relaxed) { "open" } else { "reopening" }.to_string());
+        info
+    }
+}
+
+/// Frames captured per blocking read, about 85 ms at 48 kHz.
+const AUDIO_READ_FRAMES: usize = 4096;
+
+/// Reads in a row without a single whitened bit before the input counts as
+/// silent (muted, unplugged into a digital zero, or clipped).
+const AUDIO_SILENT_READS: u32 = 12;
+
+type SharedCapture = Arc<std::sync::Mutex<Option<audio::Capture>>>;
+
+/// Noise of an ALSA capture device: the low bits of every sample, debiased
+/// with von Neumann's method (see `audio.rs`). A background task keeps the
+/// buffer filled; when a read fails the device is reopened with the `retry`
+/// backoff.
+pub struct AudioSource {
+    cfg: AudioConfig,
+    device: String,
+    core: BufferCore,
+    /// Set while the device is open and its input yields bits.
+    live: Arc<AtomicBool>,
+    retries: Arc<AtomicU64>,
+}
+
+impl AudioSource {
+    /// Opens the device, so a wrong name or an unsupported rate fails startup.
+    pub fn new(cfg: AudioConfig) -> Result<Self, String> {
+        let device = cfg.device.clone().unwrap_or_else(|| "default".to_string());
+        let capture = audio::Capture::open(&device, cfg.sample_rate.unwrap_or(DEFAULT_AUDIO_SAMPLE_RATE), cfg.channels.unwrap_or(1))?;
+        let capture: SharedCapture = Arc::new(std::sync::Mutex::new(Some(capture)));
+
+        let capacity = cfg.buffer_mebibytes.unwrap_or(1).max(1) as usize * 1024 * 1024;
+
+        // Refill whenever the buffer is not full
+        let tuning = Tuning::new(100, MAX_REPLENISH_CHUNK);
+        let core = BufferCore::new("audio", &cfg.id, &cfg.common, capacity, true, tuning);
+        let live = Arc::new(AtomicBool::new(true));
+        let retries = Arc::new(AtomicU64::new(0));
+
+        let task = (cfg.clone(), device.clone(), capture, core.buffer.clone(), core.tuning.clone(), core.filled.clone(), live.clone(), retries.clone());
+        supervisor::spawn(format!("audio-capture:{}", cfg.id), Some(STALL_TIMEOUT), move |heartbeat| {
+            let (cfg, device, capture, buffer, tuning, filled, live, retries) = task.clone();
+            let cpus = cfg.common.cpus.clone();
+            let id = cfg.id.clone();
+            let work = Self::replenish(cfg, device, capture, buffer, tuning, filled, live, retries, heartbeat);
+            affinity::on_cpus(format!("audio-{}", id), cpus, work)
+        });
+
+        Ok(Self { cfg, device, core, live, retries })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn replenish(
+        cfg: AudioConfig,
+        device: String,
+        capture: SharedCapture,
+        buffer: Arc<tokio::sync::Mutex<CircularBuffer>>,
+        tuning: Arc<Tuning>,
+        filled: Arc<Notify>,
+        live: Arc<AtomicBool>,
+        retries: Arc<AtomicU64>,
+        heartbeat: Heartbeat,
+    ) {
+        let retry = RetryPolicy::from_config(cfg.retry.as_ref());
+        let (rate, channels) = (cfg.sample_rate.unwrap_or(DEFAULT_AUDIO_SAMPLE_RATE), cfg.channels.unwrap_or(1));
+        let mut harvester = audio::Harvester::new(cfg.bits_per_sample.unwrap_or(DEFAULT_AUDIO_BITS_PER_SAMPLE), channels as usize);
+        let origin = format!("alsa:{}", device);
+        let mut interval = interval(Duration::from_millis(10));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let (mut failures, mut silent_reads) = (0u32, 0u32);
+        loop {
+            interval.tick().await;
+            heartbeat.beat();
+            let (current_size, max_size) = buffer_fill(&buffer, &cfg.id).await;
+            if !tuning.wants_refill(current_size, max_size) {
+                continue;
+            }
+            let reading = capture.clone();
+            let name = device.clone();
+            let read = tokio::task::spawn_blocking(move || {
+                let mut capture = reading.lock().unwrap_or_else(|e| e.into_inner());
+                if capture.is_none() {
+                    *capture = Some(audio::Capture::open(&name, rate, channels)?);
+                }
+                let read = capture.as_mut().map_or_else(|| Err("not open".to_string()), |c| c.read(AUDIO_READ_FRAMES));
+                if read.is_err() {
+                    // Reopened on the next pass
+                    *capture = None;
+                }
+                read
+            })
+            .await
+            .unwrap_or_else(|e| Err(e.to_string()));
+            let samples = match read {
+                Ok(samples) => samples,
+                Err(e) => {
+                    failures = failures.saturating_add(1);
+                    let delay = retry.backoff(failures);
+                    retries.fetch_add(1, Ordering::Relaxed);
+                    live.store(false, Ordering::Relaxed);
+                    log::warn!("Audio source {} failed: {} - reopening in {:?}", cfg.id, e, delay);
+                    sleep(delay).await;
+                    continue;
+                }
+            };
+            failures = 0;
+            let mut bytes = Zeroizing::new(Vec::with_capacity(AUDIO_READ_FRAMES));
+            harvester.feed(&samples, &mut bytes);
+            if bytes.is_empty() {
+                silent_reads = silent_reads.saturating_add(1);
+                if silent_reads == AUDIO_SILENT_READS {
+                    live.store(false, Ordering::Relaxed);
+                    log::warn!("Audio source {}: the input of {} is silent or its low bits are stuck - no output until it changes", cfg.id, device);
+                }
+                continue;
+            }
+            if silent_reads >= AUDIO_SILENT_READS || !live.load(Ordering::Relaxed) {
+                log::info!("Audio source {} is delivering noise from {} again", cfg.id, device);
+            }
+            silent_reads = 0;
+            live.store(true, Ordering::Relaxed);
+            // What does not fit is dropped rather than pushing out older bytes
+            bytes.truncate(max_size - current_size);
+            let span = buffer.lock().await.extend(&bytes);
+            ledger::record_read(&cfg.id, span, Origin::Device(&origin));
+            filled.notify_waiters();
+        }
+    }
+
+}
+
+#[async_trait]
+impl EntropySource for AudioSource {
+    async fn read_traced(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+        self.core.serve_buffered(num_bytes, timeout_ms).await
+    }
+
+    fn core(&self) -> Option<&BufferCore> {
+        Some(&self.core)
+    }
+
+    async fn get_buffer_status(&self) -> (String, Option<(usize, usize)>) {
+        self.core.status().await
+    }
+
+    fn metrics(&self) -> SourceMetrics {
+        SourceMetrics {
+            retries: self.retries.load(Ordering::Relaxed),
+            leftovers: self.core.lane.stats(),
+            ..SourceMetrics::default()
+        }
+    }
+
+    // No benchmark: the device has a single reader, the replenisher
+
+    fn is_available(&self) -> bool {
+        self.live.load(Ordering::Relaxed)
+    }
+
+    fn info(&self) -> HashMap<String, String> {
+        let mut info = HashMap::new();
+        info.insert("device".to_string(), self.device.clone());
+        info.insert("sample_rate".to_string(), self.cfg.sample_rate.unwrap_or(DEFAULT_AUDIO_SAMPLE_RATE).to_string());
+        info.insert("bits_per_sample".to_string(), self.cfg.bits_per_sample.unwrap_or(DEFAULT_AUDIO_BITS_PER_SAMPLE).to_string());
+        info.insert("channels".to_string(), self.cfg.channels.unwrap_or(1).to_string());
+        info.insert("live".to_string(), self.live.load(Ordering::Relaxed).to_string());
+        info
+    }
+}
+
+/// Bytes a D-Bus source asks for per call unless `request_bytes` says otherwise.
+const DBUS_REQUEST_BYTES: usize = 4096;
+
+/// Fills the buffer of a D-Bus source from an upstream service's `ReadBytes`
+/// in the background, like `HttpSource` does from a web API.
+pub struct DbusSource {
+    cfg: DbusConfig,
+    upstream: Arc<Upstream>,
+    core: BufferCore,
+    /// Cleared when a call fails, until one succeeds.
+    reachable: Arc<AtomicBool>,
+    retries: Arc<AtomicU64>,
+    rate: AtomicU64,
+}
+
+/// Buffers the bytes of one upstream answer and wakes readers waiting for them.
+async fn store_upstream(id: &str, upstream: &Upstream, buffer: &tokio::sync::Mutex<CircularBuffer>, filled: &Notify, bytes: &[u8]) {
+    let span = buffer.lock().await.extend(bytes);
+    ledger::record_read(id, span, Origin::Remote(upstream.display()));
+    filled.notify_waiters();
+}
+
+impl DbusSource {
+    pub fn new(cfg: DbusConfig) -> Result<Self, String> {
+        let upstream = Arc::new(Upstream::new(&cfg)?);
+        let capacity = cfg.buffer_mebibytes.unwrap_or(1).max(1) as usize * 1024 * 1024;
+
+        // Call whenever the buffer is not full, request_bytes at a time
+        let request_bytes = cfg.request_bytes.unwrap_or(DBUS_REQUEST_BYTES).clamp(1, MAX_REPLENISH_CHUNK);
+        let tuning = Tuning::new(100, request_bytes);
+        let core = BufferCore::new("D-Bus", &cfg.id, &cfg.common, capacity, true, tuning);
+        let reachable = Arc::new(AtomicBool::new(true));
+        let retries = Arc::new(AtomicU64::new(0));
+
+        let task = (cfg.clone(), upstream.clone(), core.buffer.clone(), core.tuning.clone(), core.filled.clone(), reachable.clone(), retries.clone());
+        supervisor::spawn(format!("dbus-upstream:{}", cfg.id), Some(STALL_TIMEOUT), move |heartbeat| {
+            let (cfg, upstream, buffer, tuning, filled, reachable, retries) = task.clone();
+            let cpus = cfg.common.cpus.clone();
+            let id = cfg.id.clone();
+            let work = Self::replenish(cfg, upstream, buffer, tuning, filled, reachable, retries, heartbeat);
+            affinity::on_cpus(format!("dbus-{}", id), cpus, work)
+        });
+
+        Ok(Self { cfg, upstream, core, reachable, retries, rate: AtomicU64::new(0) })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn replenish(
+        cfg: DbusConfig,
+        upstream: Arc<Upstream>,
+        buffer: Arc<tokio::sync::Mutex<CircularBuffer>>,
+        tuning: Arc<Tuning>,
+        filled: Arc<Notify>,
+        reachable: Arc<AtomicBool>,
+        retries: Arc<AtomicU64>,
+        heartbeat: Heartbeat,
+    ) {
+        let retry = RetryPolicy::from_config(cfg.retry.as_ref());
+        let mut interval = interval(Duration::from_millis(10));
+        let mut failures = 0u32;
+        loop {
+            interval.tick().await;
+            heartbeat.beat();
+            let (current_size, max_size) = buffer_fill(&buffer, &cfg.id).await;
+            if !tuning.wants_refill(current_size, max_size) {
+                continue;
+            }
+            let wanted = (max_size - current_size).min(tuning.chunk());
+            match upstream.read(wanted).await {
+                Ok(bytes) => {
+                    failures = 0;
+                    if !reachable.swap(true, Ordering::Relaxed) {
+                        log::info!("D-Bus source {} reachable again", cfg.id);
+                    }
+                    store_upstream(&cfg.id, &upstream, &buffer, &filled, &bytes).await;
+                }
+                Err(e) => {
+                    // An upstream that keeps failing is called less often, never given up on
+                    failures = failures.saturating_add(1);
+                    let delay = retry.backoff(failures);
+                    retries.fetch_add(1, Ordering::Relaxed);
+                    reachable.store(false, Ordering::Relaxed);
+                    log::warn!("D-Bus source {} call to {} failed: {} - trying again in {:?}", cfg.id, upstream.display(), e, delay);
+                    sleep(delay).await;
+                }
+            }
+        }
+    }
+
+}
+
+#[async_trait]
+impl EntropySource for DbusSource {
+    async fn read_traced(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+        self.core.serve_buffered(num_bytes, timeout_ms).await
+    }
+
+    fn core(&self) -> Option<&BufferCore> {
+        Some(&self.core)
+    }
+
+    async fn get_buffer_status(&self) -> (String, Option<(usize, usize)>) {
+        self.core.status().await
+    }
+
+    fn metrics(&self) -> SourceMetrics {
+        SourceMetrics {
+            retries: self.retries.load(Ordering::Relaxed),
+            estimated_rate: estimated_rate(&self.rate),
+            leftovers: self.core.lane.stats(),
+            ..SourceMetrics::default()
+        }
+    }
+
+    async fn benchmark(&self, budget: Duration) -> Option<f64> {
+        measure(&self.rate, budget, || async {
+            let bytes = self.upstream.read(BENCHMARK_CHUNK).await.map_err(|e| {
+                log::warn!("D-Bus source {} benchmark failed: {}", self.cfg.id, e);
+                Error::SourcesUnavailable
+            })?;
+            store_upstream(&self.cfg.id, &self.upstream, &self.core.buffer, &self.core.filled, &bytes).await;
+            Ok(bytes.len())
+        })
+        .await
+    }
+
+    fn is_available(&self) -> bool {
+        self.reachable.load(Ordering::Relaxed)
+    }
+
+    fn info(&self) -> HashMap<String, String> {
+        HashMap::from([("upstream".to_string(), self.upstream.display().to_string())])
+    }
+}
+
+/// Bytes a gRPC source asks for per call unless `request_bytes` says otherwise.
+#[cfg(feature = "grpc")]
+const GRPC_REQUEST_BYTES: usize = 64 * 1024;
+
+/// Fills the buffer of a gRPC source from calls to its server's `GetRandom`
+/// in the background, like `DbusSource` does from an upstream service. Each
+/// call streams its bytes into the buffer as they arrive.
+#[cfg(feature = "grpc")]
+pub struct GrpcSource {
+    cfg: GrpcConfig,
+    client: Arc<grpc::Client>,
+    core: BufferCore,
+    /// Cleared when a call fails, until one succeeds.
+    reachable: Arc<AtomicBool>,
+    retries: Arc<AtomicU64>,
+    rate: AtomicU64,
+}
+
+/// Buffers the bytes of one call and wakes readers waiting for them.
+#[cfg(feature = "grpc")]
+async fn store_called(id: &str, client: &grpc::Client, buffer: &tokio::sync::Mutex<CircularBuffer>, filled: &Notify, bytes: &[u8]) {
+    let span = buffer.lock().await.extend(bytes);
+    ledger::record_read(id, span, Origin::Remote(client.display()));
+    filled.notify_waiters();
+}
+
+#[cfg(feature = "grpc")]
+impl GrpcSource {
+    pub fn new(cfg: GrpcConfig) -> Result<Self, String> {
+        let client = Arc::new(grpc::Client::new(&cfg)?);
+        let capacity = cfg.buffer_mebibytes.unwrap_or(1).max(1) as usize * 1024 * 1024;
+
+        // Call whenever the buffer is not full, request_bytes at a time
+        let request_bytes = cfg.request_bytes.unwrap_or(GRPC_REQUEST_BYTES).clamp(1, MAX_REPLENISH_CHUNK);
+        let tuning = Tuning::new(100, request_bytes);
+        let core = BufferCore::new("gRPC", &cfg.id, &cfg.common, capacity, true, tuning);
+        let reachable = Arc::new(AtomicBool::new(true));
+        let retries = Arc::new(AtomicU64::new(0));
+
+        let task = (cfg.clone(), client.clone(), core.buffer.clone(), core.tuning.clone(), core.filled.clone(), reachable.clone(), retries.clone());
+        supervisor::spawn(format!("grpc:{}", cfg.id), Some(STALL_TIMEOUT), move |heartbeat| {
+            let (cfg, client, buffer, tuning, filled, reachable, retries) = task.clone();
+            let cpus = cfg.common.cpus.clone();
+            let id = cfg.id.clone();
+            let work = Self::replenish(cfg, client, buffer, tuning, filled, reachable, retries, heartbeat);
+            affinity::on_cpus(format!("grpc-{}", id), cpus, work)
+        });
+
+        Ok(Self { cfg, client, core, reachable, retries, rate: AtomicU64::new(0) })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn replenish(
+        cfg: GrpcConfig,
+        client: Arc<grpc::Client>,
+        buffer: Arc<tokio::sync::Mutex<CircularBuffer>>,
+        tuning: Arc<Tuning>,
+        filled: Arc<Notify>,
+        reachable: Arc<AtomicBool>,
+        retries: Arc<AtomicU64>,
+        heartbeat: Heartbeat,
+    ) {
+        let retry = RetryPolicy::from_config(cfg.retry.as_ref());
+        let mut interval = interval(Duration::from_millis(10));
+        let mut failures = 0u32;
+        loop {
+            interval.tick().await;
+            heartbeat.beat();
+            let (current_size, max_size) = buffer_fill(&buffer, &cfg.id).await;
+            if !tuning.wants_refill(current_size, max_size) {
+                continue;
+            }
+            let wanted = (max_size - current_size).min(tuning.chunk());
+            match client.read(wanted).await {
+                Ok(bytes) => {
+                    failures = 0;
+                    if !reachable.swap(true, Ordering::Relaxed) {
+                        log::info!("gRPC source {} reachable again", cfg.id);
+                    }
+                    store_called(&cfg.id, &client, &buffer, &filled, &bytes).await;
+                }
+                Err(e) => {
+                    // A server that keeps failing is called less often, never given up on
+                    failures = failures.saturating_add(1);
+                    let delay = retry.backoff(failures);
+                    retries.fetch_add(1, Ordering::Relaxed);
+                    reachable.store(false, Ordering::Relaxed);
+                    log::warn!("gRPC source {} call to {} failed: {} - trying again in {:?}", cfg.id, client.display(), e, delay);
+                    sleep(delay).await;
+                }
+            }
+        }
+    }
+
+}
+
+#[cfg(feature = "grpc")]
+#[async_trait]
+impl EntropySource for GrpcSource {
+    async fn read_traced(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+        self.core.serve_buffered(num_bytes, timeout_ms).await
+    }
+
+    fn core(&self) -> Option<&BufferCore> {
+        Some(&self.core)
+    }
+
+    async fn get_buffer_status(&self) -> (String, Option<(usize, usize)>) {
+        self.core.status().await
+    }
+
+    fn metrics(&self) -> SourceMetrics {
+        SourceMetrics {
+            retries: self.retries.load(Ordering::Relaxed),
+            estimated_rate: estimated_rate(&self.rate),
+            leftovers: self.core.lane.stats(),
+            ..SourceMetrics::default()
+        }
+    }
+
+    async fn benchmark(&self, budget: Duration) -> Option<f64> {
+        measure(&self.rate, budget, || async {
+            let bytes = self.client.read(BENCHMARK_CHUNK).await.map_err(|e| {
+                log::warn!("gRPC source {} benchmark failed: {}", self.cfg.id, e);
+                Error::SourcesUnavailable
+            })?;
+            store_called(&self.cfg.id, &self.client, &self.core.buffer, &self.core.filled, &bytes).await;
+            Ok(bytes.len())
+        })
+        .await
+    }
+
+    fn is_available(&self) -> bool {
+        self.reachable.load(Ordering::Relaxed)
+    }
+
+    fn info(&self) -> HashMap<String, String> {
+        HashMap::from([("server".to_string(), self.client.display().to_string())])
+    }
+}
+
+/// Stands in for a file source that could not be opened at startup under
+/// `start_degraded`. Unavailable until a background task manages to open it.
+pub struct DeferredSource {
+    id: String,
+    inner: Arc<OnceLock<FileSource>>,
+}
+
+impl DeferredSource {
+    pub fn spawn(cfg: FileConfig) -> Self {
+        let id = cfg.id.clone();
+        let inner = Arc::new(OnceLock::new());
+        let slot = inner.clone();
+        supervisor::spawn(format!("open-source-{}", id), None, move |_| {
+            let (cfg, slot) = (cfg.clone(), slot.clone());
+            async move {
+                if slot.get().is_none() {
+                    let src = FileSource::open_until_ready(cfg.clone()).await;
+                    log::info!("File source {} opened, no longer degraded", cfg.id);
+                    let _ = slot.set(src);
+                }
+            }
+        });
+        Self { id, inner }
+    }
+}
+
+#[async_trait]
+impl EntropySource for DeferredSource {
+    async fn read_traced(&self, num_bytes: usize, timeout_ms: u64) -> Result<(Vec<u8>, Spans), Error> {
+        match self.inner.get() {
+            Some(src) => src.read_traced(num_bytes, timeout_ms).await,
+            None => Err(Error::SourcesUnavailable),
+        }
+    }
+
+    async fn return_leftover(&self, leftover: Vec<u8>, spans: Spans) {
+        if let Some(src) = self.inner.get() {
+            src.return_leftover(leftover, spans).await;
+        }
+    }
+
+    async fn get_buffer_status(&self) -> (String, Option<(usize, usize)>) {
+        match self.inner.get() {
+            Some(src) => src.get_buffer_status().await,
+            None => (self.id.clone(), None),
+        }
+    }
+
+    fn metrics(&self) -> SourceMetrics {
+        self.inner.get().map(|src| src.metrics()).unwrap_or_default()
+    }
+
+    async fn benchmark(&self, budget: Duration) -> Option<f64> {
+        self.inner.get()?.benchmark(budget).await
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.get().is_some()
+    }
+
+    fn is_healthy(&self) -> bool {
+        self.inner.get().is_some_and(|src| src.is_healthy())
+    }
+
+    async fn resize_buffer(&self, bytes: usize) -> Result<usize, Error> {
+        self.inner.get().ok_or(Error::SourcesUnavailable)?.resize_buffer(bytes).await
+    }
+
+    fn tuning(&self) -> Option<&Tuning> {
+        self.inner.get()?.tuning()
+    }
+
+    async fn set_paused(&self, paused: bool, drain: bool) {
+        if let Some(src) = self.inner.get() {
+            src.set_paused(paused, drain).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_config(path: &std::path::Path, buffer_mebibytes: Option<u32>) -> FileConfig {
+        toml::from_str(&format!("id = \"test\"\npath = \"{}\"", path.display()))
+            .map(|cfg: FileConfig| FileConfig { buffer_mebibytes, ..cfg })
+            .unwrap()
+    }
+
+    /// Reads the whole of a non-looping file through `source` in small requests.
+    async fn drain(source: &FileSource, len: usize) -> Vec<u8> {
+        let mut served = Vec::new();
+        for _ in 0..10_000 {
+            let chunk = source.read_bytes(1000, 100).await.unwrap();
+            if chunk.is_empty() && served.len() >= len {
+                break;
+            }
+            served.extend_from_slice(&chunk);
+        }
+        served
+    }
+
+    #[tokio::test]
+    async fn test_file_bytes_served_at_most_once() {
+        let contents = os_fill_rand_octets(300_000).unwrap();
+        for (name, buffer_mebibytes) in [("buffered", Some(1)), ("unbuffered", None)] {
+            let path = std::env::temp_dir().join(format!("trng-file-once-{}-{}", std::process::id(), name));
+            std::fs::write(&path, &contents).unwrap();
+            let source = FileSource::new(file_config(&path, buffer_mebibytes)).await.unwrap();
+            // Let the replenisher (if any) pull concurrently with the requests
+            tokio::task::yield_now().await;
+            assert_eq!(drain(&source, contents.len()).await, contents, "{}", name);
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_throttled() {
+        let path = std::env::temp_dir().join(format!("trng-file-throttle-{}", std::process::id()));
+        std::fs::write(&path, os_fill_rand_octets(100_000).unwrap()).unwrap();
+        let cfg = FileConfig { max_bytes_per_sec: Some(4000), ..file_config(&path, None) };
+        let source = FileSource::new(cfg).await.unwrap();
+        // A second's worth at once, then what accrues by each deadline
+        let start = Instant::now();
+        let first = source.read_bytes(10_000, 100).await.unwrap().len();
+        assert!(first >= 4000, "{}", first);
+        let second = source.read_bytes(10_000, 500).await.unwrap().len();
+        assert!(second >= 1000, "{}", second);
+        let allowed = 4000 + 4000 * start.elapsed().as_millis() as usize / 1000;
+        assert!(first + second <= allowed, "{} + {} > {}", first, second, allowed);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_file_benchmarked() {
+        let path = std::env::temp_dir().join(format!("trng-file-benchmark-{}", std::process::id()));
+        std::fs::write(&path, os_fill_rand_octets(100_000).unwrap()).unwrap();
+        let cfg = FileConfig { loop_: Some(true), ..file_config(&path, Some(1)) };
+        let source = FileSource::new(cfg).await.unwrap();
+        assert_eq!(source.metrics().estimated_rate, None);
+        // The rate measured is kept for the infeasibility warnings
+        let rate = source.benchmark(Duration::from_millis(50)).await.unwrap();
+        assert!(rate > 0.0);
+        assert_eq!(source.metrics().estimated_rate, Some(rate));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_fifo_stream_waits_across_writers() {
+        use std::io::Write;
+        let path = std::env::temp_dir().join(format!("trng-fifo-{}", std::process::id()));
+        let c_path = std::ffi::CString::new(path.to_str().unwrap()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+        let mut cfg = file_config(&path, Some(1));
+        cfg.stream = Some(true);
+        // Opening does not wait for a writer, and there is nothing to serve yet
+        let source = FileSource::new(cfg).await.unwrap();
+        assert!(source.read_bytes(16, 50).await.unwrap().is_empty());
+
+        // Two writers in turn; the first closing is not the end of the stream
+        let writer = path.clone();
+        let writers = std::thread::spawn(move || {
+            for burst in [[1u8; 100], [2u8; 100]] {
+                std::fs::OpenOptions::new().write(true).open(&writer).unwrap().write_all(&burst).unwrap();
+                std::thread::sleep(Duration::from_millis(100));
+            }
+        });
+        let mut served = Vec::new();
+        while served.len() < 200 {
+            let chunk = source.read_bytes(200 - served.len(), 2000).await.unwrap();
+            assert!(!chunk.is_empty());
+            served.extend(chunk);
+        }
+        writers.join().unwrap();
+        assert_eq!(served, [[1u8; 100], [2u8; 100]].concat());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wiped_file_not_served_again() {
+        let mut contents = os_fill_rand_octets(300_000).unwrap();
+        contents[0] |= 1;
+        let path = std::env::temp_dir().join(format!("trng-file-wipe-{}", std::process::id()));
+        std::fs::write(&path, &contents).unwrap();
+        let cfg = FileConfig { wipe: Some(FileWipe::Zero), ..file_config(&path, None) };
+        let source = FileSource::new(cfg.clone()).await.unwrap();
+        assert_eq!(source.read_bytes(100_000, 1000).await.unwrap(), contents[..100_000]);
+        drop(source);
+        // A later run only gets what was never read, less what the first one
+        // had pulled into its buffer
+        let source = FileSource::new(cfg).await.unwrap();
+        let rest = drain(&source, 190_000).await;
+        assert!(rest.len() > 190_000 && contents[100_000..].ends_with(&rest));
+        assert!(std::fs::read(&path).unwrap().iter().all(|&b| b == 0));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_lrng_overrun_kept() {
+        let cfg: LrngConfig = toml::from_str("id = \"test\"\nbuffer_mebibytes = 1").unwrap();
+        let source = LrngSource::new(cfg);
+        // Far more than the buffer holds and than getrandom delivers in 1 ms
+        let served = source.read_bytes(16 * 1024 * 1024, 1).await.unwrap();
+        assert!(served.len() < 16 * 1024 * 1024);
+        for _ in 0..100 {
+            if source.core.lane.stats().returned > 0 {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("overrun output was not kept");
+    }
+
+    #[tokio::test]
+    async fn test_lrng_refills_between_watermarks() {
+        let toml = "id = \"test\"\nbuffer_mebibytes = 1\nreplenish_interval_ms = 60000\nlow_watermark_percent = 50\nhigh_watermark_percent = 90";
+        let source = LrngSource::new(toml::from_str(toml).unwrap());
+        let settled = |target: usize| {
+            let buffer = source.core.buffer.clone();
+            async move {
+                for _ in 0..200 {
+                    if buffer_fill(&buffer, "test").await.0 == target {
+                        return true;
+                    }
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                false
+            }
+        };
+        // The first tick fills up to the first 64 KiB step past 90%
+        assert!(settled(15 * 64 * 1024).await);
+        // Above the low watermark nothing is read, below it requests wake the
+        // replenisher long before its next tick
+        source.read_bytes(300 * 1024, 0).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(settled(15 * 64 * 1024 - 300 * 1024).await);
+        source.read_bytes(200 * 1024, 0).await.unwrap();
+        assert!(settled(15 * 64 * 1024 - 500 * 1024 + 8 * 64 * 1024).await);
+    }
+
+    #[tokio::test]
+    async fn test_fill_reports_extents() {
+        let path = std::env::temp_dir().join(format!("trng-file-extents-{}", std::process::id()));
+        std::fs::write(&path, [1u8, 2, 3, 4, 5, 6]).unwrap();
+        let mut cursor = FileCursor::open(path.to_str().unwrap(), true, false, None, None).await.unwrap();
+        let (mut buf, mut extents) = ([0u8; 10], Vec::new());
+        assert_eq!(cursor.fill(&mut buf, &mut extents).await.unwrap(), 10);
+        assert_eq!(extents, [(0, 6), (0, 4)]);
+        assert_eq!(cursor.position(), 4);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_locked_buffer_respects_deadline() {
+        let cfg: LrngConfig = toml::from_str("id = \"test\"\nbuffer_mebibytes = 1").unwrap();
+        let source = LrngSource::new(cfg);
+        let held = source.core.buffer.lock().await;
+        let started = Instant::now();
+        assert!(source.read_bytes(64, 50).await.unwrap().is_empty());
+        assert!(started.elapsed() < Duration::from_secs(1));
+        drop(held);
+        assert_eq!(source.read_bytes(64, 1000).await.unwrap().len(), 64);
+    }
+
+    /// A pseudo-terminal standing in for a serial device: the master end to
+    /// write "device output" to, and the path of the other end.
+    fn pty() -> (std::fs::File, String) {
+        use std::os::fd::FromRawFd;
+        unsafe {
+            let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            assert!(fd >= 0);
+            assert_eq!((libc::grantpt(fd), libc::unlockpt(fd)), (0, 0));
+            let mut name = [0 as libc::c_char; 64];
+            assert_eq!(libc::ptsname_r(fd, name.as_mut_ptr(), name.len()), 0);
+            let path = std::ffi::CStr::from_ptr(name.as_ptr()).to_string_lossy().into_owned();
+            (std::fs::File::from_raw_fd(fd), path)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serial_device_read_and_leftovers() {
+        use std::io::Write;
+        let (mut device, path) = pty();
+        let cfg: SerialConfig = toml::from_str(&format!("id = \"test\"\npath = \"{}\"\nparity = \"even\"", path)).unwrap();
+        let source = SerialSource::new(cfg).await.unwrap();
+        // A quiet line is not an error
+        assert!(source.read_bytes(4, 150).await.unwrap().is_empty());
+        device.write_all(&[1, 2, 3, 4, 5, 6]).unwrap();
+        let (first, mut spans) = source.read_traced(4, 1000).await.unwrap();
+        assert_eq!(first, [1, 2, 3, 4]);
+        source.return_leftover(first[2..].to_vec(), spans.split_off(2)).await;
+        let (second, spans) = source.read_traced(4, 1000).await.unwrap();
+        assert_eq!((second, spans), (vec![3, 4, 5, 6], Spans::from(Span { start: 2, len: 4 })));
+        // Unplugged
+        drop(device);
+        assert!(source.read_bytes(4, 1000).await.is_err());
+        assert!(!source.is_available());
+    }
+
+    #[tokio::test]
+    async fn test_tcp_source_reconnects() {
+        use tokio::io::AsyncWriteExt;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            // Each connection streams 3 bytes and closes
+            for round in 0u8.. {
+                let (mut conn, _) = listener.accept().await.unwrap();
+                conn.write_all(&[round; 3]).await.unwrap();
+            }
+        });
+        let cfg: TcpConfig = toml::from_str(&format!(
+            "id = \"lan\"\nhost = \"127.0.0.1\"\nport = {}\nretry = {{ initial_backoff_ms = 10 }}",
+            port
+        ))
+        .unwrap();
+        let source = TcpSource::new(cfg).unwrap();
+        assert_eq!(source.read_bytes(6, 5000).await.unwrap(), [0, 0, 0, 1, 1, 1]);
+        assert!(source.metrics().retries >= 1);
+        assert_eq!(source.info()["server"], format!("tcp://127.0.0.1:{}", port));
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_http_source_polls_into_buffer() {
+        use tokio::io::AsyncWriteExt;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // Answers "GET /random?n=<count>" with bytes 0, 1, 2, ...
+        let server = tokio::spawn(async move {
+            loop {
+                let (mut conn, _) = listener.accept().await.unwrap();
+                let mut request = vec![0u8; 1024];
+                let n = conn.read(&mut request).await.unwrap();
+                let line = String::from_utf8_lossy(&request[..n]).lines().next().unwrap_or_default().to_string();
+                let count: usize = line.split("n=").nth(1).and_then(|s| s.split(' ').next()?.parse().ok()).unwrap();
+                let body: Vec<u8> = (0..count as u8).collect();
+                conn.write_all(format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len()).as_bytes()).await.unwrap();
+                conn.write_all(&body).await.unwrap();
+            }
+        });
+        let cfg: HttpConfig = toml::from_str(&format!(
+            "id = \"web\"\nurl = \"http://127.0.0.1:{}/random?n={{bytes}}\"\npoll_interval_ms = 20\nrequest_bytes = 8",
+            port
+        ))
+        .unwrap();
+        let source = HttpSource::new(cfg).unwrap();
+        // Waits for the poller until it has enough
+        let (bytes, spans) = source.read_traced(16, 2000).await.unwrap();
+        assert_eq!(bytes, [0, 1, 2, 3, 4, 5, 6, 7, 0, 1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(spans, Spans::from(Span { start: 0, len: 16 }));
+        server.abort();
+        let _ = server.await;
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while source.is_available() && Instant::now() < deadline {
+            sleep(Duration::from_millis(10)).await;
+        }
+        assert!(!source.is_available());
+    }
+
+    #[tokio::test]
+    async fn test_jitter_source_waits_for_collector() {
+        let cfg: JitterConfig = toml::from_str("id = \"test\"\nbuffer_mebibytes = 1").unwrap();
+        let source = JitterSource::new(cfg);
+        let (bytes, spans) = source.read_traced(5000, 5000).await.unwrap();
+        assert_eq!(bytes.len(), 5000);
+        assert_eq!(spans, Spans::from(Span { start: 0, len: 5000 }));
+        assert_ne!(bytes, source.read_bytes(5000, 5000).await.unwrap());
+        assert!(source.is_available());
+    }
+
+    #[tokio::test]
+    async fn test_mqtt_source_checks_payloads() {
+        use tokio::io::AsyncWriteExt;
+        fn publish(topic: &str, payload: &[u8], retain: bool) -> Vec<u8> {
+            let mut packet = vec![0x30 | retain as u8, (2 + topic.len() + payload.len()) as u8, 0, topic.len() as u8];
+            packet.extend_from_slice(topic.as_bytes());
+            packet.extend_from_slice(payload);
+            packet
+        }
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut conn, _) = listener.accept().await.unwrap();
+            // CONNECT, then SUBSCRIBE with packet id 1
+            for reply in [&[0x20, 2, 0, 0][..], &[0x90, 3, 0, 1, 0]] {
+                let mut header = [0u8; 2];
+                conn.read_exact(&mut header).await.unwrap();
+                conn.read_exact(&mut vec![0; header[1] as usize]).await.unwrap();
+                conn.write_all(reply).await.unwrap();
+            }
+            conn.write_all(&publish("beacons/old/entropy", &(100..116).collect::<Vec<u8>>(), true)).await.unwrap();
+            conn.write_all(&publish("beacons/a/entropy", &[1; 16], false)).await.unwrap();
+            conn.write_all(&publish("beacons/a/entropy", &[1, 2, 3], false)).await.unwrap();
+            for _ in 0..2 {
+                conn.write_all(&publish("beacons/a/entropy", &(16..32).collect::<Vec<u8>>(), false)).await.unwrap();
+            }
+            conn.write_all(&publish("beacons/b/entropy", &(32..48).collect::<Vec<u8>>(), false)).await.unwrap();
+            std::future::pending::<()>().await;
+        });
+        let cfg: MqttConfig = toml::from_str(&format!(
+            "id = \"beacons\"\nbroker = \"mqtt://127.0.0.1:{}\"\ntopic = \"beacons/+/entropy\"\nqos = 0\nkeep_alive_seconds = 0",
+            port
+        ))
+        .unwrap();
+        let source = MqttSource::new(cfg).unwrap();
+        // Only the first payload of a and the one of b pass
+        assert_eq!(source.read_bytes(32, 5000).await.unwrap(), (16..48).collect::<Vec<u8>>());
+        let publishers = source.metrics().publishers;
+        assert_eq!(publishers["old"], PublisherStats { retained: 1, ..PublisherStats::default() });
+        assert_eq!(
+            publishers["a"],
+            PublisherStats { messages: 1, bytes: 16, constant: 1, wrong_size: 1, repeated: 1, ..PublisherStats::default() }
+        );
+        assert_eq!(publishers["b"].bytes, 16);
+        assert_eq!(source.info()["topic"], "beacons/+/entropy");
+        server.abort();
+    }
+
+    #[cfg(feature = "nats")]
+    #[tokio::test]
+    async fn test_nats_source_uses_confirmed_blocks() {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (conn, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = conn.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            writer.write_all(b"INFO {\"max_payload\":1048576,\"headers\":true}\r\n").await.unwrap();
+            let mut acks = 0;
+            while let Some(line) = lines.next_line().await.unwrap() {
+                let words: Vec<&str> = line.split(' ').collect();
+                let reply = match words[0] {
+                    // Sent after CONNECT and PING
+                    "SUB" => "PONG\r\n".to_string(),
+                    "PUB" if words[1] == "$JS.API.CONSUMER.INFO.ENTROPY.trng-dbus" => {
+                        lines.next_line().await.unwrap();
+                        let body = r#"{"error":{"code":404,"err_code":10014,"description":"consumer not found"}}"#;
+                        format!("MSG {} 1 {}\r\n{}\r\n", words[2], body.len(), body)
+                    }
+                    "PUB" if words[1] == "$JS.API.CONSUMER.DURABLE.CREATE.ENTROPY.trng-dbus" => {
+                        lines.next_line().await.unwrap();
+                        let body = r#"{"config":{"ack_policy":"explicit","ack_wait":30000000000}}"#;
+                        format!("MSG {} 1 {}\r\n{}\r\n", words[2], body.len(), body)
+                    }
+                    "PUB" if words[1].starts_with("$JS.API.CONSUMER.MSG.NEXT.") => {
+                        lines.next_line().await.unwrap();
+                        let block = |seq: u8, byte: u8| format!("MSG blocks 1 $JS.ACK.ENTROPY.trng-dbus.1.{}.{}.0.0 16\r\n{}\r\n", seq, seq, String::from(byte as char).repeat(16));
+                        block(1, b'a') + &block(2, b'b')
+                    }
+                    // Only the first ack is confirmed; the second block must not be used
+                    "PUB" if words[1].starts_with("$JS.ACK.") => {
+                        lines.next_line().await.unwrap();
+                        acks += 1;
+                        match acks {
+                            1 => format!("MSG {} 1 0\r\n\r\n", words[2]),
+                            _ => continue,
+                        }
+                    }
+                    _ => continue,
+                };
+                writer.write_all(reply.as_bytes()).await.unwrap();
+            }
+        });
+        let cfg: NatsConfig = toml::from_str(&format!("id = \"fleet\"\nserver = \"nats://127.0.0.1:{}\"\nstream = \"ENTROPY\"", port)).unwrap();
+        let source = NatsSource::new(cfg).unwrap();
+        assert_eq!(source.read_bytes(32, 1500).await.unwrap(), b"a".repeat(16));
+        assert_eq!(source.info()["consumer"], "trng-dbus");
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_exec_source_restarts_command() {
+        let cfg: ExecConfig = toml::from_str("id = \"test\"\ncommand = [\"printf\", \"abc\"]\nretry = { initial_backoff_ms = 10 }").unwrap();
+        let source = ExecSource::new(cfg).unwrap();
+        // Each run prints 3 bytes and exits
+        assert_eq!(source.read_bytes(6, 5000).await.unwrap(), b"abcabc");
+        assert!(source.metrics().retries >= 1);
+        let cfg: ExecConfig = toml::from_str("id = \"test\"\ncommand = []").unwrap();
+        assert!(ExecSource::new(cfg).is_err());
+    }
+
+    #[cfg(feature = "grpc")]
+    #[tokio::test]
+    async fn test_grpc_source_passes_deadline() {
+        use crate::hpack;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let frame = |kind: u8, flags: u8, payload: &[u8]| {
+            let mut frame = (payload.len() as u32).to_be_bytes()[1..].to_vec();
+            frame.extend_from_slice(&[kind, flags, 0, 0, 0, 1]);
+            frame.extend_from_slice(payload);
+            frame
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (deadlines, mut deadline) = tokio::sync::mpsc::unbounded_channel();
+        let server = tokio::spawn(async move {
+            let (mut conn, _) = listener.accept().await.unwrap();
+            let mut preface = [0u8; 24];
+            conn.read_exact(&mut preface).await.unwrap();
+            let mut decoder = hpack::Decoder::new();
+            let mut calls = 0;
+            loop {
+                let mut header = [0u8; 9];
+                conn.read_exact(&mut header).await.unwrap();
+                let mut payload = vec![0u8; u32::from_be_bytes([0, header[0], header[1], header[2]]) as usize];
+                conn.read_exact(&mut payload).await.unwrap();
+                if header[3] == 0x1 {
+                    let headers = decoder.decode(&payload).unwrap();
+                    let _ = deadlines.send(headers.into_iter().find(|(name, _)| name == "grpc-timeout").map(|(_, value)| value));
+                }
+                // Answered once the request message is complete
+                if header[3] != 0x0 || header[4] & 0x1 == 0 {
+                    continue;
+                }
+                calls += 1;
+                let mut reply = Vec::new();
+                if calls == 1 {
+                    reply.extend(frame(0x1, 0x4, &hpack::encode(&[(":status", "200"), ("content-type", "application/grpc")])));
+                    let mut message = vec![0, 0, 0, 0, 18, 0x0a, 16];
+                    message.extend_from_slice(&[b'a'; 16]);
+                    reply.extend(frame(0x0, 0, &message));
+                    reply.extend(frame(0x1, 0x5, &hpack::encode(&[("grpc-status", "0")])));
+                } else {
+                    // Later calls fail, their status only in the headers
+                    let status = [(":status", "200"), ("grpc-status", "14"), ("grpc-message", "warming%20up")];
+                    reply.extend(frame(0x1, 0x5, &hpack::encode(&status)));
+                }
+                conn.write_all(&reply).await.unwrap();
+            }
+        });
+        let cfg: GrpcConfig = toml::from_str(&format!("id = \"qrng\"\nhost = \"127.0.0.1\"\nport = {}\ntimeout_ms = 1500", port)).unwrap();
+        let source = GrpcSource::new(cfg).unwrap();
+        assert_eq!(source.read_bytes(32, 1500).await.unwrap(), b"a".repeat(16));
+        assert_eq!(deadline.recv().await.unwrap().as_deref(), Some("1500m"));
+        assert!(source.info()["server"].ends_with("/trng.v1.Entropy/GetRandom"));
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_leftovers_served_before_file() {
+        let path = std::env::temp_dir().join(format!("trng-file-leftover-{}", std::process::id()));
+        std::fs::write(&path, [1u8, 2, 3, 4, 5, 6]).unwrap();
+        let source = FileSource::new(file_config(&path, None)).await.unwrap();
+        let (first, mut spans) = source.read_traced(4, 100).await.unwrap();
+        source.return_leftover(first[2..].to_vec(), spans.split_off(2)).await;
+        // Returned bytes keep their place in the read stream
+        let (second, spans) = source.read_traced(4, 100).await.unwrap();
+        assert_eq!((second, spans), (vec![3, 4, 5, 6], Spans::from(Span { start: 2, len: 4 })));
+        assert!(source.read_bytes(4, 100).await.unwrap().is_empty());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
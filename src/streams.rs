@@ -0,0 +1,233 @@
+use crate::aggregator::Aggregator;
+use crate::config::ClientStreamsConfig;
+use crate::drbg::HmacDrbg;
+use crate::error::Error;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use crate::clock::BootInstant;
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+/// Longest session label a client may pass.
+pub const MAX_LABEL: usize = 256;
+
+const PERSONALIZATION_LABEL: &[u8] = b"trng-dbus client stream";
+
+/// A stream's DRBG and how much it has served since its last seed.
+struct State {
+    drbg: HmacDrbg,
+    /// On the boot clock, so a stream is due after a suspend.
+    seeded_at: BootInstant,
+    since_reseed: u64,
+}
+
+impl State {
+    fn due(&self, reseed_after: Duration, reseed_bytes: u64) -> bool {
+        self.seeded_at.elapsed() >= reseed_after || self.since_reseed >= reseed_bytes
+    }
+
+    fn reseed(&mut self, seed: &[u8]) {
+        self.drbg.reseed(seed);
+        self.seeded_at = BootInstant::now();
+        self.since_reseed = 0;
+    }
+}
+
+/// One client's DRBG, drawn on by nobody else.
+struct Stream {
+    id: u64,
+    owner: String,
+    /// `None` for a client's own stream, which `ReadBytes` draws from.
+    label: Option<String>,
+    /// Seeded on first use.
+    state: tokio::sync::Mutex<Option<State>>,
+}
+
+impl Stream {
+    /// Instantiates the DRBG from `seed` with the stream's id and label as
+    /// personalization, so equal seeds still give unrelated streams.
+    fn instantiate(&self, seed: &[u8]) -> State {
+        let mut material = Zeroizing::new(seed.to_vec());
+        material.extend_from_slice(PERSONALIZATION_LABEL);
+        material.extend_from_slice(&self.id.to_be_bytes());
+        material.extend_from_slice(self.label.as_deref().unwrap_or_default().as_bytes());
+        State { drbg: HmacDrbg::new(&material), seeded_at: BootInstant::now(), since_reseed: 0 }
+    }
+}
+
+/// Per-client DRBG streams (`[client_streams]`): each client, and each
+/// session it opens, is served from its own DRBG seeded from combined
+/// source output, so no client's reads shift what another one receives.
+pub struct ClientStreams {
+    reseed_after: Duration,
+    reseed_bytes: u64,
+    max_sessions_per_client: usize,
+    max_total: usize,
+    next_id: AtomicU64,
+    streams: Mutex<HashMap<u64, Arc<Stream>>>,
+}
+
+impl ClientStreams {
+    pub fn new(cfg: &ClientStreamsConfig) -> Self {
+        Self {
+            reseed_after: Duration::from_secs(cfg.reseed_seconds.unwrap_or(60)),
+            reseed_bytes: cfg.reseed_bytes.unwrap_or(1024 * 1024).max(1),
+            max_sessions_per_client: cfg.max_sessions_per_client.unwrap_or(16),
+            max_total: cfg.max_total.unwrap_or(1024),
+            next_id: AtomicU64::new(1),
+            streams: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Opens a named session for `owner` and returns its id.
+    pub fn open(&self, owner: &str, label: &str) -> Result<u64, Error> {
+        if label.len() > MAX_LABEL {
+            return Err(Error::InvalidOption("label".to_string()));
+        }
+        let mut streams = self.lock();
+        let sessions = streams.values().filter(|s| s.owner == owner && s.label.is_some()).count();
+        if sessions >= self.max_sessions_per_client {
+            return Err(Error::QuotaExceeded);
+        }
+        let id = self.insert(&mut streams, owner, Some(label.to_string()))?;
+        log::info!("Session {} ({}) opened for {}", id, label, owner);
+        Ok(id)
+    }
+
+    /// Closes one of `owner`'s sessions, zeroizing its DRBG.
+    pub fn close(&self, id: u64, owner: &str) -> Result<(), Error> {
+        let mut streams = self.lock();
+        match streams.get(&id) {
+            Some(s) if s.owner == owner && s.label.is_some() => {
+                streams.remove(&id);
+                log::info!("Session {} closed by {}", id, owner);
+                Ok(())
+            }
+            _ => Err(Error::InvalidOption("session_id".to_string())),
+        }
+    }
+
+    /// Drops the streams of a client that left the bus.
+    pub fn remove_owner(&self, owner: &str) {
+        self.lock().retain(|_, s| s.owner != owner);
+    }
+
+    /// Drops every stream; used at shutdown.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Serves `owner` from its own stream, created on first use.
+    pub async fn read_own(&self, aggregator: &Aggregator, owner: &str, num_bytes: usize, timeout_ms: u64) -> Result<Vec<u8>, Error> {
+        let stream = {
+            let mut streams = self.lock();
+            match streams.values().find(|s| s.owner == owner && s.label.is_none()) {
+                Some(stream) => stream.clone(),
+                None => {
+                    let id = self.insert(&mut streams, owner, None)?;
+                    streams[&id].clone()
+                }
+            }
+        };
+        self.generate(aggregator, &stream, num_bytes, timeout_ms).await
+    }
+
+    /// Serves one of `owner`'s sessions.
+    pub async fn read_session(
+        &self,
+        aggregator: &Aggregator,
+        id: u64,
+        owner: &str,
+        num_bytes: usize,
+        timeout_ms: u64,
+    ) -> Result<Vec<u8>, Error> {
+        let stream = self.lock().get(&id).filter(|s| s.owner == owner && s.label.is_some()).cloned();
+        let stream = stream.ok_or_else(|| Error::InvalidOption("session_id".to_string()))?;
+        self.generate(aggregator, &stream, num_bytes, timeout_ms).await
+    }
+
+    fn insert(&self, streams: &mut HashMap<u64, Arc<Stream>>, owner: &str, label: Option<String>) -> Result<u64, Error> {
+        if streams.len() >= self.max_total {
+            log::warn!("Refused a DRBG stream for {}: {} streams open", owner, streams.len());
+            return Err(Error::QuotaExceeded);
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let state = tokio::sync::Mutex::new(None);
+        streams.insert(id, Arc::new(Stream { id, owner: owner.to_string(), label, state }));
+        Ok(id)
+    }
+
+    /// Generates from `stream`, (re)seeding it first whenever its schedule
+    /// says so, so no more than `reseed_bytes` come from one seed. Returns
+    /// the bytes generated so far if a reseed cannot get entropy in time.
+    async fn generate(&self, aggregator: &Aggregator, stream: &Stream, num_bytes: usize, timeout_ms: u64) -> Result<Vec<u8>, Error> {
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        // The answer, its copy in the reply and one chunk of DRBG output
+        let chunk = num_bytes.min(self.reseed_bytes as usize);
+        let _memory = aggregator.reserve(num_bytes.saturating_mul(2).saturating_add(chunk), "a stream read")?;
+        let mut state = stream.state.lock().await;
+        let mut out = Vec::with_capacity(num_bytes);
+        while out.len() < num_bytes {
+            if state.as_ref().is_none_or(|s| s.due(self.reseed_after, self.reseed_bytes)) {
+                let left_ms = deadline.saturating_duration_since(Instant::now()).as_millis() as u64;
+                let seed = match aggregator.stream_seed(left_ms).await {
+                    Ok(seed) => seed,
+                    Err(e) if out.is_empty() => return Err(e),
+                    Err(e) => {
+                        log::warn!("Stream {} cut short at {} bytes, reseed failed: {}", stream.id, out.len(), e);
+                        break;
+                    }
+                };
+                match state.as_mut() {
+                    Some(s) => s.reseed(&seed),
+                    None => *state = Some(stream.instantiate(&seed)),
+                }
+                log::debug!("Reseeded DRBG stream {}", stream.id);
+            }
+            let s = state.as_mut().ok_or(Error::Unexpected)?;
+            let take = (num_bytes - out.len()).min((self.reseed_bytes - s.since_reseed) as usize);
+            out.extend_from_slice(&Zeroizing::new(s.drbg.generate(take, &[])));
+            s.since_reseed += take as u64;
+        }
+        aggregator.count_served(out.len());
+        Ok(out)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, Arc<Stream>>> {
+        self.streams.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drbg::SEED_LEN;
+
+    fn stream(id: u64, label: Option<&str>) -> Stream {
+        Stream { id, owner: ":1.1".to_string(), label: label.map(str::to_string), state: tokio::sync::Mutex::new(None) }
+    }
+
+    #[test]
+    fn test_streams_are_independent() {
+        let seed = [5u8; SEED_LEN];
+        let mut own = stream(1, None).instantiate(&seed);
+        let mut session = stream(2, Some("")).instantiate(&seed);
+        let mut other = stream(3, Some("jobs")).instantiate(&seed);
+        let first = own.drbg.generate(32, &[]);
+        assert_ne!(first, session.drbg.generate(32, &[]));
+        assert_ne!(first, other.drbg.generate(32, &[]));
+        assert_eq!(first, stream(1, None).instantiate(&seed).drbg.generate(32, &[]));
+    }
+
+    #[test]
+    fn test_reseed_schedule() {
+        let mut state = stream(1, None).instantiate(&[5u8; SEED_LEN]);
+        assert!(!state.due(Duration::from_secs(60), 100));
+        state.since_reseed = 100;
+        assert!(state.due(Duration::from_secs(60), 100));
+        state.reseed(&[6u8; SEED_LEN]);
+        assert!(!state.due(Duration::from_secs(60), 100));
+        assert!(state.due(Duration::ZERO, 100));
+    }
+}
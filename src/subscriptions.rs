@@ -0,0 +1,318 @@
+use crate::aggregator::Aggregator;
+use crate::config::SubscriptionConfig;
+use crate::error::Error;
+use crate::scheduler::{self, Requester};
+use crate::shaping::Shaper;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::AsyncWriteExt;
+use tokio::net::UnixStream;
+use tokio::task::AbortHandle;
+use tokio::time::{interval, Duration, Instant, MissedTickBehavior};
+use zbus::names::InterfaceName;
+use zbus::object_server::SignalEmitter;
+use zeroize::Zeroizing;
+
+/// Declared on `lv.lumii.trng.Rng` in `main.rs` and on every group interface.
+const ENTROPY_SIGNAL: &str = "Entropy";
+
+/// Shortest delivery interval a client may ask for.
+const MIN_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How often an `OpenStream` stream is topped up.
+const STREAM_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Where a subscription's chunks go.
+pub enum Sink {
+    /// Unicast `Entropy(subscription_id, bytes)` signals to the subscriber,
+    /// on the interface it subscribed through.
+    Signal(SignalEmitter<'static>, InterfaceName<'static>),
+    /// Raw bytes written to the service end of a socket pair.
+    Pipe(UnixStream),
+}
+
+/// Delivery accounting for one subscription.
+#[derive(Default)]
+struct Stats {
+    delivered: AtomicU64,
+    chunks: AtomicU64,
+    short_chunks: AtomicU64,
+}
+
+struct Entry {
+    owner: String,
+    stats: Arc<Stats>,
+    task: AbortHandle,
+}
+
+/// Active push subscriptions, each served by its own delivery task.
+pub struct Subscriptions {
+    max_bytes_per_second: u64,
+    max_per_client: usize,
+    max_total: usize,
+    next_id: AtomicU64,
+    active: Mutex<HashMap<u64, Entry>>,
+}
+
+impl Subscriptions {
+    pub fn new(cfg: Option<&SubscriptionConfig>) -> Self {
+        let cfg = cfg.cloned().unwrap_or_default();
+        Self {
+            max_bytes_per_second: cfg.max_bytes_per_second.unwrap_or(1024 * 1024),
+            max_per_client: cfg.max_per_client.unwrap_or(8),
+            max_total: cfg.max_total.unwrap_or(256),
+            next_id: AtomicU64::new(1),
+            active: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Starts pushing `bytes_per_interval` bytes every `interval` to `sink`,
+    /// each chunk first admitted by `shaper` if there is one, and returns the
+    /// subscription id.
+    pub fn start(
+        self: &Arc<Self>,
+        aggregator: Arc<Aggregator>,
+        owner: &str,
+        bytes_per_interval: usize,
+        period: Duration,
+        sink: Sink,
+        shaper: Option<Arc<Shaper>>,
+    ) -> Result<u64, Error> {
+        if bytes_per_interval == 0 {
+            return Err(Error::InvalidOption("bytes_per_interval".to_string()));
+        }
+        if period < MIN_INTERVAL {
+            return Err(Error::InvalidOption("interval_ms".to_string()));
+        }
+        let rate = bytes_per_interval as f64 / period.as_secs_f64();
+        if rate > self.max_bytes_per_second as f64 {
+            log::warn!("Subscription from {} asked for {:.0} B/s, limit is {}", owner, rate, self.max_bytes_per_second);
+            return Err(Error::QuotaExceeded);
+        }
+
+        let mut active = self.lock();
+        let owned = active.values().filter(|e| e.owner == owner).count();
+        if active.len() >= self.max_total || owned >= self.max_per_client {
+            return Err(Error::QuotaExceeded);
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let stats = Arc::new(Stats::default());
+        // Chunks are delivered outside any call, so they get no request id
+        let requester = Requester { client: owner.to_string(), priority: 0, request_id: 0 };
+        let delivery = deliver(self.clone(), aggregator, id, bytes_per_interval, period, sink, shaper, stats.clone());
+        let task = tokio::spawn(scheduler::on_behalf_of(requester, delivery));
+        active.insert(id, Entry { owner: owner.to_string(), stats, task: task.abort_handle() });
+        log::info!("Subscription {} for {}: {} bytes every {:?}", id, owner, bytes_per_interval, period);
+        Ok(id)
+    }
+
+    /// Starts a stream writing up to `rate_limit` bytes a second to `sink`,
+    /// or as much as a subscription may carry for 0, and returns its id.
+    /// Writes wait while the reader is behind, so a slow reader just gets
+    /// less; the stream is otherwise a subscription like any other.
+    pub fn open_stream(
+        self: &Arc<Self>,
+        aggregator: Arc<Aggregator>,
+        owner: &str,
+        rate_limit: u64,
+        sink: Sink,
+        shaper: Option<Arc<Shaper>>,
+    ) -> Result<u64, Error> {
+        let rate = if rate_limit == 0 { self.max_bytes_per_second } else { rate_limit };
+        let per_interval = rate * STREAM_INTERVAL.as_millis() as u64 / 1000;
+        let (bytes_per_interval, period) = match per_interval {
+            // Below 10 B/s, single bytes at longer intervals
+            0 => (1, Duration::from_secs_f64(1.0 / rate as f64)),
+            n => (n as usize, STREAM_INTERVAL),
+        };
+        self.start(aggregator, owner, bytes_per_interval, period, sink, shaper)
+    }
+
+    /// Ends a subscription on behalf of its owner; returns the bytes delivered.
+    pub fn stop(&self, id: u64, owner: &str) -> Result<u64, Error> {
+        let mut active = self.lock();
+        if active.get(&id).map(|e| e.owner.as_str()) != Some(owner) {
+            return Err(Error::InvalidOption("subscription_id".to_string()));
+        }
+        let entry = active.remove(&id).ok_or(Error::Unexpected)?;
+        entry.task.abort();
+        Ok(Self::finished(id, &entry))
+    }
+
+    /// Ends every subscription of a client that left the bus.
+    pub fn remove_owner(&self, owner: &str) {
+        let mut active = self.lock();
+        let ids: Vec<u64> = active.iter().filter(|(_, e)| e.owner == owner).map(|(id, _)| *id).collect();
+        for id in ids {
+            if let Some(entry) = active.remove(&id) {
+                entry.task.abort();
+                Self::finished(id, &entry);
+            }
+        }
+    }
+
+    /// Ends every subscription; used at shutdown and when the bus goes away.
+    pub fn stop_all(&self) {
+        for (id, entry) in self.lock().drain() {
+            entry.task.abort();
+            Self::finished(id, &entry);
+        }
+    }
+
+    fn finished(id: u64, entry: &Entry) -> u64 {
+        let delivered = entry.stats.delivered.load(Ordering::Relaxed);
+        log::info!(
+            "Subscription {} for {} ended: {} bytes in {} chunks ({} short)",
+            id,
+            entry.owner,
+            delivered,
+            entry.stats.chunks.load(Ordering::Relaxed),
+            entry.stats.short_chunks.load(Ordering::Relaxed)
+        );
+        delivered
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, Entry>> {
+        self.active.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn deliver(
+    subscriptions: Arc<Subscriptions>,
+    aggregator: Arc<Aggregator>,
+    id: u64,
+    bytes_per_interval: usize,
+    period: Duration,
+    mut sink: Sink,
+    shaper: Option<Arc<Shaper>>,
+    stats: Arc<Stats>,
+) {
+    let mut ticker = interval(period);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let timeout_ms = period.as_millis() as u64;
+    loop {
+        ticker.tick().await;
+        // A chunk the group's shaping cannot fit into its interval is skipped
+        if let Some(shaper) = &shaper {
+            if shaper.admit(bytes_per_interval, Instant::now() + period).await.is_err() {
+                stats.short_chunks.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+        }
+        let res = aggregator.read_bytes(bytes_per_interval, timeout_ms).await;
+        if let Some(shaper) = &shaper {
+            shaper.refund(bytes_per_interval.saturating_sub(res.as_ref().map_or(0, Vec::len)));
+        }
+        let bytes = match res {
+            Ok(bytes) => Zeroizing::new(bytes),
+            Err(e) => {
+                log::warn!("Subscription {} read failed: {}", id, e);
+                continue;
+            }
+        };
+        if bytes.len() < bytes_per_interval {
+            stats.short_chunks.fetch_add(1, Ordering::Relaxed);
+        }
+        if bytes.is_empty() {
+            continue;
+        }
+        let res = match &mut sink {
+            Sink::Signal(emitter, interface) => emitter.emit(interface.as_ref(), ENTROPY_SIGNAL, &(id, &bytes[..])).await.map_err(|e| e.to_string()),
+            Sink::Pipe(stream) => stream.write_all(&bytes).await.map_err(|e| e.to_string()),
+        };
+        if let Err(e) = res {
+            log::info!("Subscription {} delivery stopped: {}", id, e);
+            break;
+        }
+        stats.delivered.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+        stats.chunks.fetch_add(1, Ordering::Relaxed);
+    }
+    if let Some(entry) = subscriptions.lock().remove(&id) {
+        Subscriptions::finished(id, &entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::load_config;
+    use tokio::io::AsyncReadExt;
+
+    /// An aggregator over the OS RNG, and subscriptions of at most 4 KiB/s,
+    /// two per client.
+    async fn setup() -> (Arc<Aggregator>, Arc<Subscriptions>) {
+        let path = std::env::temp_dir().join(format!("trng-subscriptions-{}-{:?}.toml", std::process::id(), std::thread::current().id()));
+        std::fs::write(&path, "[sources]\nbenchmark_ms = 0\n\n[[sources.lrng]]\nid = \"os\"\nenabled = true\n").unwrap();
+        let cfg = load_config(path.to_str().unwrap()).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let limits = toml::from_str("max_bytes_per_second = 4096\nmax_per_client = 2").unwrap();
+        (Arc::new(Aggregator::from_config(cfg).await.unwrap()), Arc::new(Subscriptions::new(Some(&limits))))
+    }
+
+    async fn read(stream: &mut UnixStream, len: usize) -> Vec<u8> {
+        let mut buf = vec![0; len];
+        tokio::time::timeout(Duration::from_secs(5), stream.read_exact(&mut buf)).await.unwrap().unwrap();
+        buf
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_and_unsubscribe() {
+        let (agg, subs) = setup().await;
+        let pipe = || UnixStream::pair().unwrap();
+        let start = |owner: &str, bytes, ms, sink| subs.start(agg.clone(), owner, bytes, Duration::from_millis(ms), sink, None);
+        assert_eq!(start(":1.1", 0, 100, Sink::Pipe(pipe().0)), Err(Error::InvalidOption("bytes_per_interval".to_string())));
+        assert_eq!(start(":1.1", 16, 5, Sink::Pipe(pipe().0)), Err(Error::InvalidOption("interval_ms".to_string())));
+        assert_eq!(start(":1.1", 1024, 100, Sink::Pipe(pipe().0)), Err(Error::QuotaExceeded));
+
+        let (ours, mut theirs) = pipe();
+        let id = start(":1.1", 16, 10, Sink::Pipe(ours)).unwrap();
+        assert_eq!(read(&mut theirs, 32).await.len(), 32);
+
+        // Two per client; only the owner may unsubscribe
+        let other = start(":1.1", 16, 100, Sink::Pipe(pipe().0)).unwrap();
+        assert_eq!(start(":1.1", 16, 100, Sink::Pipe(pipe().0)), Err(Error::QuotaExceeded));
+        assert!(start(":1.2", 16, 100, Sink::Pipe(pipe().0)).is_ok());
+        assert_eq!(subs.stop(id, ":1.2"), Err(Error::InvalidOption("subscription_id".to_string())));
+        assert!(subs.stop(id, ":1.1").unwrap() >= 32);
+        assert_eq!(subs.stop(id, ":1.1"), Err(Error::InvalidOption("subscription_id".to_string())));
+
+        // Leaving the bus ends the rest, and a closed pipe its subscription
+        subs.remove_owner(":1.1");
+        assert_eq!(subs.stop(other, ":1.1"), Err(Error::InvalidOption("subscription_id".to_string())));
+        let (ours, theirs) = pipe();
+        let id = start(":1.1", 16, 10, Sink::Pipe(ours)).unwrap();
+        drop(theirs);
+        for _ in 0..100 {
+            if !subs.lock().contains_key(&id) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(!subs.lock().contains_key(&id));
+        subs.stop_all();
+    }
+
+    #[tokio::test]
+    async fn test_open_stream_paces_its_writes() {
+        let (agg, subs) = setup().await;
+        assert_eq!(subs.open_stream(agg.clone(), ":1.1", 8192, Sink::Pipe(UnixStream::pair().unwrap().0), None), Err(Error::QuotaExceeded));
+
+        // 1000 B/s go out as 100 bytes every 100 ms
+        let (ours, mut theirs) = UnixStream::pair().unwrap();
+        let started = Instant::now();
+        let id = subs.open_stream(agg.clone(), ":1.1", 1000, Sink::Pipe(ours), None).unwrap();
+        assert_eq!(read(&mut theirs, 300).await.len(), 300);
+        assert!(started.elapsed() >= Duration::from_millis(200));
+
+        // 0 takes the subscription limit: 409 bytes every 100 ms
+        let (ours, mut theirs) = UnixStream::pair().unwrap();
+        let started = Instant::now();
+        subs.open_stream(agg.clone(), ":1.1", 0, Sink::Pipe(ours), None).unwrap();
+        assert_eq!(read(&mut theirs, 818).await.len(), 818);
+        assert!(started.elapsed() >= Duration::from_millis(100));
+        assert!(subs.stop(id, ":1.1").unwrap() >= 300);
+        subs.stop_all();
+    }
+}
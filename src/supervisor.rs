@@ -0,0 +1,184 @@
+use crate::retry::RetryPolicy;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::task::{AbortHandle, JoinHandle};
+use tokio::time::{interval, sleep, Duration};
+
+/// Default time a supervised loop may go without a heartbeat.
+pub const STALL_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// A task that ran at least this long is considered healthy again and its
+/// restart backoff starts over.
+const HEALTHY_RUNTIME: Duration = Duration::from_secs(60);
+
+static INCIDENTS: AtomicU64 = AtomicU64::new(0);
+static STOPPING: AtomicBool = AtomicBool::new(false);
+static RUNNING: Mutex<Vec<AbortHandle>> = Mutex::new(Vec::new());
+
+/// Number of panics and stalls detected since startup.
+pub fn incident_count() -> u64 {
+    INCIDENTS.load(Ordering::Relaxed)
+}
+
+/// Aborts every supervised task and prevents further restarts. Used by
+/// shutdown before buffers are zeroized.
+pub fn stop_all() {
+    STOPPING.store(true, Ordering::SeqCst);
+    let running = std::mem::take(&mut *RUNNING.lock().unwrap_or_else(|e| e.into_inner()));
+    log::debug!("Stopping {} supervised tasks", running.len());
+    for task in running {
+        task.abort();
+    }
+}
+
+fn track(task: AbortHandle) {
+    let mut running = RUNNING.lock().unwrap_or_else(|e| e.into_inner());
+    if STOPPING.load(Ordering::SeqCst) {
+        task.abort();
+        return;
+    }
+    running.retain(|t| !t.is_finished());
+    running.push(task);
+}
+
+/// Progress marker a supervised task updates from its main loop.
+#[derive(Clone)]
+pub struct Heartbeat {
+    epoch: Instant,
+    last_ms: Arc<AtomicU64>,
+}
+
+impl Heartbeat {
+    fn new() -> Self {
+        Self { epoch: Instant::now(), last_ms: Arc::new(AtomicU64::new(0)) }
+    }
+
+    pub fn beat(&self) {
+        self.last_ms.store(self.epoch.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+
+    fn age(&self) -> Duration {
+        self.epoch.elapsed().saturating_sub(Duration::from_millis(self.last_ms.load(Ordering::Relaxed)))
+    }
+}
+
+enum Outcome {
+    Finished,
+    Cancelled,
+    Panicked(String),
+    Stalled(Duration),
+}
+
+/// Spawns the task produced by `factory` and restarts it (with backoff) when
+/// it panics or, if `stall_timeout` is set, stops beating its heartbeat.
+/// A task that returns normally is not restarted.
+pub fn spawn<F, Fut>(name: impl Into<String>, stall_timeout: Option<Duration>, factory: F) -> JoinHandle<()>
+where
+    F: Fn(Heartbeat) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let name = name.into();
+    let backoff = RetryPolicy::default();
+    tokio::spawn(async move {
+        let mut restarts = 0u32;
+        loop {
+            if STOPPING.load(Ordering::SeqCst) {
+                return;
+            }
+            let heartbeat = Heartbeat::new();
+            let started = Instant::now();
+            let mut task = tokio::spawn(factory(heartbeat.clone()));
+            track(task.abort_handle());
+            let outcome = match stall_timeout {
+                Some(limit) => {
+                    let mut check = interval(limit / 4);
+                    loop {
+                        tokio::select! {
+                            res = &mut task => break finished(res),
+                            _ = check.tick() => {
+                                if heartbeat.age() > limit {
+                                    task.abort();
+                                    break Outcome::Stalled(limit);
+                                }
+                            }
+                        }
+                    }
+                }
+                None => finished(task.await),
+            };
+
+            match outcome {
+                Outcome::Finished => {
+                    log::debug!("Task {} finished", name);
+                    return;
+                }
+                Outcome::Cancelled => return,
+                Outcome::Panicked(msg) => log::error!("Task {} panicked: {} - restarting", name, msg),
+                Outcome::Stalled(limit) => log::error!("Task {} made no progress for {:?} - restarting", name, limit),
+            }
+            let incidents = INCIDENTS.fetch_add(1, Ordering::Relaxed) + 1;
+            log::warn!("Supervisor incidents so far: {}", incidents);
+            restarts = if started.elapsed() >= HEALTHY_RUNTIME { 1 } else { restarts + 1 };
+            sleep(backoff.backoff(restarts)).await;
+        }
+    })
+}
+
+fn finished(res: Result<(), tokio::task::JoinError>) -> Outcome {
+    match res {
+        Ok(()) => Outcome::Finished,
+        Err(e) if e.is_panic() => {
+            let payload = e.into_panic();
+            let msg = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            Outcome::Panicked(msg)
+        }
+        Err(_) => Outcome::Cancelled,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU32;
+
+    #[tokio::test]
+    async fn test_restarts_after_panic() {
+        let runs = Arc::new(AtomicU32::new(0));
+        let runs_clone = runs.clone();
+        let before = incident_count();
+        let handle = spawn("test-panic", None, move |_| {
+            let run = runs_clone.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if run == 0 {
+                    panic!("first run fails");
+                }
+            }
+        });
+        handle.await.unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+        assert!(incident_count() > before);
+    }
+
+    #[tokio::test]
+    async fn test_restarts_stalled_task() {
+        let runs = Arc::new(AtomicU32::new(0));
+        let runs_clone = runs.clone();
+        let handle = spawn("test-stall", Some(Duration::from_millis(40)), move |heartbeat| {
+            let run = runs_clone.fetch_add(1, Ordering::SeqCst);
+            async move {
+                heartbeat.beat();
+                if run == 0 {
+                    sleep(Duration::from_secs(3600)).await;
+                }
+            }
+        });
+        handle.await.unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,82 @@
+use crate::config::TlsConfig;
+use crate::tls::TlsClient;
+use std::io;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Duration};
+
+/// Default for `connect_timeout_ms`, covering the TLS handshake too.
+const CONNECT_TIMEOUT_MS: u64 = 5000;
+
+/// A connected byte stream, in the clear or in TLS.
+pub trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send> Stream for S {}
+
+/// Opens connections to one `host` and `port` for the network sources,
+/// through `TlsClient` when they have a `tls` table.
+pub struct Connector {
+    host: String,
+    port: u16,
+    tls: Option<TlsClient>,
+    timeout: Duration,
+    /// Protocol the TLS handshake must agree on by ALPN.
+    alpn: Option<&'static [u8]>,
+    /// `tcp://host:port` or `tls://host:port`, for logs and the ledger.
+    display: String,
+}
+
+impl Connector {
+    pub fn new(host: &str, port: u16, tls: Option<&TlsConfig>, connect_timeout_ms: Option<u64>) -> Result<Self, String> {
+        if host.is_empty() {
+            return Err("host is empty".to_string());
+        }
+        let tls = tls.map(TlsClient::new).transpose()?;
+        let scheme = if tls.is_some() { "tls" } else { "tcp" };
+        let display = match host.contains(':') {
+            true => format!("{}://[{}]:{}", scheme, host, port),
+            false => format!("{}://{}:{}", scheme, host, port),
+        };
+        let timeout = Duration::from_millis(connect_timeout_ms.unwrap_or(CONNECT_TIMEOUT_MS).max(1));
+        Ok(Self { host: host.to_string(), port, tls, timeout, alpn: None, display })
+    }
+
+    /// Requires TLS connections to agree on `protocol` by ALPN.
+    #[cfg_attr(not(feature = "grpc"), allow(dead_code))]
+    pub fn with_alpn(mut self, protocol: &'static [u8]) -> Self {
+        self.tls = self.tls.map(|tls| tls.with_alpn(&[protocol]));
+        self.alpn = Some(protocol);
+        self
+    }
+
+    pub fn display(&self) -> &str {
+        &self.display
+    }
+
+    pub fn is_tls(&self) -> bool {
+        self.tls.is_some()
+    }
+
+    /// Connects, and completes the TLS handshake if there is one, within
+    /// the connect timeout.
+    pub async fn connect(&self) -> io::Result<Box<dyn Stream>> {
+        let connecting = async {
+            let stream = TcpStream::connect((self.host.as_str(), self.port)).await?;
+            stream.set_nodelay(true)?;
+            Ok::<Box<dyn Stream>, io::Error>(match &self.tls {
+                Some(tls) => {
+                    let stream = tls.connect(&self.host, stream).await?;
+                    if let Some(protocol) = self.alpn.filter(|&p| stream.get_ref().1.alpn_protocol() != Some(p)) {
+                        let protocol = String::from_utf8_lossy(protocol);
+                        return Err(io::Error::other(format!("the server did not agree to {} by ALPN", protocol)));
+                    }
+                    Box::new(stream)
+                }
+                None => Box::new(stream),
+            })
+        };
+        timeout(self.timeout, connecting)
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, format!("no connection within {:?}", self.timeout)))?
+    }
+}
@@ -0,0 +1,243 @@
+use crate::config::{DebiasKind, TransformKind};
+use sha2::{Digest, Sha256};
+use zeroize::{Zeroize, Zeroizing};
+
+/// A stage between a source's raw reader and its buffer. Stages keep what
+/// they cannot use yet (a partial pair, block or byte) for the next call,
+/// zeroized when dropped.
+pub trait Transform: Send + Sync {
+    /// Transforms `input`, returning the whole output bytes it completes.
+    fn apply(&mut self, input: &[u8]) -> Zeroizing<Vec<u8>>;
+
+    /// Input bytes for each output byte, at least: reading this many per
+    /// byte of room never transforms into more than fits.
+    fn input_per_output(&self) -> usize;
+}
+
+/// The von Neumann extractor: of each pair of raw bits, `01` yields 0, `10`
+/// yields 1 and equal pairs are dropped. Independent bits with any constant
+/// bias come out unbiased, at about a quarter of the raw rate for a nearly
+/// fair source.
+pub struct VonNeumann {
+    bits: BitPacker,
+}
+
+impl Transform for VonNeumann {
+    fn apply(&mut self, input: &[u8]) -> Zeroizing<Vec<u8>> {
+        let mut out = Zeroizing::new(Vec::with_capacity(input.len() / 4 + 1));
+        for &byte in input {
+            for shift in (0..8).step_by(2).rev() {
+                let pair = (byte >> shift) & 0b11;
+                if pair == 0b01 || pair == 0b10 {
+                    self.bits.push(pair >> 1, 1, &mut out);
+                }
+            }
+        }
+        out
+    }
+
+    fn input_per_output(&self) -> usize {
+        2
+    }
+}
+
+/// XORs each run of `n` bytes into one.
+pub struct XorFold {
+    n: usize,
+    acc: u8,
+    count: usize,
+}
+
+impl Drop for XorFold {
+    fn drop(&mut self) {
+        self.acc.zeroize();
+    }
+}
+
+impl Transform for XorFold {
+    fn apply(&mut self, input: &[u8]) -> Zeroizing<Vec<u8>> {
+        let mut out = Zeroizing::new(Vec::with_capacity(input.len() / self.n + 1));
+        for &byte in input {
+            self.acc ^= byte;
+            self.count += 1;
+            if self.count == self.n {
+                out.push(self.acc);
+                self.acc = 0;
+                self.count = 0;
+            }
+        }
+        out
+    }
+
+    fn input_per_output(&self) -> usize {
+        self.n
+    }
+}
+
+/// SHA-256 of each 64-byte block: 32 bytes out per 64 in.
+pub struct Sha256Blocks {
+    block: Zeroizing<Vec<u8>>,
+}
+
+/// Input bytes per `sha256` digest.
+const SHA256_BLOCK: usize = 64;
+
+impl Transform for Sha256Blocks {
+    fn apply(&mut self, input: &[u8]) -> Zeroizing<Vec<u8>> {
+        let mut out = Zeroizing::new(Vec::with_capacity(input.len() / 2 + 32));
+        for &byte in input {
+            self.block.push(byte);
+            if self.block.len() == SHA256_BLOCK {
+                out.extend_from_slice(&*Zeroizing::new(<[u8; 32]>::from(Sha256::digest(&*self.block))));
+                self.block.zeroize();
+            }
+        }
+        out
+    }
+
+    fn input_per_output(&self) -> usize {
+        SHA256_BLOCK / 32
+    }
+}
+
+/// Keeps the `n` least significant bits of each byte, packed most
+/// significant first, e.g. for ADC samples whose high bits barely move.
+pub struct Lsb {
+    n: u32,
+    bits: BitPacker,
+}
+
+impl Transform for Lsb {
+    fn apply(&mut self, input: &[u8]) -> Zeroizing<Vec<u8>> {
+        let mut out = Zeroizing::new(Vec::with_capacity(input.len() * self.n as usize / 8 + 1));
+        for &byte in input {
+            self.bits.push(byte & ((1 << self.n) - 1), self.n, &mut out);
+        }
+        out
+    }
+
+    fn input_per_output(&self) -> usize {
+        (8 / self.n as usize).max(1)
+    }
+}
+
+/// Collects bits into bytes, most significant first.
+#[derive(Default)]
+struct BitPacker {
+    acc: u16,
+    len: u32,
+}
+
+impl Drop for BitPacker {
+    fn drop(&mut self) {
+        self.acc.zeroize();
+    }
+}
+
+impl BitPacker {
+    /// Appends the low `count` bits of `bits`, pushing every byte completed.
+    fn push(&mut self, bits: u8, count: u32, out: &mut Vec<u8>) {
+        self.acc = (self.acc << count) | bits as u16;
+        self.len += count;
+        if self.len >= 8 {
+            self.len -= 8;
+            out.push((self.acc >> self.len) as u8);
+            self.acc &= (1 << self.len) - 1;
+        }
+    }
+}
+
+fn stage(kind: TransformKind) -> Box<dyn Transform> {
+    match kind {
+        TransformKind::VonNeumann => Box::new(VonNeumann { bits: BitPacker::default() }),
+        TransformKind::XorFold(n) => Box::new(XorFold { n, acc: 0, count: 0 }),
+        TransformKind::Sha256 => Box::new(Sha256Blocks { block: Zeroizing::new(Vec::with_capacity(SHA256_BLOCK)) }),
+        TransformKind::Lsb(n) => Box::new(Lsb { n: n as u32, bits: BitPacker::default() }),
+    }
+}
+
+/// A source's transforms in order: its `debias` filter, then `transforms`.
+pub struct Pipeline(Vec<Box<dyn Transform>>);
+
+impl Pipeline {
+    /// The pipeline a source's config asks for, `None` when empty. The
+    /// transform names were validated when the config was loaded.
+    pub fn from_config(debias: Option<DebiasKind>, transforms: &[String]) -> Option<Self> {
+        let debias = debias.map(|DebiasKind::VonNeumann| TransformKind::VonNeumann);
+        let stages: Vec<_> = debias.into_iter().chain(transforms.iter().filter_map(|t| t.parse().ok())).map(stage).collect();
+        (!stages.is_empty()).then_some(Self(stages))
+    }
+
+    /// Raw bytes to read per byte of room in the buffer.
+    pub fn raw_per_byte(&self) -> usize {
+        self.0.iter().map(|t| t.input_per_output()).product()
+    }
+
+    pub fn apply(&mut self, raw: &[u8]) -> Zeroizing<Vec<u8>> {
+        let mut data = Zeroizing::new(raw.to_vec());
+        for stage in &mut self.0 {
+            data = stage.apply(&data);
+        }
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pipeline(transforms: &[&str]) -> Pipeline {
+        let transforms: Vec<String> = transforms.iter().map(|t| t.to_string()).collect();
+        Pipeline::from_config(None, &transforms).unwrap()
+    }
+
+    #[test]
+    fn test_von_neumann() {
+        let mut vn = Pipeline::from_config(Some(DebiasKind::VonNeumann), &[]).unwrap();
+        // Pairs 10 01 10 10 give 1011, equal pairs nothing
+        assert!(vn.apply(&[0b1001_1010, 0b0011_0000]).is_empty());
+        assert_eq!(*vn.apply(&[0b0101_0110]), [0b1011_0001]);
+
+        // Bits that are 1 three times in four come out about evenly
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let raw: Vec<u8> = (0..100_000)
+            .map(|_| {
+                (0..8).fold(0u8, |byte, _| {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    (byte << 1) | u8::from(state & 3 != 0)
+                })
+            })
+            .collect();
+        let out = vn.apply(&raw);
+        let ones: u32 = out.iter().map(|b| b.count_ones()).sum();
+        let share = ones as f64 / (out.len() * 8) as f64;
+        assert!((0.49..0.51).contains(&share), "{}", share);
+        assert!(out.len() * vn.raw_per_byte() <= raw.len());
+    }
+
+    #[test]
+    fn test_transform_chain() {
+        let mut fold = pipeline(&["xor_fold:3"]);
+        assert_eq!(*fold.apply(&[1, 2, 4, 8]), [7]);
+        assert_eq!(*fold.apply(&[16, 32]), [56]);
+
+        let mut lsb = pipeline(&["lsb:4"]);
+        assert_eq!(*lsb.apply(&[0xa1, 0xb2, 0xc3]), [0x12]);
+        assert_eq!(*lsb.apply(&[0xd4]), [0x34]);
+
+        let input: Vec<u8> = (0..130u8).collect();
+        let mut hash = pipeline(&["sha256"]);
+        let out = hash.apply(&input);
+        assert_eq!(out.len(), 64);
+        assert_eq!(out[..32], Sha256::digest(&input[..64])[..]);
+
+        // Stages apply in order; reads are sized by every stage's ratio
+        let mut chain = pipeline(&["xor_fold", "sha256", "lsb:4"]);
+        assert_eq!(chain.raw_per_byte(), 2 * 2 * 2);
+        let out = chain.apply(&input[..128]);
+        let expected = pipeline(&["lsb:4"]).apply(&Sha256::digest((0..64).map(|i| (2 * i) ^ (2 * i + 1)).collect::<Vec<u8>>()));
+        assert_eq!(*out, *expected);
+    }
+}
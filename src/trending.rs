@@ -0,0 +1,370 @@
+use crate::aggregator::Aggregator;
+use crate::config::TrendingConfig;
+use crate::sniff::CHI_SQUARE_LIMIT;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::time::{interval_at, Duration, Instant, MissedTickBehavior};
+use zeroize::Zeroizing;
+
+/// Fewest raw bytes a sample is judged on, as for file sources.
+const MIN_SAMPLE_BYTES: usize = 4096;
+
+/// Largest sample one run may divert from a source.
+const MAX_SAMPLE_BYTES: usize = 1024 * 1024;
+
+const DEFAULT_SAMPLE_BYTES: usize = 64 * 1024;
+
+/// Deviation, in standard deviations, past which the bit tests and the
+/// serial correlation fail; random data gets there about once in 16000 runs.
+const Z_LIMIT: f64 = 4.0;
+
+/// Upper 99.5% quantile of the normal distribution, for the most common
+/// value estimate (SP 800-90B 6.3.1).
+const Z_995: f64 = 2.576;
+
+/// Results of the statistical battery on one raw sample.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Battery {
+    pub bytes: usize,
+    /// Of the byte counts, with 255 degrees of freedom.
+    pub chi_square: f64,
+    /// Shannon entropy of the byte distribution.
+    pub bits_per_byte: f64,
+    /// Most common value estimate of the min-entropy per byte, which is what
+    /// a weakening source loses first.
+    pub min_entropy: f64,
+    /// Excess of ones over zeros, in standard deviations.
+    pub monobit_z: f64,
+    /// Runs of equal bits against the count expected for the ones seen.
+    pub runs_z: f64,
+    /// Between each byte and the next; near 0 for independent bytes.
+    pub serial_correlation: f64,
+}
+
+impl Battery {
+    pub fn run(sample: &[u8]) -> Self {
+        let n = sample.len().max(2) as f64;
+        let mut counts = [0u64; 256];
+        for &b in sample {
+            counts[b as usize] += 1;
+        }
+        let expected = n / 256.0;
+        let chi_square = counts.iter().map(|&c| (c as f64 - expected).powi(2) / expected).sum();
+        let bits_per_byte = counts.iter().filter(|&&c| c > 0).map(|&c| -(c as f64 / n) * (c as f64 / n).log2()).sum();
+        let p = *counts.iter().max().unwrap_or(&0) as f64 / n;
+        let min_entropy = -(p + Z_995 * (p * (1.0 - p) / (n - 1.0)).sqrt()).min(1.0).log2();
+
+        let bits = n * 8.0;
+        let ones = sample.iter().map(|b| b.count_ones() as f64).sum::<f64>();
+        let monobit_z = (2.0 * ones - bits) / bits.sqrt();
+        // Bit runs, counting the boundaries inside and between bytes
+        let mut runs = 1.0;
+        let mut last = sample.first().map_or(0, |b| b >> 7);
+        for &b in sample {
+            for i in (0..8).rev() {
+                let bit = (b >> i) & 1;
+                runs += (bit != last) as u8 as f64;
+                last = bit;
+            }
+        }
+        let pi = ones / bits;
+        let spread = (pi * (1.0 - pi)).max(1.0 / bits);
+        let runs_z = (runs - 2.0 * bits * spread) / (2.0 * bits.sqrt() * spread);
+
+        let (mut sum, mut sum_sq, mut sum_lag) = (0.0, 0.0, 0.0);
+        for (i, &b) in sample.iter().enumerate() {
+            let (x, next) = (b as f64, sample[(i + 1) % sample.len()] as f64);
+            sum += x;
+            sum_sq += x * x;
+            sum_lag += x * next;
+        }
+        let variance = n * sum_sq - sum * sum;
+        let serial_correlation = if variance > 0.0 { (n * sum_lag - sum * sum) / variance } else { 1.0 };
+        Self { bytes: sample.len(), chi_square, bits_per_byte, min_entropy, monobit_z, runs_z, serial_correlation }
+    }
+
+    /// The tests this sample fails.
+    pub fn failures(&self) -> Vec<&'static str> {
+        let mut failed = Vec::new();
+        if self.chi_square > CHI_SQUARE_LIMIT {
+            failed.push("chi_square");
+        }
+        if self.monobit_z.abs() > Z_LIMIT {
+            failed.push("monobit");
+        }
+        if self.runs_z.abs() > Z_LIMIT {
+            failed.push("runs");
+        }
+        if self.serial_correlation.abs() * (self.bytes as f64).sqrt() > Z_LIMIT {
+            failed.push("serial_correlation");
+        }
+        failed
+    }
+
+    fn values(&self) -> [(&'static str, f64); 7] {
+        [
+            ("bytes", self.bytes as f64),
+            ("chi_square", self.chi_square),
+            ("bits_per_byte", self.bits_per_byte),
+            ("min_entropy", self.min_entropy),
+            ("monobit_z", self.monobit_z),
+            ("runs_z", self.runs_z),
+            ("serial_correlation", self.serial_correlation),
+        ]
+    }
+}
+
+/// One run's result for one source.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Point {
+    /// Unix time, in seconds.
+    pub taken_at: u64,
+    pub battery: Battery,
+}
+
+impl Point {
+    /// What `GetQualityTrend` reports: the battery's values and "failed",
+    /// the number of tests failed.
+    pub fn values(&self) -> HashMap<String, f64> {
+        let mut values: HashMap<String, f64> = self.battery.values().iter().map(|(k, v)| (k.to_string(), *v)).collect();
+        values.insert("failed".to_string(), self.battery.failures().len() as f64);
+        values
+    }
+
+    fn json_line(&self, source_id: &str) -> Value {
+        let mut line = json!({ "source": source_id, "taken_at": self.taken_at });
+        for (k, v) in self.battery.values() {
+            line[k] = json!(v);
+        }
+        line["bytes"] = json!(self.battery.bytes);
+        line
+    }
+
+    fn from_json(line: &Value) -> Option<(String, Self)> {
+        let f = |k: &str| line.get(k).and_then(Value::as_f64);
+        let battery = Battery {
+            bytes: line.get("bytes")?.as_u64()? as usize,
+            chi_square: f("chi_square")?,
+            bits_per_byte: f("bits_per_byte")?,
+            min_entropy: f("min_entropy")?,
+            monobit_z: f("monobit_z")?,
+            runs_z: f("runs_z")?,
+            serial_correlation: f("serial_correlation")?,
+        };
+        Some((line.get("source")?.as_str()?.to_string(), Self { taken_at: line.get("taken_at")?.as_u64()?, battery }))
+    }
+}
+
+/// The time series per source, oldest result first, also appended to the
+/// configured `file`.
+pub struct Trends {
+    history: usize,
+    file: Option<String>,
+    series: Mutex<HashMap<String, VecDeque<Point>>>,
+}
+
+impl Trends {
+    /// Loads what `file` kept from earlier runs, dropping what is past the
+    /// history there too, so the file does not grow without bound.
+    pub fn new(cfg: &TrendingConfig) -> Self {
+        let trends = Self { history: cfg.history.unwrap_or(720).max(1), file: cfg.file.clone(), series: Mutex::new(HashMap::new()) };
+        let Some(path) = &trends.file else { return trends };
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return trends,
+            Err(e) => {
+                log::error!("Cannot read trend file {}: {} - starting an empty series", path, e);
+                return trends;
+            }
+        };
+        let (mut lines, mut malformed) = (0, 0);
+        for line in contents.lines().filter(|l| !l.trim().is_empty()) {
+            lines += 1;
+            match serde_json::from_str(line).ok().as_ref().and_then(Point::from_json) {
+                Some((source_id, point)) => trends.push(&source_id, point),
+                None => malformed += 1,
+            }
+        }
+        if malformed > 0 {
+            log::warn!("Skipped {} malformed lines of trend file {}", malformed, path);
+        }
+        let kept: usize = trends.series.lock().unwrap().values().map(VecDeque::len).sum();
+        if kept < lines {
+            if let Err(e) = trends.rewrite(path) {
+                log::warn!("Cannot compact trend file {}: {}", path, e);
+            }
+        }
+        log::info!("Loaded {} trend results from {}", kept, path);
+        trends
+    }
+
+    fn push(&self, source_id: &str, point: Point) {
+        let mut series = self.series.lock().unwrap();
+        let points = series.entry(source_id.to_string()).or_default();
+        points.push_back(point);
+        while points.len() > self.history {
+            points.pop_front();
+        }
+    }
+
+    fn rewrite(&self, path: &str) -> io::Result<()> {
+        let mut out = String::new();
+        for (source_id, points) in self.series.lock().unwrap().iter() {
+            for point in points {
+                out.push_str(&point.json_line(source_id).to_string());
+                out.push('\n');
+            }
+        }
+        let tmp = format!("{}.tmp", path);
+        fs::write(&tmp, out)?;
+        fs::rename(&tmp, path)
+    }
+
+    /// Appends a result; a file that cannot be written only costs the
+    /// series its persistence.
+    pub fn record(&self, source_id: &str, point: Point) {
+        self.push(source_id, point);
+        if let Some(path) = &self.file {
+            let line = format!("{}\n", point.json_line(source_id));
+            let written = OpenOptions::new().create(true).append(true).open(path).and_then(|mut f| f.write_all(line.as_bytes()));
+            if let Err(e) = written {
+                log::warn!("Cannot append to trend file {}: {}", path, e);
+            }
+        }
+    }
+
+    /// Oldest first; empty for a source never sampled.
+    pub fn series(&self, source_id: &str) -> Vec<Point> {
+        self.series.lock().unwrap().get(source_id).map_or_else(Vec::new, |points| points.iter().copied().collect())
+    }
+
+    pub fn latest(&self, source_id: &str) -> Option<Point> {
+        self.series.lock().unwrap().get(source_id).and_then(|points| points.back().copied())
+    }
+}
+
+/// Captures a raw sample of each source every `interval_minutes`, starting
+/// one interval after startup, and records the battery's results. Sources
+/// that are failed, quarantined, unavailable or in maintenance are skipped
+/// for that run, so an outage shows as a gap rather than as bad results.
+pub async fn run(aggregator: Arc<Aggregator>, cfg: TrendingConfig) {
+    let Some(trends) = aggregator.trends() else { return };
+    let every = Duration::from_secs(cfg.interval_minutes.unwrap_or(60).max(1) * 60);
+    let sample_bytes = match cfg.sample_bytes {
+        Some(n) if !(MIN_SAMPLE_BYTES..=MAX_SAMPLE_BYTES).contains(&n) => {
+            log::error!("[trending] sample_bytes must be {} to {} - using the default", MIN_SAMPLE_BYTES, MAX_SAMPLE_BYTES);
+            DEFAULT_SAMPLE_BYTES
+        }
+        n => n.unwrap_or(DEFAULT_SAMPLE_BYTES),
+    };
+    let capture_timeout = cfg.capture_timeout_ms.unwrap_or(30_000);
+    let mut known: Vec<String> = aggregator.health().1.into_keys().collect();
+    known.sort();
+    for id in cfg.sources.iter().filter(|id| !known.contains(id)) {
+        log::error!("[trending] no source {} - skipping", id);
+    }
+    let ids: Vec<String> = known.into_iter().filter(|id| cfg.sources.is_empty() || cfg.sources.contains(id)).collect();
+
+    let mut ticks = interval_at(Instant::now() + every, every);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    loop {
+        ticks.tick().await;
+        let (_, states) = aggregator.health();
+        for id in &ids {
+            match states.get(id).map(String::as_str) {
+                Some("ok" | "standby") => {}
+                state => {
+                    log::info!("Trending: skipping {} this run, it is {}", id, state.unwrap_or("gone"));
+                    continue;
+                }
+            }
+            let sample = match aggregator.capture_raw(id, sample_bytes, capture_timeout).await {
+                Ok(sample) => Zeroizing::new(sample),
+                Err(e) => {
+                    log::warn!("Trending: capture from {} failed: {}", id, e);
+                    continue;
+                }
+            };
+            if sample.len() < MIN_SAMPLE_BYTES {
+                log::warn!("Trending: {} delivered {} of {} bytes within {} ms - too few to judge", id, sample.len(), sample_bytes, capture_timeout);
+                continue;
+            }
+            let before = trends.series(id);
+            let point = Point { taken_at: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()), battery: Battery::run(&sample) };
+            trends.record(id, point);
+            let b = &point.battery;
+            let change = match before.len() {
+                0 => String::new(),
+                runs => {
+                    let mean = before.iter().map(|p| p.battery.min_entropy).sum::<f64>() / runs as f64;
+                    format!(" ({:+.3} against the mean of the last {} runs)", b.min_entropy - mean, runs)
+                }
+            };
+            log::info!(
+                "Trending: {} min-entropy {:.3} bits/byte{}, chi-square {:.0}, monobit z {:.2}, runs z {:.2}, serial correlation {:.4} over {} bytes",
+                id, b.min_entropy, change, b.chi_square, b.monobit_z, b.runs_z, b.serial_correlation, b.bytes
+            );
+            let failed = b.failures();
+            if !failed.is_empty() {
+                log::warn!("Trending: raw sample of {} fails {}", id, failed.join(", "));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_battery() {
+        let random = crate::lrng::os_fill_rand_octets(64 * 1024).unwrap();
+        let battery = Battery::run(&random);
+        assert_eq!(battery.failures(), Vec::<&str>::new());
+        assert!(battery.min_entropy > 7.0 && battery.min_entropy < 8.0, "{:?}", battery);
+        assert!(battery.bits_per_byte > 7.99);
+
+        // One bit in eight stuck at 1
+        let stuck: Vec<u8> = random.iter().map(|b| b | 1).collect();
+        let battery = Battery::run(&stuck);
+        assert!(battery.failures().contains(&"monobit"));
+        assert!(battery.min_entropy < 7.0);
+
+        // Uniform bytes, but each one follows from the last
+        let walk: Vec<u8> = random.iter().scan(0u8, |x, b| {
+            *x = x.wrapping_add(b % 8);
+            Some(*x)
+        }).collect();
+        assert!(Battery::run(&walk).failures().contains(&"serial_correlation"));
+    }
+
+    #[test]
+    fn test_trends_keep_history_in_file() {
+        let path = std::env::temp_dir().join(format!("trng-trend-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let cfg = TrendingConfig { history: Some(2), file: Some(path.to_string_lossy().into_owned()), ..Default::default() };
+        let battery = Battery::run(&crate::lrng::os_fill_rand_octets(4096).unwrap());
+        let trends = Trends::new(&cfg);
+        for taken_at in 1..=3 {
+            trends.record("hw", Point { taken_at, battery });
+        }
+        assert_eq!(trends.series("hw").iter().map(|p| p.taken_at).collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 3);
+
+        // Reloading keeps the newest and compacts the file
+        let reloaded = Trends::new(&cfg);
+        assert_eq!(reloaded.series("hw").iter().map(|p| p.taken_at).collect::<Vec<_>>(), vec![2, 3]);
+        // serde_json may round the last digit of a float
+        let latest = reloaded.latest("hw").unwrap().battery;
+        assert_eq!(latest.bytes, 4096);
+        assert!((latest.min_entropy - battery.min_entropy).abs() < 1e-9);
+        assert!((latest.serial_correlation - battery.serial_correlation).abs() < 1e-9);
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+        assert!(reloaded.series("other").is_empty());
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,160 @@
+use crate::config::{BusKind, DbusAuth, DbusConfig, BUS_NAME, DEFAULT_INTERFACE, DEFAULT_OBJECT_PATH};
+use tokio::sync::Mutex;
+use tokio::time::{timeout, Duration};
+use zbus::names::{BusName, InterfaceName};
+use zbus::zvariant::ObjectPath;
+use zbus::{connection, AuthMechanism, Connection};
+use zeroize::Zeroizing;
+
+/// Status of an upstream answer from its low-assurance jitter fallback.
+const STATUS_FALLBACK: i32 = 1;
+
+/// Slack beyond the upstream's own timeout before a call is given up on.
+const CALL_SLACK: Duration = Duration::from_secs(2);
+
+#[derive(Debug, PartialEq)]
+enum Bus {
+    Session,
+    System,
+    Address(String),
+}
+
+impl Bus {
+    fn parse(bus: Option<&str>) -> Self {
+        match bus.unwrap_or("session") {
+            "session" => Bus::Session,
+            "system" => Bus::System,
+            address => Bus::Address(address.to_string()),
+        }
+    }
+}
+
+/// Whether `cfg` points at this service itself, which takes `own_name` on
+/// `own_bus`.
+pub fn is_self(cfg: &DbusConfig, own_bus: BusKind, own_name: &str) -> bool {
+    let same_bus = matches!((Bus::parse(cfg.bus.as_deref()), own_bus), (Bus::Session, BusKind::Session) | (Bus::System, BusKind::System));
+    same_bus && cfg.service.as_deref().unwrap_or(BUS_NAME) == own_name
+}
+
+/// Calls `ReadBytes` of an upstream service, connecting on first use and
+/// again after the connection fails.
+pub struct Upstream {
+    bus: Bus,
+    auth: DbusAuth,
+    service: BusName<'static>,
+    path: ObjectPath<'static>,
+    interface: InterfaceName<'static>,
+    /// Upstream `ReadBytes` timeout, in ms.
+    timeout_ms: u64,
+    /// `service` on its bus, for logs and the ledger.
+    display: String,
+    connection: Mutex<Option<Connection>>,
+}
+
+impl Upstream {
+    pub fn new(cfg: &DbusConfig) -> Result<Self, String> {
+        let bus = Bus::parse(cfg.bus.as_deref());
+        let service = cfg.service.clone().unwrap_or_else(|| BUS_NAME.to_string());
+        let display = format!("{} on {}", service, cfg.bus.as_deref().unwrap_or("session"));
+        let service = BusName::try_from(service).map_err(|e| format!("invalid service: {}", e))?;
+        let path = cfg.object_path.clone().unwrap_or_else(|| DEFAULT_OBJECT_PATH.to_string());
+        let path = ObjectPath::try_from(path).map_err(|e| format!("invalid object_path: {}", e))?;
+        let interface = cfg.interface.clone().unwrap_or_else(|| DEFAULT_INTERFACE.to_string());
+        let interface = InterfaceName::try_from(interface).map_err(|e| format!("invalid interface: {}", e))?;
+        Ok(Self {
+            bus,
+            auth: cfg.auth.unwrap_or_default(),
+            service,
+            path,
+            interface,
+            timeout_ms: cfg.timeout_ms.unwrap_or(3000),
+            display,
+            connection: Mutex::new(None),
+        })
+    }
+
+    pub fn display(&self) -> &str {
+        &self.display
+    }
+
+    async fn connect(&self) -> zbus::Result<Connection> {
+        let builder = match &self.bus {
+            Bus::Session => connection::Builder::session()?,
+            Bus::System => connection::Builder::system()?,
+            Bus::Address(address) => connection::Builder::address(address.as_str())?,
+        };
+        let mechanism = match self.auth {
+            DbusAuth::External => AuthMechanism::External,
+            DbusAuth::Anonymous => AuthMechanism::Anonymous,
+        };
+        builder.auth_mechanism(mechanism).build().await
+    }
+
+    /// Up to `bytes` from the upstream. Answers with a status other than 0
+    /// fail, including those from the upstream's fallback, which must not be
+    /// passed on as full-assurance entropy.
+    pub async fn read(&self, bytes: usize) -> Result<Zeroizing<Vec<u8>>, String> {
+        let mut connection = self.connection.lock().await;
+        let conn = match connection.as_ref() {
+            Some(conn) => conn.clone(),
+            None => {
+                let conn = self.connect().await.map_err(|e| format!("cannot connect: {}", e))?;
+                log::info!("Connected to upstream {}", self.display);
+                connection.insert(conn).clone()
+            }
+        };
+        let limit = Duration::from_millis(self.timeout_ms) + CALL_SLACK;
+        let args = (bytes as u64, self.timeout_ms);
+        let call = conn.call_method(Some(&self.service), &self.path, Some(&self.interface), "ReadBytes", &args);
+        let reply = match timeout(limit, call).await {
+            Ok(Ok(reply)) => reply,
+            Ok(Err(e)) => {
+                // Errors the upstream answered with leave the connection usable
+                if !matches!(e, zbus::Error::MethodError(..)) {
+                    *connection = None;
+                }
+                return Err(e.to_string());
+            }
+            Err(_) => return Err(format!("no answer within {:?}", limit)),
+        };
+        drop(connection);
+        let (status, data): (i32, Vec<u8>) = reply.body().deserialize().map_err(|e| e.to_string())?;
+        let data = Zeroizing::new(data);
+        match status {
+            0 => Ok(data),
+            STATUS_FALLBACK => Err("upstream answered from its low-assurance fallback".to_string()),
+            status => Err(format!("upstream answered status {}", status)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(bus: Option<&str>, service: Option<&str>) -> DbusConfig {
+        let mut toml = "id = \"up\"\n".to_string();
+        if let Some(bus) = bus {
+            toml.push_str(&format!("bus = \"{}\"\n", bus));
+        }
+        if let Some(service) = service {
+            toml.push_str(&format!("service = \"{}\"\n", service));
+        }
+        toml::from_str(&toml).unwrap()
+    }
+
+    #[test]
+    fn test_new() {
+        assert!(is_self(&config(None, None), BusKind::Session, BUS_NAME));
+        assert!(!is_self(&config(None, None), BusKind::Session, "lv.lumii.trng.Tenant"));
+        assert!(!is_self(&config(Some("system"), None), BusKind::Session, BUS_NAME));
+        assert!(is_self(&config(Some("system"), None), BusKind::System, BUS_NAME));
+        let upstream = Upstream::new(&config(Some("system"), None)).unwrap();
+        assert_eq!(upstream.bus, Bus::System);
+        assert_eq!(upstream.display(), "lv.lumii.trng on system");
+        let upstream = Upstream::new(&config(Some("tcp:host=qrng.example.org,port=55556"), None)).unwrap();
+        assert_eq!(upstream.bus, Bus::Address("tcp:host=qrng.example.org,port=55556".to_string()));
+        assert!(Upstream::new(&config(None, Some("org.example.Qrng"))).is_ok());
+        assert!(Upstream::new(&config(Some("system"), Some("not a name"))).is_err());
+    }
+}
@@ -0,0 +1,131 @@
+use crate::aggregator::Aggregator;
+use crate::config::WatchdogConfig;
+use crate::error::Error;
+use crate::events::{EventSender, ServiceEvent};
+use crate::supervisor::Heartbeat;
+use std::sync::Arc;
+use tokio::time::{interval, timeout, Duration, Instant, MissedTickBehavior};
+
+/// How long past its budget a canary may take before it counts as wedged
+/// and is abandoned, rather than just slow.
+const WEDGE_GRACE: Duration = Duration::from_secs(1);
+
+/// Largest canary one read may ask for.
+const MAX_CANARY_BYTES: usize = 4096;
+
+/// Counts consecutive failed canaries and decides when the service is
+/// marked unready and when it recovers.
+struct Tracker {
+    trip_after: u32,
+    failures: u32,
+}
+
+impl Tracker {
+    fn new(trip_after: u32) -> Self {
+        Self { trip_after: trip_after.max(1), failures: 0 }
+    }
+
+    fn tripped(&self) -> bool {
+        self.failures >= self.trip_after
+    }
+
+    /// Returns the new state (`true` healthy) when this canary changes it.
+    fn record(&mut self, ok: bool) -> Option<bool> {
+        let was_tripped = self.tripped();
+        if ok {
+            self.failures = 0;
+        } else {
+            self.failures = self.failures.saturating_add(1);
+        }
+        (was_tripped != self.tripped()).then_some(!self.tripped())
+    }
+}
+
+/// Why a canary read of `wanted` bytes counts as failed, if it does.
+fn verdict(result: Result<usize, Error>, wanted: usize, elapsed: Duration, budget: Duration) -> Result<(), String> {
+    match result {
+        Err(e) => Err(format!("canary read failed: {}", e)),
+        Ok(n) if n < wanted => Err(format!("canary read returned {} of {} bytes", n, wanted)),
+        Ok(_) if elapsed > budget => {
+            Err(format!("canary took {} ms, over the {} ms budget", elapsed.as_millis(), budget.as_millis()))
+        }
+        Ok(_) => Ok(()),
+    }
+}
+
+/// Periodically reads a canary through the whole request path (scheduler,
+/// sources, combining). While canaries keep failing or missing their latency
+/// budget the service is marked unready and `WatchdogChanged` is sent, which
+/// catches wedged states that no single supervised task notices.
+pub async fn run(aggregator: Arc<Aggregator>, cfg: WatchdogConfig, events: EventSender, heartbeat: Heartbeat) {
+    let budget = Duration::from_millis(cfg.latency_budget_ms.unwrap_or(1000));
+    let wanted = cfg.canary_bytes.unwrap_or(32).clamp(1, MAX_CANARY_BYTES);
+    let count = cfg.count_in_stats.unwrap_or(false);
+    let wedged = budget + WEDGE_GRACE;
+    let mut tracker = Tracker::new(cfg.failures.unwrap_or(1));
+    let mut ticker = interval(Duration::from_millis(cfg.interval_ms.unwrap_or(10_000).max(100)));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        heartbeat.beat();
+        // Priming after startup is the readiness check's business
+        if !aggregator.is_primed() {
+            continue;
+        }
+        let started = Instant::now();
+        let outcome = match timeout(wedged, aggregator.canary(wanted, budget.as_millis() as u64)).await {
+            Ok(result) => {
+                let elapsed = started.elapsed();
+                log::debug!("Watchdog canary: {:?} in {:?}", result, elapsed);
+                if let (true, Ok(n)) = (count, &result) {
+                    aggregator.count_served(*n);
+                }
+                verdict(result, wanted, elapsed, budget)
+            }
+            Err(_) => Err(format!("canary read gave no answer within {} ms", wedged.as_millis())),
+        };
+        report(&aggregator, &events, &mut tracker, outcome);
+    }
+}
+
+fn report(aggregator: &Aggregator, events: &EventSender, tracker: &mut Tracker, verdict: Result<(), String>) {
+    if let Err(reason) = &verdict {
+        log::warn!("Watchdog: {}", reason);
+    }
+    let Some(healthy) = tracker.record(verdict.is_ok()) else { return };
+    aggregator.set_watchdog_tripped(!healthy);
+    if healthy {
+        log::info!("Watchdog: canary reads succeed again, serving requests");
+    } else {
+        log::error!("Watchdog: marking the service unready until canary reads succeed");
+    }
+    // No subscribers is fine
+    let _ = events.send(ServiceEvent::WatchdogChanged { healthy, reason: verdict.err().unwrap_or_default() });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trips_after_consecutive_failures() {
+        let mut tracker = Tracker::new(2);
+        assert_eq!(tracker.record(false), None);
+        assert_eq!(tracker.record(true), None);
+        assert_eq!(tracker.record(false), None);
+        assert_eq!(tracker.record(false), Some(false));
+        assert_eq!(tracker.record(false), None);
+        assert_eq!(tracker.record(true), Some(true));
+        assert!(!tracker.tripped());
+    }
+
+    #[test]
+    fn test_verdict() {
+        let budget = Duration::from_millis(100);
+        let fast = Duration::from_millis(5);
+        assert!(verdict(Ok(32), 32, fast, budget).is_ok());
+        assert!(verdict(Ok(31), 32, fast, budget).unwrap_err().contains("31 of 32"));
+        assert!(verdict(Ok(32), 32, Duration::from_millis(150), budget).unwrap_err().contains("budget"));
+        assert!(verdict(Err(Error::SourcesUnavailable), 32, fast, budget).is_err());
+    }
+}
@@ -0,0 +1,104 @@
+use crate::config::FileWipe;
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::AsRawFd;
+
+/// Largest piece of zeros written at a time.
+const ZERO_CHUNK: usize = 64 * 1024;
+
+/// Destroys `len` bytes of `file` at `offset` and waits until that is on
+/// disk, so they are never read again, even by a later run.
+pub fn wipe(file: &File, mode: FileWipe, offset: u64, len: u64) -> io::Result<()> {
+    match mode {
+        FileWipe::Zero => {
+            let zeros = vec![0u8; ZERO_CHUNK];
+            let mut done = 0u64;
+            while done < len {
+                let n = (len - done).min(ZERO_CHUNK as u64) as usize;
+                file.write_all_at(&zeros[..n], offset + done)?;
+                done += n as u64;
+            }
+        }
+        FileWipe::PunchHole => punch_hole(file, offset, len)?,
+    }
+    file.sync_data()
+}
+
+#[cfg(target_os = "linux")]
+fn punch_hole(file: &File, offset: u64, len: u64) -> io::Result<()> {
+    let mode = libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE;
+    // SAFETY: fallocate only acts on the open descriptor
+    if unsafe { libc::fallocate(file.as_raw_fd(), mode, offset as libc::off_t, len as libc::off_t) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn punch_hole(_file: &File, _offset: u64, _len: u64) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "punching holes is Linux-only"))
+}
+
+/// Where the part of `file` that earlier runs did not wipe starts: past the
+/// leading holes and zeros. With `chunk_bytes` (manifest chunks, which are
+/// wiped whole) this is moved back to the start of its chunk.
+pub fn unwiped_start(file: &File, chunk_bytes: Option<u64>) -> io::Result<u64> {
+    #[cfg(target_os = "linux")]
+    // SAFETY: lseek only acts on the open descriptor
+    let data = unsafe { libc::lseek(file.as_raw_fd(), 0, libc::SEEK_DATA) };
+    #[cfg(not(target_os = "linux"))]
+    let data: i64 = 0;
+    let mut pos = match data {
+        // ENXIO: nothing but a hole
+        -1 if io::Error::last_os_error().raw_os_error() == Some(libc::ENXIO) => return Ok(file.metadata()?.len()),
+        -1 => 0,
+        data => data as u64,
+    };
+    let mut buf = vec![0u8; ZERO_CHUNK];
+    loop {
+        let n = file.read_at(&mut buf, pos)?;
+        if n == 0 {
+            break;
+        }
+        match buf[..n].iter().position(|&b| b != 0) {
+            Some(i) => {
+                pos += i as u64;
+                break;
+            }
+            None => pos += n as u64,
+        }
+    }
+    if let Some(chunk) = chunk_bytes.filter(|&c| c > 0) {
+        pos -= pos % chunk;
+    }
+    Ok(pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wipe_and_resume() {
+        for (name, mode) in [("zero", FileWipe::Zero), ("punch", FileWipe::PunchHole)] {
+            let path = std::env::temp_dir().join(format!("trng-wipe-{}-{}", std::process::id(), name));
+            let mut contents = vec![0xa5u8; 3 * ZERO_CHUNK];
+            contents[ZERO_CHUNK + 10] = 0;
+            std::fs::write(&path, &contents).unwrap();
+            let file = std::fs::OpenOptions::new().read(true).write(true).open(&path).unwrap();
+            assert_eq!(unwiped_start(&file, None).unwrap(), 0);
+            wipe(&file, mode, 0, ZERO_CHUNK as u64 + 10).unwrap();
+            let on_disk = std::fs::read(&path).unwrap();
+            assert_eq!(on_disk.len(), contents.len());
+            assert!(on_disk[..ZERO_CHUNK + 10].iter().all(|&b| b == 0));
+            assert_eq!(on_disk[ZERO_CHUNK + 11..], contents[ZERO_CHUNK + 11..]);
+            // The genuine zero right after the wiped range is skipped too
+            assert_eq!(unwiped_start(&file, None).unwrap(), ZERO_CHUNK as u64 + 11);
+            assert_eq!(unwiped_start(&file, Some(ZERO_CHUNK as u64)).unwrap(), ZERO_CHUNK as u64);
+            wipe(&file, mode, 0, contents.len() as u64).unwrap();
+            assert_eq!(unwiped_start(&file, None).unwrap(), contents.len() as u64);
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+}